@@ -0,0 +1,997 @@
+//! Command-line interface for AgentScript.
+//!
+//! ```text
+//! busbar-sf-agentscript check agents/*.agent
+//! busbar-sf-agentscript check --target prod agents/*.agent
+//! busbar-sf-agentscript check --lint-config ci/.agentscriptlint.toml agents/*.agent
+//! busbar-sf-agentscript check --junit results.xml agents/*.agent
+//! busbar-sf-agentscript fmt --check agents/*.agent
+//! busbar-sf-agentscript graph --format mermaid my.agent
+//! busbar-sf-agentscript deps --json my.agent
+//! busbar-sf-agentscript summary my.agent
+//! busbar-sf-agentscript report my.agent -o report.html
+//! busbar-sf-agentscript redact --keep targets my.agent -o shareable.agent
+//! busbar-sf-agentscript minimize --error "expected" broken.agent -o repro.agent
+//! busbar-sf-agentscript metrics --max-complexity 10 agents/*.agent
+//! busbar-sf-agentscript json my.agent
+//! busbar-sf-agentscript export my.agent -o force-app/main/default
+//! busbar-sf-agentscript import force-app/main/default -o my.agent
+//! busbar-sf-agentscript grammar --format ebnf
+//! ```
+//!
+//! Exit codes: `0` on success, `1` when a file has errors (or is not
+//! formatted, with `fmt --check`, or formats unstably), and `2` on usage or
+//! I/O errors.
+
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use busbar_sf_agentscript::error::ParseErrorInfo;
+use busbar_sf_agentscript::export::salesforce::to_salesforce_metadata;
+use busbar_sf_agentscript::formatter::format_file_with_options;
+use busbar_sf_agentscript::grammar::{export_ebnf, export_railroad};
+use busbar_sf_agentscript::graph::{
+    extract_dependencies, health_report, render_dot, render_graphml, render_mermaid,
+    render_topic_flow_svg, DependencyManifest, DotOptions, GraphMetricsRepr, HealthOptions,
+    MetricsBudget, RefGraph, SvgOptions, ValidationError,
+};
+use busbar_sf_agentscript::import::import_from_metadata;
+use busbar_sf_agentscript::import::salesforce::import_planner;
+use busbar_sf_agentscript::junit::{JunitGrouping, JunitReport};
+use busbar_sf_agentscript::lint::{find_config_file, run_lints_for, LintConfig, LintRegistry};
+use busbar_sf_agentscript::minimize::minimize;
+use busbar_sf_agentscript::parser::parse_with_structured_errors_all;
+use busbar_sf_agentscript::redact::{redact, Redaction, RedactionConfig};
+use busbar_sf_agentscript::report::generate_html;
+use busbar_sf_agentscript::schema::ast_json_schema;
+use busbar_sf_agentscript::serializer::{ClauseOrder, SerializeOptions};
+use busbar_sf_agentscript::summary::summarize;
+use busbar_sf_agentscript::text_pos::{Encoding, LineIndex};
+use busbar_sf_agentscript::validation::{
+    sort_diagnostics, validate_ast_for, SemanticError, Severity, TargetEnvironment,
+};
+use busbar_sf_agentscript::{parse, serialize, AgentFile, ErrorReporter};
+use clap::{Parser, Subcommand, ValueEnum};
+
+#[derive(Parser)]
+#[command(
+    name = "busbar-sf-agentscript",
+    version,
+    about = "Parse, validate, format, and graph AgentScript files"
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Parse and validate files, reporting errors and warnings
+    Check {
+        /// Files to check
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+        /// Exit with an error when any warnings are reported
+        #[arg(long)]
+        deny_warnings: bool,
+        /// Deployment target; stricter targets report some warnings as errors.
+        /// Defaults to each file's `target_environment:` config, then `dev`
+        #[arg(long)]
+        target: Option<TargetEnvironment>,
+        /// Lint configuration; defaults to the nearest `.agentscriptlint.toml`
+        #[arg(long, value_name = "FILE")]
+        lint_config: Option<PathBuf>,
+        /// Also write the results as a JUnit XML report
+        #[arg(long, value_name = "FILE")]
+        junit: Option<PathBuf>,
+        /// What each JUnit test case stands for
+        #[arg(long, default_value = "file", requires = "junit")]
+        junit_grouping: JunitGroupingArg,
+    },
+    /// Format files in place
+    ///
+    /// Blocks holding comments other than doc comments are left as written,
+    /// so no comment is lost. Files whose formatted source wouldn't parse
+    /// back to the same AST are left as written and reported.
+    Fmt {
+        /// Files to format
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+        /// Don't write files; exit with an error if any file is not formatted
+        #[arg(long)]
+        check: bool,
+        /// Reorder reasoning action clauses into the order the platform expects
+        #[arg(long)]
+        canonical_clause_order: bool,
+    },
+    /// Export the reference graph
+    Graph {
+        /// File to graph
+        file: PathBuf,
+        /// Output format
+        #[arg(long, short, value_enum, default_value_t = GraphFormat::Dot)]
+        format: GraphFormat,
+        /// Write to a file instead of stdout
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// List Salesforce org dependencies (flows, Apex classes, objects, ...)
+    Deps {
+        /// File to analyze
+        file: PathBuf,
+        /// Print the full dependency report as JSON
+        #[arg(long)]
+        json: bool,
+        /// Print a sorted dependency manifest (JSON, or YAML with --yaml) for diffing in CI
+        #[arg(long, conflicts_with = "json")]
+        manifest: bool,
+        /// Print the manifest as YAML instead of JSON
+        #[arg(long, requires = "manifest")]
+        yaml: bool,
+        /// Report dependencies added or removed since this JSON manifest; fail if any were added
+        #[arg(long, value_name = "MANIFEST", conflicts_with_all = ["json", "manifest"])]
+        baseline: Option<PathBuf>,
+    },
+    /// Describe the agent in plain English (purpose, topics, variables, escalation)
+    Summary {
+        /// File to summarize
+        file: PathBuf,
+        /// Print the summary as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Write a self-contained HTML report (diagram, diagnostics, reference, metrics)
+    Report {
+        /// File to report on
+        file: PathBuf,
+        /// Write to a file instead of stdout
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Mask instructions, messages, descriptions and other free text for sharing
+    Redact {
+        /// File to redact
+        file: PathBuf,
+        /// Remove text instead of masking it
+        #[arg(long)]
+        strip: bool,
+        /// Categories to leave as written (comma-separated)
+        #[arg(long, value_enum, value_delimiter = ',')]
+        keep: Vec<RedactCategory>,
+        /// Write to a file instead of stdout
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Shrink a file that fails to parse to a minimal reproducer
+    Minimize {
+        /// File to shrink
+        file: PathBuf,
+        /// Keep only inputs with a parse error containing this text
+        /// (default: the same first parse error as the original file)
+        #[arg(long, conflicts_with = "panic")]
+        error: Option<String>,
+        /// Keep only inputs that make the parser panic
+        #[arg(long)]
+        panic: bool,
+        /// Write to a file instead of stdout
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Score agent health from lint, structure, coverage, token and dependency signals
+    Health {
+        /// Files to score
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+        /// Test coverage of the agent, from 0.0 to 1.0, if measured
+        #[arg(long)]
+        coverage: Option<f64>,
+        /// Lint configuration, whose `token-budget` rule sets the topic token
+        /// budget; defaults to the nearest `.agentscriptlint.toml`
+        #[arg(long, value_name = "FILE")]
+        lint_config: Option<PathBuf>,
+        /// Exit with an error when any file scores below this
+        #[arg(long)]
+        min_score: Option<u8>,
+        /// Print the full reports as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Report per-topic fan-in/out, depth and complexity, optionally against budgets
+    Metrics {
+        /// Files to measure
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+        /// Exit with an error when a topic is reached from more topics than this
+        #[arg(long)]
+        max_fan_in: Option<usize>,
+        /// Exit with an error when a topic leads to more topics than this
+        #[arg(long)]
+        max_fan_out: Option<usize>,
+        /// Exit with an error when a topic is more transitions than this from start_agent
+        #[arg(long)]
+        max_depth: Option<usize>,
+        /// Exit with an error when a topic has more reasoning actions than this
+        #[arg(long)]
+        max_reasoning_actions: Option<usize>,
+        /// Exit with an error when a topic's directive complexity exceeds this
+        #[arg(long)]
+        max_complexity: Option<usize>,
+        /// Exit with an error when instructions nest `if` deeper than this
+        #[arg(long)]
+        max_instruction_nesting: Option<usize>,
+        /// Print the metrics as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Export Salesforce GenAiPlannerBundle / GenAiPlugin / GenAiFunction metadata
+    Export {
+        /// File to export
+        file: PathBuf,
+        /// Package directory to write into (e.g. `force-app/main/default`);
+        /// prints the files as JSON when omitted
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Import an agent from Salesforce GenAiPlanner metadata
+    Import {
+        /// Package directory containing genAiPlannerBundles/ (e.g. `force-app/main/default`)
+        dir: PathBuf,
+        /// Planner to import, when the directory contains several
+        #[arg(long)]
+        planner: Option<String>,
+        /// Write to a file instead of stdout
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Print the language grammar
+    Grammar {
+        /// Output format
+        #[arg(long, short, value_enum, default_value_t = GrammarFormat::Ebnf)]
+        format: GrammarFormat,
+        /// Write to a file instead of stdout
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Print the JSON Schema of the `json` output
+    Schema {
+        /// Write to a file instead of stdout
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Print the AST as JSON
+    Json {
+        /// File to parse
+        file: PathBuf,
+        /// Write to a file instead of stdout
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum GraphFormat {
+    Dot,
+    Graphml,
+    Mermaid,
+    /// Topic flow as a standalone SVG, without Graphviz
+    Svg,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum RedactCategory {
+    Instructions,
+    Messages,
+    Descriptions,
+    Strings,
+    Connections,
+    Targets,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum JunitGroupingArg {
+    /// One test case per file
+    File,
+    /// One test suite per file, with a test case per rule
+    Rule,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum GrammarFormat {
+    Ebnf,
+    Railroad,
+}
+
+/// Outcome of a command that ran to completion.
+enum Outcome {
+    Success,
+    Failure,
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let result = match cli.command {
+        Command::Check {
+            files,
+            deny_warnings,
+            target,
+            lint_config,
+            junit,
+            junit_grouping,
+        } => {
+            let grouping = match junit_grouping {
+                JunitGroupingArg::File => JunitGrouping::File,
+                JunitGroupingArg::Rule => JunitGrouping::Rule,
+            };
+            let junit = junit.as_deref().map(|path| (path, grouping));
+            check(&files, deny_warnings, target, lint_config.as_deref(), junit)
+        }
+        Command::Fmt {
+            files,
+            check,
+            canonical_clause_order,
+        } => fmt(&files, check, canonical_clause_order),
+        Command::Graph {
+            file,
+            format,
+            output,
+        } => graph(&file, format, output.as_deref()),
+        Command::Deps {
+            file,
+            json,
+            manifest,
+            yaml,
+            baseline,
+        } => deps(&file, json, manifest, yaml, baseline.as_deref()),
+        Command::Summary { file, json } => summary(&file, json),
+        Command::Report { file, output } => report(&file, output.as_deref()),
+        Command::Redact {
+            file,
+            strip,
+            keep,
+            output,
+        } => redact_file(&file, strip, &keep, output.as_deref()),
+        Command::Minimize {
+            file,
+            error,
+            panic,
+            output,
+        } => minimize_file(&file, error.as_deref(), panic, output.as_deref()),
+        Command::Health {
+            files,
+            coverage,
+            lint_config,
+            min_score,
+            json,
+        } => health(&files, coverage, lint_config.as_deref(), min_score, json),
+        Command::Metrics {
+            files,
+            max_fan_in,
+            max_fan_out,
+            max_depth,
+            max_reasoning_actions,
+            max_complexity,
+            max_instruction_nesting,
+            json,
+        } => {
+            let budget = MetricsBudget {
+                max_fan_in,
+                max_fan_out,
+                max_depth,
+                max_reasoning_actions,
+                max_complexity,
+                max_instruction_nesting,
+            };
+            metrics(&files, &budget, json)
+        }
+        Command::Export { file, output } => export(&file, output.as_deref()),
+        Command::Import {
+            dir,
+            planner,
+            output,
+        } => import(&dir, planner.as_deref(), output.as_deref()),
+        Command::Grammar { format, output } => grammar(format, output.as_deref()),
+        Command::Schema { output } => schema(output.as_deref()),
+        Command::Json { file, output } => json(&file, output.as_deref()),
+    };
+
+    match result {
+        Ok(Outcome::Success) => ExitCode::SUCCESS,
+        Ok(Outcome::Failure) => ExitCode::from(1),
+        Err(message) => {
+            eprintln!("error: {}", message);
+            ExitCode::from(2)
+        }
+    }
+}
+
+// =============================================================================
+// Commands
+// =============================================================================
+
+fn check(
+    files: &[PathBuf],
+    deny_warnings: bool,
+    target: Option<TargetEnvironment>,
+    lint_config: Option<&Path>,
+    junit: Option<(&Path, JunitGrouping)>,
+) -> Result<Outcome, String> {
+    let lint = load_lint_config(lint_config)?;
+    let mut errors = 0;
+    let mut warnings = 0;
+    // Every file's source and diagnostics, for the JUnit report
+    let mut results = Vec::new();
+
+    for path in files {
+        let source = read(path)?;
+        let name = path.display().to_string();
+        let reporter = reporter(&name, &source);
+
+        let (ast, parse_errors) = parse_with_structured_errors_all(&source);
+        for error in &parse_errors {
+            reporter.report_parse_error(error);
+        }
+        errors += parse_errors.len();
+        let mut diagnostics: Vec<SemanticError> =
+            parse_errors.iter().map(SemanticError::from).collect();
+
+        let Some(ast) = ast else {
+            results.push((name, source, diagnostics));
+            continue;
+        };
+        let target = target
+            .or_else(|| TargetEnvironment::from_config(&ast))
+            .unwrap_or_default();
+        let mut issues = validate_ast_for(&ast, target);
+        if let Ok(graph) = RefGraph::from_ast(&ast) {
+            let result = graph.validate();
+            issues.extend(
+                result
+                    .errors
+                    .iter()
+                    .map(|e| graph_issue(e, Severity::Error)),
+            );
+            issues.extend(
+                result
+                    .warnings
+                    .iter()
+                    .map(|e| graph_issue(e, Severity::Warning)),
+            );
+            issues.extend(
+                run_lints_for(&ast, &graph, &lint, target)
+                    .into_iter()
+                    .map(SemanticError::from),
+            );
+        }
+        sort_diagnostics(&mut issues);
+
+        for issue in &issues {
+            reporter.report_semantic_error(issue);
+            match issue.severity {
+                Severity::Error => errors += 1,
+                Severity::Warning => warnings += 1,
+            }
+        }
+        diagnostics.extend(issues);
+        results.push((name, source, diagnostics));
+    }
+
+    if let Some((path, grouping)) = junit {
+        let mut report = JunitReport::new("agentscript").deny_warnings(deny_warnings);
+        for (name, source, diagnostics) in &results {
+            report.add_file(name.as_str(), source, diagnostics.iter().cloned());
+        }
+        write(path, &report.to_xml(grouping))?;
+    }
+
+    eprintln!(
+        "checked {} file{}: {} error{}, {} warning{}",
+        files.len(),
+        plural(files.len()),
+        errors,
+        plural(errors),
+        warnings,
+        plural(warnings)
+    );
+
+    if errors > 0 || (deny_warnings && warnings > 0) {
+        Ok(Outcome::Failure)
+    } else {
+        Ok(Outcome::Success)
+    }
+}
+
+fn fmt(files: &[PathBuf], check: bool, canonical_clause_order: bool) -> Result<Outcome, String> {
+    let mut outcome = Outcome::Success;
+    let options = SerializeOptions {
+        clause_order: if canonical_clause_order {
+            ClauseOrder::Canonical
+        } else {
+            ClauseOrder::Source
+        },
+    };
+
+    for path in files {
+        let source = read(path)?;
+        if parse_or_report(path, &source).is_none() {
+            outcome = Outcome::Failure;
+            continue;
+        }
+
+        let result = format_file_with_options(&source, &options).map_err(|e| e.join("\n"))?;
+        // Never write output that would change the file's meaning
+        if let Some(diagnostic) = result.diagnostic() {
+            reporter(&path.display().to_string(), &source).report_semantic_error(&diagnostic);
+            outcome = Outcome::Failure;
+            continue;
+        }
+        let formatted = result.formatted;
+        if formatted == source {
+            continue;
+        }
+        if check {
+            eprintln!("{} is not formatted", path.display());
+            outcome = Outcome::Failure;
+        } else {
+            write(path, &formatted)?;
+            eprintln!("formatted {}", path.display());
+        }
+    }
+
+    Ok(outcome)
+}
+
+fn graph(file: &Path, format: GraphFormat, output: Option<&Path>) -> Result<Outcome, String> {
+    let source = read(file)?;
+    let Some(ast) = parse_or_report(file, &source) else {
+        return Ok(Outcome::Failure);
+    };
+    let graph = RefGraph::from_ast(&ast)
+        .map_err(|e| format!("failed to build graph for {}: {}", file.display(), e))?;
+
+    let rendered = match format {
+        GraphFormat::Dot => render_dot(&graph, DotOptions::default()),
+        GraphFormat::Graphml => render_graphml(&graph),
+        GraphFormat::Mermaid => render_mermaid(&graph),
+        GraphFormat::Svg => render_topic_flow_svg(&graph, &SvgOptions::default()),
+    };
+    emit(output, &rendered)?;
+    Ok(Outcome::Success)
+}
+
+fn redact_file(
+    file: &Path,
+    strip: bool,
+    keep: &[RedactCategory],
+    output: Option<&Path>,
+) -> Result<Outcome, String> {
+    let source = read(file)?;
+    let Some(ast) = parse_or_report(file, &source) else {
+        return Ok(Outcome::Failure);
+    };
+    let redaction = |category| {
+        if keep.contains(&category) {
+            Redaction::Keep
+        } else if strip {
+            Redaction::Strip
+        } else {
+            Redaction::Mask
+        }
+    };
+    let config = RedactionConfig {
+        instructions: redaction(RedactCategory::Instructions),
+        messages: redaction(RedactCategory::Messages),
+        descriptions: redaction(RedactCategory::Descriptions),
+        string_literals: redaction(RedactCategory::Strings),
+        connections: redaction(RedactCategory::Connections),
+        targets: redaction(RedactCategory::Targets),
+    };
+    emit(output, &serialize(&redact(&ast, &config)))?;
+    Ok(Outcome::Success)
+}
+
+fn minimize_file(
+    file: &Path,
+    error: Option<&str>,
+    panic: bool,
+    output: Option<&Path>,
+) -> Result<Outcome, String> {
+    let source = read(file)?;
+    let errors = |s: &str| parse_with_structured_errors_all(s).1;
+    let signature = |e: &ParseErrorInfo| (e.found.clone(), e.expected.clone());
+    let original = errors(&source).first().map(signature);
+    let reproduces = |s: &str| {
+        if panic {
+            return std::panic::catch_unwind(|| parse(s)).is_err();
+        }
+        let errors = errors(s);
+        match error {
+            Some(text) => errors.iter().any(|e| e.to_string().contains(text)),
+            None => original.is_some() && errors.first().map(signature) == original,
+        }
+    };
+    if panic {
+        // Expected panics would otherwise print a backtrace per attempt
+        std::panic::set_hook(Box::new(|_| {}));
+    }
+    if !reproduces(&source) {
+        return Err(format!(
+            "{} does not reproduce the problem; nothing to minimize",
+            file.display()
+        ));
+    }
+    let minimal = minimize(&source, reproduces);
+    emit(output, &minimal)?;
+    Ok(Outcome::Success)
+}
+
+fn report(file: &Path, output: Option<&Path>) -> Result<Outcome, String> {
+    let source = read(file)?;
+    let Some(ast) = parse_or_report(file, &source) else {
+        return Ok(Outcome::Failure);
+    };
+    emit(output, &generate_html(&ast))?;
+    Ok(Outcome::Success)
+}
+
+fn summary(file: &Path, json: bool) -> Result<Outcome, String> {
+    let source = read(file)?;
+    let Some(ast) = parse_or_report(file, &source) else {
+        return Ok(Outcome::Failure);
+    };
+    let summary = summarize(&ast);
+
+    let rendered = if json {
+        serde_json::to_string_pretty(&summary)
+            .map_err(|e| format!("failed to serialize summary: {}", e))?
+    } else {
+        summary.to_string()
+    };
+    emit(None, &rendered)?;
+    Ok(Outcome::Success)
+}
+
+fn deps(
+    file: &Path,
+    json: bool,
+    manifest: bool,
+    yaml: bool,
+    baseline: Option<&Path>,
+) -> Result<Outcome, String> {
+    let source = read(file)?;
+    let Some(ast) = parse_or_report(file, &source) else {
+        return Ok(Outcome::Failure);
+    };
+    let report = extract_dependencies(&ast);
+
+    if manifest {
+        let manifest = report.to_manifest();
+        let rendered = if yaml {
+            manifest.to_yaml()
+        } else {
+            manifest.to_json()
+        };
+        emit(None, &rendered)?;
+        return Ok(Outcome::Success);
+    }
+
+    if let Some(baseline) = baseline {
+        let before: DependencyManifest = serde_json::from_str(&read(baseline)?)
+            .map_err(|e| format!("{}: invalid dependency manifest: {}", baseline.display(), e))?;
+        let diff = before.diff(&report.to_manifest());
+        for (sign, entries) in [('+', &diff.added), ('-', &diff.removed)] {
+            for entry in entries {
+                let used_in: Vec<&str> = entry.used_by.iter().map(|u| u.used_in.as_str()).collect();
+                println!("{} {} {} ({})", sign, entry.category, entry.name, used_in.join(", "));
+            }
+        }
+        if diff.added.is_empty() {
+            return Ok(Outcome::Success);
+        }
+        eprintln!(
+            "{} new dependenc{} since {}",
+            diff.added.len(),
+            if diff.added.len() == 1 { "y" } else { "ies" },
+            baseline.display()
+        );
+        return Ok(Outcome::Failure);
+    }
+
+    if json {
+        let json = serde_json::to_string_pretty(&report)
+            .map_err(|e| format!("failed to serialize dependencies: {}", e))?;
+        emit(None, &json)?;
+        return Ok(Outcome::Success);
+    }
+
+    let sections = [
+        ("SObjects", &report.sobjects),
+        ("Fields", &report.fields),
+        ("Flows", &report.flows),
+        ("Apex classes", &report.apex_classes),
+        ("Knowledge bases", &report.knowledge_bases),
+        ("Connections", &report.connections),
+        ("Prompt templates", &report.prompt_templates),
+        ("External services", &report.external_services),
+    ];
+    for (heading, names) in sections {
+        if names.is_empty() {
+            continue;
+        }
+        let mut names: Vec<&String> = names.iter().collect();
+        names.sort();
+        println!("{}:", heading);
+        for name in names {
+            println!("  {}", name);
+        }
+    }
+    Ok(Outcome::Success)
+}
+
+fn health(
+    files: &[PathBuf],
+    coverage: Option<f64>,
+    lint_config: Option<&Path>,
+    min_score: Option<u8>,
+    json: bool,
+) -> Result<Outcome, String> {
+    let options = HealthOptions {
+        coverage,
+        lint: load_lint_config(lint_config)?,
+        ..HealthOptions::default()
+    };
+    let mut outcome = Outcome::Success;
+    let mut reports = serde_json::Map::new();
+
+    for path in files {
+        let source = read(path)?;
+        let Some(ast) = parse_or_report(path, &source) else {
+            outcome = Outcome::Failure;
+            continue;
+        };
+        let report = health_report(&ast, &options);
+        if min_score.is_some_and(|min| report.score < min) {
+            outcome = Outcome::Failure;
+        }
+
+        if json {
+            let value = serde_json::to_value(&report)
+                .map_err(|e| format!("failed to serialize health report: {}", e))?;
+            reports.insert(path.display().to_string(), value);
+            continue;
+        }
+
+        println!("{}: {}/100", path.display(), report.score);
+        for component in &report.components {
+            println!("  {:<14}{:>5.0}", component.name, component.score);
+        }
+        let index = LineIndex::new(&source);
+        for issue in &report.top_issues {
+            let location = issue.span.as_ref().map_or_else(String::new, |span| {
+                let location = index.location(span.start, Encoding::Utf32);
+                format!("{}:{}: ", location.line + 1, location.column + 1)
+            });
+            println!(
+                "  -{:<4.0} {}{} [{}]",
+                issue.penalty, location, issue.message, issue.component
+            );
+        }
+    }
+
+    if json {
+        let json = serde_json::to_string_pretty(&reports)
+            .map_err(|e| format!("failed to serialize health reports: {}", e))?;
+        emit(None, &json)?;
+    }
+    Ok(outcome)
+}
+
+fn metrics(files: &[PathBuf], budget: &MetricsBudget, json: bool) -> Result<Outcome, String> {
+    let mut outcome = Outcome::Success;
+    let mut reports = serde_json::Map::new();
+
+    for path in files {
+        let source = read(path)?;
+        let Some(ast) = parse_or_report(path, &source) else {
+            outcome = Outcome::Failure;
+            continue;
+        };
+        let graph = RefGraph::from_ast(&ast)
+            .map_err(|e| format!("{}: failed to build graph: {}", path.display(), e))?;
+        let metrics = graph.metrics();
+        let violations = metrics.check(budget);
+        if !violations.is_empty() {
+            outcome = Outcome::Failure;
+        }
+
+        if json {
+            let value = serde_json::json!({
+                "metrics": GraphMetricsRepr::from(&metrics),
+                "violations": violations,
+            });
+            reports.insert(path.display().to_string(), value);
+            continue;
+        }
+
+        println!("{}:", path.display());
+        println!(
+            "  {:<28}{:>7}{:>8}{:>6}{:>8}{:>11}{:>8}",
+            "topic", "fan-in", "fan-out", "depth", "actions", "complexity", "nesting"
+        );
+        for t in &metrics.topics {
+            let depth = t.depth.map_or_else(|| "-".to_string(), |d| d.to_string());
+            println!(
+                "  {:<28}{:>7}{:>8}{:>6}{:>8}{:>11}{:>8}",
+                t.name,
+                t.fan_in,
+                t.fan_out,
+                depth,
+                t.reasoning_actions,
+                t.complexity,
+                t.max_instruction_nesting
+            );
+        }
+        for violation in &violations {
+            println!("  over budget: {}", violation);
+        }
+    }
+
+    if json {
+        let json = serde_json::to_string_pretty(&reports)
+            .map_err(|e| format!("failed to serialize metrics: {}", e))?;
+        emit(None, &json)?;
+    }
+    Ok(outcome)
+}
+
+fn export(file: &Path, output: Option<&Path>) -> Result<Outcome, String> {
+    let source = read(file)?;
+    let Some(ast) = parse_or_report(file, &source) else {
+        return Ok(Outcome::Failure);
+    };
+    let metadata = to_salesforce_metadata(&ast);
+
+    let Some(dir) = output else {
+        let json = serde_json::to_string_pretty(&metadata)
+            .map_err(|e| format!("failed to serialize metadata: {}", e))?;
+        emit(None, &json)?;
+        return Ok(Outcome::Success);
+    };
+    metadata
+        .write_to(dir)
+        .map_err(|e| format!("failed to write {}: {}", dir.display(), e))?;
+    eprintln!(
+        "wrote {} file{} to {}",
+        metadata.files.len(),
+        plural(metadata.files.len()),
+        dir.display()
+    );
+    Ok(Outcome::Success)
+}
+
+fn import(dir: &Path, planner: Option<&str>, output: Option<&Path>) -> Result<Outcome, String> {
+    let agent = match planner {
+        Some(name) => import_planner(dir, name),
+        None => import_from_metadata(dir),
+    }
+    .map_err(|e| e.to_string())?;
+    emit(output, &serialize(&agent))?;
+    Ok(Outcome::Success)
+}
+
+fn grammar(format: GrammarFormat, output: Option<&Path>) -> Result<Outcome, String> {
+    let rendered = match format {
+        GrammarFormat::Ebnf => export_ebnf(),
+        GrammarFormat::Railroad => serde_json::to_string_pretty(&export_railroad())
+            .map_err(|e| format!("failed to serialize grammar: {}", e))?,
+    };
+    emit(output, &rendered)?;
+    Ok(Outcome::Success)
+}
+
+fn schema(output: Option<&Path>) -> Result<Outcome, String> {
+    let rendered = serde_json::to_string_pretty(&ast_json_schema())
+        .map_err(|e| format!("failed to serialize schema: {}", e))?;
+    emit(output, &rendered)?;
+    Ok(Outcome::Success)
+}
+
+fn json(file: &Path, output: Option<&Path>) -> Result<Outcome, String> {
+    let source = read(file)?;
+    let Some(ast) = parse_or_report(file, &source) else {
+        return Ok(Outcome::Failure);
+    };
+    let json = serde_json::to_string_pretty(&ast)
+        .map_err(|e| format!("failed to serialize AST: {}", e))?;
+    emit(output, &json)?;
+    Ok(Outcome::Success)
+}
+
+// =============================================================================
+// Helpers
+// =============================================================================
+
+/// Parse a file, reporting any parse errors. Returns `None` if there were errors.
+fn parse_or_report(path: &Path, source: &str) -> Option<AgentFile> {
+    let (ast, errors) = parse_with_structured_errors_all(source);
+    if errors.is_empty() {
+        return ast;
+    }
+    let name = path.display().to_string();
+    let reporter = reporter(&name, source);
+    for error in &errors {
+        reporter.report_parse_error(error);
+    }
+    None
+}
+
+/// Create an error reporter, with colors only when stderr is a terminal.
+fn reporter<'a>(name: &str, source: &'a str) -> ErrorReporter<'a> {
+    ErrorReporter::new(name, source).with_color(io::stderr().is_terminal())
+}
+
+/// Convert a graph validation issue into a semantic error for reporting.
+fn graph_issue(error: &ValidationError, severity: Severity) -> SemanticError {
+    SemanticError {
+        message: error.message(),
+        span: error.span().map(|(start, end)| start..end),
+        severity,
+        hint: error.hint(),
+        code: None,
+        related: Vec::new(),
+    }
+}
+
+/// Load the given lint configuration, or the nearest `.agentscriptlint.toml`.
+fn load_lint_config(path: Option<&Path>) -> Result<LintConfig, String> {
+    let Some(path) = path
+        .map(Path::to_path_buf)
+        .or_else(|| find_config_file(&std::env::current_dir().ok()?))
+    else {
+        return Ok(LintConfig::default());
+    };
+    let config = LintConfig::load(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    for id in LintRegistry::new().unknown_rules(&config) {
+        eprintln!("warning: {}: unknown lint rule '{}'", path.display(), id);
+    }
+    Ok(config)
+}
+
+fn read(path: &Path) -> Result<String, String> {
+    fs::read_to_string(path).map_err(|e| format!("failed to read {}: {}", path.display(), e))
+}
+
+fn write(path: &Path, contents: &str) -> Result<(), String> {
+    fs::write(path, contents).map_err(|e| format!("failed to write {}: {}", path.display(), e))
+}
+
+/// Write output to a file, or stdout if no file was given.
+fn emit(output: Option<&Path>, contents: &str) -> Result<(), String> {
+    let Some(path) = output else {
+        let mut stdout = io::stdout().lock();
+        let result = stdout.write_all(contents.as_bytes()).and_then(|()| {
+            if contents.ends_with('\n') {
+                Ok(())
+            } else {
+                stdout.write_all(b"\n")
+            }
+        });
+        // A closed pipe (e.g. `| head`) is not an error
+        return match result {
+            Err(e) if e.kind() != io::ErrorKind::BrokenPipe => {
+                Err(format!("failed to write output: {}", e))
+            }
+            _ => Ok(()),
+        };
+    };
+    write(path, contents)
+}
+
+fn plural(n: usize) -> &'static str {
+    if n == 1 {
+        ""
+    } else {
+        "s"
+    }
+}
@@ -0,0 +1,56 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+fn temp_file(name: &str, contents: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("agentscript-cli-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+fn without_whitespace(text: &str) -> String {
+    text.split_whitespace().collect()
+}
+
+#[test]
+fn test_fmt_keeps_comments() {
+    let source = r#"config:
+    agent_name:   "Support"
+    # owner: team-x
+    agent_label: "Support"
+
+# section: entry point
+start_agent selector:
+    description:  "Route"
+    reasoning:
+        instructions: "Route"
+
+topic main:
+    description:    "Main"
+    reasoning:
+        instructions: "Help"
+# trailing note
+"#;
+    let path = temp_file("comments.agent", source);
+    let status = Command::new(env!("CARGO_BIN_EXE_busbar-sf-agentscript"))
+        .arg("fmt")
+        .arg(&path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let formatted = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(without_whitespace(&formatted), without_whitespace(source));
+    for comment in [
+        "# owner: team-x",
+        "# section: entry point",
+        "# trailing note",
+    ] {
+        assert!(formatted.contains(comment), "lost {comment}");
+    }
+    // Blocks without comments are still formatted
+    assert!(formatted.contains("topic main:\n   description: \"Main\"\n"));
+    assert!(!formatted.ends_with("\n\n"));
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,138 @@
+//! Text of open documents, kept in sync with incremental edits.
+//!
+//! The server asks for [`TextDocumentSyncKind::INCREMENTAL`], so each
+//! `didChange` carries only the edited ranges. [`TextBuffer`] applies them
+//! in order to the last known text; positions are UTF-16, as the protocol
+//! requires.
+
+use busbar_sf_agentscript::text_pos::{Encoding, LineIndex, Location};
+use tower_lsp::lsp_types::*;
+
+/// The current text of an open document.
+pub struct TextBuffer {
+    text: String,
+    version: i32,
+}
+
+impl TextBuffer {
+    pub fn new(text: String, version: i32) -> Self {
+        Self { text, version }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn version(&self) -> i32 {
+        self.version
+    }
+
+    /// Apply the changes of one `didChange` notification, in order.
+    ///
+    /// A change without a range replaces the whole text. Ranges past the
+    /// end of a line or of the document are clamped to it.
+    pub fn apply(&mut self, changes: Vec<TextDocumentContentChangeEvent>, version: i32) {
+        for change in changes {
+            match change.range {
+                Some(range) => {
+                    let index = LineIndex::new(&self.text);
+                    let span = index.span(
+                        Location::new(range.start.line, range.start.character)
+                            ..Location::new(range.end.line, range.end.character),
+                        Encoding::Utf16,
+                    );
+                    let start = span.start.min(span.end);
+                    self.text.replace_range(start..span.end, &change.text);
+                }
+                None => self.text = change.text,
+            }
+        }
+        self.version = version;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn change(
+        range: Option<((u32, u32), (u32, u32))>,
+        text: &str,
+    ) -> TextDocumentContentChangeEvent {
+        TextDocumentContentChangeEvent {
+            range: range.map(|(start, end)| {
+                Range::new(Position::new(start.0, start.1), Position::new(end.0, end.1))
+            }),
+            range_length: None,
+            text: text.to_string(),
+        }
+    }
+
+    fn applied(text: &str, changes: Vec<TextDocumentContentChangeEvent>) -> String {
+        let mut buffer = TextBuffer::new(text.to_string(), 1);
+        buffer.apply(changes, 2);
+        assert_eq!(buffer.version(), 2);
+        buffer.text().to_string()
+    }
+
+    #[test]
+    fn test_changes_apply_in_order() {
+        // The second range is relative to the text after the first change
+        let text = applied(
+            "topic a:\n   description: \"A\"\n",
+            vec![
+                change(Some(((0, 6), (0, 7))), "billing"),
+                change(Some(((1, 17), (1, 18))), "Billing"),
+                change(Some(((2, 0), (2, 0))), "# end\n"),
+            ],
+        );
+        assert_eq!(text, "topic billing:\n   description: \"Billing\"\n# end\n");
+    }
+
+    #[test]
+    fn test_change_without_range_replaces_text() {
+        let text = applied(
+            "old",
+            vec![
+                change(Some(((0, 0), (0, 3))), "edited"),
+                change(None, "new\n"),
+                change(Some(((1, 0), (1, 0))), "x"),
+            ],
+        );
+        assert_eq!(text, "new\nx");
+    }
+
+    #[test]
+    fn test_positions_count_utf16_code_units() {
+        // 😀 is two UTF-16 code units and four bytes
+        let text = applied("a😀b\n", vec![change(Some(((0, 3), (0, 4))), "c")]);
+        assert_eq!(text, "a😀c\n");
+        let text = applied("a😀b\n", vec![change(Some(((0, 1), (0, 3))), "")]);
+        assert_eq!(text, "ab\n");
+    }
+
+    #[test]
+    fn test_out_of_range_positions_are_clamped() {
+        // Past the end of a line
+        let text = applied("ab\ncd\n", vec![change(Some(((0, 10), (0, 20))), "!")]);
+        assert_eq!(text, "ab!\ncd\n");
+        // Past the end of the document
+        let text = applied("ab\ncd", vec![change(Some(((1, 1), (9, 0))), "")]);
+        assert_eq!(text, "ab\nc");
+        // A range ending before it starts inserts at its end
+        let text = applied("abcd", vec![change(Some(((0, 3), (0, 1))), "-")]);
+        assert_eq!(text, "a-bcd");
+    }
+
+    #[test]
+    fn test_crlf_line_endings() {
+        let text = applied(
+            "topic a:\r\n   description: \"A\"\r\n",
+            vec![
+                change(Some(((1, 3), (1, 14))), "label"),
+                change(Some(((0, 8), (0, 8))), " # x"),
+            ],
+        );
+        assert_eq!(text, "topic a: # x\r\n   label: \"A\"\r\n");
+    }
+}
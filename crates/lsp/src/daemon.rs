@@ -0,0 +1,450 @@
+//! Daemon mode: the analysis engine behind a socket, for build systems and
+//! other tools that aren't editors.
+//!
+//! `busbar-sf-agentscript-lsp --daemon --socket <path>` listens on a Unix
+//! domain socket. Each connection speaks JSON-RPC 2.0, one message per line,
+//! and gets its own session that caches the documents it asked about, so
+//! asking again about an unchanged file skips the parse. Diagnostics are
+//! cached for all connections by semantic hash, in the directory given by
+//! `--cache-dir` if there is one, so reformatting a file doesn't validate it
+//! again.
+//!
+//! | Method | Params | Result |
+//! |---|---|---|
+//! | `parse` | `{uri, text?}` | `{ast, diagnostics}` |
+//! | `validate` | `{uri, text?, target?}` | `{diagnostics}` |
+//! | `graph` | `{uri, text?, topic?}` | the graph, as `agentscript/getGraph` returns it |
+//! | `format` | `{uri, text?}` | `{formatted, edits}` |
+//! | `close` | `{uri}` | `null`, dropping the document from the session |
+//!
+//! Without `text`, a `file://` uri is read from disk. Diagnostics and edits
+//! use the LSP shapes, with UTF-16 positions.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use busbar_sf_agentscript::edit::apply_edits;
+use busbar_sf_agentscript::formatter;
+use busbar_sf_agentscript::graph::{GraphRepr, PassId};
+use busbar_sf_agentscript::lint::{find_config_file, LintConfig};
+use busbar_sf_agentscript::validation::TargetEnvironment;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tower_lsp::jsonrpc::{Error, ErrorCode, Id, Request, Response, Result};
+use tower_lsp::lsp_types::{TextEdit, Url};
+
+use crate::{
+    compute_diagnostics, parse_error_to_diagnostic, to_text_edit, DiagnosticsCache, DocumentState,
+};
+
+/// Listen on the Unix socket at `path` until interrupted. Every connection
+/// shares `analysis`.
+///
+/// A socket file left behind by a daemon that is no longer running is
+/// replaced; one that still accepts connections is an error.
+#[cfg(unix)]
+pub async fn serve(path: &Path, analysis: Arc<DiagnosticsCache>) -> std::io::Result<()> {
+    use tokio::net::{UnixListener, UnixStream};
+
+    if path.exists() {
+        if UnixStream::connect(path).await.is_ok() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AddrInUse,
+                "in use by another daemon",
+            ));
+        }
+        std::fs::remove_file(path)?;
+    }
+    let listener = UnixListener::bind(path)?;
+    log::info!("Listening on {}", path.display());
+
+    let accept = async {
+        loop {
+            let (stream, _) = listener.accept().await?;
+            let analysis = analysis.clone();
+            tokio::spawn(async move {
+                if let Err(err) = serve_connection(stream, analysis).await {
+                    log::warn!("Connection closed: {}", err);
+                }
+            });
+        }
+    };
+    let result: std::io::Result<()> = tokio::select! {
+        result = accept => result,
+        _ = tokio::signal::ctrl_c() => Ok(()),
+    };
+    let _ = std::fs::remove_file(path);
+    result
+}
+
+#[cfg(not(unix))]
+pub async fn serve(_path: &Path, _analysis: Arc<DiagnosticsCache>) -> std::io::Result<()> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "daemon mode needs Unix domain sockets",
+    ))
+}
+
+/// Answer the requests on one connection, in order, until it closes.
+async fn serve_connection(
+    stream: impl AsyncRead + AsyncWrite,
+    analysis: Arc<DiagnosticsCache>,
+) -> std::io::Result<()> {
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut lines = BufReader::new(reader).lines();
+    let mut session = Session {
+        documents: HashMap::new(),
+        analysis,
+    };
+
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => session.handle(request).await,
+            Err(_) => Some(Response::from_error(Id::Null, Error::parse_error())),
+        };
+        // Notifications get no response
+        if let Some(response) = response {
+            let mut message = serde_json::to_vec(&response)?;
+            message.push(b'\n');
+            writer.write_all(&message).await?;
+        }
+    }
+    Ok(())
+}
+
+/// The document a request is about.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DocumentParams {
+    uri: Url,
+    /// The document's text; read from `uri` when absent.
+    text: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ValidateParams {
+    #[serde(flatten)]
+    document: DocumentParams,
+    /// Deployment target, as the `targetEnvironment` initialization option.
+    target: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GraphParams {
+    #[serde(flatten)]
+    document: DocumentParams,
+    /// Limit the graph to one topic and what it references.
+    topic: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CloseParams {
+    uri: Url,
+}
+
+#[derive(Serialize)]
+struct FormatResponse {
+    formatted: String,
+    edits: Vec<TextEdit>,
+}
+
+/// The documents one connection has asked about.
+struct Session {
+    documents: HashMap<Url, Arc<DocumentState>>,
+    analysis: Arc<DiagnosticsCache>,
+}
+
+impl Session {
+    async fn handle(&mut self, request: Request) -> Option<Response> {
+        let (method, id, params) = request.into_parts();
+        let result = self.call(&method, params.unwrap_or(Value::Null)).await;
+        id.map(|id| Response::from_parts(id, result))
+    }
+
+    async fn call(&mut self, method: &str, params: Value) -> Result<Value> {
+        match method {
+            "parse" => {
+                let params: DocumentParams = from_params(params)?;
+                let doc = self.document(&params).await?;
+                let diagnostics: Vec<_> = doc
+                    .parse_errors
+                    .iter()
+                    .map(|err| parse_error_to_diagnostic(&doc.source, err))
+                    .collect();
+                to_result(serde_json::json!({ "ast": doc.ast, "diagnostics": diagnostics }))
+            }
+            "validate" => {
+                let params: ValidateParams = from_params(params)?;
+                let target: Option<TargetEnvironment> = match &params.target {
+                    Some(target) => Some(target.parse().map_err(Error::invalid_params)?),
+                    None => None,
+                };
+                let doc = self.document(&params.document).await?;
+                let uri = params.document.uri;
+                let lint = lint_config(&uri);
+                let analysis = self.analysis.clone();
+                let diagnostics = tokio::task::spawn_blocking(move || {
+                    // Every pass runs, so no earlier whole-graph findings are reused
+                    let expensive = Default::default();
+                    compute_diagnostics(
+                        &uri,
+                        &doc,
+                        PassId::ALL,
+                        target,
+                        &lint,
+                        &analysis,
+                        &expensive,
+                    )
+                })
+                .await
+                .map_err(|_| Error::internal_error())?;
+                to_result(serde_json::json!({ "diagnostics": diagnostics }))
+            }
+            "graph" => {
+                let params: GraphParams = from_params(params)?;
+                let doc = self.document(&params.document).await?;
+                let graph = doc
+                    .graph
+                    .as_ref()
+                    .ok_or_else(|| Error::invalid_params("No graph available (parse errors?)"))?;
+                let repr = match &params.topic {
+                    Some(topic) => {
+                        GraphRepr::from(&graph.subgraph_for_topic(topic).ok_or_else(|| {
+                            Error::invalid_params(format!("Unknown topic '{}'", topic))
+                        })?)
+                    }
+                    None => GraphRepr::from(graph),
+                };
+                to_result(repr)
+            }
+            "format" => {
+                let params: DocumentParams = from_params(params)?;
+                let doc = self.document(&params).await?;
+                let ast = doc
+                    .ast
+                    .as_ref()
+                    .ok_or_else(|| Error::invalid_params("Document has parse errors"))?;
+                let edits = formatter::format_range(&doc.source, ast, 0..doc.source.len());
+                let formatted = apply_edits(&doc.source, edits.clone())
+                    .map_err(|e| internal_error(e.to_string()))?;
+                let edits = edits
+                    .into_iter()
+                    .map(|edit| to_text_edit(&doc.source, edit))
+                    .collect();
+                to_result(FormatResponse { formatted, edits })
+            }
+            "close" => {
+                let params: CloseParams = from_params(params)?;
+                self.documents.remove(&params.uri);
+                Ok(Value::Null)
+            }
+            _ => Err(Error::method_not_found()),
+        }
+    }
+
+    /// The snapshot of a document, parsed again only if its text changed
+    /// since this session last saw it.
+    async fn document(&mut self, params: &DocumentParams) -> Result<Arc<DocumentState>> {
+        let text = match &params.text {
+            Some(text) => text.clone(),
+            None => {
+                let path = params.uri.to_file_path().map_err(|()| {
+                    Error::invalid_params(format!("{} is not a file; pass its text", params.uri))
+                })?;
+                tokio::fs::read_to_string(&path)
+                    .await
+                    .map_err(|e| Error::invalid_params(format!("{}: {}", path.display(), e)))?
+            }
+        };
+        if let Some(doc) = self.documents.get(&params.uri) {
+            if doc.source == text {
+                return Ok(doc.clone());
+            }
+        }
+        let doc = tokio::task::spawn_blocking(move || DocumentState::new(text))
+            .await
+            .map_err(|_| Error::internal_error())?;
+        let doc = Arc::new(doc);
+        self.documents.insert(params.uri.clone(), doc.clone());
+        Ok(doc)
+    }
+}
+
+/// The `.agentscriptlint.toml` nearest a file, or the defaults.
+fn lint_config(uri: &Url) -> LintConfig {
+    let path = uri
+        .to_file_path()
+        .ok()
+        .and_then(|path| find_config_file(path.parent()?));
+    match path.map(|path| (LintConfig::load(&path), path)) {
+        Some((Ok(config), _)) => config,
+        Some((Err(err), path)) => {
+            log::warn!("{}: {}", path.display(), err);
+            LintConfig::default()
+        }
+        None => LintConfig::default(),
+    }
+}
+
+fn from_params<T: serde::de::DeserializeOwned>(params: Value) -> Result<T> {
+    serde_json::from_value(params).map_err(|e| Error::invalid_params(e.to_string()))
+}
+
+fn to_result(value: impl Serialize) -> Result<Value> {
+    serde_json::to_value(value).map_err(|e| internal_error(e.to_string()))
+}
+
+fn internal_error(message: String) -> Error {
+    Error {
+        code: ErrorCode::InternalError,
+        message: message.into(),
+        data: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AGENT: &str =
+        "topic main:\n   description: \"Main\"\n   reasoning:\n      instructions: \"Help\"\n";
+
+    /// A client connected to a fresh session.
+    struct Client {
+        lines: tokio::io::Lines<BufReader<tokio::io::ReadHalf<tokio::io::DuplexStream>>>,
+        writer: tokio::io::WriteHalf<tokio::io::DuplexStream>,
+    }
+
+    impl Client {
+        fn connect() -> Self {
+            let (client, server) = tokio::io::duplex(64 * 1024);
+            let analysis = Arc::new(std::sync::Mutex::new(Default::default()));
+            tokio::spawn(serve_connection(server, analysis));
+            let (reader, writer) = tokio::io::split(client);
+            Self {
+                lines: BufReader::new(reader).lines(),
+                writer,
+            }
+        }
+
+        async fn send(&mut self, line: &str) {
+            self.writer
+                .write_all(format!("{}\n", line).as_bytes())
+                .await
+                .unwrap();
+        }
+
+        async fn receive(&mut self) -> Value {
+            let line = self.lines.next_line().await.unwrap().unwrap();
+            serde_json::from_str(&line).unwrap()
+        }
+
+        /// Send a request and return its response, which must be for it.
+        async fn call(&mut self, id: i64, method: &str, params: Value) -> Value {
+            let request = serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "method": method,
+                "params": params,
+            });
+            self.send(&request.to_string()).await;
+            let response = self.receive().await;
+            assert_eq!(response["id"], id);
+            response
+        }
+    }
+
+    fn document(text: &str) -> Value {
+        serde_json::json!({ "uri": "file:///ws/main.agent", "text": text })
+    }
+
+    #[tokio::test]
+    async fn test_parse() {
+        let mut client = Client::connect();
+        let response = client.call(1, "parse", document(AGENT)).await;
+        let result = &response["result"];
+        assert_eq!(result["ast"]["topics"][0]["node"]["name"]["node"], "main");
+        assert_eq!(result["diagnostics"], serde_json::json!([]));
+
+        let response = client
+            .call(2, "parse", document("topic main:\n   ???\n"))
+            .await;
+        let diagnostics = response["result"]["diagnostics"].as_array().unwrap();
+        assert!(!diagnostics.is_empty());
+        assert_eq!(diagnostics[0]["range"]["start"]["line"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_validate() {
+        let mut client = Client::connect();
+        let response = client.call(1, "validate", document(AGENT)).await;
+        let diagnostics = response["result"]["diagnostics"].as_array().unwrap();
+        assert!(diagnostics
+            .iter()
+            .all(|d| d["severity"] != serde_json::json!(1)));
+
+        let mut params = document(AGENT);
+        params["target"] = "nowhere".into();
+        let response = client.call(2, "validate", params).await;
+        assert_eq!(response["error"]["code"], -32602);
+    }
+
+    #[tokio::test]
+    async fn test_format() {
+        let mut client = Client::connect();
+        let messy = AGENT.replace("description: ", "description:    ");
+        let response = client.call(1, "format", document(&messy)).await;
+        let result = &response["result"];
+        assert_eq!(result["formatted"], AGENT);
+        assert!(!result["edits"].as_array().unwrap().is_empty());
+
+        // Only files can be read without their text
+        let params = serde_json::json!({ "uri": "untitled:Untitled-1" });
+        let response = client.call(2, "format", params).await;
+        assert_eq!(response["error"]["code"], -32602);
+    }
+
+    #[tokio::test]
+    async fn test_close() {
+        let mut client = Client::connect();
+        client.call(1, "parse", document(AGENT)).await;
+        let params = serde_json::json!({ "uri": "file:///ws/main.agent" });
+        let response = client.call(2, "close", params).await;
+        assert_eq!(response["result"], Value::Null);
+        assert!(response.get("error").is_none());
+
+        // Closing a document the session doesn't have is fine too
+        let params = serde_json::json!({ "uri": "file:///ws/other.agent" });
+        let response = client.call(3, "close", params).await;
+        assert_eq!(response["result"], Value::Null);
+    }
+
+    #[tokio::test]
+    async fn test_malformed_lines_and_notifications() {
+        let mut client = Client::connect();
+        client.send("{not json").await;
+        let response = client.receive().await;
+        assert_eq!(response["id"], Value::Null);
+        assert_eq!(response["error"]["code"], -32700);
+
+        // Blank lines and notifications get no response, so the next
+        // message read answers the request after them
+        client.send("").await;
+        let notification = serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "close",
+            "params": { "uri": "file:///ws/main.agent" },
+        });
+        client.send(&notification.to_string()).await;
+        let response = client.call(1, "unknown", Value::Null).await;
+        assert_eq!(response["error"]["code"], -32601);
+    }
+}
@@ -0,0 +1,113 @@
+//! Host-provided text for the "Add description" quick fix.
+//!
+//! The server proposes descriptions with a [`DescriptionProvider`]. By
+//! default that is the built-in [`TemplateDescriptions`]; an editor can set
+//! the `descriptionCommand` initialization option to a program and its
+//! arguments instead, such as a script that asks a language model. The
+//! provider is only asked when the editor resolves the quick fix, not each
+//! time code actions are listed.
+//!
+//! [`CommandDescriptions`] runs that program once per proposal, writes the
+//! definition to its stdin as JSON, and uses its trimmed stdout as the
+//! description. When the program fails, prints nothing or runs longer than
+//! [`COMMAND_TIMEOUT`], the template text is used.
+
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use busbar_sf_agentscript::describe::{Described, DescriptionProvider, TemplateDescriptions};
+use serde_json::json;
+
+/// Longest a description command may run before it is killed.
+pub const COMMAND_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A provider the server can share between requests.
+pub type SharedProvider = std::sync::Arc<dyn DescriptionProvider + Send + Sync>;
+
+/// Descriptions from an external program, falling back to templates.
+#[derive(Debug, Clone)]
+pub struct CommandDescriptions {
+    program: String,
+    args: Vec<String>,
+}
+
+impl CommandDescriptions {
+    /// A provider running `command[0]` with the rest as arguments, or `None`
+    /// if `command` is empty.
+    pub fn new(command: &[String]) -> Option<Self> {
+        let (program, args) = command.split_first()?;
+        Some(Self {
+            program: program.clone(),
+            args: args.to_vec(),
+        })
+    }
+
+    fn run(&self, input: &str) -> Option<String> {
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .ok()?;
+        // Read output on a thread, so a program printing more than the pipe
+        // holds can still finish
+        let mut stdout = child.stdout.take()?;
+        let reader = std::thread::spawn(move || {
+            let mut output = String::new();
+            stdout.read_to_string(&mut output).map(|_| output)
+        });
+        // Dropping stdin closes it, so the program sees the end of its input
+        child.stdin.take()?.write_all(input.as_bytes()).ok()?;
+
+        let started = Instant::now();
+        loop {
+            match child.try_wait().ok()? {
+                Some(status) if status.success() => break,
+                Some(_) => return None,
+                None if started.elapsed() > COMMAND_TIMEOUT => {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return None;
+                }
+                None => std::thread::sleep(Duration::from_millis(10)),
+            }
+        }
+        let output = reader.join().ok()?.ok()?;
+        let output = output.trim();
+        (!output.is_empty()).then(|| output.to_string())
+    }
+}
+
+impl DescriptionProvider for CommandDescriptions {
+    fn describe(&self, item: &Described<'_>) -> Option<String> {
+        let (topic, target) = match item {
+            Described::Action { topic, action } => {
+                (Some(*topic), action.target.as_ref().map(|t| t.node.as_str()))
+            }
+            _ => (None, None),
+        };
+        let input = json!({
+            "kind": item.kind(),
+            "name": item.name(),
+            "topic": topic,
+            "target": target,
+            "doc": item.doc(),
+        });
+        self.run(&input.to_string())
+            .or_else(|| TemplateDescriptions.describe(item))
+    }
+}
+
+/// The provider for the `descriptionCommand` initialization option: the
+/// command when it is a non-empty array of strings, templates otherwise.
+pub fn provider_from_options(options: Option<&serde_json::Value>) -> SharedProvider {
+    let command: Option<Vec<String>> = options
+        .and_then(|o| o.get("descriptionCommand"))
+        .and_then(|c| serde_json::from_value(c.clone()).ok());
+    match command.as_deref().and_then(CommandDescriptions::new) {
+        Some(command) => std::sync::Arc::new(command),
+        None => std::sync::Arc::new(TemplateDescriptions),
+    }
+}
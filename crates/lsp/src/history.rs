@@ -0,0 +1,215 @@
+//! Recent states of each document, for debugging stale diagnostics.
+//!
+//! [`DocumentHistory`] records every version of a document's text the
+//! server sees and every diagnostic set it publishes, in a bounded ring
+//! buffer per document. The `agentscript/debugHistory` request dumps it, so
+//! a report of diagnostics that flickered or went stale can be traced to
+//! the exact sequence of edits and publishes behind it.
+
+use std::collections::{HashMap, VecDeque};
+use std::time::Instant;
+
+use serde::Serialize;
+use tower_lsp::lsp_types::{Diagnostic, Url};
+
+/// Most events kept per document; the oldest are dropped first.
+pub const HISTORY_LIMIT: usize = 64;
+
+/// Something that happened to a document.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum HistoryEvent {
+    /// The document was opened with this text.
+    Opened { version: i32, text: String },
+    /// An edit produced this version of the text.
+    Changed { version: i32, text: String },
+    /// Diagnostics were published for the text of `version`. `None` when
+    /// that text is not in the history, e.g. the on-disk copy of a closed
+    /// file.
+    Diagnostics {
+        version: Option<i32>,
+        diagnostics: Vec<Diagnostic>,
+    },
+    /// The document was closed.
+    Closed,
+}
+
+/// An event with the time and order it was recorded in.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryEntry {
+    /// Position in the order of events across all documents.
+    pub seq: u64,
+    /// Milliseconds since the server started.
+    pub elapsed_ms: u64,
+    #[serde(flatten)]
+    pub event: HistoryEvent,
+}
+
+/// The last [`HISTORY_LIMIT`] events of every document.
+pub struct DocumentHistory {
+    started: Instant,
+    next_seq: u64,
+    documents: HashMap<Url, VecDeque<HistoryEntry>>,
+}
+
+impl Default for DocumentHistory {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+            next_seq: 0,
+            documents: HashMap::new(),
+        }
+    }
+}
+
+impl DocumentHistory {
+    pub fn record(&mut self, uri: &Url, event: HistoryEvent) {
+        let entry = HistoryEntry {
+            seq: self.next_seq,
+            elapsed_ms: self.started.elapsed().as_millis() as u64,
+            event,
+        };
+        self.next_seq += 1;
+        let entries = self.documents.entry(uri.clone()).or_default();
+        if entries.len() == HISTORY_LIMIT {
+            entries.pop_front();
+        }
+        entries.push_back(entry);
+    }
+
+    /// Record published diagnostics computed from `source`, tagged with the
+    /// latest version that had that text.
+    pub fn record_diagnostics(&mut self, uri: &Url, source: &str, diagnostics: Vec<Diagnostic>) {
+        let version = self.documents.get(uri).and_then(|entries| {
+            entries.iter().rev().find_map(|entry| match &entry.event {
+                HistoryEvent::Opened { version, text }
+                | HistoryEvent::Changed { version, text }
+                    if text == source =>
+                {
+                    Some(*version)
+                }
+                _ => None,
+            })
+        });
+        self.record(
+            uri,
+            HistoryEvent::Diagnostics {
+                version,
+                diagnostics,
+            },
+        );
+    }
+
+    /// The history of one document, or of every document, sorted by URI.
+    pub fn dump(&self, uri: Option<&Url>) -> serde_json::Value {
+        let mut documents: Vec<(&Url, &VecDeque<HistoryEntry>)> = self
+            .documents
+            .iter()
+            .filter(|(u, _)| uri.is_none_or(|uri| uri == *u))
+            .collect();
+        documents.sort_by(|a, b| a.0.as_str().cmp(b.0.as_str()));
+        serde_json::json!({
+            "limit": HISTORY_LIMIT,
+            "documents": documents
+                .into_iter()
+                .map(|(uri, entries)| serde_json::json!({ "uri": uri, "entries": entries }))
+                .collect::<Vec<_>>(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn uri(name: &str) -> Url {
+        Url::parse(&format!("file:///ws/{}.agent", name)).unwrap()
+    }
+
+    fn changed(version: i32) -> HistoryEvent {
+        HistoryEvent::Changed {
+            version,
+            text: format!("v{}", version),
+        }
+    }
+
+    #[test]
+    fn test_oldest_events_are_evicted_at_capacity() {
+        let mut history = DocumentHistory::default();
+        let (a, b) = (uri("a"), uri("b"));
+        for version in 0..HISTORY_LIMIT as i32 + 10 {
+            history.record(&a, changed(version));
+        }
+        history.record(&b, HistoryEvent::Closed);
+
+        let entries = &history.documents[&a];
+        assert_eq!(entries.len(), HISTORY_LIMIT);
+        assert_eq!(entries.front().unwrap().seq, 10);
+        assert!(matches!(
+            entries.back().unwrap().event,
+            HistoryEvent::Changed { version, .. } if version == HISTORY_LIMIT as i32 + 9
+        ));
+        // Each document has its own buffer; `seq` counts across all of them
+        assert_eq!(history.documents[&b].len(), 1);
+        assert_eq!(history.documents[&b][0].seq, HISTORY_LIMIT as u64 + 10);
+    }
+
+    #[test]
+    fn test_debug_history_dump() {
+        let mut history = DocumentHistory::default();
+        let (a, b) = (uri("a"), uri("b"));
+        history.record(
+            &b,
+            HistoryEvent::Opened {
+                version: 1,
+                text: "v1".to_string(),
+            },
+        );
+        history.record(&a, changed(2));
+        history.record(&a, changed(3));
+        // Tagged with the latest version that had the text
+        history.record_diagnostics(&a, "v2", Vec::new());
+        history.record_diagnostics(&a, "on disk", Vec::new());
+        history.record(&a, HistoryEvent::Closed);
+
+        let mut dump = history.dump(None);
+        for document in dump["documents"].as_array_mut().unwrap() {
+            for entry in document["entries"].as_array_mut().unwrap() {
+                entry.as_object_mut().unwrap().remove("elapsedMs");
+            }
+        }
+        assert_eq!(
+            dump,
+            serde_json::json!({
+                "limit": HISTORY_LIMIT,
+                "documents": [
+                    {
+                        "uri": "file:///ws/a.agent",
+                        "entries": [
+                            { "seq": 1, "kind": "changed", "version": 2, "text": "v2" },
+                            { "seq": 2, "kind": "changed", "version": 3, "text": "v3" },
+                            { "seq": 3, "kind": "diagnostics", "version": 2, "diagnostics": [] },
+                            { "seq": 4, "kind": "diagnostics", "version": null, "diagnostics": [] },
+                            { "seq": 5, "kind": "closed" },
+                        ],
+                    },
+                    {
+                        "uri": "file:///ws/b.agent",
+                        "entries": [
+                            { "seq": 0, "kind": "opened", "version": 1, "text": "v1" },
+                        ],
+                    },
+                ],
+            })
+        );
+
+        let one = history.dump(Some(&b));
+        assert_eq!(one["documents"].as_array().unwrap().len(), 1);
+        assert_eq!(one["documents"][0]["uri"], "file:///ws/b.agent");
+        assert!(history.dump(Some(&uri("c")))["documents"]
+            .as_array()
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -2,23 +2,56 @@ use std::collections::HashMap;
 use std::sync::Arc;
 
 use busbar_sf_agentscript::ast::*;
+use busbar_sf_agentscript::cache::{AnalysisCache, MapSpans, SpanMap};
+use busbar_sf_agentscript::describe::{
+    description_edit, missing_descriptions, DescriptionProvider, TemplateDescriptions,
+};
+use busbar_sf_agentscript::edit::{EditSet, SourceEdit};
 use busbar_sf_agentscript::error::ParseErrorInfo;
+use busbar_sf_agentscript::export::salesforce::to_salesforce_metadata;
+use busbar_sf_agentscript::formatter;
 use busbar_sf_agentscript::graph::dependencies::{extract_dependencies, DependencyReport};
-use busbar_sf_agentscript::graph::{GraphRepr, RefGraphBuilder};
+use busbar_sf_agentscript::graph::health::{health_report, HealthOptions};
+use busbar_sf_agentscript::graph::{
+    GraphRepr, NodeIndex, OccurrenceKind, PassId, RefGraph, RefGraphBuilder, RefNode, RenameError,
+    ValidationError,
+};
+use busbar_sf_agentscript::lexer::{keyword_table, KeywordCategory};
+use busbar_sf_agentscript::lint::{find_config_file, run_lints_for, LintConfig, LintRegistry};
+use busbar_sf_agentscript::markdown::{render_instructions, render_markdown};
+use busbar_sf_agentscript::parser::{parse_async, parse_with_profile, CancellationToken};
+use busbar_sf_agentscript::simulation::{self, MockData};
+use busbar_sf_agentscript::text_pos::{self, Encoding};
+use busbar_sf_agentscript::validation::{validate_ast_for, TargetEnvironment};
 use tokio::sync::RwLock;
 use tower_lsp::jsonrpc::Result;
 use tower_lsp::lsp_types::*;
 use tower_lsp::{Client, LanguageServer, LspService, Server};
 
+mod buffer;
+mod daemon;
+mod descriptions;
+mod history;
 mod semantic_tokens;
+mod symbols;
+mod workspace;
 
+use buffer::TextBuffer;
+use descriptions::SharedProvider;
+use history::{DocumentHistory, HistoryEvent};
 use semantic_tokens::LEGEND;
+use workspace::{DefKind, WorkspaceIndex};
 
 // =============================================================================
 // Document State
 // =============================================================================
 
-/// Cached parse state for a single document.
+/// Immutable snapshot of a document: its text, AST and reference graph.
+///
+/// Snapshots are shared as `Arc<DocumentState>`. Handlers clone the `Arc`
+/// and release the document map straight away, and an edit replaces the
+/// snapshot rather than mutating it, so a slow analysis of one version never
+/// holds up requests against the next.
 struct DocumentState {
     source: String,
     ast: Option<AgentFile>,
@@ -30,7 +63,24 @@ impl DocumentState {
     fn new(source: String) -> Self {
         let (ast, parse_errors) =
             busbar_sf_agentscript::parser::parse_with_structured_errors_all(&source);
+        Self::from_parse(source, ast, parse_errors)
+    }
+
+    /// Parse without blocking the runtime, or `None` if `token` is
+    /// cancelled first. The graph is built on a blocking thread.
+    async fn parse(source: String, token: CancellationToken) -> Option<Self> {
+        let (ast, parse_errors) = parse_async(&source, token.clone()).await.ok()?;
+        let doc = tokio::task::spawn_blocking(move || Self::from_parse(source, ast, parse_errors))
+            .await
+            .ok()?;
+        (!token.is_cancelled()).then_some(doc)
+    }
 
+    fn from_parse(
+        source: String,
+        ast: Option<AgentFile>,
+        parse_errors: Vec<ParseErrorInfo>,
+    ) -> Self {
         let graph = ast
             .as_ref()
             .and_then(|a| RefGraphBuilder::new().build(a).ok());
@@ -48,9 +98,39 @@ impl DocumentState {
 // Backend
 // =============================================================================
 
+/// How long typing must pause before an edited document is re-indexed and
+/// its diagnostics are recomputed.
+const DIAGNOSTICS_DEBOUNCE: std::time::Duration = std::time::Duration::from_millis(250);
+
+/// How long typing must pause, after the debounced diagnostics, before the
+/// whole-graph validation passes run again.
+const FULL_VALIDATION_DELAY: std::time::Duration = std::time::Duration::from_millis(1000);
+
+#[derive(Clone)]
 struct Backend {
     client: Client,
-    documents: Arc<RwLock<HashMap<Url, DocumentState>>>,
+    documents: Arc<RwLock<HashMap<Url, Arc<DocumentState>>>>,
+    workspace: Arc<RwLock<WorkspaceIndex>>,
+    /// Deployment target from the `targetEnvironment` initialization option,
+    /// overriding each agent's `target_environment:` config.
+    target: Arc<RwLock<Option<TargetEnvironment>>>,
+    /// Lint configuration from the workspace's `.agentscriptlint.toml`.
+    lint: Arc<RwLock<LintConfig>>,
+    /// Cancellation for the in-flight parse of each changed document.
+    pending_parses: Arc<std::sync::Mutex<HashMap<Url, CancellationToken>>>,
+    /// Text of each open document, updated by incremental changes.
+    buffers: Arc<std::sync::Mutex<HashMap<Url, TextBuffer>>>,
+    /// Cancellation for the background diagnostics task of each document.
+    pending_diagnostics: Arc<std::sync::Mutex<HashMap<Url, CancellationToken>>>,
+    /// Recent versions and published diagnostics, for `agentscript/debugHistory`.
+    history: Arc<std::sync::Mutex<DocumentHistory>>,
+    /// Diagnostics by semantic hash, shared by every document.
+    analysis: Arc<DiagnosticsCache>,
+    /// Whole-graph findings of each document's last full validation.
+    expensive: Arc<ExpensiveFindings>,
+    /// Text for the "Add description" quick fix, from the
+    /// `descriptionCommand` initialization option.
+    descriptions: Arc<RwLock<SharedProvider>>,
 }
 
 impl std::fmt::Debug for Backend {
@@ -60,10 +140,20 @@ impl std::fmt::Debug for Backend {
 }
 
 impl Backend {
-    fn new(client: Client) -> Self {
+    fn new(client: Client, analysis: Arc<DiagnosticsCache>) -> Self {
         Self {
             client,
             documents: Arc::new(RwLock::new(HashMap::new())),
+            workspace: Arc::new(RwLock::new(WorkspaceIndex::default())),
+            target: Arc::new(RwLock::new(None)),
+            lint: Arc::new(RwLock::new(LintConfig::default())),
+            pending_parses: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            buffers: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            pending_diagnostics: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            history: Arc::new(std::sync::Mutex::new(DocumentHistory::default())),
+            analysis,
+            expensive: Arc::new(ExpensiveFindings::default()),
+            descriptions: Arc::new(RwLock::new(Arc::new(TemplateDescriptions))),
         }
     }
 
@@ -71,73 +161,530 @@ impl Backend {
     // Diagnostics
     // -------------------------------------------------------------------------
 
-    async fn publish_diagnostics(&self, uri: &Url) {
-        let docs = self.documents.read().await;
-        let Some(doc) = docs.get(uri) else { return };
-
-        let mut diagnostics = Vec::new();
+    /// The current snapshot of an open document.
+    async fn snapshot(&self, uri: &Url) -> Option<Arc<DocumentState>> {
+        self.documents.read().await.get(uri).cloned()
+    }
 
-        // Parse errors
-        for err in &doc.parse_errors {
-            diagnostics.push(parse_error_to_diagnostic(&doc.source, err));
+    /// Publish diagnostics for a document, running only the given graph passes.
+    ///
+    /// Without the whole-graph passes, their findings from the last full
+    /// validation are published again, moved past the edits made since.
+    ///
+    /// The diagnostics are computed on a blocking thread in a background
+    /// task. A later call for the same document cancels this one, so results
+    /// for a stale snapshot are never published.
+    async fn publish_diagnostics(&self, uri: &Url, passes: &[PassId]) {
+        let doc = match self.snapshot(uri).await {
+            Some(doc) => doc,
+            None => match self.workspace.read().await.get(uri) {
+                Some(doc) => doc.clone(),
+                None => return,
+            },
+        };
+        let token = CancellationToken::new();
+        if let Some(stale) = self
+            .pending_diagnostics
+            .lock()
+            .unwrap()
+            .insert(uri.clone(), token.clone())
+        {
+            stale.cancel();
         }
 
-        // Semantic validation from the AST
-        if let Some(ast) = &doc.ast {
-            let semantic_errors = busbar_sf_agentscript::validate_ast(ast);
-            for err in &semantic_errors {
-                if let Some(span) = &err.span {
-                    diagnostics.push(Diagnostic {
-                        range: span_to_range(&doc.source, span.clone()),
-                        severity: Some(match err.severity {
-                            busbar_sf_agentscript::validation::Severity::Error => {
-                                DiagnosticSeverity::ERROR
-                            }
-                            busbar_sf_agentscript::validation::Severity::Warning => {
-                                DiagnosticSeverity::WARNING
-                            }
-                        }),
-                        source: Some("agentscript".to_string()),
-                        message: err.message.clone(),
-                        ..Default::default()
-                    });
+        let target = *self.target.read().await;
+        let lint = self.lint.read().await.clone();
+        let passes = passes.to_vec();
+        let client = self.client.clone();
+        let history = self.history.clone();
+        let analysis = self.analysis.clone();
+        let expensive = self.expensive.clone();
+        let uri = uri.clone();
+        tokio::spawn(async move {
+            let computed = tokio::task::spawn_blocking({
+                let (uri, token, doc) = (uri.clone(), token.clone(), doc.clone());
+                move || {
+                    (!token.is_cancelled()).then(|| {
+                        compute_diagnostics(
+                            &uri, &doc, &passes, target, &lint, &analysis, &expensive,
+                        )
+                    })
                 }
+            })
+            .await;
+            let Ok(Some(diagnostics)) = computed else {
+                return;
+            };
+            if !token.is_cancelled() {
+                history
+                    .lock()
+                    .unwrap()
+                    .record_diagnostics(&uri, &doc.source, diagnostics.clone());
+                client.publish_diagnostics(uri, diagnostics, None).await;
+            }
+        });
+    }
+
+    /// Parse a changed document, then re-index it and publish diagnostics
+    /// of the cheap graph passes once typing pauses, and of every pass once
+    /// it has paused for longer.
+    ///
+    /// Runs as a background task per change. A newer change cancels the
+    /// parse through `token` and the wait through `pending_diagnostics`.
+    async fn reparse(self, uri: Url, version: i32, text: String, token: CancellationToken) {
+        let Some(doc) = DocumentState::parse(text, token.clone()).await else {
+            return;
+        };
+        let doc = Arc::new(doc);
+        let mut docs = self.documents.write().await;
+        if token.is_cancelled() {
+            return;
+        }
+        docs.insert(uri.clone(), doc.clone());
+        drop(docs);
+
+        let pending = CancellationToken::new();
+        if let Some(stale) = self
+            .pending_diagnostics
+            .lock()
+            .unwrap()
+            .insert(uri.clone(), pending.clone())
+        {
+            stale.cancel();
+        }
+        tokio::time::sleep(DIAGNOSTICS_DEBOUNCE).await;
+        let current = self
+            .buffers
+            .lock()
+            .unwrap()
+            .get(&uri)
+            .map(TextBuffer::version);
+        if pending.is_cancelled() || current != Some(version) {
+            return;
+        }
+        self.workspace.write().await.insert(&uri, doc);
+        self.publish_diagnostics(&uri, PassId::CHEAP).await;
+
+        // Whole-graph passes wait until typing has stopped for a while
+        tokio::time::sleep(FULL_VALIDATION_DELAY).await;
+        let current = self
+            .buffers
+            .lock()
+            .unwrap()
+            .get(&uri)
+            .map(TextBuffer::version);
+        if current == Some(version) {
+            self.publish_diagnostics(&uri, PassId::ALL).await;
+        }
+    }
+
+    /// Publish diagnostics for every indexed workspace file that is not open.
+    async fn publish_workspace_diagnostics(&self) {
+        let target = *self.target.read().await;
+        let lint = self.lint.read().await.clone();
+        let snapshots: Vec<(Url, Arc<DocumentState>)> = {
+            let docs = self.documents.read().await;
+            let index = self.workspace.read().await;
+            index
+                .iter()
+                .filter(|(uri, _)| !docs.contains_key(*uri))
+                .map(|(uri, doc)| (uri.clone(), doc.clone()))
+                .collect()
+        };
+
+        let client = self.client.clone();
+        let history = self.history.clone();
+        let analysis = self.analysis.clone();
+        let expensive = self.expensive.clone();
+        tokio::spawn(async move {
+            let computed = tokio::task::spawn_blocking(move || {
+                snapshots
+                    .into_iter()
+                    .map(|(uri, doc)| {
+                        let diagnostics = compute_diagnostics(
+                            &uri,
+                            &doc,
+                            PassId::ALL,
+                            target,
+                            &lint,
+                            &analysis,
+                            &expensive,
+                        );
+                        history.lock().unwrap().record_diagnostics(
+                            &uri,
+                            &doc.source,
+                            diagnostics.clone(),
+                        );
+                        (uri, diagnostics)
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .await;
+            for (uri, diagnostics) in computed.into_iter().flatten() {
+                client.publish_diagnostics(uri, diagnostics, None).await;
+            }
+        });
+    }
+}
+
+/// Semantic, graph and lint diagnostics of ASTs seen before, by semantic
+/// hash, so an edit that only moves whitespace or comments reuses them.
+type DiagnosticsCache = std::sync::Mutex<AnalysisCache<Vec<Finding>>>;
+
+/// A diagnostic located by byte spans, before conversion to LSP positions.
+///
+/// Findings are what [`DiagnosticsCache`] keeps: unlike line/column ranges,
+/// their spans can be moved onto a new parse of the same agent.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+struct Finding {
+    span: std::ops::Range<usize>,
+    /// The diagnostic, with a placeholder range and no related information.
+    diagnostic: Diagnostic,
+    related: Vec<(std::ops::Range<usize>, String)>,
+    /// Whether a whole-graph pass found it.
+    #[serde(default)]
+    expensive: bool,
+}
+
+impl Finding {
+    fn new(span: std::ops::Range<usize>, diagnostic: Diagnostic) -> Self {
+        Self {
+            span,
+            diagnostic,
+            related: Vec::new(),
+            expensive: false,
+        }
+    }
+
+    /// This finding in the text after `shift`, or `None` if the edit
+    /// touched one of its spans.
+    fn shifted(&self, shift: &TextShift) -> Option<Self> {
+        let related = self
+            .related
+            .iter()
+            .map(|(span, label)| Some((shift.span(span.clone())?, label.clone())))
+            .collect::<Option<_>>()?;
+        Some(Self {
+            span: shift.span(self.span.clone())?,
+            related,
+            ..self.clone()
+        })
+    }
+
+    fn to_diagnostic(&self, uri: &Url, source: &str) -> Diagnostic {
+        Diagnostic {
+            range: span_to_range(source, self.span.clone()),
+            related_information: (!self.related.is_empty()).then(|| {
+                self.related
+                    .iter()
+                    .map(|(span, label)| DiagnosticRelatedInformation {
+                        location: Location::new(uri.clone(), span_to_range(source, span.clone())),
+                        message: label.clone(),
+                    })
+                    .collect()
+            }),
+            ..self.diagnostic.clone()
+        }
+    }
+}
+
+impl MapSpans for Finding {
+    fn map_spans(self, map: &SpanMap) -> Option<Self> {
+        let related = self
+            .related
+            .into_iter()
+            .map(|(span, label)| Some((span.map_spans(map)?, label)))
+            .collect::<Option<_>>()?;
+        Some(Self {
+            span: self.span.map_spans(map)?,
+            related,
+            ..self
+        })
+    }
+}
+
+/// The difference between two versions of a document, as one replacement
+/// between their common prefix and suffix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct TextShift {
+    prefix: usize,
+    /// End of the replaced text in the old version
+    old_end: usize,
+    /// End of the replacement in the new version
+    new_end: usize,
+}
+
+impl TextShift {
+    fn between(old: &str, new: &str) -> Self {
+        let prefix = old
+            .bytes()
+            .zip(new.bytes())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let suffix = old
+            .bytes()
+            .rev()
+            .zip(new.bytes().rev())
+            .take(old.len().min(new.len()) - prefix)
+            .take_while(|(a, b)| a == b)
+            .count();
+        Self {
+            prefix,
+            old_end: old.len() - suffix,
+            new_end: new.len() - suffix,
+        }
+    }
+
+    /// Where `span` of the old version is in the new one, unless it overlaps
+    /// the replaced text.
+    fn span(&self, span: std::ops::Range<usize>) -> Option<std::ops::Range<usize>> {
+        if span.end <= self.prefix {
+            Some(span)
+        } else if span.start >= self.old_end {
+            Some(span.start - self.old_end + self.new_end..span.end - self.old_end + self.new_end)
+        } else {
+            None
+        }
+    }
+}
+
+/// Findings of the whole-graph passes from each document's last full
+/// validation, with the text their spans point into.
+type ExpensiveFindings = std::sync::Mutex<HashMap<Url, (String, Vec<Finding>)>>;
+
+/// Compute parse, semantic, and graph diagnostics for a document.
+///
+/// Only the given graph validation passes are run. Edits run
+/// [`PassId::CHEAP`] and everything else [`PassId::ALL`]; the findings of
+/// the whole-graph passes are kept in `expensive` by a full run and reused,
+/// moved past later edits, by a cheap one. Semantic diagnostics are
+/// escalated according to `target`, or else the agent's configured target
+/// environment, and lint rules run at the levels set in the lint
+/// configuration. Documents without parse errors take their findings from
+/// `cache` when it has them.
+fn compute_diagnostics(
+    uri: &Url,
+    doc: &DocumentState,
+    passes: &[PassId],
+    target: Option<TargetEnvironment>,
+    lint: &LintConfig,
+    cache: &DiagnosticsCache,
+    expensive: &ExpensiveFindings,
+) -> Vec<Diagnostic> {
+    let target = target
+        .or_else(|| doc.ast.as_ref().and_then(TargetEnvironment::from_config))
+        .unwrap_or_default();
+    // Parse errors
+    let mut diagnostics: Vec<Diagnostic> = doc
+        .parse_errors
+        .iter()
+        .map(|err| parse_error_to_diagnostic(&doc.source, err))
+        .collect();
+
+    let mut findings = match &doc.ast {
+        Some(ast) if doc.parse_errors.is_empty() => {
+            let key = format!("{:?} {} {:?}", passes, target, lint);
+            let cached = cache.lock().unwrap().get(ast, &key);
+            cached.unwrap_or_else(|| {
+                let findings = analyze(doc, passes, target, lint);
+                cache.lock().unwrap().insert(ast, &key, findings.clone());
+                findings
+            })
+        }
+        _ => analyze(doc, passes, target, lint),
+    };
+    if passes.iter().any(PassId::is_expensive) {
+        let found = findings.iter().filter(|f| f.expensive).cloned().collect();
+        expensive
+            .lock()
+            .unwrap()
+            .insert(uri.clone(), (doc.source.clone(), found));
+    } else if doc.parse_errors.is_empty() {
+        if let Some((source, previous)) = expensive.lock().unwrap().get(uri) {
+            let shift = TextShift::between(source, &doc.source);
+            findings.extend(previous.iter().filter_map(|f| f.shifted(&shift)));
+        }
+    }
+    diagnostics.extend(findings.iter().map(|f| f.to_diagnostic(uri, &doc.source)));
+
+    sort_diagnostics(&mut diagnostics);
+    diagnostics
+}
+
+/// The semantic, graph and lint findings of a document.
+fn analyze(
+    doc: &DocumentState,
+    passes: &[PassId],
+    target: TargetEnvironment,
+    lint: &LintConfig,
+) -> Vec<Finding> {
+    let mut findings = Vec::new();
+
+    // Semantic validation from the AST
+    if let Some(ast) = &doc.ast {
+        let semantic_errors = validate_ast_for(ast, target);
+        for err in semantic_errors {
+            if let Some(span) = err.span {
+                let diagnostic = Diagnostic {
+                    severity: Some(match err.severity {
+                        busbar_sf_agentscript::validation::Severity::Error => {
+                            DiagnosticSeverity::ERROR
+                        }
+                        busbar_sf_agentscript::validation::Severity::Warning => {
+                            DiagnosticSeverity::WARNING
+                        }
+                    }),
+                    code: err.code.map(|c| NumberOrString::String(c.to_string())),
+                    source: Some("agentscript".to_string()),
+                    message: err.message,
+                    ..Default::default()
+                };
+                findings.push(Finding {
+                    related: err.related,
+                    ..Finding::new(span, diagnostic)
+                });
             }
         }
+    }
 
-        // Graph validation (unresolved refs, cycles, unreachable topics, unused symbols)
-        if doc.parse_errors.is_empty() {
-            if let Some(graph) = &doc.graph {
-                let validation = graph.validate();
+    // Graph validation (unresolved refs, cycles, unreachable topics, unused symbols)
+    if doc.parse_errors.is_empty() {
+        if let Some(graph) = &doc.graph {
+            // Whole-graph passes separately, so their findings can be kept
+            for expensive in [false, true] {
+                let selected: Vec<PassId> = passes
+                    .iter()
+                    .copied()
+                    .filter(|pass| pass.is_expensive() == expensive)
+                    .collect();
+                let validation = graph.validate_selected(&selected);
+                let start = findings.len();
                 for error in &validation.errors {
+                    // Flag every repeat, pointing back at the definition in use
+                    if let ValidationError::DuplicateDefinition { spans, .. } = error {
+                        let first = spans[0].0..spans[0].1;
+                        for span in &spans[1..] {
+                            findings.push(Finding {
+                                related: vec![(first.clone(), "First defined here".to_string())],
+                                ..Finding::new(
+                                    span.0..span.1,
+                                    Diagnostic {
+                                        severity: Some(DiagnosticSeverity::ERROR),
+                                        source: Some("agentscript".to_string()),
+                                        message: error.message(),
+                                        ..Default::default()
+                                    },
+                                )
+                            });
+                        }
+                        continue;
+                    }
                     if let Some(span) = error.span() {
-                        diagnostics.push(Diagnostic {
-                            range: span_to_range(&doc.source, span.0..span.1),
-                            severity: Some(DiagnosticSeverity::ERROR),
-                            source: Some("agentscript".to_string()),
-                            message: error.message(),
-                            ..Default::default()
-                        });
+                        findings.push(Finding::new(
+                            span.0..span.1,
+                            Diagnostic {
+                                severity: Some(DiagnosticSeverity::ERROR),
+                                source: Some("agentscript".to_string()),
+                                message: error.message(),
+                                ..Default::default()
+                            },
+                        ));
                     }
                 }
                 for warning in &validation.warnings {
                     if let Some(span) = warning.span() {
-                        diagnostics.push(Diagnostic {
-                            range: span_to_range(&doc.source, span.0..span.1),
-                            severity: Some(DiagnosticSeverity::WARNING),
-                            source: Some("agentscript".to_string()),
-                            message: warning.message(),
-                            ..Default::default()
+                        findings.push(Finding::new(
+                            span.0..span.1,
+                            Diagnostic {
+                                severity: Some(DiagnosticSeverity::WARNING),
+                                source: Some("agentscript".to_string()),
+                                message: warning.message(),
+                                tags: warning
+                                    .is_unused()
+                                    .then(|| vec![DiagnosticTag::UNNECESSARY]),
+                                ..Default::default()
+                            },
+                        ));
+                    }
+                }
+                for finding in &mut findings[start..] {
+                    finding.expensive = expensive;
+                }
+            }
+
+            // Dim actions only invoked from dead code. Never-invoked actions
+            // already have an unused warning above.
+            if passes.contains(&PassId::UnreachableTopics) {
+                let unused = graph.find_unused_actions();
+                for error in graph.find_unreachable_actions() {
+                    if unused.iter().any(|u| u.span() == error.span()) {
+                        continue;
+                    }
+                    if let Some(span) = error.span() {
+                        findings.push(Finding {
+                            expensive: true,
+                            ..Finding::new(
+                                span.0..span.1,
+                                Diagnostic {
+                                    severity: Some(DiagnosticSeverity::HINT),
+                                    source: Some("agentscript".to_string()),
+                                    message: error.message(),
+                                    tags: Some(vec![DiagnosticTag::UNNECESSARY]),
+                                    ..Default::default()
+                                },
+                            )
                         });
                     }
                 }
             }
+
+            // Lint rules
+            if let Some(ast) = &doc.ast {
+                for finding in run_lints_for(ast, graph, lint, target) {
+                    let Some(span) = finding.span else { continue };
+                    findings.push(Finding::new(
+                        span,
+                        Diagnostic {
+                            severity: Some(match finding.severity {
+                                busbar_sf_agentscript::validation::Severity::Error => {
+                                    DiagnosticSeverity::ERROR
+                                }
+                                busbar_sf_agentscript::validation::Severity::Warning => {
+                                    DiagnosticSeverity::WARNING
+                                }
+                            }),
+                            code: Some(NumberOrString::String(finding.rule.to_string())),
+                            source: Some("agentscript-lint".to_string()),
+                            message: finding.message,
+                            ..Default::default()
+                        },
+                    ));
+                }
+            }
         }
+    }
 
-        self.client
-            .publish_diagnostics(uri.clone(), diagnostics, None)
-            .await;
+    findings
+}
+
+/// Sort diagnostics by range, then by code and message, so republishing an
+/// unchanged document doesn't reorder the client's problem list.
+fn sort_diagnostics(diagnostics: &mut [Diagnostic]) {
+    fn code(d: &Diagnostic) -> Option<String> {
+        d.code.as_ref().map(|code| match code {
+            NumberOrString::Number(n) => n.to_string(),
+            NumberOrString::String(s) => s.clone(),
+        })
     }
+    diagnostics.sort_by(|a, b| {
+        let pos = |p: Position| (p.line, p.character);
+        (pos(a.range.start), pos(a.range.end), code(a), &a.message).cmp(&(
+            pos(b.range.start),
+            pos(b.range.end),
+            code(b),
+            &b.message,
+        ))
+    });
 }
 
 fn parse_error_to_diagnostic(text: &str, err: &ParseErrorInfo) -> Diagnostic {
@@ -218,14 +765,26 @@ fn get_completions(doc: &DocumentState, position: Position) -> Vec<CompletionIte
                                             )),
                                             documentation: v
                                                 .node
-                                                .description
+                                                .doc
                                                 .as_ref()
+                                                .or(v.node.description.as_ref())
                                                 .map(|d| Documentation::String(d.node.clone())),
                                             ..Default::default()
                                         });
                                     }
                                 }
                             }
+
+                            // Offer to declare an unknown variable on accept
+                            if let Some(edit) = variable_declaration_edit(doc, ast, partial) {
+                                items.push(CompletionItem {
+                                    label: partial.to_string(),
+                                    kind: Some(CompletionItemKind::VARIABLE),
+                                    detail: Some("Declare new mutable string variable".to_string()),
+                                    additional_text_edits: Some(vec![edit]),
+                                    ..Default::default()
+                                });
+                            }
                         }
                         "topic" => {
                             for t in &ast.topics {
@@ -235,6 +794,11 @@ fn get_completions(doc: &DocumentState, position: Position) -> Vec<CompletionIte
                                         label: name.clone(),
                                         kind: Some(CompletionItemKind::CLASS),
                                         detail: t.node.description.as_ref().map(|d| d.node.clone()),
+                                        documentation: t
+                                            .node
+                                            .doc
+                                            .as_ref()
+                                            .map(|d| Documentation::String(d.node.clone())),
                                         ..Default::default()
                                     });
                                 }
@@ -253,6 +817,11 @@ fn get_completions(doc: &DocumentState, position: Position) -> Vec<CompletionIte
                                             .description
                                             .as_ref()
                                             .map(|d| d.node.clone()),
+                                        documentation: action
+                                            .node
+                                            .doc
+                                            .as_ref()
+                                            .map(|d| Documentation::String(d.node.clone())),
                                         ..Default::default()
                                     });
                                 }
@@ -403,20 +972,10 @@ fn get_completions(doc: &DocumentState, position: Position) -> Vec<CompletionIte
     // Type completions after "mutable" or "linked"
     let trimmed = line.trim();
     if trimmed.ends_with("mutable ") || trimmed.ends_with("linked ") {
-        let types = [
-            "string",
-            "number",
-            "boolean",
-            "date",
-            "object",
-            "timestamp",
-            "currency",
-            "id",
-            "datetime",
-            "time",
-            "integer",
-            "long",
-        ];
+        let types = keyword_table()
+            .iter()
+            .filter(|k| k.category == KeywordCategory::Type)
+            .map(|k| k.text);
         for ty in types {
             items.push(CompletionItem {
                 label: ty.to_string(),
@@ -447,6 +1006,100 @@ fn get_completions(doc: &DocumentState, position: Position) -> Vec<CompletionIte
     items
 }
 
+/// Build an edit that declares `name` in the variables block.
+///
+/// Returns `None` if `name` is not a valid identifier or begins a declared
+/// name, which completion offers instead. An empty variables block gets the
+/// declaration under its header. When the file has no variables block, one
+/// is inserted before the first `start_agent` or `topic` block and its doc
+/// comment (or appended at the end of the file).
+fn variable_declaration_edit(doc: &DocumentState, ast: &AgentFile, name: &str) -> Option<TextEdit> {
+    let valid = name
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid {
+        return None;
+    }
+
+    let source = &doc.source;
+    let declaration = |indent: &str| {
+        format!(
+            "{indent}{name}: mutable string = \"\"\n{indent}   description: \"TODO: describe {name}\"\n"
+        )
+    };
+
+    let decls = ast.variables.iter().flat_map(|v| &v.node.variables);
+    if decls.clone().any(|v| v.node.name.node.starts_with(name)) {
+        return None;
+    }
+    // An empty block doesn't parse, so look for its header in the source
+    let header = ast
+        .variables
+        .as_ref()
+        .map(|v| v.span.start)
+        .or_else(|| variables_header(source));
+
+    let (offset, new_text) = match (decls.last(), header) {
+        (Some(last), _) => {
+            let line_start = source[..last.node.name.span.start]
+                .rfind('\n')
+                .map(|i| i + 1)
+                .unwrap_or(0);
+            let indent = &source[line_start..last.node.name.span.start];
+            // Insert after the line that ends the last declaration
+            let content_end = source[..last.span.end].trim_end().len();
+            match source[content_end..].find('\n') {
+                Some(i) => (content_end + i + 1, declaration(indent)),
+                None => (source.len(), format!("\n{}", declaration(indent))),
+            }
+        }
+        // Declare right under the header of an empty block
+        (None, Some(header)) => match line_end(source, header) {
+            end if source[..end].ends_with('\n') => (end, declaration("   ")),
+            end => (end, format!("\n{}", declaration("   "))),
+        },
+        (None, None) => {
+            let block = format!("variables:\n{}\n", declaration("   "));
+            // Above any doc comment, which must stay next to its block
+            let first_block = ast
+                .start_agent
+                .as_ref()
+                .map(|s| s.node.doc.as_ref().map_or(s.span.start, |d| d.span.start))
+                .into_iter()
+                .chain(
+                    ast.topics
+                        .iter()
+                        .map(|t| t.node.doc.as_ref().map_or(t.span.start, |d| d.span.start)),
+                )
+                .min();
+            match first_block {
+                Some(start) => {
+                    let line_start = source[..start].rfind('\n').map(|i| i + 1).unwrap_or(0);
+                    (line_start, block)
+                }
+                None if source.ends_with('\n') => (source.len(), format!("\n{}", block)),
+                None => (source.len(), format!("\n\n{}", block)),
+            }
+        }
+    };
+
+    Some(to_text_edit(source, SourceEdit::insert(offset, new_text)))
+}
+
+/// Offset of a top-level `variables:` header line.
+fn variables_header(source: &str) -> Option<usize> {
+    let mut offset = 0;
+    for line in source.split_inclusive('\n') {
+        if line.trim_end() == "variables:" {
+            return Some(offset);
+        }
+        offset += line.len();
+    }
+    None
+}
+
 // =============================================================================
 // Hover
 // =============================================================================
@@ -464,14 +1117,18 @@ fn get_hover(doc: &DocumentState, position: Position) -> Option<Hover> {
                         .node
                         .description
                         .as_ref()
-                        .map(|d| format!("\n\n{}", d.node))
+                        .map(|d| format!("\n\n{}", render_markdown(&d.node)))
                         .unwrap_or_default();
                     return Some(Hover {
                         contents: HoverContents::Markup(MarkupContent {
                             kind: MarkupKind::Markdown,
                             value: format!(
-                                "**Variable** `{}`\n\n**Kind:** {:?}  \n**Type:** `{:?}`{}",
-                                var.node.name.node, var.node.kind, var.node.ty.node, desc
+                                "**Variable** `{}`\n\n**Kind:** {:?}  \n**Type:** `{:?}`{}{}",
+                                var.node.name.node,
+                                var.node.kind,
+                                var.node.ty.node,
+                                desc,
+                                doc_comment_markdown(&var.node.doc)
                             ),
                         }),
                         range: Some(span_to_range(&doc.source, var.node.name.span.clone())),
@@ -494,7 +1151,7 @@ fn get_hover(doc: &DocumentState, position: Position) -> Option<Hover> {
                             .node
                             .description
                             .as_ref()
-                            .map(|d| format!("\n\n{}", d.node))
+                            .map(|d| format!("\n\n{}", render_markdown(&d.node)))
                             .unwrap_or_default()
                     ),
                 }),
@@ -511,13 +1168,14 @@ fn get_hover(doc: &DocumentState, position: Position) -> Option<Hover> {
                     contents: HoverContents::Markup(MarkupContent {
                         kind: MarkupKind::Markdown,
                         value: format!(
-                            "**Start Agent** `{}`{}",
+                            "**Start Agent** `{}`{}{}",
                             sa.node.name.node,
                             sa.node
                                 .description
                                 .as_ref()
-                                .map(|d| format!("\n\n{}", d.node))
-                                .unwrap_or_default()
+                                .map(|d| format!("\n\n{}", render_markdown(&d.node)))
+                                .unwrap_or_default(),
+                            doc_comment_markdown(&sa.node.doc)
                         ),
                     }),
                     range: Some(span_to_range(&doc.source, sa.node.name.span.clone())),
@@ -540,12 +1198,17 @@ fn get_hover(doc: &DocumentState, position: Position) -> Option<Hover> {
                     .node
                     .description
                     .as_ref()
-                    .map(|d| format!("\n\n{}", d.node))
+                    .map(|d| format!("\n\n{}", render_markdown(&d.node)))
                     .unwrap_or_default();
                 return Some(Hover {
                     contents: HoverContents::Markup(MarkupContent {
                         kind: MarkupKind::Markdown,
-                        value: format!("**Topic** `{}`{}", topic.node.name.node, desc),
+                        value: format!(
+                            "**Topic** `{}`{}{}",
+                            topic.node.name.node,
+                            desc,
+                            doc_comment_markdown(&topic.node.doc)
+                        ),
                     }),
                     range: Some(span_to_range(&doc.source, topic.node.name.span.clone())),
                 });
@@ -563,6 +1226,120 @@ fn get_hover(doc: &DocumentState, position: Position) -> Option<Hover> {
     hover_reference_at_offset(ast, &doc.source, offset)
 }
 
+// =============================================================================
+// Signature Help
+// =============================================================================
+
+/// Signature help on a `with` line: the inputs of the `@actions.*` target
+/// the clause belongs to.
+fn get_signature_help(doc: &DocumentState, position: Position) -> Option<SignatureHelp> {
+    let ast = doc.ast.as_ref()?;
+    let offset = position_to_offset(&doc.source, position);
+    let before = &doc.source[..offset];
+    let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let line = &before[line_start..];
+    let indent = line.len() - line.trim_start().len();
+    let typed = line.trim_start().strip_prefix("with")?;
+    if !typed.starts_with(' ') {
+        return None;
+    }
+    let typed = typed.trim_start();
+    let typed = typed.split(['=', ' ']).next().unwrap_or_default();
+
+    // The owning reasoning action or `run` is the nearest less-indented line.
+    // Sibling `with` lines between it and the cursor are already bound.
+    let mut bound = Vec::new();
+    let mut owner = None;
+    for l in before[..line_start].lines().rev() {
+        let l_indent = l.len() - l.trim_start().len();
+        if l.trim().is_empty() {
+            continue;
+        }
+        if l_indent < indent {
+            owner = Some(l);
+            break;
+        }
+        if let Some(param) = l.trim_start().strip_prefix("with ") {
+            bound.push(param.split(['=', ' ']).next().unwrap_or_default());
+        }
+    }
+    let action_name: String = owner?
+        .split("@actions.")
+        .nth(1)?
+        .chars()
+        .take_while(|c| c.is_alphanumeric() || *c == '_')
+        .collect();
+
+    let action = find_actions_at_offset(ast, offset)
+        .into_iter()
+        .chain(collect_all_action_defs(ast))
+        .find(|a| a.node.name.node == action_name)?;
+    let inputs: Vec<&ParamDef> = action
+        .node
+        .inputs
+        .as_ref()
+        .map(|i| i.node.iter().map(|p| &p.node).collect())
+        .unwrap_or_default();
+
+    let mut label = format!("{}(", action_name);
+    let mut parameters = Vec::new();
+    for (i, input) in inputs.iter().enumerate() {
+        if i > 0 {
+            label.push_str(", ");
+        }
+        let start = label.encode_utf16().count() as u32;
+        label.push_str(&format!("{}: {:?}", input.name.node, input.ty.node));
+        let end = label.encode_utf16().count() as u32;
+
+        let mut md = String::new();
+        if input.is_required.as_ref().is_some_and(|r| r.node) {
+            md.push_str("**Required**");
+        }
+        if let Some(desc) = &input.description {
+            if !md.is_empty() {
+                md.push_str(" — ");
+            }
+            md.push_str(&render_markdown(&desc.node));
+        }
+        parameters.push(ParameterInformation {
+            label: ParameterLabel::LabelOffsets([start, end]),
+            documentation: (!md.is_empty()).then_some(Documentation::MarkupContent(
+                MarkupContent {
+                    kind: MarkupKind::Markdown,
+                    value: md,
+                },
+            )),
+        });
+    }
+    label.push(')');
+
+    // The parameter being typed, else the first one not yet bound
+    let active = inputs
+        .iter()
+        .position(|p| !typed.is_empty() && p.name.node.starts_with(typed))
+        .or_else(|| {
+            inputs
+                .iter()
+                .position(|p| !bound.contains(&p.name.node.as_str()))
+        });
+
+    Some(SignatureHelp {
+        signatures: vec![SignatureInformation {
+            label,
+            documentation: action.node.description.as_ref().map(|d| {
+                Documentation::MarkupContent(MarkupContent {
+                    kind: MarkupKind::Markdown,
+                    value: render_markdown(&d.node),
+                })
+            }),
+            parameters: Some(parameters),
+            active_parameter: active.map(|i| i as u32),
+        }],
+        active_signature: Some(0),
+        active_parameter: active.map(|i| i as u32),
+    })
+}
+
 // =============================================================================
 // Go-to-Definition
 // =============================================================================
@@ -610,35 +1387,35 @@ fn get_definition(doc: &DocumentState, position: Position) -> Option<Range> {
 // Find References
 // =============================================================================
 
-fn get_references(doc: &DocumentState, position: Position) -> Vec<Range> {
-    let ast = match &doc.ast {
-        Some(a) => a,
-        None => return Vec::new(),
+fn get_references(
+    doc: &DocumentState,
+    position: Position,
+    include_declaration: bool,
+) -> Vec<Range> {
+    let Some(graph) = &doc.graph else {
+        return Vec::new();
     };
     let offset = position_to_offset(&doc.source, position);
-    let symbol_name = match find_symbol_name_at_offset(ast, &doc.source, offset) {
-        Some(s) => s,
-        None => return Vec::new(),
-    };
-
-    let mut ranges = Vec::new();
-    let patterns = [
-        format!("@variables.{}", symbol_name),
-        format!("@actions.{}", symbol_name),
-        format!("@topic.{}", symbol_name),
-        format!("@outputs.{}", symbol_name),
-    ];
-
-    for pattern in &patterns {
-        let mut search_start = 0;
-        while let Some(idx) = doc.source[search_start..].find(pattern.as_str()) {
-            let abs_idx = search_start + idx;
-            ranges.push(span_to_range(&doc.source, abs_idx..abs_idx + pattern.len()));
-            search_start = abs_idx + pattern.len();
-        }
+    match graph.symbol_at(offset) {
+        Some(node) => symbol_ranges(doc, graph, node, include_declaration),
+        None => Vec::new(),
     }
+}
 
-    ranges
+/// Ranges of the references to `node`, and of its definition if
+/// `include_declaration` is set.
+fn symbol_ranges(
+    doc: &DocumentState,
+    graph: &RefGraph,
+    node: NodeIndex,
+    include_declaration: bool,
+) -> Vec<Range> {
+    graph
+        .occurrences_with_kind(node)
+        .into_iter()
+        .filter(|(_, kind)| include_declaration || *kind != OccurrenceKind::Definition)
+        .map(|((start, end), _)| span_to_range(&doc.source, start..end))
+        .collect()
 }
 
 // =============================================================================
@@ -649,108 +1426,225 @@ fn get_rename_edits(
     doc: &DocumentState,
     position: Position,
     new_name: &str,
-) -> Option<Vec<TextEdit>> {
-    let ast = doc.ast.as_ref()?;
+) -> std::result::Result<Option<Vec<TextEdit>>, RenameError> {
+    let Some(graph) = &doc.graph else {
+        return Ok(None);
+    };
     let offset = position_to_offset(&doc.source, position);
-    let symbol_name = find_symbol_name_at_offset(ast, &doc.source, offset)?;
-
-    let mut edits: Vec<TextEdit> = Vec::new();
+    let Some(mut node) = graph.symbol_at(offset) else {
+        return Ok(None);
+    };
 
-    // Rename definitions
-    if let Some(vars) = &ast.variables {
-        for v in &vars.node.variables {
-            if v.node.name.node == symbol_name {
-                edits.push(TextEdit {
-                    range: span_to_range(&doc.source, v.node.name.span.clone()),
-                    new_text: new_name.to_string(),
-                });
-            }
-        }
-    }
-    for t in &ast.topics {
-        if t.node.name.node == symbol_name {
-            edits.push(TextEdit {
-                range: span_to_range(&doc.source, t.node.name.span.clone()),
-                new_text: new_name.to_string(),
-            });
-        }
-        if let Some(actions) = &t.node.actions {
-            for a in &actions.node.actions {
-                if a.node.name.node == symbol_name {
-                    edits.push(TextEdit {
-                        range: span_to_range(&doc.source, a.node.name.span.clone()),
-                        new_text: new_name.to_string(),
-                    });
-                }
-            }
-        }
-    }
-    if let Some(sa) = &ast.start_agent {
-        if let Some(actions) = &sa.node.actions {
-            for a in &actions.node.actions {
-                if a.node.name.node == symbol_name {
-                    edits.push(TextEdit {
-                        range: span_to_range(&doc.source, a.node.name.span.clone()),
-                        new_text: new_name.to_string(),
-                    });
-                }
-            }
-        }
+    // A `topic::name` from the prepare-rename placeholder picks the topic
+    // whose symbol to rename
+    let mut new_name = new_name;
+    if let Some((topic, name)) = new_name.split_once("::") {
+        node = graph
+            .counterpart_in(node, topic)
+            .ok_or_else(|| RenameError::UnknownSymbol(topic.to_string()))?;
+        new_name = name;
     }
 
-    // Rename all @references
-    let ref_patterns = [
-        (format!("@variables.{}", symbol_name), format!("@variables.{}", new_name)),
-        (format!("@actions.{}", symbol_name), format!("@actions.{}", new_name)),
-        (format!("@topic.{}", symbol_name), format!("@topic.{}", new_name)),
-        (format!("@outputs.{}", symbol_name), format!("@outputs.{}", new_name)),
-    ];
-
-    for (pattern, new_pattern) in &ref_patterns {
-        let mut search_start = 0;
-        while let Some(idx) = doc.source[search_start..].find(pattern.as_str()) {
-            let abs_idx = search_start + idx;
-            edits.push(TextEdit {
-                range: span_to_range(&doc.source, abs_idx..abs_idx + pattern.len()),
-                new_text: new_pattern.clone(),
-            });
-            search_start = abs_idx + pattern.len();
-        }
-    }
+    let edits: Vec<TextEdit> = graph
+        .rename_symbol(node, new_name)?
+        .into_iter()
+        .map(|edit| to_text_edit(&doc.source, edit))
+        .collect();
+    Ok((!edits.is_empty()).then_some(edits))
+}
 
-    if edits.is_empty() {
-        None
+/// The range of the symbol name at `position`, with its `topic::name`
+/// qualified placeholder when other topics define the same name, so the
+/// rename can say which one it means.
+fn get_prepare_rename(doc: &DocumentState, position: Position) -> Option<PrepareRenameResponse> {
+    let graph = doc.graph.as_ref()?;
+    let offset = position_to_offset(&doc.source, position);
+    let node = graph.symbol_at(offset)?;
+    let (start, end) = graph
+        .occurrences_of(node)
+        .into_iter()
+        .find(|&(start, end)| start <= offset && offset <= end)?;
+    let range = span_to_range(&doc.source, start..end);
+    if graph.is_ambiguous(node) {
+        Some(PrepareRenameResponse::RangeWithPlaceholder {
+            range,
+            placeholder: graph.qualified_name(node)?,
+        })
     } else {
-        Some(edits)
+        Some(PrepareRenameResponse::Range(range))
     }
 }
 
 // =============================================================================
-// Document Symbols
+// Document Highlight
 // =============================================================================
 
-fn get_document_symbols(doc: &DocumentState) -> Vec<DocumentSymbol> {
-    let ast = match &doc.ast {
-        Some(a) => a,
-        None => return Vec::new(),
+fn get_document_highlights(doc: &DocumentState, position: Position) -> Vec<DocumentHighlight> {
+    let Some(graph) = &doc.graph else {
+        return Vec::new();
+    };
+    let offset = position_to_offset(&doc.source, position);
+    let Some(node) = graph.symbol_at(offset) else {
+        return Vec::new();
     };
-    let text = &doc.source;
-    let mut symbols = Vec::new();
-
-    // Config
-    if let Some(config) = &ast.config {
-        #[allow(deprecated)]
-        symbols.push(DocumentSymbol {
-            name: "config".to_string(),
-            detail: Some(config.node.agent_name.node.clone()),
-            kind: SymbolKind::MODULE,
-            tags: None,
-            deprecated: None,
-            range: span_to_range(text, config.span.clone()),
-            selection_range: span_to_range(text, config.span.clone()),
-            children: None,
-        });
-    }
+
+    graph
+        .occurrences_with_kind(node)
+        .into_iter()
+        .map(|((start, end), kind)| DocumentHighlight {
+            range: span_to_range(&doc.source, start..end),
+            kind: Some(match kind {
+                OccurrenceKind::Definition => DocumentHighlightKind::TEXT,
+                OccurrenceKind::Read => DocumentHighlightKind::READ,
+                OccurrenceKind::Write => DocumentHighlightKind::WRITE,
+            }),
+        })
+        .collect()
+}
+
+// =============================================================================
+// Call Hierarchy
+// =============================================================================
+
+/// The topic or start_agent at `offset`: the topic named or referenced
+/// there, or else the block containing it.
+fn call_hierarchy_node(graph: &RefGraph, offset: usize) -> Option<NodeIndex> {
+    if let Some(node) = graph.symbol_at(offset) {
+        if graph.get_node(node).is_some_and(RefNode::is_topic) {
+            return Some(node);
+        }
+    }
+    graph
+        .get_start_agent()
+        .into_iter()
+        .chain(graph.topic_names().filter_map(|name| graph.get_topic(name)))
+        .find(|&idx| {
+            let (start, end) = graph.get_node(idx).map_or((0, 0), RefNode::span);
+            start <= offset && offset < end
+        })
+}
+
+fn call_hierarchy_item(
+    doc: &DocumentState,
+    uri: &Url,
+    node: NodeIndex,
+) -> Option<CallHierarchyItem> {
+    let graph = doc.graph.as_ref()?;
+    let (start, end) = graph.get_node(node)?.span();
+    let (name, kind, selection) = match graph.get_node(node)? {
+        RefNode::Topic { name, .. } => {
+            let selection = graph
+                .occurrences_with_kind(node)
+                .into_iter()
+                .find(|(_, kind)| *kind == OccurrenceKind::Definition)
+                .map_or(start..end, |((s, e), _)| s..e);
+            (format!("topic {}", name), SymbolKind::MODULE, selection)
+        }
+        RefNode::StartAgent { .. } => {
+            let name = &doc.ast.as_ref()?.start_agent.as_ref()?.node.name;
+            (format!("start_agent {}", name.node), SymbolKind::CONSTRUCTOR, name.span.clone())
+        }
+        _ => return None,
+    };
+    Some(CallHierarchyItem {
+        name,
+        kind,
+        tags: None,
+        detail: None,
+        uri: uri.clone(),
+        range: span_to_range(&doc.source, start..end),
+        selection_range: span_to_range(&doc.source, selection),
+        data: None,
+    })
+}
+
+/// The node a call hierarchy item from [`call_hierarchy_item`] stands for.
+fn call_hierarchy_item_node(doc: &DocumentState, item: &CallHierarchyItem) -> Option<NodeIndex> {
+    let offset = position_to_offset(&doc.source, item.selection_range.start);
+    call_hierarchy_node(doc.graph.as_ref()?, offset)
+}
+
+/// Group transition sites by the topic at their other end, keeping the
+/// order each topic first appears in.
+fn group_transition_sites(
+    doc: &DocumentState,
+    sites: Vec<(NodeIndex, (usize, usize))>,
+) -> Vec<(NodeIndex, Vec<Range>)> {
+    let mut grouped: Vec<(NodeIndex, Vec<Range>)> = Vec::new();
+    for (node, (start, end)) in sites {
+        let range = span_to_range(&doc.source, start..end);
+        match grouped.iter_mut().find(|(n, _)| *n == node) {
+            Some((_, ranges)) => ranges.push(range),
+            None => grouped.push((node, vec![range])),
+        }
+    }
+    grouped
+}
+
+fn get_incoming_calls(
+    doc: &DocumentState,
+    uri: &Url,
+    item: &CallHierarchyItem,
+) -> Vec<CallHierarchyIncomingCall> {
+    let (Some(graph), Some(node)) = (&doc.graph, call_hierarchy_item_node(doc, item)) else {
+        return Vec::new();
+    };
+    group_transition_sites(doc, graph.transition_sites_into(node))
+        .into_iter()
+        .filter_map(|(from, from_ranges)| {
+            Some(CallHierarchyIncomingCall {
+                from: call_hierarchy_item(doc, uri, from)?,
+                from_ranges,
+            })
+        })
+        .collect()
+}
+
+fn get_outgoing_calls(
+    doc: &DocumentState,
+    uri: &Url,
+    item: &CallHierarchyItem,
+) -> Vec<CallHierarchyOutgoingCall> {
+    let (Some(graph), Some(node)) = (&doc.graph, call_hierarchy_item_node(doc, item)) else {
+        return Vec::new();
+    };
+    group_transition_sites(doc, graph.transition_sites_from(node))
+        .into_iter()
+        .filter_map(|(to, from_ranges)| {
+            Some(CallHierarchyOutgoingCall {
+                to: call_hierarchy_item(doc, uri, to)?,
+                from_ranges,
+            })
+        })
+        .collect()
+}
+
+// =============================================================================
+// Document Symbols
+// =============================================================================
+
+fn get_document_symbols(doc: &DocumentState) -> Vec<DocumentSymbol> {
+    let ast = match &doc.ast {
+        Some(a) => a,
+        None => return Vec::new(),
+    };
+    let text = &doc.source;
+    let mut symbols = Vec::new();
+
+    // Config
+    if let Some(config) = &ast.config {
+        #[allow(deprecated)]
+        symbols.push(DocumentSymbol {
+            name: "config".to_string(),
+            detail: Some(config.node.agent_name.node.clone()),
+            kind: SymbolKind::MODULE,
+            tags: None,
+            deprecated: None,
+            range: span_to_range(text, config.span.clone()),
+            selection_range: span_to_range(text, config.span.clone()),
+            children: None,
+        });
+    }
 
     // Variables
     if let Some(vars) = &ast.variables {
@@ -852,6 +1746,8 @@ fn get_document_symbols(doc: &DocumentState) -> Vec<DocumentSymbol> {
             }
         }
 
+        sa_children.extend(instruction_section_symbols(text, sa.node.reasoning.as_ref()));
+
         #[allow(deprecated)]
         symbols.push(DocumentSymbol {
             name: format!("start_agent {}", sa.node.name.node),
@@ -906,6 +1802,8 @@ fn get_document_symbols(doc: &DocumentState) -> Vec<DocumentSymbol> {
             }
         }
 
+        children.extend(instruction_section_symbols(text, topic.node.reasoning.as_ref()));
+
         #[allow(deprecated)]
         symbols.push(DocumentSymbol {
             name: format!("topic {}", topic.node.name.node),
@@ -923,30 +1821,94 @@ fn get_document_symbols(doc: &DocumentState) -> Vec<DocumentSymbol> {
         });
     }
 
+    // Blocks that failed to parse keep their place in the outline
+    for block in &ast.recovered_blocks {
+        let kind = &block.node.kind;
+        let (name, selection) = match &block.node.name {
+            Some(name) => (format!("{} {}", kind, name.node), name.span.clone()),
+            None => (kind.clone(), block.span.start..block.span.start + kind.len()),
+        };
+        #[allow(deprecated)]
+        symbols.push(DocumentSymbol {
+            name,
+            detail: Some("parse error".to_string()),
+            kind: match kind.as_str() {
+                "topic" => SymbolKind::CLASS,
+                "start_agent" => SymbolKind::CONSTRUCTOR,
+                _ => SymbolKind::MODULE,
+            },
+            tags: None,
+            deprecated: None,
+            range: span_to_range(text, block.span.clone()),
+            selection_range: span_to_range(text, selection),
+            children: None,
+        });
+    }
+
     symbols
 }
 
+/// Outline entries for the `|## heading` sections of a reasoning block's
+/// instructions, nested by heading level.
+fn instruction_section_symbols(
+    text: &str,
+    reasoning: Option<&Spanned<ReasoningBlock>>,
+) -> Vec<DocumentSymbol> {
+    fn symbols(text: &str, sections: &[InstructionSection]) -> Vec<DocumentSymbol> {
+        sections
+            .iter()
+            .map(|section| {
+                let children = symbols(text, &section.children);
+                #[allow(deprecated)]
+                DocumentSymbol {
+                    name: section.title.clone(),
+                    detail: Some("Instructions section".to_string()),
+                    kind: SymbolKind::STRING,
+                    tags: None,
+                    deprecated: None,
+                    range: span_to_range(text, section.span.clone()),
+                    selection_range: span_to_range(text, section.heading.clone()),
+                    children: (!children.is_empty()).then_some(children),
+                }
+            })
+            .collect()
+    }
+    reasoning
+        .and_then(|r| r.node.instructions.as_ref())
+        .map_or_else(Vec::new, |i| symbols(text, &i.node.sections()))
+}
+
 // =============================================================================
-// Formatting (uses the real serializer)
+// Formatting (block by block, so comments and block order survive)
 // =============================================================================
 
-fn format_document(doc: &DocumentState) -> Option<Vec<TextEdit>> {
+/// Format the top-level blocks overlapping `range`, or the whole document.
+fn format_document(doc: &DocumentState, range: Option<Range>) -> Option<Vec<TextEdit>> {
     let ast = doc.ast.as_ref()?;
-    let formatted = busbar_sf_agentscript::serialize(ast);
-    if formatted == doc.source {
-        return None;
+    let range = match range {
+        Some(range) => {
+            position_to_offset(&doc.source, range.start)..position_to_offset(&doc.source, range.end)
+        }
+        None => 0..doc.source.len(),
+    };
+    let edits: Vec<TextEdit> = formatter::format_range(&doc.source, ast, range)
+        .into_iter()
+        .map(|edit| to_text_edit(&doc.source, edit))
+        .collect();
+    (!edits.is_empty()).then_some(edits)
+}
+
+fn format_on_type(doc: &DocumentState, position: Position, ch: &str) -> Option<Vec<TextEdit>> {
+    let offset = position_to_offset(&doc.source, position);
+    let edit = formatter::format_on_type(&doc.source, offset, ch.chars().next()?)?;
+    Some(vec![to_text_edit(&doc.source, edit)])
+}
+
+fn to_text_edit(text: &str, edit: SourceEdit) -> TextEdit {
+    TextEdit {
+        range: span_to_range(text, edit.span),
+        new_text: edit.new_text,
     }
-    let end = offset_to_position(&doc.source, doc.source.len());
-    Some(vec![TextEdit {
-        range: Range {
-            start: Position {
-                line: 0,
-                character: 0,
-            },
-            end,
-        },
-        new_text: formatted,
-    }])
 }
 
 // =============================================================================
@@ -1003,9 +1965,25 @@ fn get_folding_ranges(doc: &DocumentState) -> Vec<FoldingRange> {
             add_fold(&reasoning.span);
         }
     }
+    // Instruction sections, down to the deepest heading
+    let mut sections: Vec<_> = ast
+        .start_agent
+        .iter()
+        .filter_map(|sa| sa.node.reasoning.as_ref())
+        .chain(ast.topics.iter().filter_map(|t| t.node.reasoning.as_ref()))
+        .filter_map(|r| r.node.instructions.as_ref())
+        .flat_map(|i| i.node.sections())
+        .collect();
+    while let Some(section) = sections.pop() {
+        add_fold(&section.span);
+        sections.extend(section.children);
+    }
     for conn in &ast.connections {
         add_fold(&conn.span);
     }
+    for block in &ast.recovered_blocks {
+        add_fold(&block.span);
+    }
 
     // Comment folding: consecutive comment lines
     let mut comment_start: Option<u32> = None;
@@ -1039,54 +2017,424 @@ fn get_folding_ranges(doc: &DocumentState) -> Vec<FoldingRange> {
 // Code Actions
 // =============================================================================
 
-fn get_code_actions(doc: &DocumentState, range: Range) -> Vec<CodeActionOrCommand> {
+fn get_code_actions(doc: &DocumentState, uri: &Url, range: Range) -> Vec<CodeActionOrCommand> {
     let ast = match &doc.ast {
         Some(a) => a,
         None => return Vec::new(),
     };
+    let source = &doc.source;
     let mut actions = Vec::new();
-    let start_offset = position_to_offset(&doc.source, range.start);
-
-    // Quick fix: add missing description to topics
-    for topic in &ast.topics {
-        if topic.span.contains(&start_offset) && topic.node.description.is_none() {
+    let start_offset = position_to_offset(source, range.start);
+    let end_offset = position_to_offset(source, range.end);
+    let touches = |span: (usize, usize)| span.0 <= end_offset && start_offset <= span.1;
+
+    // Quick fix: add missing description. The configured provider may be
+    // slow, so its text is only asked for when the action is resolved.
+    for missing in missing_descriptions(ast) {
+        if missing.definition.contains(&start_offset) {
+            let data = DescriptionData {
+                uri: uri.clone(),
+                kind: missing.item.kind().to_string(),
+                name: missing.item.name().to_string(),
+                definition: missing.definition.start,
+            };
             actions.push(CodeActionOrCommand::CodeAction(CodeAction {
-                title: format!("Add description to topic '{}'", topic.node.name.node),
+                title: format!("Add description to {} '{}'", data.kind, data.name),
                 kind: Some(CodeActionKind::QUICKFIX),
                 is_preferred: Some(false),
+                data: serde_json::to_value(data).ok(),
                 ..Default::default()
             }));
         }
     }
 
-    // Quick fix: add missing description to variables
-    if let Some(vars) = &ast.variables {
-        for var in &vars.node.variables {
-            if var.span.contains(&start_offset) && var.node.description.is_none() {
-                actions.push(CodeActionOrCommand::CodeAction(CodeAction {
-                    title: format!("Add description to variable '{}'", var.node.name.node),
-                    kind: Some(CodeActionKind::QUICKFIX),
-                    is_preferred: Some(false),
-                    ..Default::default()
-                }));
+    if let Some(graph) = &doc.graph {
+        // Quick fix: remove unused variable. Only offered when nothing
+        // refers to it at all; deleting a variable that is still written
+        // would leave the `set` targets unresolved.
+        for unused in graph.find_unused_variables() {
+            let ValidationError::UnusedVariable { name, span } = &unused else {
+                continue;
+            };
+            let referenced = graph.get_variable(name).is_none_or(|idx| {
+                graph
+                    .occurrences_with_kind(idx)
+                    .iter()
+                    .any(|(_, kind)| *kind != OccurrenceKind::Definition)
+            });
+            if referenced {
+                continue;
+            }
+            let decl = ast
+                .variables
+                .iter()
+                .flat_map(|v| &v.node.variables)
+                .find(|v| &v.node.name.node == name);
+            if let (true, Some(decl)) = (touches(*span), decl) {
+                // The span runs on to the next declaration's indentation
+                let last = source[..decl.span.end].trim_end().len().saturating_sub(1);
+                let start = line_start(source, decl.span.start);
+                let end = line_end(source, last.max(start));
+                actions.push(quick_fix(
+                    format!("Remove unused variable '{}'", name),
+                    uri,
+                    source,
+                    SourceEdit::delete(start..end).into(),
+                ));
+            }
+        }
+
+        // Quick fix: replace a misspelled reference with the closest name
+        for unresolved in graph.run_pass(PassId::UnresolvedReferences) {
+            let ValidationError::UnresolvedReference {
+                reference,
+                namespace,
+                span,
+                suggestion: Some(suggestion),
+                ..
+            } = &unresolved
+            else {
+                continue;
+            };
+            // Keep any property access after the name
+            let prefix = format!("@{}.", namespace);
+            let Some(name) = reference.strip_prefix(&prefix) else {
+                continue;
+            };
+            let misspelled = format!("{}{}", prefix, name.split('.').next().unwrap_or(name));
+            let Some(at) = source[span.0..span.1].find(&misspelled) else {
+                continue;
+            };
+            if touches(*span) {
+                let start = span.0 + at;
+                actions.push(quick_fix(
+                    format!("Change to '{}'", suggestion),
+                    uri,
+                    source,
+                    SourceEdit::replace(start..start + misspelled.len(), suggestion.clone()).into(),
+                ));
             }
         }
+
+        // Quick fix: create a stub definition for an unresolved @actions.x
+        let mut stubbed = std::collections::HashSet::new();
+        for unresolved in graph.run_pass(PassId::UnresolvedReferences) {
+            let ValidationError::UnresolvedReference {
+                reference,
+                namespace,
+                span,
+                ..
+            } = &unresolved
+            else {
+                continue;
+            };
+            let Some(name) = reference.strip_prefix("@actions.") else {
+                continue;
+            };
+            if namespace != "actions" || !touches(*span) || !stubbed.insert(name.to_string()) {
+                continue;
+            }
+            if let Some(edit) = action_stub_edit(ast, source, span.0, name) {
+                actions.push(quick_fix(
+                    format!("Create action definition '{}'", name),
+                    uri,
+                    source,
+                    edit.into(),
+                ));
+            }
+        }
+    }
+
+    // Quick fix: reorder reasoning action clauses into canonical order
+    let reasoning_blocks = ast
+        .start_agent
+        .iter()
+        .filter_map(|sa| sa.node.reasoning.as_ref())
+        .chain(ast.topics.iter().filter_map(|t| t.node.reasoning.as_ref()));
+    for reasoning in reasoning_blocks {
+        for action in reasoning.node.actions.iter().flat_map(|a| &a.node) {
+            if !action.span.contains(&start_offset) {
+                continue;
+            }
+            if let Some(edit) = clause_order_edit(source, &action.node) {
+                actions.push(quick_fix(
+                    format!("Reorder clauses of action '{}'", action.node.name.node),
+                    uri,
+                    source,
+                    edit.into(),
+                ));
+            }
+        }
+    }
+
+    // Quick fix: legacy `connections:` wrapper to `connection <name>:` blocks
+    if let Some(edit) = legacy_connections_edit(ast, source, start_offset) {
+        actions.push(quick_fix(
+            "Convert to 'connection <name>:' blocks".to_string(),
+            uri,
+            source,
+            edit.into(),
+        ));
     }
 
     actions
 }
 
+/// The definition an "Add description" action is for, kept in the action's
+/// `data` until `codeAction/resolve`.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct DescriptionData {
+    uri: Url,
+    kind: String,
+    name: String,
+    /// Offset of the start of the definition
+    definition: usize,
+}
+
+/// The edit adding a description to the definition named by `data`, with
+/// text from `descriptions`, or `None` if the document no longer lacks it.
+fn resolve_description_edit(
+    doc: &DocumentState,
+    data: &DescriptionData,
+    descriptions: &dyn DescriptionProvider,
+) -> Option<WorkspaceEdit> {
+    let ast = doc.ast.as_ref()?;
+    let missing = missing_descriptions(ast).into_iter().find(|missing| {
+        missing.definition.start == data.definition
+            && missing.item.kind() == data.kind
+            && missing.item.name() == data.name
+    })?;
+    let edit = description_edit(&doc.source, &missing, descriptions);
+    Some(WorkspaceEdit {
+        changes: Some(HashMap::from([(data.uri.clone(), vec![to_text_edit(&doc.source, edit)])])),
+        ..Default::default()
+    })
+}
+
+fn quick_fix(title: String, uri: &Url, source: &str, edits: EditSet) -> CodeActionOrCommand {
+    let edits = edits
+        .into_iter()
+        .map(|edit| to_text_edit(source, edit))
+        .collect();
+    CodeActionOrCommand::CodeAction(CodeAction {
+        title,
+        kind: Some(CodeActionKind::QUICKFIX),
+        edit: Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(uri.clone(), edits)])),
+            ..Default::default()
+        }),
+        is_preferred: Some(false),
+        ..Default::default()
+    })
+}
+
+/// Offset of the start of the line containing `offset`.
+fn line_start(source: &str, offset: usize) -> usize {
+    source[..offset].rfind('\n').map(|i| i + 1).unwrap_or(0)
+}
+
+/// Offset just past the newline ending the line containing `offset`.
+fn line_end(source: &str, offset: usize) -> usize {
+    source[offset..]
+        .find('\n')
+        .map(|i| offset + i + 1)
+        .unwrap_or(source.len())
+}
+
+/// Indentation for a property nested under the line containing `offset`.
+fn child_indent(source: &str, offset: usize) -> String {
+    let line = &source[line_start(source, offset)..];
+    let indent = line.len() - line.trim_start_matches(' ').len();
+    " ".repeat(indent + 3)
+}
+
+/// Insert `text` on a new line after the line containing `offset`.
+fn insert_after_line(source: &str, offset: usize, text: String) -> SourceEdit {
+    let at = line_end(source, offset);
+    let text = if at == source.len() && !source.ends_with('\n') {
+        format!("\n{}", text)
+    } else {
+        text
+    };
+    SourceEdit::insert(at, text)
+}
+
+/// Rewrite a reasoning action's clause lines in canonical order, or `None` if
+/// they already are. Each clause moves together with any comment lines above it.
+fn clause_order_edit(source: &str, action: &ReasoningAction) -> Option<SourceEdit> {
+    action.first_misordered_clause()?;
+    let clauses: Vec<(ActionClause, std::ops::Range<usize>)> = action
+        .clauses()
+        .into_iter()
+        .filter_map(|(kind, index)| Some((kind, action.clause_span(kind, index)?)))
+        .collect();
+
+    let start = line_start(source, clauses.first()?.1.start);
+    let last = &clauses.last()?.1;
+    let end = line_end(source, last.end.saturating_sub(1).max(last.start));
+    let starts: Vec<usize> = clauses
+        .iter()
+        .map(|(_, span)| line_start(source, span.start))
+        .chain([end])
+        .collect();
+    let mut segments: Vec<(ActionClause, String)> = clauses
+        .iter()
+        .zip(starts.windows(2))
+        .map(|((kind, _), bounds)| {
+            let mut text = source[bounds[0]..bounds[1]].to_string();
+            if !text.ends_with('\n') {
+                text.push('\n');
+            }
+            (*kind, text)
+        })
+        .collect();
+    segments.sort_by_key(|(kind, _)| *kind);
+
+    let mut new_text: String = segments.into_iter().map(|(_, text)| text).collect();
+    if !source[start..end].ends_with('\n') {
+        new_text.pop();
+    }
+    Some(SourceEdit::replace(start..end, new_text))
+}
+
+/// Append a stub action definition to the `actions:` block of the topic (or
+/// start_agent) containing `offset`, creating the block if needed.
+fn action_stub_edit(
+    ast: &AgentFile,
+    source: &str,
+    offset: usize,
+    name: &str,
+) -> Option<SourceEdit> {
+    let (header, actions) = ast
+        .start_agent
+        .iter()
+        .filter(|sa| sa.span.contains(&offset))
+        .map(|sa| (sa.node.name.span.start, &sa.node.actions))
+        .chain(
+            ast.topics
+                .iter()
+                .filter(|t| t.span.contains(&offset))
+                .map(|t| (t.node.name.span.start, &t.node.actions)),
+        )
+        .next()?;
+
+    let stub = |indent: &str| {
+        format!(
+            "{i}{name}:\n{i}   description: \"\"\n{i}   target: \"flow://{name}\"\n",
+            i = indent,
+            name = name
+        )
+    };
+    match actions.as_ref().and_then(|a| a.node.actions.last()) {
+        Some(last) => {
+            let indent = child_indent(source, last.node.name.span.start);
+            let indent = &indent[3..];
+            let last_line = source[..last.span.end].trim_end().len().saturating_sub(1);
+            Some(insert_after_line(source, last_line, stub(indent)))
+        }
+        None => {
+            let block_indent = child_indent(source, header);
+            let text =
+                format!("{}actions:\n{}", block_indent, stub(&format!("{}   ", block_indent)));
+            Some(insert_after_line(source, header, text))
+        }
+    }
+}
+
+/// Rewrite a legacy `connections:` wrapper containing `offset` as top-level
+/// `connection <name>:` blocks.
+fn legacy_connections_edit(ast: &AgentFile, source: &str, offset: usize) -> Option<SourceEdit> {
+    let is_block_body = |l: &str| l.starts_with([' ', '\t', '#']) || l.trim().is_empty();
+    let (start, end) = source
+        .match_indices("connections:")
+        .map(|(i, _)| i)
+        .filter(|&i| line_start(source, i) == i)
+        .map(|start| {
+            let end = source[start..]
+                .split_inclusive('\n')
+                .skip(1)
+                .take_while(|l| is_block_body(l))
+                .fold(line_end(source, start), |end, l| end + l.len());
+            (start, end)
+        })
+        .find(|(start, end)| (*start..*end).contains(&offset))?;
+
+    let mut converted = AgentFile::new();
+    converted.connections = ast
+        .connections
+        .iter()
+        .filter(|c| (start..end).contains(&c.span.start))
+        .cloned()
+        .collect();
+    // Stop after the last entry so trailing comments stay put
+    let last = converted
+        .connections
+        .iter()
+        .flat_map(|c| &c.node.entries)
+        .map(|e| e.span.end)
+        .max()?;
+    let end = line_end(source, source[..last.min(end)].trim_end().len().saturating_sub(1));
+    let text = busbar_sf_agentscript::serialize(&converted);
+    Some(SourceEdit::replace(start..end, format!("{}\n", text.trim_end())))
+}
+
 // =============================================================================
 // LSP Trait Implementation
 // =============================================================================
 
 #[tower_lsp::async_trait]
 impl LanguageServer for Backend {
-    async fn initialize(&self, _: InitializeParams) -> Result<InitializeResult> {
+    async fn initialize(&self, params: InitializeParams) -> Result<InitializeResult> {
+        let mut roots: Vec<std::path::PathBuf> = params
+            .workspace_folders
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|f| f.uri.to_file_path().ok())
+            .collect();
+        #[allow(deprecated)]
+        if roots.is_empty() {
+            roots.extend(params.root_uri.and_then(|u| u.to_file_path().ok()));
+        }
+        if let Some(path) = roots.iter().find_map(|root| find_config_file(root)) {
+            match LintConfig::load(&path) {
+                Ok(config) => {
+                    for id in LintRegistry::new().unknown_rules(&config) {
+                        let message = format!("{}: unknown lint rule '{}'", path.display(), id);
+                        self.client.log_message(MessageType::WARNING, message).await;
+                    }
+                    *self.lint.write().await = config;
+                }
+                Err(err) => {
+                    let message = format!("{}: {}", path.display(), err);
+                    self.client.log_message(MessageType::WARNING, message).await;
+                }
+            }
+        }
+        *self.workspace.write().await = WorkspaceIndex::new(roots);
+
+        if let Some(target) = params
+            .initialization_options
+            .as_ref()
+            .and_then(|o| o.get("targetEnvironment"))
+            .and_then(|t| t.as_str())
+        {
+            match target.parse() {
+                Ok(target) => *self.target.write().await = Some(target),
+                Err(message) => self.client.log_message(MessageType::WARNING, message).await,
+            }
+        }
+        *self.descriptions.write().await =
+            descriptions::provider_from_options(params.initialization_options.as_ref());
+
         Ok(InitializeResult {
             capabilities: ServerCapabilities {
-                text_document_sync: Some(TextDocumentSyncCapability::Kind(
-                    TextDocumentSyncKind::FULL,
+                text_document_sync: Some(TextDocumentSyncCapability::Options(
+                    TextDocumentSyncOptions {
+                        open_close: Some(true),
+                        change: Some(TextDocumentSyncKind::INCREMENTAL),
+                        save: Some(TextDocumentSyncSaveOptions::Supported(true)),
+                        ..Default::default()
+                    },
                 )),
                 completion_provider: Some(CompletionOptions {
                     trigger_characters: Some(vec![
@@ -1098,11 +2446,27 @@ impl LanguageServer for Backend {
                     ..Default::default()
                 }),
                 hover_provider: Some(HoverProviderCapability::Simple(true)),
+                signature_help_provider: Some(SignatureHelpOptions {
+                    trigger_characters: Some(vec![" ".to_string()]),
+                    retrigger_characters: Some(vec!["=".to_string()]),
+                    ..Default::default()
+                }),
                 definition_provider: Some(OneOf::Left(true)),
                 references_provider: Some(OneOf::Left(true)),
+                document_highlight_provider: Some(OneOf::Left(true)),
+                call_hierarchy_provider: Some(CallHierarchyServerCapability::Simple(true)),
                 document_symbol_provider: Some(OneOf::Left(true)),
-                rename_provider: Some(OneOf::Left(true)),
+                workspace_symbol_provider: Some(OneOf::Left(true)),
+                rename_provider: Some(OneOf::Right(RenameOptions {
+                    prepare_provider: Some(true),
+                    work_done_progress_options: Default::default(),
+                })),
                 document_formatting_provider: Some(OneOf::Left(true)),
+                document_range_formatting_provider: Some(OneOf::Left(true)),
+                document_on_type_formatting_provider: Some(DocumentOnTypeFormattingOptions {
+                    first_trigger_character: "\n".to_string(),
+                    more_trigger_character: None,
+                }),
                 folding_range_provider: Some(FoldingRangeProviderCapability::Simple(true)),
                 semantic_tokens_provider: Some(
                     SemanticTokensServerCapabilities::SemanticTokensOptions(
@@ -1114,7 +2478,12 @@ impl LanguageServer for Backend {
                         },
                     ),
                 ),
-                code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
+                code_action_provider: Some(CodeActionProviderCapability::Options(
+                    CodeActionOptions {
+                        resolve_provider: Some(true),
+                        ..Default::default()
+                    },
+                )),
                 ..Default::default()
             },
             ..Default::default()
@@ -1122,9 +2491,14 @@ impl LanguageServer for Backend {
     }
 
     async fn initialized(&self, _: InitializedParams) {
+        let indexed = self.workspace.write().await.scan();
         self.client
-            .log_message(MessageType::INFO, "AgentScript LSP initialized")
+            .log_message(
+                MessageType::INFO,
+                format!("AgentScript LSP initialized ({} workspace files indexed)", indexed.len()),
+            )
             .await;
+        self.publish_workspace_diagnostics().await;
     }
 
     async fn shutdown(&self) -> Result<()> {
@@ -1133,33 +2507,103 @@ impl LanguageServer for Backend {
 
     async fn did_open(&self, params: DidOpenTextDocumentParams) {
         let uri = params.text_document.uri.clone();
-        let doc = DocumentState::new(params.text_document.text);
+        self.buffers.lock().unwrap().insert(
+            uri.clone(),
+            TextBuffer::new(params.text_document.text.clone(), params.text_document.version),
+        );
+        self.history.lock().unwrap().record(
+            &uri,
+            HistoryEvent::Opened {
+                version: params.text_document.version,
+                text: params.text_document.text.clone(),
+            },
+        );
+        let Some(doc) =
+            DocumentState::parse(params.text_document.text, CancellationToken::new()).await
+        else {
+            return;
+        };
+        let doc = Arc::new(doc);
+        self.workspace.write().await.insert(&uri, doc.clone());
         self.documents.write().await.insert(uri.clone(), doc);
-        self.publish_diagnostics(&uri).await;
+        self.publish_diagnostics(&uri, PassId::ALL).await;
     }
 
     async fn did_change(&self, params: DidChangeTextDocumentParams) {
         let uri = params.text_document.uri.clone();
-        if let Some(change) = params.content_changes.into_iter().next() {
-            let doc = DocumentState::new(change.text);
-            self.documents.write().await.insert(uri.clone(), doc);
-            self.publish_diagnostics(&uri).await;
+        let version = params.text_document.version;
+        // Apply the edits before the first await so they stay in order
+        let text = {
+            let mut buffers = self.buffers.lock().unwrap();
+            let Some(buffer) = buffers.get_mut(&uri) else {
+                return;
+            };
+            buffer.apply(params.content_changes, version);
+            buffer.text().to_string()
+        };
+        self.history.lock().unwrap().record(
+            &uri,
+            HistoryEvent::Changed {
+                version,
+                text: text.clone(),
+            },
+        );
+
+        // A newer version supersedes any parse still running
+        let token = CancellationToken::new();
+        if let Some(stale) = self
+            .pending_parses
+            .lock()
+            .unwrap()
+            .insert(uri.clone(), token.clone())
+        {
+            stale.cancel();
+        }
+        // Parse in the background so requests aren't queued behind typing
+        tokio::spawn(self.clone().reparse(uri, version, text, token));
+    }
+
+    async fn did_save(&self, params: DidSaveTextDocumentParams) {
+        let uri = params.text_document.uri;
+        // Saving cancels a pending re-index, so do it here
+        if let Some(doc) = self.snapshot(&uri).await {
+            self.workspace.write().await.insert(&uri, doc);
         }
+        self.publish_diagnostics(&uri, PassId::ALL).await;
     }
 
     async fn did_close(&self, params: DidCloseTextDocumentParams) {
-        self.documents
-            .write()
-            .await
-            .remove(&params.text_document.uri);
+        let uri = params.text_document.uri;
+        if let Some(pending) = self.pending_parses.lock().unwrap().remove(&uri) {
+            pending.cancel();
+        }
+        if let Some(pending) = self.pending_diagnostics.lock().unwrap().remove(&uri) {
+            pending.cancel();
+        }
+        self.expensive.lock().unwrap().remove(&uri);
+        self.documents.write().await.remove(&uri);
+        self.buffers.lock().unwrap().remove(&uri);
+        self.history
+            .lock()
+            .unwrap()
+            .record(&uri, HistoryEvent::Closed);
+
+        // Fall back to the on-disk copy; clear diagnostics for files outside the workspace
+        if self.workspace.write().await.reload(&uri) {
+            self.publish_diagnostics(&uri, PassId::ALL).await;
+        } else {
+            self.client.publish_diagnostics(uri, Vec::new(), None).await;
+        }
     }
 
     async fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
-        let docs = self.documents.read().await;
-        let Some(doc) = docs.get(&params.text_document_position.text_document.uri) else {
+        let Some(doc) = self
+            .snapshot(&params.text_document_position.text_document.uri)
+            .await
+        else {
             return Ok(None);
         };
-        let items = get_completions(doc, params.text_document_position.position);
+        let items = get_completions(&doc, params.text_document_position.position);
         if items.is_empty() {
             Ok(None)
         } else {
@@ -1168,37 +2612,88 @@ impl LanguageServer for Backend {
     }
 
     async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
-        let docs = self.documents.read().await;
-        let Some(doc) = docs.get(&params.text_document_position_params.text_document.uri) else {
+        let Some(doc) = self
+            .snapshot(&params.text_document_position_params.text_document.uri)
+            .await
+        else {
+            return Ok(None);
+        };
+        Ok(get_hover(&doc, params.text_document_position_params.position))
+    }
+
+    async fn signature_help(&self, params: SignatureHelpParams) -> Result<Option<SignatureHelp>> {
+        let Some(doc) = self
+            .snapshot(&params.text_document_position_params.text_document.uri)
+            .await
+        else {
             return Ok(None);
         };
-        Ok(get_hover(doc, params.text_document_position_params.position))
+        Ok(get_signature_help(&doc, params.text_document_position_params.position))
     }
 
     async fn goto_definition(
         &self,
         params: GotoDefinitionParams,
     ) -> Result<Option<GotoDefinitionResponse>> {
-        let docs = self.documents.read().await;
         let uri = params
             .text_document_position_params
             .text_document
             .uri
             .clone();
-        let Some(doc) = docs.get(&uri) else {
+        let Some(doc) = self.snapshot(&uri).await else {
+            return Ok(None);
+        };
+        let position = params.text_document_position_params.position;
+        if let Some(range) = get_definition(&doc, position) {
+            return Ok(Some(GotoDefinitionResponse::Scalar(Location { uri, range })));
+        }
+
+        // Not defined in this file: look across the workspace
+        let offset = position_to_offset(&doc.source, position);
+        let Some(reference) = find_reference_at_offset(&doc.source, offset) else {
+            return Ok(None);
+        };
+        let (Some(kind), Some(name)) =
+            (DefKind::from_namespace(&reference.namespace), reference.path.first())
+        else {
             return Ok(None);
         };
-        Ok(get_definition(doc, params.text_document_position_params.position)
-            .map(|range| GotoDefinitionResponse::Scalar(Location { uri, range })))
+        let locations = self.workspace.read().await.find_definitions(kind, name);
+        Ok(match locations.len() {
+            0 => None,
+            1 => locations
+                .into_iter()
+                .next()
+                .map(GotoDefinitionResponse::Scalar),
+            _ => Some(GotoDefinitionResponse::Array(locations)),
+        })
     }
 
     async fn references(&self, params: ReferenceParams) -> Result<Option<Vec<Location>>> {
-        let docs = self.documents.read().await;
         let uri = params.text_document_position.text_document.uri.clone();
-        let Some(doc) = docs.get(&uri) else {
+        let Some(doc) = self.snapshot(&uri).await else {
             return Ok(None);
         };
-        let ranges = get_references(doc, params.text_document_position.position);
+
+        // Prefer workspace-wide results; the index includes this document
+        let position = params.text_document_position.position;
+        let include_declaration = params.context.include_declaration;
+        let symbol = doc.graph.as_ref().and_then(|graph| {
+            let offset = position_to_offset(&doc.source, position);
+            graph.get_node(graph.symbol_at(offset)?).cloned()
+        });
+        if let Some(symbol) = symbol {
+            let locations = self
+                .workspace
+                .read()
+                .await
+                .find_references(&symbol, include_declaration);
+            if !locations.is_empty() {
+                return Ok(Some(locations));
+            }
+        }
+
+        let ranges = get_references(&doc, position, include_declaration);
         if ranges.is_empty() {
             Ok(None)
         } else {
@@ -1214,13 +2709,82 @@ impl LanguageServer for Backend {
         }
     }
 
+    async fn document_highlight(
+        &self,
+        params: DocumentHighlightParams,
+    ) -> Result<Option<Vec<DocumentHighlight>>> {
+        let Some(doc) = self
+            .snapshot(&params.text_document_position_params.text_document.uri)
+            .await
+        else {
+            return Ok(None);
+        };
+        let highlights =
+            get_document_highlights(&doc, params.text_document_position_params.position);
+        if highlights.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(highlights))
+        }
+    }
+
+    async fn prepare_call_hierarchy(
+        &self,
+        params: CallHierarchyPrepareParams,
+    ) -> Result<Option<Vec<CallHierarchyItem>>> {
+        let uri = &params.text_document_position_params.text_document.uri;
+        let Some(doc) = self.snapshot(uri).await else {
+            return Ok(None);
+        };
+        let Some(graph) = &doc.graph else {
+            return Ok(None);
+        };
+        let offset = position_to_offset(&doc.source, params.text_document_position_params.position);
+        Ok(call_hierarchy_node(graph, offset)
+            .and_then(|node| call_hierarchy_item(&doc, uri, node))
+            .map(|item| vec![item]))
+    }
+
+    async fn incoming_calls(
+        &self,
+        params: CallHierarchyIncomingCallsParams,
+    ) -> Result<Option<Vec<CallHierarchyIncomingCall>>> {
+        let uri = &params.item.uri;
+        let Some(doc) = self.snapshot(uri).await else {
+            return Ok(None);
+        };
+        Ok(Some(get_incoming_calls(&doc, uri, &params.item)))
+    }
+
+    async fn outgoing_calls(
+        &self,
+        params: CallHierarchyOutgoingCallsParams,
+    ) -> Result<Option<Vec<CallHierarchyOutgoingCall>>> {
+        let uri = &params.item.uri;
+        let Some(doc) = self.snapshot(uri).await else {
+            return Ok(None);
+        };
+        Ok(Some(get_outgoing_calls(&doc, uri, &params.item)))
+    }
+
+    async fn prepare_rename(
+        &self,
+        params: TextDocumentPositionParams,
+    ) -> Result<Option<PrepareRenameResponse>> {
+        let Some(doc) = self.snapshot(&params.text_document.uri).await else {
+            return Ok(None);
+        };
+        Ok(get_prepare_rename(&doc, params.position))
+    }
+
     async fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
-        let docs = self.documents.read().await;
         let uri = params.text_document_position.text_document.uri.clone();
-        let Some(doc) = docs.get(&uri) else {
+        let Some(doc) = self.snapshot(&uri).await else {
             return Ok(None);
         };
-        let edits = get_rename_edits(doc, params.text_document_position.position, &params.new_name);
+        let edits =
+            get_rename_edits(&doc, params.text_document_position.position, &params.new_name)
+                .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?;
         Ok(edits.map(|text_edits| {
             let mut changes = HashMap::new();
             changes.insert(uri, text_edits);
@@ -1236,11 +2800,10 @@ impl LanguageServer for Backend {
         &self,
         params: DocumentSymbolParams,
     ) -> Result<Option<DocumentSymbolResponse>> {
-        let docs = self.documents.read().await;
-        let Some(doc) = docs.get(&params.text_document.uri) else {
+        let Some(doc) = self.snapshot(&params.text_document.uri).await else {
             return Ok(None);
         };
-        let symbols = get_document_symbols(doc);
+        let symbols = get_document_symbols(&doc);
         if symbols.is_empty() {
             Ok(None)
         } else {
@@ -1248,20 +2811,51 @@ impl LanguageServer for Backend {
         }
     }
 
-    async fn formatting(&self, params: DocumentFormattingParams) -> Result<Option<Vec<TextEdit>>> {
-        let docs = self.documents.read().await;
-        let Some(doc) = docs.get(&params.text_document.uri) else {
-            return Ok(None);
-        };
-        Ok(format_document(doc))
+    async fn symbol(
+        &self,
+        params: WorkspaceSymbolParams,
+    ) -> Result<Option<Vec<SymbolInformation>>> {
+        let symbols = self.workspace.read().await.symbols().search(&params.query);
+        if symbols.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(symbols))
+        }
+    }
+
+    async fn formatting(&self, params: DocumentFormattingParams) -> Result<Option<Vec<TextEdit>>> {
+        let Some(doc) = self.snapshot(&params.text_document.uri).await else {
+            return Ok(None);
+        };
+        Ok(format_document(&doc, None))
+    }
+
+    async fn range_formatting(
+        &self,
+        params: DocumentRangeFormattingParams,
+    ) -> Result<Option<Vec<TextEdit>>> {
+        let Some(doc) = self.snapshot(&params.text_document.uri).await else {
+            return Ok(None);
+        };
+        Ok(format_document(&doc, Some(params.range)))
+    }
+
+    async fn on_type_formatting(
+        &self,
+        params: DocumentOnTypeFormattingParams,
+    ) -> Result<Option<Vec<TextEdit>>> {
+        let position = params.text_document_position;
+        let Some(doc) = self.snapshot(&position.text_document.uri).await else {
+            return Ok(None);
+        };
+        Ok(format_on_type(&doc, position.position, &params.ch))
     }
 
     async fn folding_range(&self, params: FoldingRangeParams) -> Result<Option<Vec<FoldingRange>>> {
-        let docs = self.documents.read().await;
-        let Some(doc) = docs.get(&params.text_document.uri) else {
+        let Some(doc) = self.snapshot(&params.text_document.uri).await else {
             return Ok(None);
         };
-        let ranges = get_folding_ranges(doc);
+        let ranges = get_folding_ranges(&doc);
         if ranges.is_empty() {
             Ok(None)
         } else {
@@ -1273,8 +2867,7 @@ impl LanguageServer for Backend {
         &self,
         params: SemanticTokensParams,
     ) -> Result<Option<SemanticTokensResult>> {
-        let docs = self.documents.read().await;
-        let Some(doc) = docs.get(&params.text_document.uri) else {
+        let Some(doc) = self.snapshot(&params.text_document.uri).await else {
             return Ok(None);
         };
         let ast = doc.ast.as_ref();
@@ -1286,17 +2879,37 @@ impl LanguageServer for Backend {
     }
 
     async fn code_action(&self, params: CodeActionParams) -> Result<Option<CodeActionResponse>> {
-        let docs = self.documents.read().await;
-        let Some(doc) = docs.get(&params.text_document.uri) else {
+        let Some(doc) = self.snapshot(&params.text_document.uri).await else {
             return Ok(None);
         };
-        let actions = get_code_actions(doc, params.range);
+        let actions = get_code_actions(&doc, &params.text_document.uri, params.range);
         if actions.is_empty() {
             Ok(None)
         } else {
             Ok(Some(actions))
         }
     }
+
+    async fn code_action_resolve(&self, mut action: CodeAction) -> Result<CodeAction> {
+        let Some(data) = action
+            .data
+            .clone()
+            .and_then(|data| serde_json::from_value::<DescriptionData>(data).ok())
+        else {
+            return Ok(action);
+        };
+        let Some(doc) = self.snapshot(&data.uri).await else {
+            return Ok(action);
+        };
+        let descriptions = self.descriptions.read().await.clone();
+        // A description command may take a while, so keep it off the runtime
+        action.edit = tokio::task::spawn_blocking(move || {
+            resolve_description_edit(&doc, &data, descriptions.as_ref())
+        })
+        .await
+        .unwrap_or_default();
+        Ok(action)
+    }
 }
 
 // =============================================================================
@@ -1307,6 +2920,9 @@ impl LanguageServer for Backend {
 #[derive(Debug, serde::Deserialize)]
 struct GetGraphParams {
     uri: String,
+    /// Only return the subgraph for this topic.
+    #[serde(default)]
+    topic: Option<String>,
 }
 
 /// Parameters for agentscript/getDependencies request.
@@ -1371,48 +2987,60 @@ impl From<&DependencyReport> for DependencyReportRepr {
     }
 }
 
-/// Parameters for agentscript/simulate request.
+/// Parameters for agentscript/health request.
 #[derive(Debug, serde::Deserialize)]
-struct SimulateParams {
+struct HealthParams {
     uri: String,
+    /// Test coverage (0.0–1.0), when the client has measured it.
     #[serde(default)]
-    mock_data: serde_json::Value,
+    coverage: Option<f64>,
 }
 
-/// Simplified execution trace for the extension.
-#[derive(Debug, serde::Serialize)]
-struct SimulationResult {
-    steps: Vec<SimulationStep>,
-    final_context: serde_json::Value,
-    outcome: String,
-    topic_transitions: Vec<String>,
+/// Parameters for agentscript/parseProfile request.
+#[derive(Debug, serde::Deserialize)]
+struct ParseProfileParams {
+    uri: String,
 }
 
-#[derive(Debug, serde::Serialize)]
-struct SimulationStep {
-    phase: String,
-    statement_type: String,
-    detail: String,
-    variable_changes: Vec<VarChange>,
-    action_invocations: Vec<ActionInvoke>,
+/// Parameters for agentscript/mapDeployErrors request.
+#[derive(Debug, serde::Deserialize)]
+struct MapDeployErrorsParams {
+    uri: String,
+    /// `componentFailures` from `sf project deploy start --json`.
+    errors: Vec<DeployFailure>,
 }
 
-#[derive(Debug, serde::Serialize)]
-struct VarChange {
-    name: String,
-    old_value: serde_json::Value,
-    new_value: serde_json::Value,
+/// A deploy component failure. The CLI reports line numbers as either
+/// numbers or strings.
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DeployFailure {
+    file_name: Option<String>,
+    component_type: Option<String>,
+    full_name: Option<String>,
+    line_number: Option<serde_json::Value>,
+    problem: String,
 }
 
-#[derive(Debug, serde::Serialize)]
-struct ActionInvoke {
-    action_name: String,
-    inputs: serde_json::Value,
-    outputs: serde_json::Value,
+/// Parameters for agentscript/debugHistory request.
+#[derive(Debug, serde::Deserialize)]
+struct DebugHistoryParams {
+    /// Only return the history of this document.
+    #[serde(default)]
+    uri: Option<String>,
+}
+
+/// Parameters for agentscript/simulate request.
+#[derive(Debug, serde::Deserialize)]
+struct SimulateParams {
+    uri: String,
+    #[serde(default)]
+    mock_data: MockData,
 }
 
 impl Backend {
-    /// Handle agentscript/getGraph — returns the GraphRepr JSON for the given document.
+    /// Handle agentscript/getGraph — returns the GraphRepr JSON for the given
+    /// document, or for one of its topics when `topic` is set.
     async fn handle_get_graph(
         &self,
         params: serde_json::Value,
@@ -1425,16 +3053,21 @@ impl Backend {
             .parse()
             .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(format!("{}", e)))?;
 
-        let docs = self.documents.read().await;
-        let doc = docs
-            .get(&uri)
+        let doc = self
+            .snapshot(&uri)
+            .await
             .ok_or_else(|| tower_lsp::jsonrpc::Error::invalid_params("Document not found"))?;
 
         let graph = doc.graph.as_ref().ok_or_else(|| {
             tower_lsp::jsonrpc::Error::invalid_params("No graph available (parse errors?)")
         })?;
 
-        let repr = GraphRepr::from(graph);
+        let repr = match &params.topic {
+            Some(topic) => GraphRepr::from(&graph.subgraph_for_topic(topic).ok_or_else(|| {
+                tower_lsp::jsonrpc::Error::invalid_params(format!("Unknown topic '{}'", topic))
+            })?),
+            None => GraphRepr::from(graph),
+        };
         serde_json::to_value(&repr).map_err(|e| tower_lsp::jsonrpc::Error {
             code: tower_lsp::jsonrpc::ErrorCode::InternalError,
             message: e.to_string().into(),
@@ -1455,9 +3088,9 @@ impl Backend {
             .parse()
             .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(format!("{}", e)))?;
 
-        let docs = self.documents.read().await;
-        let doc = docs
-            .get(&uri)
+        let doc = self
+            .snapshot(&uri)
+            .await
             .ok_or_else(|| tower_lsp::jsonrpc::Error::invalid_params("Document not found"))?;
 
         let ast = doc.ast.as_ref().ok_or_else(|| {
@@ -1473,15 +3106,13 @@ impl Backend {
         })
     }
 
-    /// Handle agentscript/simulate — runs a dry simulation of the agent.
-    ///
-    /// This performs a static analysis simulation (walk through start_agent and
-    /// first topic's before_reasoning/after_reasoning without LLM calls).
-    async fn handle_simulate(
+    /// Handle agentscript/health — returns the weighted health report of the
+    /// document, for a status bar item showing one score per agent.
+    async fn handle_health(
         &self,
         params: serde_json::Value,
     ) -> tower_lsp::jsonrpc::Result<serde_json::Value> {
-        let params: SimulateParams = serde_json::from_value(params)
+        let params: HealthParams = serde_json::from_value(params)
             .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?;
 
         let uri: Url = params
@@ -1489,215 +3120,171 @@ impl Backend {
             .parse()
             .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(format!("{}", e)))?;
 
-        let docs = self.documents.read().await;
-        let doc = docs
-            .get(&uri)
+        let doc = self
+            .snapshot(&uri)
+            .await
             .ok_or_else(|| tower_lsp::jsonrpc::Error::invalid_params("Document not found"))?;
 
         let ast = doc.ast.as_ref().ok_or_else(|| {
             tower_lsp::jsonrpc::Error::invalid_params("No AST available (parse errors?)")
         })?;
 
-        // Build a static simulation trace by walking the AST
-        let result = build_static_simulation(ast, &params.mock_data);
-        serde_json::to_value(&result).map_err(|e| tower_lsp::jsonrpc::Error {
+        let options = HealthOptions {
+            coverage: params.coverage,
+            lint: self.lint.read().await.clone(),
+            ..HealthOptions::default()
+        };
+        let report = health_report(ast, &options);
+        serde_json::to_value(&report).map_err(|e| tower_lsp::jsonrpc::Error {
             code: tower_lsp::jsonrpc::ErrorCode::InternalError,
             message: e.to_string().into(),
             data: None,
         })
     }
-}
 
-/// Walk the AST to produce a static execution trace (no runtime needed).
-/// This shows the structural flow without actually executing expressions.
-fn build_static_simulation(ast: &AgentFile, _mock_data: &serde_json::Value) -> SimulationResult {
-    let mut steps = Vec::new();
-    let mut topic_transitions = Vec::new();
+    /// Handle agentscript/parseProfile — re-parses the document block by block
+    /// and returns per-block timing and token counts. The table is also
+    /// written to the server log so it can be pasted into bug reports.
+    async fn handle_parse_profile(
+        &self,
+        params: serde_json::Value,
+    ) -> tower_lsp::jsonrpc::Result<serde_json::Value> {
+        let params: ParseProfileParams = serde_json::from_value(params)
+            .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?;
 
-    // Walk start_agent
-    if let Some(start) = &ast.start_agent {
-        let sa = &start.node;
-        topic_transitions.push(format!("start_agent:{}", sa.name.node));
+        let uri: Url = params
+            .uri
+            .parse()
+            .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(format!("{}", e)))?;
 
-        // before_reasoning statements
-        if let Some(before) = &sa.before_reasoning {
-            for stmt in &before.node.statements {
-                steps.push(statement_to_step("before_reasoning", &stmt.node));
-            }
-        }
+        let source = {
+            let docs = self.documents.read().await;
+            docs.get(&uri)
+                .ok_or_else(|| tower_lsp::jsonrpc::Error::invalid_params("Document not found"))?
+                .source
+                .clone()
+        };
 
-        // reasoning actions
-        if let Some(reasoning) = &sa.reasoning {
-            let r = &reasoning.node;
-            if let Some(actions) = &r.actions {
-                for action in &actions.node {
-                    let a = &action.node;
-                    steps.push(SimulationStep {
-                        phase: "reasoning".to_string(),
-                        statement_type: "reasoning_action".to_string(),
-                        detail: format!(
-                            "{}: {}",
-                            a.name.node,
-                            a.description
-                                .as_ref()
-                                .map(|d| d.node.as_str())
-                                .unwrap_or("")
-                        ),
-                        variable_changes: vec![],
-                        action_invocations: vec![],
-                    });
-                }
-            }
-        }
+        let profile = parse_with_profile(&source);
+        self.client
+            .log_message(MessageType::INFO, format!("Parse profile for {}:\n{}", uri, profile))
+            .await;
 
-        // after_reasoning statements
-        if let Some(after) = &sa.after_reasoning {
-            for stmt in &after.node.statements {
-                steps.push(statement_to_step("after_reasoning", &stmt.node));
-            }
-        }
+        serde_json::to_value(&profile).map_err(|e| tower_lsp::jsonrpc::Error {
+            code: tower_lsp::jsonrpc::ErrorCode::InternalError,
+            message: e.to_string().into(),
+            data: None,
+        })
     }
 
-    // Walk topics
-    for topic in &ast.topics {
-        let t = &topic.node;
-        let name = t.name.node.clone();
-        topic_transitions.push(name.clone());
+    /// Handle agentscript/debugHistory — returns the recent versions and
+    /// published diagnostic sets of one document, or of every document,
+    /// oldest first.
+    async fn handle_debug_history(
+        &self,
+        params: serde_json::Value,
+    ) -> tower_lsp::jsonrpc::Result<serde_json::Value> {
+        let params: DebugHistoryParams = serde_json::from_value(params)
+            .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?;
+        let uri: Option<Url> = params
+            .uri
+            .map(|uri| uri.parse())
+            .transpose()
+            .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(format!("{}", e)))?;
 
-        if let Some(before) = &t.before_reasoning {
-            for stmt in &before.node.statements {
-                steps.push(statement_to_step(&format!("{}:before_reasoning", name), &stmt.node));
-            }
-        }
+        Ok(self.history.lock().unwrap().dump(uri.as_ref()))
+    }
 
-        if let Some(reasoning) = &t.reasoning {
-            let r = &reasoning.node;
-            if let Some(actions) = &r.actions {
-                for action in &actions.node {
-                    let a = &action.node;
-                    steps.push(SimulationStep {
-                        phase: format!("{}:reasoning", name),
-                        statement_type: "reasoning_action".to_string(),
-                        detail: format!(
-                            "{}: {}",
-                            a.name.node,
-                            a.description
-                                .as_ref()
-                                .map(|d| d.node.as_str())
-                                .unwrap_or("")
-                        ),
-                        variable_changes: vec![],
-                        action_invocations: vec![],
-                    });
+    /// Handle agentscript/mapDeployErrors — translates Salesforce deploy
+    /// failures for the metadata exported from this document back into
+    /// diagnostics on the AgentScript source. Failures that can't be traced
+    /// to a definition are placed at the top of the file.
+    async fn handle_map_deploy_errors(
+        &self,
+        params: serde_json::Value,
+    ) -> tower_lsp::jsonrpc::Result<serde_json::Value> {
+        let params: MapDeployErrorsParams = serde_json::from_value(params)
+            .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?;
+
+        let uri: Url = params
+            .uri
+            .parse()
+            .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(format!("{}", e)))?;
+
+        let doc = self
+            .snapshot(&uri)
+            .await
+            .ok_or_else(|| tower_lsp::jsonrpc::Error::invalid_params("Document not found"))?;
+        let Some(ast) = &doc.ast else {
+            return Ok(serde_json::json!([]));
+        };
+        let metadata = to_salesforce_metadata(ast);
+
+        let diagnostics: Vec<Diagnostic> = params
+            .errors
+            .iter()
+            .map(|failure| {
+                let line = failure.line_number.as_ref().and_then(|l| {
+                    l.as_u64()
+                        .map(|n| n as usize)
+                        .or_else(|| l.as_str()?.parse().ok())
+                });
+                let span = failure
+                    .file_name
+                    .as_deref()
+                    .and_then(|f| metadata.source_span(f, line))
+                    .or_else(|| {
+                        metadata.component_span(
+                            failure.component_type.as_deref()?,
+                            failure.full_name.as_deref()?,
+                        )
+                    })
+                    .unwrap_or(0..0);
+                Diagnostic {
+                    range: span_to_range(&doc.source, span),
+                    severity: Some(DiagnosticSeverity::ERROR),
+                    source: Some("salesforce deploy".to_string()),
+                    message: failure.problem.clone(),
+                    ..Default::default()
                 }
-            }
-        }
+            })
+            .collect();
 
-        if let Some(after) = &t.after_reasoning {
-            for stmt in &after.node.statements {
-                steps.push(statement_to_step(&format!("{}:after_reasoning", name), &stmt.node));
-            }
-        }
+        serde_json::to_value(&diagnostics).map_err(|e| tower_lsp::jsonrpc::Error {
+            code: tower_lsp::jsonrpc::ErrorCode::InternalError,
+            message: e.to_string().into(),
+            data: None,
+        })
     }
 
-    SimulationResult {
-        steps,
-        final_context: serde_json::json!({}),
-        outcome: "static_analysis".to_string(),
-        topic_transitions,
-    }
-}
+    /// Handle agentscript/simulate — dry-runs the agent against mock data.
+    async fn handle_simulate(
+        &self,
+        params: serde_json::Value,
+    ) -> tower_lsp::jsonrpc::Result<serde_json::Value> {
+        let params: SimulateParams = serde_json::from_value(params)
+            .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?;
 
-fn statement_to_step(phase: &str, stmt: &Stmt) -> SimulationStep {
-    match stmt {
-        Stmt::Set { target, value } => SimulationStep {
-            phase: phase.to_string(),
-            statement_type: "set".to_string(),
-            detail: format!(
-                "set {} = {}",
-                reference_to_string(&target.node),
-                expr_preview(&value.node)
-            ),
-            variable_changes: vec![VarChange {
-                name: reference_to_string(&target.node),
-                old_value: serde_json::Value::Null,
-                new_value: serde_json::json!("(expression)"),
-            }],
-            action_invocations: vec![],
-        },
-        Stmt::Run {
-            action,
-            with_clauses,
-            ..
-        } => {
-            let inputs: Vec<String> = with_clauses
-                .iter()
-                .map(|w| {
-                    let val = match &w.node.value.node {
-                        WithValue::Expr(e) => expr_preview(e),
-                    };
-                    format!("{}={}", w.node.param.node, val)
-                })
-                .collect();
-            SimulationStep {
-                phase: phase.to_string(),
-                statement_type: "run".to_string(),
-                detail: format!(
-                    "run {} with [{}]",
-                    reference_to_string(&action.node),
-                    inputs.join(", ")
-                ),
-                variable_changes: vec![],
-                action_invocations: vec![ActionInvoke {
-                    action_name: reference_to_string(&action.node),
-                    inputs: serde_json::json!(inputs),
-                    outputs: serde_json::json!({}),
-                }],
-            }
-        }
-        Stmt::If {
-            condition,
-            then_block,
-            ..
-        } => SimulationStep {
-            phase: phase.to_string(),
-            statement_type: "if".to_string(),
-            detail: format!(
-                "if {} → {} statements",
-                expr_preview(&condition.node),
-                then_block.len()
-            ),
-            variable_changes: vec![],
-            action_invocations: vec![],
-        },
-        Stmt::Transition { target } => SimulationStep {
-            phase: phase.to_string(),
-            statement_type: "transition".to_string(),
-            detail: format!("transition to {}", reference_to_string(&target.node)),
-            variable_changes: vec![],
-            action_invocations: vec![],
-        },
-    }
-}
+        let uri: Url = params
+            .uri
+            .parse()
+            .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(format!("{}", e)))?;
 
-fn reference_to_string(r: &Reference) -> String {
-    format!("@{}.{}", r.namespace, r.path.join("."))
-}
+        let doc = self
+            .snapshot(&uri)
+            .await
+            .ok_or_else(|| tower_lsp::jsonrpc::Error::invalid_params("Document not found"))?;
+
+        let ast = doc.ast.as_ref().ok_or_else(|| {
+            tower_lsp::jsonrpc::Error::invalid_params("No AST available (parse errors?)")
+        })?;
 
-fn expr_preview(expr: &Expr) -> String {
-    match expr {
-        Expr::String(s) => format!("\"{}\"", s),
-        Expr::Number(n) => n.to_string(),
-        Expr::Bool(b) => b.to_string(),
-        Expr::None => "None".to_string(),
-        Expr::Reference(r) => reference_to_string(r),
-        Expr::BinOp { op, left, right } => format!(
-            "{} {} {}",
-            expr_preview(&left.node),
-            format!("{:?}", op).to_lowercase(),
-            expr_preview(&right.node)
-        ),
-        _ => "(expr)".to_string(),
+        let result = simulation::simulate(ast, &params.mock_data);
+        serde_json::to_value(&result).map_err(|e| tower_lsp::jsonrpc::Error {
+            code: tower_lsp::jsonrpc::ErrorCode::InternalError,
+            message: e.to_string().into(),
+            data: None,
+        })
     }
 }
 
@@ -1713,59 +3300,15 @@ fn span_to_range(text: &str, span: std::ops::Range<usize>) -> Range {
 }
 
 fn offset_to_position(text: &str, offset: usize) -> Position {
-    let offset = offset.min(text.len());
-    let mut line = 0u32;
-    let mut last_line_start = 0;
-    for (i, c) in text[..offset].char_indices() {
-        if c == '\n' {
-            line += 1;
-            last_line_start = i + 1;
-        }
-    }
+    let location = text_pos::location(text, offset, Encoding::Utf16);
     Position {
-        line,
-        character: text[last_line_start..offset].chars().count() as u32,
+        line: location.line,
+        character: location.column,
     }
 }
 
 fn position_to_offset(text: &str, pos: Position) -> usize {
-    let mut current_line = 0u32;
-    let mut line_start = 0;
-    for (i, c) in text.char_indices() {
-        if current_line == pos.line {
-            // Walk pos.character chars from line_start
-            let mut chars = text[line_start..].char_indices();
-            for _ in 0..pos.character {
-                if chars.next().is_none() {
-                    return text.len();
-                }
-            }
-            return if let Some((ci, _)) = chars.next() {
-                line_start + ci
-            } else {
-                text.len()
-            };
-        }
-        if c == '\n' {
-            current_line += 1;
-            line_start = i + 1;
-        }
-    }
-    // If target line is the last line (no trailing newline)
-    if current_line == pos.line {
-        let mut chars = text[line_start..].char_indices();
-        for _ in 0..pos.character {
-            if chars.next().is_none() {
-                return text.len();
-            }
-        }
-        return if let Some((ci, _)) = chars.next() {
-            line_start + ci
-        } else {
-            text.len()
-        };
-    }
-    text.len()
+    text_pos::offset(text, text_pos::Location::new(pos.line, pos.character), Encoding::Utf16)
 }
 
 /// Context for reference completions.
@@ -1822,50 +3365,6 @@ fn find_reference_at_offset(source: &str, offset: usize) -> Option<Reference> {
     Some(Reference { namespace, path })
 }
 
-/// Find the symbol name at cursor (definition or @reference).
-fn find_symbol_name_at_offset(ast: &AgentFile, source: &str, offset: usize) -> Option<String> {
-    // Variable definitions
-    if let Some(vars) = &ast.variables {
-        for v in &vars.node.variables {
-            if v.node.name.span.contains(&offset) {
-                return Some(v.node.name.node.clone());
-            }
-        }
-    }
-
-    // Topic names
-    for t in &ast.topics {
-        if t.node.name.span.contains(&offset) {
-            return Some(t.node.name.node.clone());
-        }
-        if let Some(actions) = &t.node.actions {
-            for a in &actions.node.actions {
-                if a.node.name.span.contains(&offset) {
-                    return Some(a.node.name.node.clone());
-                }
-            }
-        }
-    }
-
-    // Start_agent action defs
-    if let Some(sa) = &ast.start_agent {
-        if let Some(actions) = &sa.node.actions {
-            for a in &actions.node.actions {
-                if a.node.name.span.contains(&offset) {
-                    return Some(a.node.name.node.clone());
-                }
-            }
-        }
-    }
-
-    // @references
-    if let Some(reference) = find_reference_at_offset(source, offset) {
-        return reference.path.into_iter().next();
-    }
-
-    None
-}
-
 /// Actions visible at a given offset (same topic/start_agent scope).
 fn find_actions_at_offset(ast: &AgentFile, offset: usize) -> Vec<&Spanned<ActionDef>> {
     if let Some(sa) = &ast.start_agent {
@@ -1901,6 +3400,13 @@ fn collect_all_action_defs(ast: &AgentFile) -> Vec<&Spanned<ActionDef>> {
     all
 }
 
+/// Render a definition's `#` doc comment as a hover section.
+fn doc_comment_markdown(doc: &Option<Spanned<String>>) -> String {
+    doc.as_ref()
+        .map(|d| format!("\n\n---\n\n{}", render_markdown(&d.node)))
+        .unwrap_or_default()
+}
+
 fn hover_actions_block(
     source: &str,
     actions: &Option<Spanned<ActionsBlock>>,
@@ -1914,8 +3420,9 @@ fn hover_actions_block(
         if action.span.contains(&offset) {
             let mut md = format!("**Action** `{}`", action.node.name.node);
             if let Some(desc) = &action.node.description {
-                md.push_str(&format!("\n\n{}", desc.node));
+                md.push_str(&format!("\n\n{}", render_markdown(&desc.node)));
             }
+            md.push_str(&doc_comment_markdown(&action.node.doc));
             if let Some(target) = &action.node.target {
                 md.push_str(&format!("\n\n**Target:** `{}`", target.node));
             }
@@ -1964,7 +3471,7 @@ fn hover_reasoning_block(
                 let mut md = format!("**Reasoning Action** `{}`", action.node.name.node);
                 md.push_str(&format!("\n\n**Target:** `{:?}`", action.node.target.node));
                 if let Some(desc) = &action.node.description {
-                    md.push_str(&format!("\n\n{}", desc.node));
+                    md.push_str(&format!("\n\n{}", render_markdown(&desc.node)));
                 }
                 if let Some(avail) = &action.node.available_when {
                     md.push_str(&format!("\n\n**Available when:** `{:?}`", avail.node));
@@ -1979,6 +3486,22 @@ fn hover_reasoning_block(
             }
         }
     }
+    if let Some(instructions) = &reasoning.node.instructions {
+        // References inside instructions keep their own hover
+        if instructions.span.contains(&offset) && find_reference_at_offset(source, offset).is_none()
+        {
+            return Some(Hover {
+                contents: HoverContents::Markup(MarkupContent {
+                    kind: MarkupKind::Markdown,
+                    value: format!(
+                        "**Instructions**\n\n{}",
+                        render_instructions(&instructions.node)
+                    ),
+                }),
+                range: Some(span_to_range(source, instructions.span.clone())),
+            });
+        }
+    }
     None
 }
 
@@ -1995,14 +3518,18 @@ fn hover_reference_at_offset(ast: &AgentFile, source: &str, offset: usize) -> Op
                             .node
                             .description
                             .as_ref()
-                            .map(|d| format!("\n\n{}", d.node))
+                            .map(|d| format!("\n\n{}", render_markdown(&d.node)))
                             .unwrap_or_default();
                         return Some(Hover {
                             contents: HoverContents::Markup(MarkupContent {
                                 kind: MarkupKind::Markdown,
                                 value: format!(
-                                    "**Variable** `{}`\n\n**Kind:** {:?}  \n**Type:** `{:?}`{}",
-                                    name, v.node.kind, v.node.ty.node, desc
+                                    "**Variable** `{}`\n\n**Kind:** {:?}  \n**Type:** `{:?}`{}{}",
+                                    name,
+                                    v.node.kind,
+                                    v.node.ty.node,
+                                    desc,
+                                    doc_comment_markdown(&v.node.doc)
                                 ),
                             }),
                             range: None,
@@ -2018,12 +3545,17 @@ fn hover_reference_at_offset(ast: &AgentFile, source: &str, offset: usize) -> Op
                         .node
                         .description
                         .as_ref()
-                        .map(|d| format!("\n\n{}", d.node))
+                        .map(|d| format!("\n\n{}", render_markdown(&d.node)))
                         .unwrap_or_default();
                     return Some(Hover {
                         contents: HoverContents::Markup(MarkupContent {
                             kind: MarkupKind::Markdown,
-                            value: format!("**Topic** `{}`{}", name, desc),
+                            value: format!(
+                                "**Topic** `{}`{}{}",
+                                name,
+                                desc,
+                                doc_comment_markdown(&t.node.doc)
+                            ),
                         }),
                         range: None,
                     });
@@ -2036,8 +3568,9 @@ fn hover_reference_at_offset(ast: &AgentFile, source: &str, offset: usize) -> Op
                 if &a.node.name.node == name {
                     let mut md = format!("**Action** `{}`", name);
                     if let Some(desc) = &a.node.description {
-                        md.push_str(&format!("\n\n{}", desc.node));
+                        md.push_str(&format!("\n\n{}", render_markdown(&desc.node)));
                     }
+                    md.push_str(&doc_comment_markdown(&a.node.doc));
                     if let Some(target) = &a.node.target {
                         md.push_str(&format!("\n\n**Target:** `{}`", target.node));
                     }
@@ -2081,13 +3614,515 @@ fn hover_reference_at_offset(ast: &AgentFile, source: &str, offset: usize) -> Op
 async fn main() {
     env_logger::init();
 
+    // `--daemon --socket <path>` serves tools over a socket instead of an
+    // editor over stdio, and `--cache-dir <path>` keeps diagnostics on disk
+    // between runs; other arguments (such as `--stdio`) are ignored
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let option = |name: &str| {
+        args.iter()
+            .position(|arg| arg == name)
+            .and_then(|i| args.get(i + 1))
+    };
+    let analysis = Arc::new(std::sync::Mutex::new(match option("--cache-dir") {
+        Some(dir) => AnalysisCache::with_dir(dir),
+        None => AnalysisCache::new(),
+    }));
+
+    if args.iter().any(|arg| arg == "--daemon") {
+        let Some(socket) = option("--socket") else {
+            eprintln!("--daemon needs --socket <path>");
+            std::process::exit(2);
+        };
+        if let Err(err) = daemon::serve(std::path::Path::new(socket), analysis).await {
+            eprintln!("{}: {}", socket, err);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     let stdin = tokio::io::stdin();
     let stdout = tokio::io::stdout();
 
-    let (service, socket) = LspService::build(Backend::new)
+    let (service, socket) = LspService::build(|client| Backend::new(client, analysis))
         .custom_method("agentscript/getGraph", Backend::handle_get_graph)
         .custom_method("agentscript/getDependencies", Backend::handle_get_dependencies)
+        .custom_method("agentscript/health", Backend::handle_health)
         .custom_method("agentscript/simulate", Backend::handle_simulate)
+        .custom_method("agentscript/parseProfile", Backend::handle_parse_profile)
+        .custom_method("agentscript/mapDeployErrors", Backend::handle_map_deploy_errors)
+        .custom_method("agentscript/debugHistory", Backend::handle_debug_history)
         .finish();
     Server::new(stdin, stdout, socket).serve(service).await;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `source` with the variable declaration for `name` inserted.
+    fn declare(source: &str, name: &str) -> Option<String> {
+        let doc = DocumentState::new(source.to_string());
+        let edit = variable_declaration_edit(&doc, doc.ast.as_ref().unwrap(), name)?;
+        let at = position_to_offset(source, edit.range.start);
+        Some(format!("{}{}{}", &source[..at], edit.new_text, &source[at..]))
+    }
+
+    fn variables(source: &str) -> Vec<String> {
+        let ast = busbar_sf_agentscript::parse(source).unwrap();
+        ast.variables
+            .iter()
+            .flat_map(|v| &v.node.variables)
+            .map(|v| v.node.name.node.clone())
+            .collect()
+    }
+
+    const TOPIC: &str = "topic main:\n   description: \"Main\"\n";
+
+    #[test]
+    fn test_declare_variable_without_block() {
+        let declared = declare(TOPIC, "step").unwrap();
+        assert!(declared.starts_with("variables:\n   step: mutable string"));
+        assert_eq!(variables(&declared), ["step"]);
+    }
+
+    #[test]
+    fn test_declare_variable_in_empty_block() {
+        let source = format!("variables:\n\n{}", TOPIC);
+        let declared = declare(&source, "step").unwrap();
+        assert!(declared.starts_with("variables:\n   step: mutable string"));
+        assert_eq!(variables(&declared), ["step"]);
+    }
+
+    #[test]
+    fn test_declare_variable_skips_declared_names() {
+        let source = format!("variables:\n   step: mutable number = 0\n\n{}", TOPIC);
+        assert_eq!(declare(&source, "step"), None);
+        // A prefix of a declared name is still being typed
+        assert_eq!(declare(&source, "st"), None);
+        assert_eq!(declare(&source, "step-"), None);
+        assert_eq!(variables(&declare(&source, "count").unwrap()), ["step", "count"]);
+    }
+
+    #[test]
+    fn test_declare_variable_above_doc_comment() {
+        let source = format!("# The main topic\n{}", TOPIC);
+        let declared = declare(&source, "step").unwrap();
+        let ast = busbar_sf_agentscript::parse(&declared).unwrap();
+        assert_eq!(variables(&declared), ["step"]);
+        assert_eq!(ast.topics[0].node.doc.as_ref().unwrap().node, "The main topic");
+    }
+
+    /// `source` with the edits of a workspace edit applied.
+    fn apply_workspace_edit(source: &str, edit: &WorkspaceEdit) -> String {
+        let mut edits: Vec<_> = edit
+            .changes
+            .iter()
+            .flat_map(|c| c.values().flatten())
+            .collect();
+        edits.sort_by_key(|e| (e.range.start.line, e.range.start.character));
+        let mut result = source.to_string();
+        for edit in edits.into_iter().rev() {
+            let start = position_to_offset(source, edit.range.start);
+            let end = position_to_offset(source, edit.range.end);
+            result.replace_range(start..end, &edit.new_text);
+        }
+        result
+    }
+
+    /// The quick fixes offered at `needle` in `source`, as (title, fixed source).
+    fn quick_fixes_at(source: &str, needle: &str) -> Vec<(String, String)> {
+        let doc = DocumentState::new(source.to_string());
+        let uri = Url::parse("file:///agent.agent").unwrap();
+        let at = offset_to_position(source, source.find(needle).unwrap());
+        get_code_actions(&doc, &uri, Range::new(at, at))
+            .into_iter()
+            .filter_map(|action| match action {
+                CodeActionOrCommand::CodeAction(CodeAction {
+                    title,
+                    edit: Some(edit),
+                    ..
+                }) => Some((title, apply_workspace_edit(source, &edit))),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn quick_fix_at(source: &str, needle: &str, title: &str) -> Option<String> {
+        quick_fixes_at(source, needle)
+            .into_iter()
+            .find(|(t, _)| t.starts_with(title))
+            .map(|(_, fixed)| fixed)
+    }
+
+    /// The graph of `source`, which must parse without errors.
+    fn reparse(source: &str) -> (AgentFile, RefGraph) {
+        let ast = busbar_sf_agentscript::parse(source).unwrap();
+        let graph = RefGraph::from_ast(&ast).unwrap();
+        (ast, graph)
+    }
+
+    const UNUSED: &str = "variables:
+   spare: mutable string = \"\"
+      description: \"Spare\"
+   step: mutable number = 0
+      description: \"Step\"
+   flag: mutable boolean = False
+      description: \"Flag\"
+
+start_agent main:
+   description: \"Start\"
+   reasoning:
+      instructions: \"Route\"
+      actions:
+         go: @utils.transition to @topic.help
+            description: \"Help\"
+            available when @variables.flag
+
+topic help:
+   description: \"Help\"
+   before_reasoning:
+      set @variables.step = 1
+   reasoning:
+      instructions: \"Help\"
+";
+
+    #[test]
+    fn test_quick_fix_removes_unused_variable() {
+        let fixed = quick_fix_at(UNUSED, "spare", "Remove unused variable").unwrap();
+        let (ast, graph) = reparse(&fixed);
+        assert_eq!(variables(&fixed), ["step", "flag"]);
+        assert!(graph.run_pass(PassId::UnresolvedReferences).is_empty());
+        assert_eq!(ast.topics.len(), 1);
+    }
+
+    #[test]
+    fn test_quick_fix_keeps_written_or_conditional_variables() {
+        // step is only written and flag only read by `available when`
+        assert_eq!(quick_fix_at(UNUSED, "step:", "Remove unused variable 'step'"), None);
+        assert_eq!(quick_fix_at(UNUSED, "flag:", "Remove unused variable 'flag'"), None);
+        let (_, graph) = reparse(UNUSED);
+        let mut unused: Vec<_> = graph
+            .find_unused_variables()
+            .into_iter()
+            .filter_map(|e| match e {
+                ValidationError::UnusedVariable { name, .. } => Some(name),
+                _ => None,
+            })
+            .collect();
+        unused.sort();
+        assert_eq!(unused, ["spare", "step"]);
+    }
+
+    #[test]
+    fn test_quick_fix_creates_action_stub() {
+        let source = "topic help:
+   description: \"Help\"
+   reasoning:
+      instructions: \"Help\"
+      actions:
+         go: @actions.lookup
+            description: \"Look up\"
+";
+        let fixed = quick_fix_at(source, "@actions.lookup", "Create action definition").unwrap();
+        let (ast, graph) = reparse(&fixed);
+        assert!(graph.run_pass(PassId::UnresolvedReferences).is_empty());
+        let actions = &ast.topics[0].node.actions.as_ref().unwrap().node.actions;
+        assert_eq!(actions[0].node.name.node, "lookup");
+    }
+
+    #[test]
+    fn test_quick_fix_converts_legacy_connections() {
+        let source = "connections:
+   messaging:
+      outbound_route_type: \"OmniChannelFlow\"
+      outbound_route_name: \"SpecialistQueue\"
+
+topic help:
+   description: \"Help\"
+";
+        let fixed =
+            quick_fix_at(source, "messaging", "Convert to 'connection <name>:' blocks").unwrap();
+        assert!(fixed.starts_with("connection messaging:\n"), "{}", fixed);
+        let (ast, _) = reparse(&fixed);
+        assert_eq!(ast.connections.len(), 1);
+        assert_eq!(ast.connections[0].node.name.node, "messaging");
+        assert_eq!(ast.topics.len(), 1);
+    }
+
+    #[test]
+    fn test_resolved_description_edit_reparses() {
+        let source = "topic help:
+   reasoning:
+      instructions: \"Help\"
+";
+        let doc = DocumentState::new(source.to_string());
+        let uri = Url::parse("file:///agent.agent").unwrap();
+        let at = offset_to_position(source, source.find("help").unwrap());
+        let data = get_code_actions(&doc, &uri, Range::new(at, at))
+            .into_iter()
+            .find_map(|action| match action {
+                CodeActionOrCommand::CodeAction(CodeAction {
+                    title,
+                    data: Some(data),
+                    ..
+                }) if title.starts_with("Add description") => {
+                    serde_json::from_value::<DescriptionData>(data).ok()
+                }
+                _ => None,
+            })
+            .unwrap();
+        let edit = resolve_description_edit(&doc, &data, &TemplateDescriptions).unwrap();
+        let fixed = apply_workspace_edit(source, &edit);
+        let (ast, _) = reparse(&fixed);
+        assert!(ast.topics[0].node.description.is_some());
+        assert!(missing_descriptions(&ast).is_empty());
+    }
+
+    #[test]
+    fn test_signature_help_active_parameter() {
+        let source = "topic main:
+   description: \"Main\"
+   actions:
+      lookup:
+         description: \"Look up\"
+         inputs:
+            order_id: string
+               description: \"Order\"
+            email: string
+               description: \"Email\"
+            verbose: boolean
+               description: \"Verbose\"
+         target: \"flow://Lookup\"
+   reasoning:
+      instructions: \"Help\"
+      actions:
+         find: @actions.lookup
+            description: \"Find\"
+            with order_id = ...
+            with email = ...
+            with verbose = True
+";
+        let doc = DocumentState::new(source.to_string());
+        let active = |after: &str| {
+            let offset = source.find(after).unwrap() + after.len();
+            let help = get_signature_help(&doc, offset_to_position(source, offset))?;
+            assert_eq!(
+                help.signatures[0].label,
+                "lookup(order_id: String, email: String, verbose: Boolean)"
+            );
+            help.active_parameter
+        };
+
+        // The first unbound input, or the one whose name is being typed
+        assert_eq!(active("with "), Some(0));
+        assert_eq!(active("with ord"), Some(0));
+        assert_eq!(active("= ...\n            with "), Some(1));
+        assert_eq!(active("with em"), Some(1));
+        assert_eq!(active("with v"), Some(2));
+        // Not on a `with` line
+        assert_eq!(active("description: \"Find"), None);
+        assert_eq!(active("with"), None);
+    }
+
+    #[test]
+    fn test_document_highlight_kinds() {
+        let source = "variables:
+   step: mutable number = 0
+      description: \"Step\"
+
+topic main:
+   description: \"Main\"
+   before_reasoning:
+      set @variables.step = @variables.step + 1
+   reasoning:
+      instructions: ->
+         | Step {!@variables.step}
+";
+        let doc = DocumentState::new(source.to_string());
+        let highlights = |needle: &str| {
+            let at = offset_to_position(source, source.find(needle).unwrap() + needle.len());
+            get_document_highlights(&doc, at)
+                .into_iter()
+                .map(|h| (h.range.start.line, h.range.start.character, h.kind.unwrap()))
+                .collect::<Vec<_>>()
+        };
+        let expected = [
+            (1, 3, DocumentHighlightKind::TEXT),
+            (7, 21, DocumentHighlightKind::WRITE),
+            (7, 39, DocumentHighlightKind::READ),
+            (10, 29, DocumentHighlightKind::READ),
+        ];
+
+        // From the `set` target and from a read, the same occurrences
+        assert_eq!(highlights("set @variables.st"), expected);
+        assert_eq!(highlights("{!@variables.st"), expected);
+        assert!(highlights("description: \"Ma").is_empty());
+    }
+
+    #[test]
+    fn test_text_shift_moves_spans_past_the_edit() {
+        let shift = TextShift::between("abc def ghi", "abc de_f ghi");
+        assert_eq!(shift.span(0..3), Some(0..3));
+        assert_eq!(shift.span(8..11), Some(9..12));
+        assert_eq!(shift.span(4..7), None);
+        assert_eq!(TextShift::between("aaa", "aaaa").span(0..3), Some(0..3));
+    }
+
+    #[test]
+    fn test_cheap_diagnostics_keep_whole_graph_findings() {
+        let source = "start_agent main:\n   description: \"Start\"\n\ntopic orphan:\n   description: \"Never reached\"\n";
+        let uri = Url::parse("file:///agent.agent").unwrap();
+        let cache = DiagnosticsCache::default();
+        let expensive = ExpensiveFindings::default();
+        let lint = LintConfig::default();
+        let diagnose = |source: &str, passes| {
+            let doc = DocumentState::new(source.to_string());
+            compute_diagnostics(&uri, &doc, passes, None, &lint, &cache, &expensive)
+        };
+        let unreachable = |diagnostics: &[Diagnostic]| {
+            diagnostics
+                .iter()
+                .find(|d| d.message.contains("orphan"))
+                .map(|d| d.range.start.line)
+        };
+
+        // Without a full run there is nothing to keep
+        assert_eq!(unreachable(&diagnose(source, PassId::CHEAP)), None);
+        assert_eq!(unreachable(&diagnose(source, PassId::ALL)), Some(3));
+
+        // An edit above moves the kept finding down with its topic
+        let edited = format!("# Agent\n\n{}", source);
+        assert_eq!(unreachable(&diagnose(&edited, PassId::CHEAP)), Some(5));
+
+        // An edit to the topic itself drops it until the next full run
+        let renamed = source.replace("orphan", "stray");
+        assert_eq!(unreachable(&diagnose(&renamed, PassId::CHEAP)), None);
+    }
+
+    /// A backend whose client isn't initialized, so publishing only records
+    /// the diagnostics in the history.
+    fn test_backend() -> (LspService<Backend>, tower_lsp::ClientSocket) {
+        let analysis = Arc::new(std::sync::Mutex::new(AnalysisCache::new()));
+        LspService::new(|client| Backend::new(client, analysis))
+    }
+
+    /// Versions of the text each published diagnostic set was computed from.
+    fn published_versions(backend: &Backend, uri: &Url) -> Vec<Option<i32>> {
+        let dump = backend.history.lock().unwrap().dump(Some(uri));
+        dump["documents"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .flat_map(|doc| doc["entries"].as_array().cloned().unwrap_or_default())
+            .filter(|entry| entry["kind"] == "diagnostics")
+            .map(|entry| entry["version"].as_i64().map(|v| v as i32))
+            .collect()
+    }
+
+    /// Wait until `count` diagnostic sets have been published for `uri`.
+    async fn wait_for_published(backend: &Backend, uri: &Url, count: usize) {
+        for _ in 0..200 {
+            if published_versions(backend, uri).len() >= count {
+                return;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        panic!("{:?}", published_versions(backend, uri));
+    }
+
+    fn open_params(uri: &Url, text: &str) -> DidOpenTextDocumentParams {
+        DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(
+                uri.clone(),
+                "agentscript".to_string(),
+                1,
+                text.to_string(),
+            ),
+        }
+    }
+
+    fn change_params(uri: &Url, version: i32, text: &str) -> DidChangeTextDocumentParams {
+        DidChangeTextDocumentParams {
+            text_document: VersionedTextDocumentIdentifier::new(uri.clone(), version),
+            content_changes: vec![TextDocumentContentChangeEvent {
+                range: None,
+                range_length: None,
+                text: text.to_string(),
+            }],
+        }
+    }
+
+    /// Longer than the debounce and full validation delays together.
+    const SETTLE: std::time::Duration = std::time::Duration::from_millis(1600);
+
+    #[tokio::test]
+    async fn test_superseded_versions_are_never_published() {
+        let (service, _socket) = test_backend();
+        let backend = service.inner();
+        let uri = Url::parse("file:///agent.agent").unwrap();
+        backend.did_open(open_params(&uri, TOPIC)).await;
+        wait_for_published(backend, &uri, 1).await;
+
+        backend
+            .did_change(change_params(&uri, 2, "topic main:\n"))
+            .await;
+        let latest = TOPIC.replace("Main", "Latest");
+        backend.did_change(change_params(&uri, 3, &latest)).await;
+        tokio::time::sleep(SETTLE).await;
+
+        // Cheap then full diagnostics for version 3; nothing for version 2
+        assert_eq!(published_versions(backend, &uri), [Some(1), Some(3), Some(3)]);
+        assert_eq!(backend.snapshot(&uri).await.unwrap().source, latest);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_reparse_keeps_the_snapshot() {
+        let (service, _socket) = test_backend();
+        let backend = service.inner();
+        let uri = Url::parse("file:///agent.agent").unwrap();
+        backend.did_open(open_params(&uri, TOPIC)).await;
+        wait_for_published(backend, &uri, 1).await;
+
+        let token = CancellationToken::new();
+        token.cancel();
+        backend
+            .clone()
+            .reparse(uri.clone(), 2, "topic other:\n".to_string(), token)
+            .await;
+        assert_eq!(backend.snapshot(&uri).await.unwrap().source, TOPIC);
+        assert_eq!(published_versions(backend, &uri), [Some(1)]);
+    }
+
+    #[tokio::test]
+    async fn test_did_close_clears_pending_work() {
+        let (service, _socket) = test_backend();
+        let backend = service.inner();
+        let uri = Url::parse("file:///agent.agent").unwrap();
+        backend.did_open(open_params(&uri, TOPIC)).await;
+        wait_for_published(backend, &uri, 1).await;
+        assert!(backend.expensive.lock().unwrap().contains_key(&uri));
+
+        backend
+            .did_change(change_params(&uri, 2, "topic main:\n"))
+            .await;
+        assert!(backend.pending_parses.lock().unwrap().contains_key(&uri));
+        backend
+            .did_close(DidCloseTextDocumentParams {
+                text_document: TextDocumentIdentifier::new(uri.clone()),
+            })
+            .await;
+        assert!(!backend.pending_parses.lock().unwrap().contains_key(&uri));
+        assert!(!backend
+            .pending_diagnostics
+            .lock()
+            .unwrap()
+            .contains_key(&uri));
+        assert!(!backend.expensive.lock().unwrap().contains_key(&uri));
+
+        // The change in flight neither reopens the document nor publishes
+        tokio::time::sleep(SETTLE).await;
+        assert!(backend.snapshot(&uri).await.is_none());
+        assert!(!backend.expensive.lock().unwrap().contains_key(&uri));
+        assert_eq!(published_versions(backend, &uri), [Some(1)]);
+    }
+}
@@ -1,4 +1,5 @@
 use busbar_sf_agentscript::ast::*;
+use busbar_sf_agentscript::text_pos::{self, Encoding};
 use tower_lsp::lsp_types::*;
 
 // Token types: indices into this array are used by the LSP protocol
@@ -36,18 +37,15 @@ pub fn compute_semantic_tokens(source: &str, ast: Option<&AgentFile>) -> Vec<Sem
     let mut raw_tokens: Vec<RawToken> = Vec::new();
 
     // 1. Comments (scan source directly)
-    for (i, line) in source.lines().enumerate() {
+    let mut line_start = 0;
+    for line in source.split_inclusive('\n') {
         let trimmed = line.trim_start();
         if trimmed.starts_with('#') {
-            let col = line.len() - trimmed.len();
-            raw_tokens.push(RawToken {
-                line: i as u32,
-                start_char: col as u32,
-                length: trimmed.len() as u32,
-                token_type: 9, // comment
-                modifiers: 0,
-            });
+            let start = line_start + line.len() - trimmed.len();
+            let end = start + trimmed.trim_end().len();
+            push_span_token(source, &mut raw_tokens, &(start..end), 9, 0); // comment
         }
+        line_start += line.len();
     }
 
     // 2. AST-based tokens
@@ -91,20 +89,6 @@ struct RawToken {
     modifiers: u32,
 }
 
-fn offset_to_line_col(source: &str, offset: usize) -> (u32, u32) {
-    let offset = offset.min(source.len());
-    let mut line = 0u32;
-    let mut last_line_start = 0;
-    for (i, c) in source[..offset].char_indices() {
-        if c == '\n' {
-            line += 1;
-            last_line_start = i + 1;
-        }
-    }
-    let col = source[last_line_start..offset].chars().count() as u32;
-    (line, col)
-}
-
 fn push_span_token(
     source: &str,
     tokens: &mut Vec<RawToken>,
@@ -115,15 +99,16 @@ fn push_span_token(
     if span.start >= span.end || span.start >= source.len() {
         return;
     }
-    let (line, col) = offset_to_line_col(source, span.start);
+    let start = text_pos::location(source, span.start, Encoding::Utf16);
     let text = &source[span.start..span.end.min(source.len())];
     // Only emit single-line tokens; for multi-line, emit first line only
     let first_line_len = text.find('\n').unwrap_or(text.len());
     if first_line_len > 0 {
+        let length: usize = text[..first_line_len].chars().map(char::len_utf16).sum();
         tokens.push(RawToken {
-            line,
-            start_char: col,
-            length: first_line_len as u32,
+            line: start.line,
+            start_char: start.column,
+            length: length as u32,
             token_type,
             modifiers,
         });
@@ -237,6 +222,11 @@ fn emit_reasoning_tokens(
         let abs = reasoning.span.start + kw_start;
         push_span_token(source, tokens, &(abs..abs + 9), 7, 0);
     }
+    if let Some(instructions) = &reasoning.node.instructions {
+        if let Instructions::Dynamic(parts) = &instructions.node {
+            emit_instruction_tokens(source, parts, tokens);
+        }
+    }
     if let Some(actions) = &reasoning.node.actions {
         for action in &actions.node {
             push_span_token(source, tokens, &action.node.name.span, 3, 1); // function + declaration
@@ -244,6 +234,143 @@ fn emit_reasoning_tokens(
     }
 }
 
+/// Tokens inside `instructions: ->`: `{!...}` interpolations and `if`/`else`
+/// blocks. References are left to [`emit_reference_tokens`].
+fn emit_instruction_tokens(
+    source: &str,
+    parts: &[Spanned<InstructionPart>],
+    tokens: &mut Vec<RawToken>,
+) {
+    for part in parts {
+        let span = &part.span;
+        match &part.node {
+            InstructionPart::Text(_) | InstructionPart::Heading { .. } => {}
+            InstructionPart::Interpolation(expr) => {
+                if source.get(span.start..span.start + 2) != Some("{!") {
+                    continue;
+                }
+                push_span_token(source, tokens, &(span.start..span.start + 2), 11, 0);
+                let mut inner_end = span.end;
+                if span.end > span.start + 2 && source.get(span.end - 1..span.end) == Some("}") {
+                    push_span_token(source, tokens, &(span.end - 1..span.end), 11, 0);
+                    inner_end -= 1;
+                }
+                emit_expr_tokens(source, expr, &(span.start + 2..inner_end), tokens);
+            }
+            InstructionPart::Conditional {
+                condition,
+                then_parts,
+                else_parts,
+            } => {
+                push_span_token(source, tokens, &(span.start..span.start + 2), 7, 0); // if
+                emit_expr_tokens(source, &condition.node, &condition.span, tokens);
+                emit_instruction_tokens(source, then_parts, tokens);
+                if let Some(else_parts) = else_parts {
+                    let from = then_parts.last().map_or(condition.span.end, |p| p.span.end);
+                    let to = else_parts.first().map_or(span.end, |p| p.span.start);
+                    if let Some(pos) = source.get(from..to).and_then(|gap| gap.find("else")) {
+                        push_span_token(source, tokens, &(from + pos..from + pos + 4), 7, 0);
+                    }
+                    emit_instruction_tokens(source, else_parts, tokens);
+                }
+            }
+        }
+    }
+}
+
+/// Literal and operator tokens of an expression. Operators aren't stored with
+/// spans, so they're recovered from the source text between operands.
+fn emit_expr_tokens(
+    source: &str,
+    expr: &Expr,
+    span: &std::ops::Range<usize>,
+    tokens: &mut Vec<RawToken>,
+) {
+    let child = |e: &Spanned<Expr>, tokens: &mut Vec<RawToken>| {
+        emit_expr_tokens(source, &e.node, &e.span, tokens)
+    };
+    match expr {
+        Expr::String(_) => push_span_token(source, tokens, span, 8, 0),
+        // String up to each `{!`, and again from after its `}`
+        Expr::Template(parts) => {
+            let mut from = span.start;
+            for part in parts.iter().filter(|p| !matches!(p.node, Expr::String(_))) {
+                let open = source
+                    .get(from..part.span.start)
+                    .and_then(|text| text.rfind("{!"))
+                    .map_or(part.span.start, |i| from + i);
+                push_span_token(source, tokens, &(from..open), 8, 0);
+                child(part, tokens);
+                from = source
+                    .get(part.span.end..span.end)
+                    .and_then(|text| text.find('}'))
+                    .map_or(part.span.end, |i| part.span.end + i + 1);
+            }
+            push_span_token(source, tokens, &(from..span.end), 8, 0);
+        }
+        Expr::Number(_) | Expr::Date(_) | Expr::Currency(_) => {
+            push_span_token(source, tokens, span, 10, 0)
+        }
+        Expr::Bool(_) | Expr::None => push_span_token(source, tokens, span, 7, 0),
+        Expr::Reference(_) | Expr::SlotFill => {}
+        Expr::List(items) => items.iter().for_each(|i| child(i, tokens)),
+        Expr::Object(fields) => fields.values().for_each(|v| child(v, tokens)),
+        Expr::BinOp { left, right, .. } => {
+            child(left, tokens);
+            emit_operators_between(source, left.span.end, right.span.start, tokens);
+            child(right, tokens);
+        }
+        Expr::UnaryOp { operand, .. } => {
+            emit_operators_between(source, span.start, operand.span.start, tokens);
+            child(operand, tokens);
+        }
+        Expr::Ternary {
+            condition,
+            then_expr,
+            else_expr,
+        } => {
+            child(then_expr, tokens);
+            emit_operators_between(source, then_expr.span.end, condition.span.start, tokens);
+            child(condition, tokens);
+            emit_operators_between(source, condition.span.end, else_expr.span.start, tokens);
+            child(else_expr, tokens);
+        }
+        Expr::Property { object, .. } => child(object, tokens),
+        Expr::Index { object, index } => {
+            child(object, tokens);
+            child(index, tokens);
+        }
+        Expr::Call { callee, args } => {
+            push_span_token(source, tokens, &callee.span, 3, 0); // function
+            args.iter().for_each(|a| child(a, tokens));
+        }
+    }
+}
+
+/// Emit each word between two operands: `and`/`not`/`if` as keywords,
+/// symbols like `==` as operators. Parentheses are skipped.
+fn emit_operators_between(source: &str, from: usize, to: usize, tokens: &mut Vec<RawToken>) {
+    let Some(gap) = source.get(from..to) else {
+        return;
+    };
+    let mut offset = 0;
+    for word in gap.split_whitespace() {
+        let start = offset + gap[offset..].find(word).unwrap_or(0);
+        offset = start + word.len();
+        let trimmed = word.trim_matches(|c| c == '(' || c == ')');
+        if trimmed.is_empty() {
+            continue;
+        }
+        let start = from + start + word.find(trimmed).unwrap_or(0);
+        let token_type = if trimmed.chars().all(|c| c.is_ascii_alphabetic()) {
+            7 // keyword
+        } else {
+            11 // operator
+        };
+        push_span_token(source, tokens, &(start..start + trimmed.len()), token_type, 0);
+    }
+}
+
 fn emit_reference_tokens(source: &str, tokens: &mut Vec<RawToken>) {
     let bytes = source.as_bytes();
     let mut i = 0;
@@ -294,3 +421,63 @@ fn emit_reference_tokens(source: &str, tokens: &mut Vec<RawToken>) {
         i += 1;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "topic main:
+   description: \"Main\"
+   reasoning:
+      instructions: ->
+         | Hello {!@variables.name}
+         if @variables.count > 2 and not @variables.done:
+            | Many
+         else:
+            | Few
+";
+
+    /// The tokens on `line` as (text, token type), decoded from the deltas.
+    fn tokens_on_line(line: u32) -> Vec<(&'static str, u32)> {
+        let ast = busbar_sf_agentscript::parse(SOURCE).unwrap();
+        let text = SOURCE.lines().nth(line as usize).unwrap();
+        let (mut at_line, mut at_char) = (0, 0);
+        compute_semantic_tokens(SOURCE, Some(&ast))
+            .into_iter()
+            .filter_map(|token| {
+                if token.delta_line > 0 {
+                    at_line += token.delta_line;
+                    at_char = 0;
+                }
+                at_char += token.delta_start;
+                let start = at_char as usize;
+                (at_line == line)
+                    .then(|| (&text[start..start + token.length as usize], token.token_type))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_interpolation_tokens() {
+        assert_eq!(tokens_on_line(4), [("{!", 11), ("@variables", 0), ("name", 4), ("}", 11)]);
+    }
+
+    #[test]
+    fn test_conditional_tokens() {
+        assert_eq!(
+            tokens_on_line(5),
+            [
+                ("if", 7),
+                ("@variables", 0),
+                ("count", 4),
+                (">", 11),
+                ("2", 10),
+                ("and", 7),
+                ("not", 7),
+                ("@variables", 0),
+                ("done", 4),
+            ]
+        );
+        assert_eq!(tokens_on_line(7), [("else", 7)]);
+    }
+}
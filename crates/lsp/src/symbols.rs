@@ -0,0 +1,277 @@
+//! Multi-document symbol table for `workspace/symbol`.
+//!
+//! Every indexed file contributes its agent name, `start_agent`, topics,
+//! action definitions, reasoning actions and variables. Queries match
+//! fuzzily and case-insensitively, so `#refund` finds both the
+//! `refund_processing` topic and the `process_refund` action; a leading
+//! `#` is ignored.
+//!
+//! A file that stops parsing keeps its last symbols until it parses again,
+//! so search keeps working while a definition is half-typed.
+
+use std::collections::HashMap;
+
+use busbar_sf_agentscript::ast::{ActionsBlock, AgentFile, ReasoningBlock, Spanned};
+use tower_lsp::lsp_types::*;
+
+use super::{span_to_range, DocumentState};
+
+/// Most symbols returned for a single query.
+const MAX_RESULTS: usize = 256;
+
+/// A symbol defined in an indexed file.
+#[derive(Debug, Clone)]
+struct Symbol {
+    name: String,
+    kind: SymbolKind,
+    /// Topic or agent the symbol belongs to, shown next to it in the picker.
+    container: Option<String>,
+    range: Range,
+}
+
+/// Symbols of every indexed file, keyed by URI.
+#[derive(Default)]
+pub struct SymbolTable {
+    files: HashMap<Url, Vec<Symbol>>,
+}
+
+impl SymbolTable {
+    /// Replace the symbols of a file, unless it no longer parses.
+    pub fn update(&mut self, uri: &Url, doc: &DocumentState) {
+        if let Some(ast) = &doc.ast {
+            self.files
+                .insert(uri.clone(), collect_symbols(ast, &doc.source));
+        }
+    }
+
+    /// Drop the symbols of a file.
+    pub fn remove(&mut self, uri: &Url) {
+        self.files.remove(uri);
+    }
+
+    /// Find symbols matching `query`, best matches first.
+    pub fn search(&self, query: &str) -> Vec<SymbolInformation> {
+        let query = query.trim().trim_start_matches('#').to_lowercase();
+        let mut matches: Vec<(u32, &Url, &Symbol)> = self
+            .files
+            .iter()
+            .flat_map(|(uri, symbols)| symbols.iter().map(move |s| (uri, s)))
+            .filter_map(|(uri, symbol)| Some((fuzzy_score(&query, &symbol.name)?, uri, symbol)))
+            .collect();
+        matches.sort_by(|(a_score, a_uri, a), (b_score, b_uri, b)| {
+            (a_score, &a.name, a_uri.as_str(), a.range.start.line).cmp(&(
+                b_score,
+                &b.name,
+                b_uri.as_str(),
+                b.range.start.line,
+            ))
+        });
+        matches
+            .into_iter()
+            .take(MAX_RESULTS)
+            .map(|(_, uri, symbol)| {
+                #[allow(deprecated)]
+                SymbolInformation {
+                    name: symbol.name.clone(),
+                    kind: symbol.kind,
+                    tags: None,
+                    deprecated: None,
+                    location: Location {
+                        uri: uri.clone(),
+                        range: symbol.range,
+                    },
+                    container_name: symbol.container.clone(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Score how well `name` matches a lowercase `query`; lower is better.
+///
+/// Exact matches come first, then prefixes, then matches at the start of a
+/// `_`-separated word, then other substrings, then subsequences with the
+/// fewest gaps. `None` if the query's characters do not all appear in order.
+fn fuzzy_score(query: &str, name: &str) -> Option<u32> {
+    let name = name.to_lowercase();
+    if query.is_empty() || name == query {
+        return Some(0);
+    }
+    if name.starts_with(query) {
+        return Some(1);
+    }
+    if name.contains(&format!("_{}", query)) {
+        return Some(2);
+    }
+    if name.contains(query) {
+        return Some(3);
+    }
+
+    let mut gaps = 0;
+    let mut previous_matched = true;
+    let mut query_chars = query.chars().peekable();
+    for c in name.chars() {
+        match query_chars.peek() {
+            Some(&q) if q == c => {
+                query_chars.next();
+                if !previous_matched {
+                    gaps += 1;
+                }
+                previous_matched = true;
+            }
+            Some(_) => previous_matched = false,
+            None => break,
+        }
+    }
+    query_chars.peek().is_none().then_some(4 + gaps)
+}
+
+/// Collect the symbols defined in a single file.
+fn collect_symbols(ast: &AgentFile, source: &str) -> Vec<Symbol> {
+    let agent = ast.config.as_ref().map(|c| c.node.agent_name.node.clone());
+    let mut symbols = Vec::new();
+    let mut push = |name: &Spanned<String>, kind, container: Option<&String>| {
+        symbols.push(Symbol {
+            name: name.node.clone(),
+            kind,
+            container: container.cloned(),
+            range: span_to_range(source, name.span.clone()),
+        })
+    };
+
+    if let Some(config) = &ast.config {
+        push(&config.node.agent_name, SymbolKind::MODULE, None);
+    }
+    for var in ast.variables.iter().flat_map(|v| &v.node.variables) {
+        push(&var.node.name, SymbolKind::VARIABLE, agent.as_ref());
+    }
+    let blocks = ast
+        .start_agent
+        .iter()
+        .map(|sa| {
+            let sa = &sa.node;
+            (&sa.name, SymbolKind::CONSTRUCTOR, &sa.actions, &sa.reasoning)
+        })
+        .chain(ast.topics.iter().map(|t| {
+            let t = &t.node;
+            (&t.name, SymbolKind::CLASS, &t.actions, &t.reasoning)
+        }));
+    for (name, kind, actions, reasoning) in blocks {
+        push(name, kind, agent.as_ref());
+        collect_block_actions(actions, reasoning, &name.node, &mut push);
+    }
+    symbols
+}
+
+fn collect_block_actions(
+    actions: &Option<Spanned<ActionsBlock>>,
+    reasoning: &Option<Spanned<ReasoningBlock>>,
+    block: &String,
+    push: &mut impl FnMut(&Spanned<String>, SymbolKind, Option<&String>),
+) {
+    for action in actions.iter().flat_map(|a| &a.node.actions) {
+        push(&action.node.name, SymbolKind::METHOD, Some(block));
+    }
+    let reasoning_actions = reasoning
+        .iter()
+        .flat_map(|r| r.node.actions.iter().flat_map(|a| &a.node));
+    for action in reasoning_actions {
+        push(&action.node.name, SymbolKind::EVENT, Some(block));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REFUNDS: &str = "config:
+   agent_name: \"Refunds\"
+
+topic refund_processing:
+   description: \"Refunds\"
+   actions:
+      process_refund:
+         description: \"Process\"
+         target: \"flow://Process\"
+";
+
+    const ORDERS: &str = "variables:
+   refund_total: mutable number = 0
+      description: \"Total\"
+
+topic orders:
+   description: \"Orders\"
+   reasoning:
+      instructions: \"Help\"
+      actions:
+         go_refunds: @utils.transition to @topic.refund_processing
+            description: \"Refunds\"
+";
+
+    fn uri(name: &str) -> Url {
+        Url::parse(&format!("file:///ws/{}.agent", name)).unwrap()
+    }
+
+    fn table() -> SymbolTable {
+        let mut table = SymbolTable::default();
+        table.update(&uri("refunds"), &DocumentState::new(REFUNDS.to_string()));
+        table.update(&uri("orders"), &DocumentState::new(ORDERS.to_string()));
+        table
+    }
+
+    /// (name, file) of each result, in order.
+    fn names(results: &[SymbolInformation]) -> Vec<(&str, &str)> {
+        results
+            .iter()
+            .map(|s| {
+                let file = s.location.uri.path().rsplit('/').next().unwrap();
+                (s.name.as_str(), file)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_search_across_documents() {
+        let table = table();
+        // Prefix, then word start, then a subsequence
+        assert_eq!(
+            names(&table.search("#refund")),
+            [
+                ("Refunds", "refunds.agent"),
+                ("refund_processing", "refunds.agent"),
+                ("refund_total", "orders.agent"),
+                ("go_refunds", "orders.agent"),
+                ("process_refund", "refunds.agent"),
+            ]
+        );
+        assert_eq!(names(&table.search("ORDERS")), [("orders", "orders.agent")]);
+        assert_eq!(names(&table.search("prcref")), [("process_refund", "refunds.agent")]);
+        assert!(table.search("missing").is_empty());
+
+        let action = &table.search("process_refund")[0];
+        assert_eq!(action.kind, SymbolKind::METHOD);
+        assert_eq!(action.container_name.as_deref(), Some("refund_processing"));
+        assert_eq!(action.location.range.start, Position::new(6, 6));
+    }
+
+    #[test]
+    fn test_remove_and_unparsable_documents() {
+        let mut table = table();
+        table.remove(&uri("refunds"));
+        assert_eq!(
+            names(&table.search("refund")),
+            [
+                ("refund_total", "orders.agent"),
+                ("go_refunds", "orders.agent")
+            ]
+        );
+
+        // A file that stops parsing keeps its last symbols
+        let broken = DocumentState::new("topic orders:\n   description: \"Ord".to_string());
+        assert!(broken.ast.is_none());
+        table.update(&uri("orders"), &broken);
+        assert_eq!(names(&table.search("orders")), [("orders", "orders.agent")]);
+        table.remove(&uri("orders"));
+        assert!(table.search("").is_empty());
+    }
+}
@@ -0,0 +1,405 @@
+//! Workspace-wide index of `.agent` files.
+//!
+//! On `initialize` the server scans every workspace folder for `**/*.agent`
+//! files and parses them into [`DocumentState`]s. The index backs
+//! cross-file go-to-definition, find-references and workspace symbol
+//! search, and lets the server publish diagnostics for files that are not
+//! open in the editor.
+//!
+//! Open documents shadow their on-disk copy: the index is updated from
+//! `didOpen`/`didChange`, and reloaded from disk on `didClose`.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use busbar_sf_agentscript::ast::{AgentFile, Spanned};
+use busbar_sf_agentscript::graph::{NodeIndex, RefGraph, RefNode};
+use tower_lsp::lsp_types::*;
+
+use super::symbols::SymbolTable;
+use super::{collect_all_action_defs, span_to_range, symbol_ranges, DocumentState};
+
+/// Directories that are never scanned for `.agent` files.
+const SKIPPED_DIRS: &[&str] = &["node_modules", "target", "dist", "out"];
+
+/// Kind of a symbol defined in an `.agent` file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DefKind {
+    Variable,
+    Topic,
+    Action,
+}
+
+impl DefKind {
+    /// The reference namespace used to refer to this kind of symbol.
+    pub fn from_namespace(namespace: &str) -> Option<Self> {
+        match namespace {
+            "variables" => Some(DefKind::Variable),
+            "topic" => Some(DefKind::Topic),
+            "actions" => Some(DefKind::Action),
+            _ => None,
+        }
+    }
+}
+
+/// Index of all `.agent` files in the workspace.
+#[derive(Default)]
+pub struct WorkspaceIndex {
+    roots: Vec<PathBuf>,
+    files: HashMap<Url, Arc<DocumentState>>,
+    symbols: SymbolTable,
+}
+
+impl WorkspaceIndex {
+    /// Create an empty index for the given workspace roots.
+    pub fn new(roots: Vec<PathBuf>) -> Self {
+        Self {
+            roots,
+            files: HashMap::new(),
+            symbols: SymbolTable::default(),
+        }
+    }
+
+    /// Scan all workspace roots and (re)load every `.agent` file.
+    ///
+    /// Returns the URIs of the files that were indexed.
+    pub fn scan(&mut self) -> Vec<Url> {
+        let mut paths = Vec::new();
+        for root in &self.roots {
+            collect_agent_files(root, &mut paths);
+        }
+
+        let mut indexed = Vec::new();
+        for path in paths {
+            let Ok(uri) = Url::from_file_path(&path) else {
+                continue;
+            };
+            if let Ok(source) = std::fs::read_to_string(&path) {
+                self.update(&uri, source);
+                indexed.push(uri);
+            }
+        }
+        indexed
+    }
+
+    /// Replace the indexed contents of a file.
+    pub fn update(&mut self, uri: &Url, source: String) {
+        self.insert(uri, Arc::new(DocumentState::new(source)));
+    }
+
+    /// Index a snapshot already parsed elsewhere, such as an open document.
+    pub fn insert(&mut self, uri: &Url, doc: Arc<DocumentState>) {
+        self.symbols.update(uri, &doc);
+        self.files.insert(uri.clone(), doc);
+    }
+
+    /// Reload a file from disk, dropping it from the index if it no longer exists.
+    ///
+    /// Returns `true` if the file is still indexed.
+    pub fn reload(&mut self, uri: &Url) -> bool {
+        let source = uri
+            .to_file_path()
+            .ok()
+            .and_then(|p| std::fs::read_to_string(p).ok());
+        match source {
+            Some(source) if self.is_in_workspace(uri) => {
+                self.update(uri, source);
+                true
+            }
+            _ => {
+                self.files.remove(uri);
+                self.symbols.remove(uri);
+                false
+            }
+        }
+    }
+
+    /// Get the indexed state of a file.
+    pub fn get(&self, uri: &Url) -> Option<&Arc<DocumentState>> {
+        self.files.get(uri)
+    }
+
+    /// Iterate over all indexed files.
+    pub fn iter(&self) -> impl Iterator<Item = (&Url, &Arc<DocumentState>)> {
+        self.files.iter()
+    }
+
+    /// Symbols defined across the workspace.
+    pub fn symbols(&self) -> &SymbolTable {
+        &self.symbols
+    }
+
+    /// Find every definition of `name` with the given kind across the workspace.
+    pub fn find_definitions(&self, kind: DefKind, name: &str) -> Vec<Location> {
+        let mut locations = Vec::new();
+        for (uri, doc) in &self.files {
+            let Some(ast) = &doc.ast else { continue };
+            for span in definition_spans(ast, kind, name) {
+                locations.push(Location {
+                    uri: uri.clone(),
+                    range: span_to_range(&doc.source, span),
+                });
+            }
+        }
+        sort_locations(&mut locations);
+        locations
+    }
+
+    /// Find every reference to `symbol`, a node from one file's graph,
+    /// across the workspace.
+    ///
+    /// Variables and topics match by name in every file; actions also need
+    /// the same topic, since `@actions.x` only names an action of its own
+    /// topic.
+    pub fn find_references(&self, symbol: &RefNode, include_declaration: bool) -> Vec<Location> {
+        let mut locations: Vec<Location> = self
+            .files
+            .iter()
+            .flat_map(|(uri, doc)| {
+                let ranges = match doc.graph.as_ref() {
+                    Some(graph) => find_symbol(graph, symbol)
+                        .map(|node| symbol_ranges(doc, graph, node, include_declaration))
+                        .unwrap_or_default(),
+                    None => Vec::new(),
+                };
+                ranges.into_iter().map(|range| Location {
+                    uri: uri.clone(),
+                    range,
+                })
+            })
+            .collect();
+        sort_locations(&mut locations);
+        locations
+    }
+
+    fn is_in_workspace(&self, uri: &Url) -> bool {
+        uri.to_file_path()
+            .map(|p| self.roots.iter().any(|r| p.starts_with(r)))
+            .unwrap_or(false)
+    }
+}
+
+/// The node in `graph` for the same symbol as `symbol`.
+fn find_symbol(graph: &RefGraph, symbol: &RefNode) -> Option<NodeIndex> {
+    match symbol {
+        RefNode::Variable { name, .. } => graph.get_variable(name),
+        RefNode::Topic { name, .. } => graph.get_topic(name),
+        RefNode::ActionDef { name, topic, .. } => graph.get_action_def(topic, name),
+        RefNode::ReasoningAction { name, topic, .. } => graph.get_reasoning_action(topic, name),
+        _ => None,
+    }
+}
+
+/// Spans of the definitions of `name` in a single file.
+fn definition_spans(ast: &AgentFile, kind: DefKind, name: &str) -> Vec<std::ops::Range<usize>> {
+    let matching = |n: &Spanned<String>| (n.node == name).then(|| n.span.clone());
+    match kind {
+        DefKind::Variable => ast
+            .variables
+            .iter()
+            .flat_map(|vars| vars.node.variables.iter())
+            .filter_map(|v| matching(&v.node.name))
+            .collect(),
+        DefKind::Topic => ast
+            .topics
+            .iter()
+            .filter_map(|t| matching(&t.node.name))
+            .collect(),
+        DefKind::Action => collect_all_action_defs(ast)
+            .into_iter()
+            .filter_map(|a| matching(&a.node.name))
+            .collect(),
+    }
+}
+
+/// Recursively collect `.agent` files under `dir`, skipping hidden and build directories.
+fn collect_agent_files(dir: &Path, out: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if path.is_dir() {
+            if !name.starts_with('.') && !SKIPPED_DIRS.contains(&name.as_ref()) {
+                collect_agent_files(&path, out);
+            }
+        } else if path.extension().is_some_and(|ext| ext == "agent") {
+            out.push(path);
+        }
+    }
+}
+
+fn sort_locations(locations: &mut [Location]) {
+    locations.sort_by(|a, b| {
+        (a.uri.as_str(), a.range.start.line, a.range.start.character).cmp(&(
+            b.uri.as_str(),
+            b.range.start.line,
+            b.range.start.character,
+        ))
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AGENT: &str = "variables:
+   step: mutable number = 0
+      description: \"Step\"
+   step_count: mutable number = 0
+      description: \"Steps\"
+
+topic billing:
+   description: \"Billing\"
+   actions:
+      lookup:
+         description: \"Look up\"
+         target: \"flow://Lookup\"
+   reasoning:
+      instructions: ->
+         | Step {!@variables.step} of {!@variables.step_count}
+      actions:
+         find: @actions.lookup
+            description: \"Find\"
+            set @variables.step = @outputs.step
+";
+
+    const OTHER: &str = "variables:
+   step: mutable number = 0
+      description: \"Step\"
+
+topic support:
+   description: \"Support\"
+   actions:
+      lookup:
+         description: \"Look up\"
+         target: \"flow://Lookup\"
+   reasoning:
+      instructions: ->
+         | Now at {!@variables.step}
+      actions:
+         find: @actions.lookup
+            description: \"Find\"
+";
+
+    fn index() -> WorkspaceIndex {
+        let mut index = WorkspaceIndex::default();
+        index.update(&uri("a"), AGENT.to_string());
+        index.update(&uri("b"), OTHER.to_string());
+        index
+    }
+
+    fn uri(name: &str) -> Url {
+        Url::parse(&format!("file:///ws/{}.agent", name)).unwrap()
+    }
+
+    /// The graph node for the symbol at `needle` in the indexed file `name`.
+    fn symbol(index: &WorkspaceIndex, name: &str, needle: &str) -> RefNode {
+        let doc = index.get(&uri(name)).unwrap();
+        let graph = doc.graph.as_ref().unwrap();
+        let offset = doc.source.find(needle).unwrap() + needle.len() - 1;
+        graph
+            .get_node(graph.symbol_at(offset).unwrap())
+            .unwrap()
+            .clone()
+    }
+
+    /// (file, line) of each location.
+    fn lines(locations: &[Location]) -> Vec<(String, u32)> {
+        locations
+            .iter()
+            .map(|l| {
+                let file = l.uri.path().rsplit('/').next().unwrap().to_string();
+                (file, l.range.start.line)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_find_references_respects_name_boundaries() {
+        let index = index();
+        let step = symbol(&index, "a", "{!@variables.step}");
+        assert_eq!(
+            lines(&index.find_references(&step, false)),
+            [
+                ("a.agent".to_string(), 14),
+                ("a.agent".to_string(), 18),
+                ("b.agent".to_string(), 12)
+            ]
+        );
+        assert_eq!(
+            lines(&index.find_references(&step, true)),
+            [
+                ("a.agent".to_string(), 1),
+                ("a.agent".to_string(), 14),
+                ("a.agent".to_string(), 18),
+                ("b.agent".to_string(), 1),
+                ("b.agent".to_string(), 12)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_references_keeps_actions_in_their_topic() {
+        let index = index();
+        let lookup = symbol(&index, "a", "@actions.lookup");
+        assert_eq!(
+            lines(&index.find_references(&lookup, true)),
+            [("a.agent".to_string(), 9), ("a.agent".to_string(), 16)]
+        );
+    }
+
+    #[test]
+    fn test_find_definitions_across_files() {
+        let index = index();
+        assert_eq!(
+            lines(&index.find_definitions(DefKind::Action, "lookup")),
+            [("a.agent".to_string(), 9), ("b.agent".to_string(), 7)]
+        );
+        assert_eq!(
+            lines(&index.find_definitions(DefKind::Topic, "support")),
+            [("b.agent".to_string(), 4)]
+        );
+        assert!(index
+            .find_definitions(DefKind::Variable, "missing")
+            .is_empty());
+    }
+
+    #[test]
+    fn test_scan_skips_hidden_and_build_directories() {
+        let root = std::env::temp_dir().join(format!("agentscript-lsp-{}", std::process::id()));
+        for dir in ["agents/nested", ".git", "node_modules/pkg", "target"] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        for file in [
+            "agents/main.agent",
+            "agents/nested/other.agent",
+            ".git/skipped.agent",
+            "node_modules/pkg/skipped.agent",
+            "target/skipped.agent",
+        ] {
+            std::fs::write(root.join(file), OTHER).unwrap();
+        }
+        std::fs::write(root.join("agents/notes.txt"), "not an agent").unwrap();
+
+        let mut index = WorkspaceIndex::new(vec![root.clone()]);
+        let mut indexed: Vec<String> = index
+            .scan()
+            .iter()
+            .map(|uri| uri.path().rsplit('/').next().unwrap().to_string())
+            .collect();
+        indexed.sort();
+        assert_eq!(indexed, ["main.agent", "other.agent"]);
+        assert_eq!(index.iter().count(), 2);
+
+        // A deleted file leaves the index when reloaded
+        let main = Url::from_file_path(root.join("agents/main.agent")).unwrap();
+        std::fs::remove_file(root.join("agents/main.agent")).unwrap();
+        assert!(!index.reload(&main));
+        assert!(index.get(&main).is_none());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -77,6 +77,10 @@ export function activate(context: vscode.ExtensionContext): void {
       fileEvents: vscode.workspace.createFileSystemWatcher("**/*.agent"),
     },
     outputChannelName: "AgentScript Language Server",
+    initializationOptions: {
+      targetEnvironment: config.get<string | null>("targetEnvironment", null),
+      descriptionCommand: config.get<string[]>("descriptionCommand", []),
+    },
   };
 
   client = new LanguageClient(
@@ -117,6 +121,21 @@ export function activate(context: vscode.ExtensionContext): void {
       graphProvider?.show();
     }),
 
+    vscode.commands.registerCommand("agentscript.showTopicGraph", async () => {
+      const editor = vscode.window.activeTextEditor;
+      if (!client || editor?.document.languageId !== "agentscript") return;
+      const graph = await client.sendRequest<{ topics: string[] }>(
+        "agentscript/getGraph",
+        { uri: editor.document.uri.toString() },
+      );
+      const topic = await vscode.window.showQuickPick(graph.topics.sort(), {
+        placeHolder: "Topic to focus the graph on",
+      });
+      if (topic) {
+        graphProvider?.show(topic);
+      }
+    }),
+
     vscode.commands.registerCommand("agentscript.refreshDependencies", () => {
       dependencyProvider?.refresh();
     }),
@@ -125,6 +144,32 @@ export function activate(context: vscode.ExtensionContext): void {
       simulator?.simulate();
     }),
 
+    vscode.commands.registerCommand("agentscript.parseProfile", async () => {
+      const editor = vscode.window.activeTextEditor;
+      if (!client || editor?.document.languageId !== "agentscript") return;
+      // The server writes the profile table to its output channel
+      await client.sendRequest("agentscript/parseProfile", {
+        uri: editor.document.uri.toString(),
+      });
+      client.outputChannel.show(true);
+    }),
+
+    vscode.commands.registerCommand("agentscript.showHealth", async () => {
+      const editor = vscode.window.activeTextEditor;
+      if (!client || editor?.document.languageId !== "agentscript") return;
+      const report = await client.sendRequest<HealthReport>(
+        "agentscript/health",
+        { uri: editor.document.uri.toString() },
+      );
+      const issues = report.top_issues.map(
+        (issue) => `-${Math.round(issue.penalty)} ${issue.message}`,
+      );
+      vscode.window.showInformationMessage(
+        `Agent health: ${report.score}/100`,
+        { modal: issues.length > 0, detail: issues.join("\n") },
+      );
+    }),
+
     vscode.commands.registerCommand(
       "agentscript.navigateToSpan",
       (spanStart: number, spanEnd: number) => {
@@ -152,6 +197,12 @@ export function activate(context: vscode.ExtensionContext): void {
   console.log("AgentScript extension activated with Rust LSP");
 }
 
+/** Response of the agentscript/health request. */
+interface HealthReport {
+  score: number;
+  top_issues: { component: string; message: string; penalty: number }[];
+}
+
 export async function deactivate(): Promise<void> {
   graphProvider?.dispose();
   dependencyProvider?.dispose();
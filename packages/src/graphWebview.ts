@@ -19,8 +19,29 @@ interface GraphRepr {
   variables: string[];
 }
 
+/** Stable node kinds, mirroring `NodeKind` in the Rust crate. */
+type NodeKind =
+  | 'topic'
+  | 'start_agent'
+  | 'action_def'
+  | 'reasoning_action'
+  | 'variable'
+  | 'external'
+  | 'util';
+
+/** Stable edge kinds, mirroring `EdgeKind` in the Rust crate. */
+type EdgeKind =
+  | 'routes'
+  | 'transitions_to'
+  | 'delegates'
+  | 'invokes'
+  | 'reads'
+  | 'writes'
+  | 'chains'
+  | 'escalates';
+
 interface NodeRepr {
-  node_type: string;
+  node_type: NodeKind;
   name: string | null;
   topic: string | null;
   target: string | null;
@@ -32,7 +53,7 @@ interface NodeRepr {
 interface EdgeRepr {
   source: number;
   target: number;
-  edge_type: string;
+  edge_type: EdgeKind;
 }
 
 export class GraphWebviewProvider {
@@ -40,6 +61,8 @@ export class GraphWebviewProvider {
   private readonly extensionUri: vscode.Uri;
   private client: LanguageClient | undefined;
   private disposables: vscode.Disposable[] = [];
+  /** Topic the panel is focused on, or undefined for the whole agent */
+  private topic: string | undefined;
 
   constructor(extensionUri: vscode.Uri) {
     this.extensionUri = extensionUri;
@@ -49,7 +72,8 @@ export class GraphWebviewProvider {
     this.client = client;
   }
 
-  async show(): Promise<void> {
+  /** Show the graph, focused on a single topic's subgraph when given. */
+  async show(topic?: string): Promise<void> {
     const editor = vscode.window.activeTextEditor;
     if (!editor || editor.document.languageId !== "agentscript") {
       vscode.window.showWarningMessage(
@@ -58,6 +82,7 @@ export class GraphWebviewProvider {
       return;
     }
 
+    this.topic = topic;
     if (this.panel) {
       this.panel.reveal(vscode.ViewColumn.Beside);
     } else {
@@ -89,6 +114,9 @@ export class GraphWebviewProvider {
       );
     }
 
+    this.panel.title = topic
+      ? `AgentScript: Topic Graph (${topic})`
+      : "AgentScript: Topic Graph";
     await this.updateGraph(editor.document);
 
     // Re-render on document change
@@ -132,10 +160,10 @@ export class GraphWebviewProvider {
     if (!this.client) return null;
 
     try {
-      const result = await this.client.sendRequest(
-        "agentscript/getGraph",
-        { uri: document.uri.toString() },
-      );
+      const result = await this.client.sendRequest("agentscript/getGraph", {
+        uri: document.uri.toString(),
+        topic: this.topic,
+      });
       return result as GraphRepr;
     } catch {
       // LSP request not available yet — fall back to null
@@ -262,7 +290,7 @@ export class GraphWebviewProvider {
   .node-action_def rect { fill: #7d3c98; stroke: #a569bd; }
   .node-reasoning_action rect { fill: #b7950b; stroke: #d4ac0d; }
   .node-variable rect { fill: #6c3483; stroke: #8e44ad; }
-  .node-connection rect { fill: #a04000; stroke: #d35400; }
+  .node-external rect { fill: #a04000; stroke: #d35400; }
   /* Edge type colors */
   .edge-transition { stroke: #2ecc71; }
   .edge-delegates_to { stroke: #3498db; stroke-dasharray: 6,3; }
@@ -293,8 +321,8 @@ function layoutGraph(graph, showVariables) {
   const NODE_W = 180;
   const NODE_H = 50;
 
-  // Filter nodes: only show topic-level nodes (start_agent, topic, connection) + optionally variables
-  const visibleTypes = new Set(['start_agent', 'topic', 'connection']);
+  // Filter nodes: only show topic-level nodes (start_agent, topic, external) + optionally variables
+  const visibleTypes = new Set(['start_agent', 'topic', 'external']);
   if (showVariables) visibleTypes.add('variable');
 
   const visibleNodes = graph.nodes
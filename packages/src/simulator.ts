@@ -15,7 +15,8 @@ import type { LanguageClient } from "vscode-languageclient/node";
 interface ExecutionTrace {
   steps: TraceStep[];
   final_context: Record<string, unknown>;
-  outcome: string;
+  outcome: "success" | "escalated" | "error";
+  error?: string | null;
   topic_transitions: string[];
 }
 
@@ -153,6 +154,9 @@ export class AgentSimulator {
     this.outputChannel.clear();
     this.outputChannel.appendLine(`=== Simulation: ${filename} ===`);
     this.outputChannel.appendLine(`Outcome: ${trace.outcome}`);
+    if (trace.error) {
+      this.outputChannel.appendLine(`Error: ${trace.error}`);
+    }
     this.outputChannel.appendLine(
       `Topics visited: ${trace.topic_transitions.join(" → ")}`,
     );
@@ -259,6 +263,7 @@ export class AgentSimulator {
   }
   .outcome { font-weight: 600; }
   .outcome-success { color: #2ecc71; }
+  .outcome-escalated { color: #f39c12; }
   .outcome-error { color: #e74c3c; }
   .steps { display: flex; flex-direction: column; gap: 8px; }
   .step {
@@ -298,7 +303,7 @@ export class AgentSimulator {
   <div class="summary">
     <div class="summary-row">
       <span class="label">Outcome:</span>
-      <span class="outcome ${trace.outcome === "success" ? "outcome-success" : "outcome-error"}">${this.escapeHtml(trace.outcome)}</span>
+      <span class="outcome outcome-${this.escapeHtml(trace.outcome)}">${this.escapeHtml(trace.outcome)}</span>
     </div>
     <div class="summary-row">
       <span class="label">Topic Path:</span> ${topicPath || "<em>none</em>"}
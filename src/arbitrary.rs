@@ -0,0 +1,1096 @@
+//! [`Arbitrary`] implementations for the AST (requires the `arbitrary` feature).
+//!
+//! Generated agents are structurally valid rather than random trees: names
+//! are identifiers that aren't keywords, every `@variables`, `@actions`,
+//! `@outputs` and `@topic` reference points at something the agent
+//! defines, and only constructs the parser produces are used. Their
+//! [`serialize`](crate::serialize) output parses again, so they can drive
+//! property tests of formatters, converters and other tools.
+//!
+//! Spans are placeholders (`0..0`). Call
+//! [`attach_spans`](crate::spanner::attach_spans) to point them into the
+//! serialized source.
+//!
+//! # Shrinking
+//!
+//! Every size and choice is read from the input bytes, and the first
+//! option of each choice is the simplest one. Shorter or zeroed input
+//! therefore means a smaller agent, so fuzzers that minimize their input
+//! (e.g. `cargo fuzz tmin`) shrink failures to small agents. Running out
+//! of input yields a file with only a `config:` block.
+//!
+//! Sub-types generated on their own (e.g. a lone [`Stmt`]) refer to a small
+//! fixed set of names: variables `name` and `count`, action `lookup` with
+//! input `id` and output `result`, and topic `main`.
+//!
+//! ```rust
+//! use arbitrary::{Arbitrary, Unstructured};
+//! use busbar_sf_agentscript::{parse, serialize, AgentFile};
+//!
+//! let bytes: Vec<u8> = (0..512u32).map(|i| (i * 37 % 251) as u8).collect();
+//! let agent = AgentFile::arbitrary(&mut Unstructured::new(&bytes)).unwrap();
+//! let source = serialize(&agent);
+//! assert!(parse(&source).is_ok(), "{source}");
+//! ```
+
+use crate::ast::*;
+use ::arbitrary::{Arbitrary, Result, Unstructured};
+use indexmap::IndexMap;
+
+/// Words used for identifiers. None are keywords.
+const WORDS: &[&str] = &[
+    "order", "refund", "account", "ticket", "invoice", "shipment", "customer", "payment",
+    "summary", "greeting", "address", "balance",
+];
+
+/// Words used for free text.
+const TEXT: &[&str] = &[
+    "Help", "the", "customer", "with", "their", "order", "and", "check", "status", "refund",
+    "politely", "first", "then", "done",
+];
+
+/// Maximum depth of generated expressions.
+const MAX_EXPR_DEPTH: u32 = 3;
+
+/// Maximum nesting of `if` statements in directive blocks.
+const MAX_STMT_DEPTH: u32 = 2;
+
+fn spanned<T>(node: T) -> Spanned<T> {
+    Spanned::new(node, 0..0)
+}
+
+fn optional<'a, T>(
+    u: &mut Unstructured<'a>,
+    f: impl FnOnce(&mut Unstructured<'a>) -> Result<T>,
+) -> Result<Option<T>> {
+    Ok(if u.arbitrary()? { Some(f(u)?) } else { None })
+}
+
+/// Between `min` and `max` items from `f`.
+fn items<'a, T>(
+    u: &mut Unstructured<'a>,
+    min: usize,
+    max: usize,
+    mut f: impl FnMut(&mut Unstructured<'a>) -> Result<T>,
+) -> Result<Vec<T>> {
+    let len = u.int_in_range(min..=max)?;
+    (0..len).map(|_| f(u)).collect()
+}
+
+/// An identifier not in `taken`.
+fn fresh_ident(u: &mut Unstructured, taken: &[String]) -> Result<String> {
+    let word = *u.choose(WORDS)?;
+    let mut name = word.to_string();
+    let mut n = 1;
+    while taken.contains(&name) {
+        n += 1;
+        name = format!("{word}_{n}");
+    }
+    Ok(name)
+}
+
+/// Between `min` and `max` distinct identifiers.
+fn fresh_idents(u: &mut Unstructured, min: usize, max: usize) -> Result<Vec<String>> {
+    let count = u.int_in_range(min..=max)?;
+    let mut names = Vec::with_capacity(count);
+    for _ in 0..count {
+        let name = fresh_ident(u, &names)?;
+        names.push(name);
+    }
+    Ok(names)
+}
+
+/// One to six words of text.
+fn text(u: &mut Unstructured) -> Result<String> {
+    let words = items(u, 1, 6, |u| u.choose(TEXT).copied())?;
+    Ok(words.join(" "))
+}
+
+fn spanned_text(u: &mut Unstructured) -> Result<Spanned<String>> {
+    text(u).map(spanned)
+}
+
+fn flag(u: &mut Unstructured) -> Result<Option<Spanned<bool>>> {
+    optional(u, |u| Ok(spanned(u.arbitrary()?)))
+}
+
+/// Names an agent defines, for generating references that resolve.
+#[derive(Debug, Clone, Default)]
+struct Scope {
+    variables: Vec<String>,
+    mutable_variables: Vec<String>,
+    topics: Vec<String>,
+    actions: Vec<ActionSignature>,
+}
+
+#[derive(Debug, Clone)]
+struct ActionSignature {
+    name: String,
+    inputs: Vec<String>,
+    outputs: Vec<String>,
+}
+
+impl Scope {
+    /// The fixed scope used for sub-types generated on their own.
+    fn sample() -> Self {
+        Self {
+            variables: vec!["name".into(), "count".into()],
+            mutable_variables: vec!["name".into(), "count".into()],
+            topics: vec!["main".into()],
+            actions: vec![ActionSignature {
+                name: "lookup".into(),
+                inputs: vec!["id".into()],
+                outputs: vec!["result".into()],
+            }],
+        }
+    }
+
+    fn with_actions(&self, actions: &Option<Spanned<ActionsBlock>>) -> Self {
+        let names = |params: &Option<Spanned<Vec<Spanned<ParamDef>>>>| {
+            params
+                .iter()
+                .flat_map(|p| &p.node)
+                .map(|p| p.node.name.node.clone())
+                .collect()
+        };
+        Self {
+            actions: actions
+                .iter()
+                .flat_map(|block| &block.node.actions)
+                .map(|action| ActionSignature {
+                    name: action.node.name.node.clone(),
+                    inputs: names(&action.node.inputs),
+                    outputs: names(&action.node.outputs),
+                })
+                .collect(),
+            ..self.clone()
+        }
+    }
+
+    fn variable(&self, u: &mut Unstructured) -> Result<Option<Reference>> {
+        pick(u, &self.variables, "variables")
+    }
+
+    fn mutable_variable(&self, u: &mut Unstructured) -> Result<Option<Reference>> {
+        pick(u, &self.mutable_variables, "variables")
+    }
+
+    fn topic(&self, u: &mut Unstructured) -> Result<Option<Reference>> {
+        pick(u, &self.topics, "topic")
+    }
+
+    fn action(&self, u: &mut Unstructured) -> Result<Option<&ActionSignature>> {
+        if self.actions.is_empty() {
+            return Ok(None);
+        }
+        u.choose(&self.actions).map(Some)
+    }
+
+    /// An expression of at most `depth` levels.
+    fn expr(&self, u: &mut Unstructured, depth: u32) -> Result<Expr> {
+        if depth == 0 || !u.arbitrary::<bool>()? {
+            return self.leaf_expr(u);
+        }
+        let operand = |u: &mut Unstructured| -> Result<Box<Spanned<Expr>>> {
+            Ok(Box::new(spanned(self.expr(u, depth - 1)?)))
+        };
+        Ok(match u.choose_index(8)? {
+            0 => Expr::BinOp {
+                left: operand(u)?,
+                op: u.arbitrary()?,
+                right: operand(u)?,
+            },
+            1 => Expr::UnaryOp {
+                op: UnaryOp::Not,
+                operand: operand(u)?,
+            },
+            2 => Expr::Ternary {
+                condition: operand(u)?,
+                then_expr: operand(u)?,
+                else_expr: operand(u)?,
+            },
+            3 => Expr::List(items(u, 0, 3, |u| Ok(spanned(self.expr(u, depth - 1)?)))?),
+            // Only at the top, as the lexer reads the `}}` of a nested
+            // object as a template brace
+            4 if depth == MAX_EXPR_DEPTH => {
+                let keys = fresh_idents(u, 0, 3)?;
+                let mut fields = IndexMap::new();
+                for key in keys {
+                    fields.insert(key, spanned(self.expr(u, depth - 1)?));
+                }
+                Expr::Object(fields)
+            }
+            5 => {
+                let function = u.choose(BUILTIN_FUNCTIONS)?;
+                Expr::Call {
+                    callee: spanned(function.name.to_string()),
+                    args: items(u, function.arity, function.arity, |u| {
+                        Ok(spanned(self.expr(u, depth - 1)?))
+                    })?,
+                }
+            }
+            6 => Expr::Index {
+                object: Box::new(spanned(self.accessed_object(u)?)),
+                index: Box::new(spanned(Expr::Number(Number::Int(u.int_in_range(0..=9)?)))),
+            },
+            _ => Expr::Property {
+                object: Box::new(spanned(Expr::Index {
+                    object: Box::new(spanned(self.accessed_object(u)?)),
+                    index: Box::new(spanned(Expr::Number(Number::Int(u.int_in_range(0..=9)?)))),
+                })),
+                field: spanned(fresh_ident(u, &[])?),
+            },
+        })
+    }
+
+    /// A literal or variable reference.
+    fn leaf_expr(&self, u: &mut Unstructured) -> Result<Expr> {
+        Ok(match u.choose_index(7)? {
+            0 => Expr::Bool(u.arbitrary()?),
+            1 => Expr::Number(number(u)?),
+            2 => Expr::String(text(u)?),
+            3 => Expr::None,
+            4 => Expr::Date(date(u)?),
+            5 => Expr::Currency(currency(u)?),
+            _ => match self.variable(u)? {
+                Some(reference) => Expr::Reference(reference),
+                None => Expr::None,
+            },
+        })
+    }
+
+    /// Something to index into: a variable, or a list literal without one.
+    fn accessed_object(&self, u: &mut Unstructured) -> Result<Expr> {
+        Ok(match self.variable(u)? {
+            Some(reference) => Expr::Reference(reference),
+            None => Expr::List(vec![spanned(self.leaf_expr(u)?)]),
+        })
+    }
+
+    fn spanned_expr(&self, u: &mut Unstructured) -> Result<Spanned<Expr>> {
+        Ok(spanned(self.expr(u, MAX_EXPR_DEPTH)?))
+    }
+
+    fn instructions(&self, u: &mut Unstructured) -> Result<Instructions> {
+        Ok(match u.choose_index(3)? {
+            0 => Instructions::Simple(text(u)?),
+            1 => Instructions::Static(items(u, 1, 4, spanned_text)?),
+            _ => Instructions::Dynamic(items(u, 1, 4, |u| {
+                Ok(spanned(self.instruction_part(u, true)?))
+            })?),
+        })
+    }
+
+    /// A dynamic instruction part. Conditionals are only generated at the
+    /// top level, since the parser keeps no parts of nested ones, and
+    /// interpolations not at all, since the serializer writes them on lines
+    /// of their own where the parser doesn't read them.
+    fn instruction_part(&self, u: &mut Unstructured, top_level: bool) -> Result<InstructionPart> {
+        if !top_level || !u.arbitrary::<bool>()? {
+            return Ok(InstructionPart::Text(text(u)?));
+        }
+        let part = |u: &mut Unstructured| Ok(spanned(self.instruction_part(u, false)?));
+        Ok(InstructionPart::Conditional {
+            condition: spanned(self.instruction_condition(u)?),
+            then_parts: items(u, 1, 3, part)?,
+            else_parts: optional(u, |u| items(u, 1, 3, part))?,
+        })
+    }
+
+    /// A condition for dynamic instructions: a literal, variable or
+    /// comparison of the two. The parser reads the condition up to the
+    /// first `:`, so it can't hold object literals.
+    fn instruction_condition(&self, u: &mut Unstructured) -> Result<Expr> {
+        if !u.arbitrary::<bool>()? {
+            return self.leaf_expr(u);
+        }
+        Ok(Expr::BinOp {
+            left: Box::new(spanned(self.leaf_expr(u)?)),
+            op: *u.choose(&[BinOp::Eq, BinOp::Ne, BinOp::Lt, BinOp::Gt, BinOp::Is])?,
+            right: Box::new(spanned(self.leaf_expr(u)?)),
+        })
+    }
+
+    fn with_clauses(
+        &self,
+        u: &mut Unstructured,
+        action: &ActionSignature,
+        slot_fill: bool,
+    ) -> Result<Vec<Spanned<WithClause>>> {
+        let mut clauses = Vec::new();
+        for input in &action.inputs {
+            if !u.arbitrary::<bool>()? {
+                continue;
+            }
+            let value = if slot_fill && u.arbitrary()? {
+                Expr::SlotFill
+            } else {
+                self.expr(u, MAX_EXPR_DEPTH)?
+            };
+            clauses.push(spanned(WithClause {
+                param: spanned(input.clone()),
+                value: spanned(WithValue::Expr(value)),
+            }));
+        }
+        Ok(clauses)
+    }
+
+    fn set_clauses(
+        &self,
+        u: &mut Unstructured,
+        action: &ActionSignature,
+    ) -> Result<Vec<Spanned<SetClause>>> {
+        let mut clauses = Vec::new();
+        for output in &action.outputs {
+            if !u.arbitrary::<bool>()? {
+                continue;
+            }
+            if let Some(target) = self.mutable_variable(u)? {
+                clauses.push(spanned(SetClause {
+                    target: spanned(target),
+                    source: spanned(Expr::Reference(Reference::new(
+                        "outputs",
+                        vec![output.clone()],
+                    ))),
+                }));
+            }
+        }
+        Ok(clauses)
+    }
+
+    /// Whether directive blocks can be generated: every statement
+    /// eventually sets a variable or runs an action.
+    fn has_directives(&self) -> bool {
+        !self.mutable_variables.is_empty() || !self.actions.is_empty()
+    }
+
+    fn statements(&self, u: &mut Unstructured, depth: u32) -> Result<Vec<Spanned<Stmt>>> {
+        items(u, 1, 3, |u| Ok(spanned(self.statement(u, depth)?)))
+    }
+
+    /// A directive statement. Requires [`has_directives`](Self::has_directives).
+    fn statement(&self, u: &mut Unstructured, depth: u32) -> Result<Stmt> {
+        let mut kinds = Vec::new();
+        if !self.mutable_variables.is_empty() {
+            kinds.push(ActionClause::Set);
+        }
+        if !self.actions.is_empty() {
+            kinds.push(ActionClause::Run);
+        }
+        if depth < MAX_STMT_DEPTH {
+            kinds.push(ActionClause::If);
+        }
+        Ok(match u.choose(&kinds)? {
+            ActionClause::Set => Stmt::Set {
+                target: spanned(self.mutable_variable(u)?.expect("checked above")),
+                value: self.spanned_expr(u)?,
+            },
+            ActionClause::Run => {
+                let action = self.action(u)?.expect("checked above");
+                Stmt::Run {
+                    action: spanned(Reference::new("actions", vec![action.name.clone()])),
+                    with_clauses: self.with_clauses(u, action, false)?,
+                    set_clauses: self.set_clauses(u, action)?,
+                }
+            }
+            _ => Stmt::If {
+                condition: self.spanned_expr(u)?,
+                then_block: self.statements(u, depth + 1)?,
+                else_block: optional(u, |u| self.statements(u, depth + 1))?,
+            },
+        })
+    }
+
+    fn directive_block(&self, u: &mut Unstructured) -> Result<DirectiveBlock> {
+        Ok(DirectiveBlock {
+            statements: self.statements(u, 0)?,
+        })
+    }
+
+    fn reasoning_action(&self, u: &mut Unstructured, name: String) -> Result<ReasoningAction> {
+        let mut action = ReasoningAction {
+            name: spanned(name),
+            target: spanned(ReasoningActionTarget::Escalate),
+            description: optional(u, spanned_text)?,
+            available_when: optional(u, |u| self.spanned_expr(u))?,
+            with_clauses: Vec::new(),
+            set_clauses: Vec::new(),
+            run_clauses: Vec::new(),
+            if_clauses: Vec::new(),
+            transition: None,
+            clause_order: Vec::new(),
+        };
+        match u.choose_index(4)? {
+            0 => {}
+            1 => action.target.node = ReasoningActionTarget::SetVariables,
+            2 => {
+                if let Some(topic) = self.topic(u)? {
+                    action.target.node = if u.arbitrary()? {
+                        ReasoningActionTarget::TopicDelegate(topic)
+                    } else {
+                        ReasoningActionTarget::TransitionTo(topic)
+                    };
+                }
+            }
+            _ => {
+                if let Some(target) = self.action(u)? {
+                    action.target.node = ReasoningActionTarget::Action(Reference::new(
+                        "actions",
+                        vec![target.name.clone()],
+                    ));
+                    action.with_clauses = self.with_clauses(u, target, true)?;
+                    action.set_clauses = self.set_clauses(u, target)?;
+                    action.run_clauses = items(u, 0, 2, |u| {
+                        let run = self.action(u)?.expect("scope has an action");
+                        Ok(spanned(RunClause {
+                            action: spanned(Reference::new("actions", vec![run.name.clone()])),
+                            with_clauses: self.with_clauses(u, run, false)?,
+                            set_clauses: self.set_clauses(u, run)?,
+                        }))
+                    })?;
+                    if !self.topics.is_empty() {
+                        action.if_clauses = items(u, 0, 2, |u| {
+                            Ok(spanned(IfClause {
+                                condition: self.spanned_expr(u)?,
+                                transition: self.topic(u)?.map(spanned),
+                            }))
+                        })?;
+                        action.transition = optional(u, |u| {
+                            Ok(spanned(self.topic(u)?.expect("scope has a topic")))
+                        })?;
+                    }
+                }
+            }
+        }
+        Ok(action)
+    }
+
+    fn reasoning_block(&self, u: &mut Unstructured) -> Result<ReasoningBlock> {
+        let actions = optional(u, |u| {
+            let names = fresh_idents(u, 1, 4)?;
+            let actions = names
+                .into_iter()
+                .map(|name| Ok(spanned(self.reasoning_action(u, name)?)))
+                .collect::<Result<_>>()?;
+            Ok(spanned(actions))
+        })?;
+        // An empty `reasoning:` block doesn't parse
+        let instructions = if actions.is_none() || u.arbitrary()? {
+            Some(spanned(self.instructions(u)?))
+        } else {
+            None
+        };
+        Ok(ReasoningBlock {
+            instructions,
+            actions,
+        })
+    }
+
+    fn system_override(&self, u: &mut Unstructured) -> Result<TopicSystemOverride> {
+        Ok(TopicSystemOverride {
+            instructions: Some(spanned(self.instructions(u)?)),
+        })
+    }
+
+    fn topic_block(&self, u: &mut Unstructured, name: String) -> Result<TopicBlock> {
+        let doc = optional(u, spanned_text)?;
+        // Always described, as an empty block doesn't parse
+        let description = Some(spanned_text(u)?);
+        let system = optional(u, |u| Ok(spanned(self.system_override(u)?)))?;
+        let actions = optional(u, |u| Ok(spanned(ActionsBlock::arbitrary(u)?)))?;
+        let scope = self.with_actions(&actions);
+        let directives = |u: &mut Unstructured| -> Result<Option<Spanned<DirectiveBlock>>> {
+            if !scope.has_directives() {
+                return Ok(None);
+            }
+            optional(u, |u| Ok(spanned(scope.directive_block(u)?)))
+        };
+        Ok(TopicBlock {
+            name: spanned(name),
+            quoted_name: None,
+            description,
+            system,
+            before_reasoning: directives(u)?,
+            reasoning: optional(u, |u| Ok(spanned(scope.reasoning_block(u)?)))?,
+            after_reasoning: directives(u)?,
+            actions,
+            doc,
+        })
+    }
+}
+
+fn pick(u: &mut Unstructured, names: &[String], namespace: &str) -> Result<Option<Reference>> {
+    if names.is_empty() {
+        return Ok(None);
+    }
+    let name = u.choose(names)?;
+    Ok(Some(Reference::new(namespace, vec![name.clone()])))
+}
+
+/// A non-negative number, sometimes with a fractional part.
+fn number(u: &mut Unstructured) -> Result<Number> {
+    let whole = u.int_in_range(0..=9999)?;
+    Ok(match u.choose(&[None, Some(0.5), Some(0.25)])? {
+        None => Number::Int(whole),
+        Some(fraction) => Number::from(whole as f64 + fraction),
+    })
+}
+
+/// A date this century. Every month has a 28th.
+fn date(u: &mut Unstructured) -> Result<Date> {
+    Ok(Date {
+        year: u.int_in_range(2000..=2099)?,
+        month: u.int_in_range(1..=12)?,
+        day: u.int_in_range(1..=28)?,
+    })
+}
+
+/// An amount in the default currency or with an explicit code.
+fn currency(u: &mut Unstructured) -> Result<Currency> {
+    Ok(Currency {
+        amount: number(u)?,
+        code: u
+            .choose(&[None, Some("USD"), Some("EUR")])?
+            .map(str::to_string),
+    })
+}
+
+/// A literal default value of type `ty`.
+fn default_value(u: &mut Unstructured, ty: &Type) -> Result<Expr> {
+    Ok(match ty {
+        Type::String | Type::Id | Type::Datetime | Type::Time => Expr::String(text(u)?),
+        Type::Date => Expr::Date(date(u)?),
+        Type::Currency => Expr::Currency(currency(u)?),
+        Type::Number | Type::Timestamp | Type::Integer | Type::Long => {
+            Expr::Number(Number::Int(u.int_in_range(0..=9999)?))
+        }
+        Type::Boolean => Expr::Bool(u.arbitrary()?),
+        Type::Object => Expr::Object(IndexMap::new()),
+        Type::List(_) => Expr::List(Vec::new()),
+    })
+}
+
+/// Implements `Arbitrary` for a type by generating it in the sample scope.
+macro_rules! in_sample_scope {
+    ($($ty:ty => |$scope:ident, $u:ident| $body:expr;)*) => {
+        $(
+            impl<'a> Arbitrary<'a> for $ty {
+                fn arbitrary($u: &mut Unstructured<'a>) -> Result<Self> {
+                    let $scope = Scope::sample();
+                    $body
+                }
+            }
+        )*
+    };
+}
+
+in_sample_scope! {
+    Expr => |scope, u| scope.expr(u, MAX_EXPR_DEPTH);
+    Instructions => |scope, u| scope.instructions(u);
+    InstructionPart => |scope, u| scope.instruction_part(u, true);
+    Stmt => |scope, u| scope.statement(u, 0);
+    DirectiveBlock => |scope, u| scope.directive_block(u);
+    ReasoningBlock => |scope, u| scope.reasoning_block(u);
+    TopicSystemOverride => |scope, u| scope.system_override(u);
+    ReasoningAction => |scope, u| {
+        let name = fresh_ident(u, &[])?;
+        scope.reasoning_action(u, name)
+    };
+    ReasoningActionTarget => |scope, u| {
+        let name = fresh_ident(u, &[])?;
+        Ok(scope.reasoning_action(u, name)?.target.node)
+    };
+    WithClause => |scope, u| {
+        let action = &scope.actions[0];
+        Ok(WithClause {
+            param: spanned(action.inputs[0].clone()),
+            value: spanned(WithValue::Expr(scope.expr(u, MAX_EXPR_DEPTH)?)),
+        })
+    };
+    WithValue => |scope, u| Ok(WithValue::Expr(scope.expr(u, MAX_EXPR_DEPTH)?));
+    SetClause => |scope, u| Ok(SetClause {
+        target: spanned(scope.mutable_variable(u)?.expect("sample scope has variables")),
+        source: spanned(Expr::Reference(Reference::new("outputs", vec!["result".into()]))),
+    });
+    RunClause => |scope, u| {
+        let action = &scope.actions[0];
+        Ok(RunClause {
+            action: spanned(Reference::new("actions", vec![action.name.clone()])),
+            with_clauses: scope.with_clauses(u, action, false)?,
+            set_clauses: scope.set_clauses(u, action)?,
+        })
+    };
+    IfClause => |scope, u| Ok(IfClause {
+        condition: scope.spanned_expr(u)?,
+        transition: scope.topic(u)?.map(spanned),
+    });
+    Reference => |scope, u| Ok(match u.choose_index(3)? {
+        0 => scope.variable(u)?,
+        1 => scope.topic(u)?,
+        _ => Some(Reference::new("actions", vec![scope.actions[0].name.clone()])),
+    }
+    .expect("sample scope has every kind"));
+    TopicBlock => |scope, u| {
+        let name = fresh_ident(u, &scope.topics)?;
+        scope.topic_block(u, name)
+    };
+    StartAgentBlock => |scope, u| {
+        let topic = scope.topic_block(u, "topic_selector".to_string())?;
+        Ok(start_agent(topic))
+    };
+}
+
+fn start_agent(topic: TopicBlock) -> StartAgentBlock {
+    StartAgentBlock {
+        name: topic.name,
+        description: topic.description,
+        system: topic.system,
+        actions: topic.actions,
+        before_reasoning: topic.before_reasoning,
+        reasoning: topic.reasoning,
+        after_reasoning: topic.after_reasoning,
+        doc: topic.doc,
+    }
+}
+
+impl<'a, T: Arbitrary<'a>> Arbitrary<'a> for Spanned<T> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(spanned(T::arbitrary(u)?))
+    }
+
+    fn size_hint(depth: usize) -> (usize, Option<usize>) {
+        T::size_hint(depth)
+    }
+}
+
+impl<'a> Arbitrary<'a> for AgentFile {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let config = ConfigBlock::arbitrary(u)?;
+        let variables = fresh_idents(u, 0, 6)?
+            .into_iter()
+            .map(|name| variable_decl(u, name))
+            .collect::<Result<Vec<_>>>()?;
+        let topic_names = fresh_idents(u, 0, 4)?;
+
+        let scope = Scope {
+            mutable_variables: variables
+                .iter()
+                .filter(|v| v.kind == VariableKind::Mutable)
+                .map(|v| v.name.node.clone())
+                .collect(),
+            variables: variables.iter().map(|v| v.name.node.clone()).collect(),
+            topics: topic_names.clone(),
+            actions: Vec::new(),
+        };
+
+        let system = optional(u, |u| {
+            Ok(SystemBlock {
+                instructions: Some(spanned(Instructions::Simple(text(u)?))),
+                messages: optional(u, Spanned::arbitrary)?,
+            })
+        })?;
+        let connection_names = fresh_idents(u, 0, 2)?;
+        let connections = connection_names
+            .into_iter()
+            .map(|name| {
+                Ok(spanned(ConnectionBlock {
+                    name: spanned(name),
+                    entries: connection_entries(u)?,
+                }))
+            })
+            .collect::<Result<_>>()?;
+        let language = optional(u, LanguageBlock::arbitrary)?;
+        let start_agent = optional(u, |u| {
+            let topic = scope.topic_block(u, "topic_selector".to_string())?;
+            Ok(start_agent(topic))
+        })?;
+        let topics = topic_names
+            .into_iter()
+            .map(|name| Ok(spanned(scope.topic_block(u, name)?)))
+            .collect::<Result<_>>()?;
+
+        Ok(AgentFile {
+            config: Some(spanned(config)),
+            variables: (!variables.is_empty()).then(|| {
+                spanned(VariablesBlock {
+                    variables: variables.into_iter().map(spanned).collect(),
+                })
+            }),
+            system: system.map(spanned),
+            connections,
+            language: language.map(spanned),
+            start_agent: start_agent.map(spanned),
+            topics,
+            ..AgentFile::default()
+        })
+    }
+}
+
+fn variable_decl(u: &mut Unstructured, name: String) -> Result<VariableDecl> {
+    let doc = optional(u, spanned_text)?;
+    let kind = VariableKind::arbitrary(u)?;
+    let ty = Type::arbitrary(u)?;
+    let (default, source) = match kind {
+        VariableKind::Mutable => (Some(spanned(default_value(u, &ty)?)), None),
+        VariableKind::Linked => {
+            let path = fresh_idents(u, 1, 2)?;
+            (None, Some(spanned(Reference::new("context", path))))
+        }
+    };
+    Ok(VariableDecl {
+        name: spanned(name),
+        kind,
+        ty: spanned(ty),
+        default,
+        description: optional(u, spanned_text)?,
+        source,
+        doc,
+    })
+}
+
+impl<'a> Arbitrary<'a> for VariableDecl {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let name = fresh_ident(u, &[])?;
+        variable_decl(u, name)
+    }
+}
+
+impl<'a> Arbitrary<'a> for VariablesBlock {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let names = fresh_idents(u, 1, 6)?;
+        Ok(VariablesBlock {
+            variables: names
+                .into_iter()
+                .map(|name| Ok(spanned(variable_decl(u, name)?)))
+                .collect::<Result<_>>()?,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for VariableKind {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(*u.choose(&[VariableKind::Mutable, VariableKind::Linked])?)
+    }
+}
+
+impl<'a> Arbitrary<'a> for Type {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        const SIMPLE: [Type; 12] = [
+            Type::String,
+            Type::Number,
+            Type::Boolean,
+            Type::Object,
+            Type::Date,
+            Type::Timestamp,
+            Type::Currency,
+            Type::Id,
+            Type::Datetime,
+            Type::Time,
+            Type::Integer,
+            Type::Long,
+        ];
+        let ty = u.choose(&SIMPLE)?.clone();
+        Ok(if u.ratio(1, 8)? {
+            Type::List(Box::new(ty))
+        } else {
+            ty
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for ConfigBlock {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(ConfigBlock {
+            agent_name: spanned(fresh_ident(u, &[])?),
+            agent_label: optional(u, spanned_text)?,
+            description: optional(u, spanned_text)?,
+            agent_type: optional(u, |u| {
+                let ty = u.choose(&["AgentforceServiceAgent", "AgentforceEmployeeAgent"])?;
+                Ok(spanned(ty.to_string()))
+            })?,
+            default_agent_user: optional(u, |u| {
+                Ok(spanned(format!("{}@example.com", fresh_ident(u, &[])?)))
+            })?,
+            target_environment: optional(u, |u| {
+                let target = u.choose(&["dev", "sandbox", "prod"])?;
+                Ok(spanned(target.to_string()))
+            })?,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for SystemBlock {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(SystemBlock {
+            instructions: Some(spanned(Instructions::Simple(text(u)?))),
+            messages: optional(u, Spanned::arbitrary)?,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for SystemMessages {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(SystemMessages {
+            welcome: Some(spanned_text(u)?),
+            error: optional(u, spanned_text)?,
+        })
+    }
+}
+
+fn connection_entries(u: &mut Unstructured) -> Result<Vec<Spanned<ConnectionEntry>>> {
+    let mut entries = vec![spanned(ConnectionEntry {
+        name: spanned("escalation_message".into()),
+        value: spanned_text(u)?,
+    })];
+    if u.arbitrary()? {
+        entries.push(spanned(ConnectionEntry {
+            name: spanned("outbound_route_type".into()),
+            value: spanned("OmniChannelFlow".into()),
+        }));
+        entries.push(spanned(ConnectionEntry {
+            name: spanned("outbound_route_name".into()),
+            value: spanned(fresh_ident(u, &[])?),
+        }));
+    }
+    Ok(entries)
+}
+
+impl<'a> Arbitrary<'a> for ConnectionBlock {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(ConnectionBlock {
+            name: spanned(fresh_ident(u, &[])?),
+            entries: connection_entries(u)?,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for ConnectionEntry {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(connection_entries(u)?.swap_remove(0).node)
+    }
+}
+
+impl<'a> Arbitrary<'a> for KnowledgeBlock {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(KnowledgeBlock {
+            entries: items(u, 1, 3, Spanned::arbitrary)?,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for KnowledgeEntry {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(KnowledgeEntry {
+            name: spanned(fresh_ident(u, &[])?),
+            value: spanned(Scope::default().leaf_expr(u)?),
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for LanguageBlock {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let mut entries = vec![spanned(LanguageEntry {
+            name: spanned("default_locale".into()),
+            value: spanned(Expr::String(u.choose(&["en_US", "fr", "de", "ja"])?.to_string())),
+        })];
+        if u.arbitrary()? {
+            entries.push(spanned(LanguageEntry {
+                name: spanned("all_additional_locales".into()),
+                value: spanned(Expr::Bool(u.arbitrary()?)),
+            }));
+        }
+        Ok(LanguageBlock { entries })
+    }
+}
+
+impl<'a> Arbitrary<'a> for LanguageEntry {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(LanguageBlock::arbitrary(u)?.entries.swap_remove(0).node)
+    }
+}
+
+impl<'a> Arbitrary<'a> for RecoveredBlock {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let kind = *u.choose(&["topic", "start_agent", "config", "variables"])?;
+        Ok(RecoveredBlock {
+            kind: kind.to_string(),
+            name: match kind {
+                "topic" | "start_agent" => Some(spanned(fresh_ident(u, &[])?)),
+                _ => None,
+            },
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for ActionsBlock {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let names = fresh_idents(u, 1, 3)?;
+        Ok(ActionsBlock {
+            actions: names
+                .into_iter()
+                .map(|name| Ok(spanned(action_def(u, name)?)))
+                .collect::<Result<_>>()?,
+        })
+    }
+}
+
+fn action_def(u: &mut Unstructured, name: String) -> Result<ActionDef> {
+    let params = |u: &mut Unstructured| -> Result<Spanned<Vec<Spanned<ParamDef>>>> {
+        let names = fresh_idents(u, 1, 3)?;
+        Ok(spanned(
+            names
+                .into_iter()
+                .map(|name| Ok(spanned(param_def(u, name)?)))
+                .collect::<Result<_>>()?,
+        ))
+    };
+    Ok(ActionDef {
+        doc: optional(u, spanned_text)?,
+        description: optional(u, spanned_text)?,
+        label: optional(u, spanned_text)?,
+        require_user_confirmation: flag(u)?,
+        include_in_progress_indicator: flag(u)?,
+        progress_indicator_message: optional(u, spanned_text)?,
+        inputs: optional(u, params)?,
+        outputs: optional(u, params)?,
+        target: Some(spanned(format!(
+            "{}://{}",
+            u.choose(&["flow", "apex", "prompt"])?,
+            fresh_ident(u, &[])?
+        ))),
+        name: spanned(name),
+    })
+}
+
+impl<'a> Arbitrary<'a> for ActionDef {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let name = fresh_ident(u, &[])?;
+        action_def(u, name)
+    }
+}
+
+fn param_def(u: &mut Unstructured, name: String) -> Result<ParamDef> {
+    Ok(ParamDef {
+        name: spanned(name),
+        ty: Spanned::arbitrary(u)?,
+        description: optional(u, spanned_text)?,
+        label: optional(u, spanned_text)?,
+        is_required: flag(u)?,
+        filter_from_agent: flag(u)?,
+        is_displayable: flag(u)?,
+        complex_data_type_name: optional(u, |u| {
+            Ok(spanned(format!("@apexClassType/c__{}", fresh_ident(u, &[])?)))
+        })?,
+    })
+}
+
+impl<'a> Arbitrary<'a> for ParamDef {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let name = fresh_ident(u, &[])?;
+        param_def(u, name)
+    }
+}
+
+impl<'a> Arbitrary<'a> for OutputRef {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(OutputRef {
+            name: fresh_ident(u, &[])?,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for ActionClause {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        u.choose(&ActionClause::CANONICAL).copied()
+    }
+}
+
+impl<'a> Arbitrary<'a> for BinOp {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        u.choose(&[
+            BinOp::Eq,
+            BinOp::Ne,
+            BinOp::Lt,
+            BinOp::Gt,
+            BinOp::Le,
+            BinOp::Ge,
+            BinOp::Is,
+            BinOp::IsNot,
+            BinOp::And,
+            BinOp::Or,
+            BinOp::Add,
+            BinOp::Sub,
+            BinOp::Mul,
+            BinOp::Div,
+            BinOp::Mod,
+        ])
+        .copied()
+    }
+}
+
+impl<'a> Arbitrary<'a> for UnaryOp {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        u.choose(&[UnaryOp::Not, UnaryOp::Neg]).copied()
+    }
+}
+
+impl<'a> Arbitrary<'a> for BuiltinFunction {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        u.choose(BUILTIN_FUNCTIONS).copied()
+    }
+}
+
+impl<'a> Arbitrary<'a> for Comment {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(Comment {
+            text: format!("# {}", text(u)?),
+            span: 0..0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse, serialize};
+
+    /// Deterministic pseudo-random bytes.
+    fn bytes(seed: u64, len: usize) -> Vec<u8> {
+        let mut state = seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_generated_agents_roundtrip() {
+        for seed in 0..200 {
+            let data = bytes(seed, 4096);
+            let agent = AgentFile::arbitrary(&mut Unstructured::new(&data)).unwrap();
+            let source = serialize(&agent);
+            let reparsed =
+                parse(&source).unwrap_or_else(|errors| panic!("seed {seed}: {errors:?}\n{source}"));
+            assert_eq!(serialize(&reparsed), source, "seed {seed}");
+        }
+    }
+
+    #[test]
+    fn test_empty_input_gives_minimal_agent() {
+        let agent = AgentFile::arbitrary(&mut Unstructured::new(&[])).unwrap();
+        assert!(agent.config.is_some());
+        assert!(agent.topics.is_empty());
+        assert!(agent.variables.is_none());
+        assert!(agent.start_agent.is_none());
+
+        // Truncated input gives a smaller agent
+        let data = bytes(7, 4096);
+        let full = serialize(&AgentFile::arbitrary(&mut Unstructured::new(&data)).unwrap());
+        let short = serialize(&AgentFile::arbitrary(&mut Unstructured::new(&data[..64])).unwrap());
+        assert!(short.len() <= full.len());
+    }
+}
@@ -46,6 +46,7 @@
 //! let json = serde_json::to_string(&agent).unwrap();
 //! ```
 
+use crate::target_uri::{TargetUri, TargetUriError};
 use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
 use std::ops::Range;
@@ -174,18 +175,27 @@ pub struct AgentFile {
     /// Should be the first block in the file.
     pub config: Option<Spanned<ConfigBlock>>,
 
+    /// Further `config:` sections after the first, kept for diagnostics.
+    pub duplicate_configs: Vec<Spanned<ConfigBlock>>,
+
     /// Variable declarations (`variables:` section).
     ///
     /// Defines state variables that can be `mutable` (read-write)
     /// or `linked` (read-only from external context).
     pub variables: Option<Spanned<VariablesBlock>>,
 
+    /// Further `variables:` sections after the first, kept for diagnostics.
+    pub duplicate_variables: Vec<Spanned<VariablesBlock>>,
+
     /// System configuration (`system:` section).
     ///
     /// Contains global instructions and system messages like
     /// welcome and error messages.
     pub system: Option<Spanned<SystemBlock>>,
 
+    /// Further `system:` sections after the first, kept for diagnostics.
+    pub duplicate_systems: Vec<Spanned<SystemBlock>>,
+
     /// Connection configurations (`connection <name>:` blocks).
     ///
     /// Each connection block defines escalation routing for a specific channel.
@@ -208,11 +218,24 @@ pub struct AgentFile {
     /// to appropriate topics.
     pub start_agent: Option<Spanned<StartAgentBlock>>,
 
+    /// Further `start_agent:` sections after the first.
+    ///
+    /// An agent has a single entry point; these are kept so tooling can
+    /// report and fix them rather than silently dropping them.
+    pub duplicate_start_agents: Vec<Spanned<StartAgentBlock>>,
+
     /// Conversation topics (`topic:` sections).
     ///
     /// Each topic defines a conversational context with its own
     /// reasoning instructions and available actions.
     pub topics: Vec<Spanned<TopicBlock>>,
+
+    /// Top-level blocks that failed to parse.
+    ///
+    /// Only present in the partial AST returned alongside errors (e.g. by
+    /// [`parse_with_errors`](crate::parser::parse_with_errors)). Each records
+    /// the block's extent, so editors can still show and fold it.
+    pub recovered_blocks: Vec<Spanned<RecoveredBlock>>,
 }
 
 impl AgentFile {
@@ -230,6 +253,22 @@ impl AgentFile {
     pub fn new() -> Self {
         Self::default()
     }
+
+    /// Whether `self` and `other` are the same agent, ignoring where in the
+    /// source each node was. See [`SpanlessEq`](crate::spanless::SpanlessEq).
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use busbar_sf_agentscript::parse;
+    ///
+    /// let a = parse("topic main:\n   description: \"Main\"\n").unwrap();
+    /// let b = parse("\ntopic main:\n   description:  \"Main\"\n").unwrap();
+    /// assert!(a.eq_ignore_spans(&b));
+    /// ```
+    pub fn eq_ignore_spans(&self, other: &AgentFile) -> bool {
+        crate::spanless::SpanlessEq::eq_ignore_spans(self, other)
+    }
 }
 
 // ============================================================================
@@ -270,6 +309,12 @@ pub struct ConfigBlock {
 
     /// Default user email for agent operations.
     pub default_agent_user: Option<Spanned<String>>,
+
+    /// Environment the agent is checked for: `dev`, `sandbox` or `prod`.
+    ///
+    /// Tools use it unless told otherwise; see
+    /// [`TargetEnvironment`](crate::validation::TargetEnvironment).
+    pub target_environment: Option<Spanned<String>>,
 }
 
 // ============================================================================
@@ -324,6 +369,9 @@ pub struct VariableDecl {
     ///
     /// Example: `source: @context.user.email`
     pub source: Option<Spanned<Reference>>,
+
+    /// Doc comment: contiguous `#` lines directly above the definition.
+    pub doc: Option<Spanned<String>>,
 }
 
 /// Variable mutability kind.
@@ -353,7 +401,7 @@ pub enum VariableKind {
 /// | `boolean` | True/False |
 /// | `integer` | Whole number |
 /// | `id` | Salesforce record ID |
-/// | `list<string>` | Array of strings |
+/// | `list[string]` | Array of strings (`list<string>` is also accepted) |
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Type {
     /// Text data (`string`).
@@ -380,7 +428,7 @@ pub enum Type {
     Integer,
     /// Large integer (`long`).
     Long,
-    /// Array type (`list<T>`).
+    /// Array type (`list[T]`).
     List(Box<Type>),
 }
 
@@ -395,8 +443,19 @@ impl Type {
     /// assert_eq!(Type::parse_type("string"), Some(Type::String));
     /// assert_eq!(Type::parse_type("number"), Some(Type::Number));
     /// assert_eq!(Type::parse_type("unknown"), None);
+    /// assert_eq!(
+    ///     Type::parse_type("list<id>"),
+    ///     Some(Type::List(Box::new(Type::Id)))
+    /// );
     /// ```
     pub fn parse_type(s: &str) -> Option<Self> {
+        let element = s
+            .strip_prefix("list[")
+            .and_then(|rest| rest.strip_suffix(']'))
+            .or_else(|| s.strip_prefix("list<")?.strip_suffix('>'));
+        if let Some(element) = element {
+            return Self::parse_type(element.trim()).map(|ty| Type::List(Box::new(ty)));
+        }
         match s {
             "string" => Some(Type::String),
             "number" => Some(Type::Number),
@@ -524,6 +583,8 @@ pub struct StartAgentBlock {
     pub reasoning: Option<Spanned<ReasoningBlock>>,
     /// Optional after_reasoning block.
     pub after_reasoning: Option<Spanned<DirectiveBlock>>,
+    /// Doc comment: contiguous `#` lines directly above the definition.
+    pub doc: Option<Spanned<String>>,
 }
 
 // ============================================================================
@@ -533,8 +594,11 @@ pub struct StartAgentBlock {
 /// A topic block defines a conversation topic.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TopicBlock {
-    /// Topic name.
+    /// Topic name. For a quoted name this is its [`identifier_for`] form,
+    /// which references and graph keys use.
     pub name: Spanned<String>,
+    /// The name as written when quoted: `topic "Claims – EU":`.
+    pub quoted_name: Option<Spanned<String>>,
     /// Description of the topic.
     pub description: Option<Spanned<String>>,
     /// Optional system override.
@@ -547,6 +611,49 @@ pub struct TopicBlock {
     pub reasoning: Option<Spanned<ReasoningBlock>>,
     /// Optional after_reasoning block.
     pub after_reasoning: Option<Spanned<DirectiveBlock>>,
+    /// Doc comment: contiguous `#` lines directly above the definition.
+    pub doc: Option<Spanned<String>>,
+}
+
+/// The identifier a quoted name is referenced by.
+///
+/// Every run of characters other than ASCII letters, digits and `_` becomes a
+/// single `_`, and a leading digit gets a `_` prefix.
+///
+/// # Example
+///
+/// ```rust
+/// use busbar_sf_agentscript::ast::identifier_for;
+///
+/// assert_eq!(identifier_for("Claims – EU"), "Claims_EU");
+/// assert_eq!(identifier_for("2nd line"), "_2nd_line");
+/// ```
+pub fn identifier_for(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    for c in name.trim().chars() {
+        if c.is_ascii_alphanumeric() || c == '_' {
+            out.push(c);
+        } else if !out.ends_with('_') {
+            out.push('_');
+        }
+    }
+    let out = out.trim_end_matches('_');
+    if out.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{}", out)
+    } else {
+        out.to_string()
+    }
+}
+
+/// A top-level block skipped by error recovery.
+///
+/// The span covers the header line and the whole indented body.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecoveredBlock {
+    /// Block keyword (`topic`, `start_agent`, `config`, ...).
+    pub kind: String,
+    /// Name after the keyword, for named blocks like `topic main:`.
+    pub name: Option<Spanned<String>>,
 }
 
 /// System instruction override for a topic.
@@ -586,6 +693,28 @@ pub struct ActionDef {
     pub outputs: Option<Spanned<Vec<Spanned<ParamDef>>>>,
     /// Target (e.g., "flow://FlowName").
     pub target: Option<Spanned<String>>,
+    /// Doc comment: contiguous `#` lines directly above the definition.
+    pub doc: Option<Spanned<String>>,
+}
+
+impl ActionDef {
+    /// The parsed `target:`, if the action has one.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use busbar_sf_agentscript::target_uri::TargetScheme;
+    ///
+    /// let source = "topic main:\n   actions:\n      lookup:\n         target: \"flow://Lookup\"\n";
+    /// let ast = busbar_sf_agentscript::parse(source).unwrap();
+    /// let action = &ast.topics[0].node.actions.as_ref().unwrap().node.actions[0].node;
+    /// let uri = action.target_uri().unwrap().unwrap();
+    /// assert_eq!(uri.scheme, TargetScheme::Flow);
+    /// assert_eq!(uri.name, "Lookup");
+    /// ```
+    pub fn target_uri(&self) -> Option<Result<TargetUri, TargetUriError>> {
+        self.target.as_ref().map(|target| target.node.parse())
+    }
 }
 
 /// A parameter definition (for action inputs/outputs).
@@ -641,6 +770,10 @@ pub enum Stmt {
     },
     /// Transition: `transition to @topic.name`
     Transition { target: Spanned<Reference> },
+    /// Escalation to a human agent: `escalate`
+    Escalate,
+    /// Message to the user: `respond "..."`
+    Respond { message: Spanned<String> },
 }
 
 /// A with clause binds an action input.
@@ -706,6 +839,138 @@ pub struct ReasoningAction {
     pub if_clauses: Vec<Spanned<IfClause>>,
     /// Post-action transition.
     pub transition: Option<Spanned<Reference>>,
+    /// Kind of each clause above, in source order.
+    ///
+    /// Empty for actions built in code. If it doesn't account for exactly the
+    /// clauses present (e.g. after editing the AST), [`clauses`](Self::clauses)
+    /// falls back to canonical order.
+    pub clause_order: Vec<ActionClause>,
+}
+
+/// Kinds of clause in a reasoning action body.
+///
+/// Variants are declared in the canonical order the platform expects, so the
+/// derived `Ord` sorts clauses canonically.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum ActionClause {
+    /// `description: "..."`
+    Description,
+    /// `available when <expr>`
+    AvailableWhen,
+    /// `with param = value`
+    With,
+    /// `set @variables.x = value`
+    Set,
+    /// `run @actions.x`
+    Run,
+    /// `if <expr>:`
+    If,
+    /// `transition to @topic.x`
+    Transition,
+}
+
+impl ActionClause {
+    /// All clause kinds, in canonical order.
+    pub const CANONICAL: [ActionClause; 7] = [
+        ActionClause::Description,
+        ActionClause::AvailableWhen,
+        ActionClause::With,
+        ActionClause::Set,
+        ActionClause::Run,
+        ActionClause::If,
+        ActionClause::Transition,
+    ];
+
+    /// The keyword(s) that introduce the clause.
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            ActionClause::Description => "description",
+            ActionClause::AvailableWhen => "available when",
+            ActionClause::With => "with",
+            ActionClause::Set => "set",
+            ActionClause::Run => "run",
+            ActionClause::If => "if",
+            ActionClause::Transition => "transition",
+        }
+    }
+}
+
+impl ReasoningAction {
+    /// Number of clauses of `kind` in this action.
+    pub fn clause_count(&self, kind: ActionClause) -> usize {
+        match kind {
+            ActionClause::Description => self.description.iter().count(),
+            ActionClause::AvailableWhen => self.available_when.iter().count(),
+            ActionClause::With => self.with_clauses.len(),
+            ActionClause::Set => self.set_clauses.len(),
+            ActionClause::Run => self.run_clauses.len(),
+            ActionClause::If => self.if_clauses.len(),
+            ActionClause::Transition => self.transition.iter().count(),
+        }
+    }
+
+    /// The action's clauses in source order, as `(kind, index)` pairs where
+    /// `index` counts clauses of the same kind (e.g. `(With, 1)` is
+    /// `with_clauses[1]`).
+    ///
+    /// Uses [`clause_order`](Self::clause_order) when it matches the clauses
+    /// present, and canonical order otherwise.
+    pub fn clauses(&self) -> Vec<(ActionClause, usize)> {
+        let recorded = ActionClause::CANONICAL.iter().all(|kind| {
+            self.clause_order.iter().filter(|k| *k == kind).count() == self.clause_count(*kind)
+        });
+        let order: Vec<ActionClause> = if recorded {
+            self.clause_order.clone()
+        } else {
+            ActionClause::CANONICAL
+                .iter()
+                .flat_map(|kind| std::iter::repeat_n(*kind, self.clause_count(*kind)))
+                .collect()
+        };
+
+        let mut seen = [0; ActionClause::CANONICAL.len()];
+        order
+            .into_iter()
+            .map(|kind| {
+                let index = seen[kind as usize];
+                seen[kind as usize] += 1;
+                (kind, index)
+            })
+            .collect()
+    }
+
+    /// Source span of a clause returned by [`clauses`](Self::clauses).
+    pub fn clause_span(&self, kind: ActionClause, index: usize) -> Option<Range<usize>> {
+        match kind {
+            ActionClause::Description => self.description.as_ref().map(|d| d.span.clone()),
+            ActionClause::AvailableWhen => self.available_when.as_ref().map(|a| a.span.clone()),
+            ActionClause::With => self.with_clauses.get(index).map(|w| w.span.clone()),
+            ActionClause::Set => self.set_clauses.get(index).map(|s| s.span.clone()),
+            ActionClause::Run => self.run_clauses.get(index).map(|r| r.span.clone()),
+            ActionClause::If => self.if_clauses.get(index).map(|i| i.span.clone()),
+            ActionClause::Transition => self.transition.as_ref().map(|t| t.span.clone()),
+        }
+    }
+
+    /// The first clause that appears after a clause which should follow it,
+    /// with the kind it was placed after. `None` if clauses are in canonical
+    /// order.
+    pub fn first_misordered_clause(&self) -> Option<((ActionClause, usize), ActionClause)> {
+        let mut latest: Option<ActionClause> = None;
+        for clause in self.clauses() {
+            match latest {
+                Some(previous) if clause.0 < previous => return Some((clause, previous)),
+                _ => latest = Some(clause.0),
+            }
+        }
+        None
+    }
+
+    /// Put the recorded clause order into canonical order.
+    pub fn canonicalize_clause_order(&mut self) {
+        self.clause_order = self.clauses().into_iter().map(|(kind, _)| kind).collect();
+        self.clause_order.sort();
+    }
 }
 
 /// Target of a reasoning action.
@@ -753,6 +1018,117 @@ pub enum Instructions {
     Dynamic(Vec<Spanned<InstructionPart>>),
 }
 
+impl Instructions {
+    /// Rough token count: about four characters per token.
+    ///
+    /// Interpolated values count as short strings, and a conditional as its
+    /// longer branch.
+    pub fn estimated_tokens(&self) -> usize {
+        fn chars(parts: &[Spanned<InstructionPart>]) -> usize {
+            parts
+                .iter()
+                .map(|part| match &part.node {
+                    InstructionPart::Text(text) => text.chars().count() + 1,
+                    InstructionPart::Heading { level, title } => level + title.chars().count() + 2,
+                    InstructionPart::Interpolation(_) => 16,
+                    InstructionPart::Conditional {
+                        then_parts,
+                        else_parts,
+                        ..
+                    } => chars(then_parts).max(else_parts.as_deref().map_or(0, chars)),
+                })
+                .sum()
+        }
+        let chars = match self {
+            Instructions::Simple(text) => text.chars().count(),
+            Instructions::Static(lines) => lines.iter().map(|l| l.node.chars().count() + 1).sum(),
+            Instructions::Dynamic(parts) => chars(parts),
+        };
+        chars.div_ceil(4)
+    }
+
+    /// The sections of dynamic instructions, nested by heading level.
+    ///
+    /// A section runs from its heading to the next heading at the same or
+    /// a higher level. Only headings outside conditionals start sections,
+    /// and text before the first heading belongs to none.
+    ///
+    /// ```rust
+    /// use busbar_sf_agentscript::parse;
+    ///
+    /// let source = r#"topic main:
+    ///    description: "Main"
+    ///    reasoning:
+    ///       instructions: ->
+    ///          |## Rules
+    ///          | Be polite
+    ///          |### Tone
+    ///          | Friendly
+    ///          |## Escalation
+    ///          | Ask first
+    /// "#;
+    /// let ast = parse(source).unwrap();
+    /// let reasoning = ast.topics[0].node.reasoning.as_ref().unwrap();
+    /// let sections = reasoning.node.instructions.as_ref().unwrap().node.sections();
+    /// let titles: Vec<_> = sections.iter().map(|s| s.title.as_str()).collect();
+    /// assert_eq!(titles, ["Rules", "Escalation"]);
+    /// assert_eq!(sections[0].children[0].title, "Tone");
+    /// assert_eq!(&source[sections[1].span.clone()], "|## Escalation\n         | Ask first");
+    /// ```
+    pub fn sections(&self) -> Vec<InstructionSection> {
+        let Instructions::Dynamic(parts) = self else {
+            return Vec::new();
+        };
+        let mut flat: Vec<InstructionSection> = Vec::new();
+        // Indices of the sections still open, innermost last
+        let mut open: Vec<usize> = Vec::new();
+        for part in parts {
+            if let InstructionPart::Heading { level, title } = &part.node {
+                open.retain(|&i| flat[i].level < *level);
+                open.push(flat.len());
+                flat.push(InstructionSection {
+                    level: *level,
+                    title: title.clone(),
+                    heading: part.span.clone(),
+                    span: part.span.clone(),
+                    children: Vec::new(),
+                });
+            }
+            for &i in &open {
+                flat[i].span.end = flat[i].span.end.max(part.span.end);
+            }
+        }
+        nest_sections(&mut flat.into_iter().peekable(), 0)
+    }
+}
+
+/// Nest a flat list of sections under the nearest heading of a lower level.
+fn nest_sections(
+    flat: &mut std::iter::Peekable<impl Iterator<Item = InstructionSection>>,
+    above: usize,
+) -> Vec<InstructionSection> {
+    let mut sections = Vec::new();
+    while let Some(mut section) = flat.next_if(|s| s.level > above) {
+        section.children = nest_sections(flat, section.level);
+        sections.push(section);
+    }
+    sections
+}
+
+/// A named section of dynamic instructions, from [`Instructions::sections`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct InstructionSection {
+    /// Heading level: 2 for `##`, 3 for `###`, and so on.
+    pub level: usize,
+    pub title: String,
+    /// Span of the heading line.
+    pub heading: Range<usize>,
+    /// Span of the heading and everything in the section.
+    pub span: Range<usize>,
+    /// Sections under this one, at deeper levels.
+    pub children: Vec<InstructionSection>,
+}
+
 /// A part of dynamic instructions.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum InstructionPart {
@@ -760,6 +1136,9 @@ pub enum InstructionPart {
     Text(String),
     /// Template interpolation `{!expr}`.
     Interpolation(Expr),
+    /// Section heading (`|## Rules`), a line holding only two or more `#`
+    /// and a title. A single `#` after `|` is still a comment.
+    Heading { level: usize, title: String },
     /// Conditional section.
     Conditional {
         condition: Spanned<Expr>,
@@ -783,7 +1162,10 @@ pub enum InstructionPart {
 /// |---------|---------|-------------|
 /// | `Reference` | `@variables.name` | Reference to a namespaced resource |
 /// | `String` | `"hello"` | String literal |
+/// | `Template` | `"Hi {!@variables.name}"` | Interpolated string literal |
 /// | `Number` | `42`, `3.14` | Numeric literal |
+/// | `Date` | `date(2024-01-31)` | Date literal |
+/// | `Currency` | `$199.99` | Currency literal |
 /// | `Bool` | `True`, `False` | Boolean literal |
 /// | `None` | `None` | Null value |
 /// | `List` | `[1, 2, 3]` | Array literal |
@@ -803,8 +1185,20 @@ pub enum Expr {
     /// String literal: `"text"`.
     String(String),
 
+    /// String literal with interpolations: `"Hello {!@variables.name}"`.
+    ///
+    /// The literal text between interpolations is kept as [`Expr::String`]
+    /// parts, and each `{!expr}` as the expression inside the braces.
+    Template(Vec<Spanned<Expr>>),
+
     /// Numeric literal: `42` or `3.14`.
-    Number(f64),
+    Number(Number),
+
+    /// Date literal: `date(2024-01-31)`.
+    Date(Date),
+
+    /// Currency literal: `$199.99` or `currency(199.99, "USD")`.
+    Currency(Currency),
 
     /// Boolean literal: `True` or `False`.
     Bool(bool),
@@ -869,6 +1263,311 @@ pub enum Expr {
         /// The index expression.
         index: Box<Spanned<Expr>>,
     },
+
+    /// Function call: `name(arg, ...)`.
+    ///
+    /// Any name parses; validation rejects names that are not in
+    /// [`BUILTIN_FUNCTIONS`] and calls with the wrong number of arguments.
+    Call {
+        /// The function name.
+        callee: Spanned<String>,
+        /// The arguments, in order.
+        args: Vec<Spanned<Expr>>,
+    },
+}
+
+impl Expr {
+    /// The reference a chain of `.field` accesses starts from, with the
+    /// fields appended to its path.
+    ///
+    /// Expressions parse `@outputs.policy.amount` as a `.amount` property
+    /// access on `@outputs.policy`; this gives back the path as written.
+    /// Returns `None` for anything but a reference and property accesses.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use busbar_sf_agentscript::parse;
+    ///
+    /// let ast = parse("variables:\n   total: mutable number = @variables.order.total\n").unwrap();
+    /// let default = ast.variables.unwrap().node.variables[0].node.default.clone().unwrap();
+    /// assert!(matches!(default.node, busbar_sf_agentscript::Expr::Property { .. }));
+    /// let path = default.node.reference_path().unwrap();
+    /// assert_eq!(path.full_path(), "@variables.order.total");
+    /// ```
+    pub fn reference_path(&self) -> Option<Reference> {
+        match self {
+            Expr::Reference(reference) => Some(reference.clone()),
+            Expr::Property { object, field } => {
+                let mut reference = object.node.reference_path()?;
+                reference.path.push(field.node.clone());
+                Some(reference)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// A numeric literal.
+///
+/// Whole numbers and decimals are kept apart, so `2` stays `2` through
+/// serialization and arithmetic instead of becoming a float. Decimals keep
+/// the text they were written with, so `1.50` round-trips exactly.
+///
+/// In AST JSON a number is a plain JSON number: integers load as
+/// [`Number::Int`] and anything else as [`Number::Float`] without its text.
+///
+/// # Example
+///
+/// ```rust
+/// use busbar_sf_agentscript::ast::Number;
+///
+/// let int: Number = "42".parse().unwrap();
+/// assert_eq!(int, Number::Int(42));
+///
+/// let float: Number = "1.50".parse().unwrap();
+/// assert_eq!(float.as_f64(), 1.5);
+/// assert_eq!(float.to_string(), "1.50");
+/// assert_eq!(Number::from(2.0).to_string(), "2.0");
+/// ```
+#[derive(Debug, Clone)]
+pub enum Number {
+    /// A whole number written without a decimal point: `42`.
+    Int(i64),
+
+    /// A number with a decimal point, or too large for an `i64`: `3.14`.
+    Float {
+        /// The value.
+        value: f64,
+        /// The literal as written; `None` for computed numbers.
+        text: Option<String>,
+    },
+}
+
+impl Number {
+    /// The value as a float.
+    pub fn as_f64(&self) -> f64 {
+        match self {
+            Number::Int(n) => *n as f64,
+            Number::Float { value, .. } => *value,
+        }
+    }
+
+    /// The value, if this is a whole number literal.
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Number::Int(n) => Some(*n),
+            Number::Float { .. } => None,
+        }
+    }
+}
+
+impl From<i64> for Number {
+    fn from(n: i64) -> Self {
+        Number::Int(n)
+    }
+}
+
+impl From<f64> for Number {
+    fn from(value: f64) -> Self {
+        Number::Float { value, text: None }
+    }
+}
+
+impl std::str::FromStr for Number {
+    type Err = std::num::ParseFloatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(n) = s.parse() {
+            return Ok(Number::Int(n));
+        }
+        Ok(Number::Float {
+            value: s.parse()?,
+            text: Some(s.to_string()),
+        })
+    }
+}
+
+/// Numbers are equal when they have the same kind and value; the literal
+/// text is ignored, so `1.50` equals `1.5`.
+impl PartialEq for Number {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Number::Int(a), Number::Int(b)) => a == b,
+            (Number::Float { value: a, .. }, Number::Float { value: b, .. }) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl std::fmt::Display for Number {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Number::Int(n) => write!(f, "{}", n),
+            Number::Float {
+                text: Some(text), ..
+            } => f.write_str(text),
+            // `{:?}` keeps a `.0` on whole floats, so they read back as floats
+            Number::Float { value, text: None } => write!(f, "{:?}", value),
+        }
+    }
+}
+
+impl Serialize for Number {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Number::Int(n) => serializer.serialize_i64(*n),
+            Number::Float { value, .. } => serializer.serialize_f64(*value),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Number {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct NumberVisitor;
+
+        impl serde::de::Visitor<'_> for NumberVisitor {
+            type Value = Number;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.write_str("a number")
+            }
+
+            fn visit_i64<E>(self, n: i64) -> Result<Number, E> {
+                Ok(Number::Int(n))
+            }
+
+            fn visit_u64<E: serde::de::Error>(self, n: u64) -> Result<Number, E> {
+                Ok(i64::try_from(n).map_or(Number::from(n as f64), Number::Int))
+            }
+
+            fn visit_f64<E>(self, value: f64) -> Result<Number, E> {
+                Ok(Number::from(value))
+            }
+        }
+
+        deserializer.deserialize_any(NumberVisitor)
+    }
+}
+
+/// A calendar date, written `date(2024-01-31)`.
+///
+/// Only real dates parse, so `date(2024-02-30)` is a parse error. In AST
+/// JSON a date is its ISO 8601 string.
+///
+/// # Example
+///
+/// ```rust
+/// use busbar_sf_agentscript::ast::Date;
+///
+/// let date: Date = "2024-01-31".parse().unwrap();
+/// assert_eq!((date.year, date.month, date.day), (2024, 1, 31));
+/// assert_eq!(date.to_string(), "2024-01-31");
+/// assert!("2023-02-29".parse::<Date>().is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Date {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+}
+
+impl std::str::FromStr for Date {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid date '{}', expected YYYY-MM-DD", s);
+        let parts: Vec<&str> = s.split('-').collect();
+        let [year, month, day] = parts.as_slice() else {
+            return Err(invalid());
+        };
+        if year.len() != 4 || month.len() != 2 || day.len() != 2 {
+            return Err(invalid());
+        }
+        let number = |part: &str| {
+            part.bytes()
+                .all(|b| b.is_ascii_digit())
+                .then(|| part.parse::<u16>().ok())
+                .flatten()
+                .ok_or_else(invalid)
+        };
+        let (year, month, day) = (number(year)?, number(month)?, number(day)?);
+        let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+        let days_in_month = match month {
+            1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+            4 | 6 | 9 | 11 => 30,
+            2 if leap => 29,
+            2 => 28,
+            _ => return Err(format!("invalid date '{}', month must be 01-12", s)),
+        };
+        if day == 0 || day > days_in_month {
+            return Err(format!("invalid date '{}', day must be 01-{}", s, days_in_month));
+        }
+        Ok(Date {
+            year,
+            month: month as u8,
+            day: day as u8,
+        })
+    }
+}
+
+impl std::fmt::Display for Date {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+impl TryFrom<String> for Date {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<Date> for String {
+    fn from(date: Date) -> Self {
+        date.to_string()
+    }
+}
+
+/// A money amount, written `$199.99` or `currency(199.99, "USD")`.
+///
+/// `code` is the ISO 4217 currency code when one is given; `$` amounts use
+/// the org's default currency.
+///
+/// # Example
+///
+/// ```rust
+/// use busbar_sf_agentscript::ast::{Currency, Number};
+///
+/// let price = Currency { amount: "199.99".parse().unwrap(), code: None };
+/// assert_eq!(price.to_string(), "$199.99");
+///
+/// let euros = Currency { amount: Number::Int(5), code: Some("EUR".to_string()) };
+/// assert_eq!(euros.to_string(), "currency(5, \"EUR\")");
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Currency {
+    pub amount: Number,
+    pub code: Option<String>,
+}
+
+impl Currency {
+    /// Check that `code` looks like an ISO 4217 code: three uppercase letters.
+    pub fn is_valid_code(code: &str) -> bool {
+        code.len() == 3 && code.bytes().all(|b| b.is_ascii_uppercase())
+    }
+}
+
+impl std::fmt::Display for Currency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.code {
+            Some(code) => write!(f, "currency({}, \"{}\")", self.amount, code),
+            None => write!(f, "${}", self.amount),
+        }
+    }
 }
 
 /// A reference to a namespaced resource.
@@ -946,6 +1645,57 @@ impl Reference {
     }
 }
 
+/// How deep a reference path into a namespace may go.
+///
+/// # Example
+///
+/// ```rust
+/// use busbar_sf_agentscript::ast::NamespaceRule;
+///
+/// assert_eq!(NamespaceRule::get("topic").unwrap().segments, 1);
+/// assert!(NamespaceRule::get("variables").unwrap().object_paths);
+/// assert!(NamespaceRule::get("context").is_none());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NamespaceRule {
+    /// Namespace without the `@`, e.g. `"topic"`.
+    pub namespace: &'static str,
+    /// Number of path segments a reference takes.
+    pub segments: usize,
+    /// Whether references to `object` variables may go deeper, e.g.
+    /// `@variables.user.name`.
+    pub object_paths: bool,
+}
+
+/// Path depth rules for the namespaces the language defines. Other
+/// namespaces (`actions`, `utils`, context namespaces) take any path.
+pub const NAMESPACE_RULES: &[NamespaceRule] = &[
+    NamespaceRule {
+        namespace: "variables",
+        segments: 1,
+        object_paths: true,
+    },
+    NamespaceRule {
+        namespace: "topic",
+        segments: 1,
+        object_paths: false,
+    },
+    NamespaceRule {
+        namespace: "outputs",
+        segments: 1,
+        object_paths: false,
+    },
+];
+
+impl NamespaceRule {
+    /// The rule for `namespace`, if it has one.
+    pub fn get(namespace: &str) -> Option<&'static NamespaceRule> {
+        NAMESPACE_RULES
+            .iter()
+            .find(|rule| rule.namespace == namespace)
+    }
+}
+
 /// Binary operators.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum BinOp {
@@ -966,17 +1716,30 @@ pub enum BinOp {
     // Arithmetic
     Add, // +
     Sub, // -
+    Mul, // *
+    Div, // /
+    Mod, // %
 }
 
 impl BinOp {
     /// Get the precedence of this operator (higher binds tighter).
+    ///
+    /// Prefix `not` sits between `and` and the comparisons, so
+    /// `not a == b` means `not (a == b)`; see [`UnaryOp::precedence`].
     pub fn precedence(&self) -> u8 {
         match self {
             BinOp::Or => 1,
             BinOp::And => 2,
-            BinOp::Is | BinOp::IsNot => 3,
-            BinOp::Eq | BinOp::Ne | BinOp::Lt | BinOp::Gt | BinOp::Le | BinOp::Ge => 4,
+            BinOp::Eq
+            | BinOp::Ne
+            | BinOp::Lt
+            | BinOp::Gt
+            | BinOp::Le
+            | BinOp::Ge
+            | BinOp::Is
+            | BinOp::IsNot => 4,
             BinOp::Add | BinOp::Sub => 5,
+            BinOp::Mul | BinOp::Div | BinOp::Mod => 6,
         }
     }
 }
@@ -988,6 +1751,94 @@ pub enum UnaryOp {
     Neg, // - (negative)
 }
 
+impl UnaryOp {
+    /// Get the precedence of this operator on the [`BinOp::precedence`] scale.
+    ///
+    /// The operand extends over every binary operator of equal or higher
+    /// precedence: `not` covers comparisons and arithmetic, `-` covers nothing.
+    pub fn precedence(&self) -> u8 {
+        match self {
+            UnaryOp::Not => 3,
+            UnaryOp::Neg => 7,
+        }
+    }
+}
+
+/// A built-in function callable from expressions, e.g. `len(@variables.items)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BuiltinFunction {
+    /// The function name.
+    pub name: &'static str,
+    /// Number of arguments the function takes.
+    pub arity: usize,
+    /// Signature for display, e.g. `len(value: string | list) -> number`.
+    pub signature: &'static str,
+    /// One-line description.
+    pub description: &'static str,
+}
+
+/// The functions expressions may call.
+pub const BUILTIN_FUNCTIONS: &[BuiltinFunction] = &[
+    BuiltinFunction {
+        name: "len",
+        arity: 1,
+        signature: "len(value: string | list) -> number",
+        description: "Number of characters in a string or items in a list.",
+    },
+    BuiltinFunction {
+        name: "lower",
+        arity: 1,
+        signature: "lower(text: string) -> string",
+        description: "The text in lowercase.",
+    },
+    BuiltinFunction {
+        name: "upper",
+        arity: 1,
+        signature: "upper(text: string) -> string",
+        description: "The text in uppercase.",
+    },
+    BuiltinFunction {
+        name: "trim",
+        arity: 1,
+        signature: "trim(text: string) -> string",
+        description: "The text without leading and trailing whitespace.",
+    },
+    BuiltinFunction {
+        name: "contains",
+        arity: 2,
+        signature: "contains(haystack: string | list, needle) -> boolean",
+        description: "Whether a string contains a substring, or a list an item.",
+    },
+    BuiltinFunction {
+        name: "starts_with",
+        arity: 2,
+        signature: "starts_with(text: string, prefix: string) -> boolean",
+        description: "Whether the text starts with the prefix.",
+    },
+    BuiltinFunction {
+        name: "ends_with",
+        arity: 2,
+        signature: "ends_with(text: string, suffix: string) -> boolean",
+        description: "Whether the text ends with the suffix.",
+    },
+];
+
+impl BuiltinFunction {
+    /// Look up a built-in function by name.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use busbar_sf_agentscript::ast::BuiltinFunction;
+    ///
+    /// assert_eq!(BuiltinFunction::lookup("contains").unwrap().arity, 2);
+    /// assert!(BuiltinFunction::lookup("length").is_none());
+    /// ```
+    pub fn lookup(name: &str) -> Option<&'static BuiltinFunction> {
+        BUILTIN_FUNCTIONS.iter().find(|f| f.name == name)
+    }
+}
+
 // ============================================================================
 // Comments
 // ============================================================================
@@ -1,4 +1,5 @@
 use busbar_sf_agentscript::lexer::lex_with_indentation;
+use busbar_sf_agentscript::text_pos::{Encoding, LineIndex};
 use std::env;
 use std::fs;
 
@@ -18,9 +19,10 @@ fn main() {
 
     match lex_with_indentation(&source) {
         Ok(tokens) => {
+            let index = LineIndex::new(&source);
             for (tok, span) in &tokens {
                 // Calculate line number from span
-                let line = source[..span.start].matches('\n').count() + 1;
+                let line = index.location(span.start, Encoding::Utf8).line as usize + 1;
 
                 // Print tokens around target line
                 if target_line == 0
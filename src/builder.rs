@@ -0,0 +1,1451 @@
+//! Programmatic AST construction and editing.
+//!
+//! Visual editors and code generators can build agents without writing
+//! AgentScript text: construct definitions with the fluent builders, add them
+//! to an [`AgentFile`] with the mutation helpers, then call
+//! [`serialize`](crate::serialize).
+//!
+//! Nodes created here have no source text, so they get zero-width spans
+//! placed after everything already in the file. They never overlap parsed
+//! nodes, and sort after them. The same goes for definitions copied in from
+//! another file with [`AgentFile::merge`] and the per-block merge helpers.
+//!
+//! # Example
+//!
+//! ```rust
+//! use busbar_sf_agentscript::builder::{ActionDefBuilder, TopicBuilder, VariableBuilder};
+//! use busbar_sf_agentscript::{parse, serialize, AgentFile, Expr, Type};
+//!
+//! let mut agent = AgentFile::new();
+//! agent
+//!     .add_variable(VariableBuilder::mutable("order_id", Type::String, Expr::String(String::new())))
+//!     .unwrap();
+//! agent
+//!     .add_topic(
+//!         TopicBuilder::new("orders")
+//!             .description("Order questions")
+//!             .instructions("Help with orders")
+//!             .action(
+//!                 ActionDefBuilder::new("lookup")
+//!                     .description("Look up an order")
+//!                     .input("id", Type::String)
+//!                     .target("flow://Lookup"),
+//!             )
+//!             .invoke("do_lookup", "lookup"),
+//!     )
+//!     .unwrap();
+//! agent.rename_variable("order_id", "order_number").unwrap();
+//!
+//! let source = serialize(&agent);
+//! assert!(parse(&source).is_ok());
+//! assert!(source.contains("order_number: mutable string"));
+//! ```
+
+use crate::ast::{
+    ActionDef, ActionsBlock, AgentFile, ConnectionBlock, DirectiveBlock, Expr, InstructionPart,
+    Instructions, ParamDef, ReasoningAction, ReasoningActionTarget, ReasoningBlock, Reference,
+    Spanned, Stmt, TopicBlock, Type, VariableDecl, VariableKind, VariablesBlock, WithValue,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+
+/// Errors from [`AgentFile`] mutation helpers.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum EditError {
+    /// A definition with this name already exists.
+    #[error("{kind} '{name}' is already defined")]
+    AlreadyDefined { kind: &'static str, name: String },
+
+    /// No definition with this name exists.
+    #[error("{kind} '{name}' is not defined")]
+    NotFound { kind: &'static str, name: String },
+}
+
+/// Wrap a node in a zero-width span at `at`.
+fn sp<T>(node: T, at: usize) -> Spanned<T> {
+    Spanned::new(node, at..at)
+}
+
+// ============================================================================
+// Builders
+// ============================================================================
+
+/// Fluent constructor for a [`VariableDecl`].
+#[derive(Debug, Clone)]
+pub struct VariableBuilder {
+    name: String,
+    kind: VariableKind,
+    ty: Type,
+    default: Option<Expr>,
+    source: Option<Reference>,
+    description: Option<String>,
+    doc: Option<String>,
+}
+
+impl VariableBuilder {
+    /// A `mutable` variable with a default value.
+    pub fn mutable(name: impl Into<String>, ty: Type, default: Expr) -> Self {
+        Self {
+            name: name.into(),
+            kind: VariableKind::Mutable,
+            ty,
+            default: Some(default),
+            source: None,
+            description: None,
+            doc: None,
+        }
+    }
+
+    /// A `linked` variable read from `source` (e.g. `@context.user.email`).
+    pub fn linked(name: impl Into<String>, ty: Type, source: Reference) -> Self {
+        Self {
+            name: name.into(),
+            kind: VariableKind::Linked,
+            ty,
+            default: None,
+            source: Some(source),
+            description: None,
+            doc: None,
+        }
+    }
+
+    /// Set the `description:`.
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Set the `#` doc comment.
+    pub fn doc(mut self, doc: impl Into<String>) -> Self {
+        self.doc = Some(doc.into());
+        self
+    }
+
+    /// Build the declaration with all spans at `at`.
+    pub fn build_at(self, at: usize) -> Spanned<VariableDecl> {
+        sp(
+            VariableDecl {
+                name: sp(self.name, at),
+                kind: self.kind,
+                ty: sp(self.ty, at),
+                default: self.default.map(|d| sp(d, at)),
+                description: self.description.map(|d| sp(d, at)),
+                source: self.source.map(|s| sp(s, at)),
+                doc: self.doc.map(|d| sp(d, at)),
+            },
+            at,
+        )
+    }
+
+    /// Build the declaration with all spans at offset 0.
+    pub fn build(self) -> Spanned<VariableDecl> {
+        self.build_at(0)
+    }
+}
+
+/// A parameter added through [`ActionDefBuilder`].
+#[derive(Debug, Clone)]
+struct ParamSpec {
+    name: String,
+    ty: Type,
+    description: Option<String>,
+}
+
+impl ParamSpec {
+    fn build_at(self, at: usize) -> Spanned<ParamDef> {
+        sp(
+            ParamDef {
+                name: sp(self.name, at),
+                ty: sp(self.ty, at),
+                description: self.description.map(|d| sp(d, at)),
+                label: None,
+                is_required: None,
+                filter_from_agent: None,
+                is_displayable: None,
+                complex_data_type_name: None,
+            },
+            at,
+        )
+    }
+}
+
+/// Fluent constructor for an [`ActionDef`].
+#[derive(Debug, Clone)]
+pub struct ActionDefBuilder {
+    name: String,
+    description: Option<String>,
+    label: Option<String>,
+    target: Option<String>,
+    require_user_confirmation: Option<bool>,
+    inputs: Vec<ParamSpec>,
+    outputs: Vec<ParamSpec>,
+    doc: Option<String>,
+}
+
+impl ActionDefBuilder {
+    /// An action definition with the given name.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            description: None,
+            label: None,
+            target: None,
+            require_user_confirmation: None,
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            doc: None,
+        }
+    }
+
+    /// Set the `description:`.
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Set the `label:`.
+    pub fn label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    /// Set the `target:` (e.g. `flow://LookupOrder`).
+    pub fn target(mut self, target: impl Into<String>) -> Self {
+        self.target = Some(target.into());
+        self
+    }
+
+    /// Set `require_user_confirmation:`.
+    pub fn require_user_confirmation(mut self, required: bool) -> Self {
+        self.require_user_confirmation = Some(required);
+        self
+    }
+
+    /// Add an input parameter.
+    pub fn input(mut self, name: impl Into<String>, ty: Type) -> Self {
+        self.inputs.push(ParamSpec {
+            name: name.into(),
+            ty,
+            description: None,
+        });
+        self
+    }
+
+    /// Add an input parameter with a description.
+    pub fn input_with_description(
+        mut self,
+        name: impl Into<String>,
+        ty: Type,
+        description: impl Into<String>,
+    ) -> Self {
+        self.inputs.push(ParamSpec {
+            name: name.into(),
+            ty,
+            description: Some(description.into()),
+        });
+        self
+    }
+
+    /// Add an output parameter.
+    pub fn output(mut self, name: impl Into<String>, ty: Type) -> Self {
+        self.outputs.push(ParamSpec {
+            name: name.into(),
+            ty,
+            description: None,
+        });
+        self
+    }
+
+    /// Set the `#` doc comment.
+    pub fn doc(mut self, doc: impl Into<String>) -> Self {
+        self.doc = Some(doc.into());
+        self
+    }
+
+    /// Build the definition with all spans at `at`.
+    pub fn build_at(self, at: usize) -> Spanned<ActionDef> {
+        let params = |params: Vec<ParamSpec>| {
+            (!params.is_empty())
+                .then(|| sp(params.into_iter().map(|p| p.build_at(at)).collect(), at))
+        };
+        sp(
+            ActionDef {
+                name: sp(self.name, at),
+                description: self.description.map(|d| sp(d, at)),
+                label: self.label.map(|l| sp(l, at)),
+                require_user_confirmation: self.require_user_confirmation.map(|r| sp(r, at)),
+                include_in_progress_indicator: None,
+                progress_indicator_message: None,
+                inputs: params(self.inputs),
+                outputs: params(self.outputs),
+                target: self.target.map(|t| sp(t, at)),
+                doc: self.doc.map(|d| sp(d, at)),
+            },
+            at,
+        )
+    }
+
+    /// Build the definition with all spans at offset 0.
+    pub fn build(self) -> Spanned<ActionDef> {
+        self.build_at(0)
+    }
+}
+
+/// Fluent constructor for a [`TopicBlock`].
+#[derive(Debug, Clone)]
+pub struct TopicBuilder {
+    name: String,
+    description: Option<String>,
+    instructions: Option<String>,
+    actions: Vec<ActionDefBuilder>,
+    reasoning_actions: Vec<(String, ReasoningActionTarget, Option<String>)>,
+    doc: Option<String>,
+}
+
+impl TopicBuilder {
+    /// A topic with the given name.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            description: None,
+            instructions: None,
+            actions: Vec::new(),
+            reasoning_actions: Vec::new(),
+            doc: None,
+        }
+    }
+
+    /// Set the `description:`.
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Set the reasoning `instructions:`.
+    pub fn instructions(mut self, instructions: impl Into<String>) -> Self {
+        self.instructions = Some(instructions.into());
+        self
+    }
+
+    /// Add an action definition.
+    pub fn action(mut self, action: ActionDefBuilder) -> Self {
+        self.actions.push(action);
+        self
+    }
+
+    /// Add a reasoning action that invokes `@actions.<action>`.
+    pub fn invoke(mut self, alias: impl Into<String>, action: impl Into<String>) -> Self {
+        let target = Reference::new("actions", vec![action.into()]);
+        self.reasoning_actions
+            .push((alias.into(), ReasoningActionTarget::Action(target), None));
+        self
+    }
+
+    /// Add a reasoning action that transitions to `@topic.<topic>`.
+    pub fn transition_to(
+        mut self,
+        alias: impl Into<String>,
+        topic: impl Into<String>,
+        description: impl Into<String>,
+    ) -> Self {
+        let target = Reference::new("topic", vec![topic.into()]);
+        self.reasoning_actions.push((
+            alias.into(),
+            ReasoningActionTarget::TransitionTo(target),
+            Some(description.into()),
+        ));
+        self
+    }
+
+    /// Set the `#` doc comment.
+    pub fn doc(mut self, doc: impl Into<String>) -> Self {
+        self.doc = Some(doc.into());
+        self
+    }
+
+    /// Build the topic with all spans at `at`.
+    pub fn build_at(self, at: usize) -> Spanned<TopicBlock> {
+        let actions = (!self.actions.is_empty()).then(|| {
+            sp(
+                ActionsBlock {
+                    actions: self.actions.into_iter().map(|a| a.build_at(at)).collect(),
+                },
+                at,
+            )
+        });
+
+        let reasoning_actions = (!self.reasoning_actions.is_empty()).then(|| {
+            sp(
+                self.reasoning_actions
+                    .into_iter()
+                    .map(|(name, target, description)| {
+                        sp(reasoning_action(name, target, description, at), at)
+                    })
+                    .collect(),
+                at,
+            )
+        });
+
+        sp(
+            TopicBlock {
+                name: sp(self.name, at),
+                quoted_name: None,
+                description: self.description.map(|d| sp(d, at)),
+                system: None,
+                actions,
+                before_reasoning: None,
+                reasoning: Some(sp(
+                    ReasoningBlock {
+                        instructions: self.instructions.map(|i| sp(Instructions::Simple(i), at)),
+                        actions: reasoning_actions,
+                    },
+                    at,
+                )),
+                after_reasoning: None,
+                doc: self.doc.map(|d| sp(d, at)),
+            },
+            at,
+        )
+    }
+
+    /// Build the topic with all spans at offset 0.
+    pub fn build(self) -> Spanned<TopicBlock> {
+        self.build_at(0)
+    }
+}
+
+fn reasoning_action(
+    name: String,
+    target: ReasoningActionTarget,
+    description: Option<String>,
+    at: usize,
+) -> ReasoningAction {
+    ReasoningAction {
+        name: sp(name, at),
+        target: sp(target, at),
+        description: description.map(|d| sp(d, at)),
+        available_when: None,
+        with_clauses: Vec::new(),
+        set_clauses: Vec::new(),
+        run_clauses: Vec::new(),
+        if_clauses: Vec::new(),
+        transition: None,
+        clause_order: Vec::new(),
+    }
+}
+
+// ============================================================================
+// AgentFile mutation helpers
+// ============================================================================
+
+impl AgentFile {
+    /// Add a topic built with [`TopicBuilder`].
+    pub fn add_topic(
+        &mut self,
+        topic: TopicBuilder,
+    ) -> Result<&mut Spanned<TopicBlock>, EditError> {
+        if self.topics.iter().any(|t| t.node.name.node == topic.name) {
+            return Err(EditError::AlreadyDefined {
+                kind: "topic",
+                name: topic.name,
+            });
+        }
+        let at = self.synthetic_offset();
+        self.topics.push(topic.build_at(at));
+        Ok(self.topics.last_mut().expect("topic was just pushed"))
+    }
+
+    /// Add a variable built with [`VariableBuilder`], creating the
+    /// `variables:` block if needed.
+    pub fn add_variable(
+        &mut self,
+        variable: VariableBuilder,
+    ) -> Result<&mut Spanned<VariableDecl>, EditError> {
+        if self.find_variable(&variable.name).is_some() {
+            return Err(EditError::AlreadyDefined {
+                kind: "variable",
+                name: variable.name,
+            });
+        }
+        let at = self.synthetic_offset();
+        let block = self.variables.get_or_insert_with(|| {
+            sp(
+                VariablesBlock {
+                    variables: Vec::new(),
+                },
+                at,
+            )
+        });
+        block.span.end = block.span.end.max(at);
+        block.node.variables.push(variable.build_at(at));
+        Ok(block
+            .node
+            .variables
+            .last_mut()
+            .expect("variable was just pushed"))
+    }
+
+    /// Add an action definition to a topic (or to `start_agent`, by its name).
+    pub fn add_action(
+        &mut self,
+        topic: &str,
+        action: ActionDefBuilder,
+    ) -> Result<&mut Spanned<ActionDef>, EditError> {
+        let at = self.synthetic_offset();
+        let actions = self.actions_block_mut(topic)?;
+        if actions.as_ref().is_some_and(|a| {
+            a.node
+                .actions
+                .iter()
+                .any(|d| d.node.name.node == action.name)
+        }) {
+            return Err(EditError::AlreadyDefined {
+                kind: "action",
+                name: action.name,
+            });
+        }
+        let block = actions.get_or_insert_with(|| {
+            sp(
+                ActionsBlock {
+                    actions: Vec::new(),
+                },
+                at,
+            )
+        });
+        block.span.end = block.span.end.max(at);
+        block.node.actions.push(action.build_at(at));
+        Ok(block
+            .node
+            .actions
+            .last_mut()
+            .expect("action was just pushed"))
+    }
+
+    /// Rename a variable and every `@variables.<old>` reference to it,
+    /// including references inside instruction text.
+    pub fn rename_variable(&mut self, old: &str, new: &str) -> Result<(), EditError> {
+        if self.find_variable(new).is_some() {
+            return Err(EditError::AlreadyDefined {
+                kind: "variable",
+                name: new.to_string(),
+            });
+        }
+        let Some(decl) = self.find_variable_mut(old) else {
+            return Err(EditError::NotFound {
+                kind: "variable",
+                name: old.to_string(),
+            });
+        };
+        decl.node.name.node = new.to_string();
+
+        let from = format!("@variables.{}", old);
+        let to = format!("@variables.{}", new);
+        walk_agent(
+            self,
+            &mut |r| {
+                if r.namespace == "variables" && r.path.first().is_some_and(|p| p == old) {
+                    r.path[0] = new.to_string();
+                }
+            },
+            &mut |text| replace_reference_text(text, &from, &to),
+        );
+        Ok(())
+    }
+
+    /// Remove an action definition from a topic (or from `start_agent`, by
+    /// its name), along with the reasoning actions and `run` statements in
+    /// that block that invoke it.
+    pub fn remove_action(
+        &mut self,
+        topic: &str,
+        action: &str,
+    ) -> Result<Spanned<ActionDef>, EditError> {
+        let not_found = || EditError::NotFound {
+            kind: "action",
+            name: action.to_string(),
+        };
+        let actions = self
+            .actions_block_mut(topic)?
+            .as_mut()
+            .ok_or_else(not_found)?;
+        let index = actions
+            .node
+            .actions
+            .iter()
+            .position(|a| a.node.name.node == action)
+            .ok_or_else(not_found)?;
+        let removed = actions.node.actions.remove(index);
+
+        let invokes =
+            |r: &Reference| r.namespace == "actions" && r.path.first().is_some_and(|p| p == action);
+        let (before, reasoning, after) = self.directive_blocks_mut(topic)?;
+        for block in [before, after].into_iter().flatten() {
+            remove_run_stmts(&mut block.node.statements, &invokes);
+        }
+        if let Some(ras) = reasoning.as_mut().and_then(|r| r.node.actions.as_mut()) {
+            ras.node.retain(|ra| {
+                !matches!(&ra.node.target.node, ReasoningActionTarget::Action(r) if invokes(r))
+            });
+            for ra in &mut ras.node {
+                ra.node
+                    .run_clauses
+                    .retain(|run| !invokes(&run.node.action.node));
+            }
+        }
+        Ok(removed)
+    }
+
+    fn find_variable(&self, name: &str) -> Option<&Spanned<VariableDecl>> {
+        self.variables
+            .as_ref()?
+            .node
+            .variables
+            .iter()
+            .find(|v| v.node.name.node == name)
+    }
+
+    fn find_variable_mut(&mut self, name: &str) -> Option<&mut Spanned<VariableDecl>> {
+        self.variables
+            .as_mut()?
+            .node
+            .variables
+            .iter_mut()
+            .find(|v| v.node.name.node == name)
+    }
+
+    /// The `actions:` block of a topic or of start_agent.
+    fn actions_block_mut(
+        &mut self,
+        topic: &str,
+    ) -> Result<&mut Option<Spanned<ActionsBlock>>, EditError> {
+        if let Some(t) = self.topics.iter_mut().find(|t| t.node.name.node == topic) {
+            return Ok(&mut t.node.actions);
+        }
+        match &mut self.start_agent {
+            Some(sa) if sa.node.name.node == topic => Ok(&mut sa.node.actions),
+            _ => Err(EditError::NotFound {
+                kind: "topic",
+                name: topic.to_string(),
+            }),
+        }
+    }
+
+    /// The before_reasoning, reasoning, and after_reasoning blocks of a topic or of start_agent.
+    #[allow(clippy::type_complexity)]
+    fn directive_blocks_mut(
+        &mut self,
+        topic: &str,
+    ) -> Result<
+        (
+            Option<&mut Spanned<DirectiveBlock>>,
+            &mut Option<Spanned<ReasoningBlock>>,
+            Option<&mut Spanned<DirectiveBlock>>,
+        ),
+        EditError,
+    > {
+        if let Some(t) = self.topics.iter_mut().find(|t| t.node.name.node == topic) {
+            let t = &mut t.node;
+            return Ok((t.before_reasoning.as_mut(), &mut t.reasoning, t.after_reasoning.as_mut()));
+        }
+        match &mut self.start_agent {
+            Some(sa) if sa.node.name.node == topic => {
+                let sa = &mut sa.node;
+                Ok((sa.before_reasoning.as_mut(), &mut sa.reasoning, sa.after_reasoning.as_mut()))
+            }
+            _ => Err(EditError::NotFound {
+                kind: "topic",
+                name: topic.to_string(),
+            }),
+        }
+    }
+
+    /// Offset for synthesized spans: the end of the last top-level block.
+    fn synthetic_offset(&self) -> usize {
+        let mut end = 0;
+        let mut see = |span: &std::ops::Range<usize>| end = end.max(span.end);
+        if let Some(b) = &self.config {
+            see(&b.span);
+        }
+        if let Some(b) = &self.variables {
+            see(&b.span);
+        }
+        if let Some(b) = &self.system {
+            see(&b.span);
+        }
+        if let Some(b) = &self.knowledge {
+            see(&b.span);
+        }
+        if let Some(b) = &self.language {
+            see(&b.span);
+        }
+        if let Some(b) = &self.start_agent {
+            see(&b.span);
+        }
+        self.connections.iter().for_each(|b| see(&b.span));
+        self.topics.iter().for_each(|b| see(&b.span));
+        end
+    }
+}
+
+// ============================================================================
+// Merging
+// ============================================================================
+
+/// How the [`AgentFile`] merge helpers handle a name defined in both files.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MergePolicy {
+    /// Fail with [`EditError::AlreadyDefined`], leaving the file unchanged.
+    #[default]
+    Error,
+    /// Keep the existing definition and drop the incoming one.
+    PreferLeft,
+    /// Keep both, renaming the incoming one to the first free `<name>_<n>`.
+    Rename,
+}
+
+/// A definition renamed by a merge under [`MergePolicy::Rename`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MergeRename {
+    pub kind: &'static str,
+    pub from: String,
+    pub to: String,
+}
+
+impl AgentFile {
+    /// Append the variables of `other` after this file's own, in order.
+    ///
+    /// Returns the incoming variables that were renamed.
+    ///
+    /// ```rust
+    /// use busbar_sf_agentscript::builder::MergePolicy;
+    /// use busbar_sf_agentscript::parse;
+    ///
+    /// let mut base = parse("variables:\n   order: mutable string = \"\"\n").unwrap();
+    /// let other = parse("variables:\n   order: mutable string = \"\"\n   name: mutable string = \"\"\n").unwrap();
+    ///
+    /// assert!(base.clone().merge_variables(&other, MergePolicy::Error).is_err());
+    /// let renamed = base.merge_variables(&other, MergePolicy::Rename).unwrap();
+    /// assert_eq!(renamed[0].to, "order_2");
+    /// let names: Vec<_> = base.variables.unwrap().node.variables.iter().map(|v| v.node.name.node.clone()).collect();
+    /// assert_eq!(names, ["order", "order_2", "name"]);
+    /// ```
+    pub fn merge_variables(
+        &mut self,
+        other: &AgentFile,
+        policy: MergePolicy,
+    ) -> Result<Vec<MergeRename>, EditError> {
+        let mut other = other.clone();
+        let renames = other.resolve_variable_collisions(self, policy)?;
+        self.append_variables(other.variables);
+        Ok(renames)
+    }
+
+    /// Append the topics of `other` after this file's own, in order.
+    ///
+    /// Renamed topics have their `@topic` references among the incoming
+    /// topics updated. Returns the incoming topics that were renamed.
+    pub fn merge_topics(
+        &mut self,
+        other: &AgentFile,
+        policy: MergePolicy,
+    ) -> Result<Vec<MergeRename>, EditError> {
+        let mut other = other.clone();
+        let renames = other.resolve_topic_collisions(self, policy)?;
+        self.append_topics(other.topics);
+        Ok(renames)
+    }
+
+    /// Append the connection blocks of `other` after this file's own, in order.
+    pub fn merge_connections(
+        &mut self,
+        other: &AgentFile,
+        policy: MergePolicy,
+    ) -> Result<Vec<MergeRename>, EditError> {
+        let mut other = other.clone();
+        let renames = other.resolve_connection_collisions(self, policy)?;
+        self.append_connections(other.connections);
+        Ok(renames)
+    }
+
+    /// Merge the variables, connections, and topics of `other` into this file.
+    ///
+    /// Renames apply to the whole incoming file first, so incoming topics
+    /// keep referring to their own (renamed) variables and topics. Nothing is
+    /// changed if any collision fails under [`MergePolicy::Error`].
+    pub fn merge(
+        &mut self,
+        other: &AgentFile,
+        policy: MergePolicy,
+    ) -> Result<Vec<MergeRename>, EditError> {
+        let mut other = other.clone();
+        let mut renames = other.resolve_variable_collisions(self, policy)?;
+        renames.extend(other.resolve_connection_collisions(self, policy)?);
+        renames.extend(other.resolve_topic_collisions(self, policy)?);
+        self.append_variables(other.variables);
+        self.append_connections(other.connections);
+        self.append_topics(other.topics);
+        Ok(renames)
+    }
+
+    /// Apply `policy` to the variables of `self` that `base` also defines.
+    fn resolve_variable_collisions(
+        &mut self,
+        base: &AgentFile,
+        policy: MergePolicy,
+    ) -> Result<Vec<MergeRename>, EditError> {
+        let taken = variable_names(base);
+        let incoming = variable_names(self);
+        let mut renames = Vec::new();
+        for name in incoming.iter().filter(|n| taken.contains(n)) {
+            match policy {
+                MergePolicy::Error => {
+                    return Err(EditError::AlreadyDefined {
+                        kind: "variable",
+                        name: name.clone(),
+                    })
+                }
+                MergePolicy::PreferLeft => {
+                    if let Some(vars) = &mut self.variables {
+                        vars.node.variables.retain(|v| &v.node.name.node != name);
+                    }
+                }
+                MergePolicy::Rename => {
+                    let to = free_name(name, &taken, &variable_names(self));
+                    self.rename_variable(name, &to)?;
+                    renames.push(MergeRename {
+                        kind: "variable",
+                        from: name.clone(),
+                        to,
+                    });
+                }
+            }
+        }
+        Ok(renames)
+    }
+
+    /// Apply `policy` to the topics of `self` that `base` also defines.
+    fn resolve_topic_collisions(
+        &mut self,
+        base: &AgentFile,
+        policy: MergePolicy,
+    ) -> Result<Vec<MergeRename>, EditError> {
+        let taken: Vec<String> = base
+            .topics
+            .iter()
+            .map(|t| t.node.name.node.clone())
+            .collect();
+        let incoming: Vec<String> = self
+            .topics
+            .iter()
+            .map(|t| t.node.name.node.clone())
+            .collect();
+        let mut renames = Vec::new();
+        for name in incoming.iter().filter(|n| taken.contains(n)) {
+            match policy {
+                MergePolicy::Error => {
+                    return Err(EditError::AlreadyDefined {
+                        kind: "topic",
+                        name: name.clone(),
+                    })
+                }
+                MergePolicy::PreferLeft => self.topics.retain(|t| &t.node.name.node != name),
+                MergePolicy::Rename => {
+                    let current: Vec<String> = self
+                        .topics
+                        .iter()
+                        .map(|t| t.node.name.node.clone())
+                        .collect();
+                    let to = free_name(name, &taken, &current);
+                    for topic in self.topics.iter_mut().filter(|t| &t.node.name.node == name) {
+                        topic.node.name.node = to.clone();
+                    }
+                    let (from_ref, to_ref) = (format!("@topic.{}", name), format!("@topic.{}", to));
+                    walk_agent(
+                        self,
+                        &mut |r| {
+                            if r.namespace == "topic" && r.path.first() == Some(name) {
+                                r.path[0] = to.clone();
+                            }
+                        },
+                        &mut |text| replace_reference_text(text, &from_ref, &to_ref),
+                    );
+                    renames.push(MergeRename {
+                        kind: "topic",
+                        from: name.clone(),
+                        to,
+                    });
+                }
+            }
+        }
+        Ok(renames)
+    }
+
+    /// Apply `policy` to the connections of `self` that `base` also defines.
+    fn resolve_connection_collisions(
+        &mut self,
+        base: &AgentFile,
+        policy: MergePolicy,
+    ) -> Result<Vec<MergeRename>, EditError> {
+        let taken: Vec<String> = base
+            .connections
+            .iter()
+            .map(|c| c.node.name.node.clone())
+            .collect();
+        let mut incoming: Vec<String> = self
+            .connections
+            .iter()
+            .map(|c| c.node.name.node.clone())
+            .collect();
+        let mut renames = Vec::new();
+        let mut kept = Vec::new();
+        for mut connection in std::mem::take(&mut self.connections) {
+            let name = connection.node.name.node.clone();
+            if taken.contains(&name) {
+                match policy {
+                    MergePolicy::Error => {
+                        return Err(EditError::AlreadyDefined {
+                            kind: "connection",
+                            name,
+                        })
+                    }
+                    MergePolicy::PreferLeft => continue,
+                    MergePolicy::Rename => {
+                        let to = free_name(&name, &taken, &incoming);
+                        incoming.push(to.clone());
+                        connection.node.name.node = to.clone();
+                        renames.push(MergeRename {
+                            kind: "connection",
+                            from: name,
+                            to,
+                        });
+                    }
+                }
+            }
+            kept.push(connection);
+        }
+        self.connections = kept;
+        Ok(renames)
+    }
+
+    fn append_variables(&mut self, incoming: Option<Spanned<VariablesBlock>>) {
+        let Some(incoming) = incoming else { return };
+        let at = self.synthetic_offset();
+        let block = self.variables.get_or_insert_with(|| {
+            sp(
+                VariablesBlock {
+                    variables: Vec::new(),
+                },
+                at,
+            )
+        });
+        block.span.end = block.span.end.max(at);
+        block
+            .node
+            .variables
+            .extend(incoming.node.variables.into_iter().map(|v| relocate(v, at)));
+    }
+
+    fn append_topics(&mut self, incoming: Vec<Spanned<TopicBlock>>) {
+        let at = self.synthetic_offset();
+        self.topics
+            .extend(incoming.into_iter().map(|t| relocate(t, at)));
+    }
+
+    fn append_connections(&mut self, incoming: Vec<Spanned<ConnectionBlock>>) {
+        let at = self.synthetic_offset();
+        self.connections
+            .extend(incoming.into_iter().map(|c| relocate(c, at)));
+    }
+}
+
+fn variable_names(agent: &AgentFile) -> Vec<String> {
+    agent
+        .variables
+        .iter()
+        .flat_map(|b| &b.node.variables)
+        .map(|v| v.node.name.node.clone())
+        .collect()
+}
+
+/// The first `<name>_<n>` (n >= 2) not in either list.
+fn free_name(name: &str, taken: &[String], incoming: &[String]) -> String {
+    (2..)
+        .map(|n| format!("{}_{}", name, n))
+        .find(|candidate| !taken.contains(candidate) && !incoming.contains(candidate))
+        .expect("some suffix is free")
+}
+
+/// Move every span in a merged node to the zero-width span `at`, since its
+/// original spans point into another file's source.
+fn relocate<T: Serialize + DeserializeOwned>(node: T, at: usize) -> T {
+    fn visit(value: &mut Value, at: usize) {
+        match value {
+            Value::Object(map) => {
+                for (key, child) in map.iter_mut() {
+                    let is_span = key == "span"
+                        && child.get("start").is_some_and(Value::is_u64)
+                        && child.get("end").is_some_and(Value::is_u64);
+                    if is_span {
+                        *child = serde_json::json!({ "start": at, "end": at });
+                    } else {
+                        visit(child, at);
+                    }
+                }
+            }
+            Value::Array(items) => items.iter_mut().for_each(|item| visit(item, at)),
+            _ => {}
+        }
+    }
+    let mut json = serde_json::to_value(node).expect("AST always serializes to JSON");
+    visit(&mut json, at);
+    serde_json::from_value(json).expect("span reset keeps the AST shape")
+}
+
+// ============================================================================
+// Reference walking
+// ============================================================================
+
+type RefFn<'a> = dyn FnMut(&mut Reference) + 'a;
+type TextFn<'a> = dyn FnMut(&mut String) + 'a;
+
+/// Visit every reference in the file, and every piece of instruction text
+/// (which may contain `{!@namespace.name}` interpolations).
+fn walk_agent(agent: &mut AgentFile, on_ref: &mut RefFn<'_>, on_text: &mut TextFn<'_>) {
+    if let Some(vars) = &mut agent.variables {
+        for var in &mut vars.node.variables {
+            if let Some(default) = &mut var.node.default {
+                walk_expr(&mut default.node, on_ref);
+            }
+            if let Some(source) = &mut var.node.source {
+                on_ref(&mut source.node);
+            }
+        }
+    }
+    if let Some(system) = &mut agent.system {
+        if let Some(instructions) = &mut system.node.instructions {
+            walk_instructions(&mut instructions.node, on_ref, on_text);
+        }
+    }
+    if let Some(knowledge) = &mut agent.knowledge {
+        for entry in &mut knowledge.node.entries {
+            walk_expr(&mut entry.node.value.node, on_ref);
+        }
+    }
+
+    let blocks = agent
+        .start_agent
+        .iter_mut()
+        .map(|sa| {
+            let sa = &mut sa.node;
+            (
+                &mut sa.system,
+                &mut sa.before_reasoning,
+                &mut sa.reasoning,
+                &mut sa.after_reasoning,
+            )
+        })
+        .chain(agent.topics.iter_mut().map(|t| {
+            let t = &mut t.node;
+            (&mut t.system, &mut t.before_reasoning, &mut t.reasoning, &mut t.after_reasoning)
+        }));
+
+    for (system, before, reasoning, after) in blocks {
+        if let Some(instructions) = system.as_mut().and_then(|s| s.node.instructions.as_mut()) {
+            walk_instructions(&mut instructions.node, on_ref, on_text);
+        }
+        for block in [before, after].into_iter().flatten() {
+            walk_stmts(&mut block.node.statements, on_ref);
+        }
+        let Some(reasoning) = reasoning else { continue };
+        if let Some(instructions) = &mut reasoning.node.instructions {
+            walk_instructions(&mut instructions.node, on_ref, on_text);
+        }
+        for ra in reasoning
+            .node
+            .actions
+            .iter_mut()
+            .flat_map(|a| a.node.iter_mut())
+        {
+            walk_reasoning_action(&mut ra.node, on_ref);
+        }
+    }
+}
+
+fn walk_reasoning_action(ra: &mut ReasoningAction, on_ref: &mut RefFn<'_>) {
+    match &mut ra.target.node {
+        ReasoningActionTarget::Action(r)
+        | ReasoningActionTarget::TransitionTo(r)
+        | ReasoningActionTarget::TopicDelegate(r) => on_ref(r),
+        ReasoningActionTarget::Escalate | ReasoningActionTarget::SetVariables => {}
+    }
+    if let Some(cond) = &mut ra.available_when {
+        walk_expr(&mut cond.node, on_ref);
+    }
+    for with in &mut ra.with_clauses {
+        let WithValue::Expr(e) = &mut with.node.value.node;
+        walk_expr(e, on_ref);
+    }
+    for set in &mut ra.set_clauses {
+        on_ref(&mut set.node.target.node);
+        walk_expr(&mut set.node.source.node, on_ref);
+    }
+    for run in &mut ra.run_clauses {
+        on_ref(&mut run.node.action.node);
+        for with in &mut run.node.with_clauses {
+            let WithValue::Expr(e) = &mut with.node.value.node;
+            walk_expr(e, on_ref);
+        }
+        for set in &mut run.node.set_clauses {
+            on_ref(&mut set.node.target.node);
+            walk_expr(&mut set.node.source.node, on_ref);
+        }
+    }
+    for if_clause in &mut ra.if_clauses {
+        walk_expr(&mut if_clause.node.condition.node, on_ref);
+        if let Some(t) = &mut if_clause.node.transition {
+            on_ref(&mut t.node);
+        }
+    }
+    if let Some(t) = &mut ra.transition {
+        on_ref(&mut t.node);
+    }
+}
+
+fn walk_stmts(stmts: &mut [Spanned<Stmt>], on_ref: &mut RefFn<'_>) {
+    for stmt in stmts {
+        match &mut stmt.node {
+            Stmt::Set { target, value } => {
+                on_ref(&mut target.node);
+                walk_expr(&mut value.node, on_ref);
+            }
+            Stmt::Run {
+                action,
+                with_clauses,
+                set_clauses,
+            } => {
+                on_ref(&mut action.node);
+                for with in with_clauses {
+                    let WithValue::Expr(e) = &mut with.node.value.node;
+                    walk_expr(e, on_ref);
+                }
+                for set in set_clauses {
+                    on_ref(&mut set.node.target.node);
+                    walk_expr(&mut set.node.source.node, on_ref);
+                }
+            }
+            Stmt::If {
+                condition,
+                then_block,
+                else_block,
+            } => {
+                walk_expr(&mut condition.node, on_ref);
+                walk_stmts(then_block, on_ref);
+                if let Some(else_block) = else_block {
+                    walk_stmts(else_block, on_ref);
+                }
+            }
+            Stmt::Transition { target } => on_ref(&mut target.node),
+            Stmt::Escalate | Stmt::Respond { .. } => {}
+        }
+    }
+}
+
+fn walk_instructions(
+    instructions: &mut Instructions,
+    on_ref: &mut RefFn<'_>,
+    on_text: &mut TextFn<'_>,
+) {
+    match instructions {
+        Instructions::Simple(text) => on_text(text),
+        Instructions::Static(lines) => lines.iter_mut().for_each(|l| on_text(&mut l.node)),
+        Instructions::Dynamic(parts) => walk_instruction_parts(parts, on_ref, on_text),
+    }
+}
+
+fn walk_instruction_parts(
+    parts: &mut [Spanned<InstructionPart>],
+    on_ref: &mut RefFn<'_>,
+    on_text: &mut TextFn<'_>,
+) {
+    for part in parts {
+        match &mut part.node {
+            InstructionPart::Text(text) | InstructionPart::Heading { title: text, .. } => {
+                on_text(text)
+            }
+            InstructionPart::Interpolation(expr) => walk_expr(expr, on_ref),
+            InstructionPart::Conditional {
+                condition,
+                then_parts,
+                else_parts,
+            } => {
+                walk_expr(&mut condition.node, on_ref);
+                walk_instruction_parts(then_parts, on_ref, on_text);
+                if let Some(else_parts) = else_parts {
+                    walk_instruction_parts(else_parts, on_ref, on_text);
+                }
+            }
+        }
+    }
+}
+
+fn walk_expr(expr: &mut Expr, on_ref: &mut RefFn<'_>) {
+    match expr {
+        Expr::Reference(r) => on_ref(r),
+        Expr::Template(items) | Expr::List(items) => items
+            .iter_mut()
+            .for_each(|i| walk_expr(&mut i.node, on_ref)),
+        Expr::Object(fields) => fields
+            .values_mut()
+            .for_each(|v| walk_expr(&mut v.node, on_ref)),
+        Expr::BinOp { left, right, .. } => {
+            walk_expr(&mut left.node, on_ref);
+            walk_expr(&mut right.node, on_ref);
+        }
+        Expr::UnaryOp { operand, .. } => walk_expr(&mut operand.node, on_ref),
+        Expr::Ternary {
+            condition,
+            then_expr,
+            else_expr,
+        } => {
+            walk_expr(&mut condition.node, on_ref);
+            walk_expr(&mut then_expr.node, on_ref);
+            walk_expr(&mut else_expr.node, on_ref);
+        }
+        Expr::Property { object, .. } => walk_expr(&mut object.node, on_ref),
+        Expr::Index { object, index } => {
+            walk_expr(&mut object.node, on_ref);
+            walk_expr(&mut index.node, on_ref);
+        }
+        Expr::Call { args, .. } => args.iter_mut().for_each(|a| walk_expr(&mut a.node, on_ref)),
+        Expr::String(_)
+        | Expr::Number(_)
+        | Expr::Date(_)
+        | Expr::Currency(_)
+        | Expr::Bool(_)
+        | Expr::None
+        | Expr::SlotFill => {}
+    }
+}
+
+/// Remove `run` statements (recursively, inside `if` blocks) whose action matches.
+fn remove_run_stmts(stmts: &mut Vec<Spanned<Stmt>>, invokes: &impl Fn(&Reference) -> bool) {
+    stmts.retain(|s| !matches!(&s.node, Stmt::Run { action, .. } if invokes(&action.node)));
+    for stmt in stmts {
+        if let Stmt::If {
+            then_block,
+            else_block,
+            ..
+        } = &mut stmt.node
+        {
+            remove_run_stmts(then_block, invokes);
+            if let Some(else_block) = else_block {
+                remove_run_stmts(else_block, invokes);
+            }
+        }
+    }
+}
+
+/// Replace whole-word occurrences of reference `from` with `to` in free text.
+fn replace_reference_text(text: &mut String, from: &str, to: &str) {
+    if !text.contains(from) {
+        return;
+    }
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text.as_str();
+    while let Some(pos) = rest.find(from) {
+        let after = &rest[pos + from.len()..];
+        let boundary = !after.starts_with(|c: char| c.is_alphanumeric() || c == '_');
+        out.push_str(&rest[..pos]);
+        out.push_str(if boundary { to } else { from });
+        rest = after;
+    }
+    out.push_str(rest);
+    *text = out;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse, serialize};
+
+    const SOURCE: &str = r#"config:
+   agent_name: "Orders"
+
+variables:
+   order_id: mutable string = ""
+   order_id_extra: mutable string = ""
+
+topic orders:
+   description: "Order help"
+
+   actions:
+      lookup:
+         description: "Lookup"
+         inputs:
+            id: string
+         target: "flow://Lookup"
+
+   before_reasoning:
+      run @actions.lookup
+         with id=@variables.order_id
+
+   reasoning:
+      instructions: "Use {!@variables.order_id} and {!@variables.order_id_extra}"
+      actions:
+         do_lookup: @actions.lookup
+            description: "Lookup"
+            with id=@variables.order_id
+"#;
+
+    #[test]
+    fn test_build_agent_from_scratch_serializes() {
+        let mut agent = AgentFile::new();
+        agent
+            .add_variable(
+                VariableBuilder::mutable("verified", Type::Boolean, Expr::Bool(false))
+                    .description("Verification flag"),
+            )
+            .unwrap();
+        agent
+            .add_topic(
+                TopicBuilder::new("main")
+                    .description("Main topic")
+                    .instructions("Help the customer")
+                    .action(
+                        ActionDefBuilder::new("lookup")
+                            .target("flow://Lookup")
+                            .input("id", Type::String),
+                    )
+                    .invoke("do_lookup", "lookup"),
+            )
+            .unwrap();
+
+        let source = serialize(&agent);
+        let reparsed = parse(&source).expect("builder output should parse");
+        assert_eq!(reparsed.topics[0].node.name.node, "main");
+        assert!(source.contains("do_lookup: @actions.lookup"));
+    }
+
+    #[test]
+    fn test_added_nodes_get_spans_after_existing_content() {
+        let mut agent = parse(SOURCE).unwrap();
+        let end = agent.topics[0].span.end;
+        let topic = agent.add_topic(TopicBuilder::new("billing")).unwrap();
+        assert_eq!(topic.span, end..end);
+        assert_eq!(topic.node.name.span, end..end);
+
+        assert_eq!(
+            agent.add_topic(TopicBuilder::new("billing")).unwrap_err(),
+            EditError::AlreadyDefined {
+                kind: "topic",
+                name: "billing".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_rename_variable_updates_references() {
+        let mut agent = parse(SOURCE).unwrap();
+        agent.rename_variable("order_id", "order_number").unwrap();
+
+        let source = serialize(&agent);
+        assert!(source.contains("order_number: mutable string"));
+        assert!(source.contains("with id = @variables.order_number"));
+        assert!(source.contains("{!@variables.order_number} and {!@variables.order_id_extra}"));
+        assert!(!source.contains("@variables.order_id "));
+
+        assert!(matches!(agent.rename_variable("missing", "x"), Err(EditError::NotFound { .. })));
+    }
+
+    #[test]
+    fn test_remove_action_removes_invocations() {
+        let mut agent = parse(SOURCE).unwrap();
+        let removed = agent.remove_action("orders", "lookup").unwrap();
+        assert_eq!(removed.node.name.node, "lookup");
+
+        let topic = &agent.topics[0].node;
+        assert!(topic.actions.as_ref().unwrap().node.actions.is_empty());
+        assert!(topic
+            .before_reasoning
+            .as_ref()
+            .unwrap()
+            .node
+            .statements
+            .is_empty());
+        let reasoning = topic.reasoning.as_ref().unwrap();
+        assert!(reasoning.node.actions.as_ref().unwrap().node.is_empty());
+    }
+
+    #[test]
+    fn test_merge_policies() {
+        let other = parse(
+            r#"variables:
+   order_id: mutable string = ""
+   status: mutable string = ""
+
+topic orders:
+   description: "Other orders"
+   reasoning:
+      instructions: "Check {!@variables.order_id}"
+      actions:
+         again: @utils.transition to @topic.orders
+
+topic returns:
+   description: "Returns"
+"#,
+        )
+        .unwrap();
+
+        let base = parse(SOURCE).unwrap();
+        let mut agent = base.clone();
+        assert_eq!(
+            agent.merge(&other, MergePolicy::Error).unwrap_err(),
+            EditError::AlreadyDefined {
+                kind: "variable",
+                name: "order_id".to_string()
+            }
+        );
+        assert_eq!(agent, base);
+
+        agent.merge(&other, MergePolicy::PreferLeft).unwrap();
+        let topics: Vec<_> = agent
+            .topics
+            .iter()
+            .map(|t| t.node.name.node.as_str())
+            .collect();
+        assert_eq!(topics, ["orders", "returns"]);
+        assert_eq!(variable_names(&agent), ["order_id", "order_id_extra", "status"]);
+
+        let mut agent = base.clone();
+        let end = agent.topics[0].span.end;
+        let renames = agent.merge(&other, MergePolicy::Rename).unwrap();
+        assert_eq!(
+            renames,
+            [
+                MergeRename {
+                    kind: "variable",
+                    from: "order_id".to_string(),
+                    to: "order_id_2".to_string()
+                },
+                MergeRename {
+                    kind: "topic",
+                    from: "orders".to_string(),
+                    to: "orders_2".to_string()
+                },
+            ]
+        );
+        assert_eq!(agent.topics[1].span, end..end);
+        let source = serialize(&agent);
+        assert!(parse(&source).is_ok());
+        assert!(source.contains("Check {!@variables.order_id_2}"));
+        assert!(source.contains("again: @utils.transition to @topic.orders_2"));
+    }
+
+    #[test]
+    fn test_replace_reference_text_respects_word_boundaries() {
+        let mut text = "{!@variables.a} {!@variables.ab}".to_string();
+        replace_reference_text(&mut text, "@variables.a", "@variables.c");
+        assert_eq!(text, "{!@variables.c} {!@variables.ab}");
+    }
+}
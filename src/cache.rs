@@ -0,0 +1,479 @@
+//! Content-addressed caching of analysis results.
+//!
+//! An edit that only moves whitespace or comments leaves an agent's meaning
+//! alone, so validating it again gives the same diagnostics at shifted
+//! positions. [`semantic_hash`] identifies an AST by everything except its
+//! spans, and an [`AnalysisCache`] keeps results under that hash, in memory
+//! and optionally in a directory shared between processes.
+//!
+//! A result found for an earlier parse has its spans moved onto the current
+//! AST with a [`SpanMap`]. If one of its spans doesn't start and end where
+//! some node of the earlier AST did, the result is treated as missing and
+//! computed again.
+//!
+//! # Example
+//!
+//! ```rust
+//! use busbar_sf_agentscript::cache::{semantic_hash, AnalysisCache};
+//! use busbar_sf_agentscript::parse;
+//!
+//! let before = parse("topic main:\n   description: \"Main\"\n").unwrap();
+//! let after = parse("# Home\n\ntopic main:\n    description:   \"Main\"\n").unwrap();
+//! assert_eq!(semantic_hash(&before), semantic_hash(&after));
+//!
+//! // Cache the span of the description
+//! let description = |ast: &busbar_sf_agentscript::AgentFile| {
+//!     ast.topics[0].node.description.as_ref().unwrap().span.clone()
+//! };
+//! let mut cache = AnalysisCache::new();
+//! cache.insert(&before, "description", description(&before));
+//!
+//! // The cached span is moved onto the new parse
+//! let span = cache.get(&after, "description").unwrap();
+//! assert_eq!(span, description(&after));
+//! ```
+
+use crate::ast::AgentFile;
+use crate::spanner::transfer_spans;
+use indexmap::IndexMap;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Identity of an AST apart from its spans.
+///
+/// Two parses that differ only in whitespace and comments have the same
+/// hash. Doc comments are part of the AST, so they count. The hash is
+/// stable across runs and platforms, so it can name results stored on disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SemanticHash(u128);
+
+impl fmt::Display for SemanticHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:032x}", self.0)
+    }
+}
+
+/// Hash everything in `ast` except its spans.
+pub fn semantic_hash(ast: &AgentFile) -> SemanticHash {
+    let value = serde_json::to_value(ast).expect("AST always serializes to JSON");
+    let mut hasher = Fnv::default();
+    hash_value(&value, &mut hasher);
+    SemanticHash(hasher.0)
+}
+
+/// 128-bit FNV-1a, which unlike `std`'s hashers is stable between releases.
+struct Fnv(u128);
+
+impl Default for Fnv {
+    fn default() -> Self {
+        Self(0x6c62272e07bb014262b821756295c58d)
+    }
+}
+
+impl Fnv {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u128::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0000000001000000000000000000013b);
+        }
+    }
+
+    fn write_len(&mut self, len: usize) {
+        self.write(&(len as u64).to_le_bytes());
+    }
+
+    fn write_str(&mut self, s: &str) {
+        self.write_len(s.len());
+        self.write(s.as_bytes());
+    }
+}
+
+fn hash_value(value: &Value, hasher: &mut Fnv) {
+    match value {
+        Value::Null => hasher.write(&[0]),
+        Value::Bool(b) => hasher.write(&[1, u8::from(*b)]),
+        Value::Number(n) => {
+            hasher.write(&[2]);
+            hasher.write_str(&n.to_string());
+        }
+        Value::String(s) => {
+            hasher.write(&[3]);
+            hasher.write_str(s);
+        }
+        Value::Array(items) => {
+            hasher.write(&[4]);
+            hasher.write_len(items.len());
+            for item in items {
+                hash_value(item, hasher);
+            }
+        }
+        Value::Object(fields) => {
+            hasher.write(&[5]);
+            let spanned = is_spanned(fields);
+            let fields: Vec<_> = fields
+                .iter()
+                .filter(|(key, _)| !(spanned && *key == "span"))
+                .collect();
+            hasher.write_len(fields.len());
+            for (key, value) in fields {
+                hasher.write_str(key);
+                hash_value(value, hasher);
+            }
+        }
+    }
+}
+
+/// Whether `fields` is a serialized [`Spanned`](crate::Spanned): exactly a
+/// `node` and a `span` that is a `start..end` range. Object literal keys
+/// named `span` hold an expression, so they are hashed like any other key.
+fn is_spanned(fields: &serde_json::Map<String, Value>) -> bool {
+    let is_range = |value: &Value| {
+        value.as_object().is_some_and(|range| {
+            range.len() == 2
+                && range.get("start").is_some_and(Value::is_u64)
+                && range.get("end").is_some_and(Value::is_u64)
+        })
+    };
+    fields.len() == 2 && fields.contains_key("node") && fields.get("span").is_some_and(is_range)
+}
+
+/// Where each node's span moved between two parses of the same agent.
+#[derive(Debug, Clone, Default)]
+pub struct SpanMap {
+    /// `None` when the spans didn't change.
+    moved: Option<HashMap<Range<usize>, Range<usize>>>,
+}
+
+impl SpanMap {
+    /// Map the spans of `old` onto the matching nodes of `new`. The ASTs
+    /// should have the same [`semantic_hash`].
+    pub fn between(old: &AgentFile, new: &AgentFile) -> Self {
+        if old == new {
+            return Self { moved: None };
+        }
+        let mut moved = HashMap::new();
+        transfer_spans(old, new, &mut |_, old, new| {
+            moved.entry(old).or_insert(new);
+        });
+        Self { moved: Some(moved) }
+    }
+
+    /// Where `span` is in the new parse, if a node of the old one had it.
+    pub fn get(&self, span: &Range<usize>) -> Option<Range<usize>> {
+        match &self.moved {
+            None => Some(span.clone()),
+            Some(moved) => moved.get(span).cloned(),
+        }
+    }
+}
+
+/// Values holding spans that can be moved onto another parse.
+pub trait MapSpans: Sized {
+    /// `self` with every span moved by `map`, or `None` if one isn't in it.
+    fn map_spans(self, map: &SpanMap) -> Option<Self>;
+}
+
+impl MapSpans for Range<usize> {
+    fn map_spans(self, map: &SpanMap) -> Option<Self> {
+        map.get(&self)
+    }
+}
+
+impl<T: MapSpans> MapSpans for Option<T> {
+    fn map_spans(self, map: &SpanMap) -> Option<Self> {
+        match self {
+            Some(value) => value.map_spans(map).map(Some),
+            None => Some(None),
+        }
+    }
+}
+
+impl<T: MapSpans> MapSpans for Vec<T> {
+    fn map_spans(self, map: &SpanMap) -> Option<Self> {
+        self.into_iter().map(|value| value.map_spans(map)).collect()
+    }
+}
+
+/// Entries kept in memory by default; the least recently used go first.
+pub const DEFAULT_CAPACITY: usize = 256;
+
+/// Analysis results keyed by [`semantic_hash`] and a caller-chosen key.
+///
+/// The key names the analysis and anything besides the AST it depends on,
+/// such as the target environment or lint configuration. Each entry keeps
+/// the AST its spans refer to. With [`with_dir`](Self::with_dir), entries
+/// are also written to a directory and looked up there on a memory miss;
+/// entries written by another version of this crate are ignored.
+#[derive(Debug)]
+pub struct AnalysisCache<V> {
+    entries: IndexMap<(SemanticHash, String), (Arc<AgentFile>, V)>,
+    capacity: usize,
+    dir: Option<PathBuf>,
+}
+
+/// An entry as written to disk.
+#[derive(Serialize, Deserialize)]
+struct DiskEntry<A, V> {
+    version: String,
+    key: String,
+    ast: A,
+    value: V,
+}
+
+impl<V> Default for AnalysisCache<V> {
+    fn default() -> Self {
+        Self {
+            entries: IndexMap::new(),
+            capacity: DEFAULT_CAPACITY,
+            dir: None,
+        }
+    }
+}
+
+impl<V> AnalysisCache<V>
+where
+    V: Clone + MapSpans + Serialize + DeserializeOwned,
+{
+    /// An in-memory cache of [`DEFAULT_CAPACITY`] entries.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A cache that also stores entries in `dir`, creating it when needed.
+    pub fn with_dir(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: Some(dir.into()),
+            ..Self::default()
+        }
+    }
+
+    /// Keep at most `capacity` entries in memory.
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// The result cached for `ast` under `key`, with spans into `ast`.
+    pub fn get(&mut self, ast: &AgentFile, key: &str) -> Option<V> {
+        self.lookup(semantic_hash(ast), ast, key)
+    }
+
+    /// Cache `value`, whose spans refer to `ast`.
+    pub fn insert(&mut self, ast: &AgentFile, key: &str, value: V) {
+        self.store(semantic_hash(ast), ast, key, value);
+    }
+
+    /// The result cached for `ast` under `key`, computing and caching it if
+    /// there is none.
+    pub fn get_or_insert_with(
+        &mut self,
+        ast: &AgentFile,
+        key: &str,
+        compute: impl FnOnce() -> V,
+    ) -> V {
+        let hash = semantic_hash(ast);
+        if let Some(value) = self.lookup(hash, ast, key) {
+            return value;
+        }
+        let value = compute();
+        self.store(hash, ast, key, value.clone());
+        value
+    }
+
+    /// Number of entries in memory.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether no entries are in memory.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Drop the entries in memory. Entries on disk are kept.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    fn lookup(&mut self, hash: SemanticHash, ast: &AgentFile, key: &str) -> Option<V> {
+        let id = (hash, key.to_string());
+        let (cached_ast, value) = match self.entries.shift_remove(&id) {
+            Some(entry) => entry,
+            None => {
+                let entry = self.read(hash, key)?;
+                (Arc::new(entry.ast), entry.value)
+            }
+        };
+        let mapped = value.clone().map_spans(&SpanMap::between(&cached_ast, ast));
+        // Most recently used last
+        self.entries.insert(id, (cached_ast, value));
+        self.evict();
+        mapped
+    }
+
+    fn store(&mut self, hash: SemanticHash, ast: &AgentFile, key: &str, value: V) {
+        self.write(hash, key, ast, &value);
+        let id = (hash, key.to_string());
+        self.entries.shift_remove(&id);
+        self.entries.insert(id, (Arc::new(ast.clone()), value));
+        self.evict();
+    }
+
+    fn evict(&mut self) {
+        while self.entries.len() > self.capacity {
+            self.entries.shift_remove_index(0);
+        }
+    }
+
+    fn path(&self, hash: SemanticHash, key: &str) -> Option<PathBuf> {
+        let mut hasher = Fnv::default();
+        hasher.write_str(key);
+        let dir = self.dir.as_ref()?;
+        Some(dir.join(format!("{}-{:016x}.json", hash, hasher.0 as u64)))
+    }
+
+    fn read(&self, hash: SemanticHash, key: &str) -> Option<DiskEntry<AgentFile, V>> {
+        let text = std::fs::read_to_string(self.path(hash, key)?).ok()?;
+        let entry: DiskEntry<AgentFile, V> = serde_json::from_str(&text).ok()?;
+        (entry.version == env!("CARGO_PKG_VERSION") && entry.key == key).then_some(entry)
+    }
+
+    /// Write an entry to disk. The cache is best-effort, so failures are
+    /// ignored and the result is simply computed again next time.
+    fn write(&self, hash: SemanticHash, key: &str, ast: &AgentFile, value: &V) {
+        let (Some(dir), Some(path)) = (&self.dir, self.path(hash, key)) else {
+            return;
+        };
+        let entry = DiskEntry {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            key: key.to_string(),
+            ast,
+            value,
+        };
+        let Ok(text) = serde_json::to_string(&entry) else {
+            return;
+        };
+        // Write then rename, so a concurrent reader never sees half a file
+        let temp = path.with_extension(format!("{}.tmp", std::process::id()));
+        let written = std::fs::create_dir_all(dir)
+            .and_then(|()| std::fs::write(&temp, text))
+            .and_then(|()| std::fs::rename(&temp, &path));
+        if written.is_err() {
+            let _ = std::fs::remove_file(&temp);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    const SOURCE: &str = r#"variables:
+   count: mutable number = 0
+
+topic main:
+   description: "Main"
+"#;
+
+    const REFORMATTED: &str = r#"# Counter agent
+
+variables:
+    count: mutable number = 0
+
+
+# Entry point
+
+topic main:
+    description:    "Main"
+"#;
+
+    fn description(ast: &AgentFile) -> Range<usize> {
+        ast.topics[0]
+            .node
+            .description
+            .as_ref()
+            .unwrap()
+            .span
+            .clone()
+    }
+
+    #[test]
+    fn test_semantic_hash_ignores_layout() {
+        let ast = parse(SOURCE).unwrap();
+        assert_eq!(semantic_hash(&ast), semantic_hash(&parse(REFORMATTED).unwrap()));
+        assert_eq!(semantic_hash(&ast).to_string().len(), 32);
+
+        let changed = parse(&SOURCE.replace("= 0", "= 1")).unwrap();
+        assert_ne!(semantic_hash(&ast), semantic_hash(&changed));
+    }
+
+    #[test]
+    fn test_semantic_hash_keeps_object_keys_named_span() {
+        let source = |value: u32| {
+            format!("variables:\n   x: mutable object = {{\"span\": {value}, \"node\": 0}}\n")
+        };
+        let one = parse(&source(1)).unwrap();
+        let two = parse(&source(2)).unwrap();
+        assert_ne!(semantic_hash(&one), semantic_hash(&two));
+
+        let respaced = parse(&source(1).replace(": 1", ":    1")).unwrap();
+        assert_eq!(semantic_hash(&one), semantic_hash(&respaced));
+    }
+
+    #[test]
+    fn test_cache_moves_spans_onto_new_parse() {
+        let before = parse(SOURCE).unwrap();
+        let after = parse(REFORMATTED).unwrap();
+        let mut cache = AnalysisCache::new();
+
+        let mut computed = 0;
+        let mut analyze = |ast: &AgentFile| {
+            cache.get_or_insert_with(ast, "description", || {
+                computed += 1;
+                vec![description(ast)]
+            })
+        };
+        assert_eq!(analyze(&before), vec![description(&before)]);
+        assert_eq!(analyze(&after), vec![description(&after)]);
+        assert_eq!(computed, 1);
+
+        // A span that isn't a node's can't be moved, so it is recomputed
+        let inside = description(&before).start + 1..description(&before).end;
+        cache.insert(&before, "inside", vec![inside.clone()]);
+        assert_eq!(cache.get(&before, "inside"), Some(vec![inside]));
+        assert_eq!(cache.get(&after, "inside"), None);
+
+        // Keys are separate, and a semantic change misses
+        assert_eq!(cache.get(&after, "other"), None);
+        let changed = parse(&SOURCE.replace("\"Main\"", "\"Home\"")).unwrap();
+        assert_eq!(cache.get(&changed, "description"), None);
+    }
+
+    #[test]
+    fn test_cache_capacity_and_disk() {
+        let dir = std::env::temp_dir().join(format!("agentscript-cache-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let before = parse(SOURCE).unwrap();
+        let after = parse(REFORMATTED).unwrap();
+
+        let mut cache = AnalysisCache::with_dir(&dir).with_capacity(1);
+        cache.insert(&before, "a", description(&before));
+        cache.insert(&before, "b", description(&before));
+        assert_eq!(cache.len(), 1);
+
+        // Another process sees the entries on disk
+        let mut other: AnalysisCache<Range<usize>> = AnalysisCache::with_dir(&dir);
+        assert_eq!(other.get(&after, "a"), Some(description(&after)));
+        assert_eq!(other.get(&after, "b"), Some(description(&after)));
+        assert_eq!(other.len(), 2);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,433 @@
+//! Proposed descriptions for definitions that have none.
+//!
+//! The planner chooses topics and actions by their `description:`, so a
+//! missing one is flagged by validation and offered as a quick fix. The fix
+//! asks a [`DescriptionProvider`] for the text to insert: a host can plug in
+//! its own generator, such as a language model or team templates, while
+//! [`TemplateDescriptions`] proposes text from names, targets and doc
+//! comments so the workflow works offline and always gives the same result.
+//!
+//! [`missing_descriptions`] lists the definitions without a description and
+//! [`description_edit`] builds the edit that adds one.
+//!
+//! # Example
+//!
+//! ```rust
+//! use busbar_sf_agentscript::describe::{
+//!     description_edit, missing_descriptions, Described, TemplateDescriptions,
+//! };
+//! use busbar_sf_agentscript::edit::{apply_edits, EditSet};
+//! use busbar_sf_agentscript::parse;
+//!
+//! let source = "topic refund_requests:\n   reasoning:\n      instructions: \"Help\"\n";
+//! let ast = parse(source).unwrap();
+//!
+//! let missing = missing_descriptions(&ast);
+//! assert!(matches!(missing[0].item, Described::Topic(_)));
+//!
+//! let edit = description_edit(source, &missing[0], &TemplateDescriptions);
+//! let edited = apply_edits(source, EditSet::from(edit)).unwrap();
+//! assert!(edited.starts_with("topic refund_requests:\n   description: \"Handles refund requests.\"\n"));
+//!
+//! // Any closure works as a provider; `None` falls back to an empty description
+//! let edit = description_edit(source, &missing[0], &|_: &Described<'_>| None);
+//! assert_eq!(edit.new_text, "   description: \"\"\n");
+//! ```
+
+use crate::ast::{
+    ActionDef, AgentFile, Spanned, StartAgentBlock, TopicBlock, VariableDecl, VariableKind,
+};
+use crate::edit::SourceEdit;
+use crate::serializer::escape_string;
+use crate::target_uri::TargetScheme;
+use std::ops::Range;
+
+/// A definition that can carry a `description:`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Described<'a> {
+    StartAgent(&'a StartAgentBlock),
+    Topic(&'a TopicBlock),
+    /// An action definition and the name of the topic or start_agent it is in
+    Action {
+        topic: &'a str,
+        action: &'a ActionDef,
+    },
+    Variable(&'a VariableDecl),
+}
+
+impl<'a> Described<'a> {
+    /// The definition's name.
+    pub fn name(&self) -> &'a str {
+        match self {
+            Described::StartAgent(block) => &block.name.node,
+            Described::Topic(topic) => &topic.name.node,
+            Described::Action { action, .. } => &action.name.node,
+            Described::Variable(var) => &var.name.node,
+        }
+    }
+
+    /// The kind of definition, as used in diagnostics.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Described::StartAgent(_) => "start_agent",
+            Described::Topic(_) => "topic",
+            Described::Action { .. } => "action",
+            Described::Variable(_) => "variable",
+        }
+    }
+
+    /// The doc comment above the definition, if any.
+    pub fn doc(&self) -> Option<&'a str> {
+        let doc = match self {
+            Described::StartAgent(block) => &block.doc,
+            Described::Topic(topic) => &topic.doc,
+            Described::Action { action, .. } => &action.doc,
+            Described::Variable(var) => &var.doc,
+        };
+        doc.as_ref().map(|doc| doc.node.as_str())
+    }
+}
+
+/// Proposes the text of a missing `description:`.
+///
+/// Closures taking a [`Described`] implement this, so a host can pass a
+/// generator without defining a type.
+pub trait DescriptionProvider {
+    /// A description for `item`, or `None` to leave it for the author.
+    fn describe(&self, item: &Described<'_>) -> Option<String>;
+}
+
+impl<F> DescriptionProvider for F
+where
+    F: Fn(&Described<'_>) -> Option<String>,
+{
+    fn describe(&self, item: &Described<'_>) -> Option<String> {
+        self(item)
+    }
+}
+
+/// The built-in provider: the doc comment joined into one line if there is
+/// one, otherwise a fixed sentence built from the name and, for actions,
+/// the target.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TemplateDescriptions;
+
+impl DescriptionProvider for TemplateDescriptions {
+    fn describe(&self, item: &Described<'_>) -> Option<String> {
+        if let Some(doc) = item.doc() {
+            let doc = doc.split_whitespace().collect::<Vec<_>>().join(" ");
+            if !doc.is_empty() {
+                return Some(sentence(&doc));
+            }
+        }
+        let name = words(item.name());
+        let text = match item {
+            Described::StartAgent(_) => "Chooses the topic for each request.".to_string(),
+            Described::Topic(_) => format!("Handles {}.", name),
+            Described::Action { action, .. } => match action.target_uri() {
+                Some(Ok(uri)) => match uri.scheme {
+                    TargetScheme::Flow => format!("Runs the {} flow.", words(&uri.name)),
+                    TargetScheme::Prompt => {
+                        format!("Generates text with the {} prompt template.", words(&uri.name))
+                    }
+                    TargetScheme::Create => format!("Creates a {} record.", uri.name),
+                    TargetScheme::Read => format!("Reads {} records.", uri.name),
+                    TargetScheme::Update => format!("Updates {} records.", uri.name),
+                    TargetScheme::Delete => format!("Deletes {} records.", uri.name),
+                    TargetScheme::Query => format!("Queries {} records.", uri.name),
+                    _ => format!("Calls {}.", uri),
+                },
+                _ => sentence(&capitalize(&name)),
+            },
+            Described::Variable(var) => match (&var.kind, &var.source) {
+                (VariableKind::Linked, Some(source)) => {
+                    format!("The {}, from {}.", name, source.node.full_path())
+                }
+                _ => format!("The {}.", name),
+            },
+        };
+        Some(text)
+    }
+}
+
+/// A definition without a description.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MissingDescription<'a> {
+    pub item: Described<'a>,
+    /// Span of the definition's name
+    pub span: Range<usize>,
+    /// Span of the whole definition
+    pub definition: Range<usize>,
+    /// Span of a blank `description: ""` value, quotes included
+    pub blank: Option<Range<usize>>,
+}
+
+/// Every variable, start_agent, topic and action definition without a
+/// `description:` or with a blank one, in that order.
+pub fn missing_descriptions(ast: &AgentFile) -> Vec<MissingDescription<'_>> {
+    let mut missing = Vec::new();
+    let mut check = |item,
+                     description: &Option<Spanned<String>>,
+                     name: &Spanned<String>,
+                     definition: &Range<usize>| {
+        let blank = match description {
+            Some(d) if d.node.trim().is_empty() => Some(d.span.clone()),
+            Some(_) => return,
+            None => None,
+        };
+        missing.push(MissingDescription {
+            item,
+            span: name.span.clone(),
+            definition: definition.clone(),
+            blank,
+        });
+    };
+
+    for var in ast.variables.iter().flat_map(|v| &v.node.variables) {
+        let decl = &var.node;
+        check(Described::Variable(decl), &decl.description, &decl.name, &var.span);
+    }
+    let blocks = ast.start_agent.iter().map(|b| {
+        let block = &b.node;
+        (
+            Described::StartAgent(block),
+            &block.description,
+            &block.name,
+            &block.actions,
+            &b.span,
+        )
+    });
+    let topics = ast.topics.iter().map(|t| {
+        let topic = &t.node;
+        (
+            Described::Topic(topic),
+            &topic.description,
+            &topic.name,
+            &topic.actions,
+            &t.span,
+        )
+    });
+    for (item, description, name, actions, span) in blocks.chain(topics) {
+        check(item, description, name, span);
+        for action in actions.iter().flat_map(|a| &a.node.actions) {
+            let def = &action.node;
+            let item = Described::Action {
+                topic: &name.node,
+                action: def,
+            };
+            check(item, &def.description, &def.name, &action.span);
+        }
+    }
+    missing
+}
+
+/// An edit adding `description:` as the first property of `missing`, with
+/// the text from `provider`, or empty if it has none. A blank description is
+/// filled in where it is instead.
+///
+/// The line is indented like the definition's existing properties, or three
+/// spaces deeper than its header if it has none.
+pub fn description_edit(
+    source: &str,
+    missing: &MissingDescription<'_>,
+    provider: &dyn DescriptionProvider,
+) -> SourceEdit {
+    let text = provider
+        .describe(&missing.item)
+        .map(|text| escape_string(text.trim()))
+        .unwrap_or_default();
+    if let Some(blank) = &missing.blank {
+        return SourceEdit::replace(blank.clone(), format!("\"{}\"", text));
+    }
+    let header = missing.span.start;
+    let at = source[header..]
+        .find('\n')
+        .map(|i| header + i + 1)
+        .unwrap_or(source.len());
+    let line = format!("{}description: \"{}\"\n", child_indent(source, header, at), text);
+    if at == source.len() && !source.ends_with('\n') {
+        SourceEdit::insert(at, format!("\n{}", line))
+    } else {
+        SourceEdit::insert(at, line)
+    }
+}
+
+/// Indentation for a property under the header line containing `offset`,
+/// whose next line starts at `next`.
+fn child_indent(source: &str, offset: usize, next: usize) -> String {
+    let indent = |line: &str| line.len() - line.trim_start_matches(' ').len();
+    let start = source[..offset].rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let header = indent(&source[start..]);
+    let child = source[next..]
+        .lines()
+        .find(|line| !line.trim().is_empty())
+        .map(indent)
+        .filter(|&child| child > header);
+    " ".repeat(child.unwrap_or(header + 3))
+}
+
+/// `name` as lowercase words: `refund_requests` and `refundRequests` both
+/// become `refund requests`. All-caps words such as `ID` are kept.
+fn words(name: &str) -> String {
+    let mut words: Vec<String> = Vec::new();
+    for part in name.split(['_', '-', ' ', '.']).filter(|p| !p.is_empty()) {
+        let mut word = String::new();
+        let mut prev_lower = false;
+        for c in part.chars() {
+            if c.is_uppercase() && prev_lower {
+                words.push(std::mem::take(&mut word));
+            }
+            prev_lower = c.is_lowercase() || c.is_ascii_digit();
+            word.push(c);
+        }
+        words.push(word);
+    }
+    words
+        .into_iter()
+        .map(|word| {
+            if word.len() > 1 && word.chars().all(|c| !c.is_lowercase()) {
+                word
+            } else {
+                word.to_lowercase()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// `text` ending with a full stop.
+fn sentence(text: &str) -> String {
+    let text = text.trim();
+    if text.ends_with(['.', '!', '?']) {
+        text.to_string()
+    } else {
+        format!("{}.", text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edit::{apply_edits, EditSet};
+    use crate::parse;
+
+    const SOURCE: &str = r#"variables:
+   order_id: mutable string = ""
+   customerEmail: linked string
+      source: @context.user.email
+
+start_agent selector:
+   reasoning:
+      instructions: "Route"
+
+# Refunds and returns for orders
+# placed in the last 90 days
+topic refund_requests:
+    actions:
+        lookup_order:
+            target: "flow://Lookup_Order"
+        create_case:
+            description: "Open a case"
+            target: "create://Case"
+        notify:
+            description: ""
+            target: "apex://Notifier.send"
+        summarize:
+            target: "unknown"
+    reasoning:
+        instructions: "Help"
+"#;
+
+    fn proposals(provider: &dyn DescriptionProvider) -> Vec<(String, Option<String>)> {
+        let ast = parse(SOURCE).unwrap();
+        missing_descriptions(&ast)
+            .iter()
+            .map(|m| (m.item.name().to_string(), provider.describe(&m.item)))
+            .collect()
+    }
+
+    #[test]
+    fn test_template_descriptions() {
+        let some = |name: &str, text: &str| (name.to_string(), Some(text.to_string()));
+        assert_eq!(
+            proposals(&TemplateDescriptions),
+            vec![
+                some("order_id", "The order id."),
+                some("customerEmail", "The customer email, from @context.user.email."),
+                some("selector", "Chooses the topic for each request."),
+                some(
+                    "refund_requests",
+                    "Refunds and returns for orders placed in the last 90 days.",
+                ),
+                some("lookup_order", "Runs the lookup order flow."),
+                some("notify", "Calls apex://Notifier.send."),
+                some("summarize", "Summarize."),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_description_edit_uses_provider_and_indentation() {
+        let ast = parse(SOURCE).unwrap();
+        let missing = missing_descriptions(&ast);
+        let provider = |item: &Described<'_>| {
+            (item.kind() == "action").then(|| format!("Calls {}\tnow", item.name()))
+        };
+
+        let mut edits = EditSet::new();
+        for m in &missing {
+            edits.push(description_edit(SOURCE, m, &provider)).unwrap();
+        }
+        let edited = apply_edits(SOURCE, edits).unwrap();
+        assert!(edited.contains("   order_id: mutable string = \"\"\n      description: \"\"\n"));
+        assert!(edited
+            .contains("   customerEmail: linked string\n      description: \"\"\n      source:"));
+        assert!(edited.contains("start_agent selector:\n   description: \"\"\n"));
+        assert!(edited.contains("topic refund_requests:\n    description: \"\"\n    actions:"));
+        assert!(edited.contains(
+            "        lookup_order:\n            description: \"Calls lookup_order\\tnow\"\n"
+        ));
+
+        // A blank description is filled in rather than added again
+        assert!(edited.contains(
+            "        notify:\n            description: \"Calls notify\\tnow\"\n            target:"
+        ));
+
+        // Only the blank descriptions the provider had no text for remain
+        let reparsed = parse(&edited).unwrap();
+        let remaining = missing_descriptions(&reparsed);
+        assert_eq!(remaining.len(), 4);
+        assert!(remaining
+            .iter()
+            .all(|m| m.blank.is_some() && m.item.kind() != "action"));
+    }
+
+    #[test]
+    fn test_description_edit_at_end_of_file() {
+        let source = "variables:\n   count: mutable number = 0";
+        let ast = parse(source).unwrap();
+        let missing = missing_descriptions(&ast);
+        let edit = description_edit(source, &missing[0], &TemplateDescriptions);
+        let edited = apply_edits(source, EditSet::from(edit)).unwrap();
+        assert_eq!(
+            edited,
+            "variables:\n   count: mutable number = 0\n      description: \"The count.\"\n"
+        );
+    }
+
+    #[test]
+    fn test_words() {
+        assert_eq!(words("refund_requests"), "refund requests");
+        assert_eq!(words("refundRequests"), "refund requests");
+        assert_eq!(words("Lookup_Order"), "lookup order");
+        assert_eq!(words("customer_ID"), "customer ID");
+    }
+}
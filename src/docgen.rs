@@ -0,0 +1,219 @@
+//! Markdown documentation generator.
+//!
+//! Produces a reference page for an agent from its AST: the agent config,
+//! variables, start_agent, topics, and action definitions. Each definition's
+//! `#` doc comment (see [`VariableDecl::doc`](crate::ast::VariableDecl::doc))
+//! is included ahead of its `description:`.
+//!
+//! # Example
+//!
+//! ```rust
+//! use busbar_sf_agentscript::{docgen, parse};
+//!
+//! let source = r#"
+//! config:
+//!    agent_name: "Support"
+//!
+//! ## Routes customers to the right place.
+//! topic main:
+//!    description: "Main topic"
+//! "#;
+//!
+//! let ast = parse(source).unwrap();
+//! let markdown = docgen::generate_markdown(&ast);
+//! assert!(markdown.contains("# Support"));
+//! assert!(markdown.contains("Routes customers to the right place."));
+//! ```
+
+use crate::ast::{
+    ActionDef, ActionsBlock, AgentFile, InstructionSection, ParamDef, ReasoningBlock, Spanned,
+    VariableKind,
+};
+use crate::markdown::render_markdown;
+use crate::typecheck::ExprType;
+use std::fmt::Write;
+
+/// Generate Markdown documentation for an agent.
+pub fn generate_markdown(agent: &AgentFile) -> String {
+    let mut out = String::new();
+
+    let title = agent
+        .config
+        .as_ref()
+        .map(|c| c.node.agent_name.node.as_str())
+        .unwrap_or("Agent");
+    writeln!(out, "# {}", title).unwrap();
+    if let Some(desc) = agent
+        .config
+        .as_ref()
+        .and_then(|c| c.node.description.as_ref())
+    {
+        writeln!(out, "\n{}", render_markdown(&desc.node)).unwrap();
+    }
+
+    if let Some(vars) = &agent.variables {
+        writeln!(out, "\n## Variables").unwrap();
+        for var in &vars.node.variables {
+            let v = &var.node;
+            writeln!(out, "\n### `{}`\n", v.name.node).unwrap();
+            let kind = match v.kind {
+                VariableKind::Mutable => "mutable",
+                VariableKind::Linked => "linked",
+            };
+            writeln!(out, "- **Kind:** {}", kind).unwrap();
+            writeln!(out, "- **Type:** `{}`", ExprType::from(&v.ty.node)).unwrap();
+            if let Some(source) = &v.source {
+                writeln!(out, "- **Source:** `{}`", source.node.full_path()).unwrap();
+            }
+            write_prose(&mut out, &v.doc, &v.description);
+        }
+    }
+
+    if let Some(start) = &agent.start_agent {
+        writeln!(out, "\n## Start Agent `{}`", start.node.name.node).unwrap();
+        write_prose(&mut out, &start.node.doc, &start.node.description);
+        write_sections(&mut out, &start.node.reasoning);
+        write_actions(&mut out, &start.node.actions);
+    }
+
+    if !agent.topics.is_empty() {
+        writeln!(out, "\n## Topics").unwrap();
+        for topic in &agent.topics {
+            writeln!(out, "\n### `{}`", topic.node.name.node).unwrap();
+            write_prose(&mut out, &topic.node.doc, &topic.node.description);
+            write_sections(&mut out, &topic.node.reasoning);
+            write_actions(&mut out, &topic.node.actions);
+        }
+    }
+
+    out
+}
+
+/// Write the doc comment followed by the description, each as a rendered paragraph.
+fn write_prose(
+    out: &mut String,
+    doc: &Option<Spanned<String>>,
+    description: &Option<Spanned<String>>,
+) {
+    if let Some(doc) = doc {
+        writeln!(out, "\n{}", render_markdown(&doc.node)).unwrap();
+    }
+    if let Some(desc) = description {
+        writeln!(out, "\n{}", render_markdown(&desc.node)).unwrap();
+    }
+}
+
+/// Write the outline of the `|## heading` sections in the instructions.
+fn write_sections(out: &mut String, reasoning: &Option<Spanned<ReasoningBlock>>) {
+    fn write_outline(out: &mut String, sections: &[InstructionSection], depth: usize) {
+        for section in sections {
+            writeln!(out, "{}- {}", "  ".repeat(depth), render_markdown(&section.title)).unwrap();
+            write_outline(out, &section.children, depth + 1);
+        }
+    }
+    let sections = reasoning
+        .as_ref()
+        .and_then(|r| r.node.instructions.as_ref())
+        .map_or_else(Vec::new, |i| i.node.sections());
+    if sections.is_empty() {
+        return;
+    }
+    writeln!(out, "\n#### Instructions\n").unwrap();
+    write_outline(out, &sections, 0);
+}
+
+fn write_actions(out: &mut String, actions: &Option<Spanned<ActionsBlock>>) {
+    let Some(actions) = actions else { return };
+    if actions.node.actions.is_empty() {
+        return;
+    }
+    writeln!(out, "\n#### Actions").unwrap();
+    for action in &actions.node.actions {
+        write_action(out, &action.node);
+    }
+}
+
+fn write_action(out: &mut String, action: &ActionDef) {
+    writeln!(out, "\n##### `{}`", action.name.node).unwrap();
+    write_prose(out, &action.doc, &action.description);
+    if let Some(target) = &action.target {
+        writeln!(out, "\n**Target:** `{}`", target.node).unwrap();
+    }
+    write_params(out, "Inputs", &action.inputs);
+    write_params(out, "Outputs", &action.outputs);
+}
+
+fn write_params(out: &mut String, heading: &str, params: &Option<Spanned<Vec<Spanned<ParamDef>>>>) {
+    let Some(params) = params else { return };
+    if params.node.is_empty() {
+        return;
+    }
+    writeln!(out, "\n**{}:**\n", heading).unwrap();
+    for param in &params.node {
+        let p = &param.node;
+        write!(out, "- `{}`: `{}`", p.name.node, ExprType::from(&p.ty.node)).unwrap();
+        if let Some(desc) = &p.description {
+            write!(out, " — {}", render_markdown(&desc.node)).unwrap();
+        }
+        writeln!(out).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_markdown_includes_doc_comments() {
+        let source = r#"config:
+   agent_name: "Orders"
+
+variables:
+   # Set once the customer is verified.
+   verified: mutable boolean = False
+      description: "Verification flag"
+
+topic orders:
+   description: "Order help"
+
+   actions:
+      # Calls the order lookup flow.
+      lookup:
+         description: "Lookup"
+         inputs:
+            order_id: string
+               description: "Order number"
+         target: "flow://Lookup"
+
+   reasoning:
+      instructions: "Help"
+"#;
+        let ast = crate::parse(source).expect("Failed to parse");
+        let md = generate_markdown(&ast);
+
+        assert!(md.starts_with("# Orders\n"));
+        assert!(md.contains("### `verified`"));
+        assert!(md.contains("- **Type:** `boolean`"));
+        assert!(md.contains("Set once the customer is verified.\n\nVerification flag"));
+        assert!(md.contains("##### `lookup`\n\nCalls the order lookup flow."));
+        assert!(md.contains("- `order_id`: `string` — Order number"));
+    }
+
+    #[test]
+    fn test_generate_markdown_outlines_instruction_sections() {
+        let source = r#"topic orders:
+   description: "Order help"
+   reasoning:
+      instructions: ->
+         |## Rules
+         | Be brief
+         |### Returns
+         | Within 30 days
+         |## Escalation
+         | Ask first
+"#;
+        let ast = crate::parse(source).expect("Failed to parse");
+        let md = generate_markdown(&ast);
+        assert!(md.contains("#### Instructions\n\n- Rules\n  - Returns\n- Escalation\n"), "{md}");
+    }
+}
@@ -0,0 +1,242 @@
+//! Machine-applicable source edits.
+//!
+//! Formatting, renames and editor quick fixes all describe their changes
+//! as [`SourceEdit`]s: a byte range of the original source and the text to
+//! put there. An [`EditSet`] collects the edits of one change,
+//! rejecting edits that overlap, and [`apply_edits`] applies them.
+//!
+//! Every span refers to the original source, so edits don't shift each
+//! other. Insertions at the same offset are applied in the order they were
+//! added, and before a replacement starting there.
+//!
+//! # Example
+//!
+//! ```rust
+//! use busbar_sf_agentscript::edit::{apply_edits, EditSet, SourceEdit};
+//!
+//! let source = "topic main:\n   description: \"Main\"\n";
+//! let mut edits = EditSet::new();
+//! edits.push(SourceEdit::replace(6..10, "home")).unwrap();
+//! edits.push(SourceEdit::insert(0, "# Home\n")).unwrap();
+//! // Overlaps the first edit
+//! assert!(edits.push(SourceEdit::delete(8..12)).is_err());
+//!
+//! let edited = apply_edits(source, edits).unwrap();
+//! assert_eq!(edited, "# Home\ntopic home:\n   description: \"Main\"\n");
+//! ```
+
+use serde::Serialize;
+use std::ops::Range;
+use thiserror::Error;
+
+/// A replacement of the text at `span`. An empty span inserts.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct SourceEdit {
+    /// Byte range of the text to replace.
+    pub span: Range<usize>,
+    /// The replacement text.
+    pub new_text: String,
+}
+
+impl SourceEdit {
+    /// Replace the text at `span` with `new_text`.
+    pub fn replace(span: Range<usize>, new_text: impl Into<String>) -> Self {
+        Self {
+            span,
+            new_text: new_text.into(),
+        }
+    }
+
+    /// Insert `text` at byte `offset`.
+    pub fn insert(offset: usize, text: impl Into<String>) -> Self {
+        Self::replace(offset..offset, text)
+    }
+
+    /// Delete the text at `span`.
+    pub fn delete(span: Range<usize>) -> Self {
+        Self::replace(span, String::new())
+    }
+
+    /// Whether the edit inserts without replacing anything.
+    pub fn is_insertion(&self) -> bool {
+        self.span.is_empty()
+    }
+
+    /// Whether the two edits touch the same text, so that applying both is
+    /// ambiguous. Edits that only meet at an offset don't overlap.
+    pub fn overlaps(&self, other: &SourceEdit) -> bool {
+        let (a, b) = (&self.span, &other.span);
+        if self.is_insertion() || other.is_insertion() {
+            // An insertion strictly inside a replaced range
+            (a.start > b.start && a.start < b.end) || (b.start > a.start && b.start < a.end)
+        } else {
+            a.start < b.end && b.start < a.end
+        }
+    }
+}
+
+/// Why edits can't be combined or applied.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum EditError {
+    /// Two edits replace some of the same text.
+    #[error("edits at {}..{} and {}..{} overlap", .0.start, .0.end, .1.start, .1.end)]
+    Overlap(Range<usize>, Range<usize>),
+
+    /// An edit's span is reversed, extends past the end of the source, or
+    /// splits a character.
+    #[error("edit at {}..{} is outside the source", .0.start, .0.end)]
+    InvalidSpan(Range<usize>),
+}
+
+/// Non-overlapping edits, kept in application order.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(transparent)]
+pub struct EditSet {
+    edits: Vec<SourceEdit>,
+}
+
+impl EditSet {
+    /// An empty edit set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an edit, unless it overlaps one already in the set.
+    pub fn push(&mut self, edit: SourceEdit) -> Result<(), EditError> {
+        if edit.span.start > edit.span.end {
+            return Err(EditError::InvalidSpan(edit.span));
+        }
+        if let Some(other) = self.edits.iter().find(|other| other.overlaps(&edit)) {
+            return Err(EditError::Overlap(other.span.clone(), edit.span));
+        }
+        // After every edit starting earlier, and after insertions at the same
+        // offset, so those keep the order they were added in
+        let at = self.edits.partition_point(|other| {
+            other.span.start < edit.span.start
+                || (other.span.start == edit.span.start && other.is_insertion())
+        });
+        self.edits.insert(at, edit);
+        Ok(())
+    }
+
+    /// Collect edits into a set, failing on the first overlap.
+    pub fn try_from_edits(edits: impl IntoIterator<Item = SourceEdit>) -> Result<Self, EditError> {
+        let mut set = Self::new();
+        for edit in edits {
+            set.push(edit)?;
+        }
+        Ok(set)
+    }
+
+    /// The edits in source order.
+    pub fn iter(&self) -> std::slice::Iter<'_, SourceEdit> {
+        self.edits.iter()
+    }
+
+    /// Number of edits.
+    pub fn len(&self) -> usize {
+        self.edits.len()
+    }
+
+    /// Whether the set has no edits.
+    pub fn is_empty(&self) -> bool {
+        self.edits.is_empty()
+    }
+
+    /// Apply the edits to `source`, the text their spans refer to.
+    pub fn apply(&self, source: &str) -> Result<String, EditError> {
+        let mut output = String::with_capacity(source.len());
+        let mut copied = 0;
+        for edit in &self.edits {
+            let span = &edit.span;
+            if span.start > span.end
+                || span.end > source.len()
+                || !source.is_char_boundary(span.start)
+                || !source.is_char_boundary(span.end)
+            {
+                return Err(EditError::InvalidSpan(span.clone()));
+            }
+            output.push_str(&source[copied..span.start]);
+            output.push_str(&edit.new_text);
+            copied = span.end;
+        }
+        output.push_str(&source[copied..]);
+        Ok(output)
+    }
+
+    /// The edits in source order.
+    pub fn into_vec(self) -> Vec<SourceEdit> {
+        self.edits
+    }
+}
+
+impl From<SourceEdit> for EditSet {
+    fn from(edit: SourceEdit) -> Self {
+        Self { edits: vec![edit] }
+    }
+}
+
+impl IntoIterator for EditSet {
+    type Item = SourceEdit;
+    type IntoIter = std::vec::IntoIter<SourceEdit>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.edits.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a EditSet {
+    type Item = &'a SourceEdit;
+    type IntoIter = std::slice::Iter<'a, SourceEdit>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.edits.iter()
+    }
+}
+
+/// Apply `edits`, whose spans all refer to `source`, in one pass.
+pub fn apply_edits(
+    source: &str,
+    edits: impl IntoIterator<Item = SourceEdit>,
+) -> Result<String, EditError> {
+    EditSet::try_from_edits(edits)?.apply(source)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overlap_detection() {
+        let mut edits = EditSet::new();
+        edits.push(SourceEdit::replace(2..5, "x")).unwrap();
+        // Adjacent edits and insertions at either end are fine
+        edits.push(SourceEdit::replace(5..6, "y")).unwrap();
+        edits.push(SourceEdit::insert(2, "<")).unwrap();
+        edits.push(SourceEdit::insert(5, ">")).unwrap();
+        assert_eq!(edits.push(SourceEdit::replace(4..7, "z")), Err(EditError::Overlap(2..5, 4..7)));
+        assert_eq!(edits.push(SourceEdit::insert(3, "!")), Err(EditError::Overlap(2..5, 3..3)));
+        assert_eq!(edits.apply("0123456789").unwrap(), "01<x>y6789");
+    }
+
+    #[test]
+    fn test_application_order() {
+        // Insertions at one offset keep their order, before a replacement there
+        let edits = [
+            SourceEdit::replace(0..1, "A"),
+            SourceEdit::insert(0, "1"),
+            SourceEdit::insert(0, "2"),
+            SourceEdit::insert(3, "!"),
+        ];
+        assert_eq!(apply_edits("abc", edits).unwrap(), "12Abc!");
+
+        assert_eq!(
+            apply_edits("abc", [SourceEdit::delete(2..4)]),
+            Err(EditError::InvalidSpan(2..4))
+        );
+        assert_eq!(
+            apply_edits("é", [SourceEdit::insert(1, "x")]),
+            Err(EditError::InvalidSpan(1..1))
+        );
+    }
+}
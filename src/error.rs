@@ -29,9 +29,14 @@
 //!     contexts: vec![],
 //! };
 //! // reporter.report_parse_error(&error); // Prints colorful error
+//!
+//! // Or render to a string, e.g. for tests or custom output
+//! let text = reporter.with_color(false).render_parse_error(&error);
+//! assert!(text.contains("Expected string literal"));
 //! ```
 
-use ariadne::{Color, Label, Report, ReportKind, Source};
+use crate::validation::{SemanticError, Severity};
+use ariadne::{Color, Config, Label, Report, ReportKind, Source};
 use std::fmt;
 
 /// The main error type for AgentScript operations.
@@ -81,6 +86,19 @@ impl fmt::Display for ParseErrorInfo {
     }
 }
 
+impl From<&ParseErrorInfo> for SemanticError {
+    fn from(error: &ParseErrorInfo) -> Self {
+        SemanticError {
+            message: error.to_string(),
+            span: error.span.clone(),
+            severity: Severity::Error,
+            hint: None,
+            code: Some("parse-error"),
+            related: Vec::new(),
+        }
+    }
+}
+
 /// Validation error for semantic issues.
 #[derive(Debug)]
 pub struct ValidationError {
@@ -103,22 +121,76 @@ impl fmt::Display for ValidationError {
 pub struct ErrorReporter<'src> {
     source_name: String,
     source: &'src str,
+    color: bool,
 }
 
+/// A finished ariadne report, keyed by source name.
+type AriadneReport<'a> = Report<'a, (&'a String, std::ops::Range<usize>)>;
+
 impl<'src> ErrorReporter<'src> {
     /// Create a new error reporter.
     pub fn new(source_name: impl Into<String>, source: &'src str) -> Self {
         Self {
             source_name: source_name.into(),
             source,
+            color: true,
         }
     }
 
+    /// Enable or disable ANSI colors (enabled by default).
+    pub fn with_color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+
     /// Report a parse error to stderr.
     pub fn report_parse_error(&self, error: &ParseErrorInfo) {
+        self.eprint(self.parse_error_report(error));
+    }
+
+    /// Render a parse error as a string, with a label for the error itself
+    /// and one for each parser context it occurred in.
+    pub fn render_parse_error(&self, error: &ParseErrorInfo) -> String {
+        self.render(self.parse_error_report(error))
+    }
+
+    /// Report a validation error to stderr.
+    pub fn report_validation_error(&self, error: &ValidationError) {
+        let span = error.span.clone().unwrap_or(0..0);
+
+        let mut report = Report::build(ReportKind::Error, &self.source_name, span.start)
+            .with_config(self.config())
+            .with_message(&error.message)
+            .with_label(
+                Label::new((&self.source_name, span))
+                    .with_color(Color::Yellow)
+                    .with_message("here"),
+            );
+
+        if let Some(ref hint) = error.hint {
+            report = report.with_help(hint);
+        }
+
+        self.eprint(report.finish());
+    }
+
+    /// Report a semantic error from [`validate_ast`](crate::validate_ast) to stderr.
+    ///
+    /// Warnings are reported with [`ReportKind::Warning`].
+    pub fn report_semantic_error(&self, error: &SemanticError) {
+        self.eprint(self.semantic_error_report(error));
+    }
+
+    /// Render a semantic error as a string.
+    pub fn render_semantic_error(&self, error: &SemanticError) -> String {
+        self.render(self.semantic_error_report(error))
+    }
+
+    fn parse_error_report(&self, error: &ParseErrorInfo) -> AriadneReport<'_> {
         let span = error.span.clone().unwrap_or(0..0);
 
         let mut report = Report::build(ReportKind::Error, &self.source_name, span.start)
+            .with_config(self.config())
             .with_message(&error.message);
 
         let mut label = Label::new((&self.source_name, span.clone())).with_color(Color::Red);
@@ -144,34 +216,114 @@ impl<'src> ErrorReporter<'src> {
             report = report.with_note(format!("expected one of: {}", error.expected.join(", ")));
         }
 
-        report
-            .finish()
-            .eprint((&self.source_name, Source::from(self.source)))
-            .unwrap();
+        report.finish()
     }
 
-    /// Report a validation error to stderr.
-    pub fn report_validation_error(&self, error: &ValidationError) {
+    fn semantic_error_report(&self, error: &SemanticError) -> AriadneReport<'_> {
         let span = error.span.clone().unwrap_or(0..0);
+        let (kind, color) = match error.severity {
+            Severity::Error => (ReportKind::Error, Color::Red),
+            Severity::Warning => (ReportKind::Warning, Color::Yellow),
+        };
 
-        let mut report = Report::build(ReportKind::Error, &self.source_name, span.start)
+        let mut report = Report::build(kind, &self.source_name, span.start)
+            .with_config(self.config())
             .with_message(&error.message)
             .with_label(
                 Label::new((&self.source_name, span))
-                    .with_color(Color::Yellow)
+                    .with_color(color)
                     .with_message("here"),
             );
 
+        for (span, label) in &error.related {
+            report = report.with_label(
+                Label::new((&self.source_name, span.clone()))
+                    .with_color(Color::Cyan)
+                    .with_message(label),
+            );
+        }
+        if let Some(code) = error.code {
+            report = report.with_code(code);
+        }
         if let Some(ref hint) = error.hint {
             report = report.with_help(hint);
         }
 
+        report.finish()
+    }
+
+    fn config(&self) -> Config {
+        Config::default().with_color(self.color)
+    }
+
+    fn eprint<'a>(&'a self, report: AriadneReport<'a>) {
         report
-            .finish()
             .eprint((&self.source_name, Source::from(self.source)))
             .unwrap();
     }
+
+    fn render<'a>(&'a self, report: AriadneReport<'a>) -> String {
+        let mut buf = Vec::new();
+        report
+            .write((&self.source_name, Source::from(self.source)), &mut buf)
+            .unwrap();
+        String::from_utf8_lossy(&buf).into_owned()
+    }
+}
+
+/// Render a parse error as a colored ariadne report.
+///
+/// The report labels the error span and every parser context in the
+/// error's chain (e.g. "while parsing topic block"). Use
+/// [`ErrorReporter::with_color`] for uncolored output or a custom source name.
+pub fn render_report(source: &str, error: &ParseErrorInfo) -> String {
+    ErrorReporter::new("<source>", source).render_parse_error(error)
 }
 
 /// Result type for AgentScript operations.
 pub type Result<T> = std::result::Result<T, AgentScriptError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_parse_error_labels_contexts() {
+        let source = "topic main:\n   description: 42\n";
+        let error = ParseErrorInfo {
+            message: "Expected string literal".to_string(),
+            span: Some(29..31),
+            expected: vec!["string".to_string()],
+            found: Some("42".to_string()),
+            contexts: vec![
+                ("description".to_string(), 15..31),
+                ("topic block".to_string(), 0..31),
+            ],
+        };
+
+        let text = ErrorReporter::new("main.agent", source)
+            .with_color(false)
+            .render_parse_error(&error);
+        assert!(text.contains("main.agent"));
+        assert!(text.contains("Expected string literal"));
+        assert!(text.contains("found '42'"));
+        assert!(text.contains("while parsing description"));
+        assert!(text.contains("while parsing topic block"));
+        assert!(!text.contains('\u{1b}'));
+    }
+
+    #[test]
+    fn test_render_report_is_colored() {
+        let error = ParseErrorInfo {
+            message: "Unexpected token".to_string(),
+            span: Some(0..6),
+            expected: vec!["topic".to_string()],
+            found: Some("config".to_string()),
+            contexts: vec![],
+        };
+        let text = render_report("config:\n", &error);
+        assert!(text.contains("Unexpected token"));
+        assert!(text.contains("expected one of: topic"));
+        assert!(text.contains('\u{1b}'));
+    }
+}
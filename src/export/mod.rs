@@ -0,0 +1,6 @@
+//! Export agents to platform-specific formats.
+//!
+//! - [`salesforce`] - GenAiPlannerBundle / GenAiPlugin / GenAiFunction
+//!   metadata, deployable with `sf project deploy`
+
+pub mod salesforce;
@@ -0,0 +1,864 @@
+//! Salesforce Agentforce metadata export.
+//!
+//! Converts an [`AgentFile`] into the source-format metadata files that
+//! `sf project deploy` understands:
+//!
+//! | AgentScript | Metadata | Path |
+//! |-------------|----------|------|
+//! | `config` + `start_agent` | `GenAiPlannerBundle` | `genAiPlannerBundles/<Agent>/<Agent>.genAiPlannerBundle` |
+//! | `topic` | `GenAiPlugin` | `genAiPlugins/<Topic>.genAiPlugin-meta.xml` |
+//! | action definition | `GenAiFunction` | `genAiFunctions/<Action>/<Action>.genAiFunction-meta.xml` |
+//! | action `inputs` / `outputs` | Lightning type schema | `genAiFunctions/<Action>/input/schema.json`, `output/schema.json` |
+//!
+//! Paths are relative to a package directory such as `force-app/main/default`.
+//!
+//! Metadata has no equivalent for reasoning actions, directive blocks, or
+//! variables, so those are not exported. Dynamic instructions are flattened
+//! to text, with conditional sections written as "If ...:" / "Otherwise:".
+//!
+//! # Example
+//!
+//! ```rust
+//! use busbar_sf_agentscript::export::salesforce::to_salesforce_metadata;
+//! use busbar_sf_agentscript::parse;
+//!
+//! let source = r#"
+//! config:
+//!    agent_name: "Support_Agent"
+//!
+//! topic orders:
+//!    description: "Order questions"
+//!
+//!    actions:
+//!       lookup:
+//!          description: "Look up an order"
+//!          inputs:
+//!             order_id: string
+//!          target: "flow://Lookup_Order"
+//!
+//!    reasoning:
+//!       instructions: "Help with orders"
+//! "#;
+//!
+//! let metadata = to_salesforce_metadata(&parse(source).unwrap());
+//! let function = metadata.file("genAiFunctions/lookup/lookup.genAiFunction-meta.xml").unwrap();
+//! assert!(function.contains("<invocationTarget>Lookup_Order</invocationTarget>"));
+//! assert!(function.contains("<invocationTargetType>flow</invocationTargetType>"));
+//! ```
+
+use crate::ast::{
+    ActionDef, ActionsBlock, AgentFile, InstructionPart, Instructions, ParamDef, ReasoningBlock,
+    Spanned, TopicBlock, Type,
+};
+use crate::serializer::serialize_expr;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use std::collections::HashSet;
+use std::fmt::Write as _;
+use std::io;
+use std::ops::Range;
+use std::path::Path;
+
+/// Options for [`to_salesforce_metadata_with`].
+#[derive(Debug, Clone)]
+pub struct SalesforceOptions {
+    /// Locale written to each topic's `<language>` (default `en_US`).
+    pub language: String,
+    /// Planner type of the bundle (default `AiCopilot__ReAct`).
+    pub planner_type: String,
+}
+
+impl Default for SalesforceOptions {
+    fn default() -> Self {
+        Self {
+            language: "en_US".to_string(),
+            planner_type: "AiCopilot__ReAct".to_string(),
+        }
+    }
+}
+
+/// A single generated metadata file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MetadataFile {
+    /// Path relative to the package directory, using `/` separators.
+    pub path: String,
+    /// File contents (XML or JSON).
+    pub contents: String,
+    /// Where the file's lines came from in the AgentScript source, sorted
+    /// by line. The first anchor is line 1 and covers the whole definition.
+    #[serde(skip)]
+    pub anchors: Vec<SourceAnchor>,
+}
+
+/// Maps a line of a generated file back to the AgentScript it came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceAnchor {
+    /// 1-based line in the generated file. The anchor applies to this line
+    /// and every following line up to the next anchor.
+    pub line: usize,
+    /// Span in the AgentScript source.
+    pub span: Range<usize>,
+}
+
+/// The full set of metadata files for one agent.
+///
+/// Serializes to JSON as `{"files": [{"path": ..., "contents": ...}]}`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct SalesforceMetadata {
+    /// Generated files, bundle first, then topics, then functions.
+    pub files: Vec<MetadataFile>,
+}
+
+impl SalesforceMetadata {
+    /// Get the contents of a generated file by its relative path.
+    pub fn file(&self, path: &str) -> Option<&str> {
+        self.files
+            .iter()
+            .find(|f| f.path == path)
+            .map(|f| f.contents.as_str())
+    }
+
+    /// Write every file under `dir` (e.g. `force-app/main/default`),
+    /// creating directories as needed.
+    pub fn write_to(&self, dir: impl AsRef<Path>) -> io::Result<()> {
+        let dir = dir.as_ref();
+        for file in &self.files {
+            let path = dir.join(&file.path);
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(path, &file.contents)?;
+        }
+        Ok(())
+    }
+
+    /// Map a location in a generated file back to an AgentScript span.
+    ///
+    /// Use this to translate deploy errors, which name the failing file and
+    /// usually a line. `file_name` may be relative to the package directory
+    /// or to any parent (e.g. `force-app/main/default/genAiPlugins/...`), and
+    /// may use the metadata API form without `-meta.xml`. Without a line,
+    /// the span of the whole definition is returned.
+    ///
+    /// ```rust
+    /// use busbar_sf_agentscript::export::salesforce::to_salesforce_metadata;
+    /// use busbar_sf_agentscript::parse;
+    ///
+    /// let source = "config:\n   agent_name: \"Agent\"\n\ntopic orders:\n   description: \"Orders\"\n";
+    /// let metadata = to_salesforce_metadata(&parse(source).unwrap());
+    ///
+    /// // <description> is on line 4 of the plugin file
+    /// let span = metadata
+    ///     .source_span("force-app/main/default/genAiPlugins/orders.genAiPlugin", Some(4))
+    ///     .unwrap();
+    /// assert_eq!(&source[span], "\"Orders\"");
+    /// ```
+    pub fn source_span(&self, file_name: &str, line: Option<usize>) -> Option<Range<usize>> {
+        let wanted = file_name.replace('\\', "/");
+        let wanted = wanted.trim_end_matches("-meta.xml");
+        let file = self.files.iter().find(|f| {
+            let path = f.path.trim_end_matches("-meta.xml");
+            wanted == path || wanted.ends_with(&format!("/{}", path))
+        })?;
+        let line = line.unwrap_or(1);
+        file.anchors
+            .iter()
+            .rev()
+            .find(|a| a.line <= line)
+            .or(file.anchors.first())
+            .map(|a| a.span.clone())
+    }
+
+    /// The AgentScript span of a metadata component, e.g. `("GenAiFunction",
+    /// "lookup")`, for deploy errors that name a component but no file.
+    pub fn component_span(&self, component_type: &str, full_name: &str) -> Option<Range<usize>> {
+        let path = match component_type {
+            "GenAiPlannerBundle" => {
+                format!("genAiPlannerBundles/{0}/{0}.genAiPlannerBundle", full_name)
+            }
+            "GenAiPlugin" => format!("genAiPlugins/{}.genAiPlugin-meta.xml", full_name),
+            "GenAiFunction" => format!("genAiFunctions/{0}/{0}.genAiFunction-meta.xml", full_name),
+            _ => return None,
+        };
+        self.source_span(&path, None)
+    }
+
+    fn push(&mut self, path: String, contents: String, anchors: Vec<SourceAnchor>) {
+        self.files.push(MetadataFile {
+            path,
+            contents,
+            anchors,
+        });
+    }
+
+    fn push_xml(&mut self, path: String, xml: XmlWriter) {
+        let anchors = xml.anchors.clone();
+        self.push(path, xml.finish(), anchors);
+    }
+}
+
+/// Convert an agent into Salesforce metadata with default options.
+pub fn to_salesforce_metadata(agent: &AgentFile) -> SalesforceMetadata {
+    to_salesforce_metadata_with(agent, &SalesforceOptions::default())
+}
+
+/// Convert an agent into Salesforce metadata.
+///
+/// Developer names are derived from AgentScript names, replacing characters
+/// Salesforce does not allow. When two topics define an action with the same
+/// name, the later one is exported as `<topic>_<action>`.
+pub fn to_salesforce_metadata_with(
+    agent: &AgentFile,
+    options: &SalesforceOptions,
+) -> SalesforceMetadata {
+    let mut plugin_files = SalesforceMetadata::default();
+    let mut function_names = HashSet::new();
+    let mut functions = Vec::new();
+
+    let planner_functions = agent
+        .start_agent
+        .as_ref()
+        .map(|sa| {
+            collect_functions(
+                &sa.node.name.node,
+                &sa.node.actions,
+                &mut function_names,
+                &mut functions,
+            )
+        })
+        .unwrap_or_default();
+
+    let mut plugins = Vec::new();
+    for topic in &agent.topics {
+        let t = &topic.node;
+        let name = developer_name(&t.name.node);
+        // A quoted name is the label as written, e.g. `topic "Claims – EU":`
+        let label = t.quoted_name.as_ref().unwrap_or(&t.name);
+        let function_refs =
+            collect_functions(&t.name.node, &t.actions, &mut function_names, &mut functions);
+
+        let mut xml = XmlWriter::new("GenAiPlugin", &topic.span);
+        xml.element("canEscalate", "false");
+        xml.anchor(t.description.as_ref().map_or(&t.name.span, |d| &d.span));
+        xml.element(
+            "description",
+            t.description
+                .as_ref()
+                .map(|d| d.node.as_str())
+                .unwrap_or(&label.node),
+        );
+        xml.anchor(&t.name.span);
+        xml.element("developerName", &name);
+        for (function, span) in &function_refs {
+            xml.anchor(span);
+            xml.open("genAiFunctions");
+            xml.element("functionName", function);
+            xml.close("genAiFunctions");
+        }
+        for (i, (text, span)) in plugin_instructions(&t.reasoning).iter().enumerate() {
+            xml.anchor(span);
+            xml.open("genAiPluginInstructions");
+            xml.element("description", text);
+            xml.element("developerName", &format!("instruction_{}", i));
+            xml.element("masterLabel", &format!("instruction_{}", i));
+            xml.close("genAiPluginInstructions");
+        }
+        xml.anchor(&t.name.span);
+        xml.element("language", &options.language);
+        xml.element("masterLabel", &label.node);
+        xml.element("pluginType", "Topic");
+        xml.element("scope", &plugin_scope(agent, topic));
+
+        plugin_files.push_xml(format!("genAiPlugins/{}.genAiPlugin-meta.xml", name), xml);
+        plugins.push((name, t.name.span.clone()));
+    }
+
+    let (agent_name, label, description) = match &agent.config {
+        Some(config) => {
+            let c = &config.node;
+            (
+                c.agent_name.node.as_str(),
+                c.agent_label
+                    .as_ref()
+                    .map(|l| l.node.as_str())
+                    .unwrap_or(&c.agent_name.node),
+                c.description.as_ref().map(|d| (d.node.as_str(), &d.span)),
+            )
+        }
+        None => ("Agent", "Agent", None),
+    };
+    let bundle_name = developer_name(agent_name);
+
+    let config_span = agent.config.as_ref().map(|c| c.span.clone());
+    let root_span = config_span
+        .clone()
+        .or_else(|| agent.start_agent.as_ref().map(|sa| sa.span.clone()))
+        .unwrap_or(0..0);
+    let mut xml = XmlWriter::new("GenAiPlannerBundle", &root_span);
+    if let Some((description, span)) = description {
+        xml.anchor(span);
+        xml.element("description", description);
+    }
+    for (function, span) in &planner_functions {
+        xml.anchor(span);
+        xml.open("genAiFunctions");
+        xml.element("genAiFunctionName", function);
+        xml.close("genAiFunctions");
+    }
+    for (plugin, span) in &plugins {
+        xml.anchor(span);
+        xml.open("genAiPlugins");
+        xml.element("genAiPluginName", plugin);
+        xml.close("genAiPlugins");
+    }
+    xml.anchor(&root_span);
+    xml.element("masterLabel", label);
+    xml.element("plannerType", &options.planner_type);
+
+    let mut bundle = SalesforceMetadata::default();
+    bundle.push_xml(format!("genAiPlannerBundles/{0}/{0}.genAiPlannerBundle", bundle_name), xml);
+    bundle.files.append(&mut plugin_files.files);
+
+    for (name, action) in functions {
+        write_function(&mut bundle, &name, action);
+    }
+    bundle
+}
+
+/// Assign developer names to a block's action definitions.
+fn collect_functions<'a>(
+    owner: &str,
+    actions: &'a Option<Spanned<ActionsBlock>>,
+    taken: &mut HashSet<String>,
+    out: &mut Vec<(String, &'a Spanned<ActionDef>)>,
+) -> Vec<(String, Range<usize>)> {
+    let Some(actions) = actions else {
+        return Vec::new();
+    };
+    actions
+        .node
+        .actions
+        .iter()
+        .map(|action| {
+            let a = &action.node;
+            let mut name = developer_name(&a.name.node);
+            if !taken.insert(name.clone()) {
+                name = developer_name(&format!("{}_{}", owner, a.name.node));
+                taken.insert(name.clone());
+            }
+            out.push((name.clone(), action));
+            (name, action.span.clone())
+        })
+        .collect()
+}
+
+/// Write a GenAiFunction and its input/output schemas.
+fn write_function(metadata: &mut SalesforceMetadata, name: &str, spanned: &Spanned<ActionDef>) {
+    let action = &spanned.node;
+    let (target_type, target) = invocation_target(
+        action
+            .target
+            .as_ref()
+            .map(|t| t.node.as_str())
+            .unwrap_or(""),
+    );
+
+    let mut xml = XmlWriter::new("GenAiFunction", &spanned.span);
+    xml.anchor(
+        action
+            .description
+            .as_ref()
+            .map_or(&action.name.span, |d| &d.span),
+    );
+    xml.element(
+        "description",
+        action
+            .description
+            .as_ref()
+            .map(|d| d.node.as_str())
+            .unwrap_or(&action.name.node),
+    );
+    xml.anchor(action.target.as_ref().map_or(&spanned.span, |t| &t.span));
+    xml.element("invocationTarget", target);
+    xml.element("invocationTargetType", target_type);
+    xml.anchor(&spanned.span);
+    xml.element("isConfirmationRequired", bool_str(action.require_user_confirmation.as_ref()));
+    if action
+        .include_in_progress_indicator
+        .as_ref()
+        .is_some_and(|i| i.node)
+    {
+        xml.element("isIncludeInProgressIndicator", "true");
+    }
+    xml.element(
+        "masterLabel",
+        action
+            .label
+            .as_ref()
+            .map(|l| l.node.as_str())
+            .unwrap_or(&action.name.node),
+    );
+    if let Some(message) = &action.progress_indicator_message {
+        xml.element("progressIndicatorMessage", &message.node);
+    }
+
+    let dir = format!("genAiFunctions/{}", name);
+    metadata.push_xml(format!("{}/{}.genAiFunction-meta.xml", dir, name), xml);
+    if let Some(inputs) = &action.inputs {
+        metadata.push(
+            format!("{}/input/schema.json", dir),
+            param_schema(&inputs.node, true),
+            vec![SourceAnchor {
+                line: 1,
+                span: inputs.span.clone(),
+            }],
+        );
+    }
+    if let Some(outputs) = &action.outputs {
+        metadata.push(
+            format!("{}/output/schema.json", dir),
+            param_schema(&outputs.node, false),
+            vec![SourceAnchor {
+                line: 1,
+                span: outputs.span.clone(),
+            }],
+        );
+    }
+}
+
+/// Split an action target like `flow://Name` into an invocation target type and name.
+fn invocation_target(target: &str) -> (&str, &str) {
+    let Some((scheme, name)) = target.split_once("://") else {
+        return ("standardInvocableAction", target);
+    };
+    let target_type = match scheme {
+        "flow" => "flow",
+        "apex" => "apex",
+        "prompt" => "generatePromptResponse",
+        "service" => "externalService",
+        "standard" => "standardInvocableAction",
+        _ => scheme,
+    };
+    (target_type, name)
+}
+
+/// The Lightning type JSON schema for action inputs or outputs.
+fn param_schema(params: &[Spanned<ParamDef>], input: bool) -> String {
+    let mut properties = Map::new();
+    let mut required = Vec::new();
+
+    for param in params {
+        let p = &param.node;
+        let mut prop = Map::new();
+        prop.insert(
+            "title".to_string(),
+            json!(p.label.as_ref().map(|l| &l.node).unwrap_or(&p.name.node)),
+        );
+        if let Some(description) = &p.description {
+            prop.insert("description".to_string(), json!(description.node));
+        }
+        match &p.complex_data_type_name {
+            Some(complex) => {
+                prop.insert("lightning:type".to_string(), json!(complex.node));
+            }
+            None => prop.extend(lightning_type(&p.ty.node)),
+        }
+        if input {
+            prop.insert("copilotAction:isUserInput".to_string(), json!(false));
+            if p.is_required.as_ref().is_some_and(|r| r.node) {
+                required.push(json!(p.name.node));
+            }
+        } else {
+            prop.insert(
+                "copilotAction:isDisplayable".to_string(),
+                json!(p.is_displayable.as_ref().is_some_and(|d| d.node)),
+            );
+            prop.insert(
+                "copilotAction:isUsedByPlanner".to_string(),
+                json!(!p.filter_from_agent.as_ref().is_some_and(|f| f.node)),
+            );
+        }
+        properties.insert(p.name.node.clone(), Value::Object(prop));
+    }
+
+    let mut schema = Map::new();
+    if input {
+        schema.insert("required".to_string(), Value::Array(required));
+    }
+    schema.insert("unevaluatedProperties".to_string(), json!(false));
+    schema.insert("properties".to_string(), Value::Object(properties));
+    schema.insert("lightning:type".to_string(), json!("lightning__objectType"));
+
+    let mut out =
+        serde_json::to_string_pretty(&Value::Object(schema)).expect("JSON values always serialize");
+    out.push('\n');
+    out
+}
+
+/// Schema properties describing an AgentScript type as a Lightning type.
+fn lightning_type(ty: &Type) -> Map<String, Value> {
+    let mut map = Map::new();
+    let name = match ty {
+        Type::List(inner) => {
+            map.insert("type".to_string(), json!("array"));
+            map.insert("items".to_string(), Value::Object(lightning_type(inner)));
+            return map;
+        }
+        Type::String | Type::Id => "lightning__textType",
+        Type::Number => "lightning__numberType",
+        Type::Integer | Type::Long => "lightning__integerType",
+        Type::Boolean => "lightning__booleanType",
+        Type::Object => "lightning__objectType",
+        Type::Date => "lightning__dateType",
+        Type::Datetime | Type::Timestamp => "lightning__dateTimeStringType",
+        Type::Time => "lightning__timeType",
+        Type::Currency => "lightning__currencyType",
+    };
+    map.insert("lightning:type".to_string(), json!(name));
+    map
+}
+
+/// A topic's reasoning instructions, one entry per instruction, with the
+/// span of its source.
+fn plugin_instructions(reasoning: &Option<Spanned<ReasoningBlock>>) -> Vec<(String, Range<usize>)> {
+    let Some(instructions) = reasoning
+        .as_ref()
+        .and_then(|r| r.node.instructions.as_ref())
+    else {
+        return Vec::new();
+    };
+    let text = match &instructions.node {
+        Instructions::Simple(text) => text.clone(),
+        Instructions::Static(lines) => lines
+            .iter()
+            .map(|l| l.node.as_str())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Instructions::Dynamic(parts) => {
+            let mut out = String::new();
+            flatten_parts(parts, &mut out);
+            out
+        }
+    };
+    let text = text.trim();
+    if text.is_empty() {
+        Vec::new()
+    } else {
+        vec![(text.to_string(), instructions.span.clone())]
+    }
+}
+
+fn flatten_parts(parts: &[Spanned<InstructionPart>], out: &mut String) {
+    for part in parts {
+        match &part.node {
+            InstructionPart::Text(text) => {
+                out.push_str(text);
+                out.push('\n');
+            }
+            InstructionPart::Heading { level, title } => {
+                writeln!(out, "{} {}", "#".repeat(*level), title).unwrap();
+            }
+            InstructionPart::Interpolation(expr) => {
+                write!(out, "{{!{}}}", serialize_expr(expr)).unwrap();
+            }
+            InstructionPart::Conditional {
+                condition,
+                then_parts,
+                else_parts,
+            } => {
+                writeln!(out, "If {}:", serialize_expr(&condition.node)).unwrap();
+                flatten_parts(then_parts, out);
+                if let Some(else_parts) = else_parts {
+                    writeln!(out, "Otherwise:").unwrap();
+                    flatten_parts(else_parts, out);
+                }
+            }
+        }
+    }
+}
+
+/// A topic's scope: its system instructions override, falling back to the
+/// agent's system instructions, then its description.
+fn plugin_scope(agent: &AgentFile, topic: &Spanned<TopicBlock>) -> String {
+    let instructions = topic
+        .node
+        .system
+        .as_ref()
+        .and_then(|s| s.node.instructions.as_ref())
+        .or_else(|| {
+            agent
+                .system
+                .as_ref()
+                .and_then(|s| s.node.instructions.as_ref())
+        });
+    match instructions.map(|i| &i.node) {
+        Some(Instructions::Simple(text)) => text.clone(),
+        Some(Instructions::Static(lines)) => lines
+            .iter()
+            .map(|l| l.node.as_str())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Some(Instructions::Dynamic(parts)) => {
+            let mut out = String::new();
+            flatten_parts(parts, &mut out);
+            out.trim_end().to_string()
+        }
+        None => topic
+            .node
+            .description
+            .as_ref()
+            .map(|d| d.node.clone())
+            .unwrap_or_default(),
+    }
+}
+
+/// Convert a name into a valid Salesforce developer name: letters, digits,
+/// and single underscores, starting with a letter, at most 80 characters.
+fn developer_name(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    for c in name.chars() {
+        let c = if c.is_ascii_alphanumeric() { c } else { '_' };
+        if c == '_' && (out.is_empty() || out.ends_with('_')) {
+            continue;
+        }
+        out.push(c);
+    }
+    while out.ends_with('_') {
+        out.pop();
+    }
+    if !out.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        out.insert(0, 'X');
+    }
+    out.truncate(80);
+    out
+}
+
+fn bool_str(value: Option<&Spanned<bool>>) -> &'static str {
+    if value.is_some_and(|v| v.node) {
+        "true"
+    } else {
+        "false"
+    }
+}
+
+/// Minimal writer for metadata XML documents.
+struct XmlWriter {
+    root: &'static str,
+    output: String,
+    depth: usize,
+    anchors: Vec<SourceAnchor>,
+}
+
+impl XmlWriter {
+    /// Start a document generated from the definition at `span`.
+    fn new(root: &'static str, span: &Range<usize>) -> Self {
+        let mut output = String::new();
+        writeln!(output, r#"<?xml version="1.0" encoding="UTF-8"?>"#).unwrap();
+        writeln!(output, r#"<{} xmlns="http://soap.sforce.com/2006/04/metadata">"#, root).unwrap();
+        Self {
+            root,
+            output,
+            depth: 1,
+            anchors: vec![SourceAnchor {
+                line: 1,
+                span: span.clone(),
+            }],
+        }
+    }
+
+    /// Attribute the lines written next to `span`.
+    fn anchor(&mut self, span: &Range<usize>) {
+        let line = self.output.matches('\n').count() + 1;
+        self.anchors.push(SourceAnchor {
+            line,
+            span: span.clone(),
+        });
+    }
+
+    fn indent(&mut self) {
+        for _ in 0..self.depth {
+            self.output.push_str("    ");
+        }
+    }
+
+    fn open(&mut self, tag: &str) {
+        self.indent();
+        writeln!(self.output, "<{}>", tag).unwrap();
+        self.depth += 1;
+    }
+
+    fn close(&mut self, tag: &str) {
+        self.depth -= 1;
+        self.indent();
+        writeln!(self.output, "</{}>", tag).unwrap();
+    }
+
+    fn element(&mut self, tag: &str, text: &str) {
+        self.indent();
+        writeln!(self.output, "<{0}>{1}</{0}>", tag, escape_xml(text)).unwrap();
+    }
+
+    fn finish(mut self) -> String {
+        writeln!(self.output, "</{}>", self.root).unwrap();
+        self.output
+    }
+}
+
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = r#"config:
+   agent_name: "Support Agent"
+   agent_label: "Support"
+   description: "Helps customers"
+
+start_agent selector:
+   description: "Route"
+   reasoning:
+      instructions: "Select a topic"
+      actions:
+         go_orders: @utils.transition to @topic.orders
+            description: "Orders"
+
+topic orders:
+   description: "Order questions & returns"
+
+   actions:
+      lookup:
+         description: "Look up an order"
+         inputs:
+            order_id: string
+               description: "Order ID"
+               is_required: True
+         outputs:
+            status: string
+               is_displayable: True
+         target: "flow://Lookup_Order"
+
+   reasoning:
+      instructions: "Help with orders"
+
+topic billing:
+   description: "Billing"
+
+   actions:
+      lookup:
+         description: "Look up an invoice"
+         target: "apex://InvoiceService"
+"#;
+
+    fn export() -> SalesforceMetadata {
+        to_salesforce_metadata(&crate::parse(SOURCE).expect("Failed to parse"))
+    }
+
+    #[test]
+    fn test_bundle_lists_plugins() {
+        let metadata = export();
+        assert_eq!(
+            metadata.files[0].path,
+            "genAiPlannerBundles/Support_Agent/Support_Agent.genAiPlannerBundle"
+        );
+        let bundle = &metadata.files[0].contents;
+        assert!(bundle.contains("<genAiPluginName>orders</genAiPluginName>"));
+        assert!(bundle.contains("<genAiPluginName>billing</genAiPluginName>"));
+        assert!(bundle.contains("<masterLabel>Support</masterLabel>"));
+    }
+
+    #[test]
+    fn test_plugin_escapes_and_references_functions() {
+        let metadata = export();
+        let plugin = metadata
+            .file("genAiPlugins/orders.genAiPlugin-meta.xml")
+            .unwrap();
+        assert!(plugin.contains("<description>Order questions &amp; returns</description>"));
+        assert!(plugin.contains("<functionName>lookup</functionName>"));
+        assert!(plugin.contains("<description>Help with orders</description>"));
+
+        // Same action name in a second topic gets a qualified name
+        let billing = metadata
+            .file("genAiPlugins/billing.genAiPlugin-meta.xml")
+            .unwrap();
+        assert!(billing.contains("<functionName>billing_lookup</functionName>"));
+        let function = metadata
+            .file("genAiFunctions/billing_lookup/billing_lookup.genAiFunction-meta.xml")
+            .unwrap();
+        assert!(function.contains("<invocationTargetType>apex</invocationTargetType>"));
+    }
+
+    #[test]
+    fn test_quoted_topic_name_is_label() {
+        let agent = crate::parse("topic \"Claims – EU\":\n   description: \"EU claims\"\n")
+            .expect("Failed to parse");
+        let metadata = to_salesforce_metadata(&agent);
+        let plugin = metadata
+            .file("genAiPlugins/Claims_EU.genAiPlugin-meta.xml")
+            .unwrap();
+        assert!(plugin.contains("<developerName>Claims_EU</developerName>"));
+        assert!(plugin.contains("<masterLabel>Claims – EU</masterLabel>"));
+    }
+
+    #[test]
+    fn test_function_schemas() {
+        let metadata = export();
+        let input: Value = serde_json::from_str(
+            metadata
+                .file("genAiFunctions/lookup/input/schema.json")
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(input["required"], json!(["order_id"]));
+        assert_eq!(input["properties"]["order_id"]["lightning:type"], "lightning__textType");
+
+        let output: Value = serde_json::from_str(
+            metadata
+                .file("genAiFunctions/lookup/output/schema.json")
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(output["properties"]["status"]["copilotAction:isDisplayable"], true);
+    }
+
+    #[test]
+    fn test_deploy_error_maps_to_source_span() {
+        let metadata = export();
+        let function = "genAiFunctions/lookup/lookup.genAiFunction-meta.xml";
+        let contents = metadata.file(function).unwrap();
+        let line = |tag: &str| contents.lines().position(|l| l.contains(tag)).unwrap() + 1;
+
+        let span = metadata
+            .source_span(function, Some(line("<invocationTarget>")))
+            .unwrap();
+        assert_eq!(&SOURCE[span], "\"flow://Lookup_Order\"");
+        let span = metadata
+            .source_span(
+                "force-app/main/default/genAiFunctions/lookup/lookup.genAiFunction",
+                Some(line("<description>")),
+            )
+            .unwrap();
+        assert_eq!(&SOURCE[span], "\"Look up an order\"");
+
+        let whole = metadata.component_span("GenAiPlugin", "orders").unwrap();
+        assert!(SOURCE[whole].starts_with("topic orders:"));
+        let schema = metadata
+            .source_span("genAiFunctions/lookup/input/schema.json", Some(3))
+            .unwrap();
+        assert!(SOURCE[schema].contains("order_id: string"));
+        assert_eq!(metadata.source_span("genAiPlugins/missing.genAiPlugin", None), None);
+    }
+
+    #[test]
+    fn test_developer_name() {
+        assert_eq!(developer_name("Support Agent"), "Support_Agent");
+        assert_eq!(developer_name("a--b__c_"), "a_b_c");
+        assert_eq!(developer_name("1st"), "X1st");
+    }
+}
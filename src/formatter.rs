@@ -0,0 +1,556 @@
+//! Editor formatting built on the serializer.
+//!
+//! [`serialize`](crate::serialize) rewrites a whole file: it drops comments
+//! other than doc comments and puts blocks in standard order. The
+//! functions here make smaller edits instead:
+//!
+//! - [`format_range`] reformats only the top-level blocks overlapping a
+//!   range, in place. Blocks holding comments the serializer would drop, and
+//!   blocks that failed to parse, are left as written.
+//! - [`format_on_type`] indents the line started after a line ending in `:`
+//!   or `->`.
+//!
+//! [`format_check`] verifies that formatting a file is safe: that the
+//! formatted source parses back to the same AST, and that formatting it again
+//! changes nothing. [`format_file`] makes the same checks on the whole file
+//! formatted by [`format_range`], so it keeps every comment.
+//!
+//! # Example
+//!
+//! ```rust
+//! use busbar_sf_agentscript::formatter::format_range;
+//! use busbar_sf_agentscript::parse;
+//!
+//! let source = "topic main:\n    description:   \"Main\"\n\n# Keep me\ntopic other:\n  description: \"Other\"\n";
+//! let ast = parse(source).unwrap();
+//!
+//! // Only the first topic overlaps the range
+//! let edits = format_range(source, &ast, 0..5);
+//! assert_eq!(edits.len(), 1);
+//! assert_eq!(edits[0].span, 0..38);
+//! assert_eq!(edits[0].new_text, "topic main:\n   description: \"Main\"\n");
+//! ```
+
+use crate::ast::{AgentFile, Spanned};
+use crate::edit::{apply_edits, SourceEdit};
+use crate::lexer::{self, Token};
+use crate::serializer::{serialize_with_options, ClauseOrder, SerializeOptions};
+use crate::validation::{SemanticError, Severity};
+use serde::Serialize;
+use serde_json::Value;
+use std::ops::Range;
+
+/// A replacement of the text at `span` with formatted text.
+pub type FormatEdit = SourceEdit;
+
+/// The outcome of [`format_check`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FormatResult {
+    /// The formatted source.
+    pub formatted: String,
+    /// Whether formatting `formatted` again leaves it unchanged.
+    pub is_idempotent: bool,
+    /// Whether `formatted` parses to the same AST as the original source,
+    /// ignoring spans.
+    pub roundtrip_ok: bool,
+    /// Path of the first AST node that differs after the roundtrip, such as
+    /// `topics[0].node.reasoning`.
+    pub difference: Option<String>,
+}
+
+impl FormatResult {
+    /// Whether the formatted source can safely replace the original.
+    pub fn is_stable(&self) -> bool {
+        self.is_idempotent && self.roundtrip_ok
+    }
+
+    /// An error describing why formatting is unstable, if it is.
+    pub fn diagnostic(&self) -> Option<SemanticError> {
+        let message = if !self.roundtrip_ok {
+            match &self.difference {
+                Some(path) => format!("Formatting changes the meaning of the file at '{}'", path),
+                None => "Formatted source does not parse".to_string(),
+            }
+        } else if !self.is_idempotent {
+            "Formatting the formatted source changes it again".to_string()
+        } else {
+            return None;
+        };
+        Some(SemanticError {
+            message,
+            span: None,
+            severity: Severity::Error,
+            hint: Some("This is a formatter bug; please report it with the file".to_string()),
+            code: Some("format-unstable"),
+            related: Vec::new(),
+        })
+    }
+}
+
+/// Format `source` and verify the result.
+///
+/// Parses `source`, serializes it, then re-parses and re-serializes the
+/// output. Fails with the parse errors if `source` doesn't parse.
+///
+/// ```rust
+/// use busbar_sf_agentscript::formatter::format_check;
+///
+/// let result = format_check("topic main:\n    description:   \"Main\"\n").unwrap();
+/// assert_eq!(result.formatted.trim_end(), "topic main:\n   description: \"Main\"");
+/// assert!(result.is_idempotent && result.roundtrip_ok);
+/// assert!(result.diagnostic().is_none());
+/// ```
+pub fn format_check(source: &str) -> Result<FormatResult, Vec<String>> {
+    format_check_with_options(source, &SerializeOptions::default())
+}
+
+/// [`format_check`] with serializer options.
+///
+/// With [`ClauseOrder::Canonical`], reordered reasoning action clauses
+/// still count as the same AST.
+pub fn format_check_with_options(
+    source: &str,
+    options: &SerializeOptions,
+) -> Result<FormatResult, Vec<String>> {
+    let mut ast = crate::parse(source)?;
+    let formatted = serialize_with_options(&ast, options);
+    let Ok(reparsed) = crate::parse(&formatted) else {
+        return Ok(FormatResult {
+            formatted,
+            is_idempotent: false,
+            roundtrip_ok: false,
+            difference: None,
+        });
+    };
+
+    if options.clause_order == ClauseOrder::Canonical {
+        canonicalize_clause_orders(&mut ast);
+    }
+    let roundtrip_ok = ast.eq_ignore_spans(&reparsed);
+    let difference = if roundtrip_ok {
+        None
+    } else {
+        first_difference(&spanless_json(&ast), &spanless_json(&reparsed), String::new())
+    };
+    Ok(FormatResult {
+        is_idempotent: serialize_with_options(&reparsed, options) == formatted,
+        roundtrip_ok,
+        difference,
+        formatted,
+    })
+}
+
+/// Format every top-level block of `source` with [`format_range`] and
+/// verify the result as [`format_check`] does.
+///
+/// Unlike [`format_check`], comments and the text between blocks are kept:
+/// blocks holding comments the serializer would drop are left as written.
+///
+/// ```rust
+/// use busbar_sf_agentscript::formatter::format_file;
+///
+/// let source = "# Owned by support\ntopic main:\n    description:   \"Main\"\n";
+/// let result = format_file(source).unwrap();
+/// assert_eq!(result.formatted, "# Owned by support\ntopic main:\n   description: \"Main\"\n");
+/// assert!(result.is_stable());
+/// ```
+pub fn format_file(source: &str) -> Result<FormatResult, Vec<String>> {
+    format_file_with_options(source, &SerializeOptions::default())
+}
+
+/// [`format_file`] with serializer options.
+pub fn format_file_with_options(
+    source: &str,
+    options: &SerializeOptions,
+) -> Result<FormatResult, Vec<String>> {
+    let mut ast = crate::parse(source)?;
+    let edits = format_range_with_options(source, &ast, 0..source.len(), options);
+    let formatted = apply_edits(source, edits).map_err(|e| vec![e.to_string()])?;
+    let Ok(reparsed) = crate::parse(&formatted) else {
+        return Ok(FormatResult {
+            formatted,
+            is_idempotent: false,
+            roundtrip_ok: false,
+            difference: None,
+        });
+    };
+
+    if options.clause_order == ClauseOrder::Canonical {
+        canonicalize_clause_orders(&mut ast);
+    }
+    let roundtrip_ok = ast.eq_ignore_spans(&reparsed);
+    let difference = if roundtrip_ok {
+        None
+    } else {
+        first_difference(&spanless_json(&ast), &spanless_json(&reparsed), String::new())
+    };
+    Ok(FormatResult {
+        is_idempotent: format_range_with_options(
+            &formatted,
+            &reparsed,
+            0..formatted.len(),
+            options,
+        )
+        .is_empty(),
+        roundtrip_ok,
+        difference,
+        formatted,
+    })
+}
+
+/// Reformat the top-level blocks of `ast` that overlap `range`.
+///
+/// `ast` must have been parsed from `source`. A block overlaps `range` if
+/// any of its lines, up to its last non-blank line, does; an empty range
+/// selects the block around it. Edits are in source order and don't
+/// overlap. Blocks that are already formatted get no edit.
+pub fn format_range(source: &str, ast: &AgentFile, range: Range<usize>) -> Vec<FormatEdit> {
+    format_range_with_options(source, ast, range, &SerializeOptions::default())
+}
+
+/// [`format_range`] with serializer options.
+pub fn format_range_with_options(
+    source: &str,
+    ast: &AgentFile,
+    range: Range<usize>,
+    options: &SerializeOptions,
+) -> Vec<FormatEdit> {
+    let comments: Vec<(Range<usize>, &str)> = match lexer::lex_with_indentation(source) {
+        Ok(tokens) => tokens
+            .into_iter()
+            .filter_map(|(token, span)| match token {
+                Token::Comment(text) => Some((span.start..span.end, text)),
+                _ => None,
+            })
+            .collect(),
+        Err(_) => return Vec::new(),
+    };
+
+    let mut edits: Vec<FormatEdit> = top_level_blocks(ast)
+        .into_iter()
+        .filter_map(|(span, block)| {
+            let span = span.start..content_end(source, span);
+            if span.start > range.end || range.start > span.end {
+                return None;
+            }
+            let formatted = serialize_with_options(&block, options);
+            let formatted = format!("{}\n", formatted.trim_end());
+            // Comments the serializer dropped would be lost
+            let kept = comments
+                .iter()
+                .filter(|(c, _)| span.start <= c.start && c.end <= span.end)
+                .all(|(_, text)| {
+                    let text = text.trim();
+                    formatted
+                        .lines()
+                        .any(|line| line.trim().strip_prefix('#').map(str::trim) == Some(text))
+                });
+            (kept && source[span.clone()] != formatted).then_some(FormatEdit {
+                span,
+                new_text: formatted,
+            })
+        })
+        .collect();
+    edits.sort_by_key(|e| e.span.start);
+    edits
+}
+
+/// Indent the line started by typing `ch` at `offset`.
+///
+/// After a newline typed at the end of a line ending in `:` or `->`, the new
+/// line is indented one level deeper than that line, using the file's
+/// indentation width (three spaces if it has no indented lines). `offset` is
+/// the cursor position after the typed character.
+///
+/// ```rust
+/// use busbar_sf_agentscript::formatter::format_on_type;
+///
+/// let source = "topic main:\n   reasoning:\n";
+/// let edit = format_on_type(source, source.len(), '\n').unwrap();
+/// assert_eq!(edit.span, source.len()..source.len());
+/// assert_eq!(edit.new_text, "      ");
+/// ```
+pub fn format_on_type(source: &str, offset: usize, ch: char) -> Option<FormatEdit> {
+    if ch != '\n' || !source.is_char_boundary(offset) {
+        return None;
+    }
+    let line_start = source[..offset].rfind('\n')? + 1;
+    let prev_start = source[..line_start - 1].rfind('\n').map_or(0, |i| i + 1);
+    let prev = &source[prev_start..line_start - 1];
+    let prev = prev.trim_end();
+    if !prev.ends_with(':') && !prev.ends_with("->") {
+        return None;
+    }
+
+    let indent = indent_width(prev) + indent_unit(source);
+    let line_end = source[line_start..]
+        .find('\n')
+        .map_or(source.len(), |i| line_start + i);
+    let current = &source[line_start..line_end];
+    let current = line_start..line_start + indent_width(current);
+    let new_text = " ".repeat(indent);
+    (source[current.clone()] != new_text).then_some(FormatEdit {
+        span: current,
+        new_text,
+    })
+}
+
+/// Every top-level block with its span, each as a file of its own.
+///
+/// Doc comments above blocks are outside their spans, so they are left off.
+fn top_level_blocks(ast: &AgentFile) -> Vec<(Range<usize>, AgentFile)> {
+    fn each<T: Clone>(
+        blocks: &[Spanned<T>],
+        out: &mut Vec<(Range<usize>, AgentFile)>,
+        file: impl Fn(Spanned<T>) -> AgentFile,
+    ) {
+        out.extend(blocks.iter().map(|b| (b.span.clone(), file(b.clone()))));
+    }
+
+    let mut out = Vec::new();
+    let config = ast.config.iter().chain(&ast.duplicate_configs);
+    each(&config.cloned().collect::<Vec<_>>(), &mut out, |b| AgentFile {
+        config: Some(b),
+        ..AgentFile::default()
+    });
+    let variables = ast.variables.iter().chain(&ast.duplicate_variables);
+    each(&variables.cloned().collect::<Vec<_>>(), &mut out, |b| AgentFile {
+        variables: Some(b),
+        ..AgentFile::default()
+    });
+    let system = ast.system.iter().chain(&ast.duplicate_systems);
+    each(&system.cloned().collect::<Vec<_>>(), &mut out, |b| AgentFile {
+        system: Some(b),
+        ..AgentFile::default()
+    });
+    each(&ast.connections, &mut out, |b| AgentFile {
+        connections: vec![b],
+        ..AgentFile::default()
+    });
+    each(ast.knowledge.as_slice(), &mut out, |b| AgentFile {
+        knowledge: Some(b),
+        ..AgentFile::default()
+    });
+    each(ast.language.as_slice(), &mut out, |b| AgentFile {
+        language: Some(b),
+        ..AgentFile::default()
+    });
+    let start_agents = ast.start_agent.iter().chain(&ast.duplicate_start_agents);
+    each(&start_agents.cloned().collect::<Vec<_>>(), &mut out, |mut b| {
+        b.node.doc = None;
+        AgentFile {
+            start_agent: Some(b),
+            ..AgentFile::default()
+        }
+    });
+    each(&ast.topics, &mut out, |mut b| {
+        b.node.doc = None;
+        AgentFile {
+            topics: vec![b],
+            ..AgentFile::default()
+        }
+    });
+    out
+}
+
+/// End of the last line in `span` that is neither blank nor an unindented
+/// comment. Block spans run up to the next block, so they also cover the
+/// blank lines and doc comments in between.
+fn content_end(source: &str, span: Range<usize>) -> usize {
+    let text = &source[span.clone()];
+    let mut end = span.start;
+    let mut line_start = 0;
+    for line in text.split_inclusive('\n') {
+        if !line.trim().is_empty() && !line.starts_with('#') {
+            end = span.start + line_start + line.len();
+        }
+        line_start += line.len();
+    }
+    end
+}
+
+fn canonicalize_clause_orders(ast: &mut AgentFile) {
+    let reasoning = ast
+        .start_agent
+        .iter_mut()
+        .filter_map(|sa| sa.node.reasoning.as_mut())
+        .chain(
+            ast.topics
+                .iter_mut()
+                .filter_map(|t| t.node.reasoning.as_mut()),
+        );
+    let actions = reasoning.filter_map(|r| r.node.actions.as_mut());
+    for action in actions.flat_map(|a| a.node.iter_mut()) {
+        action.node.canonicalize_clause_order();
+    }
+}
+
+/// `ast` as JSON with every span removed.
+fn spanless_json(ast: &AgentFile) -> Value {
+    fn strip(value: &mut Value) {
+        match value {
+            Value::Object(map) => {
+                map.remove("span");
+                map.values_mut().for_each(strip);
+            }
+            Value::Array(items) => items.iter_mut().for_each(strip),
+            _ => {}
+        }
+    }
+    let mut json = serde_json::to_value(ast).expect("AST always serializes to JSON");
+    strip(&mut json);
+    json
+}
+
+/// Path of the first place `a` and `b` differ.
+fn first_difference(a: &Value, b: &Value, path: String) -> Option<String> {
+    let join = |key: &str| {
+        if path.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", path, key)
+        }
+    };
+    match (a, b) {
+        (Value::Object(a), Value::Object(b)) => a
+            .iter()
+            .find_map(|(key, value)| match b.get(key) {
+                Some(other) => first_difference(value, other, join(key)),
+                None => Some(join(key)),
+            })
+            .or_else(|| {
+                b.keys()
+                    .find(|key| !a.contains_key(*key))
+                    .map(|key| join(key))
+            }),
+        (Value::Array(a), Value::Array(b)) => a
+            .iter()
+            .zip(b)
+            .enumerate()
+            .find_map(|(i, (a, b))| first_difference(a, b, format!("{}[{}]", path, i)))
+            .or_else(|| {
+                (a.len() != b.len()).then(|| format!("{}[{}]", path, a.len().min(b.len())))
+            }),
+        _ => (a != b).then_some(path),
+    }
+}
+
+/// Number of leading spaces on `line`.
+fn indent_width(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+/// Indentation width of the first indented line in `source`.
+fn indent_unit(source: &str) -> usize {
+    source
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(indent_width)
+        .find(|&width| width > 0)
+        .unwrap_or(3)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_range_keeps_comments_and_order() {
+        let source = r#"topic b:
+    description:   "B"
+
+# Config comment
+config:
+   agent_name:  "Test"
+
+topic a:
+   description: "A"  # keep me
+"#;
+        let ast = crate::parse(source).unwrap();
+        let edits = format_range(source, &ast, 0..source.len());
+        // `topic a` has a comment the serializer would drop
+        assert_eq!(
+            edits,
+            [
+                FormatEdit {
+                    span: 0..32,
+                    new_text: "topic b:\n   description: \"B\"\n".to_string(),
+                },
+                FormatEdit {
+                    span: 50..81,
+                    new_text: "config:\n   agent_name: \"Test\"\n".to_string(),
+                },
+            ]
+        );
+
+        // A cursor in the gap between blocks selects nothing
+        assert!(format_range(source, &ast, 33..33).is_empty());
+    }
+
+    #[test]
+    fn test_format_range_keeps_doc_comments() {
+        let source = "# Main topic\ntopic main:\n   actions:\n      # Look it up\n      lookup:\n        description: \"Look up\"\n        target: \"flow://Lookup\"\n";
+        let ast = crate::parse(source).unwrap();
+        let edits = format_range(source, &ast, 20..20);
+        assert_eq!(edits.len(), 1);
+        assert_eq!(edits[0].span, 13..source.len());
+        assert!(edits[0].new_text.starts_with("topic main:\n"));
+        assert!(edits[0]
+            .new_text
+            .contains("      # Look it up\n      lookup:\n"));
+    }
+
+    #[test]
+    fn test_format_on_type() {
+        let source = "topic main:\n    reasoning:\n        instructions: ->\n  \n";
+        let offset = source.len() - 3;
+        let edit = format_on_type(source, offset, '\n').unwrap();
+        assert_eq!(edit.span, offset..offset + 2);
+        assert_eq!(edit.new_text, " ".repeat(12));
+
+        let source = "topic main:\n   description: \"Main\"\n";
+        assert_eq!(format_on_type(source, source.len(), '\n'), None);
+        assert_eq!(format_on_type("topic main:\n   ", 15, '\n'), None);
+        assert_eq!(format_on_type("topic main:\n", 12, ':'), None);
+    }
+
+    #[test]
+    fn test_format_check() {
+        let source = "topic main:\n   reasoning:\n      actions:\n         go: @actions.go\n            set @variables.x = @outputs.x\n            with id = ...\n";
+        let result = format_check(source).unwrap();
+        assert!(result.is_stable());
+        let source_order = result.formatted;
+
+        let options = SerializeOptions {
+            clause_order: ClauseOrder::Canonical,
+        };
+        let result = format_check_with_options(source, &options).unwrap();
+        assert!(result.is_stable(), "{:?}", result.difference);
+        assert_ne!(result.formatted, source_order);
+
+        assert!(format_check("topic main:\n   bogus").is_err());
+    }
+
+    #[test]
+    fn test_format_check_reports_difference() {
+        let a = spanless_json(&crate::parse("topic a:\n   description: \"A\"\n").unwrap());
+        let b = spanless_json(&crate::parse("topic a:\n   description: \"B\"\n").unwrap());
+        assert_eq!(
+            first_difference(&a, &b, String::new()).as_deref(),
+            Some("topics[0].node.description.node")
+        );
+
+        let result = FormatResult {
+            formatted: String::new(),
+            is_idempotent: true,
+            roundtrip_ok: false,
+            difference: Some("topics[0].node.description.node".to_string()),
+        };
+        let diagnostic = result.diagnostic().unwrap();
+        assert_eq!(diagnostic.code, Some("format-unstable"));
+        assert_eq!(
+            diagnostic.message,
+            "Formatting changes the meaning of the file at 'topics[0].node.description.node'"
+        );
+    }
+}
@@ -0,0 +1,811 @@
+//! Machine-readable description of the AgentScript grammar.
+//!
+//! The grammar is kept here as data, next to the parser in [`crate::parser`],
+//! and rendered on demand:
+//!
+//! - [`export_ebnf()`] - W3C-style EBNF text for documentation
+//! - [`export_railroad()`] - JSON trees for railroad-diagram renderers
+//!
+//! Type names are taken from the [keyword table](crate::lexer::keyword_table)
+//! so the two can't disagree. When changing a parser, update the matching
+//! rule here.
+//!
+//! # Notation
+//!
+//! Quoted strings are literal tokens. Upper-case names are token classes
+//! produced by the lexer:
+//!
+//! | Name | Meaning |
+//! |------|---------|
+//! | `IDENT` | Identifier |
+//! | `STRING` | Double-quoted string literal |
+//! | `NUMBER` | Numeric literal |
+//! | `DATE` | Date literal, e.g. `date(2024-01-31)` |
+//! | `CURRENCY` | Amount in the default currency, e.g. `$199.99` |
+//! | `TEXT` | Any tokens up to the end of the line |
+//! | `HEADING` | `##` or more and a title, up to the end of the line |
+//! | `NEWLINE` | End of line |
+//! | `INDENT` / `DEDENT` | Increase / decrease of indentation |
+//!
+//! Entries inside an indented block are separated by newlines; comments and
+//! blank lines may appear anywhere between them.
+//!
+//! # Example
+//!
+//! ```rust
+//! use busbar_sf_agentscript::grammar::export_ebnf;
+//!
+//! let ebnf = export_ebnf();
+//! assert!(ebnf.starts_with("agent_file ::="));
+//! ```
+
+use crate::lexer::{keyword_table, KeywordCategory};
+use serde::Serialize;
+use std::fmt::Write;
+
+/// A node in a grammar rule's right-hand side.
+///
+/// The serialized form (`{"type": "Sequence", "items": [...]}`) matches the
+/// constructors of the common railroad-diagram libraries.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type")]
+pub enum Node {
+    /// A literal token, e.g. `'topic'`.
+    Terminal { text: String },
+    /// A reference to another rule.
+    NonTerminal { text: String },
+    /// A token class such as `IDENT` or `INDENT`.
+    Special { text: String },
+    /// Items in order.
+    Sequence { items: Vec<Node> },
+    /// Exactly one of the items.
+    Choice { items: Vec<Node> },
+    /// Zero or one.
+    Optional { item: Box<Node> },
+    /// Zero or more.
+    ZeroOrMore { item: Box<Node> },
+    /// One or more.
+    OneOrMore { item: Box<Node> },
+}
+
+/// A named production.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Rule {
+    /// Rule name, e.g. `"topic_block"`.
+    pub name: &'static str,
+    /// One-line description.
+    pub description: &'static str,
+    /// Right-hand side.
+    pub diagram: Node,
+}
+
+/// All grammar rules, starting with `agent_file`.
+pub fn rules() -> Vec<Rule> {
+    let types: Vec<Node> = keyword_table()
+        .iter()
+        .filter(|k| k.category == KeywordCategory::Type && k.text != "list")
+        .map(|k| t(k.text))
+        .collect();
+
+    vec![
+        // ── File structure ──────────────────────────────────────────────
+        rule("agent_file", "A complete .agent file", many(n("top_level_block"))),
+        rule(
+            "top_level_block",
+            "Any top-level block",
+            choice([
+                n("config_block"),
+                n("variables_block"),
+                n("system_block"),
+                n("start_agent_block"),
+                n("topic_block"),
+                n("knowledge_block"),
+                n("language_block"),
+                n("connection_block"),
+                n("connections_block"),
+            ]),
+        ),
+        rule(
+            "config_block",
+            "Agent configuration",
+            seq([t("config"), t(":"), block(n("config_entry"))]),
+        ),
+        rule(
+            "config_entry",
+            "Configuration field, e.g. agent_name",
+            seq([sp("IDENT"), t(":"), sp("STRING")]),
+        ),
+        rule(
+            "variables_block",
+            "Variable declarations",
+            seq([t("variables"), t(":"), block(n("variable_decl"))]),
+        ),
+        rule(
+            "variable_decl",
+            "A single variable",
+            seq([
+                sp("IDENT"),
+                t(":"),
+                choice([t("mutable"), t("linked")]),
+                n("type"),
+                opt(seq([t("="), n("expr")])),
+                opt(block(n("variable_entry"))),
+            ]),
+        ),
+        rule(
+            "variable_entry",
+            "Variable property",
+            choice([
+                n("description_entry"),
+                seq([t("source"), t(":"), n("reference")]),
+            ]),
+        ),
+        rule(
+            "system_block",
+            "Global system instructions and messages",
+            seq([t("system"), t(":"), block(n("system_entry"))]),
+        ),
+        rule(
+            "system_entry",
+            "System block entry",
+            choice([
+                seq([t("messages"), t(":"), block(n("message_entry"))]),
+                seq([t("instructions"), t(":"), sp("STRING")]),
+            ]),
+        ),
+        rule(
+            "message_entry",
+            "Built-in message text",
+            seq([choice([t("welcome"), t("error")]), t(":"), sp("STRING")]),
+        ),
+        rule(
+            "start_agent_block",
+            "Entry point of the agent",
+            seq([
+                t("start_agent"),
+                sp("IDENT"),
+                t(":"),
+                block(n("topic_entry")),
+            ]),
+        ),
+        rule(
+            "topic_block",
+            "A conversation topic",
+            seq([
+                t("topic"),
+                choice([sp("IDENT"), sp("STRING")]),
+                t(":"),
+                block(n("topic_entry")),
+            ]),
+        ),
+        rule(
+            "topic_entry",
+            "Topic or start_agent entry",
+            choice([
+                n("description_entry"),
+                seq([t("label"), t(":"), sp("STRING")]),
+                seq([t("system"), t(":"), block(n("instructions"))]),
+                n("before_reasoning_block"),
+                n("after_reasoning_block"),
+                n("reasoning_block"),
+                n("actions_block"),
+            ]),
+        ),
+        rule(
+            "description_entry",
+            "Description property",
+            seq([t("description"), t(":"), sp("STRING")]),
+        ),
+        rule(
+            "knowledge_block",
+            "Knowledge sources",
+            seq([
+                t("knowledge"),
+                t(":"),
+                block(seq([sp("IDENT"), t(":"), n("expr")])),
+            ]),
+        ),
+        rule(
+            "language_block",
+            "Locale settings",
+            seq([
+                t("language"),
+                t(":"),
+                block(seq([sp("IDENT"), t(":"), n("expr")])),
+            ]),
+        ),
+        rule(
+            "connection_block",
+            "Escalation connection",
+            seq([
+                t("connection"),
+                sp("IDENT"),
+                t(":"),
+                block(n("connection_entry")),
+            ]),
+        ),
+        rule(
+            "connections_block",
+            "Legacy wrapper around named connections",
+            seq([
+                t("connections"),
+                t(":"),
+                block(seq([sp("IDENT"), t(":"), block(n("connection_entry"))])),
+            ]),
+        ),
+        rule(
+            "connection_entry",
+            "Connection property",
+            seq([sp("IDENT"), t(":"), sp("STRING")]),
+        ),
+        // ── Action definitions ──────────────────────────────────────────
+        rule(
+            "actions_block",
+            "Action definitions",
+            seq([t("actions"), t(":"), block(n("action_def"))]),
+        ),
+        rule(
+            "action_def",
+            "An externally implemented action",
+            seq([sp("IDENT"), t(":"), block(n("action_entry"))]),
+        ),
+        rule(
+            "action_entry",
+            "Action definition property",
+            choice([
+                n("description_entry"),
+                seq([t("label"), t(":"), sp("STRING")]),
+                seq([t("require_user_confirmation"), t(":"), n("boolean")]),
+                seq([t("include_in_progress_indicator"), t(":"), n("boolean")]),
+                seq([t("progress_indicator_message"), t(":"), sp("STRING")]),
+                seq([t("target"), t(":"), sp("STRING")]),
+                seq([t("inputs"), t(":"), n("param_block")]),
+                seq([t("outputs"), t(":"), n("param_block")]),
+            ]),
+        ),
+        rule(
+            "param_block",
+            "Input or output parameters",
+            block(choice([n("description_entry"), n("param_def")])),
+        ),
+        rule(
+            "param_def",
+            "A single parameter",
+            seq([
+                n("param_name"),
+                t(":"),
+                n("type"),
+                opt(block(n("param_entry"))),
+            ]),
+        ),
+        rule(
+            "param_name",
+            "Parameter name; some keywords are allowed",
+            choice([
+                sp("IDENT"),
+                sp("STRING"),
+                n("simple_type"),
+                t("list"),
+                t("description"),
+                t("available"),
+            ]),
+        ),
+        rule(
+            "param_entry",
+            "Parameter property",
+            choice([
+                n("description_entry"),
+                seq([t("label"), t(":"), sp("STRING")]),
+                seq([t("is_required"), t(":"), n("boolean")]),
+                seq([t("is_displayable"), t(":"), n("boolean")]),
+                seq([t("is_used_by_planner"), t(":"), n("boolean")]),
+                seq([t("complex_data_type_name"), t(":"), sp("STRING")]),
+                seq([t("filter_from_agent"), t(":"), n("boolean")]),
+            ]),
+        ),
+        // ── Reasoning ───────────────────────────────────────────────────
+        rule(
+            "reasoning_block",
+            "Instructions and tools for the LLM",
+            seq([t("reasoning"), t(":"), block(n("reasoning_entry"))]),
+        ),
+        rule(
+            "reasoning_entry",
+            "Reasoning block entry",
+            choice([
+                n("instructions"),
+                seq([t("actions"), t(":"), block(n("reasoning_action"))]),
+            ]),
+        ),
+        rule(
+            "reasoning_action",
+            "A tool exposed to the LLM",
+            seq([
+                sp("IDENT"),
+                t(":"),
+                n("action_target"),
+                opt(block(n("reasoning_action_entry"))),
+            ]),
+        ),
+        rule(
+            "action_target",
+            "What a reasoning action invokes",
+            choice([
+                seq([
+                    t("@"),
+                    t("utils"),
+                    t("."),
+                    t("transition"),
+                    t("to"),
+                    n("reference"),
+                ]),
+                seq([t("@"), t("utils"), t("."), t("escalate")]),
+                seq([t("@"), t("utils"), t("."), t("setVariables")]),
+                seq([t("@"), t("topic"), t("."), sp("IDENT")]),
+                n("reference"),
+            ]),
+        ),
+        rule(
+            "reasoning_action_entry",
+            "Reasoning action property or clause",
+            choice([
+                n("description_entry"),
+                n("with_clause"),
+                n("set_clause"),
+                seq([t("available"), t("when"), n("expr")]),
+                n("run_clause"),
+                seq([t("if"), n("expr"), t(":"), block(n("transition"))]),
+                n("transition"),
+            ]),
+        ),
+        rule(
+            "transition",
+            "Transition to another topic",
+            seq([t("transition"), t("to"), n("reference")]),
+        ),
+        rule(
+            "with_clause",
+            "Bind an action input",
+            seq([
+                t("with"),
+                choice([sp("IDENT"), sp("STRING"), t("description"), t("id")]),
+                t("="),
+                n("expr"),
+            ]),
+        ),
+        rule(
+            "set_clause",
+            "Assign a variable",
+            seq([t("set"), n("reference"), t("="), n("expr")]),
+        ),
+        rule(
+            "run_clause",
+            "Invoke an action",
+            seq([
+                t("run"),
+                n("reference"),
+                opt(block(choice([n("with_clause"), n("set_clause")]))),
+            ]),
+        ),
+        // ── Instructions ────────────────────────────────────────────────
+        rule(
+            "instructions",
+            "Simple, static (:|) or dynamic (->) instructions",
+            seq([
+                t("instructions"),
+                choice([
+                    seq([t(":"), sp("STRING")]),
+                    seq([t(":|"), block(sp("TEXT"))]),
+                    seq([
+                        choice([t(":->"), seq([t(":"), t("->")])]),
+                        block(n("instruction_line")),
+                    ]),
+                ]),
+            ]),
+        ),
+        rule(
+            "instruction_line",
+            "Line of dynamic instructions",
+            choice([
+                seq([t("|"), sp("HEADING")]),
+                seq([t("|"), many(n("instruction_text")), opt(block(sp("TEXT")))]),
+                seq([
+                    t("if"),
+                    n("expr"),
+                    t(":"),
+                    block(n("instruction_line")),
+                    many(seq([t("elif"), n("expr"), t(":"), block(n("instruction_line"))])),
+                    opt(seq([t("else"), t(":"), block(n("instruction_line"))])),
+                ]),
+                n("run_clause"),
+            ]),
+        ),
+        rule(
+            "instruction_text",
+            "Literal text or an interpolated expression",
+            choice([sp("TEXT"), seq([t("{!"), n("expr"), t("}")])]),
+        ),
+        // ── Directives ──────────────────────────────────────────────────
+        rule(
+            "before_reasoning_block",
+            "Statements run before reasoning",
+            seq([t("before_reasoning"), t(":"), block(n("statement"))]),
+        ),
+        rule(
+            "after_reasoning_block",
+            "Statements run after reasoning",
+            seq([t("after_reasoning"), t(":"), block(n("statement"))]),
+        ),
+        rule(
+            "statement",
+            "Directive statement",
+            choice([
+                n("if_statement"),
+                n("set_clause"),
+                n("run_clause"),
+                n("transition"),
+                t("escalate"),
+                seq([t("respond"), sp("STRING")]),
+            ]),
+        ),
+        rule(
+            "if_statement",
+            "Conditional statements",
+            seq([
+                t("if"),
+                n("expr"),
+                t(":"),
+                block(n("statement")),
+                many(seq([t("elif"), n("expr"), t(":"), block(n("statement"))])),
+                opt(seq([t("else"), t(":"), block(n("statement"))])),
+            ]),
+        ),
+        // ── Types and expressions ───────────────────────────────────────
+        rule(
+            "type",
+            "Variable or parameter type",
+            choice([
+                n("simple_type"),
+                seq([t("list"), t("["), n("type"), t("]")]),
+                seq([t("list"), t("<"), n("type"), t(">")]),
+            ]),
+        ),
+        rule("simple_type", "Built-in scalar type", choice(types)),
+        rule("boolean", "Boolean literal", choice([t("True"), t("False")])),
+        rule(
+            "reference",
+            "Namespaced reference, e.g. @variables.name",
+            seq([t("@"), sp("IDENT"), many(seq([t("."), sp("IDENT")]))]),
+        ),
+        rule(
+            "expr",
+            "Expression, optionally a conditional expression",
+            seq([
+                n("or_expr"),
+                opt(seq([t("if"), n("or_expr"), t("else"), n("or_expr")])),
+            ]),
+        ),
+        rule(
+            "or_expr",
+            "Logical or",
+            seq([n("and_expr"), many(seq([t("or"), n("and_expr")]))]),
+        ),
+        rule(
+            "and_expr",
+            "Logical and",
+            seq([n("not_expr"), many(seq([t("and"), n("not_expr")]))]),
+        ),
+        rule(
+            "not_expr",
+            "Logical not, binding looser than comparisons",
+            seq([many(t("not")), n("comparison")]),
+        ),
+        rule(
+            "comparison",
+            "Comparison",
+            seq([
+                n("sum"),
+                many(seq([
+                    choice([
+                        t("=="),
+                        t("!="),
+                        t("<="),
+                        t(">="),
+                        t("<"),
+                        t(">"),
+                        seq([t("is"), opt(t("not"))]),
+                    ]),
+                    n("sum"),
+                ])),
+            ]),
+        ),
+        rule(
+            "sum",
+            "Addition and subtraction",
+            seq([n("term"), many(seq([choice([t("+"), t("-")]), n("term")]))]),
+        ),
+        rule(
+            "term",
+            "Multiplication, division and remainder",
+            seq([
+                n("unary"),
+                many(seq([choice([t("*"), t("/"), t("%")]), n("unary")])),
+            ]),
+        ),
+        rule("unary", "Arithmetic negation", seq([many(t("-")), n("postfix")])),
+        rule(
+            "postfix",
+            "Property access and indexing",
+            seq([
+                n("atom"),
+                many(choice([seq([t("."), sp("IDENT")]), seq([t("["), n("expr"), t("]")])])),
+            ]),
+        ),
+        rule(
+            "atom",
+            "Literal, reference, call or bracketed expression",
+            choice([
+                n("string"),
+                sp("NUMBER"),
+                sp("DATE"),
+                sp("CURRENCY"),
+                n("currency"),
+                t("True"),
+                t("False"),
+                t("None"),
+                t("..."),
+                n("reference"),
+                n("call"),
+                seq([t("("), n("expr"), t(")")]),
+                n("object"),
+                n("list"),
+            ]),
+        ),
+        rule(
+            "string",
+            "String literal, with {!expr} interpolations",
+            seq([t("\""), many(n("instruction_text")), t("\"")]),
+        ),
+        rule(
+            "currency",
+            "Amount with an ISO 4217 code, e.g. currency(199.99, \"USD\")",
+            seq([
+                t("currency"),
+                t("("),
+                sp("NUMBER"),
+                t(","),
+                sp("STRING"),
+                t(")"),
+            ]),
+        ),
+        rule(
+            "call",
+            "Built-in function call, e.g. len(@variables.items)",
+            seq([
+                sp("IDENT"),
+                t("("),
+                opt(seq([n("expr"), many(seq([t(","), n("expr")])), opt(t(","))])),
+                t(")"),
+            ]),
+        ),
+        rule("object", "Object literal", seq([t("{"), opt(n("object_entries")), t("}")])),
+        rule(
+            "object_entries",
+            "Comma-separated key-value pairs",
+            seq([
+                n("object_entry"),
+                many(seq([t(","), n("object_entry")])),
+                opt(t(",")),
+            ]),
+        ),
+        rule(
+            "object_entry",
+            "Key-value pair",
+            seq([
+                choice([sp("IDENT"), sp("KEYWORD"), sp("STRING")]),
+                t(":"),
+                n("expr"),
+            ]),
+        ),
+        rule(
+            "list",
+            "List literal",
+            seq([
+                t("["),
+                opt(seq([n("expr"), many(seq([t(","), n("expr")])), opt(t(","))])),
+                t("]"),
+            ]),
+        ),
+    ]
+}
+
+/// Render the grammar as EBNF, one rule per paragraph.
+pub fn export_ebnf() -> String {
+    let mut out = String::new();
+    for (i, rule) in rules().iter().enumerate() {
+        if i > 0 {
+            out.push('\n');
+        }
+        match &rule.diagram {
+            // Long choices get one alternative per line
+            Node::Choice { items } if items.len() > 3 || ebnf(&rule.diagram, false).len() > 72 => {
+                let _ = writeln!(out, "{} ::=", rule.name);
+                for (j, item) in items.iter().enumerate() {
+                    let bar = if j == 0 { ' ' } else { '|' };
+                    let _ = writeln!(out, "    {} {}", bar, ebnf(item, false));
+                }
+            }
+            diagram => {
+                let _ = writeln!(out, "{} ::= {}", rule.name, ebnf(diagram, false));
+            }
+        }
+    }
+    out
+}
+
+/// Render the grammar as railroad-diagram JSON: an array of
+/// `{name, description, diagram}` objects.
+pub fn export_railroad() -> serde_json::Value {
+    serde_json::to_value(rules()).expect("grammar rules serialize to JSON")
+}
+
+fn ebnf(node: &Node, nested: bool) -> String {
+    match node {
+        Node::Terminal { text } => quote(text),
+        Node::NonTerminal { text } | Node::Special { text } => text.clone(),
+        Node::Sequence { items } => {
+            let inner = items
+                .iter()
+                .map(|i| ebnf(i, true))
+                .collect::<Vec<_>>()
+                .join(" ");
+            if nested {
+                format!("( {} )", inner)
+            } else {
+                inner
+            }
+        }
+        Node::Choice { items } => {
+            let inner = items
+                .iter()
+                .map(|i| ebnf(i, false))
+                .collect::<Vec<_>>()
+                .join(" | ");
+            if nested {
+                format!("( {} )", inner)
+            } else {
+                inner
+            }
+        }
+        Node::Optional { item } => format!("{}?", ebnf(item, true)),
+        Node::ZeroOrMore { item } => format!("{}*", ebnf(item, true)),
+        Node::OneOrMore { item } => format!("{}+", ebnf(item, true)),
+    }
+}
+
+fn quote(text: &str) -> String {
+    if text.contains('\'') {
+        format!("\"{}\"", text)
+    } else {
+        format!("'{}'", text)
+    }
+}
+
+// Constructors, kept short so the rules above read like the EBNF they
+// produce.
+
+fn rule(name: &'static str, description: &'static str, diagram: Node) -> Rule {
+    Rule {
+        name,
+        description,
+        diagram,
+    }
+}
+
+fn t(text: &str) -> Node {
+    Node::Terminal {
+        text: text.to_string(),
+    }
+}
+
+fn n(name: &str) -> Node {
+    Node::NonTerminal {
+        text: name.to_string(),
+    }
+}
+
+fn sp(name: &str) -> Node {
+    Node::Special {
+        text: name.to_string(),
+    }
+}
+
+fn seq(items: impl IntoIterator<Item = Node>) -> Node {
+    Node::Sequence {
+        items: items.into_iter().collect(),
+    }
+}
+
+fn choice(items: impl IntoIterator<Item = Node>) -> Node {
+    Node::Choice {
+        items: items.into_iter().collect(),
+    }
+}
+
+fn opt(item: Node) -> Node {
+    Node::Optional {
+        item: Box::new(item),
+    }
+}
+
+fn many(item: Node) -> Node {
+    Node::ZeroOrMore {
+        item: Box::new(item),
+    }
+}
+
+/// An indented block of one or more entries.
+fn block(entry: Node) -> Node {
+    seq([
+        sp("NEWLINE"),
+        sp("INDENT"),
+        Node::OneOrMore {
+            item: Box::new(entry),
+        },
+        sp("DEDENT"),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn walk<'a>(node: &'a Node, refs: &mut Vec<&'a str>) {
+        match node {
+            Node::NonTerminal { text } => refs.push(text),
+            Node::Terminal { .. } | Node::Special { .. } => {}
+            Node::Sequence { items } | Node::Choice { items } => {
+                items.iter().for_each(|i| walk(i, refs))
+            }
+            Node::Optional { item } | Node::ZeroOrMore { item } | Node::OneOrMore { item } => {
+                walk(item, refs)
+            }
+        }
+    }
+
+    #[test]
+    fn test_rules_are_closed_and_reachable() {
+        let rules = rules();
+        let names: HashSet<_> = rules.iter().map(|r| r.name).collect();
+        assert_eq!(names.len(), rules.len(), "duplicate rule names");
+
+        let mut reachable = HashSet::new();
+        let mut stack = vec!["agent_file"];
+        while let Some(name) = stack.pop() {
+            if !reachable.insert(name) {
+                continue;
+            }
+            let rule = rules.iter().find(|r| r.name == name);
+            let rule = rule.unwrap_or_else(|| panic!("undefined rule '{}'", name));
+            let mut refs = Vec::new();
+            walk(&rule.diagram, &mut refs);
+            stack.extend(refs);
+        }
+        assert_eq!(reachable, names, "unreachable rules");
+    }
+
+    #[test]
+    fn test_export_formats() {
+        let ebnf = export_ebnf();
+        assert!(ebnf.contains("type ::= simple_type | 'list' '[' type ']'"));
+        assert!(ebnf.contains("simple_type ::=\n      'string'\n    | 'number'\n"));
+        assert!(ebnf.contains("top_level_block ::=\n      config_block\n    | variables_block\n"));
+
+        let railroad = export_railroad();
+        let first = &railroad[0];
+        assert_eq!(first["name"], "agent_file");
+        assert_eq!(first["diagram"]["type"], "ZeroOrMore");
+        assert_eq!(first["diagram"]["item"]["type"], "NonTerminal");
+    }
+}
@@ -2,64 +2,169 @@
 
 use super::edges::RefEdge;
 use super::error::{GraphBuildError, ValidationError};
-use super::nodes::RefNode;
+use super::metrics::BlockComplexity;
+use super::nodes::{RefNode, Span};
 use super::RefGraph;
 use crate::ast::{
-    Expr, InstructionPart, Instructions, ReasoningAction, ReasoningActionTarget, Reference, Type,
-    VariableKind,
+    Expr, InstructionPart, Instructions, ReasoningAction, ReasoningActionTarget, Reference, Stmt,
+    Type, VariableKind,
 };
 use crate::AgentFile;
-use petgraph::graph::{DiGraph, NodeIndex};
+use indexmap::IndexMap;
+use petgraph::graph::{DiGraph, EdgeIndex, NodeIndex};
 use std::collections::HashMap;
 
+/// How references are matched to the definitions they name.
+///
+/// ```rust
+/// use busbar_sf_agentscript::graph::{RefGraphBuilder, ResolutionPolicy};
+///
+/// let source = r#"
+/// start_agent main:
+///    description: "Route"
+///    reasoning:
+///       instructions: "Route"
+///       actions:
+///          go: @utils.transition to @topic.Refunds
+///
+/// topic refunds:
+///    description: "Refunds"
+///    reasoning:
+///       instructions: "Help"
+/// "#;
+/// let ast = busbar_sf_agentscript::parse(source).unwrap();
+/// let graph = RefGraphBuilder::new()
+///     .with_policy(ResolutionPolicy { case_insensitive: true })
+///     .build(&ast)
+///     .unwrap();
+/// assert!(graph.validate().is_ok());
+/// assert_eq!(graph.get_topic("REFUNDS"), graph.get_topic("refunds"));
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResolutionPolicy {
+    /// Match names regardless of case, as Salesforce does for API names.
+    /// Names that differ only in case are then duplicates.
+    pub case_insensitive: bool,
+}
+
+impl ResolutionPolicy {
+    /// The form of `name` that definitions and references are matched by.
+    pub fn key(&self, name: &str) -> String {
+        if self.case_insensitive {
+            name.to_lowercase()
+        } else {
+            name.to_string()
+        }
+    }
+}
+
+/// The nodes of one topic block, so that a topic sharing its name with
+/// another still gets edges from its own actions.
+struct TopicScope {
+    idx: NodeIndex,
+    action_defs: HashMap<String, NodeIndex>,
+    /// Reasoning action nodes in source order
+    reasoning_actions: Vec<NodeIndex>,
+}
+
 /// Builder for constructing a reference graph from an AST.
+///
+/// Names are matched according to a [`ResolutionPolicy`], exactly by
+/// default. Every definition gets its own node. When names repeat, the name indexes
+/// keep the first definition, so references resolve to it, and the repeats
+/// are reported by [`PassId::Duplicates`](super::PassId::Duplicates).
 pub struct RefGraphBuilder {
+    policy: ResolutionPolicy,
     graph: DiGraph<RefNode, RefEdge>,
     topics: HashMap<String, NodeIndex>,
     action_defs: HashMap<(String, String), NodeIndex>,
     reasoning_actions: HashMap<(String, String), NodeIndex>,
     variables: HashMap<String, NodeIndex>,
+    /// Topic blocks in source order
+    topic_scopes: Vec<TopicScope>,
+    /// The name and name spans of every definition, by kind and key
+    definitions: IndexMap<(&'static str, String), (String, Vec<Span>)>,
+    /// Connection nodes in source order, by name.
+    connections: Vec<(String, NodeIndex)>,
     /// Maps variable names to their declared types for property-access validation.
     variable_types: HashMap<String, Type>,
     start_agent: Option<NodeIndex>,
     unresolved_references: Vec<ValidationError>,
+    block_complexity: HashMap<NodeIndex, BlockComplexity>,
+    edge_spans: HashMap<EdgeIndex, Span>,
 }
 
 impl RefGraphBuilder {
     /// Create a new builder.
     pub fn new() -> Self {
         Self {
+            policy: ResolutionPolicy::default(),
             graph: DiGraph::new(),
             topics: HashMap::new(),
             action_defs: HashMap::new(),
             reasoning_actions: HashMap::new(),
             variables: HashMap::new(),
+            topic_scopes: Vec::new(),
+            definitions: IndexMap::new(),
+            connections: Vec::new(),
             variable_types: HashMap::new(),
             start_agent: None,
             unresolved_references: Vec::new(),
+            block_complexity: HashMap::new(),
+            edge_spans: HashMap::new(),
         }
     }
 
+    /// Match names according to `policy`.
+    pub fn with_policy(mut self, policy: ResolutionPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
     /// Build a RefGraph from an AgentFile AST.
     pub fn build(mut self, ast: &AgentFile) -> Result<RefGraph, GraphBuildError> {
         // Phase 1: Add all definition nodes
         self.add_variables(ast)?;
+        self.add_connections(ast);
         self.add_start_agent(ast)?;
         self.add_topics(ast)?;
+        self.add_knowledge_bases(ast);
 
         // Phase 2: Add all reference edges
         self.add_start_agent_edges(ast)?;
         self.add_topic_edges(ast)?;
 
-        Ok(RefGraph {
+        let duplicate_definitions = self
+            .definitions
+            .into_iter()
+            .filter(|(_, (_, spans))| spans.len() > 1)
+            .map(|((kind, _), (name, spans))| ValidationError::DuplicateDefinition {
+                name,
+                kind: kind.to_string(),
+                spans,
+            })
+            .collect();
+
+        let mut graph = RefGraph {
+            policy: self.policy,
             graph: self.graph,
             topics: self.topics,
             action_defs: self.action_defs,
             reasoning_actions: self.reasoning_actions,
             variables: self.variables,
+            connections: self.connections.into_iter().collect(),
             start_agent: self.start_agent,
             unresolved_references: self.unresolved_references,
-        })
+            duplicate_definitions,
+            parent_indices: Vec::new(),
+            block_complexity: self.block_complexity,
+            edge_spans: self.edge_spans,
+            symbol_spans: HashMap::new(),
+            param_usage: HashMap::new(),
+        };
+        graph.symbol_spans = super::rename::collect_symbol_spans(&graph, ast);
+        graph.param_usage = super::params::collect_param_usage(&graph, ast);
+        Ok(graph)
     }
 
     /// Add variable definition nodes.
@@ -77,14 +182,44 @@ impl RefGraphBuilder {
                 };
 
                 let idx = self.graph.add_node(node);
+                self.define("variable", &var.node.name);
+                let key = self.policy.key(&name);
                 self.variable_types
-                    .insert(name.clone(), var.node.ty.node.clone());
-                self.variables.insert(name, idx);
+                    .entry(key.clone())
+                    .or_insert_with(|| var.node.ty.node.clone());
+                self.variables.entry(key).or_insert(idx);
             }
         }
         Ok(())
     }
 
+    /// Add connection nodes.
+    fn add_connections(&mut self, ast: &AgentFile) {
+        for connection in &ast.connections {
+            let name = connection.node.name.node.clone();
+            let idx = self.graph.add_node(RefNode::Connection {
+                name: name.clone(),
+                span: (connection.span.start, connection.span.end),
+            });
+            self.connections.push((name, idx));
+        }
+    }
+
+    /// Add a node for each knowledge source.
+    ///
+    /// Nothing references these yet, so they have no edges.
+    fn add_knowledge_bases(&mut self, ast: &AgentFile) {
+        for entry in ast.knowledge.iter().flat_map(|k| &k.node.entries) {
+            if let Some(source) = super::dependencies::knowledge_source(&entry.node) {
+                self.graph.add_node(RefNode::KnowledgeBase {
+                    name: entry.node.name.node.clone(),
+                    source: source.to_string(),
+                    span: (entry.span.start, entry.span.end),
+                });
+            }
+        }
+    }
+
     /// Add the start_agent node.
     fn add_start_agent(&mut self, ast: &AgentFile) -> Result<(), GraphBuildError> {
         if let Some(start) = &ast.start_agent {
@@ -92,6 +227,21 @@ impl RefGraphBuilder {
             let node = RefNode::StartAgent { span };
             let idx = self.graph.add_node(node);
             self.start_agent = Some(idx);
+            self.block_complexity.insert(
+                idx,
+                BlockComplexity::measure(
+                    [
+                        start.node.before_reasoning.as_ref(),
+                        start.node.after_reasoning.as_ref(),
+                    ],
+                    start
+                        .node
+                        .reasoning
+                        .as_ref()
+                        .and_then(|r| r.node.instructions.as_ref())
+                        .map(|i| &i.node),
+                ),
+            );
 
             // Add action definitions from start_agent
             if let Some(actions) = &start.node.actions {
@@ -106,7 +256,8 @@ impl RefGraphBuilder {
                     };
                     let action_idx = self.graph.add_node(action_node);
                     self.action_defs
-                        .insert(("start_agent".to_string(), action_name), action_idx);
+                        .entry(("start_agent".to_string(), self.policy.key(&action_name)))
+                        .or_insert(action_idx);
                 }
             }
 
@@ -126,7 +277,8 @@ impl RefGraphBuilder {
                         };
                         let reasoning_idx = self.graph.add_node(reasoning_node);
                         self.reasoning_actions
-                            .insert(("start_agent".to_string(), action_name), reasoning_idx);
+                            .entry(("start_agent".to_string(), self.policy.key(&action_name)))
+                            .or_insert(reasoning_idx);
                     }
                 }
             }
@@ -146,7 +298,29 @@ impl RefGraphBuilder {
                 span,
             };
             let topic_idx = self.graph.add_node(topic_node);
-            self.topics.insert(topic_name.clone(), topic_idx);
+            self.define("topic", &topic.node.name);
+            let topic_key = self.policy.key(&topic_name);
+            self.topics.entry(topic_key.clone()).or_insert(topic_idx);
+            let mut scope = TopicScope {
+                idx: topic_idx,
+                action_defs: HashMap::new(),
+                reasoning_actions: Vec::new(),
+            };
+            self.block_complexity.insert(
+                topic_idx,
+                BlockComplexity::measure(
+                    [
+                        topic.node.before_reasoning.as_ref(),
+                        topic.node.after_reasoning.as_ref(),
+                    ],
+                    topic
+                        .node
+                        .reasoning
+                        .as_ref()
+                        .and_then(|r| r.node.instructions.as_ref())
+                        .map(|i| &i.node),
+                ),
+            );
 
             // Add action definition nodes
             if let Some(actions) = &topic.node.actions {
@@ -160,8 +334,11 @@ impl RefGraphBuilder {
                         span: action_span,
                     };
                     let action_idx = self.graph.add_node(action_node);
+                    let action_key = self.policy.key(&action_name);
                     self.action_defs
-                        .insert((topic_name.clone(), action_name), action_idx);
+                        .entry((topic_key.clone(), action_key.clone()))
+                        .or_insert(action_idx);
+                    scope.action_defs.entry(action_key).or_insert(action_idx);
                 }
             }
 
@@ -181,14 +358,48 @@ impl RefGraphBuilder {
                         };
                         let reasoning_idx = self.graph.add_node(reasoning_node);
                         self.reasoning_actions
-                            .insert((topic_name.clone(), action_name), reasoning_idx);
+                            .entry((topic_key.clone(), self.policy.key(&action_name)))
+                            .or_insert(reasoning_idx);
+                        scope.reasoning_actions.push(reasoning_idx);
                     }
                 }
             }
+            self.topic_scopes.push(scope);
         }
         Ok(())
     }
 
+    /// Record the name span of a definition.
+    fn define(&mut self, kind: &'static str, name: &crate::Spanned<String>) {
+        self.definitions
+            .entry((kind, self.policy.key(&name.node)))
+            .or_insert_with(|| (name.node.clone(), Vec::new()))
+            .1
+            .push((name.span.start, name.span.end));
+    }
+
+    /// The defined name closest to an unresolved one, as a reference.
+    ///
+    /// Actions are looked for in `topic`, the block the reference is in.
+    fn suggestion(&self, namespace: &str, topic: Option<&str>, name: &str) -> Option<String> {
+        let topic = topic.map(|t| self.policy.key(t));
+        let names: Vec<String> = self
+            .graph
+            .node_weights()
+            .filter_map(|node| match (namespace, node) {
+                ("topic", RefNode::Topic { name, .. })
+                | ("variables", RefNode::Variable { name, .. }) => Some(name.clone()),
+                ("actions", RefNode::ActionDef { name, topic: t, .. })
+                    if topic == Some(self.policy.key(t)) =>
+                {
+                    Some(name.clone())
+                }
+                _ => None,
+            })
+            .collect();
+        crate::serializer::closest(name, names.iter()).map(|c| format!("@{}.{}", namespace, c))
+    }
+
     /// Extract the target string from a ReasoningActionTarget.
     fn extract_target(target: &ReasoningActionTarget) -> Option<String> {
         match target {
@@ -216,16 +427,45 @@ impl RefGraphBuilder {
 
                 if let Some(actions) = &reasoning.node.actions {
                     for action in &actions.node {
+                        let reasoning_idx = self.reasoning_actions
+                            [&("start_agent".to_string(), self.policy.key(&action.node.name.node))];
+                        self.add_action_clause_edges(reasoning_idx, "start_agent", &action.node);
+
                         // Both TransitionTo and TopicDelegate route to a topic from start_agent
                         let routing_ref = match &action.node.target.node {
                             ReasoningActionTarget::TransitionTo(r)
                             | ReasoningActionTarget::TopicDelegate(r) => Some(r),
+                            ReasoningActionTarget::Action(r) => {
+                                let target_span =
+                                    (action.node.target.span.start, action.node.target.span.end);
+                                if let Some(action_idx) =
+                                    self.resolve_action("start_agent", r, target_span)
+                                {
+                                    self.add_reference_edge(
+                                        reasoning_idx,
+                                        action_idx,
+                                        RefEdge::Invokes,
+                                        Self::target_reference_span(&action.node.target, r),
+                                    );
+                                }
+                                None
+                            }
                             _ => None,
                         };
+                        if let ReasoningActionTarget::Escalate = &action.node.target.node {
+                            self.add_escalation_edges(reasoning_idx, &action.node.target);
+                        }
                         if let Some(reference) = routing_ref {
                             if let Some(topic_name) = Self::extract_topic_from_ref(reference) {
-                                if let Some(&topic_idx) = self.topics.get(&topic_name) {
-                                    self.graph.add_edge(start_idx, topic_idx, RefEdge::Routes);
+                                if let Some(&topic_idx) =
+                                    self.topics.get(&self.policy.key(&topic_name))
+                                {
+                                    self.add_reference_edge(
+                                        start_idx,
+                                        topic_idx,
+                                        RefEdge::Routes,
+                                        Self::target_reference_span(&action.node.target, reference),
+                                    );
                                 } else {
                                     self.unresolved_references.push(
                                         ValidationError::UnresolvedReference {
@@ -236,6 +476,7 @@ impl RefGraphBuilder {
                                                 action.node.target.span.end,
                                             ),
                                             context: "start_agent".to_string(),
+                                            suggestion: self.suggestion("topic", None, &topic_name),
                                         },
                                     );
                                 }
@@ -244,15 +485,22 @@ impl RefGraphBuilder {
                     }
                 }
             }
+            for block in [&start.node.before_reasoning, &start.node.after_reasoning]
+                .into_iter()
+                .flatten()
+            {
+                self.add_directive_edges(start_idx, "start_agent", &block.node.statements);
+            }
         }
         Ok(())
     }
 
     /// Add edges within and between topics.
     fn add_topic_edges(&mut self, ast: &AgentFile) -> Result<(), GraphBuildError> {
-        for topic in &ast.topics {
+        let scopes = std::mem::take(&mut self.topic_scopes);
+        for (topic, scope) in ast.topics.iter().zip(&scopes) {
             let topic_name = &topic.node.name.node;
-            let topic_idx = self.topics[topic_name];
+            let topic_idx = scope.idx;
 
             // Add edges from reasoning actions to their targets
             if let Some(reasoning) = &topic.node.reasoning {
@@ -261,35 +509,174 @@ impl RefGraphBuilder {
                 }
 
                 if let Some(actions) = &reasoning.node.actions {
-                    self.add_reasoning_action_edges(topic_name, topic_idx, &actions.node)?;
+                    self.add_reasoning_action_edges(topic_name, scope, &actions.node)?;
                 }
             }
+
+            for block in [&topic.node.before_reasoning, &topic.node.after_reasoning]
+                .into_iter()
+                .flatten()
+            {
+                self.add_directive_edges(topic_idx, topic_name, &block.node.statements);
+            }
         }
         Ok(())
     }
 
+    /// Add edges for the clauses under a reasoning action: variables read by
+    /// its `available when`, `with` and `if` clauses, variables its `set`
+    /// clauses write and actions its `run` clauses chain to.
+    fn add_action_clause_edges(
+        &mut self,
+        reasoning_idx: NodeIndex,
+        topic_name: &str,
+        action: &ReasoningAction,
+    ) {
+        if let Some(condition) = &action.available_when {
+            self.add_expression_edges(reasoning_idx, condition);
+        }
+
+        // Add edges for with_clauses (reading variables)
+        for clause in &action.with_clauses {
+            self.add_with_value_edges(reasoning_idx, &clause.node.value);
+        }
+
+        // Add edges for set_clauses (writing variables)
+        for clause in &action.set_clauses {
+            self.add_expression_edges(reasoning_idx, &clause.node.source);
+            self.add_write_edge(reasoning_idx, &clause.node.target, topic_name);
+        }
+
+        // Add edges for run clauses chained after the action
+        for clause in &action.run_clauses {
+            let run = &clause.node;
+            if let Some(action_idx) = self.resolve_action(
+                topic_name,
+                &run.action.node,
+                (run.action.span.start, run.action.span.end),
+            ) {
+                self.add_reference_edge(
+                    reasoning_idx,
+                    action_idx,
+                    RefEdge::Chains,
+                    (run.action.span.start, run.action.span.end),
+                );
+            }
+            for with in &run.with_clauses {
+                self.add_with_value_edges(reasoning_idx, &with.node.value);
+            }
+            for set in &run.set_clauses {
+                self.add_expression_edges(reasoning_idx, &set.node.source);
+                self.add_write_edge(reasoning_idx, &set.node.target, topic_name);
+            }
+        }
+
+        for clause in &action.if_clauses {
+            self.add_expression_edges(reasoning_idx, &clause.node.condition);
+        }
+    }
+
+    /// Add edges for the statements of a `before_reasoning` or
+    /// `after_reasoning` block, attributed to the block's topic.
+    fn add_directive_edges(
+        &mut self,
+        block_idx: NodeIndex,
+        topic_name: &str,
+        stmts: &[crate::Spanned<Stmt>],
+    ) {
+        for stmt in stmts {
+            match &stmt.node {
+                Stmt::Set { target, value } => {
+                    self.add_expression_edges(block_idx, value);
+                    self.add_write_edge(block_idx, target, topic_name);
+                }
+                Stmt::Run {
+                    action,
+                    with_clauses,
+                    set_clauses,
+                } => {
+                    if let Some(action_idx) = self.resolve_action(
+                        topic_name,
+                        &action.node,
+                        (action.span.start, action.span.end),
+                    ) {
+                        self.add_reference_edge(
+                            block_idx,
+                            action_idx,
+                            RefEdge::Invokes,
+                            (action.span.start, action.span.end),
+                        );
+                    }
+                    for clause in with_clauses {
+                        self.add_with_value_edges(block_idx, &clause.node.value);
+                    }
+                    for clause in set_clauses {
+                        self.add_expression_edges(block_idx, &clause.node.source);
+                        self.add_write_edge(block_idx, &clause.node.target, topic_name);
+                    }
+                }
+                Stmt::If {
+                    condition,
+                    then_block,
+                    else_block,
+                } => {
+                    self.add_expression_edges(block_idx, condition);
+                    self.add_directive_edges(block_idx, topic_name, then_block);
+                    if let Some(else_block) = else_block {
+                        self.add_directive_edges(block_idx, topic_name, else_block);
+                    }
+                }
+                Stmt::Transition { .. } | Stmt::Escalate | Stmt::Respond { .. } => {}
+            }
+        }
+    }
+
+    /// Look up the action named by `@actions.<name>` in a topic, recording
+    /// an unresolved reference if it isn't defined there.
+    fn resolve_action(
+        &mut self,
+        topic_name: &str,
+        reference: &Reference,
+        span: Span,
+    ) -> Option<NodeIndex> {
+        let action_ref = Self::extract_action_name(reference)?;
+        let key = (self.policy.key(topic_name), self.policy.key(&action_ref));
+        if let Some(&idx) = self.action_defs.get(&key) {
+            return Some(idx);
+        }
+        self.unresolved_references
+            .push(ValidationError::UnresolvedReference {
+                reference: reference.full_path(),
+                namespace: "actions".to_string(),
+                span,
+                context: format!("topic {}", topic_name),
+                suggestion: self.suggestion("actions", Some(topic_name), &action_ref),
+            });
+        None
+    }
+
     /// Add edges for reasoning actions in a topic.
     fn add_reasoning_action_edges(
         &mut self,
         topic_name: &str,
-        topic_idx: NodeIndex,
+        scope: &TopicScope,
         actions: &[crate::Spanned<ReasoningAction>],
     ) -> Result<(), GraphBuildError> {
-        for action in actions {
-            let action_name = &action.node.name.node;
-            let reasoning_idx =
-                self.reasoning_actions[&(topic_name.to_string(), action_name.clone())];
-
+        let topic_idx = scope.idx;
+        for (action, &reasoning_idx) in actions.iter().zip(&scope.reasoning_actions) {
             match &action.node.target.node {
                 ReasoningActionTarget::Action(reference) => {
                     // Reasoning action invokes an action definition
                     if let Some(action_ref) = Self::extract_action_name(reference) {
-                        if let Some(&target_idx) = self
-                            .action_defs
-                            .get(&(topic_name.to_string(), action_ref.clone()))
+                        if let Some(&target_idx) =
+                            scope.action_defs.get(&self.policy.key(&action_ref))
                         {
-                            self.graph
-                                .add_edge(reasoning_idx, target_idx, RefEdge::Invokes);
+                            self.add_reference_edge(
+                                reasoning_idx,
+                                target_idx,
+                                RefEdge::Invokes,
+                                Self::target_reference_span(&action.node.target, reference),
+                            );
                         } else {
                             self.unresolved_references
                                 .push(ValidationError::UnresolvedReference {
@@ -300,6 +687,11 @@ impl RefGraphBuilder {
                                         action.node.target.span.end,
                                     ),
                                     context: format!("topic {}", topic_name),
+                                    suggestion: self.suggestion(
+                                        "actions",
+                                        Some(topic_name),
+                                        &action_ref,
+                                    ),
                                 });
                         }
                     }
@@ -307,9 +699,14 @@ impl RefGraphBuilder {
                 ReasoningActionTarget::TransitionTo(reference) => {
                     // Transition to another topic
                     if let Some(target_topic) = Self::extract_topic_from_ref(reference) {
-                        if let Some(&target_idx) = self.topics.get(&target_topic) {
-                            self.graph
-                                .add_edge(topic_idx, target_idx, RefEdge::TransitionsTo);
+                        if let Some(&target_idx) = self.topics.get(&self.policy.key(&target_topic))
+                        {
+                            self.add_reference_edge(
+                                topic_idx,
+                                target_idx,
+                                RefEdge::TransitionsTo,
+                                Self::target_reference_span(&action.node.target, reference),
+                            );
                         } else {
                             self.unresolved_references
                                 .push(ValidationError::UnresolvedReference {
@@ -320,6 +717,7 @@ impl RefGraphBuilder {
                                         action.node.target.span.end,
                                     ),
                                     context: format!("topic {}", topic_name),
+                                    suggestion: self.suggestion("topic", None, &target_topic),
                                 });
                         }
                     }
@@ -327,9 +725,14 @@ impl RefGraphBuilder {
                 ReasoningActionTarget::TopicDelegate(reference) => {
                     // Delegate to another topic
                     if let Some(target_topic) = Self::extract_topic_from_ref(reference) {
-                        if let Some(&target_idx) = self.topics.get(&target_topic) {
-                            self.graph
-                                .add_edge(topic_idx, target_idx, RefEdge::Delegates);
+                        if let Some(&target_idx) = self.topics.get(&self.policy.key(&target_topic))
+                        {
+                            self.add_reference_edge(
+                                topic_idx,
+                                target_idx,
+                                RefEdge::Delegates,
+                                Self::target_reference_span(&action.node.target, reference),
+                            );
                         } else {
                             self.unresolved_references
                                 .push(ValidationError::UnresolvedReference {
@@ -340,62 +743,20 @@ impl RefGraphBuilder {
                                         action.node.target.span.end,
                                     ),
                                     context: format!("topic {}", topic_name),
+                                    suggestion: self.suggestion("topic", None, &target_topic),
                                 });
                         }
                     }
                 }
-                ReasoningActionTarget::Escalate | ReasoningActionTarget::SetVariables => {
-                    // Built-in utilities, no edges to add
+                ReasoningActionTarget::Escalate => {
+                    self.add_escalation_edges(reasoning_idx, &action.node.target);
                 }
-            }
-
-            // Add edges for with_clauses (reading variables)
-            for clause in &action.node.with_clauses {
-                self.add_with_value_edges(reasoning_idx, &clause.node.value);
-            }
-
-            // Add edges for set_clauses (writing variables)
-            for clause in &action.node.set_clauses {
-                let target_ref = &clause.node.target.node;
-                if target_ref.namespace == "variables" {
-                    // Use first path segment as the variable name; additional
-                    // segments are property accesses on object-typed variables
-                    // (e.g. @variables.user_stats.completed_tasks → "user_stats").
-                    let var_name = target_ref
-                        .path
-                        .first()
-                        .map_or_else(|| target_ref.path.join("."), |first| first.clone());
-                    if let Some(&var_idx) = self.variables.get(&var_name) {
-                        self.graph.add_edge(reasoning_idx, var_idx, RefEdge::Writes);
-                        // Validate property access: dot notation only valid on object types
-                        if target_ref.path.len() > 1 {
-                            if let Some(ty) = self.variable_types.get(&var_name) {
-                                if *ty != Type::Object {
-                                    self.unresolved_references.push(
-                                        ValidationError::InvalidPropertyAccess {
-                                            reference: target_ref.full_path(),
-                                            variable: var_name,
-                                            variable_type: Self::type_display_name(ty),
-                                            span: (
-                                                clause.node.target.span.start,
-                                                clause.node.target.span.end,
-                                            ),
-                                        },
-                                    );
-                                }
-                            }
-                        }
-                    } else {
-                        self.unresolved_references
-                            .push(ValidationError::UnresolvedReference {
-                                reference: target_ref.full_path(),
-                                namespace: "variables".to_string(),
-                                span: (clause.node.target.span.start, clause.node.target.span.end),
-                                context: format!("set clause in topic {}", topic_name),
-                            });
-                    }
+                ReasoningActionTarget::SetVariables => {
+                    // Built-in utility, no edges to add
                 }
             }
+
+            self.add_action_clause_edges(reasoning_idx, topic_name, &action.node);
         }
         Ok(())
     }
@@ -420,11 +781,13 @@ impl RefGraphBuilder {
         part: &crate::Spanned<InstructionPart>,
     ) {
         match &part.node {
-            InstructionPart::Text(_) => {}
+            InstructionPart::Text(_) | InstructionPart::Heading { .. } => {}
             InstructionPart::Interpolation(expr) => {
+                // The part's span includes the `{!` and `}` delimiters
                 let spanned_expr = crate::Spanned {
                     node: expr.clone(),
-                    span: part.span.clone(),
+                    span: part.span.start + 2
+                        ..part.span.end.saturating_sub(1).max(part.span.start + 2),
                 };
                 self.add_expression_edges(node_idx, &spanned_expr);
             }
@@ -446,6 +809,54 @@ impl RefGraphBuilder {
         }
     }
 
+    /// Add a write edge for the `@variables` target of a set clause or
+    /// `set` statement.
+    fn add_write_edge(
+        &mut self,
+        from_idx: NodeIndex,
+        target: &crate::Spanned<Reference>,
+        topic_name: &str,
+    ) {
+        let target_ref = &target.node;
+        if target_ref.namespace != "variables" {
+            return;
+        }
+        // Use first path segment as the variable name; additional
+        // segments are property accesses on object-typed variables
+        // (e.g. @variables.user_stats.completed_tasks → "user_stats").
+        let var_name = target_ref
+            .path
+            .first()
+            .map_or_else(|| target_ref.path.join("."), |first| first.clone());
+        let span = (target.span.start, target.span.end);
+        if let Some(&var_idx) = self.variables.get(&self.policy.key(&var_name)) {
+            self.add_reference_edge(from_idx, var_idx, RefEdge::Writes, span);
+            // Validate property access: dot notation only valid on object types
+            if target_ref.path.len() > 1 {
+                if let Some(ty) = self.variable_types.get(&self.policy.key(&var_name)) {
+                    if *ty != Type::Object {
+                        self.unresolved_references
+                            .push(ValidationError::InvalidPropertyAccess {
+                                reference: target_ref.full_path(),
+                                variable: var_name,
+                                variable_type: Self::type_display_name(ty),
+                                span,
+                            });
+                    }
+                }
+            }
+        } else {
+            self.unresolved_references
+                .push(ValidationError::UnresolvedReference {
+                    reference: target_ref.full_path(),
+                    namespace: "variables".to_string(),
+                    span,
+                    context: format!("set clause in topic {}", topic_name),
+                    suggestion: self.suggestion("variables", None, &var_name),
+                });
+        }
+    }
+
     /// Add edges for variable reads within a with clause value.
     fn add_with_value_edges(
         &mut self,
@@ -475,22 +886,16 @@ impl RefGraphBuilder {
                         .path
                         .first()
                         .map_or_else(|| reference.path.join("."), |first| first.clone());
-                    if let Some(&var_idx) = self.variables.get(&var_name) {
-                        self.graph.add_edge(from_idx, var_idx, RefEdge::Reads);
+                    if let Some(&var_idx) = self.variables.get(&self.policy.key(&var_name)) {
+                        self.add_reference_edge(
+                            from_idx,
+                            var_idx,
+                            RefEdge::Reads,
+                            Self::expr_reference_span(expr, reference),
+                        );
                         // Validate property access: dot notation only valid on object types
                         if reference.path.len() > 1 {
-                            if let Some(ty) = self.variable_types.get(&var_name) {
-                                if *ty != Type::Object {
-                                    self.unresolved_references.push(
-                                        ValidationError::InvalidPropertyAccess {
-                                            reference: reference.full_path(),
-                                            variable: var_name,
-                                            variable_type: Self::type_display_name(ty),
-                                            span: (expr.span.start, expr.span.end),
-                                        },
-                                    );
-                                }
-                            }
+                            self.check_property_access(&var_name, reference, expr);
                         }
                     } else {
                         self.unresolved_references
@@ -499,6 +904,7 @@ impl RefGraphBuilder {
                                 namespace: "variables".to_string(),
                                 span: (expr.span.start, expr.span.end),
                                 context: "variable read".to_string(),
+                                suggestion: self.suggestion("variables", None, &var_name),
                             });
                     }
                 } else if reference.namespace == "actions" {
@@ -513,9 +919,14 @@ impl RefGraphBuilder {
                         if let Some(action_ref) = Self::extract_action_name(reference) {
                             if let Some(&action_idx) = self
                                 .action_defs
-                                .get(&(topic_name.clone(), action_ref.clone()))
+                                .get(&(self.policy.key(&topic_name), self.policy.key(&action_ref)))
                             {
-                                self.graph.add_edge(from_idx, action_idx, RefEdge::Invokes);
+                                self.add_reference_edge(
+                                    from_idx,
+                                    action_idx,
+                                    RefEdge::Invokes,
+                                    Self::expr_reference_span(expr, reference),
+                                );
                             } else {
                                 self.unresolved_references.push(
                                     ValidationError::UnresolvedReference {
@@ -523,6 +934,11 @@ impl RefGraphBuilder {
                                         namespace: "actions".to_string(),
                                         span: (expr.span.start, expr.span.end),
                                         context: format!("topic {}", topic_name),
+                                        suggestion: self.suggestion(
+                                            "actions",
+                                            Some(&topic_name),
+                                            &action_ref,
+                                        ),
                                     },
                                 );
                             }
@@ -546,7 +962,7 @@ impl RefGraphBuilder {
                 self.add_expression_edges(from_idx, then_expr);
                 self.add_expression_edges(from_idx, else_expr);
             }
-            Expr::List(items) => {
+            Expr::Template(items) | Expr::List(items) => {
                 for item in items {
                     self.add_expression_edges(from_idx, item);
                 }
@@ -558,22 +974,103 @@ impl RefGraphBuilder {
             }
             Expr::Property { object, .. } => {
                 self.add_expression_edges(from_idx, object);
+                // Expressions parse `@variables.x.field` as a property access
+                // on `@variables.x`
+                if let (Expr::Reference(reference), Some(path)) =
+                    (&object.node, expr.node.reference_path())
+                {
+                    if let ("variables", [var_name]) =
+                        (reference.namespace.as_str(), reference.path.as_slice())
+                    {
+                        self.check_property_access(var_name, &path, expr);
+                    }
+                }
             }
             Expr::Index { object, index } => {
                 self.add_expression_edges(from_idx, object);
                 self.add_expression_edges(from_idx, index);
             }
+            Expr::Call { args, .. } => {
+                for arg in args {
+                    self.add_expression_edges(from_idx, arg);
+                }
+            }
             // Literals and slot-fill don't have references
-            Expr::String(_) | Expr::Number(_) | Expr::Bool(_) | Expr::None | Expr::SlotFill => {}
+            Expr::String(_)
+            | Expr::Number(_)
+            | Expr::Date(_)
+            | Expr::Currency(_)
+            | Expr::Bool(_)
+            | Expr::None
+            | Expr::SlotFill => {}
         }
     }
 
+    /// Report property access on a variable that isn't an object.
+    fn check_property_access(
+        &mut self,
+        var_name: &str,
+        reference: &Reference,
+        expr: &crate::Spanned<Expr>,
+    ) {
+        if let Some(ty) = self.variable_types.get(&self.policy.key(var_name)) {
+            if *ty != Type::Object {
+                self.unresolved_references
+                    .push(ValidationError::InvalidPropertyAccess {
+                        reference: reference.full_path(),
+                        variable: var_name.to_string(),
+                        variable_type: Self::type_display_name(ty),
+                        span: (expr.span.start, expr.span.end),
+                    });
+            }
+        }
+    }
+
+    /// Link an `@utils.escalate` reasoning action to the connections it
+    /// can route through.
+    ///
+    /// The runtime picks the connection for the session's channel, so every
+    /// defined connection is a possible target.
+    fn add_escalation_edges(
+        &mut self,
+        reasoning_idx: NodeIndex,
+        target: &crate::Spanned<ReasoningActionTarget>,
+    ) {
+        let span = (target.span.start, target.span.end);
+        let connections: Vec<NodeIndex> = self.connections.iter().map(|(_, idx)| *idx).collect();
+        for connection_idx in connections {
+            self.add_reference_edge(reasoning_idx, connection_idx, RefEdge::Escalates, span);
+        }
+    }
+
+    /// Add an edge for a reference, recording where the reference is.
+    fn add_reference_edge(&mut self, from: NodeIndex, to: NodeIndex, edge: RefEdge, span: Span) {
+        let idx = self.graph.add_edge(from, to, edge);
+        self.edge_spans.insert(idx, span);
+    }
+
+    /// Span of the reference in a reasoning action target, which ends the
+    /// target (`@utils.transition to @topic.x`).
+    fn target_reference_span(
+        target: &crate::Spanned<ReasoningActionTarget>,
+        reference: &Reference,
+    ) -> Span {
+        let start = target.span.end.saturating_sub(reference.full_path().len());
+        (start, target.span.end)
+    }
+
+    /// Span of a reference expression, without any property access.
+    fn expr_reference_span(expr: &crate::Spanned<Expr>, reference: &Reference) -> Span {
+        (expr.span.start, expr.span.start + reference.full_path().len())
+    }
+
     /// Extract topic name from a @topic.name reference.
+    ///
+    /// Deeper paths like `@topic.a.b` name no topic; validation reports them.
     fn extract_topic_from_ref(reference: &Reference) -> Option<String> {
-        if reference.namespace == "topic" && !reference.path.is_empty() {
-            Some(reference.path[0].clone())
-        } else {
-            None
+        match reference.path.as_slice() {
+            [name] if reference.namespace == "topic" => Some(name.clone()),
+            _ => None,
         }
     }
 
@@ -0,0 +1,716 @@
+//! Variable data-flow analysis.
+//!
+//! Tracks where each `@variables.*` is written (`set`) and read (conditions,
+//! interpolations, `with` clauses, assigned values), per topic, and reports:
+//!
+//! - **Read before set**: a variable without a default value is read in a
+//!   topic before anything on a path from `start_agent` can have set it.
+//! - **Overwritten before read**: a `before_reasoning` / `after_reasoning`
+//!   directive sets a variable that is set again before anything reads it.
+//!
+//! Within a topic, statements run in this order: `before_reasoning`,
+//! instructions, reasoning actions, `after_reasoning`. Reasoning actions are
+//! invoked by the LLM in no particular order, so a write in one of them may
+//! precede a read in any other.
+//!
+//! # Example
+//!
+//! ```rust
+//! use busbar_sf_agentscript::graph::{analyze_dataflow, RefGraph};
+//!
+//! let source = r#"
+//! variables:
+//!    count: mutable number = 0
+//!
+//! topic main:
+//!    description: "Main"
+//!    before_reasoning:
+//!       set @variables.count = 1
+//!       set @variables.count = 2
+//! "#;
+//! let ast = busbar_sf_agentscript::parse(source).unwrap();
+//! let graph = RefGraph::from_ast(&ast).unwrap();
+//! let report = analyze_dataflow(&ast, &graph);
+//! assert_eq!(report.variables["count"].writes.len(), 2);
+//! assert_eq!(report.issues.len(), 1);
+//! ```
+
+use super::edges::RefEdge;
+use super::nodes::Span;
+use super::RefGraph;
+use crate::ast::{
+    DirectiveBlock, Expr, InstructionPart, Instructions, ReasoningAction, ReasoningBlock,
+    Reference, SetClause, Stmt, VariableKind, WithClause, WithValue,
+};
+use crate::{AgentFile, Spanned};
+use indexmap::IndexMap;
+use petgraph::graph::NodeIndex;
+use petgraph::visit::EdgeRef;
+use petgraph::Direction;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+/// How a variable is accessed at a site.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AccessKind {
+    /// Written by `set`
+    Set,
+    /// Read in an `if` or `available when` condition
+    Condition,
+    /// Read in a `{!...}` instruction interpolation
+    Interpolation,
+    /// Read in a `with` clause
+    WithClause,
+    /// Read in the value of a `set`
+    Assignment,
+}
+
+/// A single read or write of a variable.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AccessSite {
+    /// Topic containing the access (`"start_agent"` for the start agent)
+    pub topic: String,
+    /// How the variable is accessed
+    pub kind: AccessKind,
+    /// Source location
+    pub span: Span,
+}
+
+/// All reads and writes of one variable, in source order.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct VariableFlow {
+    /// `set` sites
+    pub writes: Vec<AccessSite>,
+    /// Read sites
+    pub reads: Vec<AccessSite>,
+}
+
+/// A problem found by data-flow analysis.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DataFlowIssue {
+    /// A variable with no default is read before any path can set it
+    ReadBeforeSet {
+        /// The variable name
+        variable: String,
+        /// Topic containing the read
+        topic: String,
+        /// Location of the first such read in the topic
+        span: Span,
+    },
+    /// A variable is set, then set again without being read in between
+    OverwrittenBeforeRead {
+        /// The variable name
+        variable: String,
+        /// Topic containing the writes
+        topic: String,
+        /// Location of the first (dead) write
+        span: Span,
+        /// Location of the write that replaces it
+        overwritten_at: Span,
+    },
+}
+
+impl DataFlowIssue {
+    /// Get the primary span for this issue.
+    pub fn span(&self) -> Span {
+        match self {
+            DataFlowIssue::ReadBeforeSet { span, .. }
+            | DataFlowIssue::OverwrittenBeforeRead { span, .. } => *span,
+        }
+    }
+
+    /// Get a human-readable message.
+    pub fn message(&self) -> String {
+        match self {
+            DataFlowIssue::ReadBeforeSet {
+                variable, topic, ..
+            } => format!(
+                "Variable '{}' is read in topic '{}' before it can have been set",
+                variable, topic
+            ),
+            DataFlowIssue::OverwrittenBeforeRead {
+                variable, topic, ..
+            } => format!(
+                "Value set to '{}' in topic '{}' is overwritten before it is read",
+                variable, topic
+            ),
+        }
+    }
+}
+
+/// Result of [`analyze_dataflow`].
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DataFlowReport {
+    /// Accesses per variable, in declaration order. Variables that are
+    /// accessed but not declared are listed after the declared ones.
+    pub variables: IndexMap<String, VariableFlow>,
+    /// Issues, sorted by span
+    pub issues: Vec<DataFlowIssue>,
+}
+
+/// Analyze reads and writes of `@variables.*` across the agent.
+///
+/// `graph` must be built from `ast`; it supplies the topic transitions used
+/// to decide which topics can run before which.
+pub fn analyze_dataflow(ast: &AgentFile, graph: &RefGraph) -> DataFlowReport {
+    let mut report = DataFlowReport::default();
+    let mut uninitialized = HashSet::new();
+    if let Some(vars) = &ast.variables {
+        for var in &vars.node.variables {
+            report
+                .variables
+                .insert(var.node.name.node.clone(), VariableFlow::default());
+            if var.node.kind == VariableKind::Mutable && var.node.default.is_none() {
+                uninitialized.insert(var.node.name.node.as_str());
+            }
+        }
+    }
+
+    let mut scopes = Vec::new();
+    if let Some(sa) = &ast.start_agent {
+        let sa = &sa.node;
+        scopes.push(Scope::collect(
+            "start_agent",
+            graph.get_start_agent(),
+            sa.before_reasoning.as_ref(),
+            sa.reasoning.as_ref(),
+            sa.after_reasoning.as_ref(),
+        ));
+    }
+    for topic in &ast.topics {
+        let t = &topic.node;
+        scopes.push(Scope::collect(
+            &t.name.node,
+            graph.get_topic(&t.name.node),
+            t.before_reasoning.as_ref(),
+            t.reasoning.as_ref(),
+            t.after_reasoning.as_ref(),
+        ));
+    }
+
+    for scope in &scopes {
+        for event in &scope.events {
+            let flow = report.variables.entry(event.variable.clone()).or_default();
+            let site = AccessSite {
+                topic: scope.name.clone(),
+                kind: event.kind,
+                span: event.span,
+            };
+            if event.kind == AccessKind::Set {
+                flow.writes.push(site);
+            } else {
+                flow.reads.push(site);
+            }
+        }
+        report.issues.extend(scope.overwrites.iter().cloned());
+    }
+
+    report
+        .issues
+        .extend(find_reads_before_set(graph, &scopes, &uninitialized));
+    report.issues.sort_by_key(|issue| issue.span());
+    report
+}
+
+/// Report the first read per (topic, variable) that no earlier write can reach.
+fn find_reads_before_set(
+    graph: &RefGraph,
+    scopes: &[Scope],
+    uninitialized: &HashSet<&str>,
+) -> Vec<DataFlowIssue> {
+    let reachable = graph
+        .get_start_agent()
+        .map(|start| transition_closure(graph, start, Direction::Outgoing));
+    let writers: HashMap<NodeIndex, HashSet<&str>> = scopes
+        .iter()
+        .filter_map(|scope| {
+            let node = scope.node?;
+            let written = scope
+                .events
+                .iter()
+                .filter(|e| e.kind == AccessKind::Set)
+                .map(|e| e.variable.as_str())
+                .collect();
+            Some((node, written))
+        })
+        .collect();
+
+    let mut issues = Vec::new();
+    for scope in scopes {
+        let Some(node) = scope.node else { continue };
+        if reachable.as_ref().is_some_and(|r| !r.contains(&node)) {
+            continue; // Dead topic, already reported as unreachable
+        }
+        let ancestors = transition_closure(graph, node, Direction::Incoming);
+        let set_upstream = |var: &str| {
+            ancestors
+                .iter()
+                .filter(|&&a| a != node || is_on_cycle(graph, node))
+                .any(|a| writers.get(a).is_some_and(|w| w.contains(var)))
+        };
+
+        let mut reported = HashSet::new();
+        for read in scope.events.iter().filter(|e| e.kind != AccessKind::Set) {
+            let var = read.variable.as_str();
+            if !uninitialized.contains(var) || reported.contains(var) {
+                continue;
+            }
+            let set_before = scope
+                .events
+                .iter()
+                .any(|w| w.kind == AccessKind::Set && w.variable == var && w.may_precede(read));
+            if !set_before && !set_upstream(var) {
+                reported.insert(var);
+                issues.push(DataFlowIssue::ReadBeforeSet {
+                    variable: var.to_string(),
+                    topic: scope.name.clone(),
+                    span: read.span,
+                });
+            }
+        }
+    }
+    issues
+}
+
+fn is_transition(edge: &RefEdge) -> bool {
+    matches!(edge, RefEdge::Routes | RefEdge::TransitionsTo | RefEdge::Delegates)
+}
+
+/// Nodes reachable from `start` over transition edges in `direction`,
+/// including `start` itself.
+fn transition_closure(
+    graph: &RefGraph,
+    start: NodeIndex,
+    direction: Direction,
+) -> HashSet<NodeIndex> {
+    let mut seen = HashSet::new();
+    let mut stack = vec![start];
+    while let Some(idx) = stack.pop() {
+        if seen.insert(idx) {
+            for edge in graph.graph.edges_directed(idx, direction) {
+                if is_transition(edge.weight()) {
+                    stack.push(match direction {
+                        Direction::Outgoing => edge.target(),
+                        Direction::Incoming => edge.source(),
+                    });
+                }
+            }
+        }
+    }
+    seen
+}
+
+/// Whether a topic can transition back to itself.
+fn is_on_cycle(graph: &RefGraph, node: NodeIndex) -> bool {
+    graph
+        .graph
+        .edges_directed(node, Direction::Incoming)
+        .filter(|e| is_transition(e.weight()))
+        .any(|e| transition_closure(graph, node, Direction::Outgoing).contains(&e.source()))
+}
+
+// ============================================================================
+// Collection
+// ============================================================================
+
+/// Where in a topic an access happens, in execution order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Phase {
+    BeforeReasoning,
+    Instructions,
+    ReasoningActions,
+    AfterReasoning,
+}
+
+#[derive(Debug)]
+struct Event {
+    variable: String,
+    kind: AccessKind,
+    span: Span,
+    phase: Phase,
+    /// Position in the topic's access sequence
+    order: usize,
+}
+
+impl Event {
+    /// Whether this access can happen before `other` in the same topic.
+    fn may_precede(&self, other: &Event) -> bool {
+        match self.phase.cmp(&other.phase) {
+            std::cmp::Ordering::Less => true,
+            std::cmp::Ordering::Greater => false,
+            std::cmp::Ordering::Equal => {
+                self.phase == Phase::ReasoningActions || self.order < other.order
+            }
+        }
+    }
+}
+
+/// Accesses within one topic (or the start agent).
+struct Scope {
+    name: String,
+    node: Option<NodeIndex>,
+    events: Vec<Event>,
+    overwrites: Vec<DataFlowIssue>,
+}
+
+impl Scope {
+    fn collect(
+        name: &str,
+        node: Option<NodeIndex>,
+        before: Option<&Spanned<DirectiveBlock>>,
+        reasoning: Option<&Spanned<ReasoningBlock>>,
+        after: Option<&Spanned<DirectiveBlock>>,
+    ) -> Self {
+        let mut scope = Scope {
+            name: name.to_string(),
+            node,
+            events: Vec::new(),
+            overwrites: Vec::new(),
+        };
+        // Writes not yet read, carried through the topic in execution order
+        let mut pending = HashMap::new();
+
+        if let Some(block) = before {
+            scope.stmts(&block.node.statements, Phase::BeforeReasoning, &mut pending);
+        }
+        if let Some(reasoning) = reasoning {
+            if let Some(instructions) = &reasoning.node.instructions {
+                if let Instructions::Dynamic(parts) = &instructions.node {
+                    scope.instruction_parts(parts, &mut pending);
+                }
+            }
+            if let Some(actions) = &reasoning.node.actions {
+                for action in &actions.node {
+                    scope.reasoning_action(&action.node, &mut pending);
+                }
+            }
+        }
+        if let Some(block) = after {
+            scope.stmts(&block.node.statements, Phase::AfterReasoning, &mut pending);
+        }
+        scope
+    }
+
+    fn push(&mut self, variable: String, kind: AccessKind, span: Span, phase: Phase) {
+        let order = self.events.len();
+        self.events.push(Event {
+            variable,
+            kind,
+            span,
+            phase,
+            order,
+        });
+    }
+
+    fn read_expr(
+        &mut self,
+        expr: &Spanned<Expr>,
+        kind: AccessKind,
+        phase: Phase,
+        pending: &mut Pending,
+    ) {
+        let mut reads = Vec::new();
+        expr_reads(&expr.node, &expr.span, &mut reads);
+        for (variable, span) in reads {
+            pending.remove(&variable);
+            self.push(variable, kind, span, phase);
+        }
+    }
+
+    fn write(&mut self, target: &Spanned<Reference>, phase: Phase, pending: &mut Pending) {
+        let Some(variable) = variable_name(&target.node) else {
+            return;
+        };
+        let span = (target.span.start, target.span.end);
+        // Property writes (`@variables.obj.field`) update part of the value
+        if target.node.path.len() == 1 {
+            if let Some(previous) = pending.insert(variable.clone(), span) {
+                self.overwrites.push(DataFlowIssue::OverwrittenBeforeRead {
+                    variable: variable.clone(),
+                    topic: self.name.clone(),
+                    span: previous,
+                    overwritten_at: span,
+                });
+            }
+        } else {
+            pending.remove(&variable);
+        }
+        self.push(variable, AccessKind::Set, span, phase);
+    }
+
+    fn with_clauses(
+        &mut self,
+        clauses: &[Spanned<WithClause>],
+        phase: Phase,
+        pending: &mut Pending,
+    ) {
+        for clause in clauses {
+            let WithValue::Expr(expr) = &clause.node.value.node;
+            let expr = Spanned::new(expr.clone(), clause.node.value.span.clone());
+            self.read_expr(&expr, AccessKind::WithClause, phase, pending);
+        }
+    }
+
+    fn set_clauses(&mut self, clauses: &[Spanned<SetClause>], phase: Phase, pending: &mut Pending) {
+        for clause in clauses {
+            self.read_expr(&clause.node.source, AccessKind::Assignment, phase, pending);
+            self.write(&clause.node.target, phase, pending);
+        }
+    }
+
+    fn stmts(&mut self, stmts: &[Spanned<Stmt>], phase: Phase, pending: &mut Pending) {
+        for stmt in stmts {
+            match &stmt.node {
+                Stmt::Set { target, value } => {
+                    self.read_expr(value, AccessKind::Assignment, phase, pending);
+                    self.write(target, phase, pending);
+                }
+                Stmt::Run {
+                    with_clauses,
+                    set_clauses,
+                    ..
+                } => {
+                    self.with_clauses(with_clauses, phase, pending);
+                    self.set_clauses(set_clauses, phase, pending);
+                }
+                Stmt::If {
+                    condition,
+                    then_block,
+                    else_block,
+                } => {
+                    self.read_expr(condition, AccessKind::Condition, phase, pending);
+                    let mut else_pending = pending.clone();
+                    self.stmts(then_block, phase, pending);
+                    if let Some(else_block) = else_block {
+                        self.stmts(else_block, phase, &mut else_pending);
+                    }
+                    // A write is still unread if it is unread on either branch
+                    for (variable, span) in else_pending {
+                        pending.entry(variable).or_insert(span);
+                    }
+                }
+                Stmt::Transition { .. } | Stmt::Escalate | Stmt::Respond { .. } => {}
+            }
+        }
+    }
+
+    fn instruction_parts(&mut self, parts: &[Spanned<InstructionPart>], pending: &mut Pending) {
+        let phase = Phase::Instructions;
+        for part in parts {
+            match &part.node {
+                InstructionPart::Text(_) | InstructionPart::Heading { .. } => {}
+                InstructionPart::Interpolation(expr) => {
+                    let expr = Spanned::new(expr.clone(), part.span.clone());
+                    self.read_expr(&expr, AccessKind::Interpolation, phase, pending);
+                }
+                InstructionPart::Conditional {
+                    condition,
+                    then_parts,
+                    else_parts,
+                } => {
+                    self.read_expr(condition, AccessKind::Condition, phase, pending);
+                    self.instruction_parts(then_parts, pending);
+                    if let Some(else_parts) = else_parts {
+                        self.instruction_parts(else_parts, pending);
+                    }
+                }
+            }
+        }
+    }
+
+    fn reasoning_action(&mut self, action: &ReasoningAction, pending: &mut Pending) {
+        let phase = Phase::ReasoningActions;
+        if let Some(cond) = &action.available_when {
+            self.read_expr(cond, AccessKind::Condition, phase, pending);
+        }
+        self.with_clauses(&action.with_clauses, phase, pending);
+        for run in &action.run_clauses {
+            self.with_clauses(&run.node.with_clauses, phase, pending);
+        }
+        for clause in &action.if_clauses {
+            self.read_expr(&clause.node.condition, AccessKind::Condition, phase, pending);
+        }
+
+        // The LLM may or may not invoke the action, so its writes never
+        // overwrite earlier ones for certain.
+        let mut maybe = Pending::new();
+        self.set_clauses(&action.set_clauses, phase, &mut maybe);
+        for run in &action.run_clauses {
+            self.set_clauses(&run.node.set_clauses, phase, &mut maybe);
+        }
+        for variable in maybe.keys() {
+            pending.remove(variable);
+        }
+    }
+}
+
+/// Unread writes: variable name to the span of the write.
+type Pending = HashMap<String, Span>;
+
+fn variable_name(reference: &Reference) -> Option<String> {
+    if reference.namespace == "variables" {
+        reference.path.first().cloned()
+    } else {
+        None
+    }
+}
+
+/// Collect `@variables.*` reads in an expression, with their spans.
+fn expr_reads(expr: &Expr, span: &std::ops::Range<usize>, out: &mut Vec<(String, Span)>) {
+    let child =
+        |e: &Spanned<Expr>, out: &mut Vec<(String, Span)>| expr_reads(&e.node, &e.span, out);
+    match expr {
+        Expr::Reference(r) => {
+            if let Some(name) = variable_name(r) {
+                out.push((name, (span.start, span.end)));
+            }
+        }
+        Expr::Template(items) | Expr::List(items) => items.iter().for_each(|i| child(i, out)),
+        Expr::Object(fields) => fields.values().for_each(|v| child(v, out)),
+        Expr::BinOp { left, right, .. } => {
+            child(left, out);
+            child(right, out);
+        }
+        Expr::UnaryOp { operand, .. } => child(operand, out),
+        Expr::Ternary {
+            condition,
+            then_expr,
+            else_expr,
+        } => {
+            child(condition, out);
+            child(then_expr, out);
+            child(else_expr, out);
+        }
+        Expr::Property { object, .. } => child(object, out),
+        Expr::Index { object, index } => {
+            child(object, out);
+            child(index, out);
+        }
+        Expr::Call { args, .. } => args.iter().for_each(|a| child(a, out)),
+        Expr::String(_)
+        | Expr::Number(_)
+        | Expr::Date(_)
+        | Expr::Currency(_)
+        | Expr::Bool(_)
+        | Expr::None
+        | Expr::SlotFill => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn analyze(source: &str) -> DataFlowReport {
+        let ast = crate::parse(source).expect("Failed to parse");
+        let graph = RefGraph::from_ast(&ast).expect("Failed to build graph");
+        analyze_dataflow(&ast, &graph)
+    }
+
+    #[test]
+    fn test_read_before_set_across_topics() {
+        // order_id has no default. It's set in lookup, which runs after
+        // main, so main's read is too early while summary's is fine.
+        let source = r#"config:
+   agent_name: "Test"
+
+variables:
+   order_id: mutable string
+   greeting: mutable string = "hi"
+
+start_agent selector:
+   description: "Route"
+   reasoning:
+      instructions: "Select"
+      actions:
+         go_main: @utils.transition to @topic.main
+            description: "Main"
+
+topic main:
+   description: "Main"
+   reasoning:
+      instructions: ->
+         | Order {!@variables.order_id}, {!@variables.greeting}
+      actions:
+         go_lookup: @utils.transition to @topic.lookup
+            description: "Lookup"
+
+topic lookup:
+   description: "Lookup"
+   before_reasoning:
+      set @variables.order_id = "123"
+   reasoning:
+      instructions: "Lookup"
+      actions:
+         go_summary: @utils.transition to @topic.summary
+            description: "Summary"
+
+topic summary:
+   description: "Summary"
+   before_reasoning:
+      if @variables.order_id != "":
+         set @variables.greeting = "done"
+"#;
+        let report = analyze(source);
+        assert_eq!(report.variables["order_id"].writes.len(), 1);
+        assert_eq!(report.variables["order_id"].reads.len(), 2);
+        assert_eq!(report.variables["order_id"].reads[0].kind, AccessKind::Interpolation);
+        assert_eq!(report.variables["order_id"].reads[1].kind, AccessKind::Condition);
+
+        let read_before_set: Vec<_> = report
+            .issues
+            .iter()
+            .filter_map(|i| match i {
+                DataFlowIssue::ReadBeforeSet {
+                    variable, topic, ..
+                } => Some((variable.as_str(), topic.as_str())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(read_before_set, [("order_id", "main")]);
+    }
+
+    #[test]
+    fn test_overwritten_before_read() {
+        let source = r#"config:
+   agent_name: "Test"
+
+variables:
+   status: mutable string = ""
+   count: mutable number = 0
+
+topic main:
+   description: "Main"
+   before_reasoning:
+      set @variables.status = "a"
+      if @variables.count > 0:
+         set @variables.status = "b"
+      set @variables.count = @variables.count + 1
+      set @variables.count = @variables.count + 1
+   after_reasoning:
+      set @variables.status = "c"
+"#;
+        let report = analyze(source);
+        let overwritten: Vec<_> = report
+            .issues
+            .iter()
+            .filter_map(|i| match i {
+                DataFlowIssue::OverwrittenBeforeRead {
+                    span,
+                    overwritten_at,
+                    ..
+                } => Some((&source[span.0..span.1], &source[overwritten_at.0..overwritten_at.1])),
+                _ => None,
+            })
+            .collect();
+        // "a" is replaced inside the if, and either "a" or "b" is replaced by
+        // "c" in after_reasoning. The count increments read the old value.
+        assert_eq!(overwritten.len(), 2, "{:?}", report.issues);
+        assert!(report
+            .issues
+            .iter()
+            .all(|i| i.message().contains("'status'")));
+    }
+}
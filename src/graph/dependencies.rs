@@ -8,11 +8,16 @@
 //! - **Connections**: Referenced for escalation routing
 //!
 //! This enables offline analysis of agent dependencies without round-tripping to the org.
+//!
+//! For CI, [`DependencyReport::to_manifest`] produces a sorted, span-free
+//! [`DependencyManifest`] that can be committed and diffed, and
+//! [`DependencyReport::diff`] lists the dependencies a change introduces.
 
-use crate::ast::{ActionDef, ConnectionBlock, KnowledgeBlock};
+use crate::ast::{ActionDef, ConnectionBlock, Expr, KnowledgeBlock, KnowledgeEntry};
 use crate::AgentFile;
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt::Write;
 
 /// Type of Salesforce org dependency.
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -156,6 +161,153 @@ impl DependencyReport {
             + self.prompt_templates.len()
             + self.external_services.len()
     }
+
+    /// A machine-readable manifest of every dependency and where it is used.
+    pub fn to_manifest(&self) -> DependencyManifest {
+        let mut entries: BTreeMap<(&str, String), BTreeSet<ManifestUsage>> = BTreeMap::new();
+        for dep in &self.all_dependencies {
+            entries
+                .entry((dep.dep_type.category(), dep.dep_type.name()))
+                .or_default()
+                .insert(ManifestUsage {
+                    used_in: dep.used_in.clone(),
+                    action: dep.action_name.clone(),
+                });
+        }
+        DependencyManifest {
+            version: DependencyManifest::VERSION,
+            dependencies: entries
+                .into_iter()
+                .map(|((category, name), used_by)| ManifestEntry {
+                    category: category.to_string(),
+                    name,
+                    used_by: used_by.into_iter().collect(),
+                })
+                .collect(),
+        }
+    }
+
+    /// The dependencies added and removed going from `self` to `other`,
+    /// e.g. from the base of a pull request to its head.
+    pub fn diff(&self, other: &DependencyReport) -> DependencyDiff {
+        self.to_manifest().diff(&other.to_manifest())
+    }
+}
+
+/// A sorted list of dependencies with their usages, without source spans,
+/// so that it only changes when the dependencies do.
+///
+/// ```rust
+/// use busbar_sf_agentscript::graph::extract_dependencies;
+///
+/// let source = r#"topic orders:
+///    description: "Orders"
+///    actions:
+///       lookup:
+///          description: "Find an order"
+///          target: "flow://GetOrder"
+/// "#;
+/// let manifest = extract_dependencies(&busbar_sf_agentscript::parse(source).unwrap()).to_manifest();
+/// assert_eq!(manifest.dependencies[0].category, "flow");
+/// assert_eq!(manifest.dependencies[0].name, "GetOrder");
+/// assert!(manifest.to_yaml().contains("- category: \"flow\""));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DependencyManifest {
+    /// Format version, bumped on incompatible changes.
+    pub version: u32,
+    /// Dependencies sorted by category, then name.
+    pub dependencies: Vec<ManifestEntry>,
+}
+
+/// One dependency in a [`DependencyManifest`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// The [`DependencyType::category`], e.g. `flow` or `apex_class`
+    pub category: String,
+    /// The [`DependencyType::name`]
+    pub name: String,
+    /// Where the dependency is used, sorted
+    pub used_by: Vec<ManifestUsage>,
+}
+
+/// A place a dependency is used.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ManifestUsage {
+    /// Topic name, `start_agent`, `knowledge`, or `connection`
+    pub used_in: String,
+    /// The action (or knowledge entry) that references the dependency
+    pub action: String,
+}
+
+impl DependencyManifest {
+    /// The current manifest format version.
+    pub const VERSION: u32 = 1;
+
+    /// The manifest as pretty-printed JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("manifest serializes")
+    }
+
+    /// The manifest as YAML.
+    pub fn to_yaml(&self) -> String {
+        // JSON strings are valid YAML double-quoted scalars
+        let quote = |s: &str| serde_json::to_string(s).expect("string serializes");
+        let mut yaml = format!("version: {}\n", self.version);
+        if self.dependencies.is_empty() {
+            yaml.push_str("dependencies: []\n");
+            return yaml;
+        }
+        yaml.push_str("dependencies:\n");
+        for entry in &self.dependencies {
+            writeln!(yaml, "  - category: {}", quote(&entry.category)).unwrap();
+            writeln!(yaml, "    name: {}", quote(&entry.name)).unwrap();
+            yaml.push_str("    used_by:\n");
+            for usage in &entry.used_by {
+                writeln!(yaml, "      - used_in: {}", quote(&usage.used_in)).unwrap();
+                writeln!(yaml, "        action: {}", quote(&usage.action)).unwrap();
+            }
+        }
+        yaml
+    }
+
+    /// The dependencies added and removed going from `self` to `other`.
+    /// Changes to where a dependency is used are not reported.
+    pub fn diff(&self, other: &DependencyManifest) -> DependencyDiff {
+        let key = |e: &ManifestEntry| (e.category.clone(), e.name.clone());
+        let before: HashSet<_> = self.dependencies.iter().map(key).collect();
+        let after: HashSet<_> = other.dependencies.iter().map(key).collect();
+        DependencyDiff {
+            added: other
+                .dependencies
+                .iter()
+                .filter(|e| !before.contains(&key(e)))
+                .cloned()
+                .collect(),
+            removed: self
+                .dependencies
+                .iter()
+                .filter(|e| !after.contains(&key(e)))
+                .cloned()
+                .collect(),
+        }
+    }
+}
+
+/// Dependencies introduced and dropped between two reports or manifests.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DependencyDiff {
+    /// New dependencies, with where the newer version uses them
+    pub added: Vec<ManifestEntry>,
+    /// Dependencies no longer used, with where the older version used them
+    pub removed: Vec<ManifestEntry>,
+}
+
+impl DependencyDiff {
+    /// Whether the dependencies are unchanged.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
 }
 
 /// Extract all Salesforce org dependencies from an AgentScript AST.
@@ -310,15 +462,35 @@ fn parse_action_target(target: &str) -> DependencyType {
     DependencyType::Custom(target.to_string())
 }
 
+/// The source a knowledge entry points at, if it names one.
+///
+/// Entries with string values (`policies: "KnowledgeBase://Policies"`) are
+/// sources; others, like `citations_enabled: True`, are settings.
+pub(super) fn knowledge_source(entry: &KnowledgeEntry) -> Option<&str> {
+    match &entry.value.node {
+        Expr::String(source) => Some(source),
+        _ => None,
+    }
+}
+
 /// Extract dependencies from knowledge block.
+///
+/// Each source is recorded by the name after its `scheme://` prefix, so
+/// `"KnowledgeBase://Policies"` becomes the knowledge base `Policies`.
 fn extract_from_knowledge(knowledge: &KnowledgeBlock, report: &mut DependencyReport) {
     for entry in &knowledge.entries {
-        let name = entry.node.name.node.clone();
+        let Some(source) = knowledge_source(&entry.node) else {
+            continue;
+        };
+        let name = source
+            .split_once("://")
+            .map_or(source, |(_, name)| name)
+            .to_string();
         report.knowledge_bases.insert(name.clone());
         report.all_dependencies.push(Dependency {
-            dep_type: DependencyType::KnowledgeBase(name.clone()),
+            dep_type: DependencyType::KnowledgeBase(name),
             used_in: "knowledge".to_string(),
-            action_name: name,
+            action_name: entry.node.name.node.clone(),
             span: (entry.span.start, entry.span.end),
         });
     }
@@ -384,6 +556,85 @@ mod tests {
         assert!(matches!(dep, DependencyType::ExternalService(name) if name == "WeatherAPI"));
     }
 
+    #[test]
+    fn test_knowledge_sources() {
+        let source = r#"config:
+   agent_name: "Test"
+
+knowledge:
+   policies: "KnowledgeBase://InsurancePolicies"
+   citations_enabled: True
+"#;
+        let ast = crate::parse(source).unwrap();
+        let report = extract_dependencies(&ast);
+
+        assert_eq!(report.knowledge_bases.len(), 1);
+        assert!(report.knowledge_bases.contains("InsurancePolicies"));
+        let deps = report.get_by_type("knowledge");
+        assert_eq!(deps.len(), 1);
+        assert_eq!(deps[0].action_name, "policies");
+    }
+
+    #[test]
+    fn test_manifest_and_diff() {
+        let base = r#"topic orders:
+   description: "Orders"
+   actions:
+      lookup:
+         description: "Find"
+         target: "flow://GetOrder"
+      refund:
+         description: "Refund"
+         target: "apex://RefundService"
+"#;
+        let head = r#"topic orders:
+   description: "Orders"
+   actions:
+      lookup:
+         description: "Find"
+         target: "flow://GetOrder"
+      summarize:
+         description: "Summarize"
+         target: "prompt://OrderSummary"
+topic billing:
+   description: "Billing"
+   actions:
+      lookup:
+         description: "Find"
+         target: "flow://GetOrder"
+"#;
+        let base = extract_dependencies(&crate::parse(base).unwrap());
+        let head = extract_dependencies(&crate::parse(head).unwrap());
+
+        let manifest = head.to_manifest();
+        let names: Vec<(&str, &str)> = manifest
+            .dependencies
+            .iter()
+            .map(|e| (e.category.as_str(), e.name.as_str()))
+            .collect();
+        assert_eq!(names, vec![("flow", "GetOrder"), ("prompt_template", "OrderSummary")]);
+        let used_in: Vec<&str> = manifest.dependencies[0]
+            .used_by
+            .iter()
+            .map(|u| u.used_in.as_str())
+            .collect();
+        assert_eq!(used_in, vec!["billing", "orders"]);
+
+        let json = manifest.to_json();
+        let parsed: DependencyManifest = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, manifest);
+        assert!(manifest
+            .to_yaml()
+            .starts_with("version: 1\ndependencies:\n  - category: \"flow\"\n"));
+
+        let diff = base.diff(&head);
+        assert_eq!(diff.added.len(), 1);
+        assert_eq!(diff.added[0].name, "OrderSummary");
+        assert_eq!(diff.removed.len(), 1);
+        assert_eq!(diff.removed[0].name, "RefundService");
+        assert!(head.diff(&head).is_empty());
+    }
+
     #[test]
     #[ignore = "Recipe file uses {} empty object literal which is not valid AgentScript"]
     fn test_full_dependency_extraction() {
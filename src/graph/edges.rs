@@ -30,21 +30,87 @@ pub enum RefEdge {
     Escalates,
 }
 
+/// The kind of a [`RefEdge`], for styling and filtering exports.
+///
+/// This is the `edge_type` of [`EdgeRepr`](super::EdgeRepr) and the other
+/// export types. The set of kinds and their serialized names (`"routes"`,
+/// `"transitions_to"`, ...) are stable: adding or renaming a kind is a
+/// breaking change, so downstream visualizers can match on them exhaustively.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EdgeKind {
+    /// StartAgent routes to a topic
+    Routes,
+    /// Topic transitions to another topic
+    TransitionsTo,
+    /// Topic delegates to another topic
+    Delegates,
+    /// Reasoning action invokes an action definition
+    Invokes,
+    /// Action reads a variable
+    Reads,
+    /// Action writes to a variable
+    Writes,
+    /// Action chains to another action
+    Chains,
+    /// Escalation routes to a connection
+    Escalates,
+}
+
+impl EdgeKind {
+    /// Every kind, in declaration order.
+    pub const ALL: [EdgeKind; 8] = [
+        EdgeKind::Routes,
+        EdgeKind::TransitionsTo,
+        EdgeKind::Delegates,
+        EdgeKind::Invokes,
+        EdgeKind::Reads,
+        EdgeKind::Writes,
+        EdgeKind::Chains,
+        EdgeKind::Escalates,
+    ];
+
+    /// The serialized name of this kind.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EdgeKind::Routes => "routes",
+            EdgeKind::TransitionsTo => "transitions_to",
+            EdgeKind::Delegates => "delegates",
+            EdgeKind::Invokes => "invokes",
+            EdgeKind::Reads => "reads",
+            EdgeKind::Writes => "writes",
+            EdgeKind::Chains => "chains",
+            EdgeKind::Escalates => "escalates",
+        }
+    }
+}
+
+impl std::fmt::Display for EdgeKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 impl RefEdge {
-    /// Get a human-readable label for this edge type.
-    pub fn label(&self) -> &'static str {
+    /// Get the kind of this edge.
+    pub fn kind(&self) -> EdgeKind {
         match self {
-            RefEdge::Routes => "routes",
-            RefEdge::TransitionsTo => "transitions_to",
-            RefEdge::Delegates => "delegates",
-            RefEdge::Invokes => "invokes",
-            RefEdge::Reads => "reads",
-            RefEdge::Writes => "writes",
-            RefEdge::Chains => "chains",
-            RefEdge::Escalates => "escalates",
+            RefEdge::Routes => EdgeKind::Routes,
+            RefEdge::TransitionsTo => EdgeKind::TransitionsTo,
+            RefEdge::Delegates => EdgeKind::Delegates,
+            RefEdge::Invokes => EdgeKind::Invokes,
+            RefEdge::Reads => EdgeKind::Reads,
+            RefEdge::Writes => EdgeKind::Writes,
+            RefEdge::Chains => EdgeKind::Chains,
+            RefEdge::Escalates => EdgeKind::Escalates,
         }
     }
 
+    /// Get a human-readable label for this edge type.
+    pub fn label(&self) -> &'static str {
+        self.kind().as_str()
+    }
+
     /// Check if this is a control flow edge (affects execution path).
     pub fn is_control_flow(&self) -> bool {
         matches!(
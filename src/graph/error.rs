@@ -32,6 +32,10 @@ pub enum ValidationError {
         span: Span,
         /// Context where the reference was used
         context: String,
+        /// The defined name closest to the reference, as a reference
+        /// (e.g., "@variables.customer_id"), if one is close enough to be
+        /// a likely typo
+        suggestion: Option<String>,
     },
 
     /// A cycle was detected in topic transitions
@@ -58,6 +62,16 @@ pub enum ValidationError {
         span: Span,
     },
 
+    /// An action definition is only invoked from code unreachable from start_agent
+    UnreachableActionDef {
+        /// The action name
+        name: String,
+        /// The parent topic name
+        topic: String,
+        /// Source location
+        span: Span,
+    },
+
     /// A variable is never read
     UnusedVariable {
         /// The variable name
@@ -66,6 +80,21 @@ pub enum ValidationError {
         span: Span,
     },
 
+    /// An input of an invoked action is never bound by a `with` clause, or
+    /// an output is never captured by a `set` clause
+    UnusedParameter {
+        /// The parameter name
+        name: String,
+        /// Whether the parameter is an output (as opposed to an input)
+        output: bool,
+        /// The action name
+        action: String,
+        /// The parent topic name
+        topic: String,
+        /// Source location of the parameter definition
+        span: Span,
+    },
+
     /// A variable is read but never written
     UninitializedVariable {
         /// The variable name
@@ -85,6 +114,27 @@ pub enum ValidationError {
         /// Source location of the reference
         span: Span,
     },
+
+    /// A reasoning action escalates, but no connection is defined to
+    /// route the escalation through
+    EscalationWithoutConnection {
+        /// The reasoning action name
+        name: String,
+        /// The parent topic name
+        topic: String,
+        /// Source location
+        span: Span,
+    },
+
+    /// Two or more definitions share a name; references resolve to the first
+    DuplicateDefinition {
+        /// The shared name
+        name: String,
+        /// What is defined (e.g., "topic", "variable")
+        kind: String,
+        /// Name spans of every definition, in source order
+        spans: Vec<Span>,
+    },
 }
 
 impl ValidationError {
@@ -94,11 +144,16 @@ impl ValidationError {
             ValidationError::UnresolvedReference { span, .. }
             | ValidationError::UnreachableTopic { span, .. }
             | ValidationError::UnusedActionDef { span, .. }
+            | ValidationError::UnreachableActionDef { span, .. }
             | ValidationError::UnusedVariable { span, .. }
+            | ValidationError::UnusedParameter { span, .. }
             | ValidationError::InvalidPropertyAccess { span, .. }
+            | ValidationError::EscalationWithoutConnection { span, .. }
             | ValidationError::UninitializedVariable {
                 read_span: span, ..
             } => Some(*span),
+            // Point at the first definition that is shadowed
+            ValidationError::DuplicateDefinition { spans, .. } => spans.get(1).copied(),
             ValidationError::CycleDetected { .. } => None,
         }
     }
@@ -120,9 +175,31 @@ impl ValidationError {
             ValidationError::UnusedActionDef { name, topic, .. } => {
                 format!("Action '{}' in topic '{}' is never invoked", name, topic)
             }
+            ValidationError::UnreachableActionDef { name, topic, .. } => {
+                format!("Action '{}' in topic '{}' is unreachable from start_agent", name, topic)
+            }
             ValidationError::UnusedVariable { name, .. } => {
                 format!("Variable '{}' is never read", name)
             }
+            ValidationError::UnusedParameter {
+                name,
+                output,
+                action,
+                topic,
+                ..
+            } => {
+                if *output {
+                    format!(
+                        "Output '{}' of action '{}' in topic '{}' is never captured by a set clause",
+                        name, action, topic
+                    )
+                } else {
+                    format!(
+                        "Input '{}' of action '{}' in topic '{}' is never bound by a with clause",
+                        name, action, topic
+                    )
+                }
+            }
             ValidationError::UninitializedVariable { name, .. } => {
                 format!("Variable '{}' is read but never written", name)
             }
@@ -137,6 +214,28 @@ impl ValidationError {
                     reference, variable, variable_type
                 )
             }
+            ValidationError::EscalationWithoutConnection { name, topic, .. } => {
+                format!(
+                    "Action '{}' in topic '{}' escalates, but no connection is defined",
+                    name, topic
+                )
+            }
+            ValidationError::DuplicateDefinition { name, kind, spans } => {
+                let mut kind = kind.clone();
+                kind[..1].make_ascii_uppercase();
+                format!("{} '{}' is defined {} times", kind, name, spans.len())
+            }
+        }
+    }
+
+    /// A suggestion for fixing this issue, if there is one.
+    pub fn hint(&self) -> Option<String> {
+        match self {
+            ValidationError::UnresolvedReference {
+                suggestion: Some(suggestion),
+                ..
+            } => Some(format!("Did you mean '{}'?", suggestion)),
+            _ => None,
         }
     }
 
@@ -154,7 +253,10 @@ impl ValidationError {
     pub fn is_unused(&self) -> bool {
         matches!(
             self,
-            ValidationError::UnusedActionDef { .. } | ValidationError::UnusedVariable { .. }
+            ValidationError::UnusedActionDef { .. }
+                | ValidationError::UnreachableActionDef { .. }
+                | ValidationError::UnusedVariable { .. }
+                | ValidationError::UnusedParameter { .. }
         )
     }
 }
@@ -4,9 +4,14 @@
 //! data for external consumption (JSON, WASM, etc.).
 
 use super::error::ValidationError;
-use super::{RefGraph, RefNode, ValidationResult};
+use super::{
+    EdgeKind, GraphMetrics, LayoutOptions, NodeKind, RefGraph, RefGraphView, RefNode, TopicMetrics,
+    ValidationResult,
+};
+use petgraph::graph::NodeIndex;
 use petgraph::visit::EdgeRef;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 // ============================================================================
 // Basic representations
@@ -23,24 +28,49 @@ pub struct GraphRepr {
 
 impl From<&RefGraph> for GraphRepr {
     fn from(graph: &RefGraph) -> Self {
-        let inner = graph.inner();
+        GraphRepr::from(&graph.view())
+    }
+}
 
-        let nodes: Vec<NodeRepr> = inner
-            .node_indices()
-            .filter_map(|idx| graph.get_node(idx).map(NodeRepr::from))
-            .collect();
+impl From<&RefGraphView<'_>> for GraphRepr {
+    /// Export only the visible nodes and edges. Edge endpoints are positions
+    /// in `nodes`, so they are renumbered when the view hides nodes.
+    fn from(view: &RefGraphView<'_>) -> Self {
+        let mut positions = HashMap::new();
+        let mut nodes = Vec::new();
+        for idx in view.node_indices() {
+            if let Some(node) = view.get_node(idx) {
+                positions.insert(idx, nodes.len());
+                nodes.push(NodeRepr::from(node));
+            }
+        }
 
-        let edges: Vec<EdgeRepr> = inner
+        let edges: Vec<EdgeRepr> = view
             .edge_references()
-            .map(|e| EdgeRepr {
-                source: e.source().index(),
-                target: e.target().index(),
-                edge_type: e.weight().label().to_string(),
+            .filter_map(|e| {
+                Some(EdgeRepr {
+                    source: *positions.get(&e.source())?,
+                    target: *positions.get(&e.target())?,
+                    edge_type: e.weight().kind(),
+                })
             })
             .collect();
 
-        let topics: Vec<String> = graph.topic_names().map(|s| s.to_string()).collect();
-        let variables: Vec<String> = graph.variable_names().map(|s| s.to_string()).collect();
+        let graph = view.graph();
+        let topics: Vec<String> = graph
+            .topic_names()
+            .filter(|name| graph.get_topic(name).is_some_and(|idx| view.contains(idx)))
+            .map(|s| s.to_string())
+            .collect();
+        let variables: Vec<String> = graph
+            .variable_names()
+            .filter(|name| {
+                graph
+                    .get_variable(name)
+                    .is_some_and(|idx| view.contains(idx))
+            })
+            .map(|s| s.to_string())
+            .collect();
 
         Self {
             nodes,
@@ -54,7 +84,7 @@ impl From<&RefGraph> for GraphRepr {
 /// Serializable representation of a RefNode.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NodeRepr {
-    pub node_type: String,
+    pub node_type: NodeKind,
     pub name: Option<String>,
     pub topic: Option<String>,
     pub target: Option<String>,
@@ -67,7 +97,7 @@ impl From<&RefNode> for NodeRepr {
     fn from(node: &RefNode) -> Self {
         match node {
             RefNode::StartAgent { span } => NodeRepr {
-                node_type: "start_agent".to_string(),
+                node_type: NodeKind::StartAgent,
                 name: None,
                 topic: None,
                 target: None,
@@ -76,7 +106,7 @@ impl From<&RefNode> for NodeRepr {
                 span_end: span.1,
             },
             RefNode::Topic { name, span } => NodeRepr {
-                node_type: "topic".to_string(),
+                node_type: NodeKind::Topic,
                 name: Some(name.clone()),
                 topic: None,
                 target: None,
@@ -85,7 +115,7 @@ impl From<&RefNode> for NodeRepr {
                 span_end: span.1,
             },
             RefNode::ActionDef { name, topic, span } => NodeRepr {
-                node_type: "action_def".to_string(),
+                node_type: NodeKind::ActionDef,
                 name: Some(name.clone()),
                 topic: Some(topic.clone()),
                 target: None,
@@ -99,7 +129,7 @@ impl From<&RefNode> for NodeRepr {
                 target,
                 span,
             } => NodeRepr {
-                node_type: "reasoning_action".to_string(),
+                node_type: NodeKind::ReasoningAction,
                 name: Some(name.clone()),
                 topic: Some(topic.clone()),
                 target: target.clone(),
@@ -112,7 +142,7 @@ impl From<&RefNode> for NodeRepr {
                 mutable,
                 span,
             } => NodeRepr {
-                node_type: "variable".to_string(),
+                node_type: NodeKind::Variable,
                 name: Some(name.clone()),
                 topic: None,
                 target: None,
@@ -121,7 +151,7 @@ impl From<&RefNode> for NodeRepr {
                 span_end: span.1,
             },
             RefNode::Connection { name, span } => NodeRepr {
-                node_type: "connection".to_string(),
+                node_type: NodeKind::External,
                 name: Some(name.clone()),
                 topic: None,
                 target: None,
@@ -129,6 +159,15 @@ impl From<&RefNode> for NodeRepr {
                 span_start: span.0,
                 span_end: span.1,
             },
+            RefNode::KnowledgeBase { name, source, span } => NodeRepr {
+                node_type: NodeKind::External,
+                name: Some(name.clone()),
+                topic: None,
+                target: Some(source.clone()),
+                mutable: None,
+                span_start: span.0,
+                span_end: span.1,
+            },
         }
     }
 }
@@ -138,7 +177,7 @@ impl From<&RefNode> for NodeRepr {
 pub struct EdgeRepr {
     pub source: usize,
     pub target: usize,
-    pub edge_type: String,
+    pub edge_type: EdgeKind,
 }
 
 // ============================================================================
@@ -189,9 +228,15 @@ impl From<&ValidationError> for ValidationErrorRepr {
                 ValidationError::CycleDetected { .. } => "cycle_detected",
                 ValidationError::UnreachableTopic { .. } => "unreachable_topic",
                 ValidationError::UnusedActionDef { .. } => "unused_action_def",
+                ValidationError::UnreachableActionDef { .. } => "unreachable_action_def",
                 ValidationError::UnusedVariable { .. } => "unused_variable",
+                ValidationError::UnusedParameter { .. } => "unused_parameter",
                 ValidationError::UninitializedVariable { .. } => "uninitialized_variable",
                 ValidationError::InvalidPropertyAccess { .. } => "invalid_property_access",
+                ValidationError::EscalationWithoutConnection { .. } => {
+                    "escalation_without_connection"
+                }
+                ValidationError::DuplicateDefinition { .. } => "duplicate_definition",
             }
             .to_string(),
             message: error.message(),
@@ -216,7 +261,7 @@ pub struct VariableUsagesRepr {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UsageInfoRepr {
     pub location: String,
-    pub node_type: String,
+    pub node_type: NodeKind,
     pub topic: Option<String>,
     pub context: Option<String>,
 }
@@ -226,7 +271,7 @@ impl UsageInfoRepr {
         match node {
             RefNode::ActionDef { name, topic, .. } => UsageInfoRepr {
                 location: name.clone(),
-                node_type: "action_def".to_string(),
+                node_type: NodeKind::ActionDef,
                 topic: Some(topic.clone()),
                 context: None,
             },
@@ -237,34 +282,88 @@ impl UsageInfoRepr {
                 ..
             } => UsageInfoRepr {
                 location: name.clone(),
-                node_type: "reasoning_action".to_string(),
+                node_type: NodeKind::ReasoningAction,
                 topic: Some(topic.clone()),
                 context: target.clone(),
             },
             RefNode::Topic { name, .. } => UsageInfoRepr {
                 location: name.clone(),
-                node_type: "topic".to_string(),
+                node_type: NodeKind::Topic,
                 topic: Some(name.clone()),
                 context: None,
             },
             RefNode::StartAgent { .. } => UsageInfoRepr {
                 location: "start_agent".to_string(),
-                node_type: "start_agent".to_string(),
+                node_type: NodeKind::StartAgent,
                 topic: None,
                 context: None,
             },
             RefNode::Variable { name, .. } => UsageInfoRepr {
                 location: name.clone(),
-                node_type: "variable".to_string(),
+                node_type: NodeKind::Variable,
                 topic: None,
                 context: None,
             },
             RefNode::Connection { name, .. } => UsageInfoRepr {
                 location: name.clone(),
-                node_type: "connection".to_string(),
+                node_type: NodeKind::External,
                 topic: None,
                 context: None,
             },
+            RefNode::KnowledgeBase { name, source, .. } => UsageInfoRepr {
+                location: name.clone(),
+                node_type: NodeKind::External,
+                topic: None,
+                context: Some(source.clone()),
+            },
+        }
+    }
+}
+
+// ============================================================================
+// Metrics representations
+// ============================================================================
+
+/// Serializable representation of GraphMetrics.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GraphMetricsRepr {
+    pub topics: Vec<TopicMetricsRepr>,
+    pub max_depth: Option<usize>,
+    pub total_complexity: usize,
+}
+
+impl From<&GraphMetrics> for GraphMetricsRepr {
+    fn from(metrics: &GraphMetrics) -> Self {
+        Self {
+            topics: metrics.topics.iter().map(TopicMetricsRepr::from).collect(),
+            max_depth: metrics.max_depth(),
+            total_complexity: metrics.total_complexity(),
+        }
+    }
+}
+
+/// Serializable representation of TopicMetrics.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TopicMetricsRepr {
+    pub name: String,
+    pub fan_in: usize,
+    pub fan_out: usize,
+    pub depth: Option<usize>,
+    pub reasoning_actions: usize,
+    pub complexity: usize,
+    pub max_instruction_nesting: usize,
+}
+
+impl From<&TopicMetrics> for TopicMetricsRepr {
+    fn from(topic: &TopicMetrics) -> Self {
+        Self {
+            name: topic.name.clone(),
+            fan_in: topic.fan_in,
+            fan_out: topic.fan_out,
+            depth: topic.depth,
+            reasoning_actions: topic.reasoning_actions,
+            complexity: topic.complexity,
+            max_instruction_nesting: topic.max_instruction_nesting,
         }
     }
 }
@@ -289,12 +388,18 @@ pub struct GraphExport {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GraphExportNode {
     pub id: usize,
-    pub node_type: String,
+    pub node_type: NodeKind,
     pub name: Option<String>,
     pub topic: Option<String>,
     pub target: Option<String>,
     pub mutable: Option<bool>,
     pub span: SpanRepr,
+    /// Layout hint from [`GraphExport::from_graph_with_layout`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub x: Option<f64>,
+    /// Layout hint from [`GraphExport::from_graph_with_layout`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub y: Option<f64>,
 }
 
 /// Edge representation for full export.
@@ -302,7 +407,7 @@ pub struct GraphExportNode {
 pub struct GraphExportEdge {
     pub source: usize,
     pub target: usize,
-    pub edge_type: String,
+    pub edge_type: EdgeKind,
 }
 
 /// Span representation.
@@ -375,6 +480,8 @@ impl GraphExport {
                             start: repr.span_start,
                             end: repr.span_end,
                         },
+                        x: None,
+                        y: None,
                     }
                 })
             })
@@ -386,7 +493,7 @@ impl GraphExport {
             .map(|e| GraphExportEdge {
                 source: e.source().index(),
                 target: e.target().index(),
-                edge_type: e.weight().label().to_string(),
+                edge_type: e.weight().kind(),
             })
             .collect();
 
@@ -509,4 +616,18 @@ impl GraphExport {
             },
         }
     }
+
+    /// Build a full export with `x`/`y` hints on every node from
+    /// [`RefGraph::layered_layout`].
+    pub fn from_graph_with_layout(graph: &RefGraph, options: &LayoutOptions) -> Self {
+        let mut export = Self::from_graph(graph);
+        let layout = graph.layered_layout(options);
+        for node in &mut export.nodes {
+            if let Some(position) = layout.get(&NodeIndex::new(node.id)) {
+                node.x = Some(position.x);
+                node.y = Some(position.y);
+            }
+        }
+        export
+    }
 }
@@ -0,0 +1,369 @@
+//! Aggregate "health score" for an agent.
+//!
+//! [`health_report`] combines several independent signals into one weighted
+//! score from 0 to 100, and lists the issues that cost the most points:
+//!
+//! | Component | Source | Penalty |
+//! |-----------|--------|---------|
+//! | `lint` | [`validate_ast`](crate::validate_ast) | 15 per error, 3 per warning |
+//! | `structure` | [`RefGraph::validate`] | 15 per error, 5 per warning |
+//! | `coverage` | [`HealthOptions::coverage`], when measured | 100 × uncovered fraction |
+//! | `tokens` | [`TokenBudget`] against [`HealthOptions::lint`] | up to 50 per topic over budget |
+//! | `dependencies` | [`extract_dependencies`] | 1–6 per org dependency, by kind |
+//!
+//! Each component starts at 100 and is floored at 0. The overall score is the
+//! weighted mean of the components; a component without data (coverage that
+//! was not measured) is left out rather than scored as 0.
+//!
+//! # Example
+//!
+//! ```rust
+//! use busbar_sf_agentscript::graph::health::{health_report, HealthOptions};
+//!
+//! let source = r#"
+//! start_agent main:
+//!    description: "Route requests"
+//!    reasoning:
+//!       instructions: "Help the user"
+//! "#;
+//! let ast = busbar_sf_agentscript::parse(source).unwrap();
+//! let report = health_report(&ast, &HealthOptions::default());
+//! assert_eq!(report.score, 100);
+//! assert!(report.top_issues.is_empty());
+//! ```
+
+use super::dependencies::{extract_dependencies, DependencyType};
+use super::RefGraph;
+use crate::ast::AgentFile;
+use crate::lint::{LintConfig, TokenBudget};
+use crate::validation::{validate_ast, Severity};
+use serde::Serialize;
+use std::ops::Range;
+
+/// Relative weight of each health component.
+///
+/// Weights need not sum to 1; the score is normalized by the weights of the
+/// components that have data.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct HealthWeights {
+    pub lint: f64,
+    pub structure: f64,
+    pub coverage: f64,
+    pub tokens: f64,
+    pub dependencies: f64,
+}
+
+impl Default for HealthWeights {
+    fn default() -> Self {
+        Self {
+            lint: 0.35,
+            structure: 0.2,
+            coverage: 0.15,
+            tokens: 0.15,
+            dependencies: 0.15,
+        }
+    }
+}
+
+/// Inputs and tuning for [`health_report`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct HealthOptions {
+    /// Fraction (0.0–1.0) of the agent exercised by tests, if measured.
+    pub coverage: Option<f64>,
+    /// Lint configuration; its `token-budget` rule sets the estimated
+    /// instruction tokens a topic may use before it is penalized.
+    pub lint: LintConfig,
+    /// Component weights.
+    pub weights: HealthWeights,
+    /// Number of issues to keep in [`HealthReport::top_issues`].
+    pub max_issues: usize,
+}
+
+impl Default for HealthOptions {
+    fn default() -> Self {
+        Self {
+            coverage: None,
+            lint: LintConfig::default(),
+            weights: HealthWeights::default(),
+            max_issues: 5,
+        }
+    }
+}
+
+/// The score of one health component.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HealthComponent {
+    /// Component name, e.g. `"lint"`.
+    pub name: &'static str,
+    /// Score from 0 to 100.
+    pub score: f64,
+    /// Weight the component contributed with.
+    pub weight: f64,
+}
+
+/// An issue that lowered the health score.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HealthIssue {
+    /// The component the issue counts against.
+    pub component: &'static str,
+    /// Human-readable description.
+    pub message: String,
+    /// Source location, when the issue has one.
+    pub span: Option<Range<usize>>,
+    /// Points deducted from the component's score.
+    pub penalty: f64,
+}
+
+/// Raw counts behind the score.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct HealthMetrics {
+    pub topics: usize,
+    pub actions: usize,
+    pub variables: usize,
+    pub errors: usize,
+    pub warnings: usize,
+    /// Estimated instruction tokens of the largest topic.
+    pub max_topic_tokens: usize,
+    /// Unique org dependencies.
+    pub dependencies: usize,
+}
+
+/// A weighted health score for an agent. See the [module docs](self).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HealthReport {
+    /// Overall score from 0 (worst) to 100 (best).
+    pub score: u8,
+    /// Per-component scores.
+    pub components: Vec<HealthComponent>,
+    /// The issues that cost the most points, largest penalty first.
+    pub top_issues: Vec<HealthIssue>,
+    pub metrics: HealthMetrics,
+}
+
+/// Compute the health report of an agent.
+pub fn health_report(ast: &AgentFile, options: &HealthOptions) -> HealthReport {
+    let weights = &options.weights;
+    let mut metrics = HealthMetrics::default();
+    let mut issues = Vec::new();
+    let mut components = Vec::new();
+
+    // Lint
+    let mut lint = Vec::new();
+    for error in validate_ast(ast) {
+        let penalty = match error.severity {
+            Severity::Error => {
+                metrics.errors += 1;
+                15.0
+            }
+            Severity::Warning => {
+                metrics.warnings += 1;
+                3.0
+            }
+        };
+        lint.push(issue("lint", error.message, error.span, penalty));
+    }
+    components.push(component("lint", weights.lint, &lint));
+    issues.extend(lint);
+
+    // Structure
+    let mut structure = Vec::new();
+    match RefGraph::from_ast(ast) {
+        Ok(graph) => {
+            let stats = graph.stats();
+            metrics.topics = stats.topics;
+            metrics.actions = stats.action_defs;
+            metrics.variables = stats.variables;
+            let result = graph.validate();
+            for (errors, penalty, severity) in [
+                (&result.errors, 15.0, Severity::Error),
+                (&result.warnings, 5.0, Severity::Warning),
+            ] {
+                for error in errors {
+                    match severity {
+                        Severity::Error => metrics.errors += 1,
+                        Severity::Warning => metrics.warnings += 1,
+                    }
+                    let span = error.span().map(|(start, end)| start..end);
+                    structure.push(issue("structure", error.message(), span, penalty));
+                }
+            }
+        }
+        Err(error) => {
+            metrics.errors += 1;
+            structure.push(issue("structure", error.to_string(), None, 100.0));
+        }
+    }
+    components.push(component("structure", weights.structure, &structure));
+    issues.extend(structure);
+
+    // Coverage
+    if let Some(coverage) = options.coverage {
+        let coverage = coverage.clamp(0.0, 1.0);
+        let mut uncovered = Vec::new();
+        if coverage < 1.0 {
+            uncovered.push(issue(
+                "coverage",
+                format!("Tests cover {:.0}% of the agent", coverage * 100.0),
+                None,
+                (1.0 - coverage) * 100.0,
+            ));
+        }
+        components.push(component("coverage", weights.coverage, &uncovered));
+        issues.extend(uncovered);
+    }
+
+    // Token budgets
+    let mut tokens = Vec::new();
+    let budget = TokenBudget::max(&options.lint).max(1);
+    for (name, estimate) in TokenBudget::estimates(ast) {
+        metrics.max_topic_tokens = metrics.max_topic_tokens.max(estimate);
+        if estimate > budget {
+            let over = (estimate - budget) as f64 / budget as f64;
+            tokens.push(issue(
+                "tokens",
+                format!(
+                    "'{}' uses ~{} instruction tokens, over the budget of {}",
+                    name.node, estimate, budget
+                ),
+                Some(name.span.clone()),
+                (over * 100.0).min(50.0),
+            ));
+        }
+    }
+    components.push(component("tokens", weights.tokens, &tokens));
+    issues.extend(tokens);
+
+    // Dependency risk
+    let mut dependencies = Vec::new();
+    let report = extract_dependencies(ast);
+    metrics.dependencies = report.unique_count();
+    let mut seen = std::collections::HashSet::new();
+    for dependency in &report.all_dependencies {
+        if !seen.insert(&dependency.dep_type) {
+            continue;
+        }
+        let penalty = dependency_risk(&dependency.dep_type);
+        dependencies.push(issue(
+            "dependencies",
+            format!(
+                "Depends on {} '{}'",
+                dependency.dep_type.category().replace('_', " "),
+                dependency.dep_type.name()
+            ),
+            Some(dependency.span.0..dependency.span.1),
+            penalty,
+        ));
+    }
+    components.push(component("dependencies", weights.dependencies, &dependencies));
+    issues.extend(dependencies);
+
+    let total_weight: f64 = components.iter().map(|c| c.weight).sum();
+    let score = if total_weight > 0.0 {
+        components.iter().map(|c| c.score * c.weight).sum::<f64>() / total_weight
+    } else {
+        100.0
+    };
+
+    issues.sort_by(|a, b| b.penalty.total_cmp(&a.penalty));
+    issues.truncate(options.max_issues);
+
+    HealthReport {
+        score: score.round().clamp(0.0, 100.0) as u8,
+        components,
+        top_issues: issues,
+        metrics,
+    }
+}
+
+fn issue(
+    component: &'static str,
+    message: String,
+    span: Option<Range<usize>>,
+    penalty: f64,
+) -> HealthIssue {
+    HealthIssue {
+        component,
+        message,
+        span,
+        penalty,
+    }
+}
+
+fn component(name: &'static str, weight: f64, issues: &[HealthIssue]) -> HealthComponent {
+    let penalty: f64 = issues.iter().map(|i| i.penalty).sum();
+    HealthComponent {
+        name,
+        score: (100.0 - penalty).max(0.0),
+        weight,
+    }
+}
+
+/// Points a dependency costs: code and external systems can break a deploy
+/// or change under the agent, schema references rarely do.
+fn dependency_risk(dependency: &DependencyType) -> f64 {
+    match dependency {
+        DependencyType::ExternalService(_) | DependencyType::Custom(_) => 6.0,
+        DependencyType::ApexClass(_)
+        | DependencyType::ApexMethod { .. }
+        | DependencyType::Connection(_) => 4.0,
+        DependencyType::Flow(_) => 3.0,
+        DependencyType::KnowledgeBase(_) | DependencyType::PromptTemplate(_) => 2.0,
+        DependencyType::SObject(_) | DependencyType::Field { .. } => 1.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_health_report_weights_and_top_issues() {
+        let source = r#"
+start_agent main:
+   description: "Route"
+   reasoning:
+      instructions: "Help the user with their order and anything else they ask about"
+      actions:
+         go: @utils.transition to @topic.orders
+            description: "Orders"
+
+topic orders:
+   description: "Orders"
+   actions:
+      lookup:
+         description: "Look up an order"
+         target: "apex://OrderLookup"
+   reasoning:
+      instructions: "Answer order questions"
+      actions:
+         find: @actions.lookup
+            with id = ...
+"#;
+        let ast = crate::parse(source).unwrap();
+        let options = HealthOptions {
+            coverage: Some(0.5),
+            lint: LintConfig::from_toml("[rules.token-budget]\nmax = 10\n").unwrap(),
+            max_issues: 2,
+            ..HealthOptions::default()
+        };
+        let report = health_report(&ast, &options);
+
+        let component = |name| report.components.iter().find(|c| c.name == name).unwrap();
+        assert_eq!(component("coverage").score, 50.0);
+        assert_eq!(component("dependencies").score, 96.0);
+        // 16 estimated tokens against a budget of 10: 60% over, capped at 50
+        assert_eq!(component("tokens").score, 50.0);
+        assert_eq!(report.metrics.max_topic_tokens, 16);
+        assert_eq!(report.metrics.dependencies, 1);
+
+        assert!(report.score < 100);
+        let top: Vec<_> = report.top_issues.iter().map(|i| i.component).collect();
+        assert_eq!(top, ["coverage", "tokens"]);
+        assert_eq!(&source[report.top_issues[1].span.clone().unwrap()], "main");
+
+        // Coverage that was not measured is left out, not scored as zero
+        let unmeasured = health_report(&ast, &HealthOptions::default());
+        assert!(unmeasured.components.iter().all(|c| c.name != "coverage"));
+        assert!(unmeasured.score > report.score);
+    }
+}
@@ -0,0 +1,345 @@
+//! Layered auto-layout.
+//!
+//! A lightweight Sugiyama-style pass that gives every node an `x`/`y`
+//! position, so frontends can draw a readable diagram without a layout
+//! engine of their own:
+//!
+//! 1. Edges that close a cycle are reversed, starting the search from
+//!    `start_agent` so transitions back to earlier topics point upward.
+//! 2. Each node is placed one layer below the deepest node pointing at it.
+//! 3. Nodes within a layer are reordered by the average position of their
+//!    neighbours, sweeping down and up, to reduce edge crossings.
+//!
+//! Edges spanning several layers get no bend points; the result is a hint,
+//! not a full graph drawing.
+
+use super::{RefGraph, RefGraphView};
+use petgraph::graph::NodeIndex;
+use petgraph::visit::EdgeRef;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// Options for [`RefGraph::layered_layout`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayoutOptions {
+    /// Vertical distance between layers.
+    pub layer_spacing: f64,
+    /// Horizontal distance between nodes in a layer.
+    pub node_spacing: f64,
+    /// Number of down-and-up ordering sweeps.
+    pub sweeps: usize,
+}
+
+impl Default for LayoutOptions {
+    fn default() -> Self {
+        Self {
+            layer_spacing: 100.0,
+            node_spacing: 180.0,
+            sweeps: 4,
+        }
+    }
+}
+
+/// Position of a node from [`RefGraph::layered_layout`].
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct NodePosition {
+    /// Horizontal position of the node's center; never negative.
+    pub x: f64,
+    /// Vertical position of the node's center; grows with the layer.
+    pub y: f64,
+    /// Layer, counted from 0 at the top.
+    pub layer: usize,
+}
+
+impl RefGraph {
+    /// Compute a layered layout with a position for every node.
+    ///
+    /// Layers run top to bottom following edge direction, so
+    /// `start_agent` is at the top and variables are near the bottom.
+    /// Layers are centered on the widest one.
+    ///
+    /// ```rust
+    /// use busbar_sf_agentscript::graph::{LayoutOptions, RefGraph};
+    ///
+    /// let source = r#"
+    /// start_agent main:
+    ///    description: "Route"
+    ///    reasoning:
+    ///       instructions: "Route"
+    ///       actions:
+    ///          go: @utils.transition to @topic.orders
+    ///
+    /// topic orders:
+    ///    description: "Orders"
+    ///    reasoning:
+    ///       instructions: "Help"
+    /// "#;
+    /// let graph = RefGraph::from_ast(&busbar_sf_agentscript::parse(source).unwrap()).unwrap();
+    /// let layout = graph.layered_layout(&LayoutOptions::default());
+    ///
+    /// let start = layout[&graph.get_start_agent().unwrap()];
+    /// let orders = layout[&graph.get_topic("orders").unwrap()];
+    /// assert!(start.y < orders.y);
+    /// ```
+    pub fn layered_layout(&self, options: &LayoutOptions) -> HashMap<NodeIndex, NodePosition> {
+        self.view().layered_layout(options)
+    }
+}
+
+impl RefGraphView<'_> {
+    /// Compute a layered layout of the visible nodes and edges, as
+    /// [`RefGraph::layered_layout`] does for the whole graph.
+    pub fn layered_layout(&self, options: &LayoutOptions) -> HashMap<NodeIndex, NodePosition> {
+        let edges = self.acyclic_edges();
+        let layers = self.assign_layers(&edges);
+        let layers = order_layers(layers, &edges, options.sweeps);
+
+        let widest = layers.iter().map(Vec::len).max().unwrap_or(0);
+        let mut positions = HashMap::new();
+        for (layer, nodes) in layers.iter().enumerate() {
+            let offset = (widest - nodes.len()) as f64 / 2.0;
+            for (i, &idx) in nodes.iter().enumerate() {
+                positions.insert(
+                    idx,
+                    NodePosition {
+                        x: (offset + i as f64) * options.node_spacing,
+                        y: layer as f64 * options.layer_spacing,
+                        layer,
+                    },
+                );
+            }
+        }
+        positions
+    }
+
+    /// Distinct edges with those closing a cycle reversed, found by a
+    /// depth-first search from `start_agent` and then every node not yet
+    /// reached. Self-loops are dropped.
+    fn acyclic_edges(&self) -> Vec<(NodeIndex, NodeIndex)> {
+        let mut visited = HashSet::new();
+        let mut edges = HashSet::new();
+        let roots = self
+            .graph()
+            .start_agent
+            .filter(|idx| self.contains(*idx))
+            .into_iter()
+            .chain(self.node_indices());
+        for root in roots {
+            if !visited.insert(root) {
+                continue;
+            }
+            // Stack of nodes on the current path, each with its successors
+            let mut path = vec![(root, self.successors(root))];
+            let mut on_path = HashSet::from([root]);
+            while let Some((node, successors)) = path.last_mut() {
+                let node = *node;
+                let Some(next) = successors.pop() else {
+                    on_path.remove(&node);
+                    path.pop();
+                    continue;
+                };
+                if next == node {
+                    continue;
+                }
+                if on_path.contains(&next) {
+                    edges.insert((next, node));
+                    continue;
+                }
+                edges.insert((node, next));
+                if visited.insert(next) {
+                    on_path.insert(next);
+                    path.push((next, self.successors(next)));
+                }
+            }
+        }
+        let mut edges: Vec<_> = edges.into_iter().collect();
+        edges.sort();
+        edges
+    }
+
+    /// Successors of `node`, in reverse so popping yields edge order.
+    fn successors(&self, node: NodeIndex) -> Vec<NodeIndex> {
+        let mut successors: Vec<_> = self
+            .graph()
+            .inner()
+            .edges(node)
+            .filter(|e| self.contains_edge(e.id()))
+            .map(|e| e.target())
+            .collect();
+        successors.sort();
+        successors.dedup();
+        successors.reverse();
+        successors
+    }
+
+    /// Group nodes into layers by their longest path from a source.
+    fn assign_layers(&self, edges: &[(NodeIndex, NodeIndex)]) -> Vec<Vec<NodeIndex>> {
+        let mut incoming: HashMap<NodeIndex, usize> = HashMap::new();
+        let mut outgoing: HashMap<NodeIndex, Vec<NodeIndex>> = HashMap::new();
+        for &(source, target) in edges {
+            *incoming.entry(target).or_default() += 1;
+            outgoing.entry(source).or_default().push(target);
+        }
+
+        let mut layer_of: HashMap<NodeIndex, usize> = HashMap::new();
+        let mut ready: Vec<NodeIndex> = self
+            .node_indices()
+            .filter(|idx| !incoming.contains_key(idx))
+            .collect();
+        ready.reverse();
+        while let Some(node) = ready.pop() {
+            let layer = *layer_of.entry(node).or_default();
+            for &target in outgoing.get(&node).into_iter().flatten() {
+                let target_layer = layer_of.entry(target).or_default();
+                *target_layer = (*target_layer).max(layer + 1);
+                let remaining = incoming.get_mut(&target).expect("counted above");
+                *remaining -= 1;
+                if *remaining == 0 {
+                    ready.push(target);
+                }
+            }
+        }
+
+        let mut layers = vec![Vec::new(); layer_of.values().max().map_or(0, |l| l + 1)];
+        for idx in self.node_indices() {
+            layers[layer_of[&idx]].push(idx);
+        }
+        layers
+    }
+}
+
+/// Reorder nodes within layers by the barycenter of their neighbours in
+/// the layer above (sweeping down) or below (sweeping up).
+fn order_layers(
+    mut layers: Vec<Vec<NodeIndex>>,
+    edges: &[(NodeIndex, NodeIndex)],
+    sweeps: usize,
+) -> Vec<Vec<NodeIndex>> {
+    let mut layer_of = HashMap::new();
+    for (layer, nodes) in layers.iter().enumerate() {
+        for &idx in nodes {
+            layer_of.insert(idx, layer);
+        }
+    }
+
+    for _ in 0..sweeps {
+        for layer in 1..layers.len() {
+            reorder(&mut layers, layer, layer - 1, edges, &layer_of);
+        }
+        for layer in (0..layers.len().saturating_sub(1)).rev() {
+            reorder(&mut layers, layer, layer + 1, edges, &layer_of);
+        }
+    }
+    layers
+}
+
+/// Sort `layers[layer]` by the mean position of each node's neighbours in
+/// `layers[fixed]`. Nodes without such neighbours keep their position.
+fn reorder(
+    layers: &mut [Vec<NodeIndex>],
+    layer: usize,
+    fixed: usize,
+    edges: &[(NodeIndex, NodeIndex)],
+    layer_of: &HashMap<NodeIndex, usize>,
+) {
+    let fixed_positions: HashMap<NodeIndex, usize> = layers[fixed]
+        .iter()
+        .enumerate()
+        .map(|(i, &idx)| (idx, i))
+        .collect();
+
+    let mut keyed: Vec<(f64, NodeIndex)> = layers[layer]
+        .iter()
+        .enumerate()
+        .map(|(i, &idx)| {
+            let neighbours: Vec<usize> = edges
+                .iter()
+                .filter_map(|&(source, target)| {
+                    if source == idx && layer_of[&target] == fixed {
+                        Some(target)
+                    } else if target == idx && layer_of[&source] == fixed {
+                        Some(source)
+                    } else {
+                        None
+                    }
+                })
+                .map(|n| fixed_positions[&n])
+                .collect();
+            let key = if neighbours.is_empty() {
+                i as f64
+            } else {
+                neighbours.iter().sum::<usize>() as f64 / neighbours.len() as f64
+            };
+            (key, idx)
+        })
+        .collect();
+    keyed.sort_by(|a, b| a.0.total_cmp(&b.0));
+    layers[layer] = keyed.into_iter().map(|(_, idx)| idx).collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layered_layout() {
+        let source = r#"variables:
+   order_id: mutable string = ""
+
+start_agent selector:
+   description: "Route"
+   reasoning:
+      instructions: "Select"
+      actions:
+         go_billing: @utils.transition to @topic.billing
+         go_orders: @utils.transition to @topic.orders
+
+topic orders:
+   description: "Orders"
+   reasoning:
+      instructions: "Help"
+      actions:
+         save: @utils.setVariables
+            with order_id=...
+            set @variables.order_id = @outputs.id
+         back: @utils.transition to @topic.billing
+
+topic billing:
+   description: "Billing"
+   reasoning:
+      instructions: "Bill"
+      actions:
+         back: @utils.transition to @topic.orders
+"#;
+        let graph = RefGraph::from_ast(&crate::parse(source).unwrap()).unwrap();
+        let layout = graph.layered_layout(&LayoutOptions::default());
+        assert_eq!(layout.len(), graph.node_count());
+
+        let start = layout[&graph.get_start_agent().unwrap()];
+        let orders = layout[&graph.get_topic("orders").unwrap()];
+        let billing = layout[&graph.get_topic("billing").unwrap()];
+        assert_eq!(start.layer, 0);
+        assert_eq!(start.y, 0.0);
+        // The cycle between the topics is broken, putting one below the other
+        assert_ne!(orders.layer, billing.layer);
+        assert!(orders.layer > 0 && billing.layer > 0);
+
+        // Positions are distinct and never negative
+        let mut seen = HashSet::new();
+        for position in layout.values() {
+            assert!(position.x >= 0.0 && position.y >= 0.0);
+            assert!(seen.insert((position.x.to_bits(), position.y.to_bits())));
+        }
+
+        let export =
+            super::super::GraphExport::from_graph_with_layout(&graph, &LayoutOptions::default());
+        let json = serde_json::to_value(&export).unwrap();
+        assert!(json["nodes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .all(|n| n["x"].is_number() && n["y"].is_number()));
+        let json = serde_json::to_value(super::super::GraphExport::from_graph(&graph)).unwrap();
+        assert!(json["nodes"][0].get("x").is_none());
+    }
+}
@@ -0,0 +1,397 @@
+//! Per-topic size and complexity metrics.
+//!
+//! [`RefGraph::metrics`] measures every topic (and `start_agent`):
+//!
+//! | Metric | Meaning |
+//! |--------|---------|
+//! | `fan_in` | distinct topics that route, transition or delegate to it |
+//! | `fan_out` | distinct topics it routes, transitions or delegates to |
+//! | `depth` | fewest transitions from `start_agent`; `None` if unreachable |
+//! | `reasoning_actions` | reasoning actions it offers the planner |
+//! | `complexity` | 1 + decision points in `before_reasoning`/`after_reasoning` |
+//! | `max_instruction_nesting` | deepest `if` nesting in its instructions |
+//!
+//! Decision points are `if` statements and the `and`/`or` operators in their
+//! conditions, as in cyclomatic complexity.
+//!
+//! [`GraphMetrics::check`] compares the metrics to a [`MetricsBudget`], so CI
+//! can fail when an agent grows too complex.
+//!
+//! # Example
+//!
+//! ```rust
+//! use busbar_sf_agentscript::graph::{metrics::MetricsBudget, RefGraph};
+//!
+//! let source = r#"
+//! variables:
+//!    verified: mutable boolean = False
+//!
+//! start_agent main:
+//!    description: "Route"
+//!    reasoning:
+//!       instructions: "Route"
+//!       actions:
+//!          go: @utils.transition to @topic.orders
+//!
+//! topic orders:
+//!    description: "Orders"
+//!    before_reasoning:
+//!       if @variables.verified and @variables.verified:
+//!          set @variables.verified = True
+//!    reasoning:
+//!       instructions: "Help"
+//! "#;
+//! let graph = RefGraph::from_ast(&busbar_sf_agentscript::parse(source).unwrap()).unwrap();
+//! let metrics = graph.metrics();
+//!
+//! let orders = metrics.topic("orders").unwrap();
+//! assert_eq!((orders.fan_in, orders.depth, orders.complexity), (1, Some(1), 3));
+//!
+//! let budget = MetricsBudget { max_complexity: Some(2), ..Default::default() };
+//! let violations = metrics.check(&budget);
+//! assert_eq!(violations[0].to_string(), "orders: complexity 3 exceeds budget of 2");
+//! ```
+
+use super::edges::RefEdge;
+use super::nodes::RefNode;
+use super::RefGraph;
+use crate::ast::{BinOp, DirectiveBlock, Expr, InstructionPart, Instructions, Spanned, Stmt};
+use petgraph::graph::NodeIndex;
+use petgraph::visit::EdgeRef;
+use petgraph::Direction;
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::fmt;
+
+/// Measures of a topic's own blocks, recorded when the graph is built.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct BlockComplexity {
+    pub complexity: usize,
+    pub instruction_nesting: usize,
+}
+
+impl BlockComplexity {
+    pub(crate) fn measure(
+        directives: [Option<&Spanned<DirectiveBlock>>; 2],
+        instructions: Option<&Instructions>,
+    ) -> Self {
+        let decisions: usize = directives
+            .into_iter()
+            .flatten()
+            .map(|d| stmt_decisions(&d.node.statements))
+            .sum();
+        let instruction_nesting = match instructions {
+            Some(Instructions::Dynamic(parts)) => part_nesting(parts),
+            _ => 0,
+        };
+        Self {
+            complexity: 1 + decisions,
+            instruction_nesting,
+        }
+    }
+}
+
+fn stmt_decisions(stmts: &[Spanned<Stmt>]) -> usize {
+    stmts
+        .iter()
+        .map(|stmt| match &stmt.node {
+            Stmt::If {
+                condition,
+                then_block,
+                else_block,
+            } => {
+                1 + boolean_operators(&condition.node)
+                    + stmt_decisions(then_block)
+                    + else_block.as_deref().map_or(0, stmt_decisions)
+            }
+            _ => 0,
+        })
+        .sum()
+}
+
+fn boolean_operators(expr: &Expr) -> usize {
+    match expr {
+        Expr::BinOp { left, op, right } => {
+            usize::from(matches!(op, BinOp::And | BinOp::Or))
+                + boolean_operators(&left.node)
+                + boolean_operators(&right.node)
+        }
+        Expr::UnaryOp { operand, .. } => boolean_operators(&operand.node),
+        Expr::Ternary {
+            condition,
+            then_expr,
+            else_expr,
+        } => {
+            boolean_operators(&condition.node)
+                + boolean_operators(&then_expr.node)
+                + boolean_operators(&else_expr.node)
+        }
+        _ => 0,
+    }
+}
+
+fn part_nesting(parts: &[Spanned<InstructionPart>]) -> usize {
+    parts
+        .iter()
+        .map(|part| match &part.node {
+            InstructionPart::Conditional {
+                then_parts,
+                else_parts,
+                ..
+            } => 1 + part_nesting(then_parts).max(else_parts.as_deref().map_or(0, part_nesting)),
+            _ => 0,
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+/// Metrics of every topic in a graph.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GraphMetrics {
+    /// `start_agent` first, then topics in graph order.
+    pub topics: Vec<TopicMetrics>,
+}
+
+/// Metrics of one topic, or of `start_agent` (named `"start_agent"`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct TopicMetrics {
+    pub node: NodeIndex,
+    pub name: String,
+    pub fan_in: usize,
+    pub fan_out: usize,
+    /// Fewest transitions from `start_agent`; `None` if unreachable.
+    pub depth: Option<usize>,
+    pub reasoning_actions: usize,
+    pub complexity: usize,
+    pub max_instruction_nesting: usize,
+}
+
+/// Limits for [`GraphMetrics::check`]. Unset limits are not checked.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MetricsBudget {
+    pub max_fan_in: Option<usize>,
+    pub max_fan_out: Option<usize>,
+    pub max_depth: Option<usize>,
+    pub max_reasoning_actions: Option<usize>,
+    pub max_complexity: Option<usize>,
+    pub max_instruction_nesting: Option<usize>,
+}
+
+/// A metric over its budget.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BudgetViolation {
+    pub topic: String,
+    pub metric: &'static str,
+    pub value: usize,
+    pub limit: usize,
+}
+
+impl fmt::Display for BudgetViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} {} exceeds budget of {}",
+            self.topic, self.metric, self.value, self.limit
+        )
+    }
+}
+
+impl GraphMetrics {
+    /// Metrics of a topic by name.
+    pub fn topic(&self, name: &str) -> Option<&TopicMetrics> {
+        self.topics.iter().find(|t| t.name == name)
+    }
+
+    /// The greatest depth of any reachable topic.
+    pub fn max_depth(&self) -> Option<usize> {
+        self.topics.iter().filter_map(|t| t.depth).max()
+    }
+
+    /// Sum of all topics' complexity.
+    pub fn total_complexity(&self) -> usize {
+        self.topics.iter().map(|t| t.complexity).sum()
+    }
+
+    /// Every metric that exceeds `budget`, topic by topic.
+    pub fn check(&self, budget: &MetricsBudget) -> Vec<BudgetViolation> {
+        let mut violations = Vec::new();
+        for t in &self.topics {
+            let measured = [
+                ("fan_in", Some(t.fan_in), budget.max_fan_in),
+                ("fan_out", Some(t.fan_out), budget.max_fan_out),
+                ("depth", t.depth, budget.max_depth),
+                ("reasoning_actions", Some(t.reasoning_actions), budget.max_reasoning_actions),
+                ("complexity", Some(t.complexity), budget.max_complexity),
+                (
+                    "max_instruction_nesting",
+                    Some(t.max_instruction_nesting),
+                    budget.max_instruction_nesting,
+                ),
+            ];
+            for (metric, value, limit) in measured {
+                if let (Some(value), Some(limit)) = (value, limit) {
+                    if value > limit {
+                        violations.push(BudgetViolation {
+                            topic: t.name.clone(),
+                            metric,
+                            value,
+                            limit,
+                        });
+                    }
+                }
+            }
+        }
+        violations
+    }
+}
+
+impl RefGraph {
+    /// Compute per-topic metrics. See the [module docs](super::metrics).
+    pub fn metrics(&self) -> GraphMetrics {
+        let is_flow = |edge: &RefEdge| {
+            matches!(edge, RefEdge::Routes | RefEdge::TransitionsTo | RefEdge::Delegates)
+        };
+        let neighbors = |idx: NodeIndex, direction: Direction| {
+            self.graph
+                .edges_directed(idx, direction)
+                .filter(|e| is_flow(e.weight()))
+                .map(move |e| match direction {
+                    Direction::Outgoing => e.target(),
+                    Direction::Incoming => e.source(),
+                })
+                .collect::<BTreeSet<_>>()
+        };
+
+        let mut depths = HashMap::new();
+        if let Some(start) = self.start_agent {
+            depths.insert(start, 0);
+            let mut queue = VecDeque::from([start]);
+            while let Some(idx) = queue.pop_front() {
+                let depth = depths[&idx];
+                for next in neighbors(idx, Direction::Outgoing) {
+                    depths.entry(next).or_insert_with(|| {
+                        queue.push_back(next);
+                        depth + 1
+                    });
+                }
+            }
+        }
+
+        let topics = self
+            .graph
+            .node_indices()
+            .filter_map(|idx| {
+                let name = match &self.graph[idx] {
+                    RefNode::StartAgent { .. } => "start_agent",
+                    RefNode::Topic { name, .. } => name,
+                    _ => return None,
+                };
+                let block = self.block_complexity.get(&idx).copied().unwrap_or_default();
+                Some(TopicMetrics {
+                    node: idx,
+                    name: name.to_string(),
+                    fan_in: neighbors(idx, Direction::Incoming).len(),
+                    fan_out: neighbors(idx, Direction::Outgoing).len(),
+                    depth: depths.get(&idx).copied(),
+                    reasoning_actions: self.get_topic_reasoning_actions(name).len(),
+                    complexity: block.complexity.max(1),
+                    max_instruction_nesting: block.instruction_nesting,
+                })
+            })
+            .collect();
+        GraphMetrics { topics }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metrics() {
+        let source = r#"variables:
+   a: mutable boolean = False
+   b: mutable boolean = False
+
+start_agent selector:
+   description: "Route"
+   reasoning:
+      instructions: "Select"
+      actions:
+         go_orders: @utils.transition to @topic.orders
+         go_billing: @utils.transition to @topic.billing
+
+topic orders:
+   description: "Orders"
+   before_reasoning:
+      if @variables.a or @variables.b:
+         if @variables.a:
+            set @variables.b = True
+      else:
+         set @variables.a = True
+   reasoning:
+      instructions:->
+         | Help with orders
+         if @variables.a:
+            | Only a
+         else:
+            | Not a
+      actions:
+         to_billing: @utils.transition to @topic.billing
+         to_refunds: @utils.transition to @topic.refunds
+
+topic billing:
+   description: "Billing"
+   reasoning:
+      instructions: "Bill"
+
+topic refunds:
+   description: "Refunds"
+   reasoning:
+      instructions: "Refund"
+
+topic orphan:
+   description: "Orphan"
+   reasoning:
+      instructions: "Unused"
+"#;
+        let graph = RefGraph::from_ast(&crate::parse(source).unwrap()).unwrap();
+        let metrics = graph.metrics();
+
+        let names: Vec<_> = metrics.topics.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, ["start_agent", "orders", "billing", "refunds", "orphan"]);
+
+        let start = metrics.topic("start_agent").unwrap();
+        assert_eq!((start.fan_in, start.fan_out, start.depth), (0, 2, Some(0)));
+        assert_eq!(start.reasoning_actions, 2);
+
+        let orders = metrics.topic("orders").unwrap();
+        assert_eq!((orders.fan_in, orders.fan_out, orders.depth), (1, 2, Some(1)));
+        assert_eq!(orders.complexity, 4);
+        assert_eq!(orders.max_instruction_nesting, 1);
+
+        let billing = metrics.topic("billing").unwrap();
+        assert_eq!((billing.fan_in, billing.depth, billing.complexity), (2, Some(1), 1));
+        assert_eq!(metrics.topic("refunds").unwrap().depth, Some(2));
+        assert_eq!(metrics.topic("orphan").unwrap().depth, None);
+        assert_eq!(metrics.max_depth(), Some(2));
+
+        let budget = MetricsBudget {
+            max_fan_in: Some(1),
+            max_instruction_nesting: Some(0),
+            ..Default::default()
+        };
+        let violations: Vec<_> = metrics
+            .check(&budget)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            violations,
+            [
+                "orders: max_instruction_nesting 1 exceeds budget of 0",
+                "billing: fan_in 2 exceeds budget of 1",
+            ]
+        );
+    }
+}
@@ -7,11 +7,20 @@
 //!
 //! ## Features
 //!
-//! - **Reference Resolution**: Validate that all `@variables.*`, `@actions.*`, `@topic.*` references resolve
+//! - **Reference Resolution**: Validate that all `@variables.*`, `@actions.*`, `@topic.*` references resolve, exactly or ignoring case per [`ResolutionPolicy`], with near-miss suggestions
 //! - **Cycle Detection**: Ensure topic transitions form a DAG (no cycles)
 //! - **Reachability Analysis**: Find unreachable topics from `start_agent`
 //! - **Usage Queries**: Find all usages of a definition, or all dependencies of a node
 //! - **Dead Code Detection**: Identify unused actions and variables
+//! - **Data Flow**: Find variables read before they are set, or overwritten unread, via [`analyze_dataflow`]
+//! - **Filtered Views**: Render or export a subset of the graph via [`RefGraph::filter`]
+//! - **Topic Subgraphs**: Extract a focused, owned graph for one topic via [`RefGraph::subgraph_for_topic`]
+//! - **Health Score**: Combine lint, structure, coverage, token and dependency signals via [`health_report`]
+//! - **Metrics**: Per-topic fan-in/out, depth and complexity, checked against budgets, via [`RefGraph::metrics`]
+//! - **petgraph Interop**: Copy into a serializable [`StableRefGraph`] for arbitrary petgraph algorithms via [`RefGraph::to_petgraph_stable`]
+//! - **Auto-Layout**: Layered `x`/`y` hints for drawing the graph via [`RefGraph::layered_layout`]
+//! - **Rename**: Span-exact edits renaming a definition and its references via [`RefGraph::rename_symbol`]
+//! - **Notebook Display**: SVG through Graphviz via [`RefGraph::to_svg`], and an evcxr display hook
 //!
 //! ## Example
 //!
@@ -40,57 +49,119 @@
 //! ```
 
 mod builder;
+pub mod dataflow;
 pub mod dependencies;
 mod edges;
 mod error;
 pub mod export;
+pub mod health;
+mod layout;
+pub mod metrics;
 mod nodes;
+mod params;
 mod queries;
+mod rename;
 pub mod render;
+mod subgraph;
 mod validation;
+mod view;
 
 #[cfg(feature = "wasm")]
 pub mod wasm;
 
-pub use builder::RefGraphBuilder;
-pub use dependencies::{extract_dependencies, Dependency, DependencyReport, DependencyType};
-pub use edges::RefEdge;
+pub use builder::{RefGraphBuilder, ResolutionPolicy};
+pub use dataflow::{
+    analyze_dataflow, AccessKind, AccessSite, DataFlowIssue, DataFlowReport, VariableFlow,
+};
+pub use dependencies::{
+    extract_dependencies, Dependency, DependencyDiff, DependencyManifest, DependencyReport,
+    DependencyType, ManifestEntry, ManifestUsage,
+};
+pub use edges::{EdgeKind, RefEdge};
 pub use error::{GraphBuildError, ValidationError};
-pub use export::{EdgeRepr, GraphExport, GraphRepr, NodeRepr, ValidationResultRepr};
-pub use nodes::RefNode;
+pub use export::{
+    EdgeRepr, GraphExport, GraphMetricsRepr, GraphRepr, NodeRepr, TopicMetricsRepr,
+    ValidationResultRepr,
+};
+pub use health::{health_report, HealthOptions, HealthReport};
+pub use layout::{LayoutOptions, NodePosition};
+pub use metrics::{BudgetViolation, GraphMetrics, MetricsBudget, TopicMetrics};
+pub use nodes::{NodeKind, RefNode, Span};
 pub use queries::QueryResult;
-pub use render::{render_actions_view, render_full_view, render_graphml, render_topic_flow};
-pub use validation::ValidationResult;
+pub use rename::{OccurrenceKind, RenameEdit, RenameError};
+pub use render::{
+    render_actions_view, render_dot, render_full_view, render_graphml, render_mermaid, render_svg,
+    render_topic_flow, render_topic_flow_svg, DotOptions, GraphvizError, SvgOptions,
+};
+pub use validation::{PassId, ValidationResult};
+pub use view::RefGraphView;
 
-use petgraph::graph::{DiGraph, NodeIndex};
+/// Index of a node in a [`RefGraph`], as returned by lookups like
+/// [`RefGraph::get_topic`].
+pub use petgraph::graph::NodeIndex;
+
+use petgraph::graph::{DiGraph, EdgeIndex};
+use petgraph::stable_graph::StableDiGraph;
 use std::collections::HashMap;
 
+/// A reference graph as a petgraph [`StableDiGraph`], from
+/// [`RefGraph::to_petgraph_stable`].
+///
+/// Node indices stay valid when other nodes are removed, and the graph
+/// implements `Serialize` and `Deserialize`.
+pub type StableRefGraph = StableDiGraph<RefNode, RefEdge>;
+
 /// A reference graph built from an AgentScript AST.
 ///
 /// The graph represents relationships between definitions (topics, actions, variables)
 /// and can be used for validation, analysis, and querying.
 #[derive(Debug)]
 pub struct RefGraph {
+    /// How names in the indexes below are matched
+    policy: ResolutionPolicy,
+
     /// The underlying directed graph
     graph: DiGraph<RefNode, RefEdge>,
 
-    /// Index of topic nodes by name
+    /// Index of topic nodes by name key
     topics: HashMap<String, NodeIndex>,
 
-    /// Index of action definition nodes by (topic_name, action_name)
+    /// Index of action definition nodes by (topic_name, action_name) keys
     action_defs: HashMap<(String, String), NodeIndex>,
 
-    /// Index of reasoning action nodes by (topic_name, action_name)
+    /// Index of reasoning action nodes by (topic_name, action_name) keys
     reasoning_actions: HashMap<(String, String), NodeIndex>,
 
-    /// Index of variable nodes by name
+    /// Index of variable nodes by name key
     variables: HashMap<String, NodeIndex>,
 
+    /// Index of connection nodes by name
+    connections: HashMap<String, NodeIndex>,
+
     /// The start_agent node index (if present)
     start_agent: Option<NodeIndex>,
 
     /// References that could not be resolved during build
     unresolved_references: Vec<ValidationError>,
+
+    /// Names defined more than once, found during build
+    duplicate_definitions: Vec<ValidationError>,
+
+    /// For subgraphs, the index of each node in the parent graph; empty
+    /// for graphs built from an AST
+    parent_indices: Vec<NodeIndex>,
+
+    /// Directive and instruction complexity of start_agent and topic nodes
+    block_complexity: HashMap<NodeIndex, metrics::BlockComplexity>,
+
+    /// Span of the `@...` reference each edge was created from
+    edge_spans: HashMap<EdgeIndex, Span>,
+
+    /// Name spans of each node's definition and resolved references
+    symbol_spans: HashMap<NodeIndex, rename::SymbolSpans>,
+
+    /// Parameters of each action definition, and whether they are bound
+    param_usage: HashMap<NodeIndex, Vec<params::ParamUsage>>,
 }
 
 impl RefGraph {
@@ -107,33 +178,92 @@ impl RefGraph {
         &self.graph
     }
 
+    /// Copy the graph into an owned petgraph [`StableDiGraph`].
+    ///
+    /// Nodes keep their indices, so lookups like [`get_topic`](Self::get_topic)
+    /// can be used on the copy. Use it to run petgraph algorithms that
+    /// remove nodes, or to persist the graph with serde without going
+    /// through [`GraphRepr`].
+    ///
+    /// ```rust
+    /// use busbar_sf_agentscript::graph::{RefGraph, StableRefGraph};
+    /// use petgraph::algo::has_path_connecting;
+    ///
+    /// let source = r#"
+    /// start_agent main:
+    ///    description: "Route"
+    ///    reasoning:
+    ///       instructions: "Route"
+    ///       actions:
+    ///          go: @utils.transition to @topic.orders
+    ///
+    /// topic orders:
+    ///    description: "Orders"
+    ///    reasoning:
+    ///       instructions: "Help"
+    /// "#;
+    /// let graph = RefGraph::from_ast(&busbar_sf_agentscript::parse(source).unwrap()).unwrap();
+    /// let stable = graph.to_petgraph_stable();
+    /// let start = graph.get_start_agent().unwrap();
+    /// let orders = graph.get_topic("orders").unwrap();
+    /// assert!(has_path_connecting(&stable, start, orders, None));
+    ///
+    /// let json = serde_json::to_string(&stable).unwrap();
+    /// let restored: StableRefGraph = serde_json::from_str(&json).unwrap();
+    /// assert_eq!(restored[orders], stable[orders]);
+    /// ```
+    pub fn to_petgraph_stable(&self) -> StableRefGraph {
+        StableDiGraph::from(self.graph.clone())
+    }
+
     /// Get a node by its index.
     pub fn get_node(&self, index: NodeIndex) -> Option<&RefNode> {
         self.graph.node_weight(index)
     }
 
+    /// Get the span of the `@...` reference an edge was created from.
+    ///
+    /// The span covers the whole reference, such as `@variables.order_id`,
+    /// but not property accesses after it.
+    pub fn reference_span(&self, edge: EdgeIndex) -> Option<Span> {
+        self.edge_spans.get(&edge).copied()
+    }
+
+    /// The policy names were matched by when the graph was built.
+    pub fn policy(&self) -> ResolutionPolicy {
+        self.policy
+    }
+
     /// Look up a topic node by name.
+    ///
+    /// Names are matched as the graph's [`ResolutionPolicy`] says; the
+    /// first of several topics with the same name is returned.
     pub fn get_topic(&self, name: &str) -> Option<NodeIndex> {
-        self.topics.get(name).copied()
+        self.topics.get(&self.policy.key(name)).copied()
     }
 
     /// Look up an action definition node by topic and action name.
     pub fn get_action_def(&self, topic: &str, action: &str) -> Option<NodeIndex> {
         self.action_defs
-            .get(&(topic.to_string(), action.to_string()))
+            .get(&(self.policy.key(topic), self.policy.key(action)))
             .copied()
     }
 
     /// Look up a reasoning action node by topic and action name.
     pub fn get_reasoning_action(&self, topic: &str, action: &str) -> Option<NodeIndex> {
         self.reasoning_actions
-            .get(&(topic.to_string(), action.to_string()))
+            .get(&(self.policy.key(topic), self.policy.key(action)))
             .copied()
     }
 
     /// Look up a variable node by name.
     pub fn get_variable(&self, name: &str) -> Option<NodeIndex> {
-        self.variables.get(name).copied()
+        self.variables.get(&self.policy.key(name)).copied()
+    }
+
+    /// Look up a connection node by name.
+    pub fn get_connection(&self, name: &str) -> Option<NodeIndex> {
+        self.connections.get(name).copied()
     }
 
     /// Get the start_agent node index.
@@ -143,12 +273,16 @@ impl RefGraph {
 
     /// Get all topic names in the graph.
     pub fn topic_names(&self) -> impl Iterator<Item = &str> {
-        self.topics.keys().map(|s| s.as_str())
+        self.topics
+            .values()
+            .filter_map(|&idx| self.graph[idx].name())
     }
 
     /// Get all variable names in the graph.
     pub fn variable_names(&self) -> impl Iterator<Item = &str> {
-        self.variables.keys().map(|s| s.as_str())
+        self.variables
+            .values()
+            .filter_map(|&idx| self.graph[idx].name())
     }
 
     /// Get the number of nodes in the graph.
@@ -192,4 +326,184 @@ topic help:
         assert!(graph.get_topic("help").is_some());
         assert!(graph.get_start_agent().is_some());
     }
+
+    #[test]
+    fn test_to_petgraph_stable() {
+        let source = r#"variables:
+   order_id: mutable string = ""
+
+start_agent topic_selector:
+   description: "Route"
+   reasoning:
+      instructions: "Select"
+      actions:
+         go_help: @utils.transition to @topic.help
+
+topic help:
+   description: "Help"
+   reasoning:
+      instructions: "Help"
+      actions:
+         save: @utils.setVariables
+            with order_id=...
+"#;
+        let graph = RefGraph::from_ast(&crate::parse(source).unwrap()).unwrap();
+        let mut stable = graph.to_petgraph_stable();
+        assert_eq!(stable.node_count(), graph.node_count());
+        assert_eq!(stable.edge_count(), graph.edge_count());
+
+        // Indices survive removing other nodes, and a serde round trip
+        let help = graph.get_topic("help").unwrap();
+        stable.remove_node(graph.get_variable("order_id").unwrap());
+        let json = serde_json::to_value(&stable).unwrap();
+        let restored: StableRefGraph = serde_json::from_value(json).unwrap();
+        assert_eq!(restored.node_count(), graph.node_count() - 1);
+        assert_eq!(restored.node_weight(help), graph.get_node(help));
+        assert!(restored
+            .node_weight(graph.get_variable("order_id").unwrap())
+            .is_none());
+    }
+
+    #[test]
+    fn test_node_and_edge_kinds() {
+        // Serialized names are part of the export format
+        for kind in NodeKind::ALL {
+            assert_eq!(serde_json::to_value(kind).unwrap(), kind.as_str());
+        }
+        for kind in EdgeKind::ALL {
+            assert_eq!(serde_json::to_value(kind).unwrap(), kind.as_str());
+        }
+
+        let source = r#"start_agent topic_selector:
+   description: "Route"
+   reasoning:
+      instructions: "Select"
+      actions:
+         go_help: @utils.transition to @topic.help
+
+topic help:
+   description: "Help"
+   reasoning:
+      instructions: "Help"
+"#;
+        let graph = RefGraph::from_ast(&crate::parse(source).unwrap()).unwrap();
+        let json = serde_json::to_value(GraphRepr::from(&graph)).unwrap();
+        let node_types: Vec<_> = json["nodes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|n| n["node_type"].as_str().unwrap())
+            .collect();
+        assert!(node_types.contains(&"start_agent"));
+        assert!(node_types.contains(&"topic"));
+        assert!(node_types.contains(&"reasoning_action"));
+        assert!(json["edges"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|e| e["edge_type"] == "routes"));
+        assert_eq!(
+            graph
+                .get_node(graph.get_topic("help").unwrap())
+                .unwrap()
+                .kind(),
+            NodeKind::Topic
+        );
+    }
+
+    #[test]
+    fn test_knowledge_base_nodes() {
+        let source = r#"config:
+   agent_name: "Test"
+
+knowledge:
+   policies: "KnowledgeBase://InsurancePolicies"
+   citations_enabled: True
+
+topic help:
+   description: "Help"
+   reasoning:
+      instructions: "Help"
+"#;
+        let graph = RefGraph::from_ast(&crate::parse(source).unwrap()).unwrap();
+        let knowledge: Vec<_> = graph
+            .inner()
+            .node_weights()
+            .filter(|n| matches!(n, RefNode::KnowledgeBase { .. }))
+            .collect();
+        assert_eq!(knowledge.len(), 1);
+        assert_eq!(knowledge[0].label(), "knowledge:policies");
+        assert_eq!(knowledge[0].kind(), NodeKind::External);
+        assert_eq!(graph.stats().knowledge_bases, 1);
+        assert!(graph.validate().errors.is_empty());
+    }
+
+    #[test]
+    fn test_reference_spans() {
+        let source = r#"variables:
+   step: mutable number = 0
+   step_count: mutable number = 0
+
+start_agent topic_selector:
+   description: "Route"
+   reasoning:
+      instructions: "Select"
+      actions:
+         go_help: @utils.transition to @topic.help
+
+topic help:
+   description: "Help"
+   actions:
+      lookup:
+         description: "Look up"
+         target: "flow://Lookup"
+   reasoning:
+      instructions:->
+         | Step {!@variables.step} of {!@variables.step_count}
+      actions:
+         do_lookup: @actions.lookup
+            available when @variables.step_count > 0
+            with step=@variables.step
+            set @variables.step = @outputs.next
+"#;
+        let graph = RefGraph::from_ast(&crate::parse(source).unwrap()).unwrap();
+
+        // Every edge records the reference naming its target
+        for edge in graph.inner().edge_indices() {
+            let (_, target) = graph.inner().edge_endpoints(edge).unwrap();
+            let (start, end) = graph.reference_span(edge).unwrap();
+            let text = &source[start..end];
+            assert!(text.starts_with('@'), "{text}");
+            assert!(
+                text.ends_with(&format!(".{}", graph.get_node(target).unwrap().name().unwrap())),
+                "{text}"
+            );
+        }
+
+        let step = graph.get_variable("step").unwrap();
+        let occurrences = graph.occurrences_of(step);
+        assert_eq!(occurrences.len(), 4);
+        assert!(occurrences.windows(2).all(|w| w[0] < w[1]));
+        assert!(occurrences.iter().all(|&(s, e)| &source[s..e] == "step"));
+    }
+
+    #[test]
+    fn test_property_access_resolves_root_reference() {
+        let source = r#"variables:
+   order: mutable object = {}
+
+topic main:
+   description: "Main"
+   reasoning:
+      instructions:->
+         | Total: {!@variables.order.total}, first item: {!@variables.order.items[0].sku}
+"#;
+        let graph = RefGraph::from_ast(&crate::parse(source).unwrap()).unwrap();
+        assert!(graph.validate().errors.is_empty());
+
+        let order = graph.get_variable("order").unwrap();
+        let occurrences = graph.occurrences_of(order);
+        assert_eq!(occurrences.len(), 3);
+        assert!(occurrences.iter().all(|&(s, e)| &source[s..e] == "order"));
+    }
 }
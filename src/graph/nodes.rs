@@ -61,9 +61,90 @@ pub enum RefNode {
         /// Source location
         span: Span,
     },
+
+    /// A knowledge source from the `knowledge:` block
+    KnowledgeBase {
+        /// Entry name
+        name: String,
+        /// The configured source, e.g. `KnowledgeBase://Policies`
+        source: String,
+        /// Source location
+        span: Span,
+    },
+}
+
+/// The kind of a [`RefNode`], for styling and filtering exports.
+///
+/// This is the `node_type` of [`NodeRepr`](super::NodeRepr) and the other
+/// export types. The set of kinds and their serialized names (`"topic"`,
+/// `"start_agent"`, ...) are stable: adding or renaming a kind is a breaking
+/// change, so downstream visualizers can match on them exhaustively.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeKind {
+    /// A topic definition
+    Topic,
+    /// The start_agent entry point
+    StartAgent,
+    /// An action definition within a topic
+    ActionDef,
+    /// A reasoning action within a topic
+    ReasoningAction,
+    /// A variable definition
+    Variable,
+    /// A connection outside the agent, such as an escalation target or
+    /// a knowledge source
+    External,
+    /// A built-in `@utils` target. The graph doesn't create nodes for
+    /// these yet; reasoning actions record them in their `target`.
+    Util,
+}
+
+impl NodeKind {
+    /// Every kind, in declaration order.
+    pub const ALL: [NodeKind; 7] = [
+        NodeKind::Topic,
+        NodeKind::StartAgent,
+        NodeKind::ActionDef,
+        NodeKind::ReasoningAction,
+        NodeKind::Variable,
+        NodeKind::External,
+        NodeKind::Util,
+    ];
+
+    /// The serialized name of this kind.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NodeKind::Topic => "topic",
+            NodeKind::StartAgent => "start_agent",
+            NodeKind::ActionDef => "action_def",
+            NodeKind::ReasoningAction => "reasoning_action",
+            NodeKind::Variable => "variable",
+            NodeKind::External => "external",
+            NodeKind::Util => "util",
+        }
+    }
+}
+
+impl std::fmt::Display for NodeKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
 }
 
 impl RefNode {
+    /// Get the kind of this node.
+    pub fn kind(&self) -> NodeKind {
+        match self {
+            RefNode::StartAgent { .. } => NodeKind::StartAgent,
+            RefNode::Topic { .. } => NodeKind::Topic,
+            RefNode::ActionDef { .. } => NodeKind::ActionDef,
+            RefNode::ReasoningAction { .. } => NodeKind::ReasoningAction,
+            RefNode::Variable { .. } => NodeKind::Variable,
+            RefNode::Connection { .. } | RefNode::KnowledgeBase { .. } => NodeKind::External,
+        }
+    }
+
     /// Get a human-readable label for this node.
     pub fn label(&self) -> String {
         match self {
@@ -75,6 +156,7 @@ impl RefNode {
             }
             RefNode::Variable { name, .. } => format!("variable:{}", name),
             RefNode::Connection { name, .. } => format!("connection:{}", name),
+            RefNode::KnowledgeBase { name, .. } => format!("knowledge:{}", name),
         }
     }
 
@@ -86,7 +168,8 @@ impl RefNode {
             | RefNode::ActionDef { span, .. }
             | RefNode::ReasoningAction { span, .. }
             | RefNode::Variable { span, .. }
-            | RefNode::Connection { span, .. } => *span,
+            | RefNode::Connection { span, .. }
+            | RefNode::KnowledgeBase { span, .. } => *span,
         }
     }
 
@@ -98,7 +181,8 @@ impl RefNode {
             | RefNode::ActionDef { name, .. }
             | RefNode::ReasoningAction { name, .. }
             | RefNode::Variable { name, .. }
-            | RefNode::Connection { name, .. } => Some(name),
+            | RefNode::Connection { name, .. }
+            | RefNode::KnowledgeBase { name, .. } => Some(name),
         }
     }
 
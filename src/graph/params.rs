@@ -0,0 +1,228 @@
+//! Action parameters and the invocations that bind them.
+
+use super::nodes::Span;
+use super::RefGraph;
+use crate::ast::{
+    ActionsBlock, AgentFile, Expr, ParamDef, ReasoningActionTarget, ReasoningBlock, Reference,
+    SetClause, Spanned, Stmt, WithClause,
+};
+use petgraph::graph::NodeIndex;
+use std::collections::HashMap;
+
+/// A declared input or output of an action definition.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct ParamUsage {
+    /// Parameter name
+    pub name: String,
+    /// Whether this is an output (as opposed to an input)
+    pub output: bool,
+    /// Source location of the parameter definition
+    pub span: Span,
+    /// Whether some invocation binds the input with `with`, or captures
+    /// the output with `set`
+    pub used: bool,
+}
+
+/// Record the parameters of every action definition in `ast`, which
+/// `graph` was built from, and whether any invocation uses them.
+pub(super) fn collect_param_usage(
+    graph: &RefGraph,
+    ast: &AgentFile,
+) -> HashMap<NodeIndex, Vec<ParamUsage>> {
+    let mut collector = Collector {
+        graph,
+        scope: String::new(),
+        params: HashMap::new(),
+    };
+    let start = ast.start_agent.iter().map(|start| {
+        let start = &start.node;
+        (
+            "start_agent".to_string(),
+            start.actions.as_ref(),
+            [
+                start.before_reasoning.as_ref(),
+                start.after_reasoning.as_ref(),
+            ],
+            start.reasoning.as_ref(),
+        )
+    });
+    let topics = ast.topics.iter().map(|topic| {
+        let topic = &topic.node;
+        (
+            topic.name.node.clone(),
+            topic.actions.as_ref(),
+            [
+                topic.before_reasoning.as_ref(),
+                topic.after_reasoning.as_ref(),
+            ],
+            topic.reasoning.as_ref(),
+        )
+    });
+    let blocks: Vec<_> = start.chain(topics).collect();
+
+    // Declarations first, so invocations in any block can mark them
+    for (scope, actions, _, _) in &blocks {
+        collector.scope = scope.clone();
+        collector.declare(*actions);
+    }
+    for (scope, _, directives, reasoning) in &blocks {
+        collector.scope = scope.clone();
+        for directives in directives.iter().flatten() {
+            collector.stmts(&directives.node.statements);
+        }
+        if let Some(reasoning) = reasoning {
+            collector.reasoning(reasoning);
+        }
+    }
+    collector.params
+}
+
+struct Collector<'g> {
+    graph: &'g RefGraph,
+    /// Topic whose actions `@actions` references resolve to.
+    scope: String,
+    params: HashMap<NodeIndex, Vec<ParamUsage>>,
+}
+
+impl Collector<'_> {
+    fn declare(&mut self, actions: Option<&Spanned<ActionsBlock>>) {
+        for action in actions.iter().flat_map(|a| &a.node.actions) {
+            let Some(idx) = self
+                .graph
+                .get_action_def(&self.scope, &action.node.name.node)
+            else {
+                continue;
+            };
+            let params = |list: &Option<Spanned<Vec<Spanned<ParamDef>>>>, output: bool| {
+                list.iter()
+                    .flat_map(|l| &l.node)
+                    .map(move |param| ParamUsage {
+                        name: param.node.name.node.clone(),
+                        output,
+                        span: (param.span.start, param.span.end),
+                        used: false,
+                    })
+                    .collect::<Vec<_>>()
+            };
+            let entry = self.params.entry(idx).or_default();
+            entry.extend(params(&action.node.inputs, false));
+            entry.extend(params(&action.node.outputs, true));
+        }
+    }
+
+    fn reasoning(&mut self, reasoning: &Spanned<ReasoningBlock>) {
+        for action in reasoning.node.actions.iter().flat_map(|a| &a.node) {
+            let action = &action.node;
+            let ReasoningActionTarget::Action(target) = &action.target.node else {
+                continue;
+            };
+            // Outputs of a reasoning action can also decide its transitions
+            let conditions = action.if_clauses.iter().map(|c| &c.node.condition);
+            self.invoke(target, &action.with_clauses, &action.set_clauses, conditions);
+            for run in &action.run_clauses {
+                let run = &run.node;
+                self.invoke(&run.action.node, &run.with_clauses, &run.set_clauses, []);
+            }
+        }
+    }
+
+    fn stmts(&mut self, stmts: &[Spanned<Stmt>]) {
+        for stmt in stmts {
+            match &stmt.node {
+                Stmt::Run {
+                    action,
+                    with_clauses,
+                    set_clauses,
+                } => self.invoke(&action.node, with_clauses, set_clauses, []),
+                Stmt::If {
+                    then_block,
+                    else_block,
+                    ..
+                } => {
+                    self.stmts(then_block);
+                    if let Some(else_block) = else_block {
+                        self.stmts(else_block);
+                    }
+                }
+                Stmt::Set { .. }
+                | Stmt::Transition { .. }
+                | Stmt::Escalate
+                | Stmt::Respond { .. } => {}
+            }
+        }
+    }
+
+    /// Mark the inputs `with_clauses` bind and the outputs `set_clauses`
+    /// and `conditions` read as used, on the action `target` names.
+    fn invoke<'a>(
+        &mut self,
+        target: &Reference,
+        with_clauses: &[Spanned<WithClause>],
+        set_clauses: &[Spanned<SetClause>],
+        conditions: impl IntoIterator<Item = &'a Spanned<Expr>>,
+    ) {
+        let idx = match (target.namespace.as_str(), target.path.first()) {
+            ("actions", Some(name)) => self.graph.get_action_def(&self.scope, name),
+            _ => None,
+        };
+        let Some(params) = idx.and_then(|idx| self.params.get_mut(&idx)) else {
+            return;
+        };
+
+        let mut outputs = Vec::new();
+        for clause in set_clauses {
+            output_reads(&clause.node.source.node, &mut outputs);
+        }
+        for condition in conditions {
+            output_reads(&condition.node, &mut outputs);
+        }
+        for param in params.iter_mut() {
+            param.used |= if param.output {
+                outputs.contains(&param.name.as_str())
+            } else {
+                with_clauses.iter().any(|c| c.node.param.node == param.name)
+            };
+        }
+    }
+}
+
+/// Collect the names of the `@outputs.*` an expression reads.
+fn output_reads<'a>(expr: &'a Expr, out: &mut Vec<&'a str>) {
+    let mut child = |e: &'a Spanned<Expr>| output_reads(&e.node, out);
+    match expr {
+        Expr::Reference(r) => {
+            if let ("outputs", Some(name)) = (r.namespace.as_str(), r.path.first()) {
+                out.push(name);
+            }
+        }
+        Expr::Template(items) | Expr::List(items) => items.iter().for_each(child),
+        Expr::Object(fields) => fields.values().for_each(child),
+        Expr::BinOp { left, right, .. } => {
+            child(left);
+            child(right);
+        }
+        Expr::UnaryOp { operand, .. } => child(operand),
+        Expr::Ternary {
+            condition,
+            then_expr,
+            else_expr,
+        } => {
+            child(condition);
+            child(then_expr);
+            child(else_expr);
+        }
+        Expr::Property { object, .. } => child(object),
+        Expr::Index { object, index } => {
+            child(object);
+            child(index);
+        }
+        Expr::Call { args, .. } => args.iter().for_each(child),
+        Expr::String(_)
+        | Expr::Number(_)
+        | Expr::Date(_)
+        | Expr::Currency(_)
+        | Expr::Bool(_)
+        | Expr::None
+        | Expr::SlotFill => {}
+    }
+}
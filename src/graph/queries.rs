@@ -1,7 +1,7 @@
 //! Query operations on the reference graph.
 
 use super::edges::RefEdge;
-use super::nodes::RefNode;
+use super::nodes::{RefNode, Span};
 use super::RefGraph;
 use petgraph::algo::toposort;
 use petgraph::graph::NodeIndex;
@@ -80,12 +80,13 @@ impl RefGraph {
         QueryResult { nodes }
     }
 
-    /// Find all reasoning actions that invoke the given action definition.
+    /// Find all nodes that invoke the given action definition: reasoning
+    /// actions, and topics or start_agent whose directives `run` it.
     pub fn find_action_invokers(&self, action_def: NodeIndex) -> QueryResult {
         let nodes = self
             .graph
             .edges_directed(action_def, Direction::Incoming)
-            .filter(|e| matches!(e.weight(), RefEdge::Invokes))
+            .filter(|e| matches!(e.weight(), RefEdge::Invokes | RefEdge::Chains))
             .map(|e| e.source())
             .collect();
 
@@ -116,6 +117,42 @@ impl RefGraph {
         QueryResult { nodes }
     }
 
+    /// Find the transitions into a topic, with where each one is written.
+    ///
+    /// Returns the topic or start_agent making each transition, routing or
+    /// delegation, with the span of its `@topic` reference, in source order.
+    pub fn transition_sites_into(&self, topic: NodeIndex) -> Vec<(NodeIndex, Span)> {
+        self.transition_sites(topic, Direction::Incoming)
+    }
+
+    /// Find the transitions out of a topic or start_agent, with where each
+    /// one is written.
+    ///
+    /// Returns the target topic of each transition, routing or delegation,
+    /// with the span of its `@topic` reference, in source order.
+    pub fn transition_sites_from(&self, node: NodeIndex) -> Vec<(NodeIndex, Span)> {
+        self.transition_sites(node, Direction::Outgoing)
+    }
+
+    fn transition_sites(&self, node: NodeIndex, direction: Direction) -> Vec<(NodeIndex, Span)> {
+        let mut sites: Vec<_> = self
+            .graph
+            .edges_directed(node, direction)
+            .filter(|e| {
+                matches!(e.weight(), RefEdge::Routes | RefEdge::TransitionsTo | RefEdge::Delegates)
+            })
+            .filter_map(|e| {
+                let other = match direction {
+                    Direction::Incoming => e.source(),
+                    Direction::Outgoing => e.target(),
+                };
+                Some((other, self.reference_span(e.id())?))
+            })
+            .collect();
+        sites.sort_by_key(|&(_, span)| span);
+        sites
+    }
+
     /// Get a topological ordering of topics (for execution order).
     ///
     /// Returns None if there are cycles.
@@ -132,6 +169,91 @@ impl RefGraph {
         })
     }
 
+    /// Enumerate transition paths from one topic to another.
+    ///
+    /// Each path starts at `from_topic`, ends at `to_topic`, and follows
+    /// transition and delegation edges without visiting a topic twice.
+    /// `max_depth` is the maximum number of transitions in a path. Returns no
+    /// paths if either topic doesn't exist.
+    pub fn paths_between(
+        &self,
+        from_topic: &str,
+        to_topic: &str,
+        max_depth: usize,
+    ) -> Vec<Vec<NodeIndex>> {
+        let (Some(from), Some(to)) = (self.get_topic(from_topic), self.get_topic(to_topic)) else {
+            return Vec::new();
+        };
+
+        let mut paths = Vec::new();
+        let mut path = vec![from];
+        self.walk_transitions(&mut path, max_depth, &mut |path, _| {
+            if path.last() == Some(&to) {
+                paths.push(path.to_vec());
+                false
+            } else {
+                true
+            }
+        });
+        paths
+    }
+
+    /// Enumerate every conversation flow starting at start_agent.
+    ///
+    /// Each path starts with the start_agent node, followed by the topics it
+    /// passes through. A path ends at a topic with no further transitions, or
+    /// where every transition leads back to a topic already on the path.
+    /// Paths are returned in source order, which makes them suitable for
+    /// generating test scenarios.
+    pub fn all_paths_from_start(&self) -> Vec<Vec<NodeIndex>> {
+        let Some(start) = self.start_agent else {
+            return Vec::new();
+        };
+
+        let mut paths = Vec::new();
+        let mut path = vec![start];
+        self.walk_transitions(&mut path, usize::MAX, &mut |path, is_leaf| {
+            if is_leaf && path.len() > 1 {
+                paths.push(path.to_vec());
+            }
+            true
+        });
+        paths
+    }
+
+    /// Depth-first walk over simple transition paths starting at the last
+    /// node of `path`. `visit` is called for every path with whether it can
+    /// be extended, and returns whether to keep extending it.
+    fn walk_transitions(
+        &self,
+        path: &mut Vec<NodeIndex>,
+        max_depth: usize,
+        visit: &mut impl FnMut(&[NodeIndex], bool) -> bool,
+    ) {
+        let current = *path.last().expect("path is never empty");
+        let mut next: Vec<NodeIndex> = self
+            .graph
+            .edges_directed(current, Direction::Outgoing)
+            .filter(|e| {
+                matches!(e.weight(), RefEdge::Routes | RefEdge::TransitionsTo | RefEdge::Delegates)
+            })
+            .map(|e| e.target())
+            .filter(|target| !path.contains(target))
+            .collect();
+        next.sort();
+        next.dedup();
+
+        let at_limit = path.len() > max_depth;
+        if !visit(path, next.is_empty()) || at_limit {
+            return;
+        }
+        for target in next {
+            path.push(target);
+            self.walk_transitions(path, max_depth, visit);
+            path.pop();
+        }
+    }
+
     /// Get all reasoning actions in a topic.
     pub fn get_topic_reasoning_actions(&self, topic_name: &str) -> Vec<NodeIndex> {
         self.reasoning_actions
@@ -160,6 +282,7 @@ impl RefGraph {
                 Some(RefNode::Variable { .. }) => stats.variables += 1,
                 Some(RefNode::StartAgent { .. }) => stats.has_start_agent = true,
                 Some(RefNode::Connection { .. }) => stats.connections += 1,
+                Some(RefNode::KnowledgeBase { .. }) => stats.knowledge_bases += 1,
                 None => {}
             }
         }
@@ -186,6 +309,7 @@ pub struct GraphStats {
     pub reasoning_actions: usize,
     pub variables: usize,
     pub connections: usize,
+    pub knowledge_bases: usize,
     pub has_start_agent: bool,
     pub transitions: usize,
     pub invocations: usize,
@@ -196,7 +320,12 @@ pub struct GraphStats {
 impl GraphStats {
     /// Total number of definitions.
     pub fn total_definitions(&self) -> usize {
-        self.topics + self.action_defs + self.reasoning_actions + self.variables + self.connections
+        self.topics
+            + self.action_defs
+            + self.reasoning_actions
+            + self.variables
+            + self.connections
+            + self.knowledge_bases
     }
 
     /// Total number of edges.
@@ -268,6 +397,30 @@ topic topic_b:
         assert_eq!(result.nodes[0], topic_a_idx, "Expected transition source to be topic_a");
     }
 
+    #[test]
+    fn test_transition_sites() {
+        let source = two_topic_source();
+        let graph = parse_and_build(source);
+        let start = graph.get_start_agent().unwrap();
+        let topic_a = graph.get_topic("topic_a").unwrap();
+        let topic_b = graph.get_topic("topic_b").unwrap();
+
+        let into_a = graph.transition_sites_into(topic_a);
+        assert_eq!(into_a.len(), 1);
+        assert_eq!(into_a[0].0, start);
+        let (s, e) = into_a[0].1;
+        assert_eq!(&source[s..e], "@topic.topic_a");
+
+        let from_a = graph.transition_sites_from(topic_a);
+        assert_eq!(from_a.len(), 1);
+        assert_eq!(from_a[0].0, topic_b);
+        let (s, e) = from_a[0].1;
+        assert_eq!(&source[s..e], "@topic.topic_b");
+
+        assert_eq!(graph.transition_sites_from(start)[0].0, topic_a);
+        assert!(graph.transition_sites_from(topic_b).is_empty());
+    }
+
     #[test]
     fn test_find_outgoing_transitions_empty_for_leaf_topic() {
         // topic_b has no outgoing transitions — it is a leaf node.
@@ -278,6 +431,62 @@ topic topic_b:
         assert!(result.is_empty(), "Expected no outgoing transitions from leaf topic_b");
     }
 
+    #[test]
+    fn test_paths_between_and_from_start() {
+        // main can reach done directly or through detour; detour also loops
+        // back to main.
+        let source = r#"config:
+   agent_name: "Test"
+
+start_agent selector:
+   description: "Route"
+   reasoning:
+      instructions: "Select"
+      actions:
+         go_main: @utils.transition to @topic.main
+            description: "Go to main"
+
+topic main:
+   description: "Main"
+   reasoning:
+      instructions: "Main"
+      actions:
+         go_detour: @utils.transition to @topic.detour
+            description: "Detour"
+         go_done: @utils.transition to @topic.done
+            description: "Done"
+
+topic detour:
+   description: "Detour"
+   reasoning:
+      instructions: "Detour"
+      actions:
+         back: @utils.transition to @topic.main
+            description: "Back"
+         go_done: @utils.transition to @topic.done
+            description: "Done"
+
+topic done:
+   description: "Done"
+   reasoning:
+      instructions: "Done"
+"#;
+        let graph = parse_and_build(source);
+        let main = graph.get_topic("main").unwrap();
+        let detour = graph.get_topic("detour").unwrap();
+        let done = graph.get_topic("done").unwrap();
+
+        let paths = graph.paths_between("main", "done", 5);
+        assert_eq!(paths, vec![vec![main, detour, done], vec![main, done]]);
+        assert_eq!(graph.paths_between("main", "done", 1), vec![vec![main, done]]);
+        assert!(graph.paths_between("done", "main", 5).is_empty());
+        assert!(graph.paths_between("main", "missing", 5).is_empty());
+
+        let start = graph.get_start_agent().unwrap();
+        let flows = graph.all_paths_from_start();
+        assert_eq!(flows, vec![vec![start, main, detour, done], vec![start, main, done],]);
+    }
+
     #[test]
     fn test_topic_execution_order_for_acyclic_graph() {
         // An acyclic start → topic_a → topic_b graph should yield a valid topological
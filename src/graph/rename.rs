@@ -0,0 +1,785 @@
+//! Symbol occurrences and rename.
+//!
+//! While the graph is built, the name in every definition and in every
+//! `@variables`, `@actions` and `@topic` reference that resolves to a node
+//! is recorded with its exact span. [`RefGraph::rename_symbol`] turns these
+//! into edits, so renaming `step` never touches `step_count`, and renaming
+//! an action only touches references in the topic that defines it.
+//!
+//! References inside plain `instructions: "..."` and `instructions:|` text
+//! are not parsed, so they are not recorded.
+
+use super::nodes::{RefNode, Span};
+use super::RefGraph;
+use crate::ast::{
+    AgentFile, DirectiveBlock, Expr, InstructionPart, Instructions, ReasoningAction,
+    ReasoningActionTarget, Reference, SetClause, Spanned, Stmt, WithClause, WithValue,
+};
+use crate::edit::SourceEdit;
+use crate::lexer::keyword_table;
+use petgraph::graph::NodeIndex;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::ops::Range;
+use thiserror::Error;
+
+/// Where a node's name appears in the source.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SymbolSpans {
+    /// The name in the definition.
+    pub(crate) definition: Option<Range<usize>>,
+    /// The name in each resolved reference that reads the node, in
+    /// source order.
+    pub(crate) references: Vec<Range<usize>>,
+    /// The name in each `set` target assigning the node, in source order.
+    pub(crate) writes: Vec<Range<usize>>,
+}
+
+/// How a symbol occurrence from [`RefGraph::occurrences_with_kind`] uses
+/// the symbol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OccurrenceKind {
+    /// The name in the symbol's definition
+    Definition,
+    /// A reference that reads or invokes the symbol
+    Read,
+    /// A `set` target assigning the symbol
+    Write,
+}
+
+/// A replacement of a name with the new name, from
+/// [`RefGraph::rename_symbol`].
+pub type RenameEdit = SourceEdit;
+
+/// Why a symbol can't be renamed.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum RenameError {
+    /// The node doesn't exist, or has no name that can be renamed
+    /// (`start_agent`).
+    #[error("this symbol can't be renamed")]
+    NotRenamable,
+
+    /// The new name isn't an identifier, or is a keyword.
+    #[error("'{0}' is not a valid name")]
+    InvalidName(String),
+
+    /// Another symbol in the same scope already has the new name.
+    #[error("'{0}' is already defined")]
+    NameTaken(String),
+
+    /// A qualified symbol that names nothing in the graph.
+    #[error("no symbol '{0}'")]
+    UnknownSymbol(String),
+}
+
+impl RefGraph {
+    /// Edits that rename `node` and every reference to it.
+    ///
+    /// Variables, topics, action definitions and reasoning actions can be
+    /// renamed. Action references are resolved within the topic, so
+    /// renaming `lookup` in one topic leaves another topic's `lookup` alone.
+    /// Edits are in source order.
+    ///
+    /// ```rust
+    /// use busbar_sf_agentscript::graph::RefGraph;
+    ///
+    /// let source = r#"variables:
+    ///    step: mutable number = 0
+    ///    step_count: mutable number = 0
+    ///
+    /// topic main:
+    ///    description: "Main"
+    ///    reasoning:
+    ///       instructions: "Help"
+    ///       actions:
+    ///          next: @utils.setVariables
+    ///             with step=@variables.step_count
+    ///             set @variables.step = @variables.step_count
+    /// "#;
+    /// let graph = RefGraph::from_ast(&busbar_sf_agentscript::parse(source).unwrap()).unwrap();
+    /// let step = graph.get_variable("step").unwrap();
+    /// let edits = graph.rename_symbol(step, "stage").unwrap();
+    ///
+    /// // The definition and `@variables.step`, but not `step_count`
+    /// assert_eq!(edits.len(), 2);
+    /// assert!(edits.iter().all(|e| &source[e.span.clone()] == "step"));
+    /// ```
+    pub fn rename_symbol(
+        &self,
+        node: NodeIndex,
+        new_name: &str,
+    ) -> Result<Vec<RenameEdit>, RenameError> {
+        let taken = match self.get_node(node) {
+            Some(RefNode::Variable { .. }) => self.get_variable(new_name),
+            Some(RefNode::Topic { .. }) => self.get_topic(new_name),
+            Some(RefNode::ActionDef { topic, .. }) => self.get_action_def(topic, new_name),
+            Some(RefNode::ReasoningAction { topic, .. }) => {
+                self.get_reasoning_action(topic, new_name)
+            }
+            _ => return Err(RenameError::NotRenamable),
+        };
+        if !is_identifier(new_name) {
+            return Err(RenameError::InvalidName(new_name.to_string()));
+        }
+        if taken.is_some_and(|idx| idx != node) {
+            return Err(RenameError::NameTaken(new_name.to_string()));
+        }
+
+        Ok(self
+            .occurrences_of(node)
+            .into_iter()
+            .map(|(start, end)| SourceEdit::replace(start..end, new_name))
+            .collect())
+    }
+
+    /// Like [`rename_symbol`](Self::rename_symbol), for the symbol a
+    /// qualified name from [`qualified_name`](Self::qualified_name) names.
+    ///
+    /// When topics define actions of the same name, the qualifier picks the
+    /// one to rename, and only references within that topic are edited.
+    ///
+    /// ```rust
+    /// use busbar_sf_agentscript::graph::RefGraph;
+    ///
+    /// let source = r#"topic billing:
+    ///    description: "Billing"
+    ///    actions:
+    ///       lookup:
+    ///          target: "flow://Lookup"
+    ///    reasoning:
+    ///       instructions: "Bill"
+    ///       actions:
+    ///          go: @actions.lookup
+    ///
+    /// topic orders:
+    ///    description: "Orders"
+    ///    actions:
+    ///       lookup:
+    ///          target: "flow://Lookup"
+    ///    reasoning:
+    ///       instructions: "Order"
+    ///       actions:
+    ///          go: @actions.lookup
+    /// "#;
+    /// let graph = RefGraph::from_ast(&busbar_sf_agentscript::parse(source).unwrap()).unwrap();
+    /// let edits = graph.rename_qualified("orders::lookup", "find").unwrap();
+    ///
+    /// // The definition and the reference in `orders` only
+    /// assert_eq!(edits.len(), 2);
+    /// assert!(edits.iter().all(|e| e.span.start > source.find("topic orders").unwrap()));
+    /// ```
+    pub fn rename_qualified(
+        &self,
+        symbol: &str,
+        new_name: &str,
+    ) -> Result<Vec<RenameEdit>, RenameError> {
+        let node = self
+            .resolve_qualified(symbol)
+            .ok_or_else(|| RenameError::UnknownSymbol(symbol.to_string()))?;
+        self.rename_symbol(node, new_name)
+    }
+
+    /// The name that identifies a renamable node across the whole file:
+    /// `topic::action` for action definitions and reasoning actions,
+    /// `variables::name` for variables, and the topic name for topics.
+    pub fn qualified_name(&self, node: NodeIndex) -> Option<String> {
+        match self.get_node(node)? {
+            RefNode::Topic { name, .. } => Some(name.clone()),
+            RefNode::Variable { name, .. } => Some(format!("variables::{name}")),
+            RefNode::ActionDef { name, topic, .. }
+            | RefNode::ReasoningAction { name, topic, .. } => Some(format!("{topic}::{name}")),
+            _ => None,
+        }
+    }
+
+    /// The node a qualified name from [`qualified_name`](Self::qualified_name)
+    /// names.
+    ///
+    /// `topic::name` is the topic's action definition, or its reasoning
+    /// action when the topic defines no action of that name.
+    pub fn resolve_qualified(&self, symbol: &str) -> Option<NodeIndex> {
+        match symbol.split_once("::") {
+            Some(("variables", name)) => self.get_variable(name),
+            Some((topic, name)) => self
+                .get_action_def(topic, name)
+                .or_else(|| self.get_reasoning_action(topic, name)),
+            None => self.get_topic(symbol),
+        }
+    }
+
+    /// The action definition or reasoning action of the same kind and name
+    /// as `node` in `topic`, such as `orders::lookup` for `billing::lookup`.
+    /// In `node`'s own topic, that is `node`.
+    pub fn counterpart_in(&self, node: NodeIndex, topic: &str) -> Option<NodeIndex> {
+        match self.get_node(node)? {
+            RefNode::ActionDef { name, .. } => self.get_action_def(topic, name),
+            RefNode::ReasoningAction { name, .. } => self.get_reasoning_action(topic, name),
+            _ => None,
+        }
+    }
+
+    /// Whether another topic defines a symbol of the same kind and name as
+    /// `node`, so that its plain name is ambiguous.
+    pub fn is_ambiguous(&self, node: NodeIndex) -> bool {
+        let topic = match self.get_node(node) {
+            Some(RefNode::ActionDef { topic, .. } | RefNode::ReasoningAction { topic, .. }) => {
+                topic
+            }
+            _ => return false,
+        };
+        std::iter::once("start_agent")
+            .chain(self.topic_names())
+            .filter(|other| *other != topic)
+            .any(|other| self.counterpart_in(node, other).is_some())
+    }
+
+    /// Spans of `node`'s name in its definition and in every reference to
+    /// it, in source order.
+    ///
+    /// These are the spans [`rename_symbol`](Self::rename_symbol) edits.
+    /// Unlike edges, they include references in `before_reasoning` and
+    /// `after_reasoning` directives.
+    pub fn occurrences_of(&self, node: NodeIndex) -> Vec<Span> {
+        self.occurrences_with_kind(node)
+            .into_iter()
+            .map(|(span, _)| span)
+            .collect()
+    }
+
+    /// Like [`occurrences_of`](Self::occurrences_of), with whether each
+    /// occurrence is the definition, a read or a write.
+    pub fn occurrences_with_kind(&self, node: NodeIndex) -> Vec<(Span, OccurrenceKind)> {
+        let Some(spans) = self.symbol_spans.get(&node) else {
+            return Vec::new();
+        };
+        let mut occurrences: Vec<(Span, OccurrenceKind)> = spans
+            .definition
+            .iter()
+            .map(|span| (span, OccurrenceKind::Definition))
+            .chain(
+                spans
+                    .references
+                    .iter()
+                    .map(|span| (span, OccurrenceKind::Read)),
+            )
+            .chain(
+                spans
+                    .writes
+                    .iter()
+                    .map(|span| (span, OccurrenceKind::Write)),
+            )
+            .map(|(span, kind)| ((span.start, span.end), kind))
+            .collect();
+        occurrences.sort_unstable_by_key(|&(span, _)| span);
+        occurrences.dedup_by_key(|&mut (span, _)| span);
+        occurrences
+    }
+
+    /// The node whose name is at byte `offset`, in its definition or in a
+    /// reference to it.
+    pub fn symbol_at(&self, offset: usize) -> Option<NodeIndex> {
+        self.symbol_spans.iter().find_map(|(idx, spans)| {
+            spans
+                .definition
+                .iter()
+                .chain(&spans.references)
+                .chain(&spans.writes)
+                .any(|span| span.start <= offset && offset <= span.end)
+                .then_some(*idx)
+        })
+    }
+}
+
+/// Whether `name` can be used as a symbol name.
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !keyword_table().iter().any(|k| k.text == name)
+}
+
+/// Record the name spans of every definition and resolved reference in
+/// `ast`, which `graph` was built from.
+pub(super) fn collect_symbol_spans(
+    graph: &RefGraph,
+    ast: &AgentFile,
+) -> HashMap<NodeIndex, SymbolSpans> {
+    let mut collector = Collector {
+        graph,
+        scope: String::new(),
+        spans: HashMap::new(),
+    };
+    collector.agent(ast);
+    collector.spans
+}
+
+struct Collector<'g> {
+    graph: &'g RefGraph,
+    /// Topic whose actions `@actions` references resolve to.
+    scope: String,
+    spans: HashMap<NodeIndex, SymbolSpans>,
+}
+
+/// The parts of `start_agent` and topic blocks that hold definitions and
+/// references.
+struct Block<'a> {
+    name: Option<&'a Spanned<String>>,
+    actions: Option<&'a Spanned<crate::ast::ActionsBlock>>,
+    directives: [Option<&'a Spanned<DirectiveBlock>>; 2],
+    system: Option<&'a Spanned<Instructions>>,
+    reasoning: Option<&'a Spanned<crate::ast::ReasoningBlock>>,
+}
+
+impl Collector<'_> {
+    fn agent(&mut self, ast: &AgentFile) {
+        for var in ast.variables.iter().flat_map(|v| &v.node.variables) {
+            if let Some(idx) = self.graph.get_variable(&var.node.name.node) {
+                self.define(idx, &var.node.name);
+            }
+            if let Some(default) = &var.node.default {
+                self.expr(default);
+            }
+        }
+
+        if let Some(start) = &ast.start_agent {
+            self.scope = "start_agent".to_string();
+            let start = &start.node;
+            self.block(Block {
+                name: None,
+                actions: start.actions.as_ref(),
+                directives: [
+                    start.before_reasoning.as_ref(),
+                    start.after_reasoning.as_ref(),
+                ],
+                system: start
+                    .system
+                    .as_ref()
+                    .and_then(|s| s.node.instructions.as_ref()),
+                reasoning: start.reasoning.as_ref(),
+            });
+        }
+        for topic in &ast.topics {
+            self.scope = topic.node.name.node.clone();
+            let topic = &topic.node;
+            self.block(Block {
+                name: Some(&topic.name),
+                actions: topic.actions.as_ref(),
+                directives: [
+                    topic.before_reasoning.as_ref(),
+                    topic.after_reasoning.as_ref(),
+                ],
+                system: topic
+                    .system
+                    .as_ref()
+                    .and_then(|s| s.node.instructions.as_ref()),
+                reasoning: topic.reasoning.as_ref(),
+            });
+        }
+    }
+
+    fn block(&mut self, block: Block<'_>) {
+        if let Some(name) = block.name {
+            if let Some(idx) = self.graph.get_topic(&name.node) {
+                self.define(idx, name);
+            }
+        }
+        for action in block.actions.iter().flat_map(|a| &a.node.actions) {
+            if let Some(idx) = self
+                .graph
+                .get_action_def(&self.scope, &action.node.name.node)
+            {
+                self.define(idx, &action.node.name);
+            }
+        }
+        for directives in block.directives.into_iter().flatten() {
+            self.stmts(&directives.node.statements);
+        }
+        if let Some(instructions) = block.system {
+            self.instructions(instructions);
+        }
+        let Some(reasoning) = block.reasoning else {
+            return;
+        };
+        if let Some(instructions) = &reasoning.node.instructions {
+            self.instructions(instructions);
+        }
+        for action in reasoning.node.actions.iter().flat_map(|a| &a.node) {
+            self.reasoning_action(action);
+        }
+    }
+
+    fn define(&mut self, idx: NodeIndex, name: &Spanned<String>) {
+        self.spans.entry(idx).or_default().definition = Some(name.span.clone());
+    }
+
+    /// Record a read of `reference`, whose text starts at byte `start`.
+    fn reference(&mut self, reference: &Reference, start: usize) {
+        if let Some((idx, span)) = self.resolve(reference, start) {
+            self.spans.entry(idx).or_default().references.push(span);
+        }
+    }
+
+    /// Record `reference` as the target of a `set`.
+    fn assignment(&mut self, reference: &Spanned<Reference>) {
+        if let Some((idx, span)) = self.resolve(&reference.node, reference.span.start) {
+            self.spans.entry(idx).or_default().writes.push(span);
+        }
+    }
+
+    /// The node `reference` names and the span of its name, if it resolves.
+    fn resolve(&self, reference: &Reference, start: usize) -> Option<(NodeIndex, Range<usize>)> {
+        let name = reference.path.first()?;
+        let idx = match reference.namespace.as_str() {
+            "variables" => self.graph.get_variable(name),
+            "topic" => self.graph.get_topic(name),
+            "actions" => self.graph.get_action_def(&self.scope, name),
+            _ => None,
+        };
+        // `@namespace.` precedes the name
+        let name_start = start + reference.namespace.len() + 2;
+        Some((idx?, name_start..name_start + name.len()))
+    }
+
+    fn spanned_reference(&mut self, reference: &Spanned<Reference>) {
+        self.reference(&reference.node, reference.span.start);
+    }
+
+    fn expr(&mut self, expr: &Spanned<Expr>) {
+        self.expr_at(&expr.node, Some(expr.span.start));
+    }
+
+    /// Walk `expr`, whose text starts at `start` if known.
+    fn expr_at(&mut self, expr: &Expr, start: Option<usize>) {
+        match expr {
+            Expr::Reference(reference) => {
+                if let Some(start) = start {
+                    self.reference(reference, start);
+                }
+            }
+            Expr::Template(items) | Expr::List(items) => {
+                items.iter().for_each(|item| self.expr(item))
+            }
+            Expr::Object(fields) => fields.values().for_each(|value| self.expr(value)),
+            Expr::BinOp { left, right, .. } => {
+                self.expr(left);
+                self.expr(right);
+            }
+            Expr::UnaryOp { operand, .. } => self.expr(operand),
+            Expr::Ternary {
+                condition,
+                then_expr,
+                else_expr,
+            } => {
+                self.expr(condition);
+                self.expr(then_expr);
+                self.expr(else_expr);
+            }
+            Expr::Property { object, .. } => self.expr(object),
+            Expr::Index { object, index } => {
+                self.expr(object);
+                self.expr(index);
+            }
+            Expr::Call { args, .. } => args.iter().for_each(|arg| self.expr(arg)),
+            Expr::String(_)
+            | Expr::Number(_)
+            | Expr::Date(_)
+            | Expr::Currency(_)
+            | Expr::Bool(_)
+            | Expr::None
+            | Expr::SlotFill => {}
+        }
+    }
+
+    fn with_clauses(&mut self, clauses: &[Spanned<WithClause>]) {
+        for clause in clauses {
+            let WithValue::Expr(expr) = &clause.node.value.node;
+            self.expr_at(expr, Some(clause.node.value.span.start));
+        }
+    }
+
+    fn set_clauses(&mut self, clauses: &[Spanned<SetClause>]) {
+        for clause in clauses {
+            self.assignment(&clause.node.target);
+            self.expr(&clause.node.source);
+        }
+    }
+
+    fn stmts(&mut self, stmts: &[Spanned<Stmt>]) {
+        for stmt in stmts {
+            match &stmt.node {
+                Stmt::Set { target, value } => {
+                    self.assignment(target);
+                    self.expr(value);
+                }
+                Stmt::Run {
+                    action,
+                    with_clauses,
+                    set_clauses,
+                } => {
+                    self.spanned_reference(action);
+                    self.with_clauses(with_clauses);
+                    self.set_clauses(set_clauses);
+                }
+                Stmt::If {
+                    condition,
+                    then_block,
+                    else_block,
+                } => {
+                    self.expr(condition);
+                    self.stmts(then_block);
+                    if let Some(else_block) = else_block {
+                        self.stmts(else_block);
+                    }
+                }
+                Stmt::Transition { target } => self.spanned_reference(target),
+                Stmt::Escalate | Stmt::Respond { .. } => {}
+            }
+        }
+    }
+
+    fn instructions(&mut self, instructions: &Spanned<Instructions>) {
+        if let Instructions::Dynamic(parts) = &instructions.node {
+            self.instruction_parts(parts);
+        }
+    }
+
+    fn instruction_parts(&mut self, parts: &[Spanned<InstructionPart>]) {
+        for part in parts {
+            match &part.node {
+                InstructionPart::Text(_) | InstructionPart::Heading { .. } => {}
+                // The part's span starts at `{!`
+                InstructionPart::Interpolation(expr) => {
+                    self.expr_at(expr, Some(part.span.start + 2))
+                }
+                InstructionPart::Conditional {
+                    condition,
+                    then_parts,
+                    else_parts,
+                } => {
+                    self.expr(condition);
+                    self.instruction_parts(then_parts);
+                    if let Some(else_parts) = else_parts {
+                        self.instruction_parts(else_parts);
+                    }
+                }
+            }
+        }
+    }
+
+    fn reasoning_action(&mut self, action: &Spanned<ReasoningAction>) {
+        let ra = &action.node;
+        if let Some(idx) = self.graph.get_reasoning_action(&self.scope, &ra.name.node) {
+            self.define(idx, &ra.name);
+        }
+        match &ra.target.node {
+            // The reference ends the target: `@utils.transition to @topic.x`
+            ReasoningActionTarget::Action(reference)
+            | ReasoningActionTarget::TransitionTo(reference)
+            | ReasoningActionTarget::TopicDelegate(reference) => {
+                let start = ra.target.span.end - reference.full_path().len();
+                self.reference(reference, start);
+            }
+            ReasoningActionTarget::Escalate | ReasoningActionTarget::SetVariables => {}
+        }
+        if let Some(condition) = &ra.available_when {
+            self.expr(condition);
+        }
+        self.with_clauses(&ra.with_clauses);
+        self.set_clauses(&ra.set_clauses);
+        for run in &ra.run_clauses {
+            self.spanned_reference(&run.node.action);
+            self.with_clauses(&run.node.with_clauses);
+            self.set_clauses(&run.node.set_clauses);
+        }
+        for clause in &ra.if_clauses {
+            self.expr(&clause.node.condition);
+            if let Some(transition) = &clause.node.transition {
+                self.spanned_reference(transition);
+            }
+        }
+        if let Some(transition) = &ra.transition {
+            self.spanned_reference(transition);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = r#"variables:
+   step: mutable number = 0
+   step_count: mutable number = 0
+
+start_agent selector:
+   description: "Route"
+   reasoning:
+      instructions: "Route"
+      actions:
+         go: @utils.transition to @topic.orders
+
+topic orders:
+   description: "Orders"
+   actions:
+      lookup:
+         description: "Look up"
+         inputs:
+            step: number
+         outputs:
+            total: number
+         target: "flow://Lookup"
+   before_reasoning:
+      if @variables.step > 0:
+         run @actions.lookup
+            with step=@variables.step
+            set @variables.step_count = @outputs.total
+   reasoning:
+      instructions:->
+         | Step {!@variables.step} of {!@variables.step_count}
+      actions:
+         do_lookup: @actions.lookup
+            available when @variables.step_count < 3
+            with step=@variables.step
+         again: @topic.orders
+
+topic billing:
+   description: "Billing"
+   actions:
+      lookup:
+         description: "Look up"
+         target: "flow://Lookup"
+   reasoning:
+      instructions: "Bill"
+      actions:
+         do_lookup: @actions.lookup
+"#;
+
+    fn renamed(graph: &RefGraph, node: NodeIndex, new_name: &str) -> String {
+        let edits = graph.rename_symbol(node, new_name).unwrap();
+        crate::edit::apply_edits(SOURCE, edits).unwrap()
+    }
+
+    #[test]
+    fn test_rename_symbol() {
+        let graph = RefGraph::from_ast(&crate::parse(SOURCE).unwrap()).unwrap();
+
+        // Every recorded span covers exactly the symbol's name
+        for (idx, spans) in &graph.symbol_spans {
+            let name = match graph.get_node(*idx).unwrap() {
+                RefNode::Variable { name, .. }
+                | RefNode::Topic { name, .. }
+                | RefNode::ActionDef { name, .. }
+                | RefNode::ReasoningAction { name, .. } => name,
+                node => panic!("unexpected node {node:?}"),
+            };
+            for span in spans
+                .definition
+                .iter()
+                .chain(&spans.references)
+                .chain(&spans.writes)
+            {
+                assert_eq!(&SOURCE[span.clone()], name);
+            }
+        }
+
+        let step_count = graph.get_variable("step_count").unwrap();
+        let kinds: Vec<_> = graph
+            .occurrences_with_kind(step_count)
+            .into_iter()
+            .map(|(_, kind)| kind)
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                OccurrenceKind::Definition,
+                OccurrenceKind::Write,
+                OccurrenceKind::Read,
+                OccurrenceKind::Read,
+            ]
+        );
+
+        let step = graph.get_variable("step").unwrap();
+        let source = renamed(&graph, step, "stage");
+        assert_eq!(source.matches("@variables.stage").count(), 4);
+        assert_eq!(source.matches("@variables.step_count").count(), 3);
+        assert!(source.contains("   stage: mutable number = 0"));
+        // Action parameters of the same name are left alone
+        assert!(source.contains("with step=@variables.stage"));
+        assert!(crate::parse(&source).is_ok());
+
+        let orders = graph.get_topic("orders").unwrap();
+        let source = renamed(&graph, orders, "sales");
+        assert!(source.contains("topic sales:"));
+        assert!(source.contains("@utils.transition to @topic.sales"));
+        assert!(source.contains("again: @topic.sales"));
+
+        let lookup = graph.get_action_def("orders", "lookup").unwrap();
+        let source = renamed(&graph, lookup, "find");
+        assert_eq!(source.matches("@actions.find").count(), 2);
+        assert_eq!(source.matches("@actions.lookup").count(), 1);
+        assert_eq!(source.matches("      lookup:").count(), 1);
+
+        assert_eq!(graph.symbol_at(SOURCE.find("step_count =").unwrap() + 2), {
+            graph.get_variable("step_count")
+        });
+        assert_eq!(graph.symbol_at(SOURCE.find("@actions.lookup").unwrap() + 9), Some(lookup));
+    }
+
+    #[test]
+    fn test_rename_symbol_errors() {
+        let graph = RefGraph::from_ast(&crate::parse(SOURCE).unwrap()).unwrap();
+        let step = graph.get_variable("step").unwrap();
+        assert_eq!(
+            graph.rename_symbol(step, "step_count"),
+            Err(RenameError::NameTaken("step_count".to_string()))
+        );
+        assert_eq!(
+            graph.rename_symbol(step, "topic"),
+            Err(RenameError::InvalidName("topic".to_string()))
+        );
+        assert_eq!(
+            graph.rename_symbol(step, "2fast"),
+            Err(RenameError::InvalidName("2fast".to_string()))
+        );
+        assert_eq!(
+            graph.rename_symbol(graph.get_start_agent().unwrap(), "main"),
+            Err(RenameError::NotRenamable)
+        );
+        // Renaming to the current name is a no-op edit set
+        assert!(graph.rename_symbol(step, "step").is_ok());
+    }
+
+    #[test]
+    fn test_rename_qualified() {
+        let graph = RefGraph::from_ast(&crate::parse(SOURCE).unwrap()).unwrap();
+        let lookup = graph.get_action_def("billing", "lookup").unwrap();
+        assert_eq!(graph.qualified_name(lookup).as_deref(), Some("billing::lookup"));
+        assert_eq!(graph.resolve_qualified("billing::lookup"), Some(lookup));
+        assert_eq!(graph.resolve_qualified("variables::step"), graph.get_variable("step"));
+        assert_eq!(graph.resolve_qualified("orders"), graph.get_topic("orders"));
+        assert_eq!(
+            graph.resolve_qualified("billing::do_lookup"),
+            graph.get_reasoning_action("billing", "do_lookup")
+        );
+
+        assert!(graph.is_ambiguous(lookup));
+        assert!(!graph.is_ambiguous(graph.get_variable("step").unwrap()));
+        assert_eq!(
+            graph.counterpart_in(lookup, "orders"),
+            graph.get_action_def("orders", "lookup")
+        );
+
+        let edits = graph.rename_qualified("billing::lookup", "find").unwrap();
+        let billing = SOURCE.find("topic billing").unwrap();
+        assert_eq!(edits.len(), 2);
+        assert!(edits.iter().all(|e| e.span.start > billing));
+
+        assert_eq!(
+            graph.rename_qualified("support::lookup", "find"),
+            Err(RenameError::UnknownSymbol("support::lookup".to_string()))
+        );
+    }
+}
@@ -0,0 +1,294 @@
+//! Graphviz DOT export for graph visualization.
+//!
+//! The output can be piped straight into Graphviz, e.g. `dot -Tsvg graph.dot > graph.svg`.
+
+use super::super::{RefGraph, RefGraphView, RefNode};
+use petgraph::graph::NodeIndex;
+use petgraph::visit::EdgeRef;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// Layout direction for the DOT graph.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RankDir {
+    /// Top to bottom
+    #[default]
+    TopBottom,
+    /// Left to right
+    LeftRight,
+}
+
+impl RankDir {
+    fn as_str(&self) -> &'static str {
+        match self {
+            RankDir::TopBottom => "TB",
+            RankDir::LeftRight => "LR",
+        }
+    }
+}
+
+/// Options controlling DOT output.
+#[derive(Debug, Clone)]
+pub struct DotOptions {
+    /// Name of the generated digraph
+    pub graph_name: String,
+    /// Layout direction
+    pub rankdir: RankDir,
+    /// Group each topic with its actions in a `cluster_*` subgraph
+    pub cluster_topics: bool,
+    /// Include variable nodes and their read/write edges
+    pub include_variables: bool,
+    /// Label edges with their `RefEdge` type
+    pub edge_labels: bool,
+}
+
+impl Default for DotOptions {
+    fn default() -> Self {
+        Self {
+            graph_name: "agentscript".to_string(),
+            rankdir: RankDir::default(),
+            cluster_topics: true,
+            include_variables: true,
+            edge_labels: true,
+        }
+    }
+}
+
+/// Render a RefGraph as Graphviz DOT.
+///
+/// Nodes are styled by their `RefNode` kind, edges are labelled with the
+/// `RefEdge` type, and (optionally) each topic is drawn as a cluster
+/// containing its action definitions and reasoning actions.
+///
+/// Accepts a `&RefGraph` or a filtered [`RefGraphView`].
+pub fn render_dot<'a>(graph: impl Into<RefGraphView<'a>>, options: DotOptions) -> String {
+    let view = graph.into();
+    let graph = view.graph();
+    let options = &options;
+    let mut output = String::new();
+
+    writeln!(output, "digraph {} {{", quote(&options.graph_name)).unwrap();
+    writeln!(output, "  rankdir={};", options.rankdir.as_str()).unwrap();
+    writeln!(output, r#"  node [fontname="Helvetica", fontsize=10];"#).unwrap();
+    writeln!(output, r#"  edge [fontname="Helvetica", fontsize=9];"#).unwrap();
+
+    // Group nodes by owning topic so clusters are emitted in a stable order
+    let mut clusters: BTreeMap<&str, Vec<NodeIndex>> = BTreeMap::new();
+    let mut loose: Vec<NodeIndex> = Vec::new();
+
+    for idx in view.node_indices() {
+        let Some(node) = view.get_node(idx) else {
+            continue;
+        };
+        if !is_included(node, options) {
+            continue;
+        }
+        match cluster_key(node) {
+            Some(topic) if options.cluster_topics => clusters.entry(topic).or_default().push(idx),
+            _ => loose.push(idx),
+        }
+    }
+
+    for idx in &loose {
+        write_node(&mut output, graph, *idx, "  ");
+    }
+
+    for (topic, nodes) in &clusters {
+        writeln!(output, "  subgraph {} {{", quote(&format!("cluster_{}", topic))).unwrap();
+        writeln!(output, "    label={};", quote(topic)).unwrap();
+        writeln!(output, r#"    style="rounded,dashed";"#).unwrap();
+        writeln!(output, r##"    color="#999999";"##).unwrap();
+        for idx in nodes {
+            write_node(&mut output, graph, *idx, "    ");
+        }
+        writeln!(output, "  }}").unwrap();
+    }
+
+    for edge in view.edge_references() {
+        let (Some(source), Some(target)) =
+            (graph.get_node(edge.source()), graph.get_node(edge.target()))
+        else {
+            continue;
+        };
+        if !is_included(source, options) || !is_included(target, options) {
+            continue;
+        }
+
+        let weight = edge.weight();
+        let mut attrs = Vec::new();
+        if options.edge_labels {
+            attrs.push(format!("label={}", quote(weight.label())));
+        }
+        if weight.is_data_flow() {
+            attrs.push("style=dashed".to_string());
+            attrs.push(r##"color="#888888""##.to_string());
+        }
+
+        write!(output, "  n{} -> n{}", edge.source().index(), edge.target().index()).unwrap();
+        if !attrs.is_empty() {
+            write!(output, " [{}]", attrs.join(", ")).unwrap();
+        }
+        writeln!(output, ";").unwrap();
+    }
+
+    writeln!(output, "}}").unwrap();
+    output
+}
+
+/// Write a single node statement.
+fn write_node(output: &mut String, graph: &RefGraph, idx: NodeIndex, indent: &str) {
+    let Some(node) = graph.get_node(idx) else {
+        return;
+    };
+    let (shape, fill) = node_style(node);
+    writeln!(
+        output,
+        r#"{}n{} [label={}, shape={}, style="filled", fillcolor="{}"];"#,
+        indent,
+        idx.index(),
+        quote(&display_label(node)),
+        shape,
+        fill
+    )
+    .unwrap();
+}
+
+/// Topic that owns a node, used for clustering.
+fn cluster_key(node: &RefNode) -> Option<&str> {
+    match node {
+        RefNode::Topic { name, .. } => Some(name.as_str()),
+        RefNode::ActionDef { topic, .. } | RefNode::ReasoningAction { topic, .. } => {
+            Some(topic.as_str())
+        }
+        _ => None,
+    }
+}
+
+fn is_included(node: &RefNode, options: &DotOptions) -> bool {
+    options.include_variables || !matches!(node, RefNode::Variable { .. })
+}
+
+/// Short label shown inside the node.
+fn display_label(node: &RefNode) -> String {
+    match node {
+        RefNode::StartAgent { .. } => "start_agent".to_string(),
+        RefNode::Topic { name, .. } => name.clone(),
+        RefNode::ActionDef { name, .. } => name.clone(),
+        RefNode::ReasoningAction { name, target, .. } => match target {
+            Some(t) => format!("{}\n→ {}", name, t),
+            None => name.clone(),
+        },
+        RefNode::Variable { name, mutable, .. } => {
+            if *mutable {
+                format!("@variables.{}", name)
+            } else {
+                format!("@variables.{} (linked)", name)
+            }
+        }
+        RefNode::Connection { name, .. } => format!("connection:{}", name),
+        RefNode::KnowledgeBase { name, .. } => format!("knowledge:{}", name),
+    }
+}
+
+/// Shape and fill colour for each node kind.
+pub(super) fn node_style(node: &RefNode) -> (&'static str, &'static str) {
+    match node {
+        RefNode::StartAgent { .. } => ("doublecircle", "#ffd966"),
+        RefNode::Topic { .. } => ("box", "#9fc5e8"),
+        RefNode::ActionDef { .. } => ("component", "#b6d7a8"),
+        RefNode::ReasoningAction { .. } => ("ellipse", "#d9d2e9"),
+        RefNode::Variable { .. } => ("note", "#fff2cc"),
+        RefNode::Connection { .. } => ("hexagon", "#f4cccc"),
+        RefNode::KnowledgeBase { .. } => ("cylinder", "#d0e0e3"),
+    }
+}
+
+/// Quote and escape a DOT identifier or string.
+fn quote(s: &str) -> String {
+    let escaped = s
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n");
+    format!("\"{}\"", escaped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_and_build(source: &str) -> RefGraph {
+        let ast = crate::parse(source).expect("Failed to parse");
+        RefGraph::from_ast(&ast).expect("Failed to build graph")
+    }
+
+    const SOURCE: &str = r#"config:
+   agent_name: "Test"
+
+variables:
+   order_id: mutable string = ""
+
+start_agent selector:
+   description: "Route"
+   reasoning:
+      instructions: "Select"
+      actions:
+         go_main: @utils.transition to @topic.main
+            description: "Go to main"
+
+topic main:
+   description: "Main topic"
+
+   actions:
+      lookup:
+         description: "Look up an order"
+         inputs:
+            id: string
+               description: "Order ID"
+         target: "flow://Lookup"
+
+   reasoning:
+      instructions: "Help"
+      actions:
+         do_lookup: @actions.lookup
+            description: "Lookup"
+            with id=@variables.order_id
+"#;
+
+    #[test]
+    fn test_quote_escapes() {
+        assert_eq!(quote("plain"), "\"plain\"");
+        assert_eq!(quote(r#"a "b""#), r#""a \"b\"""#);
+        assert_eq!(quote("a\nb"), "\"a\\nb\"");
+    }
+
+    #[test]
+    fn test_render_dot_clusters_and_labels() {
+        let graph = parse_and_build(SOURCE);
+        let dot = render_dot(&graph, DotOptions::default());
+
+        assert!(dot.starts_with("digraph \"agentscript\" {"));
+        assert!(dot.trim_end().ends_with('}'));
+        assert!(dot.contains("subgraph \"cluster_main\""));
+        assert!(dot.contains("label=\"invokes\""));
+        assert!(dot.contains("label=\"reads\""));
+        assert!(dot.contains("shape=doublecircle"));
+    }
+
+    #[test]
+    fn test_render_dot_without_variables_or_clusters() {
+        let graph = parse_and_build(SOURCE);
+        let options = DotOptions {
+            cluster_topics: false,
+            include_variables: false,
+            edge_labels: false,
+            rankdir: RankDir::LeftRight,
+            ..Default::default()
+        };
+        let dot = render_dot(&graph, options);
+
+        assert!(dot.contains("rankdir=LR;"));
+        assert!(!dot.contains("subgraph"));
+        assert!(!dot.contains("@variables.order_id"));
+        assert!(!dot.contains("label=\"reads\""));
+    }
+}
@@ -3,12 +3,12 @@
 //! GraphML is an XML-based format for graph exchange that is widely supported
 //! by graph visualization tools like yEd, Gephi, Cytoscape, etc.
 
-use super::super::{RefGraph, RefNode};
+use super::super::{NodeKind, RefGraphView, RefNode};
 use petgraph::visit::EdgeRef;
 use std::fmt::Write;
 
 type NodeAttrs<'a> = (
-    &'static str,
+    NodeKind,
     Option<&'a str>,
     Option<&'a str>,
     Option<&'a str>,
@@ -22,8 +22,10 @@ type NodeAttrs<'a> = (
 /// - Node attributes: node_type, name, topic, target, mutable, span
 /// - Edge attributes: edge_type
 /// - yEd-compatible metadata keys
-pub fn render_graphml(graph: &RefGraph) -> String {
-    let inner = graph.inner();
+///
+/// Accepts a `&RefGraph` or a filtered [`RefGraphView`].
+pub fn render_graphml<'a>(graph: impl Into<RefGraphView<'a>>) -> String {
+    let view = graph.into();
     let mut output = String::new();
 
     // XML header and GraphML schema
@@ -85,8 +87,8 @@ pub fn render_graphml(graph: &RefGraph) -> String {
     writeln!(output, r#"  <graph id="G" edgedefault="directed">"#).unwrap();
 
     // Output nodes
-    for idx in inner.node_indices() {
-        if let Some(node) = graph.get_node(idx) {
+    for idx in view.node_indices() {
+        if let Some(node) = view.get_node(idx) {
             let id = idx.index();
             let (node_type, name, topic, target, mutable, span) = extract_node_attrs(node);
 
@@ -117,7 +119,8 @@ pub fn render_graphml(graph: &RefGraph) -> String {
     }
 
     // Output edges
-    for (edge_id, edge) in inner.edge_references().enumerate() {
+    for edge in view.edge_references() {
+        let edge_id = edge.id().index();
         let source = edge.source().index();
         let target = edge.target().index();
         let edge_type = edge.weight().label();
@@ -142,18 +145,25 @@ pub fn render_graphml(graph: &RefGraph) -> String {
 /// Extract node attributes for GraphML output.
 fn extract_node_attrs(node: &RefNode) -> NodeAttrs<'_> {
     match node {
-        RefNode::StartAgent { span } => ("start_agent", None, None, None, None, *span),
-        RefNode::Topic { name, span } => ("topic", Some(name.as_str()), None, None, None, *span),
-        RefNode::ActionDef { name, topic, span } => {
-            ("action_def", Some(name.as_str()), Some(topic.as_str()), None, None, *span)
+        RefNode::StartAgent { span } => (NodeKind::StartAgent, None, None, None, None, *span),
+        RefNode::Topic { name, span } => {
+            (NodeKind::Topic, Some(name.as_str()), None, None, None, *span)
         }
+        RefNode::ActionDef { name, topic, span } => (
+            NodeKind::ActionDef,
+            Some(name.as_str()),
+            Some(topic.as_str()),
+            None,
+            None,
+            *span,
+        ),
         RefNode::ReasoningAction {
             name,
             topic,
             target,
             span,
         } => (
-            "reasoning_action",
+            NodeKind::ReasoningAction,
             Some(name.as_str()),
             Some(topic.as_str()),
             target.as_deref(),
@@ -164,15 +174,23 @@ fn extract_node_attrs(node: &RefNode) -> NodeAttrs<'_> {
             name,
             mutable,
             span,
-        } => ("variable", Some(name.as_str()), None, None, Some(*mutable), *span),
+        } => (NodeKind::Variable, Some(name.as_str()), None, None, Some(*mutable), *span),
         RefNode::Connection { name, span } => {
-            ("connection", Some(name.as_str()), None, None, None, *span)
+            (NodeKind::External, Some(name.as_str()), None, None, None, *span)
         }
+        RefNode::KnowledgeBase { name, source, span } => (
+            NodeKind::External,
+            Some(name.as_str()),
+            None,
+            Some(source.as_str()),
+            None,
+            *span,
+        ),
     }
 }
 
 /// Escape special XML characters.
-fn escape_xml(s: &str) -> String {
+pub(super) fn escape_xml(s: &str) -> String {
     s.replace('&', "&amp;")
         .replace('<', "&lt;")
         .replace('>', "&gt;")
@@ -0,0 +1,110 @@
+//! SVG rendering through a local Graphviz install.
+//!
+//! [`RefGraph::to_svg`] pipes the [`render_dot`] output through `dot -Tsvg`,
+//! and [`RefGraph::evcxr_display`] uses it to show the graph inline in evcxr
+//! notebooks, falling back to the ASCII topic flow when Graphviz is missing.
+
+use super::super::RefGraph;
+use super::{render_dot, render_topic_flow, DotOptions};
+use std::io::Write;
+use std::process::{Command, Stdio};
+use thiserror::Error;
+
+/// Errors from [`RefGraph::to_svg`].
+#[derive(Debug, Error)]
+pub enum GraphvizError {
+    /// The `dot` executable could not be started or talked to
+    #[error("Could not run Graphviz `dot`: {0}")]
+    Unavailable(#[from] std::io::Error),
+
+    /// `dot` ran but rejected the input
+    #[error("Graphviz `dot` failed: {0}")]
+    Failed(String),
+}
+
+impl RefGraph {
+    /// Render the graph as SVG with Graphviz `dot`, using the default
+    /// [`DotOptions`].
+    ///
+    /// Requires `dot` on the `PATH`; returns [`GraphvizError::Unavailable`]
+    /// otherwise.
+    pub fn to_svg(&self) -> Result<String, GraphvizError> {
+        run_dot("dot", &render_dot(self, DotOptions::default()))
+    }
+
+    /// Rich display hook for [evcxr](https://github.com/evcxr/evcxr)
+    /// notebooks: the SVG from [`RefGraph::to_svg`], or the ASCII topic flow
+    /// when Graphviz is not installed.
+    pub fn evcxr_display(&self) {
+        match self.to_svg() {
+            Ok(svg) => println!("EVCXR_BEGIN_CONTENT image/svg+xml\n{}\nEVCXR_END_CONTENT", svg),
+            Err(_) => println!(
+                "EVCXR_BEGIN_CONTENT text/plain\n{}\nEVCXR_END_CONTENT",
+                render_topic_flow(self).trim_end()
+            ),
+        }
+    }
+}
+
+/// Run `program -Tsvg` on `dot` and return its standard output.
+fn run_dot(program: &str, dot: &str) -> Result<String, GraphvizError> {
+    let mut child = Command::new(program)
+        .arg("-Tsvg")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    // Write from another thread so a large graph cannot fill the output
+    // pipe while we are still writing the input
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let input = dot.to_string();
+    let writer = std::thread::spawn(move || stdin.write_all(input.as_bytes()));
+    let output = child.wait_with_output()?;
+    writer.join().expect("writer thread panicked")?;
+
+    if !output.status.success() {
+        return Err(GraphvizError::Failed(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_svg() {
+        let source = r#"config:
+   agent_name: "Test"
+
+start_agent selector:
+   description: "Route"
+   reasoning:
+      instructions: "Select"
+      actions:
+         go_main: @utils.transition to @topic.main
+
+topic main:
+   description: "Main topic"
+   reasoning:
+      instructions: "Help"
+"#;
+        let ast = crate::parse(source).unwrap();
+        let graph = RefGraph::from_ast(&ast).unwrap();
+
+        // Graphviz is optional, so only check the output when it is installed
+        match graph.to_svg() {
+            Ok(svg) => {
+                assert!(svg.contains("<svg"));
+                assert!(svg.contains("main"));
+            }
+            Err(e) => assert!(matches!(e, GraphvizError::Unavailable(_)), "{}", e),
+        }
+
+        let missing = run_dot("busbar-no-such-graphviz", "digraph {}");
+        assert!(matches!(missing, Err(GraphvizError::Unavailable(_))));
+    }
+}
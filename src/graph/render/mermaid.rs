@@ -0,0 +1,184 @@
+//! Mermaid flowchart export for graph visualization.
+//!
+//! The output can be embedded directly in Markdown (GitHub, GitLab, mdBook)
+//! inside a ```` ```mermaid ```` fence.
+
+use super::super::{RefGraph, RefGraphView, RefNode};
+use petgraph::graph::NodeIndex;
+use petgraph::visit::EdgeRef;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// Render a RefGraph as a Mermaid flowchart.
+///
+/// Each topic is drawn as a subgraph containing its action definitions and
+/// reasoning actions. Control-flow edges are solid, data-flow edges
+/// (variable reads/writes) are dotted, and every edge is labelled with its
+/// `RefEdge` type.
+///
+/// Accepts a `&RefGraph` or a filtered [`RefGraphView`].
+pub fn render_mermaid<'a>(graph: impl Into<RefGraphView<'a>>) -> String {
+    let view = graph.into();
+    let graph = view.graph();
+    let mut output = String::new();
+
+    writeln!(output, "flowchart TD").unwrap();
+
+    // Group nodes by owning topic so subgraphs are emitted in a stable order
+    let mut subgraphs: BTreeMap<&str, Vec<NodeIndex>> = BTreeMap::new();
+    let mut loose: Vec<NodeIndex> = Vec::new();
+
+    for idx in view.node_indices() {
+        let Some(node) = view.get_node(idx) else {
+            continue;
+        };
+        match owning_topic(node) {
+            Some(topic) => subgraphs.entry(topic).or_default().push(idx),
+            None => loose.push(idx),
+        }
+    }
+
+    for idx in &loose {
+        write_node(&mut output, graph, *idx, "    ");
+    }
+
+    for (i, (topic, nodes)) in subgraphs.iter().enumerate() {
+        writeln!(output, "    subgraph topic_{}[\"{}\"]", i, escape(topic)).unwrap();
+        for idx in nodes {
+            write_node(&mut output, graph, *idx, "        ");
+        }
+        writeln!(output, "    end").unwrap();
+    }
+
+    for edge in view.edge_references() {
+        let weight = edge.weight();
+        let arrow = if weight.is_data_flow() { "-.->" } else { "-->" };
+        writeln!(
+            output,
+            "    n{} {}|{}| n{}",
+            edge.source().index(),
+            arrow,
+            weight.label(),
+            edge.target().index()
+        )
+        .unwrap();
+    }
+
+    output
+}
+
+/// Write a single node declaration.
+fn write_node(output: &mut String, graph: &RefGraph, idx: NodeIndex, indent: &str) {
+    let Some(node) = graph.get_node(idx) else {
+        return;
+    };
+    let label = escape(&display_label(node));
+    let (open, close) = node_shape(node);
+    writeln!(output, "{}n{}{}\"{}\"{}", indent, idx.index(), open, label, close).unwrap();
+}
+
+/// Topic that owns a node, used for subgraphs.
+fn owning_topic(node: &RefNode) -> Option<&str> {
+    match node {
+        RefNode::Topic { name, .. } => Some(name.as_str()),
+        RefNode::ActionDef { topic, .. } | RefNode::ReasoningAction { topic, .. } => {
+            Some(topic.as_str())
+        }
+        _ => None,
+    }
+}
+
+/// Short label shown inside the node.
+fn display_label(node: &RefNode) -> String {
+    match node {
+        RefNode::StartAgent { .. } => "start_agent".to_string(),
+        RefNode::Topic { name, .. } => name.clone(),
+        RefNode::ActionDef { name, .. } => name.clone(),
+        RefNode::ReasoningAction { name, target, .. } => match target {
+            Some(t) => format!("{}<br/>→ {}", name, t),
+            None => name.clone(),
+        },
+        RefNode::Variable { name, mutable, .. } => {
+            if *mutable {
+                format!("@variables.{}", name)
+            } else {
+                format!("@variables.{} (linked)", name)
+            }
+        }
+        RefNode::Connection { name, .. } => format!("connection:{}", name),
+        RefNode::KnowledgeBase { name, .. } => format!("knowledge:{}", name),
+    }
+}
+
+/// Opening and closing shape delimiters for each node kind.
+fn node_shape(node: &RefNode) -> (&'static str, &'static str) {
+    match node {
+        RefNode::StartAgent { .. } => ("((", "))"),
+        RefNode::Topic { .. } => ("[", "]"),
+        RefNode::ActionDef { .. } => ("[[", "]]"),
+        RefNode::ReasoningAction { .. } => ("(", ")"),
+        RefNode::Variable { .. } => ("[/", "/]"),
+        RefNode::Connection { .. } => ("{{", "}}"),
+        RefNode::KnowledgeBase { .. } => ("[(", ")]"),
+    }
+}
+
+/// Escape text for use inside a quoted Mermaid label.
+fn escape(s: &str) -> String {
+    s.replace('"', "#quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_mermaid() {
+        let source = r#"config:
+   agent_name: "Test"
+
+variables:
+   order_id: mutable string = ""
+
+start_agent selector:
+   description: "Route"
+   reasoning:
+      instructions: "Select"
+      actions:
+         go_main: @utils.transition to @topic.main
+            description: "Go to main"
+
+topic main:
+   description: "Main topic"
+
+   actions:
+      lookup:
+         description: "Look up an order"
+         inputs:
+            id: string
+               description: "Order ID"
+         target: "flow://Lookup"
+
+   reasoning:
+      instructions: "Help"
+      actions:
+         do_lookup: @actions.lookup
+            description: "Lookup"
+            with id=@variables.order_id
+"#;
+        let ast = crate::parse(source).expect("Failed to parse");
+        let graph = RefGraph::from_ast(&ast).expect("Failed to build graph");
+        let mermaid = render_mermaid(&graph);
+
+        assert!(mermaid.starts_with("flowchart TD\n"));
+        assert!(mermaid.contains("subgraph topic_0[\"main\"]"));
+        assert!(mermaid.contains("((\"start_agent\"))"));
+        assert!(mermaid.contains("-->|invokes|"));
+        assert!(mermaid.contains("-.->|reads|"));
+    }
+
+    #[test]
+    fn test_escape_quotes() {
+        assert_eq!(escape(r#"say "hi""#), "say #quot;hi#quot;");
+    }
+}
@@ -3,9 +3,22 @@
 //! This module provides various output formats for visualizing RefGraph structures:
 //! - ASCII tree rendering for terminal display
 //! - GraphML export for external visualization tools
+//! - Graphviz DOT export for `dot -Tsvg` and friends
+//! - SVG via a local Graphviz install, for notebooks and reports
+//! - Standalone SVG with a built-in layered layout, for WASM and other
+//!   environments without Graphviz
+//! - Mermaid flowcharts for embedding in Markdown
 
 mod ascii;
+mod dot;
 mod graphml;
+mod graphviz;
+mod mermaid;
+mod svg;
 
 pub use ascii::{render_actions_view, render_ascii_tree, render_full_view, render_topic_flow};
+pub use dot::{render_dot, DotOptions, RankDir};
 pub use graphml::render_graphml;
+pub use graphviz::GraphvizError;
+pub use mermaid::render_mermaid;
+pub use svg::{render_svg, render_topic_flow_svg, SvgOptions};
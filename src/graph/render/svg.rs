@@ -0,0 +1,313 @@
+//! Standalone SVG rendering.
+//!
+//! [`render_svg`] draws a graph or view as an SVG document without any
+//! external tools, so it also works in WASM where Graphviz is unavailable.
+//! Nodes are placed by [`RefGraphView::layered_layout`] and drawn as labelled
+//! boxes with straight arrows between them; arrows pointing back up a layer
+//! are dashed. [`render_topic_flow_svg`] draws the topic flow only.
+//!
+//! Nodes can be colored by a score, such as simulation coverage or a
+//! [metric](super::super::metrics), through [`SvgOptions::heat`].
+
+use super::super::metrics::{GraphMetrics, TopicMetrics};
+use super::super::{LayoutOptions, RefEdge, RefGraph, RefGraphView, RefNode};
+use super::dot::node_style;
+use super::graphml::escape_xml;
+use petgraph::graph::NodeIndex;
+use petgraph::visit::EdgeRef;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+/// Margin around the drawing.
+const MARGIN: f64 = 20.0;
+
+/// Approximate width of a label character at the default font size.
+const CHAR_WIDTH: f64 = 7.0;
+
+/// Options controlling SVG output.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SvgOptions {
+    /// Width of each node box
+    pub node_width: f64,
+    /// Height of each node box
+    pub node_height: f64,
+    /// Spacing and ordering of the layered layout
+    pub layout: LayoutOptions,
+    /// Scores in `0.0..=1.0` by node. Scored nodes are filled on a scale
+    /// from red (`0.0`) to green (`1.0`) instead of their kind's color.
+    pub heat: HashMap<NodeIndex, f64>,
+}
+
+impl Default for SvgOptions {
+    fn default() -> Self {
+        Self {
+            node_width: 140.0,
+            node_height: 36.0,
+            layout: LayoutOptions::default(),
+            heat: HashMap::new(),
+        }
+    }
+}
+
+impl SvgOptions {
+    /// Color topics by a metric, relative to the largest value: the topic
+    /// with the highest value is red and a value of zero is green.
+    ///
+    /// ```rust
+    /// use busbar_sf_agentscript::graph::{render::{render_topic_flow_svg, SvgOptions}, RefGraph};
+    ///
+    /// let source = r#"
+    /// start_agent main:
+    ///    description: "Route"
+    ///    reasoning:
+    ///       instructions: "Route"
+    ///       actions:
+    ///          go: @utils.transition to @topic.orders
+    ///
+    /// topic orders:
+    ///    description: "Orders"
+    ///    reasoning:
+    ///       instructions: "Help"
+    /// "#;
+    /// let graph = RefGraph::from_ast(&busbar_sf_agentscript::parse(source).unwrap()).unwrap();
+    /// let options = SvgOptions::default().heat_from_metrics(&graph.metrics(), |m| m.fan_in as f64);
+    /// let svg = render_topic_flow_svg(&graph, &options);
+    /// assert!(svg.starts_with("<svg"));
+    /// ```
+    pub fn heat_from_metrics(
+        mut self,
+        metrics: &GraphMetrics,
+        metric: impl Fn(&TopicMetrics) -> f64,
+    ) -> Self {
+        let values: Vec<(NodeIndex, f64)> =
+            metrics.topics.iter().map(|m| (m.node, metric(m))).collect();
+        let max = values.iter().map(|(_, v)| *v).fold(0.0, f64::max);
+        for (node, value) in values {
+            let score = if max > 0.0 { 1.0 - value / max } else { 1.0 };
+            self.heat.insert(node, score);
+        }
+        self
+    }
+}
+
+/// Render the topic flow, `start_agent` and topics with the routes,
+/// transitions and delegations between them, as SVG.
+pub fn render_topic_flow_svg(graph: &RefGraph, options: &SvgOptions) -> String {
+    let view = graph
+        .filter(|node| matches!(node, RefNode::StartAgent { .. } | RefNode::Topic { .. }))
+        .filter_edges(|edge| {
+            matches!(edge, RefEdge::Routes | RefEdge::TransitionsTo | RefEdge::Delegates)
+        });
+    render_svg(&view, options)
+}
+
+/// Render a graph or view as a standalone SVG document.
+///
+/// Self-loops are not drawn, and parallel edges are drawn once.
+pub fn render_svg<'a>(graph: impl Into<RefGraphView<'a>>, options: &SvgOptions) -> String {
+    let view = graph.into();
+    let positions = view.layered_layout(&options.layout);
+    let (half_width, half_height) = (options.node_width / 2.0, options.node_height / 2.0);
+    let center = |idx: NodeIndex| {
+        let position = positions[&idx];
+        (position.x + MARGIN + half_width, position.y + MARGIN + half_height)
+    };
+
+    let width = positions.values().map(|p| p.x).fold(0.0, f64::max) + options.node_width;
+    let height = positions.values().map(|p| p.y).fold(0.0, f64::max) + options.node_height;
+    let (width, height) = if positions.is_empty() {
+        (2.0 * MARGIN, 2.0 * MARGIN)
+    } else {
+        (width + 2.0 * MARGIN, height + 2.0 * MARGIN)
+    };
+
+    let mut output = String::new();
+    writeln!(
+        output,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w:.0}" height="{h:.0}" viewBox="0 0 {w:.0} {h:.0}" font-family="Helvetica, Arial, sans-serif" font-size="12">"#,
+        w = width,
+        h = height
+    )
+    .unwrap();
+    writeln!(
+        output,
+        r##"  <defs><marker id="arrow" viewBox="0 0 10 10" refX="10" refY="5" markerWidth="8" markerHeight="8" orient="auto-start-reverse"><path d="M0,0 L10,5 L0,10 z" fill="#555"/></marker></defs>"##
+    )
+    .unwrap();
+
+    let mut drawn = HashSet::new();
+    for edge in view.edge_references() {
+        let (source, target) = (edge.source(), edge.target());
+        if source == target || !drawn.insert((source, target)) {
+            continue;
+        }
+        let (sx, sy) = center(source);
+        let (tx, ty) = center(target);
+        let (x1, y1) = clip_to_box((sx, sy), (tx - sx, ty - sy), half_width, half_height);
+        let (x2, y2) = clip_to_box((tx, ty), (sx - tx, sy - ty), half_width, half_height);
+        let backward = positions[&target].layer <= positions[&source].layer;
+        writeln!(
+            output,
+            r##"  <line class="edge {}" x1="{:.1}" y1="{:.1}" x2="{:.1}" y2="{:.1}" stroke="#555"{} marker-end="url(#arrow)"/>"##,
+            edge.weight().kind(),
+            x1,
+            y1,
+            x2,
+            y2,
+            if backward {
+                r#" stroke-dasharray="4 3""#
+            } else {
+                ""
+            }
+        )
+        .unwrap();
+    }
+
+    let max_chars = ((options.node_width - 8.0) / CHAR_WIDTH).max(1.0) as usize;
+    for idx in view.node_indices() {
+        let node = &view.graph().inner()[idx];
+        let (cx, cy) = center(idx);
+        let fill = match options.heat.get(&idx) {
+            Some(score) => heat_color(*score),
+            None => node_style(node).1.to_string(),
+        };
+        let label = node.name().unwrap_or("start_agent");
+        writeln!(
+            output,
+            r##"  <g class="node {}"><title>{}</title><rect x="{:.1}" y="{:.1}" width="{:.1}" height="{:.1}" rx="6" fill="{}" stroke="#333"/><text x="{:.1}" y="{:.1}" text-anchor="middle" dominant-baseline="central">{}</text></g>"##,
+            node.kind(),
+            escape_xml(&node.label()),
+            cx - half_width,
+            cy - half_height,
+            options.node_width,
+            options.node_height,
+            fill,
+            cx,
+            cy,
+            escape_xml(&truncate(label, max_chars))
+        )
+        .unwrap();
+    }
+
+    writeln!(output, "</svg>").unwrap();
+    output
+}
+
+/// The point where a ray from the center of a box leaves it.
+fn clip_to_box(
+    (x, y): (f64, f64),
+    (dx, dy): (f64, f64),
+    half_width: f64,
+    half_height: f64,
+) -> (f64, f64) {
+    let tx = if dx == 0.0 {
+        f64::INFINITY
+    } else {
+        half_width / dx.abs()
+    };
+    let ty = if dy == 0.0 {
+        f64::INFINITY
+    } else {
+        half_height / dy.abs()
+    };
+    let t = tx.min(ty);
+    if t.is_finite() {
+        (x + dx * t, y + dy * t)
+    } else {
+        (x, y)
+    }
+}
+
+/// Fill color for a score, from red at `0.0` through yellow to green at `1.0`.
+fn heat_color(score: f64) -> String {
+    const RED: (f64, f64, f64) = (234.0, 153.0, 153.0);
+    const YELLOW: (f64, f64, f64) = (255.0, 229.0, 153.0);
+    const GREEN: (f64, f64, f64) = (182.0, 215.0, 168.0);
+
+    let score = if score.is_nan() {
+        0.0
+    } else {
+        score.clamp(0.0, 1.0)
+    };
+    let (from, to, t) = if score < 0.5 {
+        (RED, YELLOW, score * 2.0)
+    } else {
+        (YELLOW, GREEN, score * 2.0 - 1.0)
+    };
+    let mix = |a: f64, b: f64| (a + (b - a) * t).round() as u8;
+    format!("#{:02x}{:02x}{:02x}", mix(from.0, to.0), mix(from.1, to.1), mix(from.2, to.2))
+}
+
+/// Shorten a label to `max` characters, marking the cut with an ellipsis.
+fn truncate(label: &str, max: usize) -> String {
+    if label.chars().count() <= max {
+        return label.to_string();
+    }
+    let mut short: String = label.chars().take(max.saturating_sub(1)).collect();
+    short.push('…');
+    short
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = r#"start_agent selector:
+   description: "Route"
+   reasoning:
+      instructions: "Select"
+      actions:
+         go_orders: @utils.transition to @topic.orders
+         go_billing: @utils.transition to @topic.billing
+
+topic orders:
+   description: "Orders"
+   reasoning:
+      instructions: "Help"
+      actions:
+         back: @utils.transition to @topic.billing
+
+topic billing:
+   description: "Billing"
+   reasoning:
+      instructions: "Help"
+      actions:
+         back: @utils.transition to @topic.orders
+"#;
+
+    #[test]
+    fn test_render_topic_flow_svg() {
+        let graph = RefGraph::from_ast(&crate::parse(SOURCE).unwrap()).unwrap();
+        let svg = render_topic_flow_svg(&graph, &SvgOptions::default());
+
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+        assert!(svg.trim_end().ends_with("</svg>"));
+        assert_eq!(svg.matches("<g class=\"node").count(), 3);
+        assert!(svg.contains(">orders</text>"));
+        assert!(svg.contains("<title>topic:billing</title>"));
+        // Two routes, plus the transitions both ways between the topics
+        assert_eq!(svg.matches("<line class=\"edge").count(), 4);
+        assert_eq!(svg.matches("stroke-dasharray").count(), 1);
+        assert!(!svg.contains("go_orders"));
+    }
+
+    #[test]
+    fn test_heat_coloring() {
+        let graph = RefGraph::from_ast(&crate::parse(SOURCE).unwrap()).unwrap();
+        let orders = graph.get_topic("orders").unwrap();
+        let mut options = SvgOptions::default();
+        options.heat.insert(orders, 0.0);
+        let svg = render_topic_flow_svg(&graph, &options);
+        assert!(svg.contains(&format!("fill=\"{}\"", heat_color(0.0))));
+
+        let options =
+            SvgOptions::default().heat_from_metrics(&graph.metrics(), |m| m.fan_in as f64);
+        // Both topics have the most incoming transitions; start_agent has none
+        assert_eq!(options.heat[&orders], 0.0);
+        assert_eq!(options.heat[&graph.get_start_agent().unwrap()], 1.0);
+
+        assert_eq!(heat_color(0.0), "#ea9999");
+        assert_eq!(heat_color(1.0), "#b6d7a8");
+        assert_eq!(truncate("refund_processing", 8), "refund_…");
+    }
+}
@@ -0,0 +1,236 @@
+//! Owned per-topic subgraphs.
+//!
+//! Unlike a [`RefGraphView`](super::RefGraphView), a subgraph is a new
+//! [`RefGraph`] with its own node indices, so every query, validation pass
+//! and renderer works on it unchanged. [`RefGraph::parent_index`] maps its
+//! nodes back to the graph it was extracted from.
+
+use super::nodes::RefNode;
+use super::RefGraph;
+use petgraph::graph::{DiGraph, NodeIndex};
+use petgraph::visit::EdgeRef;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+impl RefGraph {
+    /// Extract the part of the graph that concerns a single topic.
+    ///
+    /// The subgraph holds the topic, its action definitions and reasoning
+    /// actions, and whatever those reference directly: variables read or
+    /// written, topics transitioned to, and so on. Referenced nodes of
+    /// other topics are kept as leaves without their own references, so
+    /// the result stays focused even for large agents. Edges between kept
+    /// nodes are copied.
+    ///
+    /// Returns `None` if there is no topic named `name`.
+    ///
+    /// ```rust
+    /// use busbar_sf_agentscript::graph::RefGraph;
+    ///
+    /// let source = r#"
+    /// start_agent main:
+    ///    description: "Route"
+    ///    reasoning:
+    ///       instructions: "Route"
+    ///       actions:
+    ///          go: @utils.transition to @topic.orders
+    ///
+    /// topic orders:
+    ///    description: "Orders"
+    ///    reasoning:
+    ///       instructions: "Help"
+    /// "#;
+    /// let graph = RefGraph::from_ast(&busbar_sf_agentscript::parse(source).unwrap()).unwrap();
+    /// let orders = graph.subgraph_for_topic("orders").unwrap();
+    ///
+    /// let topic = orders.get_topic("orders").unwrap();
+    /// assert_eq!(orders.parent_index(topic), graph.get_topic("orders"));
+    /// assert!(orders.get_start_agent().is_none());
+    /// ```
+    pub fn subgraph_for_topic(&self, name: &str) -> Option<RefGraph> {
+        let root = self.get_topic(name)?;
+        let owned = |node: &RefNode| match node {
+            RefNode::ActionDef { topic, .. } | RefNode::ReasoningAction { topic, .. } => {
+                self.policy.key(topic) == self.policy.key(name)
+            }
+            _ => false,
+        };
+
+        // Only the topic's own nodes are expanded; everything they point
+        // at is included as a leaf.
+        let mut keep = vec![false; self.graph.node_count()];
+        keep[root.index()] = true;
+        for idx in self.graph.node_indices() {
+            if idx == root || owned(&self.graph[idx]) {
+                keep[idx.index()] = true;
+                for edge in self.graph.edges(idx) {
+                    keep[edge.target().index()] = true;
+                }
+            }
+        }
+
+        let mut graph = DiGraph::new();
+        let mut parent_indices = Vec::new();
+        let mut positions = HashMap::new();
+        for idx in self.graph.node_indices().filter(|i| keep[i.index()]) {
+            positions.insert(idx, graph.add_node(self.graph[idx].clone()));
+            parent_indices.push(idx);
+        }
+        let mut edge_spans = HashMap::new();
+        for edge in self.graph.edge_references() {
+            if let (Some(&source), Some(&target)) =
+                (positions.get(&edge.source()), positions.get(&edge.target()))
+            {
+                let idx = graph.add_edge(source, target, edge.weight().clone());
+                if let Some(&span) = self.edge_spans.get(&edge.id()) {
+                    edge_spans.insert(idx, span);
+                }
+            }
+        }
+
+        let span = self.graph[root].span();
+        let unresolved_references = self
+            .unresolved_references
+            .iter()
+            .filter(|e| {
+                e.span()
+                    .is_some_and(|(start, _)| span.0 <= start && start < span.1)
+            })
+            .cloned()
+            .collect();
+
+        Some(RefGraph {
+            policy: self.policy,
+            topics: remap(&self.topics, &positions),
+            action_defs: remap(&self.action_defs, &positions),
+            reasoning_actions: remap(&self.reasoning_actions, &positions),
+            variables: remap(&self.variables, &positions),
+            connections: remap(&self.connections, &positions),
+            start_agent: self
+                .start_agent
+                .and_then(|idx| positions.get(&idx).copied()),
+            unresolved_references,
+            // Duplicates are a property of the whole file
+            duplicate_definitions: Vec::new(),
+            parent_indices,
+            block_complexity: self
+                .block_complexity
+                .iter()
+                .filter_map(|(idx, block)| Some((*positions.get(idx)?, *block)))
+                .collect(),
+            edge_spans,
+            symbol_spans: self
+                .symbol_spans
+                .iter()
+                .filter_map(|(idx, spans)| Some((*positions.get(idx)?, spans.clone())))
+                .collect(),
+            param_usage: self
+                .param_usage
+                .iter()
+                .filter_map(|(idx, params)| Some((*positions.get(idx)?, params.clone())))
+                .collect(),
+            graph,
+        })
+    }
+
+    /// Index of `index` in the graph this one was extracted from.
+    ///
+    /// For a graph built from an AST, every index maps to itself.
+    pub fn parent_index(&self, index: NodeIndex) -> Option<NodeIndex> {
+        if self.parent_indices.is_empty() {
+            return self.graph.node_weight(index).map(|_| index);
+        }
+        self.parent_indices.get(index.index()).copied()
+    }
+}
+
+/// Re-key a name index by subgraph positions, dropping nodes left out.
+fn remap<K: Clone + Eq + Hash>(
+    index: &HashMap<K, NodeIndex>,
+    positions: &HashMap<NodeIndex, NodeIndex>,
+) -> HashMap<K, NodeIndex> {
+    index
+        .iter()
+        .filter_map(|(key, idx)| Some((key.clone(), *positions.get(idx)?)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subgraph_for_topic() {
+        let source = r#"variables:
+   order_id: mutable string = ""
+   status: mutable string = ""
+
+start_agent selector:
+   description: "Route"
+   reasoning:
+      instructions: "Select"
+      actions:
+         go_orders: @utils.transition to @topic.orders
+         go_billing: @utils.transition to @topic.billing
+
+topic orders:
+   description: "Orders"
+   actions:
+      lookup:
+         description: "Look up an order"
+         target: "flow://Lookup"
+   reasoning:
+      instructions: "Help"
+      actions:
+         do_lookup: @actions.lookup
+            with id=@variables.order_id
+         to_billing: @utils.transition to @topic.billing
+
+topic billing:
+   description: "Billing"
+   actions:
+      charge:
+         description: "Charge"
+         target: "flow://Charge"
+   reasoning:
+      instructions: "Bill"
+      actions:
+         do_charge: @actions.charge
+            with status=@variables.status
+"#;
+        let graph = RefGraph::from_ast(&crate::parse(source).unwrap()).unwrap();
+        let sub = graph.subgraph_for_topic("orders").unwrap();
+
+        let mut labels: Vec<_> = sub.inner().node_weights().map(RefNode::label).collect();
+        labels.sort();
+        assert_eq!(
+            labels,
+            [
+                "action:orders:lookup",
+                "reasoning:orders:do_lookup",
+                "reasoning:orders:to_billing",
+                "topic:billing",
+                "topic:orders",
+                "variable:order_id",
+            ]
+        );
+
+        // Every node and edge maps back to the same node in the parent
+        for idx in sub.inner().node_indices() {
+            let parent = sub.parent_index(idx).unwrap();
+            assert_eq!(sub.get_node(idx), graph.get_node(parent));
+        }
+        for edge in sub.inner().edge_references() {
+            let source = sub.parent_index(edge.source()).unwrap();
+            let target = sub.parent_index(edge.target()).unwrap();
+            assert!(graph.inner().contains_edge(source, target));
+        }
+        assert!(sub.edge_count() > 0);
+
+        assert!(sub.get_topic("billing").is_some());
+        assert!(sub.get_action_def("billing", "charge").is_none());
+        assert!(sub.get_variable("status").is_none());
+        assert!(graph.subgraph_for_topic("missing").is_none());
+        assert_eq!(graph.parent_index(NodeIndex::new(0)), Some(NodeIndex::new(0)));
+    }
+}
@@ -36,30 +36,127 @@ impl ValidationResult {
     }
 }
 
+/// An independent validation pass over the reference graph.
+///
+/// Passes can be run selectively with [`RefGraph::validate_selected`], e.g.
+/// only the [`cheap`](PassId::CHEAP) ones on every keystroke and all of them
+/// on save.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PassId {
+    /// Report references that could not be resolved during graph build
+    UnresolvedReferences,
+    /// Detect cycles in topic transitions
+    Cycles,
+    /// Find topics unreachable from start_agent
+    UnreachableTopics,
+    /// Find action definitions that are never invoked
+    UnusedActions,
+    /// Find variables that are never read
+    UnusedVariables,
+    /// Find action inputs and outputs that no invocation binds
+    UnusedParameters,
+    /// Find escalations with no connection to route through
+    Escalations,
+    /// Report topics and variables defined more than once
+    Duplicates,
+}
+
+impl PassId {
+    /// Every pass, in the order [`RefGraph::validate`] reports them.
+    pub const ALL: &'static [PassId] = &[
+        PassId::UnresolvedReferences,
+        PassId::Cycles,
+        PassId::UnreachableTopics,
+        PassId::UnusedActions,
+        PassId::UnusedVariables,
+        PassId::UnusedParameters,
+        PassId::Escalations,
+        PassId::Duplicates,
+    ];
+
+    /// Passes that only look at a node's immediate edges.
+    pub const CHEAP: &'static [PassId] = &[
+        PassId::UnresolvedReferences,
+        PassId::UnusedActions,
+        PassId::UnusedVariables,
+        PassId::UnusedParameters,
+        PassId::Escalations,
+        PassId::Duplicates,
+    ];
+
+    /// Whether this pass traverses the whole graph.
+    pub fn is_expensive(&self) -> bool {
+        !Self::CHEAP.contains(self)
+    }
+
+    /// Whether issues found by this pass are errors (as opposed to warnings).
+    pub fn reports_errors(&self) -> bool {
+        matches!(
+            self,
+            PassId::UnresolvedReferences
+                | PassId::Cycles
+                | PassId::Escalations
+                | PassId::Duplicates
+        )
+    }
+}
+
 impl RefGraph {
     /// Perform full validation of the reference graph.
     ///
     /// Returns errors for issues that would cause runtime failures,
     /// and warnings for issues that may indicate problems.
     pub fn validate(&self) -> ValidationResult {
-        let mut result = ValidationResult::default();
-
-        // Report unresolved references found during graph build
-        result.errors.extend(self.unresolved_references.clone());
-
-        // Check for cycles
-        result.errors.extend(self.find_cycles());
-
-        // Check for unreachable topics
-        result.warnings.extend(self.find_unreachable_topics());
+        self.validate_selected(PassId::ALL)
+    }
 
-        // Check for unused definitions
-        result.warnings.extend(self.find_unused_actions());
-        result.warnings.extend(self.find_unused_variables());
+    /// Run only the given validation passes.
+    ///
+    /// With the `parallel` feature the passes run concurrently on the rayon
+    /// thread pool. Errors and warnings are each sorted by position, then by
+    /// message; issues without a position, like cycles, come last.
+    pub fn validate_selected(&self, passes: &[PassId]) -> ValidationResult {
+        #[cfg(feature = "parallel")]
+        let outputs: Vec<(PassId, Vec<ValidationError>)> = {
+            use rayon::prelude::*;
+            passes
+                .par_iter()
+                .map(|&pass| (pass, self.run_pass(pass)))
+                .collect()
+        };
+        #[cfg(not(feature = "parallel"))]
+        let outputs: Vec<(PassId, Vec<ValidationError>)> = passes
+            .iter()
+            .map(|&pass| (pass, self.run_pass(pass)))
+            .collect();
 
+        let mut result = ValidationResult::default();
+        for (pass, issues) in outputs {
+            if pass.reports_errors() {
+                result.errors.extend(issues);
+            } else {
+                result.warnings.extend(issues);
+            }
+        }
+        sort_issues(&mut result.errors);
+        sort_issues(&mut result.warnings);
         result
     }
 
+    /// Run a single validation pass.
+    pub fn run_pass(&self, pass: PassId) -> Vec<ValidationError> {
+        match pass {
+            PassId::UnresolvedReferences => self.unresolved_references.clone(),
+            PassId::Cycles => self.find_cycles(),
+            PassId::UnreachableTopics => self.find_unreachable_topics(),
+            PassId::UnusedActions => self.find_unused_actions(),
+            PassId::UnusedVariables => self.find_unused_variables(),
+            PassId::UnusedParameters => self.find_unused_parameters(),
+            PassId::Escalations => self.find_unrouted_escalations(),
+            PassId::Duplicates => self.duplicate_definitions.clone(),
+        }
+    }
+
     /// Find cycles in topic transitions.
     ///
     /// Topic transitions should form a DAG. Cycles indicate infinite loops.
@@ -125,7 +222,7 @@ impl RefGraph {
             .collect()
     }
 
-    /// Find action definitions that are never invoked.
+    /// Find action definitions that are never invoked or chained to.
     pub fn find_unused_actions(&self) -> Vec<ValidationError> {
         self.action_defs
             .iter()
@@ -134,7 +231,7 @@ impl RefGraph {
                 let has_incoming = self
                     .graph
                     .edges_directed(idx, Direction::Incoming)
-                    .any(|e| matches!(e.weight(), RefEdge::Invokes));
+                    .any(|e| matches!(e.weight(), RefEdge::Invokes | RefEdge::Chains));
 
                 if !has_incoming {
                     if let Some(RefNode::ActionDef { span, .. }) = self.graph.node_weight(idx) {
@@ -153,6 +250,69 @@ impl RefGraph {
             .collect()
     }
 
+    /// Find action definitions that can't be invoked from start_agent.
+    ///
+    /// An action is reachable when it is invoked (by a reasoning action, a
+    /// `run` clause, an instruction interpolation or a `run` statement in a
+    /// `before_reasoning`/`after_reasoning` directive) from start_agent or
+    /// from a topic reachable from it. Unlike [`find_unused_actions`](Self::find_unused_actions),
+    /// this also reports actions whose only callers live in dead topics.
+    /// Results are sorted by span.
+    pub fn find_unreachable_actions(&self) -> Vec<ValidationError> {
+        let start_idx = match self.start_agent {
+            Some(idx) => idx,
+            None => return vec![],
+        };
+
+        let reachable_topics = self.find_reachable_from(start_idx);
+        let is_live_topic = |topic: &str| {
+            if topic == "start_agent" {
+                return true;
+            }
+            self.topics
+                .get(topic)
+                .is_some_and(|idx| reachable_topics.contains(idx))
+        };
+
+        // Seed with every live topic and the reasoning actions they contain,
+        // then follow invocation edges only.
+        let mut stack: Vec<NodeIndex> = reachable_topics.iter().copied().collect();
+        stack.extend(
+            self.reasoning_actions
+                .iter()
+                .filter(|((topic, _), _)| is_live_topic(topic))
+                .map(|(_, &idx)| idx),
+        );
+        let mut visited = HashSet::new();
+        while let Some(idx) = stack.pop() {
+            if visited.insert(idx) {
+                for edge in self.graph.edges_directed(idx, Direction::Outgoing) {
+                    if matches!(edge.weight(), RefEdge::Invokes | RefEdge::Chains) {
+                        stack.push(edge.target());
+                    }
+                }
+            }
+        }
+
+        let mut errors: Vec<ValidationError> = self
+            .action_defs
+            .iter()
+            .filter(|(_, idx)| !visited.contains(idx))
+            .filter_map(|((topic, name), &idx)| match self.graph.node_weight(idx) {
+                Some(RefNode::ActionDef { span, .. }) => {
+                    Some(ValidationError::UnreachableActionDef {
+                        name: name.clone(),
+                        topic: topic.clone(),
+                        span: *span,
+                    })
+                }
+                _ => None,
+            })
+            .collect();
+        errors.sort_by_key(|e| e.span());
+        errors
+    }
+
     /// Find variables that are never read.
     pub fn find_unused_variables(&self) -> Vec<ValidationError> {
         self.variables
@@ -180,6 +340,76 @@ impl RefGraph {
             .collect()
     }
 
+    /// Find inputs of invoked actions that no `with` clause binds, and
+    /// outputs that no `set` clause captures.
+    ///
+    /// Outputs read by an invocation's `if` conditions count as captured.
+    /// Actions that are never invoked are left out; [`find_unused_actions`]
+    /// reports them already. Results are sorted by span.
+    ///
+    /// [`find_unused_actions`]: Self::find_unused_actions
+    pub fn find_unused_parameters(&self) -> Vec<ValidationError> {
+        let mut errors: Vec<ValidationError> = self
+            .action_defs
+            .iter()
+            .filter(|(_, &idx)| {
+                self.graph
+                    .edges_directed(idx, Direction::Incoming)
+                    .any(|e| matches!(e.weight(), RefEdge::Invokes))
+            })
+            .flat_map(|((topic, action), idx)| {
+                self.param_usage
+                    .get(idx)
+                    .into_iter()
+                    .flatten()
+                    .filter(|param| !param.used)
+                    .map(|param| ValidationError::UnusedParameter {
+                        name: param.name.clone(),
+                        output: param.output,
+                        action: action.clone(),
+                        topic: topic.clone(),
+                        span: param.span,
+                    })
+            })
+            .collect();
+        errors.sort_by_key(|e| e.span());
+        errors
+    }
+
+    /// Find `@utils.escalate` reasoning actions with no connection to
+    /// escalate through.
+    ///
+    /// The builder links every escalation to every defined connection, so
+    /// this reports all escalations when the agent defines none. Results
+    /// are sorted by span.
+    pub fn find_unrouted_escalations(&self) -> Vec<ValidationError> {
+        let mut errors: Vec<ValidationError> = self
+            .reasoning_actions
+            .values()
+            .filter_map(|&idx| match self.graph.node_weight(idx) {
+                Some(RefNode::ReasoningAction {
+                    name,
+                    topic,
+                    target: Some(target),
+                    span,
+                }) if target == "@utils.escalate" => {
+                    let routed = self
+                        .graph
+                        .edges_directed(idx, Direction::Outgoing)
+                        .any(|e| matches!(e.weight(), RefEdge::Escalates));
+                    (!routed).then(|| ValidationError::EscalationWithoutConnection {
+                        name: name.clone(),
+                        topic: topic.clone(),
+                        span: *span,
+                    })
+                }
+                _ => None,
+            })
+            .collect();
+        errors.sort_by_key(|e| e.span());
+        errors
+    }
+
     /// Find all nodes reachable from a starting node.
     fn find_reachable_from(&self, start: NodeIndex) -> HashSet<NodeIndex> {
         let mut reachable = HashSet::new();
@@ -198,6 +428,14 @@ impl RefGraph {
     }
 }
 
+/// Sort issues by position, then by message.
+fn sort_issues(issues: &mut [ValidationError]) {
+    issues.sort_by_cached_key(|issue| {
+        let span = issue.span();
+        (span.is_none(), span, issue.message())
+    });
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -373,6 +611,197 @@ topic main:
         );
     }
 
+    #[test]
+    fn test_unreachable_action_def_detected() {
+        // lookup is invoked, but only from a topic nothing transitions to;
+        // fetch is invoked from a reachable topic.
+        let source = r#"config:
+   agent_name: "Test"
+
+start_agent selector:
+   description: "Route"
+   reasoning:
+      instructions: "Select"
+      actions:
+         go_help: @utils.transition to @topic.help
+            description: "Go to help"
+
+topic help:
+   description: "Help topic"
+   actions:
+      fetch:
+         description: "Fetch"
+         target: "flow://Fetch"
+   reasoning:
+      instructions: "Provide help"
+      actions:
+         do_fetch: @actions.fetch
+            description: "Fetch"
+
+topic orphan:
+   description: "Never reached"
+   actions:
+      lookup:
+         description: "Lookup"
+         target: "flow://Lookup"
+   reasoning:
+      instructions: "Orphan"
+      actions:
+         do_lookup: @actions.lookup
+            description: "Lookup"
+"#;
+        let graph = parse_and_build(source);
+        assert!(graph.find_unused_actions().is_empty());
+
+        let unreachable = graph.find_unreachable_actions();
+        assert_eq!(unreachable.len(), 1, "got: {:?}", unreachable);
+        match &unreachable[0] {
+            ValidationError::UnreachableActionDef { name, topic, span } => {
+                assert_eq!((topic.as_str(), name.as_str()), ("orphan", "lookup"));
+                assert!(source[span.0..span.1].starts_with("lookup"));
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_directive_and_run_clause_callers_reach_actions() {
+        // fetch is only run from before_reasoning, notify only from a run
+        // clause, and both read or write variables nothing else touches.
+        let source = r#"config:
+   agent_name: "Test"
+
+variables:
+   ready: mutable boolean = False
+      description: "Ready"
+   result: mutable string = ""
+      description: "Result"
+
+start_agent selector:
+   description: "Route"
+   reasoning:
+      instructions: "Select"
+      actions:
+         go_help: @utils.transition to @topic.help
+            description: "Go to help"
+
+topic help:
+   description: "Help topic"
+   actions:
+      fetch:
+         description: "Fetch"
+         target: "flow://Fetch"
+      notify:
+         description: "Notify"
+         target: "flow://Notify"
+      greet:
+         description: "Greet"
+         target: "flow://Greet"
+
+   before_reasoning:
+      if @variables.ready:
+         run @actions.fetch
+            set @variables.result = @outputs.value
+
+   reasoning:
+      instructions: "Provide help"
+      actions:
+         do_greet: @actions.greet
+            description: "Greet"
+            run @actions.notify
+               with message = @variables.result
+"#;
+        let graph = parse_and_build(source);
+        assert!(graph.unresolved_references.is_empty());
+        assert!(graph.find_unused_actions().is_empty());
+        assert!(
+            graph.find_unreachable_actions().is_empty(),
+            "got: {:?}",
+            graph.find_unreachable_actions()
+        );
+        assert!(graph.find_unused_variables().is_empty());
+
+        let help = graph.get_topic("help").unwrap();
+        let fetch = graph.get_action_def("help", "fetch").unwrap();
+        let edge = graph.inner().find_edge(help, fetch).unwrap();
+        assert_eq!(graph.inner()[edge], RefEdge::Invokes);
+        let span = graph.reference_span(edge).unwrap();
+        assert_eq!(&source[span.0..span.1], "@actions.fetch");
+
+        let do_greet = graph.get_reasoning_action("help", "do_greet").unwrap();
+        let notify = graph.get_action_def("help", "notify").unwrap();
+        let edge = graph.inner().find_edge(do_greet, notify).unwrap();
+        assert_eq!(graph.inner()[edge], RefEdge::Chains);
+    }
+
+    #[test]
+    fn test_directive_run_of_undefined_action_is_unresolved() {
+        let source = r#"config:
+   agent_name: "Test"
+
+topic main:
+   description: "Main"
+   before_reasoning:
+      run @actions.missing
+   reasoning:
+      instructions: "Help"
+"#;
+        let graph = parse_and_build(source);
+        let errors = &graph.unresolved_references;
+        assert_eq!(errors.len(), 1, "got: {:?}", errors);
+        assert!(matches!(
+            &errors[0],
+            ValidationError::UnresolvedReference { namespace, .. } if namespace == "actions"
+        ));
+    }
+
+    #[test]
+    fn test_clause_conditions_read_variables() {
+        // Each variable is only read by a condition: `available when` in
+        // start_agent, an `if` clause and a directive `if`.
+        let source = r#"variables:
+   flag: mutable boolean = False
+      description: "Flag"
+   step: mutable number = 0
+      description: "Step"
+   ready: mutable boolean = False
+      description: "Ready"
+
+start_agent main:
+   description: "Start"
+   reasoning:
+      instructions: "Route"
+      actions:
+         go: @utils.transition to @topic.help
+            description: "Help"
+            available when @variables.flag
+
+topic help:
+   description: "Help"
+   after_reasoning:
+      if @variables.ready:
+         transition to @topic.help
+   reasoning:
+      instructions: "Help"
+      actions:
+         check: @actions.check
+            description: "Check"
+            if @variables.step > 1:
+               transition to @topic.help
+   actions:
+      check:
+         description: "Check"
+         target: "flow://Check"
+"#;
+        let graph = parse_and_build(source);
+        assert!(graph.unresolved_references.is_empty());
+        assert!(
+            graph.find_unused_variables().is_empty(),
+            "got: {:?}",
+            graph.find_unused_variables()
+        );
+    }
+
     #[test]
     fn test_unused_variable_detected() {
         // customer_name is declared in the variables block but is never read by
@@ -678,4 +1107,285 @@ topic main:
             "Expected an unresolved reference error for @variables.nonexistent_var"
         );
     }
+
+    #[test]
+    fn test_validate_selected_runs_only_requested_passes() {
+        let source = r#"config:
+   agent_name: "Test"
+
+variables:
+   unused: mutable string = ""
+
+start_agent topic_selector:
+   description: "Route to topics"
+   reasoning:
+      instructions: "Select the best topic"
+      actions:
+         go_help: @utils.transition to @topic.help
+            description: "Go to help topic"
+
+topic help:
+   description: "Help topic"
+   reasoning:
+      instructions: "Provide help"
+
+topic orphan:
+   description: "Never reached"
+   reasoning:
+      instructions: "Nothing"
+"#;
+        let graph = parse_and_build(source);
+
+        let cheap = graph.validate_selected(PassId::CHEAP);
+        assert!(cheap.warnings.iter().any(
+            |w| matches!(w, ValidationError::UnusedVariable { name, .. } if name == "unused")
+        ));
+        assert!(!cheap
+            .warnings
+            .iter()
+            .any(|w| matches!(w, ValidationError::UnreachableTopic { .. })));
+
+        let reachability = graph.validate_selected(&[PassId::UnreachableTopics]);
+        assert_eq!(reachability.warnings.len(), 1);
+        assert!(reachability.errors.is_empty());
+
+        let full = graph.validate();
+        assert_eq!(full.warnings.len(), cheap.warnings.len() + reachability.warnings.len());
+    }
+
+    #[test]
+    fn test_escalation_requires_connection() {
+        let without = r#"start_agent topic_selector:
+   description: "Route"
+   reasoning:
+      instructions: "Route"
+      actions:
+         go_help: @utils.transition to @topic.help
+
+topic help:
+   description: "Help"
+   reasoning:
+      instructions: "Help"
+      actions:
+         human: @utils.escalate
+"#;
+        let graph = parse_and_build(without);
+        let errors = graph.run_pass(PassId::Escalations);
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(
+            &errors[0],
+            ValidationError::EscalationWithoutConnection { name, topic, .. }
+                if name == "human" && topic == "help"
+        ));
+        assert!(graph.validate().errors.contains(&errors[0]));
+
+        let with = format!(
+            "connection messaging:\n   escalation_message: \"Connecting you\"\n\n{}",
+            without
+        );
+        let graph = parse_and_build(&with);
+        assert!(graph.run_pass(PassId::Escalations).is_empty());
+
+        let connection = graph.get_connection("messaging").unwrap();
+        let human = graph.get_reasoning_action("help", "human").unwrap();
+        let edge = graph.inner().find_edge(human, connection).unwrap();
+        assert_eq!(graph.inner()[edge], RefEdge::Escalates);
+        assert_eq!(graph.stats().connections, 1);
+    }
+
+    #[test]
+    fn test_issues_sorted_by_position() {
+        let source = r#"variables:
+   c: mutable string = ""
+   a: mutable string = ""
+   b: mutable string = ""
+
+topic main:
+   description: "Main"
+   reasoning:
+      instructions: "Help"
+"#;
+        let graph = parse_and_build(source);
+        let names: Vec<_> = graph
+            .validate()
+            .warnings
+            .iter()
+            .filter_map(|w| match w {
+                ValidationError::UnusedVariable { name, .. } => Some(name.clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(names, ["c", "a", "b"]);
+    }
+
+    #[test]
+    fn test_unused_parameters_detected() {
+        let source = r#"variables:
+   policy: mutable string = ""
+   amount: mutable number = 0
+
+topic main:
+   description: "Main"
+   actions:
+      lookup:
+         description: "Look up a policy"
+         inputs:
+            policy_id: string
+            include_history: boolean
+         outputs:
+            coverage: number
+            history: list[object]
+         target: "flow://Lookup"
+      archive:
+         description: "Never invoked"
+         inputs:
+            policy_id: string
+         target: "flow://Archive"
+   reasoning:
+      instructions: ->
+         | Coverage: {!@variables.amount}
+      actions:
+         look_up: @actions.lookup
+            with policy_id = @variables.policy
+            set @variables.amount = @outputs.coverage
+"#;
+        let graph = parse_and_build(source);
+        let unused = graph.run_pass(PassId::UnusedParameters);
+        let names: Vec<_> = unused
+            .iter()
+            .map(|e| match e {
+                ValidationError::UnusedParameter {
+                    name,
+                    output,
+                    action,
+                    ..
+                } => (name.as_str(), *output, action.as_str()),
+                other => panic!("unexpected {:?}", other),
+            })
+            .collect();
+        // `archive` is reported as unused as a whole instead
+        assert_eq!(
+            names,
+            [
+                ("include_history", false, "lookup"),
+                ("history", true, "lookup")
+            ]
+        );
+        let (start, end) = unused[0].span().unwrap();
+        assert!(source[start..end].starts_with("include_history: boolean"));
+        assert!(graph.validate().warnings.contains(&unused[1]));
+        assert!(unused[1].is_unused());
+    }
+
+    #[test]
+    fn test_duplicate_definitions() {
+        let source = r#"variables:
+   order: mutable string = ""
+   order: mutable object = {}
+
+topic help:
+   description: "Help"
+   reasoning:
+      instructions: "Help"
+
+topic help:
+   description: "More help"
+   actions:
+      lookup:
+         target: "flow://Lookup"
+   reasoning:
+      instructions: "Help"
+      actions:
+         look_up: @actions.lookup
+"#;
+        let graph = parse_and_build(source);
+        let duplicates = graph.run_pass(PassId::Duplicates);
+        let found: Vec<_> = duplicates
+            .iter()
+            .map(|e| match e {
+                ValidationError::DuplicateDefinition { name, kind, spans } => {
+                    let sites: Vec<_> = spans.iter().map(|s| &source[s.0..s.1]).collect();
+                    assert_eq!(sites, [name.as_str(), name.as_str()]);
+                    (kind.as_str(), name.as_str())
+                }
+                other => panic!("unexpected {:?}", other),
+            })
+            .collect();
+        assert_eq!(found, [("variable", "order"), ("topic", "help")]);
+        assert_eq!(duplicates[1].message(), "Topic 'help' is defined 2 times");
+        assert_eq!(duplicates[1].span().map(|s| s.0), source.rfind("help:"));
+        assert!(graph.validate().errors.contains(&duplicates[0]));
+
+        // Both topics are nodes, and each resolves its own actions
+        let topics = graph
+            .inner()
+            .node_weights()
+            .filter(|n| matches!(n, RefNode::Topic { .. }))
+            .count();
+        assert_eq!(topics, 2);
+        assert!(graph.run_pass(PassId::UnresolvedReferences).is_empty());
+        let lookup = graph.get_action_def("help", "lookup").unwrap();
+        assert_eq!(graph.find_action_invokers(lookup).len(), 1);
+    }
+
+    #[test]
+    fn test_resolution_policy_and_suggestions() {
+        let source = r#"variables:
+   order_id: mutable string = ""
+
+start_agent main:
+   description: "Route"
+   reasoning:
+      instructions: ->
+         | Order {!@variables.orderid}
+      actions:
+         go: @utils.transition to @topic.Refunds
+
+topic refunds:
+   description: "Refunds"
+   actions:
+      lookup:
+         target: "flow://Lookup"
+   reasoning:
+      instructions: "Help"
+      actions:
+         look_up: @actions.Lookup
+
+topic REFUNDS:
+   description: "Shouting"
+"#;
+        let ast = crate::parse(source).unwrap();
+        let exact = RefGraph::from_ast(&ast).unwrap();
+        let hints: Vec<_> = exact
+            .run_pass(PassId::UnresolvedReferences)
+            .iter()
+            .map(|e| e.hint().unwrap())
+            .collect();
+        assert_eq!(
+            hints,
+            [
+                "Did you mean '@variables.order_id'?",
+                "Did you mean '@topic.refunds'?",
+                "Did you mean '@actions.lookup'?",
+            ]
+        );
+        assert!(exact.run_pass(PassId::Duplicates).is_empty());
+
+        let graph = crate::graph::RefGraphBuilder::new()
+            .with_policy(crate::graph::ResolutionPolicy {
+                case_insensitive: true,
+            })
+            .build(&ast)
+            .unwrap();
+        let unresolved = graph.run_pass(PassId::UnresolvedReferences);
+        assert_eq!(unresolved.len(), 1, "{:?}", unresolved);
+        let refunds = graph.get_topic("Refunds").unwrap();
+        assert_eq!(graph.get_node(refunds).and_then(RefNode::name), Some("refunds"));
+        let lookup = graph.get_action_def("REFUNDS", "LOOKUP").unwrap();
+        assert_eq!(graph.find_action_invokers(lookup).len(), 1);
+
+        let duplicates = graph.run_pass(PassId::Duplicates);
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(duplicates[0].message(), "Topic 'refunds' is defined 2 times");
+    }
 }
@@ -0,0 +1,253 @@
+//! Filtered views over a reference graph.
+//!
+//! A [`RefGraphView`] borrows a [`RefGraph`] and records which nodes and
+//! edges are visible, without copying any node or edge data. Renderers and
+//! exports accept views, so callers can draw e.g. "only topics and
+//! transitions" without building a new graph.
+
+use super::edges::RefEdge;
+use super::nodes::RefNode;
+use super::RefGraph;
+use petgraph::graph::{EdgeIndex, EdgeReference, NodeIndex};
+use petgraph::visit::EdgeRef;
+
+/// A borrowed subset of a [`RefGraph`].
+///
+/// An edge is visible only if it passes the edge filter and both of its
+/// endpoints are visible.
+#[derive(Debug, Clone)]
+pub struct RefGraphView<'a> {
+    graph: &'a RefGraph,
+    nodes: Vec<bool>,
+    edges: Vec<bool>,
+}
+
+impl<'a> RefGraphView<'a> {
+    /// A view containing every node and edge of `graph`.
+    pub fn new(graph: &'a RefGraph) -> Self {
+        Self {
+            graph,
+            nodes: vec![true; graph.node_count()],
+            edges: vec![true; graph.edge_count()],
+        }
+    }
+
+    /// The underlying graph.
+    pub fn graph(&self) -> &'a RefGraph {
+        self.graph
+    }
+
+    /// Narrow the view to nodes matching `predicate`.
+    pub fn filter(mut self, predicate: impl Fn(&RefNode) -> bool) -> Self {
+        let inner = self.graph.inner();
+        for idx in inner.node_indices() {
+            if !predicate(&inner[idx]) {
+                self.nodes[idx.index()] = false;
+            }
+        }
+        self
+    }
+
+    /// Narrow the view to edges matching `predicate`.
+    pub fn filter_edges(mut self, predicate: impl Fn(&RefEdge) -> bool) -> Self {
+        for edge in self.graph.inner().edge_references() {
+            if !predicate(edge.weight()) {
+                self.edges[edge.id().index()] = false;
+            }
+        }
+        self
+    }
+
+    /// Narrow the view to `node` and its direct neighbours.
+    pub fn neighborhood(mut self, node: NodeIndex) -> Self {
+        let mut keep = vec![false; self.nodes.len()];
+        if self.contains(node) {
+            keep[node.index()] = true;
+            for edge in self.edge_references() {
+                if edge.source() == node {
+                    keep[edge.target().index()] = true;
+                } else if edge.target() == node {
+                    keep[edge.source().index()] = true;
+                }
+            }
+        }
+        self.nodes = keep;
+        self
+    }
+
+    /// Check whether a node is visible.
+    pub fn contains(&self, index: NodeIndex) -> bool {
+        self.nodes.get(index.index()).copied().unwrap_or(false)
+    }
+
+    /// Check whether an edge is visible.
+    pub fn contains_edge(&self, index: EdgeIndex) -> bool {
+        let Some((source, target)) = self.graph.inner().edge_endpoints(index) else {
+            return false;
+        };
+        self.edges[index.index()] && self.contains(source) && self.contains(target)
+    }
+
+    /// Get a visible node by its index.
+    pub fn get_node(&self, index: NodeIndex) -> Option<&'a RefNode> {
+        if self.contains(index) {
+            self.graph.get_node(index)
+        } else {
+            None
+        }
+    }
+
+    /// Iterate over the indices of visible nodes.
+    pub fn node_indices(&self) -> impl Iterator<Item = NodeIndex> + '_ {
+        self.graph
+            .inner()
+            .node_indices()
+            .filter(|idx| self.contains(*idx))
+    }
+
+    /// Iterate over visible edges.
+    pub fn edge_references(&self) -> impl Iterator<Item = EdgeReference<'a, RefEdge>> + '_ {
+        self.graph
+            .inner()
+            .edge_references()
+            .filter(|edge| self.contains_edge(edge.id()))
+    }
+
+    /// Get the number of visible nodes.
+    pub fn node_count(&self) -> usize {
+        self.nodes.iter().filter(|v| **v).count()
+    }
+
+    /// Get the number of visible edges.
+    pub fn edge_count(&self) -> usize {
+        self.edge_references().count()
+    }
+}
+
+impl<'a> From<&'a RefGraph> for RefGraphView<'a> {
+    fn from(graph: &'a RefGraph) -> Self {
+        RefGraphView::new(graph)
+    }
+}
+
+impl<'a> From<&RefGraphView<'a>> for RefGraphView<'a> {
+    fn from(view: &RefGraphView<'a>) -> Self {
+        view.clone()
+    }
+}
+
+impl RefGraph {
+    /// Create a view over every node and edge.
+    pub fn view(&self) -> RefGraphView<'_> {
+        RefGraphView::new(self)
+    }
+
+    /// Create a view containing only nodes matching `predicate`.
+    ///
+    /// Edges are kept when both endpoints match. Nothing is copied; the
+    /// view borrows this graph.
+    ///
+    /// ```rust
+    /// use busbar_sf_agentscript::graph::{render_mermaid, RefEdge, RefGraph, RefNode};
+    ///
+    /// let source = r#"
+    /// config:
+    ///    agent_name: "Test"
+    ///
+    /// start_agent selector:
+    ///    description: "Route"
+    ///    reasoning:
+    ///       instructions: "Select"
+    ///       actions:
+    ///          go: @utils.transition to @topic.main
+    ///             description: "Go"
+    ///
+    /// topic main:
+    ///    description: "Main"
+    ///    reasoning:
+    ///       instructions: "Help"
+    /// "#;
+    /// let ast = busbar_sf_agentscript::parse(source).unwrap();
+    /// let graph = RefGraph::from_ast(&ast).unwrap();
+    ///
+    /// // Only topics and the transitions between them
+    /// let view = graph
+    ///     .filter(|node| matches!(node, RefNode::Topic { .. } | RefNode::StartAgent { .. }))
+    ///     .filter_edges(|edge| matches!(edge, RefEdge::Routes | RefEdge::TransitionsTo));
+    /// assert!(view.node_count() < graph.node_count());
+    /// let mermaid = render_mermaid(&view);
+    /// assert!(!mermaid.contains("(\"go"));
+    /// ```
+    pub fn filter(&self, predicate: impl Fn(&RefNode) -> bool) -> RefGraphView<'_> {
+        self.view().filter(predicate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = r#"config:
+   agent_name: "Test"
+
+variables:
+   order_id: mutable string = ""
+   status: mutable string = ""
+
+start_agent selector:
+   description: "Route"
+   reasoning:
+      instructions: "Select"
+      actions:
+         go_main: @utils.transition to @topic.main
+            description: "Go to main"
+
+topic main:
+   description: "Main topic"
+
+   actions:
+      lookup:
+         description: "Look up an order"
+         inputs:
+            id: string
+               description: "Order ID"
+         target: "flow://Lookup"
+
+   reasoning:
+      instructions: "Help"
+      actions:
+         do_lookup: @actions.lookup
+            description: "Lookup"
+            with id=@variables.order_id
+"#;
+
+    fn build() -> RefGraph {
+        let ast = crate::parse(SOURCE).expect("Failed to parse");
+        RefGraph::from_ast(&ast).expect("Failed to build graph")
+    }
+
+    #[test]
+    fn test_filter_drops_nodes_and_dangling_edges() {
+        let graph = build();
+        let view = graph.filter(|node| !matches!(node, RefNode::Variable { .. }));
+
+        assert_eq!(view.node_count(), graph.node_count() - 2);
+        assert!(view.edge_count() < graph.edge_count());
+        assert!(view.edge_references().all(|e| !e.weight().is_data_flow()));
+        let var = graph.get_variable("order_id").unwrap();
+        assert!(!view.contains(var));
+        assert!(view.get_node(var).is_none());
+    }
+
+    #[test]
+    fn test_neighborhood_of_variable() {
+        let graph = build();
+        let var = graph.get_variable("order_id").unwrap();
+        let view = graph.view().neighborhood(var);
+
+        // The variable and the reasoning action that reads it
+        assert_eq!(view.node_count(), 2);
+        assert_eq!(view.edge_count(), 1);
+        assert!(view.contains(graph.get_reasoning_action("main", "do_lookup").unwrap()));
+    }
+}
@@ -2,11 +2,11 @@
 //!
 //! This module provides thin JavaScript-accessible wrappers around the core
 //! graph functionality. All actual logic lives in other modules:
-//! - `render/` - ASCII and GraphML rendering
+//! - `render/` - ASCII, GraphML, DOT, SVG and Mermaid rendering
 //! - `export` - Serialization types
 //! - Core crate - Graph building, validation, queries
 
-use super::{export, render, RefGraph};
+use super::{export, render, LayoutOptions, RefGraph, TopicMetrics};
 use wasm_bindgen::prelude::*;
 
 // ============================================================================
@@ -14,8 +14,10 @@ use wasm_bindgen::prelude::*;
 // ============================================================================
 
 /// Build a reference graph from an AgentScript AST.
-#[wasm_bindgen]
-pub fn build_graph(ast: JsValue) -> Result<JsValue, JsValue> {
+#[wasm_bindgen(unchecked_return_type = "GraphRepr")]
+pub fn build_graph(
+    #[wasm_bindgen(unchecked_param_type = "AgentFile")] ast: JsValue,
+) -> Result<JsValue, JsValue> {
     let agent: crate::AgentFile = serde_wasm_bindgen::from_value(ast)
         .map_err(|e| JsValue::from_str(&format!("Failed to deserialize AST: {}", e)))?;
 
@@ -28,7 +30,7 @@ pub fn build_graph(ast: JsValue) -> Result<JsValue, JsValue> {
 }
 
 /// Build a reference graph from AgentScript source code.
-#[wasm_bindgen]
+#[wasm_bindgen(unchecked_return_type = "GraphRepr")]
 pub fn build_graph_from_source(source: &str) -> Result<JsValue, JsValue> {
     let graph = parse_and_build(source)?;
     let repr = export::GraphRepr::from(&graph);
@@ -41,7 +43,7 @@ pub fn build_graph_from_source(source: &str) -> Result<JsValue, JsValue> {
 // ============================================================================
 
 /// Validate a reference graph and return any errors/warnings.
-#[wasm_bindgen]
+#[wasm_bindgen(unchecked_return_type = "ValidationResultRepr")]
 pub fn validate_graph(source: &str) -> Result<JsValue, JsValue> {
     let graph = parse_and_build(source)?;
     let result = graph.validate();
@@ -50,12 +52,20 @@ pub fn validate_graph(source: &str) -> Result<JsValue, JsValue> {
         .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
 }
 
+/// Find cycles in topic transitions, one `CycleDetected` error per cycle.
+#[wasm_bindgen(unchecked_return_type = "ValidationErrorRepr[]")]
+pub fn find_cycles(source: &str) -> Result<JsValue, JsValue> {
+    let cycles = cycle_reprs(&parse_and_build(source)?);
+    serde_wasm_bindgen::to_value(&cycles)
+        .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
+}
+
 // ============================================================================
 // Statistics
 // ============================================================================
 
 /// Get statistics about a reference graph.
-#[wasm_bindgen]
+#[wasm_bindgen(unchecked_return_type = "GraphStats")]
 pub fn get_graph_stats(source: &str) -> Result<JsValue, JsValue> {
     let graph = parse_and_build(source)?;
     let stats = graph.stats();
@@ -63,12 +73,21 @@ pub fn get_graph_stats(source: &str) -> Result<JsValue, JsValue> {
         .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
 }
 
+/// Get per-topic metrics (fan-in/out, depth, complexity) as a `GraphMetricsRepr`.
+#[wasm_bindgen(unchecked_return_type = "GraphMetricsRepr")]
+pub fn get_graph_metrics(source: &str) -> Result<JsValue, JsValue> {
+    let graph = parse_and_build(source)?;
+    let repr = export::GraphMetricsRepr::from(&graph.metrics());
+    serde_wasm_bindgen::to_value(&repr)
+        .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
+}
+
 // ============================================================================
 // Queries
 // ============================================================================
 
 /// Find all usages of a topic by name.
-#[wasm_bindgen]
+#[wasm_bindgen(unchecked_return_type = "NodeRepr[]")]
 pub fn find_topic_usages(source: &str, topic_name: &str) -> Result<JsValue, JsValue> {
     let graph = parse_and_build(source)?;
 
@@ -88,7 +107,7 @@ pub fn find_topic_usages(source: &str, topic_name: &str) -> Result<JsValue, JsVa
 }
 
 /// Find all topics that a given topic transitions to.
-#[wasm_bindgen]
+#[wasm_bindgen(unchecked_return_type = "NodeRepr[]")]
 pub fn find_topic_transitions(source: &str, topic_name: &str) -> Result<JsValue, JsValue> {
     let graph = parse_and_build(source)?;
 
@@ -160,6 +179,45 @@ pub fn render_graph(source: &str, view: &str) -> Result<String, JsValue> {
     }
 }
 
+// ============================================================================
+// Rendering (SVG)
+// ============================================================================
+
+/// Render the topic flow as a standalone SVG document.
+///
+/// `color_by` optionally colors topics by a metric, from green for zero to
+/// red for the highest value: `complexity`, `fan_in`, `fan_out` or
+/// `reasoning_actions`.
+#[wasm_bindgen]
+pub fn render_topic_flow_svg(source: &str, color_by: Option<String>) -> Result<String, JsValue> {
+    let graph = parse_and_build(source)?;
+    let mut options = render::SvgOptions::default();
+    if let Some(metric) = color_by {
+        let metric: fn(&TopicMetrics) -> usize =
+            match metric.as_str() {
+                "complexity" => |m| m.complexity,
+                "fan_in" => |m| m.fan_in,
+                "fan_out" => |m| m.fan_out,
+                "reasoning_actions" => |m| m.reasoning_actions,
+                _ => return Err(JsValue::from_str(
+                    "Invalid metric. Use 'complexity', 'fan_in', 'fan_out', or 'reasoning_actions'",
+                )),
+            };
+        options = options.heat_from_metrics(&graph.metrics(), |m| metric(m) as f64);
+    }
+    Ok(render::render_topic_flow_svg(&graph, &options))
+}
+
+// ============================================================================
+// Rendering (Mermaid)
+// ============================================================================
+
+/// Render the reference graph as a Mermaid flowchart.
+#[wasm_bindgen]
+pub fn render_mermaid(source: &str) -> Result<String, JsValue> {
+    mermaid_from_source(source).map_err(|e| JsValue::from_str(&e))
+}
+
 // ============================================================================
 // Export (JSON)
 // ============================================================================
@@ -173,6 +231,15 @@ pub fn export_graph_json(source: &str) -> Result<String, JsValue> {
         .map_err(|e| JsValue::from_str(&format!("JSON serialization error: {}", e)))
 }
 
+/// Export the graph as JSON, with `x`/`y` layout hints on every node.
+#[wasm_bindgen]
+pub fn export_graph_json_with_layout(source: &str) -> Result<String, JsValue> {
+    let graph = parse_and_build(source)?;
+    let export = export::GraphExport::from_graph_with_layout(&graph, &LayoutOptions::default());
+    serde_json::to_string_pretty(&export)
+        .map_err(|e| JsValue::from_str(&format!("JSON serialization error: {}", e)))
+}
+
 /// Compact JSON export (no pretty printing).
 #[wasm_bindgen]
 pub fn export_graph_json_compact(source: &str) -> Result<String, JsValue> {
@@ -195,12 +262,23 @@ pub fn export_graphml(source: &str) -> Result<String, JsValue> {
     Ok(render::render_graphml(&graph))
 }
 
+// ============================================================================
+// Export (DOT)
+// ============================================================================
+
+/// Export the reference graph as Graphviz DOT with default options.
+#[wasm_bindgen]
+pub fn export_dot(source: &str) -> Result<String, JsValue> {
+    let graph = parse_and_build(source)?;
+    Ok(render::render_dot(&graph, render::DotOptions::default()))
+}
+
 // ============================================================================
 // Dependencies
 // ============================================================================
 
 /// Extract all Salesforce org dependencies from AgentScript source.
-#[wasm_bindgen]
+#[wasm_bindgen(unchecked_return_type = "DependencyReport")]
 pub fn extract_dependencies(source: &str) -> Result<JsValue, JsValue> {
     let agent = crate::parse(source).map_err(|errs| JsValue::from_str(&errs.join("\n")))?;
 
@@ -249,8 +327,80 @@ pub fn graph_version() -> String {
 
 /// Parse source and build graph - common helper to reduce duplication.
 fn parse_and_build(source: &str) -> Result<RefGraph, JsValue> {
-    let agent = crate::parse(source).map_err(|errs| JsValue::from_str(&errs.join("\n")))?;
+    build(source).map_err(|e| JsValue::from_str(&e))
+}
+
+/// [`parse_and_build`] with a plain error message, so it runs natively.
+fn build(source: &str) -> Result<RefGraph, String> {
+    let agent = crate::parse(source).map_err(|errs| errs.join("\n"))?;
+    RefGraph::from_ast(&agent).map_err(|e| format!("Failed to build graph: {}", e))
+}
+
+/// The body of [`render_mermaid`].
+fn mermaid_from_source(source: &str) -> Result<String, String> {
+    Ok(render::render_mermaid(&build(source)?))
+}
 
-    RefGraph::from_ast(&agent)
-        .map_err(|e| JsValue::from_str(&format!("Failed to build graph: {}", e)))
+/// The cycles [`find_cycles`] returns.
+fn cycle_reprs(graph: &RefGraph) -> Vec<export::ValidationErrorRepr> {
+    graph
+        .find_cycles()
+        .iter()
+        .map(export::ValidationErrorRepr::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOOP: &str = r#"start_agent main:
+   description: "Route"
+   reasoning:
+      instructions: "Route"
+      actions:
+         go: @utils.transition to @topic.a
+            description: "A"
+
+topic a:
+   description: "A"
+   reasoning:
+      instructions: "A"
+      actions:
+         to_b: @utils.transition to @topic.b
+            description: "B"
+
+topic b:
+   description: "B"
+   reasoning:
+      instructions: "B"
+      actions:
+         to_a: @utils.transition to @topic.a
+            description: "A"
+"#;
+
+    #[test]
+    fn test_mermaid_from_source() {
+        let mermaid = mermaid_from_source(LOOP).unwrap();
+        assert!(mermaid.starts_with("flowchart TD\n"));
+        assert!(mermaid.contains("subgraph"));
+        assert!(mermaid.contains("|transitions_to|"), "{}", mermaid);
+
+        let error = mermaid_from_source("topic a:\n   ???\n").unwrap_err();
+        assert!(!error.is_empty());
+    }
+
+    #[test]
+    fn test_cycle_reprs() {
+        let cycles = cycle_reprs(&build(LOOP).unwrap());
+        assert_eq!(cycles.len(), 1);
+        assert_eq!(cycles[0].error_type, "cycle_detected");
+        assert!(cycles[0]
+            .message
+            .starts_with("Cycle detected in topic transitions"));
+        assert!(cycles[0].message.contains("a -> b") || cycles[0].message.contains("b -> a"));
+
+        let acyclic = LOOP.replace("to_a: @utils.transition to @topic.a", "to_a: @utils.escalate");
+        assert!(cycle_reprs(&build(&acyclic).unwrap()).is_empty());
+    }
 }
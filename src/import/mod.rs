@@ -0,0 +1,8 @@
+//! Import agents from platform-specific formats.
+//!
+//! - [`salesforce`] - GenAiPlannerBundle / GenAiPlugin / GenAiFunction
+//!   metadata, as retrieved with `sf project retrieve`
+
+pub mod salesforce;
+
+pub use salesforce::{import_from_metadata, ImportError};
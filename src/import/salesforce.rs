@@ -0,0 +1,792 @@
+//! Salesforce Agentforce metadata import.
+//!
+//! The inverse of [`export::salesforce`](crate::export::salesforce): reads a
+//! package directory (e.g. `force-app/main/default`) and synthesizes an
+//! [`AgentFile`] that can be written out with [`serialize`](crate::serialize).
+//!
+//! | Metadata | AgentScript |
+//! |----------|-------------|
+//! | `GenAiPlannerBundle` (or legacy `GenAiPlanner`) | `config`, plus a `start_agent` that routes to each topic |
+//! | `GenAiPlugin`, or a bundle's inline `localTopics` | `topic`, with plugin instructions as reasoning instructions and `scope` as system instructions |
+//! | `GenAiFunction` + `input`/`output` `schema.json` | action definition, plus a reasoning action that invokes it |
+//! | `Bot` `contextVariables` | `linked` variables sourced from `@context` |
+//! | `BotVersion` `conversationVariables` | `mutable` variables |
+//!
+//! Topics and functions referenced by the planner but not present in the
+//! directory (e.g. standard Salesforce topics) are imported as stubs whose
+//! doc comment says so. Spans in the imported AST are all `0..0`.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use busbar_sf_agentscript::import::import_from_metadata;
+//! use std::path::Path;
+//!
+//! let agent = import_from_metadata(Path::new("force-app/main/default")).unwrap();
+//! std::fs::write("agent.agent", busbar_sf_agentscript::serialize(&agent)).unwrap();
+//! ```
+
+use crate::ast::{
+    ActionDef, ActionsBlock, AgentFile, ConfigBlock, Expr, Instructions, Number, ParamDef,
+    ReasoningAction, ReasoningActionTarget, ReasoningBlock, Reference, Spanned, StartAgentBlock,
+    TopicBlock, TopicSystemOverride, Type, VariableDecl, VariableKind, VariablesBlock,
+};
+use roxmltree::{Document, Node};
+use serde_json::Value;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Errors from [`import_from_metadata`].
+#[derive(Debug, Error)]
+pub enum ImportError {
+    /// A metadata file could not be read.
+    #[error("failed to read {path}: {source}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    /// A metadata file is not well-formed XML.
+    #[error("invalid XML in {path}: {message}")]
+    Xml { path: PathBuf, message: String },
+
+    /// An input or output schema is not valid JSON.
+    #[error("invalid schema in {path}: {message}")]
+    Schema { path: PathBuf, message: String },
+
+    /// The directory contains no planner.
+    #[error("no GenAiPlannerBundle or GenAiPlanner found in {0}")]
+    NoPlanner(PathBuf),
+
+    /// The directory contains several planners; use [`import_planner`] to pick one.
+    #[error("multiple planners found ({}); specify which one to import", .0.join(", "))]
+    MultiplePlanners(Vec<String>),
+
+    /// The named planner does not exist in the directory.
+    #[error("planner '{0}' not found")]
+    PlannerNotFound(String),
+}
+
+/// Import the single planner in a package directory.
+pub fn import_from_metadata(dir: &Path) -> Result<AgentFile, ImportError> {
+    let mut planners = find_planners(dir)?;
+    match planners.len() {
+        0 => Err(ImportError::NoPlanner(dir.to_path_buf())),
+        1 => {
+            let (name, path) = planners.remove(0);
+            import_planner_file(dir, &name, &path)
+        }
+        _ => Err(ImportError::MultiplePlanners(
+            planners.into_iter().map(|(name, _)| name).collect(),
+        )),
+    }
+}
+
+/// Import a planner by developer name from a package directory.
+pub fn import_planner(dir: &Path, name: &str) -> Result<AgentFile, ImportError> {
+    let (name, path) = find_planners(dir)?
+        .into_iter()
+        .find(|(n, _)| n == name)
+        .ok_or_else(|| ImportError::PlannerNotFound(name.to_string()))?;
+    import_planner_file(dir, &name, &path)
+}
+
+/// Planner developer names and files, sorted by name.
+fn find_planners(dir: &Path) -> Result<Vec<(String, PathBuf)>, ImportError> {
+    let mut planners = Vec::new();
+
+    // genAiPlannerBundles/<Name>/<Name>.genAiPlannerBundle
+    for entry in read_dir(&dir.join("genAiPlannerBundles"))? {
+        let Some(name) = entry.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        let file = entry.join(format!("{}.genAiPlannerBundle", name));
+        if file.is_file() {
+            planners.push((name.to_string(), file));
+        }
+    }
+
+    // genAiPlanners/<Name>.genAiPlanner-meta.xml
+    for entry in read_dir(&dir.join("genAiPlanners"))? {
+        let Some(name) = entry
+            .file_name()
+            .and_then(|n| n.to_str())
+            .and_then(|n| n.strip_suffix(".genAiPlanner-meta.xml"))
+        else {
+            continue;
+        };
+        if !planners.iter().any(|(n, _)| n == name) {
+            planners.push((name.to_string(), entry));
+        }
+    }
+
+    planners.sort();
+    Ok(planners)
+}
+
+fn import_planner_file(dir: &Path, name: &str, path: &Path) -> Result<AgentFile, ImportError> {
+    let source = read(path)?;
+    let doc = parse_xml(path, &source)?;
+    let root = doc.root_element();
+
+    let mut agent = AgentFile::new();
+    let label = child_text(root, "masterLabel");
+    agent.config = Some(sp(ConfigBlock {
+        agent_name: sp(name.to_string()),
+        agent_label: label.filter(|l| l != name).map(sp),
+        description: child_text(root, "description").map(sp),
+        agent_type: None,
+        default_agent_user: None,
+        target_environment: None,
+    }));
+    agent.variables = import_variables(dir, name)?;
+
+    // Planner-level functions belong to the start_agent
+    let planner_functions: Vec<String> = children(root, "genAiFunctions")
+        .filter_map(|f| {
+            child_text(f, "genAiFunctionName").or_else(|| child_text(f, "functionName"))
+        })
+        .collect();
+    let start_actions = planner_functions
+        .iter()
+        .map(|f| import_function(dir, f))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Topics: inline localTopics, then referenced GenAiPlugins
+    for local in children(root, "localTopics") {
+        agent.topics.push(topic_from_plugin(dir, local, None)?);
+    }
+    let plugin_names = children(root, "genAiPlugins")
+        .chain(children(root, "localTopicLinks"))
+        .filter_map(|p| child_text(p, "genAiPluginName"));
+    for plugin in plugin_names {
+        if agent.topics.iter().any(|t| t.node.name.node == plugin) {
+            continue;
+        }
+        let topic = import_plugin(dir, &plugin)?;
+        agent.topics.push(topic);
+    }
+
+    let routes = agent
+        .topics
+        .iter()
+        .map(|t| {
+            let name = &t.node.name.node;
+            let description = t
+                .node
+                .description
+                .as_ref()
+                .map(|d| d.node.clone())
+                .unwrap_or_else(|| name.clone());
+            reasoning_action(
+                format!("go_{}", name),
+                ReasoningActionTarget::TransitionTo(Reference::new("topic", vec![name.clone()])),
+                description,
+            )
+        })
+        .chain(invocations(&start_actions))
+        .collect();
+
+    agent.start_agent = Some(sp(StartAgentBlock {
+        name: sp("topic_selector".to_string()),
+        description: Some(sp("Route the conversation to the right topic".to_string())),
+        system: None,
+        actions: actions_block(start_actions),
+        before_reasoning: None,
+        reasoning: Some(sp(ReasoningBlock {
+            instructions: Some(sp(Instructions::Simple(
+                "Select the topic that best matches the user's request.".to_string(),
+            ))),
+            actions: Some(sp(routes)),
+        })),
+        after_reasoning: None,
+        doc: None,
+    }));
+
+    Ok(agent)
+}
+
+/// Import a GenAiPlugin by developer name, or a stub if its file is missing.
+fn import_plugin(dir: &Path, name: &str) -> Result<Spanned<TopicBlock>, ImportError> {
+    let path = dir
+        .join("genAiPlugins")
+        .join(format!("{}.genAiPlugin-meta.xml", name));
+    if !path.is_file() {
+        return Ok(sp(TopicBlock {
+            name: sp(identifier(name)),
+            quoted_name: None,
+            description: Some(sp(name.to_string())),
+            system: None,
+            actions: None,
+            before_reasoning: None,
+            reasoning: None,
+            after_reasoning: None,
+            doc: Some(sp(format!(
+                "Imported stub: GenAiPlugin '{}' was not found in the metadata.",
+                name
+            ))),
+        }));
+    }
+    let source = read(&path)?;
+    let doc = parse_xml(&path, &source)?;
+    topic_from_plugin(dir, doc.root_element(), Some(name))
+}
+
+/// Build a topic from a GenAiPlugin (or inline localTopics) element.
+fn topic_from_plugin(
+    dir: &Path,
+    plugin: Node,
+    file_name: Option<&str>,
+) -> Result<Spanned<TopicBlock>, ImportError> {
+    let name = child_text(plugin, "developerName")
+        .or_else(|| file_name.map(str::to_string))
+        .or_else(|| child_text(plugin, "fullName"))
+        .unwrap_or_else(|| "topic".to_string());
+    let description = child_text(plugin, "description");
+
+    let mut instructions: Vec<(i64, String)> = children(plugin, "genAiPluginInstructions")
+        .filter_map(|i| {
+            let order = child_text(i, "sortOrder")
+                .and_then(|o| o.parse().ok())
+                .unwrap_or(i64::MAX);
+            child_text(i, "description").map(|d| (order, d))
+        })
+        .collect();
+    instructions.sort_by_key(|(order, _)| *order);
+    let instructions = text_instructions(instructions.into_iter().map(|(_, text)| text));
+
+    let scope = child_text(plugin, "scope").filter(|s| Some(s) != description.as_ref());
+    let system = scope
+        .and_then(|s| text_instructions(std::iter::once(s)))
+        .map(|i| {
+            sp(TopicSystemOverride {
+                instructions: Some(i),
+            })
+        });
+
+    let actions = children(plugin, "genAiFunctions")
+        .filter_map(|f| child_text(f, "functionName"))
+        .map(|f| import_function(dir, &f))
+        .collect::<Result<Vec<_>, _>>()?;
+    let reasoning_actions: Vec<_> = invocations(&actions).collect();
+
+    Ok(sp(TopicBlock {
+        name: sp(identifier(&name)),
+        quoted_name: None,
+        description: description.map(sp),
+        system,
+        actions: actions_block(actions),
+        before_reasoning: None,
+        reasoning: Some(sp(ReasoningBlock {
+            instructions,
+            actions: (!reasoning_actions.is_empty()).then(|| sp(reasoning_actions)),
+        })),
+        after_reasoning: None,
+        doc: None,
+    }))
+}
+
+/// Import a GenAiFunction and its schemas, or a stub if its file is missing.
+fn import_function(dir: &Path, name: &str) -> Result<Spanned<ActionDef>, ImportError> {
+    let function_dir = dir.join("genAiFunctions").join(name);
+    let path = function_dir.join(format!("{}.genAiFunction-meta.xml", name));
+
+    let mut action = ActionDef {
+        name: sp(identifier(name)),
+        description: None,
+        label: None,
+        require_user_confirmation: None,
+        include_in_progress_indicator: None,
+        progress_indicator_message: None,
+        inputs: None,
+        outputs: None,
+        target: Some(sp(name.to_string())),
+        doc: None,
+    };
+
+    if !path.is_file() {
+        action.doc = Some(sp(format!(
+            "Imported stub: GenAiFunction '{}' was not found in the metadata.",
+            name
+        )));
+        return Ok(sp(action));
+    }
+
+    let source = read(&path)?;
+    let doc = parse_xml(&path, &source)?;
+    let root = doc.root_element();
+
+    action.description = child_text(root, "description").map(sp);
+    action.label = child_text(root, "masterLabel")
+        .filter(|l| l != name)
+        .map(sp);
+    action.require_user_confirmation = child_bool(root, "isConfirmationRequired")
+        .filter(|c| *c)
+        .map(sp);
+    action.include_in_progress_indicator = child_bool(root, "isIncludeInProgressIndicator")
+        .filter(|c| *c)
+        .map(sp);
+    action.progress_indicator_message = child_text(root, "progressIndicatorMessage").map(sp);
+    if let Some(target) = child_text(root, "invocationTarget") {
+        let scheme = match child_text(root, "invocationTargetType").as_deref() {
+            Some("flow") => Some("flow"),
+            Some("apex") => Some("apex"),
+            Some("generatePromptResponse") => Some("prompt"),
+            Some("externalService") => Some("service"),
+            Some("standardInvocableAction") => Some("standard"),
+            _ => None,
+        };
+        action.target = Some(sp(match scheme {
+            Some(scheme) => format!("{}://{}", scheme, target),
+            None => target,
+        }));
+    }
+
+    action.inputs = import_schema(&function_dir.join("input").join("schema.json"), true)?;
+    action.outputs = import_schema(&function_dir.join("output").join("schema.json"), false)?;
+    Ok(sp(action))
+}
+
+/// Import a Lightning type schema as parameter definitions.
+#[allow(clippy::type_complexity)]
+fn import_schema(
+    path: &Path,
+    input: bool,
+) -> Result<Option<Spanned<Vec<Spanned<ParamDef>>>>, ImportError> {
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let source = read(path)?;
+    let schema: Value = serde_json::from_str(&source).map_err(|e| ImportError::Schema {
+        path: path.to_path_buf(),
+        message: e.to_string(),
+    })?;
+    let Some(properties) = schema.get("properties").and_then(Value::as_object) else {
+        return Ok(None);
+    };
+    let required: Vec<&str> = schema
+        .get("required")
+        .and_then(Value::as_array)
+        .map(|r| r.iter().filter_map(Value::as_str).collect())
+        .unwrap_or_default();
+
+    let params: Vec<_> = properties
+        .iter()
+        .map(|(name, prop)| {
+            let text = |key: &str| prop.get(key).and_then(Value::as_str).map(str::to_string);
+            let flag = |key: &str| prop.get(key).and_then(Value::as_bool);
+            let lightning = text("lightning:type");
+            let ty = schema_type(prop);
+            let complex = lightning.filter(|l| !l.starts_with("lightning__")).map(sp);
+            sp(ParamDef {
+                name: sp(name.clone()),
+                ty: sp(ty),
+                description: text("description").map(sp),
+                label: text("title").filter(|t| t != name).map(sp),
+                is_required: (input && required.contains(&name.as_str())).then(|| sp(true)),
+                filter_from_agent: (!input)
+                    .then(|| flag("copilotAction:isUsedByPlanner"))
+                    .flatten()
+                    .filter(|used| !used)
+                    .map(|_| sp(true)),
+                is_displayable: (!input)
+                    .then(|| flag("copilotAction:isDisplayable"))
+                    .flatten()
+                    .filter(|d| *d)
+                    .map(sp),
+                complex_data_type_name: complex,
+            })
+        })
+        .collect();
+
+    Ok((!params.is_empty()).then(|| sp(params)))
+}
+
+/// The AgentScript type for a schema property.
+fn schema_type(prop: &Value) -> Type {
+    if prop.get("type").and_then(Value::as_str) == Some("array") {
+        let items = prop.get("items").unwrap_or(&Value::Null);
+        return Type::List(Box::new(schema_type(items)));
+    }
+    match prop.get("lightning:type").and_then(Value::as_str) {
+        Some("lightning__numberType") | Some("lightning__doubleType") => Type::Number,
+        Some("lightning__integerType") => Type::Integer,
+        Some("lightning__booleanType") => Type::Boolean,
+        Some("lightning__dateType") => Type::Date,
+        Some("lightning__dateTimeStringType") => Type::Datetime,
+        Some("lightning__timeType") => Type::Time,
+        Some("lightning__currencyType") => Type::Currency,
+        Some("lightning__textType") | Some("lightning__richTextType") | None => Type::String,
+        Some(_) => Type::Object,
+    }
+}
+
+/// Import Bot context variables and BotVersion conversation variables.
+fn import_variables(
+    dir: &Path,
+    name: &str,
+) -> Result<Option<Spanned<VariablesBlock>>, ImportError> {
+    let bot_dir = dir.join("bots").join(name);
+    let mut variables = Vec::new();
+
+    let bot = bot_dir.join(format!("{}.bot-meta.xml", name));
+    if bot.is_file() {
+        let source = read(&bot)?;
+        let doc = parse_xml(&bot, &source)?;
+        for var in children(doc.root_element(), "contextVariables") {
+            let Some(name) = child_text(var, "developerName") else {
+                continue;
+            };
+            variables.push(sp(VariableDecl {
+                name: sp(identifier(&name)),
+                kind: VariableKind::Linked,
+                ty: sp(bot_type(var)),
+                default: None,
+                description: child_text(var, "description")
+                    .or_else(|| child_text(var, "label"))
+                    .map(sp),
+                source: Some(sp(Reference::new("context", vec![name]))),
+                doc: None,
+            }));
+        }
+    }
+
+    let mut versions = read_dir(&bot_dir)?;
+    versions.retain(|p| {
+        p.file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| n.ends_with(".botVersion-meta.xml"))
+    });
+    // Use the latest version (v1, v2, ...)
+    if let Some(version) = versions.last() {
+        let source = read(version)?;
+        let doc = parse_xml(version, &source)?;
+        for var in children(doc.root_element(), "conversationVariables") {
+            let Some(name) = child_text(var, "developerName") else {
+                continue;
+            };
+            if variables.iter().any(|v| v.node.name.node == name) {
+                continue;
+            }
+            let ty = bot_type(var);
+            let default = match &ty {
+                Type::String | Type::Id => Expr::String(String::new()),
+                Type::Number | Type::Currency => Expr::Number(Number::Int(0)),
+                Type::Boolean => Expr::Bool(false),
+                Type::List(_) => Expr::List(Vec::new()),
+                _ => Expr::None,
+            };
+            variables.push(sp(VariableDecl {
+                name: sp(identifier(&name)),
+                kind: VariableKind::Mutable,
+                ty: sp(ty),
+                default: Some(sp(default)),
+                description: child_text(var, "description")
+                    .or_else(|| child_text(var, "label"))
+                    .map(sp),
+                source: None,
+                doc: None,
+            }));
+        }
+    }
+
+    Ok((!variables.is_empty()).then(|| sp(VariablesBlock { variables })))
+}
+
+/// The AgentScript type of a Bot variable's `dataType` and `collectionType`.
+fn bot_type(var: Node) -> Type {
+    let ty = match child_text(var, "dataType").as_deref() {
+        Some("Number") => Type::Number,
+        Some("Boolean") => Type::Boolean,
+        Some("Date") => Type::Date,
+        Some("DateTime") => Type::Timestamp,
+        Some("Currency") => Type::Currency,
+        Some("Id") => Type::Id,
+        Some("Object") | Some("SObject") => Type::Object,
+        _ => Type::String,
+    };
+    if child_text(var, "collectionType").as_deref() == Some("List") {
+        Type::List(Box::new(ty))
+    } else {
+        ty
+    }
+}
+
+// ============================================================================
+// AST helpers
+// ============================================================================
+
+fn sp<T>(node: T) -> Spanned<T> {
+    Spanned::new(node, 0..0)
+}
+
+fn actions_block(actions: Vec<Spanned<ActionDef>>) -> Option<Spanned<ActionsBlock>> {
+    (!actions.is_empty()).then(|| sp(ActionsBlock { actions }))
+}
+
+/// A reasoning action invoking each action definition.
+fn invocations(
+    actions: &[Spanned<ActionDef>],
+) -> impl Iterator<Item = Spanned<ReasoningAction>> + '_ {
+    actions.iter().map(|a| {
+        let name = &a.node.name.node;
+        let description = a
+            .node
+            .description
+            .as_ref()
+            .map(|d| d.node.clone())
+            .unwrap_or_else(|| name.clone());
+        reasoning_action(
+            name.clone(),
+            ReasoningActionTarget::Action(Reference::new("actions", vec![name.clone()])),
+            description,
+        )
+    })
+}
+
+fn reasoning_action(
+    name: String,
+    target: ReasoningActionTarget,
+    description: String,
+) -> Spanned<ReasoningAction> {
+    sp(ReasoningAction {
+        name: sp(name),
+        target: sp(target),
+        description: Some(sp(description)),
+        available_when: None,
+        with_clauses: Vec::new(),
+        set_clauses: Vec::new(),
+        run_clauses: Vec::new(),
+        if_clauses: Vec::new(),
+        transition: None,
+        clause_order: Vec::new(),
+    })
+}
+
+/// Instructions from instruction texts: a simple string for a single line,
+/// otherwise a multiline block.
+fn text_instructions(texts: impl Iterator<Item = String>) -> Option<Spanned<Instructions>> {
+    let lines: Vec<String> = texts
+        .flat_map(|t| t.lines().map(|l| l.trim().to_string()).collect::<Vec<_>>())
+        .filter(|l| !l.is_empty())
+        .collect();
+    match lines.len() {
+        0 => None,
+        1 => Some(sp(Instructions::Simple(lines.into_iter().next().unwrap()))),
+        _ => Some(sp(Instructions::Static(lines.into_iter().map(sp).collect()))),
+    }
+}
+
+/// Make a metadata developer name usable as an AgentScript identifier.
+fn identifier(name: &str) -> String {
+    let mut out: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if !out.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        out.insert(0, '_');
+    }
+    out
+}
+
+// ============================================================================
+// File and XML helpers
+// ============================================================================
+
+fn read(path: &Path) -> Result<String, ImportError> {
+    fs::read_to_string(path).map_err(|source| ImportError::Io {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+/// Sorted entries of a directory, or none if it does not exist.
+fn read_dir(dir: &Path) -> Result<Vec<PathBuf>, ImportError> {
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let io_err = |source| ImportError::Io {
+        path: dir.to_path_buf(),
+        source,
+    };
+    let mut entries = fs::read_dir(dir)
+        .map_err(io_err)?
+        .map(|e| e.map(|e| e.path()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(io_err)?;
+    entries.sort();
+    Ok(entries)
+}
+
+fn parse_xml<'a>(path: &Path, source: &'a str) -> Result<Document<'a>, ImportError> {
+    Document::parse(source).map_err(|e| ImportError::Xml {
+        path: path.to_path_buf(),
+        message: e.to_string(),
+    })
+}
+
+fn children<'a, 'input>(
+    node: Node<'a, 'input>,
+    tag: &'static str,
+) -> impl Iterator<Item = Node<'a, 'input>> {
+    node.children()
+        .filter(move |c| c.is_element() && c.tag_name().name() == tag)
+}
+
+fn child_text(node: Node, tag: &'static str) -> Option<String> {
+    children(node, tag)
+        .next()
+        .and_then(|c| c.text())
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+}
+
+fn child_bool(node: Node, tag: &'static str) -> Option<bool> {
+    child_text(node, tag).map(|t| t == "true")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::salesforce::to_salesforce_metadata;
+
+    const SOURCE: &str = r#"config:
+   agent_name: "Support_Agent"
+   description: "Helps customers"
+
+start_agent selector:
+   description: "Route"
+   reasoning:
+      instructions: "Select"
+      actions:
+         go_orders: @utils.transition to @topic.orders
+            description: "Orders"
+
+topic orders:
+   description: "Order questions"
+
+   actions:
+      lookup:
+         description: "Look up an order"
+         require_user_confirmation: True
+         inputs:
+            order_id: string
+               description: "Order ID"
+               is_required: True
+         outputs:
+            status: string
+               is_displayable: True
+         target: "flow://Lookup_Order"
+
+   reasoning:
+      instructions: "Help with orders"
+"#;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "agentscript-import-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_export_import_round_trip() {
+        let dir = temp_dir("round-trip");
+        let original = crate::parse(SOURCE).unwrap();
+        to_salesforce_metadata(&original).write_to(&dir).unwrap();
+
+        let imported = import_from_metadata(&dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let config = &imported.config.as_ref().unwrap().node;
+        assert_eq!(config.agent_name.node, "Support_Agent");
+
+        let topic = &imported.topics[0].node;
+        assert_eq!(topic.name.node, "orders");
+        let action = &topic.actions.as_ref().unwrap().node.actions[0].node;
+        assert_eq!(action.target.as_ref().unwrap().node, "flow://Lookup_Order");
+        assert!(action.require_user_confirmation.as_ref().unwrap().node);
+        let input = &action.inputs.as_ref().unwrap().node[0].node;
+        assert_eq!(input.name.node, "order_id");
+        assert!(input.is_required.as_ref().unwrap().node);
+
+        let reasoning = topic.reasoning.as_ref().unwrap();
+        assert!(matches!(
+            &reasoning.node.instructions.as_ref().unwrap().node,
+            Instructions::Simple(s) if s == "Help with orders"
+        ));
+
+        // The synthesized agent serializes to valid AgentScript
+        let source = crate::serialize(&imported);
+        let reparsed = crate::parse(&source).expect("imported agent should parse");
+        assert_eq!(reparsed.topics.len(), 1);
+        assert!(source.contains("go_orders: @utils.transition to @topic.orders"));
+        assert!(source.contains("lookup: @actions.lookup"));
+    }
+
+    #[test]
+    fn test_missing_plugin_and_bot_variables() {
+        let dir = temp_dir("stubs");
+        let bundle_dir = dir.join("genAiPlannerBundles/Agent");
+        let bot_dir = dir.join("bots/Agent");
+        fs::create_dir_all(&bundle_dir).unwrap();
+        fs::create_dir_all(&bot_dir).unwrap();
+        fs::write(
+            bundle_dir.join("Agent.genAiPlannerBundle"),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<GenAiPlannerBundle xmlns="http://soap.sforce.com/2006/04/metadata">
+    <genAiPlugins>
+        <genAiPluginName>GeneralFAQ</genAiPluginName>
+    </genAiPlugins>
+    <masterLabel>Agent</masterLabel>
+</GenAiPlannerBundle>
+"#,
+        )
+        .unwrap();
+        fs::write(
+            bot_dir.join("v1.botVersion-meta.xml"),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<BotVersion xmlns="http://soap.sforce.com/2006/04/metadata">
+    <conversationVariables>
+        <dataType>Boolean</dataType>
+        <developerName>verified</developerName>
+        <label>Verified</label>
+    </conversationVariables>
+</BotVersion>
+"#,
+        )
+        .unwrap();
+
+        let imported = import_from_metadata(&dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let topic = &imported.topics[0].node;
+        assert_eq!(topic.name.node, "GeneralFAQ");
+        assert!(topic.doc.as_ref().unwrap().node.contains("not found"));
+
+        let var = &imported.variables.as_ref().unwrap().node.variables[0].node;
+        assert_eq!(var.name.node, "verified");
+        assert_eq!(var.ty.node, Type::Boolean);
+        assert_eq!(var.default.as_ref().unwrap().node, Expr::Bool(false));
+
+        assert!(crate::parse(&crate::serialize(&imported)).is_ok());
+    }
+
+    #[test]
+    fn test_no_planner() {
+        let dir = temp_dir("empty");
+        assert!(matches!(import_from_metadata(&dir), Err(ImportError::NoPlanner(_))));
+    }
+}
@@ -0,0 +1,348 @@
+//! JUnit XML reports of diagnostics.
+//!
+//! CI servers such as Jenkins and GitLab show JUnit XML test results
+//! natively. [`JunitReport`] turns the diagnostics of checked files into
+//! such a report, so agent validation shows up next to regular test suites:
+//! a file, or a rule on a file, is a test case that fails when it has
+//! errors.
+//!
+//! # Example
+//!
+//! ```rust
+//! use busbar_sf_agentscript::junit::{JunitGrouping, JunitReport};
+//! use busbar_sf_agentscript::{parse, validate_ast};
+//!
+//! let source = "topic main:\n   description: \"Main\"\n   reasoning:\n      instructions: ->\n         | Hi {!@variables.name.first}\n";
+//! let diagnostics = validate_ast(&parse(source).unwrap());
+//!
+//! let mut report = JunitReport::new("agentscript");
+//! report.add_file("main.agent", source, diagnostics);
+//! let xml = report.to_xml(JunitGrouping::File);
+//! assert!(xml.contains(r#"<testcase name="main.agent" classname="agentscript">"#));
+//! assert!(xml.contains("<failure"));
+//! ```
+
+use crate::text_pos::{Encoding, LineIndex};
+use crate::validation::{SemanticError, Severity};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// What each test case in a report stands for.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum JunitGrouping {
+    /// One test case per file, in a single test suite.
+    #[default]
+    File,
+    /// One test suite per file, with a test case per rule that reported a
+    /// diagnostic. A file without diagnostics has a single passing `check`
+    /// test case.
+    Rule,
+}
+
+/// A JUnit XML report built up file by file.
+#[derive(Debug, Clone)]
+pub struct JunitReport<'src> {
+    name: String,
+    deny_warnings: bool,
+    files: Vec<CheckedFile<'src>>,
+}
+
+#[derive(Debug, Clone)]
+struct CheckedFile<'src> {
+    path: String,
+    index: LineIndex<'src>,
+    diagnostics: Vec<SemanticError>,
+}
+
+impl<'src> JunitReport<'src> {
+    /// Create an empty report. `name` names the test suite, and is the
+    /// class name of every test case.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            deny_warnings: false,
+            files: Vec::new(),
+        }
+    }
+
+    /// Fail test cases on warnings as well as errors.
+    pub fn deny_warnings(mut self, deny: bool) -> Self {
+        self.deny_warnings = deny;
+        self
+    }
+
+    /// Add a checked file with all of its diagnostics, including parse
+    /// errors converted with [`SemanticError::from`].
+    pub fn add_file(
+        &mut self,
+        path: impl Into<String>,
+        source: &'src str,
+        diagnostics: impl IntoIterator<Item = SemanticError>,
+    ) {
+        self.files.push(CheckedFile {
+            path: path.into(),
+            index: LineIndex::new(source),
+            diagnostics: diagnostics.into_iter().collect(),
+        });
+    }
+
+    /// Render the report.
+    pub fn to_xml(&self, grouping: JunitGrouping) -> String {
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        match grouping {
+            JunitGrouping::File => {
+                let cases: Vec<TestCase> = self
+                    .files
+                    .iter()
+                    .map(|file| TestCase {
+                        name: &file.path,
+                        file,
+                        diagnostics: file.diagnostics.iter().collect(),
+                    })
+                    .collect();
+                let failures = self.count_failures(&cases);
+                writeln!(
+                    xml,
+                    "<testsuites name=\"{}\" tests=\"{}\" failures=\"{}\">",
+                    escape_xml(&self.name),
+                    cases.len(),
+                    failures
+                )
+                .unwrap();
+                self.write_suite(&mut xml, &self.name, &cases);
+            }
+            JunitGrouping::Rule => {
+                let suites: Vec<(&CheckedFile, Vec<TestCase>)> = self
+                    .files
+                    .iter()
+                    .map(|file| (file, rule_cases(file)))
+                    .collect();
+                let tests: usize = suites.iter().map(|(_, cases)| cases.len()).sum();
+                let failures: usize = suites
+                    .iter()
+                    .map(|(_, cases)| self.count_failures(cases))
+                    .sum();
+                writeln!(
+                    xml,
+                    "<testsuites name=\"{}\" tests=\"{}\" failures=\"{}\">",
+                    escape_xml(&self.name),
+                    tests,
+                    failures
+                )
+                .unwrap();
+                for (file, cases) in &suites {
+                    self.write_suite(&mut xml, &file.path, cases);
+                }
+            }
+        }
+        xml.push_str("</testsuites>\n");
+        xml
+    }
+
+    fn fails(&self, diagnostic: &SemanticError) -> bool {
+        diagnostic.severity == Severity::Error || self.deny_warnings
+    }
+
+    fn count_failures(&self, cases: &[TestCase]) -> usize {
+        cases
+            .iter()
+            .filter(|case| case.diagnostics.iter().any(|d| self.fails(d)))
+            .count()
+    }
+
+    fn write_suite(&self, xml: &mut String, name: &str, cases: &[TestCase]) {
+        writeln!(
+            xml,
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"0\" skipped=\"0\">",
+            escape_xml(name),
+            cases.len(),
+            self.count_failures(cases)
+        )
+        .unwrap();
+        for case in cases {
+            write!(
+                xml,
+                "    <testcase name=\"{}\" classname=\"{}\"",
+                escape_xml(case.name),
+                escape_xml(&self.name)
+            )
+            .unwrap();
+            if case.diagnostics.is_empty() {
+                xml.push_str("/>\n");
+                continue;
+            }
+            xml.push_str(">\n");
+
+            let (failures, others): (Vec<&SemanticError>, Vec<&SemanticError>) =
+                case.diagnostics.iter().partition(|d| self.fails(d));
+            if let Some(first) = failures.first() {
+                let message = if failures.len() == 1 {
+                    first.message.clone()
+                } else {
+                    format!("{} (and {} more)", first.message, failures.len() - 1)
+                };
+                writeln!(
+                    xml,
+                    "      <failure message=\"{}\" type=\"{}\">{}</failure>",
+                    escape_xml(&message),
+                    first.code.unwrap_or("error"),
+                    escape_xml(&case.file.describe(&failures))
+                )
+                .unwrap();
+            }
+            if !others.is_empty() {
+                writeln!(
+                    xml,
+                    "      <system-out>{}</system-out>",
+                    escape_xml(&case.file.describe(&others))
+                )
+                .unwrap();
+            }
+            xml.push_str("    </testcase>\n");
+        }
+        xml.push_str("  </testsuite>\n");
+    }
+}
+
+/// A test case and the diagnostics that decide it.
+struct TestCase<'a, 'src> {
+    name: &'a str,
+    file: &'a CheckedFile<'src>,
+    diagnostics: Vec<&'a SemanticError>,
+}
+
+/// A test case per rule that reported on `file`, in rule order.
+fn rule_cases<'a, 'src>(file: &'a CheckedFile<'src>) -> Vec<TestCase<'a, 'src>> {
+    if file.diagnostics.is_empty() {
+        return vec![TestCase {
+            name: "check",
+            file,
+            diagnostics: Vec::new(),
+        }];
+    }
+    let mut rules: BTreeMap<&str, Vec<&SemanticError>> = BTreeMap::new();
+    for diagnostic in &file.diagnostics {
+        rules
+            .entry(diagnostic.code.unwrap_or("other"))
+            .or_default()
+            .push(diagnostic);
+    }
+    rules
+        .into_iter()
+        .map(|(name, diagnostics)| TestCase {
+            name,
+            file,
+            diagnostics,
+        })
+        .collect()
+}
+
+impl CheckedFile<'_> {
+    /// One `path:line:column: severity[code]: message` line per diagnostic,
+    /// with one-based positions, followed by its hint if any.
+    fn describe(&self, diagnostics: &[&SemanticError]) -> String {
+        let mut text = String::new();
+        for diagnostic in diagnostics {
+            text.push_str(&self.path);
+            if let Some(span) = &diagnostic.span {
+                let location = self.index.location(span.start, Encoding::Utf32);
+                write!(text, ":{}:{}", location.line + 1, location.column + 1).unwrap();
+            }
+            let severity = match diagnostic.severity {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+            };
+            write!(text, ": {}", severity).unwrap();
+            if let Some(code) = diagnostic.code {
+                write!(text, "[{}]", code).unwrap();
+            }
+            writeln!(text, ": {}", diagnostic.message).unwrap();
+            if let Some(hint) = &diagnostic.hint {
+                writeln!(text, "  hint: {}", hint).unwrap();
+            }
+        }
+        text
+    }
+}
+
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diagnostic(severity: Severity, code: &'static str, span: usize) -> SemanticError {
+        SemanticError {
+            message: format!("{} <here>", code),
+            span: Some(span..span + 1),
+            severity,
+            hint: None,
+            code: Some(code),
+            related: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_junit_by_file() {
+        let mut report = JunitReport::new("agentscript");
+        report.add_file("ok.agent", "", []);
+        report.add_file(
+            "bad.agent",
+            "a\nbc\n",
+            [
+                diagnostic(Severity::Warning, "unused-variable", 0),
+                diagnostic(Severity::Error, "undefined-reference", 3),
+            ],
+        );
+        let xml = report.to_xml(JunitGrouping::File);
+        assert_eq!(
+            xml,
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites name="agentscript" tests="2" failures="1">
+  <testsuite name="agentscript" tests="2" failures="1" errors="0" skipped="0">
+    <testcase name="ok.agent" classname="agentscript"/>
+    <testcase name="bad.agent" classname="agentscript">
+      <failure message="undefined-reference &lt;here&gt;" type="undefined-reference">bad.agent:2:2: error[undefined-reference]: undefined-reference &lt;here&gt;
+</failure>
+      <system-out>bad.agent:1:1: warning[unused-variable]: unused-variable &lt;here&gt;
+</system-out>
+    </testcase>
+  </testsuite>
+</testsuites>
+"#
+        );
+
+        let xml = report.deny_warnings(true).to_xml(JunitGrouping::File);
+        assert!(xml.contains("message=\"unused-variable &lt;here&gt; (and 1 more)\""));
+    }
+
+    #[test]
+    fn test_junit_by_rule() {
+        let mut report = JunitReport::new("agentscript");
+        report.add_file("ok.agent", "", []);
+        report.add_file(
+            "bad.agent",
+            "abc",
+            [
+                diagnostic(Severity::Warning, "unused-variable", 0),
+                diagnostic(Severity::Error, "undefined-reference", 1),
+                diagnostic(Severity::Error, "undefined-reference", 2),
+            ],
+        );
+        let xml = report.to_xml(JunitGrouping::Rule);
+        assert!(xml.contains("<testsuites name=\"agentscript\" tests=\"3\" failures=\"1\">"));
+        assert!(xml.contains("<testsuite name=\"ok.agent\" tests=\"1\" failures=\"0\""));
+        assert!(xml.contains("<testcase name=\"check\" classname=\"agentscript\"/>"));
+        assert!(xml.contains("<testsuite name=\"bad.agent\" tests=\"2\" failures=\"1\""));
+        assert!(xml.contains("message=\"undefined-reference &lt;here&gt; (and 1 more)\""));
+        assert!(xml.contains(
+            "<testcase name=\"unused-variable\" classname=\"agentscript\">\n      <system-out>"
+        ));
+    }
+}
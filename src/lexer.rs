@@ -40,6 +40,9 @@
 //! | Literals | `"text"`, `42`, `True`, `False`, `None` |
 //! | Punctuation | `:`, `.`, `@`, `\|`, `->` |
 //! | Indentation | `INDENT`, `DEDENT`, `Newline` |
+//!
+//! The full keyword list, with categories, is available from
+//! [`keyword_table()`].
 
 use chumsky::prelude::*;
 
@@ -107,6 +110,7 @@ pub enum Token<'src> {
 
     // Statement keywords
     If,
+    Elif,
     Else,
     Run,
     With,
@@ -123,19 +127,22 @@ pub enum Token<'src> {
     None,
 
     // Operators
-    Eq,     // ==
-    Ne,     // !=
-    Lt,     // <
-    Gt,     // >
-    Le,     // <=
-    Ge,     // >=
-    Assign, // =
-    Is,     // is
-    Not,    // not
-    And,    // and
-    Or,     // or
-    Plus,   // +
-    Minus,  // -
+    Eq,      // ==
+    Ne,      // !=
+    Lt,      // <
+    Gt,      // >
+    Le,      // <=
+    Ge,      // >=
+    Assign,  // =
+    Is,      // is
+    Not,     // not
+    And,     // and
+    Or,      // or
+    Plus,    // +
+    Minus,   // -
+    Star,    // *
+    Slash,   // /
+    Percent, // %
 
     // Punctuation
     Colon,        // :
@@ -153,17 +160,13 @@ pub enum Token<'src> {
     LBrace,       // {
     RBrace,       // }
     ExclBrace,    // {!
-    DoubleLBrace, // {{
-    DoubleBrace,  // }}
+    DoubleLBrace, // {{ (`}}` is two RBrace, closing nested object literals)
     Ellipsis,     // ...
 
     // Additional text punctuation (appears in instruction content)
-    Slash,       // /
     Question,    // ?
     Exclamation, // !
     Dollar,      // $
-    Percent,     // %
-    Star,        // *
     Ampersand,   // &
     Semicolon,   // ;
     Backtick,    // `
@@ -182,8 +185,14 @@ pub enum Token<'src> {
     // String literal (content without quotes)
     StringLit(&'src str),
 
-    // Number literal
-    NumberLit(f64),
+    // Number literal (text as written)
+    NumberLit(&'src str),
+
+    // Date literal (text as written, e.g. `date(2024-01-31)`)
+    DateLit(&'src str),
+
+    // Currency literal (text as written, e.g. `$199.99`)
+    CurrencyLit(&'src str),
 
     // Comment (text without #)
     Comment(&'src str),
@@ -198,66 +207,10 @@ pub enum Token<'src> {
 
 impl std::fmt::Display for Token<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(text) = self.keyword_text() {
+            return f.write_str(text);
+        }
         match self {
-            Token::Config => write!(f, "config"),
-            Token::Variables => write!(f, "variables"),
-            Token::System => write!(f, "system"),
-            Token::StartAgent => write!(f, "start_agent"),
-            Token::Topic => write!(f, "topic"),
-            Token::Actions => write!(f, "actions"),
-            Token::Inputs => write!(f, "inputs"),
-            Token::Outputs => write!(f, "outputs"),
-            Token::Target => write!(f, "target"),
-            Token::Reasoning => write!(f, "reasoning"),
-            Token::Instructions => write!(f, "instructions"),
-            Token::BeforeReasoning => write!(f, "before_reasoning"),
-            Token::AfterReasoning => write!(f, "after_reasoning"),
-            Token::Messages => write!(f, "messages"),
-            Token::Welcome => write!(f, "welcome"),
-            Token::Error => write!(f, "error"),
-            Token::Connection => write!(f, "connection"),
-            Token::Connections => write!(f, "connections"),
-            Token::Knowledge => write!(f, "knowledge"),
-            Token::Language => write!(f, "language"),
-            Token::Mutable => write!(f, "mutable"),
-            Token::Linked => write!(f, "linked"),
-            Token::Description => write!(f, "description"),
-            Token::Source => write!(f, "source"),
-            Token::Label => write!(f, "label"),
-            Token::IsRequired => write!(f, "is_required"),
-            Token::IsDisplayable => write!(f, "is_displayable"),
-            Token::IsUsedByPlanner => write!(f, "is_used_by_planner"),
-            Token::ComplexDataTypeName => write!(f, "complex_data_type_name"),
-            Token::FilterFromAgent => write!(f, "filter_from_agent"),
-            Token::RequireUserConfirmation => write!(f, "require_user_confirmation"),
-            Token::IncludeInProgressIndicator => write!(f, "include_in_progress_indicator"),
-            Token::ProgressIndicatorMessage => write!(f, "progress_indicator_message"),
-            Token::String => write!(f, "string"),
-            Token::Number => write!(f, "number"),
-            Token::Boolean => write!(f, "boolean"),
-            Token::Object => write!(f, "object"),
-            Token::List => write!(f, "list"),
-            Token::Date => write!(f, "date"),
-            Token::Timestamp => write!(f, "timestamp"),
-            Token::Currency => write!(f, "currency"),
-            Token::Id => write!(f, "id"),
-            Token::Datetime => write!(f, "datetime"),
-            Token::Time => write!(f, "time"),
-            Token::Integer => write!(f, "integer"),
-            Token::Long => write!(f, "long"),
-            Token::If => write!(f, "if"),
-            Token::Else => write!(f, "else"),
-            Token::Run => write!(f, "run"),
-            Token::With => write!(f, "with"),
-            Token::Set => write!(f, "set"),
-            Token::To => write!(f, "to"),
-            Token::As => write!(f, "as"),
-            Token::Transition => write!(f, "transition"),
-            Token::Available => write!(f, "available"),
-            Token::When => write!(f, "when"),
-            Token::True => write!(f, "True"),
-            Token::False => write!(f, "False"),
-            Token::None => write!(f, "None"),
             Token::Eq => write!(f, "=="),
             Token::Ne => write!(f, "!="),
             Token::Lt => write!(f, "<"),
@@ -265,12 +218,11 @@ impl std::fmt::Display for Token<'_> {
             Token::Le => write!(f, "<="),
             Token::Ge => write!(f, ">="),
             Token::Assign => write!(f, "="),
-            Token::Is => write!(f, "is"),
-            Token::Not => write!(f, "not"),
-            Token::And => write!(f, "and"),
-            Token::Or => write!(f, "or"),
             Token::Plus => write!(f, "+"),
             Token::Minus => write!(f, "-"),
+            Token::Star => write!(f, "*"),
+            Token::Slash => write!(f, "/"),
+            Token::Percent => write!(f, "%"),
             Token::Colon => write!(f, ":"),
             Token::Dot => write!(f, "."),
             Token::Comma => write!(f, ","),
@@ -287,14 +239,10 @@ impl std::fmt::Display for Token<'_> {
             Token::RBrace => write!(f, "}}"),
             Token::ExclBrace => write!(f, "{{!"),
             Token::DoubleLBrace => write!(f, "{{{{"),
-            Token::DoubleBrace => write!(f, "}}}}"),
             Token::Ellipsis => write!(f, "..."),
-            Token::Slash => write!(f, "/"),
             Token::Question => write!(f, "?"),
             Token::Exclamation => write!(f, "!"),
             Token::Dollar => write!(f, "$"),
-            Token::Percent => write!(f, "%"),
-            Token::Star => write!(f, "*"),
             Token::Ampersand => write!(f, "&"),
             Token::Semicolon => write!(f, ";"),
             Token::Backtick => write!(f, "`"),
@@ -306,15 +254,186 @@ impl std::fmt::Display for Token<'_> {
             Token::UnicodeText(s) => write!(f, "{}", s),
             Token::Ident(s) => write!(f, "{}", s),
             Token::StringLit(s) => write!(f, "\"{}\"", s),
-            Token::NumberLit(n) => write!(f, "{}", n),
+            Token::NumberLit(n) | Token::DateLit(n) | Token::CurrencyLit(n) => write!(f, "{}", n),
             Token::Comment(s) => write!(f, "# {}", s),
             Token::Newline => write!(f, "\\n"),
             Token::Indent => write!(f, "INDENT"),
             Token::Dedent => write!(f, "DEDENT"),
+            _ => unreachable!("keywords are written above"),
         }
     }
 }
 
+/// Grammatical role of a keyword, as listed in [`keyword_table()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeywordCategory {
+    /// Block and sub-block names (`config`, `topic`, `reasoning`, ...)
+    Block,
+    /// Property names inside variables, parameters and action definitions
+    Property,
+    /// Type names (`string`, `number`, ...)
+    Type,
+    /// Statement keywords (`if`, `run`, `set`, ...)
+    Statement,
+    /// Literal keywords (`True`, `False`, `None`)
+    Literal,
+    /// Word operators (`and`, `or`, `not`, `is`)
+    Operator,
+}
+
+impl KeywordCategory {
+    /// Name of the category, e.g. `"type"`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KeywordCategory::Block => "block",
+            KeywordCategory::Property => "property",
+            KeywordCategory::Type => "type",
+            KeywordCategory::Statement => "statement",
+            KeywordCategory::Literal => "literal",
+            KeywordCategory::Operator => "operator",
+        }
+    }
+}
+
+/// A reserved word of the language.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keyword {
+    /// Source spelling, e.g. `"start_agent"`.
+    pub text: &'static str,
+    /// The token the lexer produces for it.
+    pub token: Token<'static>,
+    /// What the keyword is used for.
+    pub category: KeywordCategory,
+}
+
+/// Declares the keyword table, and generates the lexer lookup and
+/// [`Token::keyword_text`] from it so the three can't drift apart.
+macro_rules! keywords {
+    ($($category:ident { $($text:literal => $variant:ident,)* })*) => {
+        const KEYWORDS: &[Keyword] = &[
+            $($(Keyword {
+                text: $text,
+                token: Token::$variant,
+                category: KeywordCategory::$category,
+            },)*)*
+        ];
+
+        /// Look up the keyword token for an identifier.
+        fn keyword_token(s: &str) -> Option<Token<'static>> {
+            match s {
+                $($($text => Some(Token::$variant),)*)*
+                _ => None,
+            }
+        }
+
+        impl Token<'_> {
+            /// Source spelling if this token is a keyword.
+            pub fn keyword_text(&self) -> Option<&'static str> {
+                match self {
+                    $($(Token::$variant => Some($text),)*)*
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+keywords! {
+    Block {
+        "config" => Config,
+        "variables" => Variables,
+        "system" => System,
+        "start_agent" => StartAgent,
+        "topic" => Topic,
+        "actions" => Actions,
+        "inputs" => Inputs,
+        "outputs" => Outputs,
+        "target" => Target,
+        "reasoning" => Reasoning,
+        "instructions" => Instructions,
+        "before_reasoning" => BeforeReasoning,
+        "after_reasoning" => AfterReasoning,
+        "messages" => Messages,
+        "welcome" => Welcome,
+        "error" => Error,
+        "connection" => Connection,
+        "connections" => Connections,
+        "knowledge" => Knowledge,
+        "language" => Language,
+    }
+    Property {
+        "mutable" => Mutable,
+        "linked" => Linked,
+        "description" => Description,
+        "source" => Source,
+        "label" => Label,
+        "is_required" => IsRequired,
+        "is_displayable" => IsDisplayable,
+        "is_used_by_planner" => IsUsedByPlanner,
+        "complex_data_type_name" => ComplexDataTypeName,
+        "filter_from_agent" => FilterFromAgent,
+        "require_user_confirmation" => RequireUserConfirmation,
+        "include_in_progress_indicator" => IncludeInProgressIndicator,
+        "progress_indicator_message" => ProgressIndicatorMessage,
+    }
+    Type {
+        "string" => String,
+        "number" => Number,
+        "boolean" => Boolean,
+        "object" => Object,
+        "list" => List,
+        "date" => Date,
+        "timestamp" => Timestamp,
+        "currency" => Currency,
+        "id" => Id,
+        "datetime" => Datetime,
+        "time" => Time,
+        "integer" => Integer,
+        "long" => Long,
+    }
+    Statement {
+        "if" => If,
+        "elif" => Elif,
+        "else" => Else,
+        "run" => Run,
+        "with" => With,
+        "set" => Set,
+        "to" => To,
+        "as" => As,
+        "transition" => Transition,
+        "available" => Available,
+        "when" => When,
+    }
+    Literal {
+        "True" => True,
+        "False" => False,
+        "None" => None,
+    }
+    Operator {
+        "is" => Is,
+        "not" => Not,
+        "and" => And,
+        "or" => Or,
+    }
+}
+
+/// Every keyword the lexer recognizes, grouped by category.
+///
+/// ```rust
+/// use busbar_sf_agentscript::lexer::{keyword_table, KeywordCategory};
+///
+/// let types: Vec<_> = keyword_table()
+///     .iter()
+///     .filter(|k| k.category == KeywordCategory::Type)
+///     .map(|k| k.text)
+///     .collect();
+/// assert!(types.contains(&"string"));
+/// ```
+pub fn keyword_table() -> &'static [Keyword] {
+    KEYWORDS
+}
+
 /// Span type for tokens.
 pub type Span = SimpleSpan<usize>;
 
@@ -338,7 +457,21 @@ pub fn lexer<'src>(
     let number = text::int(10)
         .then(just('.').then(text::digits(10)).or_not())
         .to_slice()
-        .map(|s: &str| Token::NumberLit(s.parse().unwrap()));
+        .map(Token::NumberLit);
+
+    // Date literals: `date(2024-01-31)`. The parser checks the date is real.
+    let date_lit = just("date(")
+        .then(text::digits(10))
+        .then(just('-'))
+        .then(text::digits(10))
+        .then(just('-'))
+        .then(text::digits(10))
+        .then(just(')'))
+        .to_slice()
+        .map(Token::DateLit);
+
+    // Currency literals: `$199.99`
+    let currency_lit = just('$').then(number).to_slice().map(Token::CurrencyLit);
 
     // Multi-character operators (must come before single char versions)
     let multi_char_ops = choice((
@@ -352,7 +485,6 @@ pub fn lexer<'src>(
         just(">=").to(Token::Ge),
         just("{!").to(Token::ExclBrace),
         just("{{").to(Token::DoubleLBrace),
-        just("}}").to(Token::DoubleBrace),
     ));
 
     // Single character operators and punctuation
@@ -362,6 +494,9 @@ pub fn lexer<'src>(
         just('=').to(Token::Assign),
         just('+').to(Token::Plus),
         just('-').to(Token::Minus),
+        just('*').to(Token::Star),
+        just('/').to(Token::Slash),
+        just('%').to(Token::Percent),
         just(':').to(Token::Colon),
         just('.').to(Token::Dot),
         just(',').to(Token::Comma),
@@ -377,12 +512,9 @@ pub fn lexer<'src>(
 
     // Additional punctuation that appears in instruction content
     let text_punctuation = choice((
-        just('/').to(Token::Slash),
         just('?').to(Token::Question),
         just('!').to(Token::Exclamation),
         just('$').to(Token::Dollar),
-        just('%').to(Token::Percent),
-        just('*').to(Token::Star),
         just('&').to(Token::Ampersand),
         just(';').to(Token::Semicolon),
         just('`').to(Token::Backtick),
@@ -405,79 +537,8 @@ pub fn lexer<'src>(
     // Lex identifiers and keywords in a single pass: parse as ident, then do
     // a constant-time match to check if it's a keyword. This replaces 63
     // individual text::keyword() calls that caused O(keywords) backtracking
-    // per identifier token.
-    let ident_or_keyword = text::ident().map(|s: &str| match s {
-        // Block keywords
-        "config" => Token::Config,
-        "variables" => Token::Variables,
-        "system" => Token::System,
-        "start_agent" => Token::StartAgent,
-        "topic" => Token::Topic,
-        "actions" => Token::Actions,
-        "inputs" => Token::Inputs,
-        "outputs" => Token::Outputs,
-        "target" => Token::Target,
-        "reasoning" => Token::Reasoning,
-        "instructions" => Token::Instructions,
-        "before_reasoning" => Token::BeforeReasoning,
-        "after_reasoning" => Token::AfterReasoning,
-        "messages" => Token::Messages,
-        // More keywords
-        "welcome" => Token::Welcome,
-        "error" => Token::Error,
-        "connection" => Token::Connection,
-        "connections" => Token::Connections,
-        "knowledge" => Token::Knowledge,
-        "language" => Token::Language,
-        "mutable" => Token::Mutable,
-        "linked" => Token::Linked,
-        "description" => Token::Description,
-        "source" => Token::Source,
-        "label" => Token::Label,
-        "is_required" => Token::IsRequired,
-        "is_displayable" => Token::IsDisplayable,
-        "is_used_by_planner" => Token::IsUsedByPlanner,
-        "complex_data_type_name" => Token::ComplexDataTypeName,
-        "filter_from_agent" => Token::FilterFromAgent,
-        "require_user_confirmation" => Token::RequireUserConfirmation,
-        "include_in_progress_indicator" => Token::IncludeInProgressIndicator,
-        "progress_indicator_message" => Token::ProgressIndicatorMessage,
-        // Type keywords
-        "string" => Token::String,
-        "number" => Token::Number,
-        "boolean" => Token::Boolean,
-        "object" => Token::Object,
-        "list" => Token::List,
-        "date" => Token::Date,
-        "timestamp" => Token::Timestamp,
-        "currency" => Token::Currency,
-        "datetime" => Token::Datetime,
-        "time" => Token::Time,
-        "integer" => Token::Integer,
-        "long" => Token::Long,
-        "id" => Token::Id,
-        // Statement keywords
-        "if" => Token::If,
-        "else" => Token::Else,
-        "run" => Token::Run,
-        "with" => Token::With,
-        "set" => Token::Set,
-        "to" => Token::To,
-        "as" => Token::As,
-        "transition" => Token::Transition,
-        "available" => Token::Available,
-        "when" => Token::When,
-        // Literal and operator keywords
-        "True" => Token::True,
-        "False" => Token::False,
-        "None" => Token::None,
-        "is" => Token::Is,
-        "not" => Token::Not,
-        "and" => Token::And,
-        "or" => Token::Or,
-        // Not a keyword — regular identifier
-        _ => Token::Ident(s),
-    });
+    // per identifier token. The match is generated from the keyword table.
+    let ident_or_keyword = text::ident().map(|s: &str| keyword_token(s).unwrap_or(Token::Ident(s)));
 
     // Newline
     let newline = just('\n').to(Token::Newline);
@@ -486,6 +547,8 @@ pub fn lexer<'src>(
     let token = choice((
         comment,
         string_lit,
+        date_lit,
+        currency_lit,
         number,
         multi_char_ops,
         single_char_ops,
@@ -512,6 +575,10 @@ pub fn lexer<'src>(
 /// - INDENT is emitted when going to a deeper level
 /// - DEDENT is emitted when returning to a shallower level
 /// - Indent levels are tracked on a stack
+///
+/// A line ending in `= [` or `= {` starts a multi-line list or object
+/// literal: newlines up to its closing bracket are dropped, so the literal's
+/// lines don't affect indentation.
 pub fn add_indentation_tokens<'src>(
     source: &'src str,
     tokens: Vec<Spanned<Token<'src>>>,
@@ -539,11 +606,35 @@ pub fn add_indentation_tokens<'src>(
         }
     };
 
+    // Open brackets of the multi-line literal being joined
+    let mut literal_depth = 0usize;
+
     let mut i = 0;
     while i < tokens.len() {
         let (tok, span) = &tokens[i];
 
-        if matches!(tok, Token::Newline) {
+        if literal_depth > 0 {
+            match tok {
+                Token::LParen | Token::LBracket | Token::LBrace | Token::ExclBrace => {
+                    literal_depth += 1
+                }
+                Token::DoubleLBrace => literal_depth += 2,
+                Token::RParen | Token::RBracket | Token::RBrace => literal_depth -= 1,
+                _ => {}
+            }
+            if !matches!(tok, Token::Newline) {
+                result.push((tok.clone(), *span));
+            }
+            i += 1;
+        } else if matches!(tok, Token::LBracket | Token::LBrace)
+            && i > 0
+            && matches!(tokens[i - 1].0, Token::Assign)
+            && matches!(tokens.get(i + 1), Some((Token::Newline, _)))
+        {
+            literal_depth = 1;
+            result.push((tok.clone(), *span));
+            i += 1;
+        } else if matches!(tok, Token::Newline) {
             result.push((tok.clone(), *span));
 
             // Look at next non-comment, non-newline token to determine indentation
@@ -616,6 +707,19 @@ pub fn lex_with_indentation<'src>(
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_keyword_table_round_trips() {
+        for keyword in keyword_table() {
+            let tokens = lexer().parse(keyword.text).into_result().unwrap();
+            assert_eq!(tokens.len(), 1, "{}", keyword.text);
+            assert_eq!(tokens[0].0, keyword.token);
+            assert_eq!(keyword.token.keyword_text(), Some(keyword.text));
+            assert_eq!(keyword.token.to_string(), keyword.text);
+        }
+        assert_eq!(Token::Ident("foo").keyword_text(), None);
+        assert_eq!(keyword_token("foo"), None);
+    }
+
     #[test]
     fn test_basic_tokens() {
         let input = "config: agent_name";
@@ -653,7 +757,7 @@ mod tests {
 
     #[test]
     fn test_operators() {
-        let input = "== != < > <= >= = + -";
+        let input = "== != < > <= >= = + - * / %";
         let result = lexer().parse(input).into_result();
         assert!(result.is_ok());
         let tokens: Vec<_> = result.unwrap().into_iter().map(|(t, _)| t).collect();
@@ -669,6 +773,9 @@ mod tests {
                 Token::Assign,
                 Token::Plus,
                 Token::Minus,
+                Token::Star,
+                Token::Slash,
+                Token::Percent,
             ]
         );
     }
@@ -708,9 +815,27 @@ mod tests {
         assert_eq!(
             tokens,
             vec![
-                Token::NumberLit(42.0),
-                Token::NumberLit(3.15),
-                Token::NumberLit(0.0),
+                Token::NumberLit("42"),
+                Token::NumberLit("3.15"),
+                Token::NumberLit("0"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_date_and_currency_literals() {
+        let input = "date(2024-01-31) $199.99 $5 date x";
+        let result = lexer().parse(input).into_result();
+        assert!(result.is_ok());
+        let tokens: Vec<_> = result.unwrap().into_iter().map(|(t, _)| t).collect();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::DateLit("date(2024-01-31)"),
+                Token::CurrencyLit("$199.99"),
+                Token::CurrencyLit("$5"),
+                Token::Date,
+                Token::Ident("x"),
             ]
         );
     }
@@ -775,4 +900,24 @@ topic main:
         assert_eq!(indents, 2, "Should have 2 INDENTs");
         assert_eq!(dedents, 2, "Should have 2 DEDENTs");
     }
+    #[test]
+    fn test_multiline_literal_joins_lines() {
+        let input = "variables:\n   tags: mutable list[object] = [\n      {name: \"x\"},\n         {deep: {a: 1}}\n   ]\n   next: mutable string\n";
+        let tokens: Vec<_> = lex_with_indentation(input)
+            .unwrap()
+            .into_iter()
+            .map(|(t, _)| t)
+            .collect();
+        let literal = tokens.iter().position(|t| *t == Token::Assign).unwrap() + 1;
+        let close = tokens.iter().rposition(|t| *t == Token::RBracket).unwrap();
+        assert!(!tokens[literal..close]
+            .iter()
+            .any(|t| matches!(t, Token::Newline | Token::Indent | Token::Dedent)));
+        // `}}` closes two objects
+        assert_eq!(tokens[close - 2..close], [Token::RBrace, Token::RBrace]);
+        // The following variable is still in the same block
+        assert_eq!(tokens[close + 1..close + 3], [Token::Newline, Token::Ident("next")]);
+        let indents = tokens.iter().filter(|t| matches!(t, Token::Indent)).count();
+        assert_eq!(indents, 1);
+    }
 }
@@ -6,8 +6,10 @@
 //!
 //! ## Feature Flags
 //!
-//! - `graph` - Enable graph analysis, validation, and rendering (brings in `petgraph`)
+//! - `graph` - Enable graph analysis, validation, rendering, and linting (brings in `petgraph`)
 //! - `wasm` - Enable WebAssembly bindings for browser use
+//! - `import` - Enable importing agents from Salesforce metadata (brings in `roxmltree`)
+//! - `arbitrary` - Implement [`arbitrary::Arbitrary`](https://docs.rs/arbitrary) for the AST, generating valid agents for property tests
 //!
 //! ## Quick Start
 //!
@@ -23,10 +25,31 @@
 //! ```
 
 pub mod ast;
+pub mod builder;
+pub mod cache;
+pub mod describe;
+pub mod docgen;
+pub mod edit;
 pub mod error;
+pub mod export;
+pub mod formatter;
+pub mod grammar;
+pub mod junit;
 pub mod lexer;
+pub mod markdown;
+pub mod minimize;
 pub mod parser;
+pub mod redact;
+pub mod schema;
 pub mod serializer;
+pub mod simulation;
+pub mod spanless;
+pub mod spanner;
+pub mod summary;
+pub mod target_uri;
+pub mod text_pos;
+pub mod typecheck;
+pub mod typescript;
 pub mod validation;
 
 #[cfg(feature = "wasm")]
@@ -35,6 +58,18 @@ pub mod wasm;
 #[cfg(feature = "graph")]
 pub mod graph;
 
+#[cfg(feature = "graph")]
+pub mod lint;
+
+#[cfg(feature = "graph")]
+pub mod report;
+
+#[cfg(feature = "import")]
+pub mod import;
+
+#[cfg(feature = "arbitrary")]
+pub mod arbitrary;
+
 // Re-export commonly used types
 pub use ast::{AgentFile, Expr, Reference, Spanned, Type};
 pub use error::{AgentScriptError, ErrorReporter};
@@ -53,6 +88,7 @@ pub fn parse_source(source: &str) -> Result<AgentFile, Vec<String>> {
 
 #[cfg(test)]
 mod tests {
+    use super::ast::{Currency, Date, Number};
     use super::*;
     use chumsky::prelude::*;
 
@@ -72,4 +108,610 @@ config:
         let tokens = lexer::lexer().parse(source);
         assert!(tokens.has_output());
     }
+    #[test]
+    fn test_parse_date_and_currency_literals() {
+        let source = r#"variables:
+       renewal: mutable date = date(2024-02-29)
+       price: mutable currency = $199.99
+       refund: mutable currency = currency(5, "EUR")
+    "#;
+        let ast = parse(source).unwrap();
+        let defaults: Vec<Expr> = ast
+            .variables
+            .unwrap()
+            .node
+            .variables
+            .into_iter()
+            .map(|v| v.node.default.unwrap().node)
+            .collect();
+        assert_eq!(
+            defaults,
+            [
+                Expr::Date(Date {
+                    year: 2024,
+                    month: 2,
+                    day: 29
+                }),
+                Expr::Currency(Currency {
+                    amount: "199.99".parse().unwrap(),
+                    code: None
+                }),
+                Expr::Currency(Currency {
+                    amount: Number::Int(5),
+                    code: Some("EUR".to_string())
+                }),
+            ]
+        );
+
+        let errors =
+            parse("variables:\n   renewal: mutable date = date(2023-02-29)\n").unwrap_err();
+        assert!(
+            errors
+                .iter()
+                .any(|e| e.contains("invalid date '2023-02-29', day must be 01-28")),
+            "{:?}",
+            errors
+        );
+
+        let errors =
+            parse("variables:\n   price: mutable currency = currency(5, \"usd\")\n").unwrap_err();
+        assert!(
+            errors
+                .iter()
+                .any(|e| e.contains("invalid currency code 'usd'")),
+            "{:?}",
+            errors
+        );
+    }
+
+    #[test]
+    fn test_parse_quoted_topic_name() {
+        let source = r#"start_agent topic_selector:
+   reasoning:
+      actions:
+         go_claims: @utils.transition to @topic.Claims_EU
+
+topic "Claims – EU":
+   description: "EU claims"
+"#;
+        let ast = parse(source).unwrap();
+        let topic = &ast.topics[0].node;
+        assert_eq!(topic.name.node, "Claims_EU");
+        assert_eq!(topic.quoted_name.as_ref().unwrap().node, "Claims – EU");
+        assert_eq!(&source[topic.name.span.clone()], "\"Claims – EU\"");
+
+        let errors = parse("topic \"--\":\n   description: \"Dashes\"\n").unwrap_err();
+        assert!(
+            errors
+                .iter()
+                .any(|e| e.contains("invalid topic name \"--\", expected a letter or digit")),
+            "{:?}",
+            errors
+        );
+    }
+
+    #[test]
+    fn test_parse_property_access_on_references() {
+        let source = r#"variables:
+   amount: mutable number = @outputs.policy.coverage_amount
+   first: mutable string = @variables.items[0].name
+"#;
+        let ast = parse(source).unwrap();
+        let defaults: Vec<ast::Spanned<Expr>> = ast
+            .variables
+            .as_ref()
+            .unwrap()
+            .node
+            .variables
+            .iter()
+            .map(|v| v.node.default.clone().unwrap())
+            .collect();
+
+        let Expr::Property { object, field } = &defaults[0].node else {
+            panic!("expected property access, got {:?}", defaults[0].node);
+        };
+        assert_eq!(
+            object.node,
+            Expr::Reference(Reference::new("outputs", vec!["policy".to_string()]))
+        );
+        assert_eq!(&source[object.span.clone()], "@outputs.policy");
+        assert_eq!(&source[field.span.clone()], "coverage_amount");
+        assert_eq!(&source[defaults[0].span.clone()], "@outputs.policy.coverage_amount");
+
+        let Expr::Property { object, field } = &defaults[1].node else {
+            panic!("expected property access, got {:?}", defaults[1].node);
+        };
+        assert_eq!(field.node, "name");
+        let Expr::Index { object, index } = &object.node else {
+            panic!("expected index access, got {:?}", object.node);
+        };
+        assert_eq!(
+            object.node,
+            Expr::Reference(Reference::new("variables", vec!["items".to_string()]))
+        );
+        assert_eq!(index.node, Expr::Number(Number::Int(0)));
+
+        let serialized = serialize(&ast);
+        assert!(serialized.contains("= @outputs.policy.coverage_amount\n"));
+        assert!(serialized.contains("= @variables.items[0].name\n"));
+        assert!(parse(&serialized).unwrap().eq_ignore_spans(&ast));
+    }
+
+    #[test]
+    fn test_parse_directive_statements() {
+        let source = r#"topic help:
+   description: "Help"
+   after_reasoning:
+      if @variables.failed:
+         respond "Connecting you to an agent"
+         escalate
+      else:
+         transition to @topic.done
+"#;
+        let ast = parse(source).unwrap();
+        let statements = &ast.topics[0]
+            .node
+            .after_reasoning
+            .as_ref()
+            .unwrap()
+            .node
+            .statements;
+        let ast::Stmt::If {
+            then_block,
+            else_block,
+            ..
+        } = &statements[0].node
+        else {
+            panic!("expected if, got {:?}", statements[0].node);
+        };
+        assert!(matches!(
+            &then_block[0].node,
+            ast::Stmt::Respond { message } if message.node == "Connecting you to an agent"
+        ));
+        assert_eq!(then_block[1].node, ast::Stmt::Escalate);
+        assert_eq!(&source[then_block[1].span.clone()], "escalate");
+        assert!(matches!(
+            &else_block.as_ref().unwrap()[0].node,
+            ast::Stmt::Transition { target } if target.node.full_path() == "@topic.done"
+        ));
+
+        let serialized = serialize(&ast);
+        assert!(serialized.contains("respond \"Connecting you to an agent\"\n"));
+        assert!(parse(&serialized).unwrap().eq_ignore_spans(&ast));
+    }
+
+    #[test]
+    fn test_parse_elif_chains() {
+        let source = r#"topic main:
+   description: "Main"
+   before_reasoning:
+      if @variables.count == 0:
+         set @variables.tier = "none"
+      elif @variables.count == 1:
+         set @variables.tier = "one"
+      elif @variables.count == 2:
+         set @variables.tier = "two"
+      else:
+         set @variables.tier = "many"
+   reasoning:
+      instructions: ->
+         if @variables.count == 0:
+            | Nothing yet.
+         elif @variables.count == 1:
+            | One item.
+         else:
+            | {!@variables.count} items.
+"#;
+        let ast = parse(source).unwrap();
+        let topic = &ast.topics[0].node;
+
+        // Each elif is an if alone in the previous else branch
+        let ast::Stmt::If { else_block, .. } =
+            &topic.before_reasoning.as_ref().unwrap().node.statements[0].node
+        else {
+            panic!("expected if");
+        };
+        let elif = &else_block.as_ref().unwrap()[0];
+        assert!(source[elif.span.clone()].starts_with("elif @variables.count == 1:"));
+        let ast::Stmt::If { else_block, .. } = &elif.node else {
+            panic!("expected elif, got {:?}", elif.node);
+        };
+        let ast::Stmt::If { else_block, .. } = &else_block.as_ref().unwrap()[0].node else {
+            panic!("expected second elif");
+        };
+        assert!(matches!(
+            &else_block.as_ref().unwrap()[0].node,
+            ast::Stmt::Set { value, .. } if value.node == ast::Expr::String("many".to_string())
+        ));
+
+        let Some(ast::Instructions::Dynamic(parts)) = topic
+            .reasoning
+            .as_ref()
+            .and_then(|r| r.node.instructions.as_ref())
+            .map(|i| &i.node)
+        else {
+            panic!("expected dynamic instructions");
+        };
+        let ast::InstructionPart::Conditional { else_parts, .. } = &parts[0].node else {
+            panic!("expected conditional, got {:?}", parts[0].node);
+        };
+        assert!(matches!(
+            &else_parts.as_ref().unwrap()[0].node,
+            ast::InstructionPart::Conditional {
+                else_parts: Some(_),
+                ..
+            }
+        ));
+
+        // Chains serialize back as elif, not as nested ifs
+        let serialized = serialize(&ast);
+        assert_eq!(serialized.matches("elif ").count(), 3);
+        assert_eq!(serialized.matches("else:").count(), 2);
+        assert!(parse(&serialized).unwrap().eq_ignore_spans(&ast));
+    }
+
+    #[test]
+    fn test_parse_negative_numbers() {
+        let source = r#"variables:
+   balance: mutable number = -5
+   bounds: mutable list[number] = [-1, 2]
+
+topic main:
+   description: "Main"
+   before_reasoning:
+      set @variables.balance=-1
+      set @variables.balance = 3 - -1.5
+      if @variables.balance < -2:
+         set @variables.balance = -(@variables.balance + 1)
+   reasoning:
+      instructions: ->
+         if @variables.balance > -1:
+            | Owed {!-@variables.balance}
+      actions:
+         go: @actions.shift
+            with offset=-3.5
+"#;
+        let ast = parse(source).unwrap();
+        let neg = |expr: &ast::Expr| match expr {
+            ast::Expr::UnaryOp {
+                op: ast::UnaryOp::Neg,
+                operand,
+            } => operand.node.clone(),
+            other => panic!("expected negation, got {other:?}"),
+        };
+        let number = |n: i64| ast::Expr::Number(n.into());
+
+        let variables = &ast.variables.as_ref().unwrap().node.variables;
+        assert_eq!(neg(&variables[0].node.default.as_ref().unwrap().node), number(5));
+
+        let topic = &ast.topics[0].node;
+        let statements = &topic.before_reasoning.as_ref().unwrap().node.statements;
+        let ast::Stmt::Set { value, .. } = &statements[0].node else {
+            panic!("expected set");
+        };
+        assert_eq!(neg(&value.node), number(1));
+        let ast::Stmt::Set { value, .. } = &statements[1].node else {
+            panic!("expected set");
+        };
+        let ast::Expr::BinOp {
+            op: ast::BinOp::Sub,
+            right,
+            ..
+        } = &value.node
+        else {
+            panic!("expected subtraction, got {:?}", value.node);
+        };
+        assert_eq!(neg(&right.node), ast::Expr::Number(1.5.into()));
+
+        let reasoning = &topic.reasoning.as_ref().unwrap().node;
+        let Some(ast::Instructions::Dynamic(parts)) =
+            reasoning.instructions.as_ref().map(|i| &i.node)
+        else {
+            panic!("expected dynamic instructions");
+        };
+        let ast::InstructionPart::Conditional {
+            condition,
+            then_parts,
+            ..
+        } = &parts[0].node
+        else {
+            panic!("expected conditional");
+        };
+        assert!(
+            matches!(&condition.node, ast::Expr::BinOp { right, .. } if neg(&right.node) == number(1))
+        );
+        assert!(then_parts
+            .iter()
+            .any(|p| matches!(&p.node, ast::InstructionPart::Interpolation(e) if matches!(neg(e), ast::Expr::Reference(_)))));
+        let with = &reasoning.actions.as_ref().unwrap().node[0]
+            .node
+            .with_clauses[0];
+        let ast::WithValue::Expr(value) = &with.node.value.node;
+        assert_eq!(neg(value), ast::Expr::Number(3.5.into()));
+
+        let serialized = serialize(&ast);
+        for expected in [
+            "balance: mutable number = -5",
+            "[-1, 2]",
+            "set @variables.balance = -1\n",
+            "3 - -1.5",
+            "-(@variables.balance + 1)",
+            "{!-@variables.balance}",
+            "with offset = -3.5",
+        ] {
+            assert!(serialized.contains(expected), "{expected} in {serialized}");
+        }
+        assert!(parse(&serialized).unwrap().eq_ignore_spans(&ast));
+    }
+
+    #[test]
+    fn test_parse_string_concatenation_and_templates() {
+        let source = r#"variables:
+   name: mutable string = "Ada"
+   greeting: mutable string = ""
+
+topic main:
+   description: "Main"
+   before_reasoning:
+      set @variables.greeting = "Hello " + @variables.name
+      set @variables.greeting = "Hello {!@variables.name}, you have {! len(@variables.name) } letters"
+      set @variables.greeting = "Not {!an expression"
+   reasoning:
+      instructions: "Help"
+      actions:
+         greet: @actions.greet
+            with message = "Hi " + @variables.name + "!"
+            with note = "Dear {!@variables.name}"
+"#;
+        let ast = parse(source).unwrap();
+        let topic = &ast.topics[0].node;
+        let statements = &topic.before_reasoning.as_ref().unwrap().node.statements;
+        let value = |i: usize| match &statements[i].node {
+            ast::Stmt::Set { value, .. } => value.clone(),
+            other => panic!("expected set, got {other:?}"),
+        };
+
+        assert!(matches!(
+            value(0).node,
+            ast::Expr::BinOp {
+                op: ast::BinOp::Add,
+                ..
+            }
+        ));
+
+        let ast::Expr::Template(parts) = value(1).node else {
+            panic!("expected template, got {:?}", value(1).node);
+        };
+        let text: Vec<_> = parts.iter().map(|p| &source[p.span.clone()]).collect();
+        assert_eq!(
+            text,
+            [
+                "Hello ",
+                "@variables.name",
+                ", you have ",
+                "len(@variables.name)",
+                " letters"
+            ]
+        );
+        assert_eq!(parts[0].node, ast::Expr::String("Hello ".to_string()));
+        assert!(
+            matches!(&parts[1].node, ast::Expr::Reference(r) if r.full_path() == "@variables.name")
+        );
+        assert!(matches!(parts[3].node, ast::Expr::Call { .. }));
+
+        assert_eq!(value(2).node, ast::Expr::String("Not {!an expression".to_string()));
+
+        let action = &topic
+            .reasoning
+            .as_ref()
+            .unwrap()
+            .node
+            .actions
+            .as_ref()
+            .unwrap()
+            .node[0];
+        let values: Vec<_> = action
+            .node
+            .with_clauses
+            .iter()
+            .map(|w| {
+                let ast::WithValue::Expr(value) = &w.node.value.node;
+                value.clone()
+            })
+            .collect();
+        assert!(matches!(values[0], ast::Expr::BinOp { .. }));
+        assert!(matches!(values[1], ast::Expr::Template(_)));
+
+        let serialized = serialize(&ast);
+        for expected in [
+            r#"set @variables.greeting = "Hello " + @variables.name"#,
+            r#""Hello {!@variables.name}, you have {!len(@variables.name)} letters""#,
+            r#"with message = "Hi " + @variables.name + "!""#,
+            r#"with note = "Dear {!@variables.name}""#,
+        ] {
+            assert!(serialized.contains(expected), "{expected} in {serialized}");
+        }
+        assert!(parse(&serialized).unwrap().eq_ignore_spans(&ast));
+    }
+
+    #[test]
+    fn test_parse_list_and_object_literals() {
+        let source = r#"variables:
+   tags: mutable list[string] = ["a", "b"]
+   info: mutable object = {status: "new", count: 0, "first name": "Ada"}
+   nested: mutable object = {items: [1, 2, {deep: True}], meta: {label: {}}}
+   rows: mutable list[object] = [
+      {name: "x", tags: []},
+      {name: "y", tags: [
+         "z"
+      ]}
+   ]
+   after: mutable string = "kept"
+
+topic main:
+   description: "Main"
+   before_reasoning:
+      set @variables.info = {status: "done", count: @variables.info.count + 1}
+   reasoning:
+      instructions: "Help"
+      actions:
+         save: @actions.save
+            with payload = {
+               tags: ["x", "y"],
+               flags: [True, False]
+            }
+"#;
+        let ast = parse(source).unwrap();
+        let variables = &ast.variables.as_ref().unwrap().node.variables;
+        let names: Vec<_> = variables
+            .iter()
+            .map(|v| v.node.name.node.as_str())
+            .collect();
+        assert_eq!(names, ["tags", "info", "nested", "rows", "after"]);
+        let default = |i: usize| variables[i].node.default.as_ref().unwrap().node.clone();
+
+        let ast::Expr::List(tags) = default(0) else {
+            panic!("expected list, got {:?}", default(0));
+        };
+        assert_eq!(tags[1].node, ast::Expr::String("b".to_string()));
+
+        let ast::Expr::Object(info) = default(1) else {
+            panic!("expected object, got {:?}", default(1));
+        };
+        let keys: Vec<_> = info.keys().map(String::as_str).collect();
+        assert_eq!(keys, ["status", "count", "first name"]);
+
+        let ast::Expr::Object(nested) = default(2) else {
+            panic!("expected object, got {:?}", default(2));
+        };
+        let ast::Expr::List(items) = &nested["items"].node else {
+            panic!("expected list, got {:?}", nested["items"].node);
+        };
+        assert!(
+            matches!(&items[2].node, ast::Expr::Object(o) if o["deep"].node == ast::Expr::Bool(true))
+        );
+        assert!(matches!(&nested["meta"].node, ast::Expr::Object(o) if o.contains_key("label")));
+
+        let ast::Expr::List(rows) = default(3) else {
+            panic!("expected list, got {:?}", default(3));
+        };
+        assert_eq!(rows.len(), 2);
+        assert_eq!(
+            &source[rows[1].span.clone()],
+            "{name: \"y\", tags: [\n         \"z\"\n      ]}"
+        );
+
+        let serialized = serialize(&ast);
+        for expected in [
+            r#"tags: mutable list[string] = ["a", "b"]"#,
+            r#"info: mutable object = {status: "new", count: 0, "first name": "Ada"}"#,
+            r#"nested: mutable object = {items: [1, 2, {deep: True}], meta: {label: {}}}"#,
+            r#"rows: mutable list[object] = [{name: "x", tags: []}, {name: "y", tags: ["z"]}]"#,
+            r#"set @variables.info = {status: "done", count: @variables.info.count + 1}"#,
+            r#"with payload = {tags: ["x", "y"], flags: [True, False]}"#,
+        ] {
+            assert!(serialized.contains(expected), "{expected} in {serialized}");
+        }
+        assert!(parse(&serialized).unwrap().eq_ignore_spans(&ast));
+    }
+
+    #[test]
+    fn test_parse_list_types() {
+        let source = r#"variables:
+   tags: mutable list[string] = []
+   ids: mutable list<id> = []
+
+topic main:
+   description: "Main"
+
+   actions:
+      lookup:
+         inputs:
+            keys: list<string>
+         outputs:
+            rows: list[object]
+         target: "flow://Lookup"
+"#;
+        let ast = parse(source).unwrap();
+        let variables = &ast.variables.as_ref().unwrap().node.variables;
+        let list = |ty| ast::Type::List(Box::new(ty));
+        assert_eq!(variables[0].node.ty.node, list(ast::Type::String));
+        assert_eq!(variables[1].node.ty.node, list(ast::Type::Id));
+
+        let serialized = serialize(&ast);
+        for expected in [
+            "ids: mutable list[id] = []",
+            "keys: list[string]",
+            "rows: list[object]",
+        ] {
+            assert!(serialized.contains(expected), "{expected} in {serialized}");
+        }
+        assert!(parse(&serialized).unwrap().eq_ignore_spans(&ast));
+
+        let errors = parse_source("variables:\n   tags: mutable list[tag] = []\n").unwrap_err();
+        assert!(errors.iter().any(|e| e.contains("unknown type 'tag'")), "{errors:?}");
+    }
+
+    #[test]
+    fn test_parse_instruction_headings() {
+        let source = r#"topic main:
+   description: "Main"
+   reasoning:
+      instructions: ->
+         |## Rules
+         | Be polite to {!@variables.name}
+         | # not a heading
+         | ### Tone
+         | Friendly
+         if @variables.vip:
+            |## Priority
+            | Skip the queue
+         |## Escalation
+         | Ask first
+"#;
+        let ast = parse(source).unwrap();
+        let instructions = &ast.topics[0]
+            .node
+            .reasoning
+            .as_ref()
+            .unwrap()
+            .node
+            .instructions
+            .as_ref()
+            .unwrap()
+            .node;
+        let ast::Instructions::Dynamic(parts) = instructions else {
+            panic!("expected dynamic instructions, got {:?}", instructions);
+        };
+        let headings: Vec<_> = parts
+            .iter()
+            .filter_map(|p| match &p.node {
+                ast::InstructionPart::Heading { level, title } => {
+                    Some((*level, title.as_str(), &source[p.span.clone()]))
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            headings,
+            [
+                (2, "Rules", "|## Rules"),
+                (3, "Tone", "| ### Tone"),
+                (2, "Escalation", "|## Escalation"),
+            ]
+        );
+
+        // Headings inside conditionals are kept, but start no section
+        let sections = instructions.sections();
+        assert_eq!(sections.len(), 2);
+        assert_eq!(sections[0].children.len(), 1);
+        assert!(source[sections[0].span.clone()]
+            .trim_end()
+            .ends_with("| Skip the queue"));
+
+        let serialized = serialize(&ast);
+        assert!(serialized.contains("| ## Rules\n"), "{serialized}");
+        assert!(serialized.contains("| ### Tone\n"), "{serialized}");
+        assert!(parse(&serialized).unwrap().eq_ignore_spans(&ast));
+    }
 }
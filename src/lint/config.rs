@@ -0,0 +1,394 @@
+//! `.agentscriptlint.toml` configuration.
+//!
+//! Only the subset of TOML the configuration needs is understood: `[rules]`,
+//! `[rules.<id>]` and [`[policies.<code>]`](super::policy) tables, `#`
+//! comments, and string, integer and boolean values.
+//!
+//! ```toml
+//! [rules]
+//! require-descriptions = "error"
+//! naming-convention = "off"
+//!
+//! [rules.max-topic-count]
+//! severity = "warn"
+//! max = 12
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use thiserror::Error;
+
+use super::policy::Policy;
+use crate::validation::Severity;
+
+/// File name the configuration is looked up under.
+pub const CONFIG_FILE_NAME: &str = ".agentscriptlint.toml";
+
+/// Errors reading a lint configuration.
+#[derive(Debug, Error)]
+pub enum LintConfigError {
+    /// The file could not be read.
+    #[error("failed to read {path}: {source}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    /// The file is not valid configuration.
+    #[error("line {line}: {message}")]
+    Syntax { line: usize, message: String },
+}
+
+/// How a rule reports its findings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LintLevel {
+    /// The rule does not run.
+    Off,
+    /// Findings are warnings.
+    Warn,
+    /// Findings are errors.
+    Error,
+}
+
+impl LintLevel {
+    /// The diagnostic severity of this level, or `None` when the rule is off.
+    pub fn severity(self) -> Option<Severity> {
+        match self {
+            LintLevel::Off => None,
+            LintLevel::Warn => Some(Severity::Warning),
+            LintLevel::Error => Some(Severity::Error),
+        }
+    }
+}
+
+impl FromStr for LintLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "off" | "allow" => Ok(LintLevel::Off),
+            "warn" | "warning" => Ok(LintLevel::Warn),
+            "error" | "deny" => Ok(LintLevel::Error),
+            _ => Err(format!("unknown lint level '{}', expected off, warn, or error", s)),
+        }
+    }
+}
+
+impl fmt::Display for LintLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            LintLevel::Off => "off",
+            LintLevel::Warn => "warn",
+            LintLevel::Error => "error",
+        })
+    }
+}
+
+/// A rule option value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LintValue {
+    String(String),
+    Integer(i64),
+    Bool(bool),
+}
+
+/// Options of a single rule, e.g. `max = 12`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RuleOptions(BTreeMap<String, LintValue>);
+
+impl RuleOptions {
+    /// Raw value of an option.
+    pub fn get(&self, key: &str) -> Option<&LintValue> {
+        self.0.get(key)
+    }
+
+    /// A non-negative integer option.
+    pub fn get_usize(&self, key: &str) -> Option<usize> {
+        match self.0.get(key)? {
+            LintValue::Integer(n) => usize::try_from(*n).ok(),
+            _ => None,
+        }
+    }
+
+    /// A string option.
+    pub fn get_str(&self, key: &str) -> Option<&str> {
+        match self.0.get(key)? {
+            LintValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// A boolean option.
+    pub fn get_bool(&self, key: &str) -> Option<bool> {
+        match self.0.get(key)? {
+            LintValue::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    /// All options, in key order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &LintValue)> {
+        self.0.iter().map(|(k, v)| (k.as_str(), v))
+    }
+
+    /// Set an option.
+    pub fn set(&mut self, key: impl Into<String>, value: LintValue) {
+        self.0.insert(key.into(), value);
+    }
+}
+
+/// Configuration of a single rule.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RuleConfig {
+    /// Level override; the rule's default level applies when `None`.
+    pub level: Option<LintLevel>,
+    /// Rule-specific options.
+    pub options: RuleOptions,
+}
+
+/// Per-rule levels and options, usually loaded from `.agentscriptlint.toml`.
+///
+/// The default configuration runs every rule at its default level.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LintConfig {
+    rules: BTreeMap<String, RuleConfig>,
+    policies: Vec<Policy>,
+}
+
+/// The table a configuration line belongs to.
+enum Table {
+    /// `[rules]`
+    Rules,
+    /// `[rules.<id>]`
+    Rule(String),
+    /// `[policies.<code>]`, with the line of its header.
+    Policy(String, usize, RuleOptions),
+}
+
+impl LintConfig {
+    /// Parse a configuration file's contents.
+    pub fn from_toml(source: &str) -> Result<Self, LintConfigError> {
+        let mut config = LintConfig::default();
+        let mut table = Table::Rules;
+
+        for (i, raw) in source.lines().enumerate() {
+            let line = i + 1;
+            let syntax = |message: String| LintConfigError::Syntax { line, message };
+            let text = strip_comment(raw).trim();
+            if text.is_empty() {
+                continue;
+            }
+
+            if let Some(header) = text.strip_prefix('[') {
+                let header = header
+                    .strip_suffix(']')
+                    .ok_or_else(|| syntax("unterminated table header".to_string()))?
+                    .trim();
+                let next = match header.split_once('.') {
+                    None if header == "rules" => Table::Rules,
+                    Some(("rules", id)) if !id.trim().is_empty() => {
+                        let id = id.trim().trim_matches('"').to_string();
+                        config.rules.entry(id.clone()).or_default();
+                        Table::Rule(id)
+                    }
+                    Some(("policies", code)) if !code.trim().is_empty() => {
+                        let code = code.trim().trim_matches('"').to_string();
+                        Table::Policy(code, line, RuleOptions::default())
+                    }
+                    _ => return Err(syntax(format!("unknown table [{}]", header))),
+                };
+                config.finish_table(std::mem::replace(&mut table, next))?;
+                continue;
+            }
+
+            let (key, value) = text
+                .split_once('=')
+                .ok_or_else(|| syntax(format!("expected `key = value`, found `{}`", text)))?;
+            let key = key.trim().trim_matches('"');
+            let value = parse_value(value.trim()).map_err(syntax)?;
+
+            match &mut table {
+                Table::Rules => {
+                    let LintValue::String(level) = value else {
+                        return Err(syntax(format!("level of '{}' must be a string", key)));
+                    };
+                    config.set_level(key, level.parse().map_err(syntax)?);
+                }
+                Table::Rule(id) => {
+                    let rule = config.rules.entry(id.clone()).or_default();
+                    if key == "severity" || key == "level" {
+                        let LintValue::String(level) = value else {
+                            return Err(syntax(format!("{} of '{}' must be a string", key, id)));
+                        };
+                        rule.level = Some(level.parse().map_err(syntax)?);
+                    } else {
+                        rule.options.set(key, value);
+                    }
+                }
+                Table::Policy(_, _, options) => options.set(key, value),
+            }
+        }
+
+        config.finish_table(table)?;
+        Ok(config)
+    }
+
+    /// Build the policy a `[policies.<code>]` table declares, once all of
+    /// its keys are known.
+    fn finish_table(&mut self, table: Table) -> Result<(), LintConfigError> {
+        if let Table::Policy(code, line, options) = table {
+            let policy = Policy::from_options(&code, options).map_err(|message| {
+                LintConfigError::Syntax {
+                    line,
+                    message: format!("policy '{}': {}", code, message),
+                }
+            })?;
+            self.policies.push(policy);
+        }
+        Ok(())
+    }
+
+    /// Read and parse a configuration file.
+    pub fn load(path: &Path) -> Result<Self, LintConfigError> {
+        let source = std::fs::read_to_string(path).map_err(|source| LintConfigError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        Self::from_toml(&source)
+    }
+
+    /// Configuration of a rule, if the file mentions it.
+    pub fn rule(&self, id: &str) -> Option<&RuleConfig> {
+        self.rules.get(id)
+    }
+
+    /// Ids of all rules the configuration mentions.
+    pub fn rule_ids(&self) -> impl Iterator<Item = &str> {
+        self.rules.keys().map(String::as_str)
+    }
+
+    /// Organization policies declared in `[policies.<code>]` tables.
+    pub fn policies(&self) -> &[Policy] {
+        &self.policies
+    }
+
+    /// Add a policy.
+    pub fn add_policy(&mut self, policy: Policy) {
+        self.policies.push(policy);
+    }
+
+    /// Override the level of a rule.
+    pub fn set_level(&mut self, id: &str, level: LintLevel) {
+        self.rules.entry(id.to_string()).or_default().level = Some(level);
+    }
+}
+
+/// Find `.agentscriptlint.toml` in `start` or the nearest ancestor directory.
+pub fn find_config_file(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .map(|dir| dir.join(CONFIG_FILE_NAME))
+        .find(|path| path.is_file())
+}
+
+/// Drop a trailing `#` comment, ignoring `#` inside strings.
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            }
+            (Some(q), c) if c == q && !escaped => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '#') => return &line[..i],
+            _ => {}
+        }
+        escaped = false;
+    }
+    line
+}
+
+fn parse_value(text: &str) -> Result<LintValue, String> {
+    if let Some(body) = text.strip_prefix('"') {
+        let body = body
+            .strip_suffix('"')
+            .ok_or_else(|| format!("unterminated string `{}`", text))?;
+        let mut value = String::new();
+        let mut chars = body.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                value.push(c);
+                continue;
+            }
+            value.push(match chars.next() {
+                Some('n') => '\n',
+                Some('t') => '\t',
+                Some(c @ ('"' | '\\')) => c,
+                other => return Err(format!("invalid escape `\\{}`", other.unwrap_or(' '))),
+            });
+        }
+        return Ok(LintValue::String(value));
+    }
+    if let Some(body) = text.strip_prefix('\'') {
+        return body
+            .strip_suffix('\'')
+            .map(|s| LintValue::String(s.to_string()))
+            .ok_or_else(|| format!("unterminated string `{}`", text));
+    }
+    match text {
+        "true" => return Ok(LintValue::Bool(true)),
+        "false" => return Ok(LintValue::Bool(false)),
+        _ => {}
+    }
+    text.replace('_', "")
+        .parse()
+        .map(LintValue::Integer)
+        .map_err(|_| format!("unsupported value `{}`", text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        let config = LintConfig::from_toml(
+            r#"
+# Project lint settings
+[rules]
+require-descriptions = "error"  # stricter than default
+naming-convention = 'off'
+
+[rules.max-topic-count]
+severity = "warn"
+max = 1_2
+note = "a # is not a comment"
+"#,
+        )
+        .unwrap();
+
+        let level = |id| config.rule(id).and_then(|r| r.level);
+        assert_eq!(level("require-descriptions"), Some(LintLevel::Error));
+        assert_eq!(level("naming-convention"), Some(LintLevel::Off));
+        assert_eq!(level("max-topic-count"), Some(LintLevel::Warn));
+
+        let options = &config.rule("max-topic-count").unwrap().options;
+        assert_eq!(options.get_usize("max"), Some(12));
+        assert_eq!(options.get_str("note"), Some("a # is not a comment"));
+        assert_eq!(options.get_str("max"), None);
+
+        let err = LintConfig::from_toml("[rules]\nmax-topic-count = \"loud\"\n").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 2: unknown lint level 'loud', expected off, warn, or error"
+        );
+        let err = LintConfig::from_toml("[lints]\n").unwrap_err();
+        assert_eq!(err.to_string(), "line 1: unknown table [lints]");
+    }
+}
@@ -0,0 +1,352 @@
+//! # lint
+//!
+//! Configurable style and maintainability checks.
+//!
+//! Where [`validate_ast`](crate::validate_ast) reports problems the platform
+//! cares about, lint rules encode project conventions: how many topics an
+//! agent may have, what must be documented, how deeply conditions may nest,
+//! and how things are named. Each [`Rule`] has an id and a default level,
+//! and both the level and the rule's options can be changed per project in
+//! an [`.agentscriptlint.toml`](CONFIG_FILE_NAME) file, which can also
+//! declare organization [policies](policy) that run alongside the rules.
+//!
+//! ## Built-in rules
+//!
+//! | Rule | Default | Options |
+//! |------|---------|---------|
+//! | `max-topic-count` | warn | `max` (20) |
+//! | `require-descriptions` | warn | |
+//! | `no-deep-nesting` | warn | `max-depth` (3) |
+//! | `naming-convention` | warn | `style` (`"snake_case"` or `"camelCase"`) |
+//! | `token-budget` | warn | `max` (2000 estimated instruction tokens per topic) |
+//!
+//! [`run_lints_for`] also reports the findings a
+//! [`TargetEnvironment`] escalates as errors, whatever their level.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use busbar_sf_agentscript::graph::RefGraph;
+//! use busbar_sf_agentscript::lint::{run_lints, LintConfig};
+//!
+//! let source = r#"
+//! start_agent Main:
+//!    description: "Route requests"
+//!    reasoning:
+//!       instructions: "Help the user"
+//! "#;
+//! let ast = busbar_sf_agentscript::parse(source).unwrap();
+//! let graph = RefGraph::from_ast(&ast).unwrap();
+//!
+//! let lints = run_lints(&ast, &graph, &LintConfig::default());
+//! assert_eq!(lints[0].rule, "naming-convention");
+//!
+//! let config = LintConfig::from_toml("[rules]\nnaming-convention = \"off\"\n").unwrap();
+//! assert!(run_lints(&ast, &graph, &config).is_empty());
+//! ```
+
+mod config;
+pub mod policy;
+mod rules;
+
+pub use config::{
+    find_config_file, LintConfig, LintConfigError, LintLevel, LintValue, RuleConfig, RuleOptions,
+    CONFIG_FILE_NAME,
+};
+pub use policy::{Policy, PolicySubject};
+pub use rules::{MaxTopicCount, NamingConvention, NoDeepNesting, RequireDescriptions, TokenBudget};
+
+use crate::ast::AgentFile;
+use crate::graph::RefGraph;
+use crate::validation::{SemanticError, Severity, TargetEnvironment};
+use serde::Serialize;
+use std::ops::Range;
+
+/// What a rule sees while checking an agent.
+pub struct LintContext<'a> {
+    pub ast: &'a AgentFile,
+    pub graph: &'a RefGraph,
+    /// The rule's options from the configuration.
+    pub options: &'a RuleOptions,
+}
+
+/// A problem found by a rule, before the configured level is applied.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub message: String,
+    pub span: Option<Range<usize>>,
+    pub hint: Option<String>,
+}
+
+impl Finding {
+    /// Create a finding without a hint.
+    pub fn new(message: impl Into<String>, span: Option<Range<usize>>) -> Self {
+        Self {
+            message: message.into(),
+            span,
+            hint: None,
+        }
+    }
+
+    /// Attach a hint.
+    pub fn with_hint(mut self, hint: impl Into<String>) -> Self {
+        self.hint = Some(hint.into());
+        self
+    }
+}
+
+/// A lint check.
+pub trait Rule: Send + Sync {
+    /// Stable kebab-case id, used in configuration and as the diagnostic code.
+    fn id(&self) -> &'static str;
+
+    /// One-line description of what the rule checks.
+    fn description(&self) -> &'static str;
+
+    /// Level used when the configuration does not set one.
+    fn default_level(&self) -> LintLevel {
+        LintLevel::Warn
+    }
+
+    /// Check an agent.
+    fn check(&self, cx: &LintContext<'_>) -> Vec<Finding>;
+}
+
+/// A finding reported by a rule at its configured level.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LintDiagnostic {
+    /// Id of the rule that reported it.
+    pub rule: &'static str,
+    pub severity: Severity,
+    pub message: String,
+    pub span: Option<Range<usize>>,
+    pub hint: Option<String>,
+}
+
+impl From<LintDiagnostic> for SemanticError {
+    fn from(diagnostic: LintDiagnostic) -> Self {
+        SemanticError {
+            message: diagnostic.message,
+            span: diagnostic.span,
+            severity: diagnostic.severity,
+            hint: diagnostic.hint,
+            code: Some(diagnostic.rule),
+            related: Vec::new(),
+        }
+    }
+}
+
+/// A set of rules to run.
+pub struct LintRegistry {
+    rules: Vec<Box<dyn Rule>>,
+}
+
+impl LintRegistry {
+    /// A registry without any rules.
+    pub fn empty() -> Self {
+        Self { rules: Vec::new() }
+    }
+
+    /// A registry with the built-in rules.
+    pub fn new() -> Self {
+        let mut registry = Self::empty();
+        registry.register(MaxTopicCount);
+        registry.register(RequireDescriptions);
+        registry.register(NoDeepNesting);
+        registry.register(NamingConvention);
+        registry.register(TokenBudget);
+        registry
+    }
+
+    /// Add a rule. A rule with the same id replaces the existing one.
+    pub fn register(&mut self, rule: impl Rule + 'static) {
+        self.rules.retain(|r| r.id() != rule.id());
+        self.rules.push(Box::new(rule));
+    }
+
+    /// The registered rules, in the order they run.
+    pub fn rules(&self) -> impl Iterator<Item = &dyn Rule> {
+        self.rules.iter().map(|r| r.as_ref())
+    }
+
+    /// Rule ids the configuration mentions that neither a registered rule
+    /// nor one of its policies has.
+    pub fn unknown_rules<'c>(&self, config: &'c LintConfig) -> Vec<&'c str> {
+        config
+            .rule_ids()
+            .filter(|id| !self.rules.iter().any(|r| r.id() == *id))
+            .filter(|id| !config.policies().iter().any(|p| p.code() == *id))
+            .collect()
+    }
+
+    /// Run every enabled rule and the configuration's policies, ordering
+    /// diagnostics by position.
+    pub fn run(
+        &self,
+        ast: &AgentFile,
+        graph: &RefGraph,
+        config: &LintConfig,
+    ) -> Vec<LintDiagnostic> {
+        let default_options = RuleOptions::default();
+        let mut diagnostics = Vec::new();
+
+        let policies = config.policies().iter().map(|p| p as &dyn Rule);
+        for rule in self.rules().chain(policies) {
+            let rule_config = config.rule(rule.id());
+            let level = rule_config
+                .and_then(|c| c.level)
+                .unwrap_or_else(|| rule.default_level());
+            let Some(severity) = level.severity() else {
+                continue;
+            };
+            let cx = LintContext {
+                ast,
+                graph,
+                options: rule_config.map_or(&default_options, |c| &c.options),
+            };
+            diagnostics.extend(rule.check(&cx).into_iter().map(|f| LintDiagnostic {
+                rule: rule.id(),
+                severity: severity.clone(),
+                message: f.message,
+                span: f.span,
+                hint: f.hint,
+            }));
+        }
+
+        diagnostics.sort_by_key(|d| d.span.as_ref().map_or(0, |s| s.start));
+        diagnostics
+    }
+}
+
+impl Default for LintRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Run the built-in rules and the configuration's policies.
+pub fn run_lints(ast: &AgentFile, graph: &RefGraph, config: &LintConfig) -> Vec<LintDiagnostic> {
+    LintRegistry::new().run(ast, graph, config)
+}
+
+/// Run the built-in rules and the configuration's policies, reporting the
+/// findings `target` escalates as errors.
+pub fn run_lints_for(
+    ast: &AgentFile,
+    graph: &RefGraph,
+    config: &LintConfig,
+    target: TargetEnvironment,
+) -> Vec<LintDiagnostic> {
+    let mut diagnostics = run_lints(ast, graph, config);
+    for diagnostic in &mut diagnostics {
+        if target.escalates(diagnostic.rule) {
+            diagnostic.severity = Severity::Error;
+        }
+    }
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = r#"
+variables:
+   customerName: mutable string
+   order_id: mutable string
+      description: "Current order"
+
+start_agent main:
+   description: "Route"
+   reasoning:
+      instructions: "Route"
+      actions:
+         go: @utils.transition to @topic.orders
+
+topic orders:
+   description: "Orders"
+   before_reasoning:
+      if @variables.order_id:
+         if @variables.customerName:
+            set @variables.order_id = ""
+   reasoning:
+      instructions: "Help"
+
+topic returns:
+   description: "Returns"
+   reasoning:
+      instructions: "Help"
+"#;
+
+    fn lint(config: &str) -> Vec<LintDiagnostic> {
+        let ast = crate::parse(SOURCE).unwrap();
+        let graph = RefGraph::from_ast(&ast).unwrap();
+        run_lints(&ast, &graph, &LintConfig::from_toml(config).unwrap())
+    }
+
+    #[test]
+    fn test_default_rules() {
+        let lints = lint("");
+        let rules: Vec<_> = lints.iter().map(|d| d.rule).collect();
+        assert_eq!(rules, ["require-descriptions", "naming-convention"]);
+        assert_eq!(lints[1].message, "Variable 'customerName' is not snake_case");
+        assert_eq!(lints[1].hint.as_deref(), Some("Rename it to 'customer_name'"));
+        assert_eq!(&SOURCE[lints[1].span.clone().unwrap()], "customerName");
+        assert!(lints.iter().all(|d| d.severity == Severity::Warning));
+    }
+
+    #[test]
+    fn test_configured_rules() {
+        let lints = lint(
+            r#"
+[rules]
+naming-convention = "off"
+require-descriptions = "error"
+
+[rules.max-topic-count]
+max = 1
+
+[rules.no-deep-nesting]
+max-depth = 1
+"#,
+        );
+        let found: Vec<_> = lints.iter().map(|d| (d.rule, d.severity.clone())).collect();
+        assert_eq!(
+            found,
+            [
+                ("require-descriptions", Severity::Error),
+                ("no-deep-nesting", Severity::Warning),
+                ("max-topic-count", Severity::Warning),
+            ]
+        );
+        assert_eq!(lints[2].message, "Agent has 2 topics, more than the maximum of 1");
+        assert_eq!(&SOURCE[lints[2].span.clone().unwrap()], "returns");
+
+        let registry = LintRegistry::new();
+        let config = LintConfig::from_toml("[rules.no-such-rule]\nmax = 1\n").unwrap();
+        assert_eq!(registry.unknown_rules(&config), ["no-such-rule"]);
+    }
+
+    #[test]
+    fn test_token_budget_escalates_for_prod() {
+        let ast = crate::parse(SOURCE).unwrap();
+        let graph = RefGraph::from_ast(&ast).unwrap();
+        let config = LintConfig::from_toml("[rules.token-budget]\nmax = 1\n").unwrap();
+        assert_eq!(TokenBudget::max(&config), 1);
+        assert_eq!(TokenBudget::max(&LintConfig::default()), TokenBudget::DEFAULT_MAX);
+
+        let budget = |target| {
+            run_lints_for(&ast, &graph, &config, target)
+                .into_iter()
+                .filter(|d| d.rule == "token-budget")
+                .collect::<Vec<_>>()
+        };
+        let dev = budget(TargetEnvironment::Dev);
+        assert_eq!(dev.len(), 1);
+        assert_eq!(dev[0].message, "'main' uses ~2 instruction tokens, over the budget of 1");
+        assert_eq!(dev[0].severity, Severity::Warning);
+        assert!(budget(TargetEnvironment::Prod)
+            .iter()
+            .all(|d| d.severity == Severity::Error));
+    }
+}
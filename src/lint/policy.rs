@@ -0,0 +1,590 @@
+//! Organization policies declared in configuration.
+//!
+//! Platform teams can add their own checks without writing Rust by
+//! declaring `[policies.<code>]` tables in `.agentscriptlint.toml`. Each
+//! policy picks a `subject`, optionally narrows it with filters, and states
+//! one or more assertions every matching subject must satisfy. Violations
+//! are reported like any other lint, with the table name as their code.
+//!
+//! ```toml
+//! [policies.topic-escalation]
+//! subject = "topic"
+//! require-escalation = true
+//! message = "Topic '{name}' must be able to reach a human"
+//!
+//! [policies.no-apex-in-service-agents]
+//! subject = "action"
+//! agent-type = "AgentforceServiceAgent"
+//! forbid-target = "apex://"
+//! severity = "warn"
+//!
+//! [policies.variable-names]
+//! subject = "variable"
+//! name-matches = "^[a-z_]+$"
+//! ```
+//!
+//! | Key | Meaning |
+//! |-----|---------|
+//! | `subject` | `topic`, `start_agent`, `action`, `reasoning-action`, or `variable` |
+//! | `severity` | `warn` or `error` (default) |
+//! | `message`, `hint` | Replace the default message; `{name}` is the subject's name |
+//! | `agent-type` | Only apply to agents with this `config.agent_type` |
+//! | `name-pattern` | Only apply to subjects whose name matches this regex |
+//! | `name-matches` | The name must match this regex |
+//! | `require-description` | The subject must have a description |
+//! | `forbid-target` | Actions must not target a URI with this prefix |
+//! | `require-escalation` | The topic must escalate, or transition to a topic that does |
+//! | `max-actions` | The topic must define at most this many actions |
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Mutex, OnceLock};
+
+use regex::Regex;
+
+use super::{Finding, LintContext, LintLevel, LintValue, Rule, RuleOptions};
+use crate::ast::{ActionsBlock, AgentFile, ReasoningActionTarget, ReasoningBlock, Spanned};
+use crate::graph::{RefGraph, RefNode};
+
+/// What a policy applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PolicySubject {
+    Topic,
+    StartAgent,
+    Action,
+    ReasoningAction,
+    Variable,
+}
+
+impl PolicySubject {
+    fn parse(s: &str) -> Option<Self> {
+        Some(match s {
+            "topic" => PolicySubject::Topic,
+            "start_agent" => PolicySubject::StartAgent,
+            "action" => PolicySubject::Action,
+            "reasoning-action" => PolicySubject::ReasoningAction,
+            "variable" => PolicySubject::Variable,
+            _ => return None,
+        })
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            PolicySubject::Topic => "Topic",
+            PolicySubject::StartAgent => "start_agent",
+            PolicySubject::Action => "Action",
+            PolicySubject::ReasoningAction => "Reasoning action",
+            PolicySubject::Variable => "Variable",
+        }
+    }
+}
+
+/// A check every matching subject must pass.
+#[derive(Debug, Clone)]
+enum Assertion {
+    NameMatches(Regex),
+    RequireDescription,
+    ForbidTarget(String),
+    RequireEscalation,
+    MaxActions(usize),
+}
+
+/// An organization policy, usually declared in a `[policies.<code>]` table.
+///
+/// Policies are [`Rule`]s: the code is the rule id, so a policy can be
+/// turned off or re-levelled under `[rules]` like a built-in rule.
+#[derive(Debug, Clone)]
+pub struct Policy {
+    code: &'static str,
+    subject: PolicySubject,
+    level: LintLevel,
+    message: Option<String>,
+    hint: Option<String>,
+    agent_type: Option<String>,
+    name_pattern: Option<Regex>,
+    assertions: Vec<Assertion>,
+    /// The options the policy was built from, for equality.
+    options: RuleOptions,
+}
+
+impl PartialEq for Policy {
+    fn eq(&self, other: &Self) -> bool {
+        self.code == other.code && self.options == other.options
+    }
+}
+
+impl Eq for Policy {}
+
+impl Policy {
+    /// Build a policy from the keys of its configuration table.
+    pub fn from_options(code: &str, options: RuleOptions) -> Result<Self, String> {
+        let string = |key: &str| match options.get(key) {
+            None => Ok(None),
+            Some(LintValue::String(s)) => Ok(Some(s.clone())),
+            Some(_) => Err(format!("'{}' must be a string", key)),
+        };
+        let regex = |key: &str| {
+            string(key)?
+                .map(|s| Regex::new(&s).map_err(|e| format!("invalid '{}': {}", key, e)))
+                .transpose()
+        };
+        let flag = |key: &str| match options.get(key) {
+            None => Ok(false),
+            Some(LintValue::Bool(b)) => Ok(*b),
+            Some(_) => Err(format!("'{}' must be true or false", key)),
+        };
+
+        let subject = string("subject")?.ok_or("missing 'subject'")?;
+        let subject = PolicySubject::parse(&subject).ok_or_else(|| {
+            format!(
+                "unknown subject '{}', expected topic, start_agent, action, reasoning-action, or variable",
+                subject
+            )
+        })?;
+        let level = match string("severity")? {
+            Some(level) => level.parse()?,
+            None => LintLevel::Error,
+        };
+
+        let mut assertions = Vec::new();
+        if let Some(pattern) = regex("name-matches")? {
+            assertions.push(Assertion::NameMatches(pattern));
+        }
+        if flag("require-description")? {
+            if subject == PolicySubject::ReasoningAction {
+                return Err("'require-description' does not apply to reasoning actions".into());
+            }
+            assertions.push(Assertion::RequireDescription);
+        }
+        if let Some(prefix) = string("forbid-target")? {
+            if subject != PolicySubject::Action {
+                return Err("'forbid-target' only applies to actions".into());
+            }
+            assertions.push(Assertion::ForbidTarget(prefix));
+        }
+        if flag("require-escalation")? {
+            if subject != PolicySubject::Topic {
+                return Err("'require-escalation' only applies to topics".into());
+            }
+            assertions.push(Assertion::RequireEscalation);
+        }
+        if let Some(value) = options.get("max-actions") {
+            if !matches!(subject, PolicySubject::Topic | PolicySubject::StartAgent) {
+                return Err("'max-actions' only applies to topics and start_agent".into());
+            }
+            let max = options.get_usize("max-actions").ok_or_else(|| {
+                format!("'max-actions' must be a non-negative integer, found {:?}", value)
+            })?;
+            assertions.push(Assertion::MaxActions(max));
+        }
+        if assertions.is_empty() {
+            return Err("no assertions; expected one of name-matches, require-description, forbid-target, require-escalation, or max-actions".into());
+        }
+
+        const KEYS: &[&str] = &[
+            "subject",
+            "severity",
+            "message",
+            "hint",
+            "agent-type",
+            "name-pattern",
+            "name-matches",
+            "require-description",
+            "forbid-target",
+            "require-escalation",
+            "max-actions",
+        ];
+        if let Some((key, _)) = options.iter().find(|(key, _)| !KEYS.contains(key)) {
+            return Err(format!("unknown key '{}'", key));
+        }
+
+        Ok(Policy {
+            code: intern(code),
+            subject,
+            level,
+            message: string("message")?,
+            hint: string("hint")?,
+            agent_type: string("agent-type")?,
+            name_pattern: regex("name-pattern")?,
+            assertions,
+            options,
+        })
+    }
+
+    /// The policy's code, reported as the diagnostic code.
+    pub fn code(&self) -> &'static str {
+        self.code
+    }
+
+    /// What the policy applies to.
+    pub fn subject(&self) -> PolicySubject {
+        self.subject
+    }
+
+    fn violation(&self, subject: &Subject<'_>, default_message: String) -> Finding {
+        let message = match &self.message {
+            Some(message) => message.replace("{name}", &subject.name.node),
+            None => default_message,
+        };
+        let mut finding = Finding::new(message, Some(subject.name.span.clone()));
+        finding.hint = self
+            .hint
+            .as_ref()
+            .map(|hint| hint.replace("{name}", &subject.name.node));
+        finding
+    }
+}
+
+impl Rule for Policy {
+    fn id(&self) -> &'static str {
+        self.code
+    }
+
+    fn description(&self) -> &'static str {
+        "Organization policy"
+    }
+
+    fn default_level(&self) -> LintLevel {
+        self.level
+    }
+
+    fn check(&self, cx: &LintContext<'_>) -> Vec<Finding> {
+        if let Some(agent_type) = &self.agent_type {
+            let actual = cx
+                .ast
+                .config
+                .as_ref()
+                .and_then(|c| c.node.agent_type.as_ref());
+            if actual.is_none_or(|t| &t.node != agent_type) {
+                return Vec::new();
+            }
+        }
+
+        let escalating = self
+            .assertions
+            .iter()
+            .any(|a| matches!(a, Assertion::RequireEscalation))
+            .then(|| escalating_topics(cx.ast, cx.graph));
+
+        let mut findings = Vec::new();
+        for subject in subjects(cx.ast, self.subject) {
+            if self
+                .name_pattern
+                .as_ref()
+                .is_some_and(|p| !p.is_match(&subject.name.node))
+            {
+                continue;
+            }
+            let what = format!("{} '{}'", self.subject.label(), subject.name.node);
+            for assertion in &self.assertions {
+                let message = match assertion {
+                    Assertion::NameMatches(pattern) if !pattern.is_match(&subject.name.node) => {
+                        format!("{} does not match {}", what, pattern)
+                    }
+                    Assertion::RequireDescription
+                        if subject.description.is_none_or(|d| d.node.trim().is_empty()) =>
+                    {
+                        format!("{} has no description", what)
+                    }
+                    Assertion::ForbidTarget(prefix) => match subject.target {
+                        Some(target) if target.node.starts_with(prefix.as_str()) => {
+                            format!("{} targets '{}', which is not allowed", what, target.node)
+                        }
+                        _ => continue,
+                    },
+                    Assertion::RequireEscalation
+                        if !escalating
+                            .as_ref()
+                            .is_some_and(|e| e.contains(subject.name.node.as_str())) =>
+                    {
+                        format!("{} has no escalation path", what)
+                    }
+                    Assertion::MaxActions(max) if subject.action_count > *max => format!(
+                        "{} defines {} actions, more than the maximum of {}",
+                        what, subject.action_count, max
+                    ),
+                    _ => continue,
+                };
+                findings.push(self.violation(&subject, message));
+            }
+        }
+        findings
+    }
+}
+
+/// A named element a policy can apply to.
+struct Subject<'a> {
+    name: &'a Spanned<String>,
+    description: Option<&'a Spanned<String>>,
+    target: Option<&'a Spanned<String>>,
+    action_count: usize,
+}
+
+impl<'a> Subject<'a> {
+    fn named(name: &'a Spanned<String>) -> Self {
+        Subject {
+            name,
+            description: None,
+            target: None,
+            action_count: 0,
+        }
+    }
+
+    fn topic(
+        name: &'a Spanned<String>,
+        description: &'a Option<Spanned<String>>,
+        actions: &'a Option<Spanned<ActionsBlock>>,
+    ) -> Self {
+        Subject {
+            description: description.as_ref(),
+            action_count: actions.as_ref().map_or(0, |a| a.node.actions.len()),
+            ..Subject::named(name)
+        }
+    }
+}
+
+fn subjects(ast: &AgentFile, kind: PolicySubject) -> Vec<Subject<'_>> {
+    let blocks = || {
+        let start = ast
+            .start_agent
+            .iter()
+            .map(|s| (&s.node.actions, &s.node.reasoning));
+        let topics = ast
+            .topics
+            .iter()
+            .map(|t| (&t.node.actions, &t.node.reasoning));
+        start.chain(topics)
+    };
+
+    match kind {
+        PolicySubject::Topic => ast
+            .topics
+            .iter()
+            .map(|t| Subject::topic(&t.node.name, &t.node.description, &t.node.actions))
+            .collect(),
+        PolicySubject::StartAgent => ast
+            .start_agent
+            .iter()
+            .map(|s| Subject::topic(&s.node.name, &s.node.description, &s.node.actions))
+            .collect(),
+        PolicySubject::Action => blocks()
+            .flat_map(|(actions, _)| actions.iter().flat_map(|a| &a.node.actions))
+            .map(|a| Subject {
+                description: a.node.description.as_ref(),
+                target: a.node.target.as_ref(),
+                ..Subject::named(&a.node.name)
+            })
+            .collect(),
+        PolicySubject::ReasoningAction => blocks()
+            .flat_map(|(_, reasoning)| reasoning_actions(reasoning))
+            .map(|a| Subject::named(&a.node.name))
+            .collect(),
+        PolicySubject::Variable => ast
+            .variables
+            .iter()
+            .flat_map(|v| &v.node.variables)
+            .map(|v| Subject {
+                description: v.node.description.as_ref(),
+                ..Subject::named(&v.node.name)
+            })
+            .collect(),
+    }
+}
+
+fn reasoning_actions(
+    reasoning: &Option<Spanned<ReasoningBlock>>,
+) -> impl Iterator<Item = &Spanned<crate::ast::ReasoningAction>> {
+    reasoning
+        .iter()
+        .flat_map(|r| r.node.actions.iter())
+        .flat_map(|a| &a.node)
+}
+
+/// Topics that escalate, directly or by transitioning to a topic that does.
+fn escalating_topics<'a>(ast: &'a AgentFile, graph: &RefGraph) -> HashSet<&'a str> {
+    let mut escalating: HashSet<&str> = ast
+        .topics
+        .iter()
+        .filter(|t| {
+            reasoning_actions(&t.node.reasoning)
+                .any(|a| matches!(a.node.target.node, ReasoningActionTarget::Escalate))
+        })
+        .map(|t| t.node.name.node.as_str())
+        .collect();
+
+    // Propagate backwards along transitions until nothing changes.
+    let successors: HashMap<&str, Vec<String>> = ast
+        .topics
+        .iter()
+        .map(|t| {
+            let name = t.node.name.node.as_str();
+            let next = graph
+                .get_topic(name)
+                .map(|idx| graph.find_outgoing_transitions(idx).nodes)
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|idx| match graph.get_node(idx) {
+                    Some(RefNode::Topic { name, .. }) => Some(name.clone()),
+                    _ => None,
+                })
+                .collect();
+            (name, next)
+        })
+        .collect();
+    loop {
+        let before = escalating.len();
+        for (name, next) in &successors {
+            if next.iter().any(|n| escalating.contains(n.as_str())) {
+                escalating.insert(name);
+            }
+        }
+        if escalating.len() == before {
+            return escalating;
+        }
+    }
+}
+
+/// Give a policy code the `'static` lifetime rule ids have.
+///
+/// Each distinct code is allocated once, so reloading configuration does
+/// not grow memory.
+fn intern(code: &str) -> &'static str {
+    static CODES: OnceLock<Mutex<HashSet<&'static str>>> = OnceLock::new();
+    let mut codes = CODES
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    if let Some(code) = codes.get(code) {
+        return code;
+    }
+    let code: &'static str = Box::leak(code.to_string().into_boxed_str());
+    codes.insert(code);
+    code
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lint::{run_lints, LintConfig};
+    use crate::validation::Severity;
+
+    const SOURCE: &str = r#"
+config:
+   agent_name: "Support"
+   agent_type: "AgentforceServiceAgent"
+
+variables:
+   OrderId: mutable string
+
+start_agent main:
+   description: "Route"
+   reasoning:
+      instructions: "Route"
+      actions:
+         orders: @utils.transition to @topic.orders
+         billing: @utils.transition to @topic.billing
+
+topic orders:
+   description: "Orders"
+   actions:
+      refund:
+         description: "Refund"
+         target: "apex://RefundService"
+   reasoning:
+      instructions: "Help"
+      actions:
+         human: @utils.transition to @topic.escalation
+
+topic escalation:
+   description: "Escalate"
+   reasoning:
+      instructions: "Hand off"
+      actions:
+         escalate: @utils.escalate
+
+topic billing:
+   description: "Billing"
+   reasoning:
+      instructions: "Help"
+"#;
+
+    const POLICIES: &str = r#"
+[policies.topic-escalation]
+subject = "topic"
+require-escalation = true
+hint = "Add an @utils.escalate action to '{name}'"
+
+[policies.no-apex]
+subject = "action"
+agent-type = "AgentforceServiceAgent"
+forbid-target = "apex://"
+severity = "warn"
+
+[policies.variable-names]
+subject = "variable"
+name-matches = "^[a-z_]+$"
+message = "Rename {name}"
+
+[rules]
+naming-convention = "off"
+require-descriptions = "off"
+"#;
+
+    fn lint(config: &str) -> Vec<crate::lint::LintDiagnostic> {
+        let ast = crate::parse(SOURCE).unwrap();
+        let graph = RefGraph::from_ast(&ast).unwrap();
+        run_lints(&ast, &graph, &LintConfig::from_toml(config).unwrap())
+    }
+
+    #[test]
+    fn test_policies() {
+        let found: Vec<_> = lint(POLICIES)
+            .into_iter()
+            .map(|d| (d.rule, d.severity, d.message, d.hint))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("variable-names", Severity::Error, "Rename OrderId".to_string(), None),
+                (
+                    "no-apex",
+                    Severity::Warning,
+                    "Action 'refund' targets 'apex://RefundService', which is not allowed"
+                        .to_string(),
+                    None
+                ),
+                (
+                    "topic-escalation",
+                    Severity::Error,
+                    "Topic 'billing' has no escalation path".to_string(),
+                    Some("Add an @utils.escalate action to 'billing'".to_string())
+                ),
+            ]
+        );
+
+        // Policies can be re-levelled like built-in rules
+        let config = format!("{}no-apex = \"off\"\n", POLICIES);
+        assert!(lint(&config).iter().all(|d| d.rule != "no-apex"));
+
+        let other_agent = POLICIES.replace("AgentforceServiceAgent", "AgentforceEmployeeAgent");
+        assert!(lint(&other_agent).iter().all(|d| d.rule != "no-apex"));
+    }
+
+    #[test]
+    fn test_invalid_policies() {
+        let err = |toml: &str| LintConfig::from_toml(toml).unwrap_err().to_string();
+        assert_eq!(
+            err("[policies.p]\nsubject = \"variable\"\nrequire-escalation = true\n"),
+            "line 1: policy 'p': 'require-escalation' only applies to topics"
+        );
+        assert_eq!(
+            err("[rules]\n\n[policies.p]\nsubject = \"topic\"\n"),
+            "line 3: policy 'p': no assertions; expected one of name-matches, require-description, forbid-target, require-escalation, or max-actions"
+        );
+        assert_eq!(
+            err("[policies.p]\nsubject = \"topic\"\nmax-actions = 3\ncolour = \"red\"\n"),
+            "line 1: policy 'p': unknown key 'colour'"
+        );
+        assert!(err("[policies.p]\nsubject = \"variable\"\nname-matches = \"[\"\n")
+            .starts_with("line 1: policy 'p': invalid 'name-matches'"));
+    }
+}
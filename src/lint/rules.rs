@@ -0,0 +1,426 @@
+//! Built-in lint rules.
+
+use super::{Finding, LintConfig, LintContext, Rule};
+use crate::ast::{
+    ActionsBlock, AgentFile, DirectiveBlock, InstructionPart, Instructions, ReasoningBlock,
+    Spanned, Stmt,
+};
+
+/// Limits the number of topics in an agent.
+///
+/// Option `max` (default 20).
+pub struct MaxTopicCount;
+
+impl Rule for MaxTopicCount {
+    fn id(&self) -> &'static str {
+        "max-topic-count"
+    }
+
+    fn description(&self) -> &'static str {
+        "Agents with many topics are hard for the planner to route between"
+    }
+
+    fn check(&self, cx: &LintContext<'_>) -> Vec<Finding> {
+        let max = cx.options.get_usize("max").unwrap_or(20);
+        let topics = &cx.ast.topics;
+        if topics.len() <= max {
+            return Vec::new();
+        }
+        let first_extra = &topics[max].node.name;
+        vec![Finding::new(
+            format!("Agent has {} topics, more than the maximum of {}", topics.len(), max),
+            Some(first_extra.span.clone()),
+        )
+        .with_hint("Split the agent, or merge topics that handle related requests")]
+    }
+}
+
+/// Requires descriptions on variables and action inputs and outputs.
+///
+/// Topic and action descriptions are already required by validation.
+pub struct RequireDescriptions;
+
+impl Rule for RequireDescriptions {
+    fn id(&self) -> &'static str {
+        "require-descriptions"
+    }
+
+    fn description(&self) -> &'static str {
+        "Variables and action parameters should be described"
+    }
+
+    fn check(&self, cx: &LintContext<'_>) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        let mut require = |what: String,
+                           name: &Spanned<String>,
+                           description: &Option<Spanned<String>>| {
+            if description
+                .as_ref()
+                .is_none_or(|d| d.node.trim().is_empty())
+            {
+                findings.push(
+                    Finding::new(format!("{} has no description", what), Some(name.span.clone()))
+                        .with_hint("Add a `description:` so the planner knows what it holds"),
+                );
+            }
+        };
+
+        if let Some(variables) = &cx.ast.variables {
+            for v in &variables.node.variables {
+                require(
+                    format!("Variable '{}'", v.node.name.node),
+                    &v.node.name,
+                    &v.node.description,
+                );
+            }
+        }
+
+        for actions in action_blocks(cx) {
+            for action in &actions.node.actions {
+                let action_name = &action.node.name.node;
+                for (kind, params) in [
+                    ("Input", &action.node.inputs),
+                    ("Output", &action.node.outputs),
+                ] {
+                    for p in params.iter().flat_map(|p| &p.node) {
+                        require(
+                            format!("{} '{}' of action '{}'", kind, p.node.name.node, action_name),
+                            &p.node.name,
+                            &p.node.description,
+                        );
+                    }
+                }
+            }
+        }
+
+        findings
+    }
+}
+
+/// Limits how deeply `if` conditions may nest.
+///
+/// Option `max-depth` (default 3). Directive blocks and dynamic
+/// instructions are checked.
+pub struct NoDeepNesting;
+
+impl Rule for NoDeepNesting {
+    fn id(&self) -> &'static str {
+        "no-deep-nesting"
+    }
+
+    fn description(&self) -> &'static str {
+        "Deeply nested conditions are hard to follow"
+    }
+
+    fn check(&self, cx: &LintContext<'_>) -> Vec<Finding> {
+        let max = cx.options.get_usize("max-depth").unwrap_or(3);
+        let mut findings = Vec::new();
+        for block in topic_like_blocks(cx) {
+            for directives in [block.before_reasoning, block.after_reasoning]
+                .into_iter()
+                .flatten()
+            {
+                check_stmts(&directives.node.statements, 1, max, &mut findings);
+            }
+            if let Some(Instructions::Dynamic(parts)) = block
+                .reasoning
+                .and_then(|r| r.node.instructions.as_ref())
+                .map(|i| &i.node)
+            {
+                check_parts(parts, 1, max, &mut findings);
+            }
+        }
+        findings
+    }
+}
+
+fn nesting_finding(depth: usize, max: usize, condition: &Spanned<crate::ast::Expr>) -> Finding {
+    Finding::new(
+        format!("Conditions nested {} deep, more than the maximum of {}", depth, max),
+        Some(condition.span.clone()),
+    )
+    .with_hint("Combine the conditions, or move the inner branch into its own topic")
+}
+
+fn check_stmts(stmts: &[Spanned<Stmt>], depth: usize, max: usize, findings: &mut Vec<Finding>) {
+    for stmt in stmts {
+        if let Stmt::If {
+            condition,
+            then_block,
+            else_block,
+        } = &stmt.node
+        {
+            if depth > max {
+                findings.push(nesting_finding(depth, max, condition));
+                continue;
+            }
+            check_stmts(then_block, depth + 1, max, findings);
+            if let Some(else_block) = else_block {
+                check_stmts(else_block, depth + 1, max, findings);
+            }
+        }
+    }
+}
+
+fn check_parts(
+    parts: &[Spanned<InstructionPart>],
+    depth: usize,
+    max: usize,
+    findings: &mut Vec<Finding>,
+) {
+    for part in parts {
+        if let InstructionPart::Conditional {
+            condition,
+            then_parts,
+            else_parts,
+        } = &part.node
+        {
+            if depth > max {
+                findings.push(nesting_finding(depth, max, condition));
+                continue;
+            }
+            check_parts(then_parts, depth + 1, max, findings);
+            if let Some(else_parts) = else_parts {
+                check_parts(else_parts, depth + 1, max, findings);
+            }
+        }
+    }
+}
+
+/// Enforces a naming style for topics, actions and variables.
+///
+/// Option `style`: `"snake_case"` (default) or `"camelCase"`. Action
+/// parameters are not checked, since their names are dictated by the
+/// Flow or Apex target.
+pub struct NamingConvention;
+
+#[derive(Clone, Copy)]
+enum NamingStyle {
+    SnakeCase,
+    CamelCase,
+}
+
+impl NamingStyle {
+    fn name(self) -> &'static str {
+        match self {
+            NamingStyle::SnakeCase => "snake_case",
+            NamingStyle::CamelCase => "camelCase",
+        }
+    }
+
+    fn matches(self, name: &str) -> bool {
+        let mut chars = name.chars();
+        let starts_lower = chars.next().is_some_and(|c| c.is_ascii_lowercase());
+        starts_lower
+            && chars.all(|c| match self {
+                NamingStyle::SnakeCase => c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_',
+                NamingStyle::CamelCase => c.is_ascii_alphanumeric(),
+            })
+    }
+
+    fn convert(self, name: &str) -> String {
+        let mut words: Vec<String> = Vec::new();
+        let mut prev_lower = false;
+        for c in name.chars() {
+            if c == '_' || c == '-' {
+                words.push(String::new());
+                prev_lower = false;
+                continue;
+            }
+            if words.is_empty() || (c.is_uppercase() && prev_lower) {
+                words.push(String::new());
+            }
+            prev_lower = c.is_lowercase() || c.is_ascii_digit();
+            words.last_mut().unwrap().extend(c.to_lowercase());
+        }
+        let mut words = words.into_iter().filter(|w| !w.is_empty());
+        match self {
+            NamingStyle::SnakeCase => words.collect::<Vec<_>>().join("_"),
+            NamingStyle::CamelCase => {
+                let mut out = words.next().unwrap_or_default();
+                for word in words {
+                    let mut chars = word.chars();
+                    out.extend(chars.next().map(|c| c.to_ascii_uppercase()));
+                    out.push_str(chars.as_str());
+                }
+                out
+            }
+        }
+    }
+}
+
+impl Rule for NamingConvention {
+    fn id(&self) -> &'static str {
+        "naming-convention"
+    }
+
+    fn description(&self) -> &'static str {
+        "Topics, actions and variables should follow one naming style"
+    }
+
+    fn check(&self, cx: &LintContext<'_>) -> Vec<Finding> {
+        let style = match cx.options.get_str("style").unwrap_or("snake_case") {
+            "snake_case" => NamingStyle::SnakeCase,
+            "camelCase" => NamingStyle::CamelCase,
+            other => {
+                return vec![Finding::new(
+                    format!("Unknown naming style '{}', expected snake_case or camelCase", other),
+                    None,
+                )]
+            }
+        };
+
+        let mut findings = Vec::new();
+        let mut check = |kind: &str, name: &Spanned<String>| {
+            if !style.matches(&name.node) {
+                findings.push(
+                    Finding::new(
+                        format!("{} '{}' is not {}", kind, name.node, style.name()),
+                        Some(name.span.clone()),
+                    )
+                    .with_hint(format!("Rename it to '{}'", style.convert(&name.node))),
+                );
+            }
+        };
+
+        if let Some(variables) = &cx.ast.variables {
+            for v in &variables.node.variables {
+                check("Variable", &v.node.name);
+            }
+        }
+        if let Some(start) = &cx.ast.start_agent {
+            check("start_agent", &start.node.name);
+        }
+        for topic in &cx.ast.topics {
+            check("Topic", &topic.node.name);
+        }
+        for block in topic_like_blocks(cx) {
+            for action in block.actions.iter().flat_map(|a| &a.node.actions) {
+                check("Action", &action.node.name);
+            }
+            for action in block
+                .reasoning
+                .and_then(|r| r.node.actions.as_ref())
+                .iter()
+                .flat_map(|a| &a.node)
+            {
+                check("Reasoning action", &action.node.name);
+            }
+        }
+
+        findings
+    }
+}
+
+/// Limits the estimated instruction tokens of each topic and start_agent.
+///
+/// Option `max` (default [`DEFAULT_MAX`](Self::DEFAULT_MAX)). A block's
+/// estimate includes the global system instructions it also receives.
+pub struct TokenBudget;
+
+impl TokenBudget {
+    /// Budget used when the configuration does not set `max`.
+    pub const DEFAULT_MAX: usize = 2000;
+
+    /// The budget `config` sets for this rule.
+    pub fn max(config: &LintConfig) -> usize {
+        config
+            .rule(TokenBudget.id())
+            .and_then(|rule| rule.options.get_usize("max"))
+            .unwrap_or(Self::DEFAULT_MAX)
+    }
+
+    /// Estimated instruction tokens of the start_agent and each topic, in
+    /// source order.
+    pub fn estimates(ast: &AgentFile) -> Vec<(&Spanned<String>, usize)> {
+        let global = ast
+            .system
+            .as_ref()
+            .and_then(|s| s.node.instructions.as_ref())
+            .map_or(0, |i| i.node.estimated_tokens());
+        let blocks = ast
+            .start_agent
+            .iter()
+            .map(|sa| (&sa.node.name, &sa.node.system, &sa.node.reasoning))
+            .chain(
+                ast.topics
+                    .iter()
+                    .map(|t| (&t.node.name, &t.node.system, &t.node.reasoning)),
+            );
+        blocks
+            .map(|(name, system, reasoning)| {
+                let system = system.as_ref().and_then(|s| s.node.instructions.as_ref());
+                let reasoning = reasoning
+                    .as_ref()
+                    .and_then(|r| r.node.instructions.as_ref());
+                let own: usize = [system, reasoning]
+                    .into_iter()
+                    .flatten()
+                    .map(|i| i.node.estimated_tokens())
+                    .sum();
+                (name, global + own)
+            })
+            .collect()
+    }
+}
+
+impl Rule for TokenBudget {
+    fn id(&self) -> &'static str {
+        "token-budget"
+    }
+
+    fn description(&self) -> &'static str {
+        "Long instructions crowd the prompt"
+    }
+
+    fn check(&self, cx: &LintContext<'_>) -> Vec<Finding> {
+        let max = cx.options.get_usize("max").unwrap_or(Self::DEFAULT_MAX);
+        Self::estimates(cx.ast)
+            .into_iter()
+            .filter(|(_, estimate)| *estimate > max)
+            .map(|(name, estimate)| {
+                Finding::new(
+                    format!(
+                        "'{}' uses ~{} instruction tokens, over the budget of {}",
+                        name.node, estimate, max
+                    ),
+                    Some(name.span.clone()),
+                )
+                .with_hint(
+                    "Long instructions crowd the prompt; move detail into actions or split the topic",
+                )
+            })
+            .collect()
+    }
+}
+
+/// The blocks `start_agent` and topics have in common.
+struct TopicLike<'a> {
+    actions: Option<&'a Spanned<ActionsBlock>>,
+    before_reasoning: Option<&'a Spanned<DirectiveBlock>>,
+    reasoning: Option<&'a Spanned<ReasoningBlock>>,
+    after_reasoning: Option<&'a Spanned<DirectiveBlock>>,
+}
+
+fn topic_like_blocks<'a>(cx: &LintContext<'a>) -> Vec<TopicLike<'a>> {
+    let start = cx.ast.start_agent.iter().map(|s| TopicLike {
+        actions: s.node.actions.as_ref(),
+        before_reasoning: s.node.before_reasoning.as_ref(),
+        reasoning: s.node.reasoning.as_ref(),
+        after_reasoning: s.node.after_reasoning.as_ref(),
+    });
+    let topics = cx.ast.topics.iter().map(|t| TopicLike {
+        actions: t.node.actions.as_ref(),
+        before_reasoning: t.node.before_reasoning.as_ref(),
+        reasoning: t.node.reasoning.as_ref(),
+        after_reasoning: t.node.after_reasoning.as_ref(),
+    });
+    start.chain(topics).collect()
+}
+
+fn action_blocks<'a>(cx: &LintContext<'a>) -> Vec<&'a Spanned<ActionsBlock>> {
+    topic_like_blocks(cx)
+        .into_iter()
+        .filter_map(|b| b.actions)
+        .collect()
+}
@@ -0,0 +1,263 @@
+//! Markdown rendering for descriptions and instructions.
+//!
+//! Descriptions and instructions are free text that often contain
+//! markdown-ish content (`## Rules`, `*` bullets, `|` pipes). Dumped raw into
+//! an LSP hover or generated docs they render poorly or break the
+//! surrounding layout. This module normalizes such text into safe Markdown:
+//!
+//! - headings are demoted to bold lines so they don't outrank the hover/doc title
+//! - `*`, `+` and `•` bullets become `-`; `1)` numbering becomes `1.`
+//! - leading `|` instruction pipes are stripped; other pipes are escaped so
+//!   they aren't read as tables
+//! - `<`, `>` and `&` are escaped so they aren't read as HTML
+//! - `{!expr}` template interpolations are shown as inline code
+//! - single line breaks are preserved as hard breaks
+//!
+//! # Example
+//!
+//! ```rust
+//! use busbar_sf_agentscript::markdown::render_markdown;
+//!
+//! let md = render_markdown("## Rules\n* Be polite\n* Use <name>");
+//! assert_eq!(md, "**Rules**\n\n- Be polite\n- Use &lt;name&gt;");
+//! ```
+
+use crate::ast::{InstructionPart, Instructions, Spanned};
+use crate::serializer::serialize_expr;
+
+/// A normalized source line.
+enum Line {
+    Blank,
+    Heading(String),
+    Bullet(String),
+    Numbered(String, String),
+    Text(String),
+}
+
+/// Render free text (a description or instruction body) as safe Markdown.
+pub fn render_markdown(text: &str) -> String {
+    let lines: Vec<Line> = text.lines().map(classify).collect();
+
+    let mut out = String::new();
+    let mut prev: Option<&Line> = None;
+    for line in &lines {
+        match (prev, line) {
+            (_, Line::Blank) => {}
+            (None, _) => {}
+            (Some(Line::Blank), _) => out.push_str("\n\n"),
+            // Keep list items together, but separate lists from paragraphs
+            (Some(Line::Bullet(_) | Line::Numbered(..)), Line::Bullet(_) | Line::Numbered(..)) => {
+                out.push('\n')
+            }
+            (Some(Line::Text(_)), Line::Text(_)) => out.push_str("  \n"),
+            (Some(_), _) => out.push_str("\n\n"),
+        }
+        match line {
+            Line::Blank => {}
+            Line::Heading(h) => {
+                out.push_str("**");
+                out.push_str(h);
+                out.push_str("**");
+            }
+            Line::Bullet(b) => {
+                out.push_str("- ");
+                out.push_str(b);
+            }
+            Line::Numbered(n, t) => {
+                out.push_str(n);
+                out.push_str(". ");
+                out.push_str(t);
+            }
+            Line::Text(t) => out.push_str(t),
+        }
+        prev = Some(line);
+    }
+    out
+}
+
+/// Render instructions as safe Markdown.
+///
+/// Dynamic conditionals are shown as italic `if`/`else` markers followed by
+/// their content.
+pub fn render_instructions(instructions: &Instructions) -> String {
+    let text = match instructions {
+        Instructions::Simple(text) => text.clone(),
+        Instructions::Static(lines) => lines
+            .iter()
+            .map(|l| l.node.as_str())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Instructions::Dynamic(parts) => {
+            let mut text = String::new();
+            flatten_parts(parts, &mut text);
+            text
+        }
+    };
+    render_markdown(&text)
+}
+
+fn flatten_parts(parts: &[Spanned<InstructionPart>], out: &mut String) {
+    for part in parts {
+        match &part.node {
+            InstructionPart::Text(text) => {
+                if !out.is_empty() && !out.ends_with('\n') {
+                    out.push('\n');
+                }
+                out.push_str(text);
+            }
+            InstructionPart::Heading { level, title } => {
+                out.push_str(&format!("\n\n{} {}\n", "#".repeat(*level), title));
+            }
+            InstructionPart::Interpolation(expr) => {
+                if !out.is_empty() && !out.ends_with(['\n', ' ']) {
+                    out.push(' ');
+                }
+                out.push_str(&format!("{{!{}}}", serialize_expr(expr)));
+            }
+            InstructionPart::Conditional {
+                condition,
+                then_parts,
+                else_parts,
+            } => {
+                out.push_str(&format!("\n\n{{if {}}}\n", serialize_expr(&condition.node)));
+                flatten_parts(then_parts, out);
+                if let Some(else_parts) = else_parts {
+                    out.push_str("\n\n{else}\n");
+                    flatten_parts(else_parts, out);
+                }
+                out.push_str("\n\n");
+            }
+        }
+    }
+}
+
+fn classify(raw: &str) -> Line {
+    let mut line = raw.trim();
+
+    // Instruction pipes: `| text`
+    if let Some(rest) = line.strip_prefix('|') {
+        line = rest.trim();
+    }
+    if line.is_empty() {
+        return Line::Blank;
+    }
+
+    // Control markers produced by `render_instructions`
+    if let Some(inner) = line.strip_prefix("{if ").and_then(|l| l.strip_suffix('}')) {
+        return Line::Text(format!("_if_ `{}`:", inner));
+    }
+    if line == "{else}" {
+        return Line::Text("_else:_".to_string());
+    }
+
+    let hashes = line.chars().take_while(|&c| c == '#').count();
+    if (1..=6).contains(&hashes) && line[hashes..].starts_with(' ') {
+        return Line::Heading(escape_inline(line[hashes..].trim()));
+    }
+
+    for bullet in ["- ", "* ", "+ ", "• "] {
+        if let Some(rest) = line.strip_prefix(bullet) {
+            return Line::Bullet(escape_inline(rest.trim()));
+        }
+    }
+
+    let digits = line.chars().take_while(char::is_ascii_digit).count();
+    if digits > 0 {
+        let rest = &line[digits..];
+        if let Some(text) = rest.strip_prefix(". ").or_else(|| rest.strip_prefix(") ")) {
+            return Line::Numbered(line[..digits].to_string(), escape_inline(text.trim()));
+        }
+    }
+
+    Line::Text(escape_inline(line))
+}
+
+/// Escape characters that would be interpreted as HTML or tables, and wrap
+/// `{!expr}` interpolations in inline code.
+fn escape_inline(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("{!") {
+        out.push_str(&escape_chars(&rest[..start]));
+        match rest[start..].find('}') {
+            Some(end) => {
+                let interp = &rest[start..start + end + 1];
+                out.push('`');
+                out.push_str(&interp.replace('`', "'"));
+                out.push('`');
+                rest = &rest[start + end + 1..];
+            }
+            None => {
+                out.push_str(&escape_chars(&rest[start..]));
+                rest = "";
+            }
+        }
+    }
+    out.push_str(&escape_chars(rest));
+    out
+}
+
+fn escape_chars(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '|' => out.push_str("\\|"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{Expr, Reference};
+
+    #[test]
+    fn test_headings_and_lists() {
+        let md = render_markdown("Intro line\n## Rules\n1) First\n2) Second\n• Third");
+        assert_eq!(md, "Intro line\n\n**Rules**\n\n1. First\n2. Second\n- Third");
+    }
+
+    #[test]
+    fn test_line_breaks_and_paragraphs() {
+        let md = render_markdown("line one\nline two\n\nnext paragraph");
+        assert_eq!(md, "line one  \nline two\n\nnext paragraph");
+    }
+
+    #[test]
+    fn test_escapes_pipes_html_and_interpolations() {
+        let md = render_markdown("| Say a | b to <user> & {!@variables.name}");
+        assert_eq!(md, "Say a \\| b to &lt;user&gt; &amp; `{!@variables.name}`");
+    }
+
+    #[test]
+    fn test_render_dynamic_instructions() {
+        let instructions = Instructions::Dynamic(vec![
+            Spanned::new(InstructionPart::Text("Hello".to_string()), 0..5),
+            Spanned::new(
+                InstructionPart::Interpolation(Expr::Reference(Reference::new(
+                    "variables",
+                    vec!["name".to_string()],
+                ))),
+                5..10,
+            ),
+            Spanned::new(
+                InstructionPart::Conditional {
+                    condition: Spanned::new(Expr::Bool(true), 10..14),
+                    then_parts: vec![Spanned::new(
+                        InstructionPart::Text("- Be brief".to_string()),
+                        14..20,
+                    )],
+                    else_parts: None,
+                },
+                10..20,
+            ),
+        ]);
+        let md = render_instructions(&instructions);
+        assert_eq!(md, "Hello `{!@variables.name}`\n\n_if_ `True`:\n\n- Be brief");
+    }
+}
@@ -0,0 +1,140 @@
+//! Minimal reproducers for parser bugs.
+//!
+//! [`minimize`] shrinks an agent file to a small input that still has some
+//! property, usually "fails to parse with this error" or "makes the parser
+//! panic". It runs delta debugging (Zeller's ddmin) first over lines and
+//! then over the characters that are left, so the result is 1-minimal:
+//! removing any single line or character loses the property.
+//!
+//! # Example
+//!
+//! ```rust
+//! use busbar_sf_agentscript::minimize::minimize;
+//! use busbar_sf_agentscript::parse;
+//!
+//! let source = r#"config:
+//!    agent_name: "Support"
+//!
+//! topic main:
+//!    description: "Main"
+//!    reasoning:
+//!       instructions: "Help"
+//!       actions:
+//!          go: @utils.transition to
+//! "#;
+//!
+//! let still_fails = |s: &str| {
+//!     parse(s).is_err_and(|errors| errors.iter().any(|e| e.contains("transition")))
+//! };
+//! let minimal = minimize(source, still_fails);
+//! assert!(still_fails(&minimal));
+//! assert!(minimal.len() < source.len());
+//! ```
+//!
+//! To minimize a panic, wrap the parser call in
+//! [`std::panic::catch_unwind`] inside the predicate.
+
+use std::collections::HashSet;
+
+/// Shrink `source` to a minimal input for which `predicate` still holds.
+///
+/// `predicate` must be deterministic. If it does not hold for `source`
+/// itself, `source` is returned unchanged.
+pub fn minimize(source: &str, mut predicate: impl FnMut(&str) -> bool) -> String {
+    if !predicate(source) {
+        return source.to_string();
+    }
+    let mut tested = HashSet::new();
+    let mut test = |candidate: &str| tested.insert(candidate.to_string()) && predicate(candidate);
+
+    let lines: Vec<&str> = source.split_inclusive('\n').collect();
+    let lines = ddmin(lines, &mut test).concat();
+    let chars: Vec<&str> = lines
+        .char_indices()
+        .map(|(i, c)| &lines[i..i + c.len_utf8()])
+        .collect();
+    ddmin(chars, &mut test).concat()
+}
+
+/// Remove as many `units` as possible while `test` holds for their
+/// concatenation. `test` is assumed to hold for all of `units`.
+fn ddmin<'a>(mut units: Vec<&'a str>, test: &mut impl FnMut(&str) -> bool) -> Vec<&'a str> {
+    let mut granularity = 2;
+    while units.len() >= 2 {
+        let chunk = units.len().div_ceil(granularity);
+        let chunks: Vec<&[&'a str]> = units.chunks(chunk).collect();
+
+        // A single chunk may be enough on its own
+        if let Some(subset) = chunks.iter().find(|c| test(&c.concat())) {
+            units = subset.to_vec();
+            granularity = 2;
+            continue;
+        }
+        // Otherwise try dropping one chunk at a time
+        let complement = (0..chunks.len()).find_map(|skip| {
+            let rest: Vec<&'a str> = chunks
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| *i != skip)
+                .flat_map(|(_, c)| c.iter().copied())
+                .collect();
+            test(&rest.concat()).then_some(rest)
+        });
+        if let Some(rest) = complement {
+            units = rest;
+            granularity = (granularity - 1).max(2);
+            continue;
+        }
+
+        if granularity >= units.len() {
+            break;
+        }
+        granularity = (granularity * 2).min(units.len());
+    }
+    units
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_minimize_is_one_minimal() {
+        let source = "alpha\nbeta\ngamma BUG here\ndelta\n";
+        let minimal = minimize(source, |s| s.contains("BUG"));
+        assert_eq!(minimal, "BUG");
+
+        // Scattered requirements are all kept, in order
+        let needs_all = |s: &str| s.contains('a') && s.contains("BU") && s.contains('d');
+        let minimal = minimize(source, needs_all);
+        assert!(needs_all(&minimal));
+        assert_eq!(minimal.len(), 4);
+    }
+
+    #[test]
+    fn test_minimize_parse_error() {
+        let source = r#"config:
+   agent_name: "Support"
+
+variables:
+   order_id: mutable string = ""
+
+topic main:
+   description: "Main"
+   reasoning:
+      instructions: "Help"
+      actions:
+         lookup: @actions.lookup
+            with id=@variables.order_id
+         go: @utils.transition to
+"#;
+        let fails = |s: &str| crate::parse(s).is_err();
+        let minimal = minimize(source, fails);
+        assert!(fails(&minimal));
+        assert!(minimal.len() < source.len() / 4, "{:?}", minimal);
+
+        // Inputs that do not fail are returned unchanged
+        let valid = "topic main:\n   description: \"Main\"\n";
+        assert_eq!(minimize(valid, fails), valid);
+    }
+}
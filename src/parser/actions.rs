@@ -150,8 +150,8 @@ enum ActionDefEntry {
     IncludeInProgressIndicator(Spanned<bool>),
     ProgressIndicatorMessage(Spanned<String>),
     Target(Spanned<String>),
-    Inputs(Vec<Spanned<ParamDef>>),
-    Outputs(Vec<Spanned<ParamDef>>),
+    Inputs(Spanned<Vec<Spanned<ParamDef>>>),
+    Outputs(Spanned<Vec<Spanned<ParamDef>>>),
 }
 
 /// Helper enum for inputs/outputs block entries (can be param or description).
@@ -261,7 +261,12 @@ pub(crate) fn action_def<'tokens, 'src: 'tokens>() -> impl Parser<
                         entries.unwrap_or_default()
                     })
                     .labelled("inputs block")
-                    .map(|entries| ActionDefEntry::Inputs(extract_params(entries))),
+                    .map_with(|entries, e| {
+                        ActionDefEntry::Inputs(Spanned::new(
+                            extract_params(entries),
+                            to_ast_span(e.span()),
+                        ))
+                    }),
                 just(Token::Outputs)
                     .map_with(|_, e| e.span()) // Capture the span of 'outputs'
                     .then_ignore(just(Token::Colon))
@@ -287,7 +292,12 @@ pub(crate) fn action_def<'tokens, 'src: 'tokens>() -> impl Parser<
                         entries.unwrap_or_default()
                     })
                     .labelled("outputs block")
-                    .map(|entries| ActionDefEntry::Outputs(extract_params(entries))),
+                    .map_with(|entries, e| {
+                        ActionDefEntry::Outputs(Spanned::new(
+                            extract_params(entries),
+                            to_ast_span(e.span()),
+                        ))
+                    }),
             ))
             .separated_by(skip_block_noise())
             .allow_trailing()
@@ -306,6 +316,7 @@ pub(crate) fn action_def<'tokens, 'src: 'tokens>() -> impl Parser<
                 inputs: None,
                 outputs: None,
                 target: None,
+                doc: None,
             };
 
             for entry in entries {
@@ -322,12 +333,8 @@ pub(crate) fn action_def<'tokens, 'src: 'tokens>() -> impl Parser<
                         def.progress_indicator_message = Some(m)
                     }
                     ActionDefEntry::Target(t) => def.target = Some(t),
-                    ActionDefEntry::Inputs(i) => {
-                        def.inputs = Some(Spanned::new(i, to_ast_span(e.span())))
-                    }
-                    ActionDefEntry::Outputs(o) => {
-                        def.outputs = Some(Spanned::new(o, to_ast_span(e.span())))
-                    }
+                    ActionDefEntry::Inputs(i) => def.inputs = Some(i),
+                    ActionDefEntry::Outputs(o) => def.outputs = Some(o),
                 }
             }
 
@@ -0,0 +1,383 @@
+//! Cancellable and async parsing.
+//!
+//! [`parse_cancellable`] and [`parse_async`] lex the file once, then parse
+//! it one top-level block at a time, checking a [`CancellationToken`]
+//! before each block. An editor that receives a newer version of a
+//! document can cancel the token and drop the stale parse instead of
+//! waiting for it. [`parse_with_progress`] also reports a [`ParseProgress`]
+//! after each block, for progress bars on large files. The result is the
+//! same as
+//! [`parse_with_structured_errors_all`](super::parse_with_structured_errors_all).
+
+use super::limits::{limit_error, ParseOptions};
+use super::primitives::{self, SpannedToken};
+use super::profile::split_blocks;
+use super::{
+    assemble_file, doc_comments, structured_lex_error, structured_parse_error, top_level_blocks,
+    TopLevelBlock,
+};
+use crate::ast::AgentFile;
+use crate::error::ParseErrorInfo;
+use crate::lexer;
+use chumsky::input::Input as _;
+use chumsky::span::Span as _;
+use chumsky::Parser as _;
+use serde::Serialize;
+use std::future::Future;
+use std::ops::Range;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use thiserror::Error;
+
+/// A partial AST and every error found, as from
+/// [`parse_with_structured_errors_all`](super::parse_with_structured_errors_all).
+pub type ParseOutput = (Option<AgentFile>, Vec<ParseErrorInfo>);
+
+/// A shared flag that tells a parse to stop.
+///
+/// Clones share the flag, so one can be handed to the parse and another
+/// kept to cancel it.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    /// Create a token that is not cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancel every parse holding this token or a clone of it.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether [`cancel`](Self::cancel) has been called.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// The parse was abandoned because its token was cancelled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+#[error("parse cancelled")]
+pub struct Cancelled;
+
+/// Parse a file, giving up at the next block boundary once `token` is
+/// cancelled.
+///
+/// ```rust
+/// use busbar_sf_agentscript::parser::{parse_cancellable, Cancelled, CancellationToken};
+///
+/// let source = "topic main:\n   description: \"Main\"\n";
+/// let token = CancellationToken::new();
+/// let (ast, errors) = parse_cancellable(source, &token).unwrap();
+/// assert!(errors.is_empty());
+/// assert_eq!(ast.unwrap().topics.len(), 1);
+///
+/// token.cancel();
+/// assert_eq!(parse_cancellable(source, &token).unwrap_err(), Cancelled);
+/// ```
+pub fn parse_cancellable(
+    source: &str,
+    token: &CancellationToken,
+) -> Result<ParseOutput, Cancelled> {
+    parse_with_progress(source, token, |_| {})
+}
+
+/// How far a parse has got.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct ParseProgress {
+    /// Top-level blocks parsed so far.
+    pub blocks_parsed: usize,
+    /// Top-level blocks in the file, known once the file is lexed.
+    pub blocks_total: usize,
+    /// Source bytes up to the end of the last parsed block.
+    pub bytes_parsed: usize,
+    /// Length of the source in bytes.
+    pub bytes_total: usize,
+}
+
+/// Parse a file like [`parse_cancellable`], calling `on_progress` after
+/// lexing and after each top-level block.
+///
+/// `on_progress` may cancel `token` to stop the parse.
+///
+/// ```rust
+/// use busbar_sf_agentscript::parser::{parse_with_progress, CancellationToken};
+///
+/// let source = "topic main:\n   description: \"Main\"\n\ntopic other:\n   description: \"Other\"\n";
+/// let mut reports = Vec::new();
+/// let (ast, _) = parse_with_progress(source, &CancellationToken::new(), |p| reports.push(p)).unwrap();
+/// assert_eq!(ast.unwrap().topics.len(), 2);
+///
+/// let last = reports.last().unwrap();
+/// assert_eq!((last.blocks_parsed, last.blocks_total), (2, 2));
+/// assert_eq!(last.bytes_parsed, source.len());
+/// ```
+pub fn parse_with_progress(
+    source: &str,
+    token: &CancellationToken,
+    mut on_progress: impl FnMut(ParseProgress),
+) -> Result<ParseOutput, Cancelled> {
+    let mut task = parse_async(source, token.clone());
+    loop {
+        let done = task.advance()?;
+        on_progress(task.progress());
+        if done {
+            return Ok(task.finish());
+        }
+    }
+}
+
+/// Parse a file as a future that yields to the executor between top-level
+/// blocks.
+///
+/// The future resolves to `Err(Cancelled)` at the first block boundary
+/// after `token` is cancelled. It needs no particular runtime, so it works
+/// on Tokio as well as in the browser.
+pub fn parse_async(source: &str, token: CancellationToken) -> ParseTask<'_> {
+    ParseTask {
+        source,
+        token,
+        options: ParseOptions::default(),
+        tokens: None,
+        chunks: Vec::new(),
+        next: 0,
+        blocks: Vec::new(),
+        errors: Vec::new(),
+        done: false,
+    }
+}
+
+/// The future returned by [`parse_async`].
+pub struct ParseTask<'src> {
+    source: &'src str,
+    token: CancellationToken,
+    options: ParseOptions,
+    /// `None` until lexed; empty after a lexer error or crossed limit.
+    tokens: Option<Vec<SpannedToken<'src>>>,
+    /// Token ranges of the top-level blocks.
+    chunks: Vec<Range<usize>>,
+    next: usize,
+    blocks: Vec<TopLevelBlock>,
+    errors: Vec<ParseErrorInfo>,
+    done: bool,
+}
+
+impl ParseTask<'_> {
+    /// Parse within `options` instead of the default limits.
+    pub fn with_options(mut self, options: ParseOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// How far the parse has got.
+    pub fn progress(&self) -> ParseProgress {
+        let bytes_parsed = match (&self.tokens, self.next.checked_sub(1)) {
+            (Some(tokens), Some(last)) => tokens
+                .get(self.chunks[last].end)
+                .map_or(self.source.len(), |(_, span)| span.start),
+            _ => 0,
+        };
+        ParseProgress {
+            blocks_parsed: self.next,
+            blocks_total: self.chunks.len(),
+            bytes_parsed,
+            bytes_total: self.source.len(),
+        }
+    }
+
+    /// Do the next unit of work: lex, or parse one block. Returns whether
+    /// the parse is complete.
+    fn advance(&mut self) -> Result<bool, Cancelled> {
+        if self.token.is_cancelled() {
+            return Err(Cancelled);
+        }
+
+        let Some(tokens) = &self.tokens else {
+            let lexed = self
+                .options
+                .check_source(self.source)
+                .map_err(|e| vec![limit_error(self.source, &e)])
+                .and_then(|()| {
+                    lexer::lex_with_indentation(self.source).map_err(|errs| {
+                        errs.iter()
+                            .map(|e| structured_lex_error(self.source, e))
+                            .collect()
+                    })
+                })
+                .and_then(|tokens| {
+                    self.options
+                        .check_tokens(&tokens)
+                        .map_err(|e| vec![limit_error(self.source, &e)])?;
+                    Ok(tokens)
+                });
+            let tokens = match lexed {
+                Ok(tokens) => tokens,
+                Err(errors) => {
+                    self.errors = errors;
+                    Vec::new()
+                }
+            };
+            let mut start = 0;
+            self.chunks = split_blocks(&tokens)
+                .iter()
+                .map(|chunk| {
+                    start += chunk.len();
+                    start - chunk.len()..start
+                })
+                .collect();
+            self.tokens = Some(tokens);
+            return Ok(self.chunks.is_empty());
+        };
+
+        let range = self.chunks[self.next].clone();
+        // A block ends where the next one starts, or at the end of the file
+        let end = tokens
+            .get(range.end)
+            .map_or(self.source.len(), |(_, span)| span.start);
+        let eoi = primitives::Span::new((), end..end);
+        let (blocks, errs) = top_level_blocks()
+            .parse(tokens[range].split_token_span(eoi))
+            .into_output_errors();
+        self.blocks.extend(blocks.into_iter().flatten());
+        self.errors
+            .extend(errs.iter().map(|e| structured_parse_error(self.source, e)));
+
+        self.next += 1;
+        Ok(self.next == self.chunks.len())
+    }
+
+    fn finish(&mut self) -> ParseOutput {
+        self.done = true;
+        let errors = std::mem::take(&mut self.errors);
+        if self.tokens.as_ref().is_some_and(Vec::is_empty) && !errors.is_empty() {
+            return (None, errors);
+        }
+        let mut file = assemble_file(std::mem::take(&mut self.blocks));
+        doc_comments::attach_doc_comments(self.source, &mut file);
+        (Some(file), errors)
+    }
+}
+
+impl Future for ParseTask<'_> {
+    type Output = Result<ParseOutput, Cancelled>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        assert!(!self.done, "ParseTask polled after completion");
+        match self.advance() {
+            Err(cancelled) => {
+                self.done = true;
+                Poll::Ready(Err(cancelled))
+            }
+            Ok(true) => Poll::Ready(Ok(self.finish())),
+            Ok(false) => {
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::task::Waker;
+
+    const SOURCE: &str = r#"# Leading comment
+config:
+   agent_name: "Test"
+
+variables:
+   x: mutable string = ""
+
+# Main topic
+topic main:
+   description: "Main"
+   reasoning:
+      instructions: "Help"
+
+topic broken:
+   description: 42
+
+topic other:
+   description: "Other"
+"#;
+
+    #[test]
+    fn test_matches_whole_file_parse() {
+        let (expected, expected_errors) = super::super::parse_with_structured_errors_all(SOURCE);
+        let (ast, errors) = parse_cancellable(SOURCE, &CancellationToken::new()).unwrap();
+        assert_eq!(ast, expected);
+        assert_eq!(
+            errors.iter().map(|e| &e.message).collect::<Vec<_>>(),
+            expected_errors
+                .iter()
+                .map(|e| &e.message)
+                .collect::<Vec<_>>()
+        );
+        assert!(!errors.is_empty());
+
+        let (ast, errors) =
+            parse_cancellable("topic main:\n   description: \"Main", &CancellationToken::new())
+                .unwrap();
+        assert!(ast.is_none());
+        assert!(errors[0].message.starts_with("Lexer error"));
+    }
+
+    #[test]
+    fn test_async_parse_yields_and_cancels() {
+        let mut cx = Context::from_waker(Waker::noop());
+
+        let mut task = parse_async(SOURCE, CancellationToken::new());
+        let mut polls = 1;
+        let output = loop {
+            match Pin::new(&mut task).poll(&mut cx) {
+                Poll::Ready(output) => break output,
+                Poll::Pending => polls += 1,
+            }
+        };
+        let (ast, _) = output.unwrap();
+        assert_eq!(ast.unwrap().topics.len(), 2);
+        // Lexing, then one poll per top-level block
+        assert_eq!(polls, 6);
+
+        let token = CancellationToken::new();
+        let mut task = parse_async(SOURCE, token.clone());
+        assert_eq!(
+            task.progress(),
+            ParseProgress {
+                bytes_total: SOURCE.len(),
+                ..ParseProgress::default()
+            }
+        );
+        assert!(Pin::new(&mut task).poll(&mut cx).is_pending());
+        assert!(Pin::new(&mut task).poll(&mut cx).is_pending());
+        token.cancel();
+        assert!(matches!(Pin::new(&mut task).poll(&mut cx), Poll::Ready(Err(Cancelled))));
+    }
+
+    #[test]
+    fn test_progress_reports_each_block() {
+        let token = CancellationToken::new();
+        let mut reports = Vec::new();
+        parse_with_progress(SOURCE, &token, |p| reports.push(p)).unwrap();
+        // Lexing, then one report per top-level block
+        assert_eq!(reports.len(), 6);
+        assert!(reports.iter().all(|p| p.blocks_total == 5));
+        assert_eq!(reports[0].blocks_parsed, 0);
+        assert!(reports
+            .windows(2)
+            .all(|w| w[0].bytes_parsed < w[1].bytes_parsed));
+        assert_eq!(reports[5].bytes_parsed, SOURCE.len());
+
+        let result = parse_with_progress(SOURCE, &token, |p| {
+            if p.blocks_parsed == 2 {
+                token.cancel();
+            }
+        });
+        assert_eq!(result.unwrap_err(), Cancelled);
+    }
+}
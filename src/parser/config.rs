@@ -69,6 +69,7 @@ pub fn config_block<'tokens, 'src: 'tokens>() -> impl Parser<
                 description: None,
                 agent_type: None,
                 default_agent_user: None,
+                target_environment: None,
             };
 
             for (name, value) in entries {
@@ -78,6 +79,7 @@ pub fn config_block<'tokens, 'src: 'tokens>() -> impl Parser<
                     "description" => config.description = Some(value),
                     "agent_type" => config.agent_type = Some(value),
                     "default_agent_user" => config.default_agent_user = Some(value),
+                    "target_environment" => config.target_environment = Some(value),
                     _ => {}
                 }
             }
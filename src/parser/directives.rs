@@ -6,9 +6,10 @@ use crate::ast::{DirectiveBlock, Spanned, Stmt};
 use crate::lexer::Token;
 use chumsky::prelude::*;
 
-use super::expressions::{expr, reference};
+use super::expressions::{expr, spanned_reference};
 use super::primitives::{
-    dedent, indent, newline, skip_block_noise, to_ast_span, ParserInput, Span,
+    dedent, ident, indent, newline, skip_block_noise, spanned_string, to_ast_span, ParserInput,
+    Span,
 };
 use super::reasoning::{set_clause, with_clause};
 
@@ -27,69 +28,82 @@ fn directive_stmt<'tokens, 'src: 'tokens>() -> impl Parser<
     extra::Err<Rich<'tokens, Token<'src>, Span>>,
 > + Clone {
     recursive(|stmt| {
+        let block = newline()
+            .ignore_then(indent())
+            .ignore_then(
+                stmt.clone()
+                    .separated_by(skip_block_noise())
+                    .allow_trailing()
+                    .collect::<Vec<_>>(),
+            )
+            .then_ignore(skip_block_noise())
+            .then_ignore(dedent());
+        let else_keyword = newline().or_not().ignore_then(skip_block_noise());
+
         choice((
-            // if condition: with optional else:
+            // if condition: with optional elif condition: and else:
             just(Token::If)
                 .ignore_then(expr())
                 .then_ignore(just(Token::Colon))
+                .then(block.clone())
                 .then(
-                    newline()
-                        .ignore_then(indent())
+                    else_keyword
+                        .clone()
                         .ignore_then(
-                            stmt.clone()
-                                .separated_by(skip_block_noise())
-                                .allow_trailing()
-                                .collect::<Vec<_>>(),
+                            just(Token::Elif)
+                                .ignore_then(expr())
+                                .then_ignore(just(Token::Colon))
+                                .then(block.clone())
+                                .map_with(|branch, e| (branch, to_ast_span(e.span()))),
                         )
-                        .then_ignore(skip_block_noise())
-                        .then_ignore(dedent()),
+                        .repeated()
+                        .collect::<Vec<_>>(),
                 )
                 .then(
-                    newline()
-                        .or_not()
-                        .ignore_then(skip_block_noise())
+                    else_keyword
                         .ignore_then(just(Token::Else))
                         .ignore_then(just(Token::Colon))
-                        .ignore_then(newline())
-                        .ignore_then(indent())
-                        .ignore_then(
-                            stmt.clone()
-                                .separated_by(skip_block_noise())
-                                .allow_trailing()
-                                .collect::<Vec<_>>(),
-                        )
-                        .then_ignore(skip_block_noise())
-                        .then_ignore(dedent())
+                        .ignore_then(block)
                         .or_not(),
                 )
                 .labelled("if statement")
-                .map_with(|((condition, then_block), else_block), e| {
+                .map_with(|(((condition, then_block), elifs), else_block), e| {
+                    let span = to_ast_span(e.span());
+                    // Each `elif` is an `if` alone in the previous branch's
+                    // `else`, spanning to the end of the chain
+                    let else_block = elifs.into_iter().rev().fold(
+                        else_block,
+                        |else_block, ((condition, then_block), elif_span)| {
+                            Some(vec![Spanned::new(
+                                Stmt::If {
+                                    condition,
+                                    then_block,
+                                    else_block,
+                                },
+                                elif_span.start..span.end,
+                            )])
+                        },
+                    );
                     Spanned::new(
                         Stmt::If {
                             condition,
                             then_block,
                             else_block,
                         },
-                        to_ast_span(e.span()),
+                        span,
                     )
                 }),
             // set @ref = expr
             just(Token::Set)
-                .ignore_then(reference())
+                .ignore_then(spanned_reference())
                 .then_ignore(just(Token::Assign))
                 .then(expr())
                 .map_with(|(target, value), e| {
-                    Spanned::new(
-                        Stmt::Set {
-                            target: Spanned::new(target, to_ast_span(e.span())),
-                            value,
-                        },
-                        to_ast_span(e.span()),
-                    )
+                    Spanned::new(Stmt::Set { target, value }, to_ast_span(e.span()))
                 }),
             // run @ref with optional clauses
             just(Token::Run)
-                .ignore_then(reference())
+                .ignore_then(spanned_reference())
                 .then(
                     newline()
                         .ignore_then(indent())
@@ -118,13 +132,31 @@ fn directive_stmt<'tokens, 'src: 'tokens>() -> impl Parser<
                     }
                     Spanned::new(
                         Stmt::Run {
-                            action: Spanned::new(action, to_ast_span(e.span())),
+                            action,
                             with_clauses,
                             set_clauses,
                         },
                         to_ast_span(e.span()),
                     )
                 }),
+            // transition to @topic.name
+            just(Token::Transition)
+                .ignore_then(just(Token::To))
+                .ignore_then(spanned_reference())
+                .map_with(|target, e| {
+                    Spanned::new(Stmt::Transition { target }, to_ast_span(e.span()))
+                }),
+            // escalate
+            ident()
+                .filter(|s| *s == "escalate")
+                .map_with(|_, e| Spanned::new(Stmt::Escalate, to_ast_span(e.span()))),
+            // respond "message"
+            ident()
+                .filter(|s| *s == "respond")
+                .ignore_then(spanned_string())
+                .map_with(|message, e| {
+                    Spanned::new(Stmt::Respond { message }, to_ast_span(e.span()))
+                }),
         ))
     })
 }
@@ -0,0 +1,156 @@
+//! Doc comment attachment.
+//!
+//! Contiguous `#` comment lines directly above a variable, topic,
+//! start_agent, or action definition are treated as that definition's
+//! documentation, much like rustdoc treats `///`. A blank line (or any
+//! non-comment line) ends the doc comment.
+//!
+//! ```text
+//! variables:
+//!    # The customer's verified email address.
+//!    # Populated after identity verification.
+//!    email: mutable string = ""
+//! ```
+
+use crate::ast::{AgentFile, Spanned};
+
+/// Attach doc comments from `source` to every documentable definition in `agent`.
+pub(crate) fn attach_doc_comments(source: &str, agent: &mut AgentFile) {
+    if let Some(vars) = &mut agent.variables {
+        for var in &mut vars.node.variables {
+            var.node.doc = doc_comment_above(source, var.node.name.span.start);
+        }
+    }
+
+    let start_agents = agent
+        .start_agent
+        .iter_mut()
+        .chain(&mut agent.duplicate_start_agents);
+    for start in start_agents {
+        start.node.doc = doc_comment_above(source, start.node.name.span.start);
+        if let Some(actions) = &mut start.node.actions {
+            for action in &mut actions.node.actions {
+                action.node.doc = doc_comment_above(source, action.node.name.span.start);
+            }
+        }
+    }
+
+    for topic in &mut agent.topics {
+        topic.node.doc = doc_comment_above(source, topic.node.name.span.start);
+        if let Some(actions) = &mut topic.node.actions {
+            for action in &mut actions.node.actions {
+                action.node.doc = doc_comment_above(source, action.node.name.span.start);
+            }
+        }
+    }
+}
+
+/// Collect the doc comment for the definition on the line containing `offset`.
+///
+/// Returns the comment text with the leading `#` (and one following space)
+/// stripped from each line, joined with newlines. The span covers the
+/// comment lines in the source.
+pub(crate) fn doc_comment_above(source: &str, offset: usize) -> Option<Spanned<String>> {
+    let offset = offset.min(source.len());
+    let mut line_start = source[..offset].rfind('\n').map(|i| i + 1).unwrap_or(0);
+
+    let mut lines = Vec::new();
+    let mut span_start = line_start;
+    let mut span_end = None;
+
+    while line_start > 0 {
+        let prev_end = line_start - 1;
+        let prev_start = source[..prev_end].rfind('\n').map(|i| i + 1).unwrap_or(0);
+        let line = source[prev_start..prev_end].trim_end_matches('\r');
+        let trimmed = line.trim_start();
+
+        let Some(text) = trimmed.strip_prefix('#') else {
+            break;
+        };
+        lines.push(
+            text.strip_prefix(' ')
+                .unwrap_or(text)
+                .trim_end()
+                .to_string(),
+        );
+        span_start = prev_start + (line.len() - trimmed.len());
+        span_end.get_or_insert(prev_start + line.len());
+        line_start = prev_start;
+    }
+
+    let span_end = span_end?;
+    lines.reverse();
+    Some(Spanned::new(lines.join("\n"), span_start..span_end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_doc_comment_above() {
+        let source =
+            "variables:\n   # First line\n   #  indented\n   name: mutable string = \"\"\n";
+        let offset = source.find("name").unwrap();
+        let doc = doc_comment_above(source, offset).expect("doc comment");
+        assert_eq!(doc.node, "First line\n indented");
+        assert_eq!(&source[doc.span.clone()], "# First line\n   #  indented");
+    }
+
+    #[test]
+    fn test_blank_line_ends_doc_comment() {
+        let source = "# Detached\n\ntopic main:\n";
+        let offset = source.find("main").unwrap();
+        assert!(doc_comment_above(source, offset).is_none());
+    }
+
+    #[test]
+    fn test_attach_doc_comments_to_definitions() {
+        let source = r#"config:
+   agent_name: "Test"
+
+variables:
+   # Customer email address.
+   email: mutable string = ""
+
+# Handles order questions.
+topic orders:
+   description: "Orders"
+
+   actions:
+      # Looks up an order by id.
+      lookup:
+         description: "Lookup"
+         target: "flow://Lookup"
+
+   reasoning:
+      instructions: "Help"
+"#;
+        let ast = crate::parse(source).expect("Failed to parse");
+        let var = &ast.variables.as_ref().unwrap().node.variables[0].node;
+        assert_eq!(var.doc.as_ref().unwrap().node, "Customer email address.");
+
+        let topic = &ast.topics[0].node;
+        assert_eq!(topic.doc.as_ref().unwrap().node, "Handles order questions.");
+
+        let action = &topic.actions.as_ref().unwrap().node.actions[0].node;
+        assert_eq!(action.doc.as_ref().unwrap().node, "Looks up an order by id.");
+    }
+
+    #[test]
+    fn test_attach_doc_comments_to_duplicate_start_agents() {
+        let source = r#"# Greets the user.
+start_agent first:
+   description: "First"
+
+# Also greets the user.
+start_agent second:
+   description: "Second"
+"#;
+        let ast = crate::parse(source).expect("Failed to parse");
+        let first = &ast.start_agent.as_ref().unwrap().node;
+        assert_eq!(first.doc.as_ref().unwrap().node, "Greets the user.");
+        let second = &ast.duplicate_start_agents[0].node;
+        assert_eq!(second.doc.as_ref().unwrap().node, "Also greets the user.");
+    }
+}
@@ -6,11 +6,45 @@
 
 use indexmap::IndexMap;
 
-use crate::ast::{BinOp, Expr, Reference, Spanned, Type, UnaryOp};
+use crate::ast::{BinOp, Expr, NamespaceRule, Reference, Spanned, Type, UnaryOp};
 use crate::lexer::Token;
+use chumsky::pratt::{infix, left, postfix, prefix};
 use chumsky::prelude::*;
 
-use super::primitives::{number_lit, string_lit, to_ast_span, ParserInput, Span};
+use super::primitives::{
+    currency_lit, date_lit, number_lit, string_lit, to_ast_span, ParserInput, Span,
+};
+
+/// Parse a reference's `@namespace`.
+fn namespace<'tokens, 'src: 'tokens>() -> impl Parser<
+    'tokens,
+    ParserInput<'tokens, 'src>,
+    String,
+    extra::Err<Rich<'tokens, Token<'src>, Span>>,
+> + Clone {
+    just(Token::At).ignore_then(select! {
+        Token::Ident(s) => s.to_string(),
+        Token::Variables => "variables".to_string(),
+        Token::Actions => "actions".to_string(),
+        Token::Outputs => "outputs".to_string(),
+        Token::Topic => "topic".to_string(),
+        Token::Inputs => "inputs".to_string(),
+    })
+}
+
+/// Parse a segment of a reference path, after its `.`.
+fn path_segment<'tokens, 'src: 'tokens>() -> impl Parser<
+    'tokens,
+    ParserInput<'tokens, 'src>,
+    String,
+    extra::Err<Rich<'tokens, Token<'src>, Span>>,
+> + Clone {
+    select! {
+        Token::Ident(s) => s.to_string(),
+        Token::Transition => "transition".to_string(),
+        Token::To => "to".to_string(),
+    }
+}
 
 /// Parse a reference: @namespace.path.to.something
 pub fn reference<'tokens, 'src: 'tokens>() -> impl Parser<
@@ -19,30 +53,64 @@ pub fn reference<'tokens, 'src: 'tokens>() -> impl Parser<
     Reference,
     extra::Err<Rich<'tokens, Token<'src>, Span>>,
 > + Clone {
-    just(Token::At)
-        .ignore_then(select! {
-            Token::Ident(s) => s.to_string(),
-            Token::Variables => "variables".to_string(),
-            Token::Actions => "actions".to_string(),
-            Token::Outputs => "outputs".to_string(),
-            Token::Topic => "topic".to_string(),
-            Token::Inputs => "inputs".to_string(),
-        })
+    namespace()
         .then(
             just(Token::Dot)
-                .ignore_then(select! {
-                    Token::Ident(s) => s.to_string(),
-                    Token::Transition => "transition".to_string(),
-                    Token::To => "to".to_string(),
-                })
+                .ignore_then(path_segment())
                 .repeated()
                 .collect::<Vec<_>>(),
         )
         .map(|(namespace, path)| Reference { namespace, path })
 }
 
+/// Parse a reference in an expression.
+///
+/// Path segments beyond the ones the namespace takes (see
+/// [`NamespaceRule`]) access properties of the referenced value, so
+/// `@outputs.policy.amount` is a `.amount` property access on
+/// `@outputs.policy`.
+fn reference_expr<'tokens, 'src: 'tokens>() -> impl Parser<
+    'tokens,
+    ParserInput<'tokens, 'src>,
+    Spanned<Expr>,
+    extra::Err<Rich<'tokens, Token<'src>, Span>>,
+> + Clone {
+    namespace()
+        .map_with(|namespace, e| (namespace, to_ast_span(e.span())))
+        .then(
+            just(Token::Dot)
+                .ignore_then(
+                    path_segment()
+                        .map_with(|segment, e| Spanned::new(segment, to_ast_span(e.span()))),
+                )
+                .repeated()
+                .collect::<Vec<_>>(),
+        )
+        .map(|((namespace, namespace_span), mut path)| {
+            let taken = NamespaceRule::get(&namespace).map_or(path.len(), |rule| rule.segments);
+            let fields = path.split_off(taken.min(path.len()));
+            let end = path
+                .last()
+                .map_or(namespace_span.end, |segment| segment.span.end);
+            let reference = Reference {
+                namespace,
+                path: path.into_iter().map(|segment| segment.node).collect(),
+            };
+            let root = Spanned::new(Expr::Reference(reference), namespace_span.start..end);
+            fields.into_iter().fold(root, |object, field| {
+                let span = object.span.start..field.span.end;
+                Spanned::new(
+                    Expr::Property {
+                        object: Box::new(object),
+                        field,
+                    },
+                    span,
+                )
+            })
+        })
+}
+
 /// Parse a spanned reference.
-#[allow(dead_code)]
 pub fn spanned_reference<'tokens, 'src: 'tokens>() -> impl Parser<
     'tokens,
     ParserInput<'tokens, 'src>,
@@ -75,11 +143,23 @@ fn type_spec<'tokens, 'src: 'tokens>() -> impl Parser<
             Token::Long => Type::Long,
         };
 
-        // list[inner_type]
+        // An element type, naming any unknown one in the error
+        let element = choice((
+            type_.clone(),
+            select! { Token::Ident(name) => name }.validate(|name, e, emitter| {
+                emitter.emit(Rich::custom(e.span(), format!("unknown type '{}'", name)));
+                Type::Object
+            }),
+        ));
+
+        // list[inner_type], or list<inner_type>
         let list_type = just(Token::List)
-            .ignore_then(just(Token::LBracket))
-            .ignore_then(type_.clone())
-            .then_ignore(just(Token::RBracket))
+            .ignore_then(choice((
+                element
+                    .clone()
+                    .delimited_by(just(Token::LBracket), just(Token::RBracket)),
+                element.delimited_by(just(Token::Lt), just(Token::Gt)),
+            )))
             .map(|inner| Type::List(Box::new(inner)));
 
         choice((list_type, simple_type))
@@ -124,7 +204,56 @@ fn property_ident<'tokens, 'src: 'tokens>() -> impl Parser<
     .map_with(|s, e| Spanned::new(s, to_ast_span(e.span())))
 }
 
+/// Binding power of postfix `.field` and `[index]` access, above every
+/// prefix and infix operator.
+const POSTFIX_PRECEDENCE: u16 = 10;
+
+/// A postfix access applied to an operand.
+#[derive(Clone)]
+enum Access {
+    Field(Spanned<String>),
+    Index(Spanned<Expr>),
+}
+
+fn binary_power(op: BinOp) -> u16 {
+    op.precedence() as u16
+}
+
+fn unary_power(op: UnaryOp) -> u16 {
+    op.precedence() as u16
+}
+
+fn binary(left: Spanned<Expr>, op: BinOp, right: Spanned<Expr>) -> Spanned<Expr> {
+    let span = left.span.start..right.span.end;
+    Spanned::new(
+        Expr::BinOp {
+            left: Box::new(left),
+            op,
+            right: Box::new(right),
+        },
+        span,
+    )
+}
+
+fn unary(op: UnaryOp, operand: Spanned<Expr>, span: Span) -> Spanned<Expr> {
+    Spanned::new(
+        Expr::UnaryOp {
+            op,
+            operand: Box::new(operand),
+        },
+        to_ast_span(span),
+    )
+}
+
 /// Parse an expression.
+///
+/// Operators bind, from loosest to tightest: the `x if c else y` ternary,
+/// `or`, `and`, prefix `not`, comparisons (`== != < > <= >= is`, `is not`),
+/// `+ -`, `* / %`, unary `-`, then `.field` / `[index]` access; calls like
+/// `len(@variables.items)` are atoms. Binary
+/// operators are left-associative and parentheses group. Binding powers come from
+/// [`BinOp::precedence`] and [`UnaryOp::precedence`], which the serializer
+/// also uses to decide where parentheses are needed.
 pub fn expr<'tokens, 'src: 'tokens>() -> impl Parser<
     'tokens,
     ParserInput<'tokens, 'src>,
@@ -132,23 +261,39 @@ pub fn expr<'tokens, 'src: 'tokens>() -> impl Parser<
     extra::Err<Rich<'tokens, Token<'src>, Span>>,
 > + Clone {
     recursive(|expr| {
-        // Object literal key: identifier or string
+        // Object literal key: identifier, keyword or string
         let object_key = choice((
-            select! { Token::Ident(s) => s.to_string() },
+            select! {
+                Token::Ident(s) => s.to_string(),
+                token if token.keyword_text().is_some() => token.to_string(),
+            },
             string_lit().map(|s| s.to_string()),
         ));
 
         // Atomic expressions (highest precedence)
         let atom = choice((
             // Literals
-            string_lit().map(|s| Expr::String(s.to_string())),
+            string_lit().map_with(|s, e| string_expr(s, e.span().start + 1)),
             number_lit().map(Expr::Number),
+            date_lit().map(Expr::Date),
+            currency_lit().map(Expr::Currency),
             just(Token::True).to(Expr::Bool(true)),
             just(Token::False).to(Expr::Bool(false)),
             just(Token::None).to(Expr::None),
             just(Token::Ellipsis).to(Expr::SlotFill),
-            // Reference
-            reference().map(Expr::Reference),
+            // Reference, with any property access past its path
+            reference_expr().map(|e| e.node),
+            // Function call: name(arg, ...)
+            select! { Token::Ident(s) => s.to_string() }
+                .map_with(|name, e| Spanned::new(name, to_ast_span(e.span())))
+                .then(
+                    expr.clone()
+                        .separated_by(just(Token::Comma))
+                        .allow_trailing()
+                        .collect::<Vec<_>>()
+                        .delimited_by(just(Token::LParen), just(Token::RParen)),
+                )
+                .map(|(callee, args)| Expr::Call { callee, args }),
             // Parenthesized expression
             expr.clone()
                 .delimited_by(just(Token::LParen), just(Token::RParen))
@@ -180,57 +325,11 @@ pub fn expr<'tokens, 'src: 'tokens>() -> impl Parser<
         ))
         .map_with(|e, extra| Spanned::new(e, to_ast_span(extra.span())));
 
-        // Postfix operators: .field and [index]
-        let postfix = atom.clone().foldl_with(
-            choice((
-                // Property access: .field
-                just(Token::Dot)
-                    .ignore_then(property_ident())
-                    .map(|f| (true, f, None)),
-                // Index access: [expr]
-                expr.clone()
-                    .delimited_by(just(Token::LBracket), just(Token::RBracket))
-                    .map(|idx| (false, Spanned::new(String::new(), 0..0), Some(idx))),
-            ))
-            .repeated(),
-            |obj, (is_prop, field, idx), e| {
-                let span = obj.span.start..e.span().end;
-                if is_prop {
-                    Spanned::new(
-                        Expr::Property {
-                            object: Box::new(obj),
-                            field,
-                        },
-                        span,
-                    )
-                } else {
-                    Spanned::new(
-                        Expr::Index {
-                            object: Box::new(obj),
-                            index: Box::new(idx.unwrap()),
-                        },
-                        span,
-                    )
-                }
-            },
-        );
-
-        // Unary operators
-        let unary =
-            choice((just(Token::Not).to(UnaryOp::Not), just(Token::Minus).to(UnaryOp::Neg)))
-                .repeated()
-                .foldr_with(postfix.clone(), |op, expr, e| {
-                    let span = expr.span.start..e.span().end;
-                    Spanned::new(
-                        Expr::UnaryOp {
-                            op,
-                            operand: Box::new(expr),
-                        },
-                        span,
-                    )
-                });
-
-        // Comparison operators
+        // Operators, from loosest to tightest binding: `or`, `and`, prefix
+        // `not`, comparisons (including `is` / `is not`), `+ -`, `* / %`,
+        // unary `-`, then postfix `.field` and `[index]`. Binding powers come from
+        // `BinOp::precedence` / `UnaryOp::precedence` so the serializer
+        // parenthesizes with the same table.
         let comparison_op = choice((
             just(Token::Eq).to(BinOp::Eq),
             just(Token::Ne).to(BinOp::Ne),
@@ -238,71 +337,64 @@ pub fn expr<'tokens, 'src: 'tokens>() -> impl Parser<
             just(Token::Ge).to(BinOp::Ge),
             just(Token::Lt).to(BinOp::Lt),
             just(Token::Gt).to(BinOp::Gt),
+            just(Token::Is)
+                .ignore_then(just(Token::Not).or_not())
+                .map(|not| {
+                    if not.is_some() {
+                        BinOp::IsNot
+                    } else {
+                        BinOp::Is
+                    }
+                }),
         ));
-
-        // Arithmetic operators (higher precedence)
         let add_op = choice((just(Token::Plus).to(BinOp::Add), just(Token::Minus).to(BinOp::Sub)));
+        let mul_op = choice((
+            just(Token::Star).to(BinOp::Mul),
+            just(Token::Slash).to(BinOp::Div),
+            just(Token::Percent).to(BinOp::Mod),
+        ));
 
-        // Build expression with precedence using foldl
-        let sum = unary
-            .clone()
-            .foldl_with(add_op.then(unary).repeated(), |l, (op, r), e| {
-                let span = l.span.start..e.span().end;
-                Spanned::new(
-                    Expr::BinOp {
-                        left: Box::new(l),
-                        op,
-                        right: Box::new(r),
-                    },
-                    span,
-                )
-            });
-
-        let comparison =
-            sum.clone()
-                .foldl_with(comparison_op.then(sum).repeated(), |l, (op, r), e| {
-                    let span = l.span.start..e.span().end;
-                    Spanned::new(
-                        Expr::BinOp {
-                            left: Box::new(l),
-                            op,
-                            right: Box::new(r),
-                        },
-                        span,
-                    )
-                });
+        // Postfix operators: .field and [index]
+        let postfix_op = choice((
+            just(Token::Dot)
+                .ignore_then(property_ident())
+                .map(Access::Field),
+            expr.clone()
+                .delimited_by(just(Token::LBracket), just(Token::RBracket))
+                .map(Access::Index),
+        ));
 
-        // Logical AND
-        let and_expr = comparison.clone().foldl_with(
-            just(Token::And).ignore_then(comparison).repeated(),
-            |l, r, e| {
-                let span = l.span.start..e.span().end;
-                Spanned::new(
-                    Expr::BinOp {
-                        left: Box::new(l),
-                        op: BinOp::And,
-                        right: Box::new(r),
+        let or_expr = atom.pratt((
+            postfix(POSTFIX_PRECEDENCE, postfix_op, |obj: Spanned<Expr>, access, e| {
+                let span = obj.span.start..to_ast_span(e.span()).end;
+                let node = match access {
+                    Access::Field(field) => Expr::Property {
+                        object: Box::new(obj),
+                        field,
                     },
-                    span,
-                )
-            },
-        );
-
-        // Logical OR
-        let or_expr = and_expr.clone().foldl_with(
-            just(Token::Or).ignore_then(and_expr).repeated(),
-            |l, r, e| {
-                let span = l.span.start..e.span().end;
-                Spanned::new(
-                    Expr::BinOp {
-                        left: Box::new(l),
-                        op: BinOp::Or,
-                        right: Box::new(r),
+                    Access::Index(index) => Expr::Index {
+                        object: Box::new(obj),
+                        index: Box::new(index),
                     },
-                    span,
-                )
-            },
-        );
+                };
+                Spanned::new(node, span)
+            }),
+            prefix(unary_power(UnaryOp::Neg), just(Token::Minus), |_, operand, e| {
+                unary(UnaryOp::Neg, operand, e.span())
+            }),
+            infix(left(binary_power(BinOp::Mul)), mul_op, |l, op, r, _| binary(l, op, r)),
+            infix(left(binary_power(BinOp::Add)), add_op, |l, op, r, _| binary(l, op, r)),
+            infix(left(binary_power(BinOp::Eq)), comparison_op, |l, op, r, _| binary(l, op, r)),
+            prefix(unary_power(UnaryOp::Not), just(Token::Not), |_, operand, e| {
+                unary(UnaryOp::Not, operand, e.span())
+            }),
+            infix(left(binary_power(BinOp::And)), just(Token::And), |l, _, r, _| {
+                binary(l, BinOp::And, r)
+            }),
+            infix(left(binary_power(BinOp::Or)), just(Token::Or), |l, _, r, _| {
+                binary(l, BinOp::Or, r)
+            }),
+        ));
 
         // Box or_expr to break the type chain and prevent exponential compile times
         // See: https://github.com/zesterer/chumsky/discussions/396
@@ -335,3 +427,64 @@ pub fn expr<'tokens, 'src: 'tokens>() -> impl Parser<
             .boxed()
     })
 }
+
+/// The expression for a string literal: an [`Expr::Template`] if `text` has
+/// `{!expr}` interpolations, else an [`Expr::String`]. `start` is the source
+/// offset of `text`, just inside the opening quote.
+///
+/// A literal whose interpolations don't parse stays a plain string.
+fn string_expr(text: &str, start: usize) -> Expr {
+    template_parts(text, start).map_or_else(|| Expr::String(text.to_string()), Expr::Template)
+}
+
+fn template_parts(text: &str, start: usize) -> Option<Vec<Spanned<Expr>>> {
+    if !text.contains("{!") {
+        return None;
+    }
+    let mut parts = Vec::new();
+    let mut text_start = 0;
+    while let Some(open) = text[text_start..].find("{!").map(|i| text_start + i) {
+        // The matching `}`, skipping the braces of object literals
+        let inner = open + 2;
+        let mut depth = 0;
+        let close = text[inner..].char_indices().find_map(|(i, c)| match c {
+            '{' => {
+                depth += 1;
+                None
+            }
+            '}' if depth == 0 => Some(inner + i),
+            '}' => {
+                depth -= 1;
+                None
+            }
+            _ => None,
+        })?;
+        if open > text_start {
+            let literal = Expr::String(text[text_start..open].to_string());
+            parts.push(Spanned::new(literal, start + text_start..start + open));
+        }
+        parts.push(interpolation(&text[inner..close], start + inner)?);
+        text_start = close + 1;
+    }
+    if text_start < text.len() {
+        let literal = Expr::String(text[text_start..].to_string());
+        parts.push(Spanned::new(literal, start + text_start..start + text.len()));
+    }
+    Some(parts)
+}
+
+/// Parse the expression of an interpolation, whose text starts at `offset`.
+fn interpolation(source: &str, offset: usize) -> Option<Spanned<Expr>> {
+    let tokens = crate::lexer::lexer().parse(source).into_result().ok()?;
+    let shift = |span: Span| Span::new((), span.start + offset..span.end + offset);
+    let tokens: Vec<_> = tokens
+        .into_iter()
+        .map(|(token, span)| (token, shift(span)))
+        .collect();
+    let eoi = shift(Span::new((), source.len()..source.len()));
+    let parsed = expr()
+        .parse(tokens.split_token_span(eoi))
+        .into_result()
+        .ok();
+    parsed
+}
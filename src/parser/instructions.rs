@@ -11,10 +11,11 @@
 
 use std::borrow::Cow;
 
-use crate::ast::{BinOp, Expr, InstructionPart, Instructions, Spanned, UnaryOp};
+use crate::ast::{Expr, InstructionPart, Instructions, Spanned};
 use crate::lexer::Token;
 use chumsky::prelude::*;
 
+use super::expressions::expr;
 use super::primitives::{
     dedent, indent, newline, skip_block_noise, spanned_string, to_ast_span, ParserInput, Span,
 };
@@ -152,7 +153,7 @@ fn parse_multiline_text_content(tokens: &[(Token<'_>, Span)]) -> Vec<Spanned<Str
                             if !line_text.is_empty() {
                                 line_text.push(' ');
                             }
-                            line_text.push_str(&n.to_string());
+                            line_text.push_str(n);
                         }
                         other => {
                             let s = token_to_text(other);
@@ -209,7 +210,7 @@ fn parse_multiline_text_content(tokens: &[(Token<'_>, Span)]) -> Vec<Spanned<Str
                         if !line_text.is_empty() {
                             line_text.push(' ');
                         }
-                        line_text.push_str(&n.to_string());
+                        line_text.push_str(n);
                     }
                     other => {
                         let s = token_to_text(other);
@@ -327,243 +328,23 @@ fn parse_text_line_with_interpolations(
 
 /// Build an expression tree from a slice of tokens.
 ///
-/// Handles references (`@variables.x`), literals, binary operators, and
-/// unary negation (`not`). Used by both interpolation and condition parsing.
-/// The `empty_expr` parameter controls what is returned when the token slice
-/// is empty (conditions default to `Expr::Bool(true)`, interpolations to `Expr::None`).
+/// Runs the shared [`expr`] parser over the tokens, so conditions and
+/// interpolations get the same precedence and grouping as expressions
+/// elsewhere. Tokens after a complete expression are ignored. The
+/// `empty_expr` parameter controls what is returned when the token slice is
+/// empty or holds no expression (conditions default to `Expr::Bool(true)`,
+/// interpolations to `Expr::None`).
 fn build_expr_from_tokens(tokens: &[(Token<'_>, Span)], empty_expr: Expr) -> Spanned<Expr> {
-    use crate::ast::Reference;
-
-    if tokens.is_empty() {
+    let (Some(first), Some(last)) = (tokens.first(), tokens.last()) else {
         return Spanned::new(empty_expr, 0..0);
-    }
-
-    let start = tokens[0].1.start;
-    let end = tokens.last().map(|t| t.1.end).unwrap_or(start);
-
-    let mut expr_parts: Vec<Spanned<Expr>> = Vec::new();
-    let mut ops: Vec<BinOp> = Vec::new();
-    let mut i = 0;
-    let mut negate_next = false;
-
-    while i < tokens.len() {
-        match &tokens[i].0 {
-            Token::Not => {
-                negate_next = true;
-                i += 1;
-            }
-            Token::At => {
-                let ref_start = tokens[i].1.start;
-                i += 1;
-                let mut ref_parts = Vec::new();
-                while i < tokens.len() {
-                    match &tokens[i].0 {
-                        Token::Variables => {
-                            ref_parts.push("variables".to_string());
-                            i += 1;
-                        }
-                        Token::Actions => {
-                            ref_parts.push("actions".to_string());
-                            i += 1;
-                        }
-                        Token::Outputs => {
-                            ref_parts.push("outputs".to_string());
-                            i += 1;
-                        }
-                        Token::Topic => {
-                            ref_parts.push("topic".to_string());
-                            i += 1;
-                        }
-                        Token::Inputs => {
-                            ref_parts.push("inputs".to_string());
-                            i += 1;
-                        }
-                        Token::Ident(s) => {
-                            ref_parts.push(s.to_string());
-                            i += 1;
-                        }
-                        Token::Dot => {
-                            i += 1;
-                        }
-                        _ => break,
-                    }
-                }
-                let ref_end = if i > 0 {
-                    tokens[i.saturating_sub(1)].1.end
-                } else {
-                    ref_start
-                };
-                let namespace = ref_parts.first().cloned().unwrap_or_default();
-                let path = ref_parts.into_iter().skip(1).collect();
-                let mut expr = Spanned::new(
-                    Expr::Reference(Reference { namespace, path }),
-                    ref_start..ref_end,
-                );
-                if negate_next {
-                    expr = Spanned::new(
-                        Expr::UnaryOp {
-                            op: UnaryOp::Not,
-                            operand: Box::new(expr),
-                        },
-                        ref_start..ref_end,
-                    );
-                    negate_next = false;
-                }
-                expr_parts.push(expr);
-            }
-            Token::StringLit(s) => {
-                let span = tokens[i].1;
-                let mut expr = Spanned::new(Expr::String(s.to_string()), span.start..span.end);
-                if negate_next {
-                    expr = Spanned::new(
-                        Expr::UnaryOp {
-                            op: UnaryOp::Not,
-                            operand: Box::new(expr),
-                        },
-                        span.start..span.end,
-                    );
-                    negate_next = false;
-                }
-                expr_parts.push(expr);
-                i += 1;
-            }
-            Token::NumberLit(n) => {
-                let span = tokens[i].1;
-                let mut expr = Spanned::new(Expr::Number(*n), span.start..span.end);
-                if negate_next {
-                    expr = Spanned::new(
-                        Expr::UnaryOp {
-                            op: UnaryOp::Not,
-                            operand: Box::new(expr),
-                        },
-                        span.start..span.end,
-                    );
-                    negate_next = false;
-                }
-                expr_parts.push(expr);
-                i += 1;
-            }
-            Token::True => {
-                let span = tokens[i].1;
-                let mut expr = Spanned::new(Expr::Bool(true), span.start..span.end);
-                if negate_next {
-                    expr = Spanned::new(
-                        Expr::UnaryOp {
-                            op: UnaryOp::Not,
-                            operand: Box::new(expr),
-                        },
-                        span.start..span.end,
-                    );
-                    negate_next = false;
-                }
-                expr_parts.push(expr);
-                i += 1;
-            }
-            Token::False => {
-                let span = tokens[i].1;
-                let mut expr = Spanned::new(Expr::Bool(false), span.start..span.end);
-                if negate_next {
-                    expr = Spanned::new(
-                        Expr::UnaryOp {
-                            op: UnaryOp::Not,
-                            operand: Box::new(expr),
-                        },
-                        span.start..span.end,
-                    );
-                    negate_next = false;
-                }
-                expr_parts.push(expr);
-                i += 1;
-            }
-            Token::None => {
-                let span = tokens[i].1;
-                let mut expr = Spanned::new(Expr::None, span.start..span.end);
-                if negate_next {
-                    expr = Spanned::new(
-                        Expr::UnaryOp {
-                            op: UnaryOp::Not,
-                            operand: Box::new(expr),
-                        },
-                        span.start..span.end,
-                    );
-                    negate_next = false;
-                }
-                expr_parts.push(expr);
-                i += 1;
-            }
-            Token::Ellipsis => {
-                let span = tokens[i].1;
-                expr_parts.push(Spanned::new(Expr::SlotFill, span.start..span.end));
-                i += 1;
-            }
-            Token::Plus => {
-                ops.push(BinOp::Add);
-                i += 1;
-            }
-            Token::Minus => {
-                ops.push(BinOp::Sub);
-                i += 1;
-            }
-            Token::And => {
-                ops.push(BinOp::And);
-                i += 1;
-            }
-            Token::Or => {
-                ops.push(BinOp::Or);
-                i += 1;
-            }
-            Token::Eq => {
-                ops.push(BinOp::Eq);
-                i += 1;
-            }
-            Token::Ne => {
-                ops.push(BinOp::Ne);
-                i += 1;
-            }
-            Token::Lt => {
-                ops.push(BinOp::Lt);
-                i += 1;
-            }
-            Token::Gt => {
-                ops.push(BinOp::Gt);
-                i += 1;
-            }
-            Token::Le => {
-                ops.push(BinOp::Le);
-                i += 1;
-            }
-            Token::Ge => {
-                ops.push(BinOp::Ge);
-                i += 1;
-            }
-            _ => {
-                i += 1;
-            }
-        }
-    }
-
-    if expr_parts.is_empty() {
-        return Spanned::new(empty_expr, start..end);
-    }
-
-    // Build the expression tree (left-to-right, no precedence for now)
-    let mut result = expr_parts.remove(0);
-    for (i, op) in ops.into_iter().enumerate() {
-        if i < expr_parts.len() {
-            let right = expr_parts.remove(0);
-            let span = result.span.start..right.span.end;
-            result = Spanned::new(
-                Expr::BinOp {
-                    left: Box::new(result),
-                    op,
-                    right: Box::new(right),
-                },
-                span,
-            );
-        }
-    }
+    };
 
-    result
+    let eoi = Span::new((), last.1.end..last.1.end);
+    expr()
+        .then_ignore(any().repeated())
+        .parse(tokens.split_token_span(eoi))
+        .into_output()
+        .unwrap_or_else(|| Spanned::new(empty_expr, first.1.start..last.1.end))
 }
 
 /// Build an expression from interpolation tokens.
@@ -621,9 +402,23 @@ fn parse_instruction_parts_with_depth(
                     }
                 }
 
-                // Parse the main line with interpolations
+                // Blank lines and comments after the block aren't part of its text
+                while matches!(
+                    continuation_tokens.last(),
+                    Some((Token::Newline | Token::Comment(_), _))
+                ) {
+                    continuation_tokens.pop();
+                }
+
+                // Parse the main line as a heading, or as text with interpolations
                 let line_tokens = &tokens[line_start..line_end];
-                let mut line_parts = parse_text_line_with_interpolations(line_tokens, start_span);
+                let mut line_parts = match parse_heading(line_tokens) {
+                    Some(heading) => {
+                        let end = line_tokens.last().map_or(start_span.end, |t| t.1.end);
+                        vec![Spanned::new(heading, start_span.start..end)]
+                    }
+                    None => parse_text_line_with_interpolations(line_tokens, start_span),
+                };
 
                 // If there's continuation, add it to the last text part or create a new one
                 if !continuation_tokens.is_empty() {
@@ -675,7 +470,22 @@ fn parse_instruction_parts_with_depth(
     parts
 }
 
-/// Parse an if block from tokens.
+/// A `|` line holding only `##` or more and a title is a section heading.
+/// The lexer reads it as a comment, without the first `#`.
+fn parse_heading(tokens: &[(Token<'_>, Span)]) -> Option<InstructionPart> {
+    let [(Token::Comment(comment), _)] = tokens else {
+        return None;
+    };
+    let title = comment.trim_start_matches('#');
+    let level = comment.len() - title.len() + 1;
+    let title = title.trim();
+    (level >= 2 && !title.is_empty()).then(|| InstructionPart::Heading {
+        level,
+        title: title.to_string(),
+    })
+}
+
+/// Parse an if block, with any `elif` and `else` after it, from tokens.
 fn parse_if_block(
     tokens: &[(Token<'_>, Span)],
     start: usize,
@@ -713,8 +523,12 @@ fn parse_if_block(
     // Use depth 1 to prevent nested if statements inside if bodies
     let then_parts = parse_instruction_parts_with_depth(&then_tokens, 1);
 
-    // Check for else
-    let else_parts = if i < tokens.len() && matches!(&tokens[i].0, Token::Else) {
+    // Check for elif, parsed as an `if` alone in the else branch, or else
+    let else_parts = if i < tokens.len() && matches!(&tokens[i].0, Token::Elif) {
+        let (part, new_i) = parse_if_block(tokens, i);
+        i = new_i;
+        part.map(|p| vec![p])
+    } else if i < tokens.len() && matches!(&tokens[i].0, Token::Else) {
         i += 1;
         if i < tokens.len() && matches!(&tokens[i].0, Token::Colon) {
             i += 1;
@@ -818,7 +632,10 @@ fn skip_if_block(tokens: &[(Token<'_>, Span)], start: usize) -> usize {
             i += 1;
         }
     }
-    // Check for else block and skip it too
+    // Check for elif and else blocks and skip them too
+    if i < tokens.len() && matches!(&tokens[i].0, Token::Elif) {
+        return skip_if_block(tokens, i);
+    }
     if i < tokens.len() && matches!(&tokens[i].0, Token::Else) {
         i += 1;
         if i < tokens.len() && matches!(&tokens[i].0, Token::Colon) {
@@ -892,19 +709,17 @@ fn needs_space_after_last(s: &str) -> bool {
 
 /// Convert a single token to its text representation.
 /// Returns `Cow::Borrowed` for static tokens and identifiers (zero-alloc),
-/// only allocating for `StringLit` (needs wrapping quotes) and `NumberLit` (needs formatting).
+/// only allocating for `StringLit` (needs wrapping quotes).
 fn token_to_text<'a>(tok: &Token<'a>) -> Cow<'a, str> {
+    if let Some(text) = tok.keyword_text() {
+        return Cow::Borrowed(text);
+    }
     match tok {
         Token::Ident(s) => Cow::Borrowed(*s),
         Token::StringLit(s) => Cow::Owned(format!("\"{}\"", s)),
-        Token::NumberLit(n) => {
-            if n.fract() == 0.0 {
-                Cow::Owned(format!("{}", *n as i64))
-            } else {
-                Cow::Owned(n.to_string())
-            }
+        Token::UnicodeText(s) | Token::NumberLit(s) | Token::DateLit(s) | Token::CurrencyLit(s) => {
+            Cow::Borrowed(*s)
         }
-        Token::UnicodeText(s) => Cow::Borrowed(*s),
         Token::Newline => Cow::Borrowed("\n"),
         Token::Colon => Cow::Borrowed(":"),
         Token::Dot => Cow::Borrowed("."),
@@ -919,7 +734,6 @@ fn token_to_text<'a>(tok: &Token<'a>) -> Cow<'a, str> {
         Token::RBrace => Cow::Borrowed("}"),
         Token::ExclBrace => Cow::Borrowed("{!"),
         Token::DoubleLBrace => Cow::Borrowed("{{"),
-        Token::DoubleBrace => Cow::Borrowed("}}"),
         Token::At => Cow::Borrowed("@"),
         Token::Slash => Cow::Borrowed("/"),
         Token::Question => Cow::Borrowed("?"),
@@ -945,72 +759,9 @@ fn token_to_text<'a>(tok: &Token<'a>) -> Cow<'a, str> {
         Token::Ellipsis => Cow::Borrowed("..."),
         Token::Arrow => Cow::Borrowed("->"),
         Token::Pipe => Cow::Borrowed("|"),
-        // Keywords
-        Token::If => Cow::Borrowed("if"),
-        Token::Else => Cow::Borrowed("else"),
-        Token::And => Cow::Borrowed("and"),
-        Token::Or => Cow::Borrowed("or"),
-        Token::Not => Cow::Borrowed("not"),
-        Token::True => Cow::Borrowed("True"),
-        Token::False => Cow::Borrowed("False"),
-        Token::None => Cow::Borrowed("None"),
-        Token::To => Cow::Borrowed("to"),
-        Token::With => Cow::Borrowed("with"),
-        Token::Set => Cow::Borrowed("set"),
-        Token::Run => Cow::Borrowed("run"),
-        Token::As => Cow::Borrowed("as"),
-        Token::Is => Cow::Borrowed("is"),
-        Token::Available => Cow::Borrowed("available"),
-        Token::When => Cow::Borrowed("when"),
-        Token::Transition => Cow::Borrowed("transition"),
-        Token::Variables => Cow::Borrowed("variables"),
-        Token::Actions => Cow::Borrowed("actions"),
-        Token::Outputs => Cow::Borrowed("outputs"),
-        Token::Inputs => Cow::Borrowed("inputs"),
-        Token::Topic => Cow::Borrowed("topic"),
-        Token::Description => Cow::Borrowed("description"),
-        Token::Source => Cow::Borrowed("source"),
-        Token::Target => Cow::Borrowed("target"),
-        Token::Label => Cow::Borrowed("label"),
-        Token::Config => Cow::Borrowed("config"),
-        Token::System => Cow::Borrowed("system"),
-        Token::Reasoning => Cow::Borrowed("reasoning"),
-        Token::Instructions => Cow::Borrowed("instructions"),
-        Token::Messages => Cow::Borrowed("messages"),
-        Token::Welcome => Cow::Borrowed("welcome"),
-        Token::Error => Cow::Borrowed("error"),
-        Token::Connection => Cow::Borrowed("connection"),
-        Token::Connections => Cow::Borrowed("connections"),
-        Token::Knowledge => Cow::Borrowed("knowledge"),
-        Token::Language => Cow::Borrowed("language"),
-        Token::StartAgent => Cow::Borrowed("start_agent"),
-        Token::BeforeReasoning => Cow::Borrowed("before_reasoning"),
-        Token::AfterReasoning => Cow::Borrowed("after_reasoning"),
-        Token::Mutable => Cow::Borrowed("mutable"),
-        Token::Linked => Cow::Borrowed("linked"),
-        Token::String => Cow::Borrowed("string"),
-        Token::Number => Cow::Borrowed("number"),
-        Token::Boolean => Cow::Borrowed("boolean"),
-        Token::Object => Cow::Borrowed("object"),
-        Token::List => Cow::Borrowed("list"),
-        Token::Date => Cow::Borrowed("date"),
-        Token::Timestamp => Cow::Borrowed("timestamp"),
-        Token::Currency => Cow::Borrowed("currency"),
-        Token::Id => Cow::Borrowed("id"),
-        Token::Datetime => Cow::Borrowed("datetime"),
-        Token::Time => Cow::Borrowed("time"),
-        Token::Integer => Cow::Borrowed("integer"),
-        Token::Long => Cow::Borrowed("long"),
         Token::ColonPipe => Cow::Borrowed(":|"),
         Token::ColonArrow => Cow::Borrowed(":->"),
-        Token::IsRequired => Cow::Borrowed("is_required"),
-        Token::IsDisplayable => Cow::Borrowed("is_displayable"),
-        Token::IsUsedByPlanner => Cow::Borrowed("is_used_by_planner"),
-        Token::ComplexDataTypeName => Cow::Borrowed("complex_data_type_name"),
-        Token::FilterFromAgent => Cow::Borrowed("filter_from_agent"),
-        Token::RequireUserConfirmation => Cow::Borrowed("require_user_confirmation"),
-        Token::IncludeInProgressIndicator => Cow::Borrowed("include_in_progress_indicator"),
-        Token::ProgressIndicatorMessage => Cow::Borrowed("progress_indicator_message"),
         Token::Comment(_) | Token::Indent | Token::Dedent => Cow::Borrowed(""),
+        _ => unreachable!("keywords are handled above"),
     }
 }
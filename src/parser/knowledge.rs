@@ -0,0 +1,50 @@
+//! Knowledge block parser.
+//!
+//! Parses the `knowledge:` block.
+
+use crate::ast::{KnowledgeBlock, KnowledgeEntry, Spanned};
+use crate::lexer::Token;
+use chumsky::prelude::*;
+
+use super::expressions::expr;
+use super::primitives::{
+    dedent, indent, newline, skip_block_noise, spanned_ident, to_ast_span, ParserInput, Span,
+};
+
+/// Parse a knowledge entry (key: value pair).
+fn knowledge_entry<'tokens, 'src: 'tokens>() -> impl Parser<
+    'tokens,
+    ParserInput<'tokens, 'src>,
+    Spanned<KnowledgeEntry>,
+    extra::Err<Rich<'tokens, Token<'src>, Span>>,
+> + Clone {
+    spanned_ident()
+        .then_ignore(just(Token::Colon))
+        .then(expr())
+        .map_with(|(name, value), e| {
+            Spanned::new(KnowledgeEntry { name, value }, to_ast_span(e.span()))
+        })
+}
+
+/// Parse the knowledge block.
+pub(crate) fn knowledge_block<'tokens, 'src: 'tokens>() -> impl Parser<
+    'tokens,
+    ParserInput<'tokens, 'src>,
+    Spanned<KnowledgeBlock>,
+    extra::Err<Rich<'tokens, Token<'src>, Span>>,
+> + Clone {
+    just(Token::Knowledge)
+        .ignore_then(just(Token::Colon))
+        .ignore_then(newline())
+        .ignore_then(skip_block_noise())
+        .ignore_then(indent())
+        .ignore_then(
+            knowledge_entry()
+                .separated_by(skip_block_noise())
+                .allow_trailing()
+                .collect::<Vec<_>>(),
+        )
+        .then_ignore(skip_block_noise())
+        .then_ignore(dedent())
+        .map_with(|entries, e| Spanned::new(KnowledgeBlock { entries }, to_ast_span(e.span())))
+}
@@ -0,0 +1,314 @@
+//! Input size and nesting guards.
+//!
+//! The parser and the passes over its AST recurse once per level of
+//! nesting, so an adversarial file (a few thousand `not`s, or one long
+//! `a or a or …` chain) can overflow the stack, and a huge one can exhaust
+//! memory in the language server or the browser. [`ParseOptions`] caps the
+//! input before parsing starts and reports a [`LimitExceeded`] diagnostic
+//! instead.
+//!
+//! Every parse entry point applies [`ParseOptions::default`];
+//! [`parse_with_options`](super::parse_with_options) takes other limits.
+
+use super::primitives::SpannedToken;
+use crate::error::ParseErrorInfo;
+use crate::lexer::Token;
+use crate::text_pos::{self, Encoding};
+use std::ops::Range;
+use thiserror::Error;
+
+/// Limits on the input a parse accepts.
+///
+/// ```rust
+/// use busbar_sf_agentscript::parser::{parse_with_options, ParseOptions};
+///
+/// let source = format!("topic main:\n   description: \"{}\"\n", "x".repeat(100));
+/// let options = ParseOptions { max_source_len: 64, ..ParseOptions::default() };
+/// let (ast, errors) = parse_with_options(&source, &options);
+/// assert!(ast.is_none());
+/// assert!(errors[0].message.contains("more than the limit of 64"));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// Largest source accepted, in bytes.
+    pub max_source_len: usize,
+    /// Most tokens accepted, including indentation tokens.
+    pub max_tokens: usize,
+    /// Deepest nesting accepted. Indentation levels, open brackets, and
+    /// prefix `not`s and `-`s on the current line each count as one level.
+    pub max_depth: usize,
+    /// Most binary operators accepted on one line. A chain like
+    /// `a or b or …` does not nest in the source, but it still builds an
+    /// expression tree one level deeper per operator.
+    pub max_operators: usize,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            max_source_len: 4 * 1024 * 1024,
+            max_tokens: 1_000_000,
+            max_depth: 128,
+            max_operators: 512,
+        }
+    }
+}
+
+/// Input rejected by a [`ParseOptions`] limit.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum LimitExceeded {
+    #[error("input is {len} bytes, more than the limit of {limit}")]
+    SourceTooLarge { len: usize, limit: usize },
+
+    #[error("input has more than the limit of {limit} tokens")]
+    TooManyTokens { limit: usize, span: Range<usize> },
+
+    #[error("input nests more than the limit of {limit} levels deep")]
+    TooDeep { limit: usize, span: Range<usize> },
+
+    #[error("line has more than the limit of {limit} operators")]
+    TooManyOperators { limit: usize, span: Range<usize> },
+}
+
+impl LimitExceeded {
+    /// Where the limit was crossed; `None` for the size of the whole file.
+    pub fn span(&self) -> Option<Range<usize>> {
+        match self {
+            LimitExceeded::SourceTooLarge { .. } => None,
+            LimitExceeded::TooManyTokens { span, .. }
+            | LimitExceeded::TooDeep { span, .. }
+            | LimitExceeded::TooManyOperators { span, .. } => Some(span.clone()),
+        }
+    }
+}
+
+impl ParseOptions {
+    /// Check the source length, before lexing.
+    pub fn check_source(&self, source: &str) -> Result<(), LimitExceeded> {
+        if source.len() > self.max_source_len {
+            return Err(LimitExceeded::SourceTooLarge {
+                len: source.len(),
+                limit: self.max_source_len,
+            });
+        }
+        Ok(())
+    }
+
+    /// Check the token count and nesting depth, before parsing.
+    pub(crate) fn check_tokens(&self, tokens: &[SpannedToken<'_>]) -> Result<(), LimitExceeded> {
+        if let Some((_, span)) = tokens.get(self.max_tokens) {
+            return Err(LimitExceeded::TooManyTokens {
+                limit: self.max_tokens,
+                span: span.start..span.end,
+            });
+        }
+
+        let (mut indent, mut brackets, mut prefixes, mut operators) =
+            (0usize, 0usize, 0usize, 0usize);
+        let mut previous: Option<&Token<'_>> = None;
+        for (token, span) in tokens {
+            let current = previous.replace(token);
+            match token {
+                Token::Indent => indent += 1,
+                Token::Dedent => indent = indent.saturating_sub(1),
+                Token::Newline => (prefixes, operators) = (0, 0),
+                Token::LParen | Token::LBracket | Token::LBrace | Token::ExclBrace => brackets += 1,
+                Token::DoubleLBrace => brackets += 2,
+                Token::RParen | Token::RBracket | Token::RBrace => {
+                    brackets = brackets.saturating_sub(1)
+                }
+                // `is not` is one operator
+                Token::Not if current == Some(&Token::Is) => continue,
+                Token::Not => prefixes += 1,
+                Token::Minus if current.is_none_or(starts_operand) => prefixes += 1,
+                Token::Eq
+                | Token::Ne
+                | Token::Lt
+                | Token::Gt
+                | Token::Le
+                | Token::Ge
+                | Token::Is
+                | Token::And
+                | Token::Or
+                | Token::Plus
+                | Token::Minus
+                | Token::Star
+                | Token::Slash
+                | Token::Percent => {
+                    operators += 1;
+                    if operators > self.max_operators {
+                        return Err(LimitExceeded::TooManyOperators {
+                            limit: self.max_operators,
+                            span: span.start..span.end,
+                        });
+                    }
+                    continue;
+                }
+                _ => continue,
+            }
+            if indent + brackets + prefixes > self.max_depth {
+                return Err(LimitExceeded::TooDeep {
+                    limit: self.max_depth,
+                    span: span.start..span.end,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether an operand is expected after `token`, making a following `-`
+/// a prefix rather than a subtraction.
+fn starts_operand(token: &Token<'_>) -> bool {
+    matches!(
+        token,
+        Token::Newline
+            | Token::Indent
+            | Token::Dedent
+            | Token::Colon
+            | Token::Comma
+            | Token::Assign
+            | Token::If
+            | Token::Elif
+            | Token::When
+            | Token::To
+            | Token::LParen
+            | Token::LBracket
+            | Token::LBrace
+            | Token::ExclBrace
+            | Token::DoubleLBrace
+            | Token::Eq
+            | Token::Ne
+            | Token::Lt
+            | Token::Gt
+            | Token::Le
+            | Token::Ge
+            | Token::Is
+            | Token::Not
+            | Token::And
+            | Token::Or
+            | Token::Plus
+            | Token::Minus
+            | Token::Star
+            | Token::Slash
+            | Token::Percent
+    )
+}
+
+/// A parse error for a crossed limit.
+pub(super) fn limit_error(source: &str, error: &LimitExceeded) -> ParseErrorInfo {
+    let span = error.span();
+    let location =
+        text_pos::location(source, span.as_ref().map_or(0, |s| s.start), Encoding::Utf32);
+    let (line, col) = (location.line as usize + 1, location.column as usize + 1);
+    ParseErrorInfo {
+        message: format!("Parse error at line {}, column {}: {}", line, col, error),
+        span,
+        expected: vec![],
+        found: None,
+        contexts: vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::parse_with_options;
+    use super::*;
+
+    fn condition(expr: &str) -> String {
+        format!(
+            "topic main:\n   reasoning:\n      instructions: ->\n         if {}:\n            | hi\n",
+            expr
+        )
+    }
+
+    #[test]
+    fn test_default_limits_accept_normal_files() {
+        let source = condition(&format!("{}True", "not ".repeat(50)));
+        let (ast, errors) = parse_with_options(&source, &ParseOptions::default());
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert!(ast.is_some());
+    }
+
+    #[test]
+    fn test_deep_nesting_is_rejected() {
+        // Deep enough to overflow the stack if it were parsed
+        let source = condition(&format!("{}True", "not ".repeat(20_000)));
+        let (ast, errors) = crate::parser::parse_with_structured_errors_all(&source);
+        assert!(ast.is_none());
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0]
+            .message
+            .ends_with("input nests more than the limit of 128 levels deep"));
+        assert_eq!(&source[errors[0].span.clone().unwrap()], "not");
+
+        let token = crate::parser::CancellationToken::new();
+        let (ast, cancellable_errors) = crate::parser::parse_cancellable(&source, &token).unwrap();
+        assert!(ast.is_none());
+        assert_eq!(cancellable_errors[0].message, errors[0].message);
+
+        let chain = vec!["True"; 20_000].join(" or ");
+        let errors = crate::parse(&condition(&chain)).unwrap_err();
+        assert!(
+            errors[0].ends_with("line has more than the limit of 512 operators"),
+            "{:?}",
+            errors
+        );
+    }
+
+    #[test]
+    fn test_operator_chains_do_not_count_as_nesting() {
+        let sum = vec!["1"; 130].join(" + ");
+        let source = condition(&format!("@variables.a == {} and @variables.b is not None", sum));
+        let (ast, errors) = parse_with_options(&source, &ParseOptions::default());
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert!(ast.is_some());
+
+        // The longest chain accepted is still shallow enough for the passes
+        let longest = condition(&vec!["True"; 512].join(" or "));
+        let ast = crate::parse(&longest).unwrap();
+        crate::validate_ast(&ast);
+        crate::serialize(&ast);
+
+        let negated = condition(&format!("{}1 > 0", "- ".repeat(200)));
+        let errors = crate::parse(&negated).unwrap_err();
+        assert!(errors[0].contains("levels deep"), "{:?}", errors);
+    }
+
+    #[test]
+    fn test_custom_limits() {
+        let source = condition("(@variables.a + 1) * 2 > 3");
+        let tight = ParseOptions {
+            max_depth: 3,
+            ..ParseOptions::default()
+        };
+        let (_, errors) = parse_with_options(&source, &tight);
+        assert!(errors[0].message.contains("limit of 3 levels"));
+        assert_eq!(&source[errors[0].span.clone().unwrap()], "(");
+
+        let short_lines = ParseOptions {
+            max_operators: 2,
+            ..ParseOptions::default()
+        };
+        let (_, errors) = parse_with_options(&source, &short_lines);
+        assert!(errors[0].message.contains("limit of 2 operators"));
+        assert_eq!(&source[errors[0].span.clone().unwrap()], ">");
+
+        let few_tokens = ParseOptions {
+            max_tokens: 10,
+            ..ParseOptions::default()
+        };
+        let (_, errors) = parse_with_options(&source, &few_tokens);
+        assert!(errors[0].message.contains("limit of 10 tokens"));
+
+        let small = ParseOptions {
+            max_source_len: 10,
+            ..ParseOptions::default()
+        };
+        let (_, errors) = parse_with_options(&source, &small);
+        assert_eq!(errors[0].span, None);
+        assert!(errors[0]
+            .message
+            .starts_with("Parse error at line 1, column 1: input is"));
+    }
+}
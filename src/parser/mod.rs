@@ -61,22 +61,32 @@
 //! - `config` - Config block parsing
 //! - `variables` - Variable declarations
 //! - `system` - System instructions and messages
+//! - `knowledge` - Knowledge source configuration
 //! - `topics` - Topic and start_agent blocks
 //! - `actions` - Action definitions
 //! - `reasoning` - Reasoning blocks
 //! - `expressions` - Expression parsing
 //! - `instructions` - Static and dynamic instructions
+//! - `doc_comments` - Attaching `#` doc comments to definitions
+//! - `profile` - Per-block timing via [`parse_with_profile()`]
+//! - `cancel` - Cancellable and async parsing via [`parse_cancellable()`]
+//! - `limits` - Input size and nesting guards via [`ParseOptions`]
 //!
 //! [`AgentFile`]: crate::ast::AgentFile
 
 mod actions;
+mod cancel;
 mod config;
 mod connections;
 mod directives;
+mod doc_comments;
 mod expressions;
 mod instructions;
+mod knowledge;
 mod language;
+mod limits;
 mod primitives;
+mod profile;
 mod reasoning;
 mod system;
 #[cfg(not(test))]
@@ -86,27 +96,16 @@ mod variables;
 
 use crate::ast::AgentFile;
 use crate::lexer;
+use crate::text_pos::{self, Encoding};
 
 // Re-export the span type
+pub use cancel::{
+    parse_async, parse_cancellable, parse_with_progress, CancellationToken, Cancelled, ParseOutput,
+    ParseProgress, ParseTask,
+};
+pub use limits::{LimitExceeded, ParseOptions};
 pub use primitives::Span;
-
-/// Convert a character offset to (line, column) - both 1-indexed
-fn offset_to_line_col(source: &str, offset: usize) -> (usize, usize) {
-    let mut line = 1;
-    let mut col = 1;
-    for (i, ch) in source.char_indices() {
-        if i >= offset {
-            break;
-        }
-        if ch == '\n' {
-            line += 1;
-            col = 1;
-        } else {
-            col += 1;
-        }
-    }
-    (line, col)
-}
+pub use profile::{parse_with_profile, BlockProfile, ParseProfile};
 
 /// Get the line content at a given line number (1-indexed)
 fn get_line_content(source: &str, line_num: usize) -> &str {
@@ -119,7 +118,8 @@ fn format_parse_error<'tokens, 'src>(
     error: &Rich<'tokens, crate::lexer::Token<'src>, primitives::Span>,
 ) -> String {
     let span = error.span();
-    let (line, col) = offset_to_line_col(source, span.start);
+    let location = text_pos::location(source, span.start, Encoding::Utf32);
+    let (line, col) = (location.line as usize + 1, location.column as usize + 1);
     let line_content = get_line_content(source, line);
 
     // Build expected string
@@ -132,10 +132,11 @@ fn format_parse_error<'tokens, 'src>(
         format!(", expected one of: {}", expected.join(", "))
     };
 
-    // Build found string
-    let found_str = match error.found() {
-        Some(tok) => format!("found '{}'", tok),
-        None => "found end of input".to_string(),
+    // Build found string; custom errors carry their own message instead
+    let found_str = match (error.reason(), error.found()) {
+        (chumsky::error::RichReason::Custom(message), _) => message.clone(),
+        (_, Some(tok)) => format!("found '{}'", tok),
+        (_, None) => "found end of input".to_string(),
     };
 
     // Build context chain from .labelled() calls - shows WHERE in the parse tree we failed
@@ -146,7 +147,7 @@ fn format_parse_error<'tokens, 'src>(
         let ctx_labels: Vec<String> = contexts
             .iter()
             .map(|(label, ctx_span)| {
-                let (ctx_line, _) = offset_to_line_col(source, ctx_span.start);
+                let ctx_line = text_pos::location(source, ctx_span.start, Encoding::Utf32).line + 1;
                 format!("{} (line {})", label, ctx_line)
             })
             .collect();
@@ -179,7 +180,8 @@ fn format_lexer_error(
     span_start: usize,
     span_end: usize,
 ) -> String {
-    let (line, col) = offset_to_line_col(source, span_start);
+    let location = text_pos::location(source, span_start, Encoding::Utf32);
+    let (line, col) = (location.line as usize + 1, location.column as usize + 1);
     let line_content = get_line_content(source, line);
 
     format!(
@@ -203,10 +205,11 @@ use primitives::{skip_toplevel_noise, ParserInput};
 
 use chumsky::input::Input as _;
 use chumsky::prelude::*;
-use chumsky::recovery::skip_then_retry_until;
+use chumsky::recovery::{skip_then_retry_until, via_parser};
 
 use config::config_block;
 use connections::{connection_block, connections_wrapper_block};
+use knowledge::knowledge_block;
 use language::language_block;
 use system::system_block;
 use topics::{start_agent_block, topic_block};
@@ -230,6 +233,11 @@ pub fn parse(source: &str) -> Result<AgentFile, Vec<String>> {
 /// Returns both a partial AST (if recovery succeeded) and ALL errors found.
 /// This allows collecting multiple errors in a single parse pass.
 pub fn parse_with_errors(source: &str) -> (Option<AgentFile>, Vec<String>) {
+    let options = ParseOptions::default();
+    if let Err(e) = options.check_source(source) {
+        return (None, vec![limits::limit_error(source, &e).to_string()]);
+    }
+
     // Phase 1: Lexical analysis with indentation tokens
     let tokens = match lexer::lex_with_indentation(source) {
         Ok(tokens) => tokens,
@@ -244,13 +252,19 @@ pub fn parse_with_errors(source: &str) -> (Option<AgentFile>, Vec<String>) {
             return (None, errors);
         }
     };
+    if let Err(e) = options.check_tokens(&tokens) {
+        return (None, vec![limits::limit_error(source, &e).to_string()]);
+    }
 
     // Phase 2: Parse into AST using token-based parser
     let eoi_span = primitives::Span::new((), source.len()..source.len());
     let token_stream = tokens.as_slice().split_token_span(eoi_span);
 
     // Use into_output_errors to get BOTH partial results AND all errors
-    let (result, errs) = agent_file_parser().parse(token_stream).into_output_errors();
+    let (mut result, errs) = agent_file_parser().parse(token_stream).into_output_errors();
+    if let Some(agent) = &mut result {
+        doc_comments::attach_doc_comments(source, agent);
+    }
 
     let errors: Vec<String> = errs.iter().map(|e| format_parse_error(source, e)).collect();
     (result, errors)
@@ -285,64 +299,90 @@ pub fn parse_with_structured_errors(
 pub fn parse_with_structured_errors_all(
     source: &str,
 ) -> (Option<AgentFile>, Vec<crate::error::ParseErrorInfo>) {
-    use crate::error::ParseErrorInfo;
+    parse_with_options(source, &ParseOptions::default())
+}
+
+/// Parse an AgentScript file within the given size and nesting limits,
+/// returning a partial AST and all errors.
+///
+/// Input over a limit is not parsed; the result is `None` and a single
+/// error describing the limit.
+pub fn parse_with_options(
+    source: &str,
+    options: &ParseOptions,
+) -> (Option<AgentFile>, Vec<crate::error::ParseErrorInfo>) {
+    if let Err(e) = options.check_source(source) {
+        return (None, vec![limits::limit_error(source, &e)]);
+    }
 
     // Phase 1: Lexical analysis with indentation tokens
     let tokens = match lexer::lex_with_indentation(source) {
         Ok(tokens) => tokens,
         Err(errs) => {
-            let errors: Vec<ParseErrorInfo> = errs
+            let errors = errs
                 .iter()
-                .map(|e| {
-                    let span = e.span();
-                    let (line, col) = offset_to_line_col(source, span.start);
-                    ParseErrorInfo {
-                        message: format!(
-                            "Lexer error at line {}, column {}: {}",
-                            line,
-                            col,
-                            e.reason()
-                        ),
-                        span: Some(span.start..span.end),
-                        expected: vec![],
-                        found: None,
-                        contexts: vec![],
-                    }
-                })
+                .map(|e| structured_lex_error(source, e))
                 .collect();
             return (None, errors);
         }
     };
+    if let Err(e) = options.check_tokens(&tokens) {
+        return (None, vec![limits::limit_error(source, &e)]);
+    }
 
     // Phase 2: Parse into AST using token-based parser
     let eoi_span = primitives::Span::new((), source.len()..source.len());
     let token_stream = tokens.as_slice().split_token_span(eoi_span);
 
     // Use into_output_errors to get BOTH partial results AND all errors
-    let (result, errs) = agent_file_parser().parse(token_stream).into_output_errors();
+    let (mut result, errs) = agent_file_parser().parse(token_stream).into_output_errors();
+    if let Some(agent) = &mut result {
+        doc_comments::attach_doc_comments(source, agent);
+    }
 
-    let errors: Vec<ParseErrorInfo> = errs
+    let errors = errs
         .iter()
-        .map(|e| {
-            let span = e.span();
-            let (line, col) = offset_to_line_col(source, span.start);
-            // Collect contexts from labelled parsers
-            let contexts: Vec<(String, std::ops::Range<usize>)> = e
-                .contexts()
-                .map(|(label, ctx_span)| (label.to_string(), ctx_span.start..ctx_span.end))
-                .collect();
+        .map(|e| structured_parse_error(source, e))
+        .collect();
+    (result, errors)
+}
 
-            ParseErrorInfo {
-                message: format!("Parse error at line {}, column {}: {}", line, col, e.reason()),
-                span: Some(span.start..span.end),
-                expected: e.expected().map(|exp| format!("{}", exp)).collect(),
-                found: e.found().map(|tok| format!("{}", tok)),
-                contexts,
-            }
-        })
+fn structured_lex_error<'src>(
+    source: &str,
+    error: &Rich<'src, char, lexer::Span>,
+) -> crate::error::ParseErrorInfo {
+    let span = error.span();
+    let location = text_pos::location(source, span.start, Encoding::Utf32);
+    let (line, col) = (location.line as usize + 1, location.column as usize + 1);
+    crate::error::ParseErrorInfo {
+        message: format!("Lexer error at line {}, column {}: {}", line, col, error.reason()),
+        span: Some(span.start..span.end),
+        expected: vec![],
+        found: None,
+        contexts: vec![],
+    }
+}
+
+fn structured_parse_error<'tokens, 'src>(
+    source: &str,
+    error: &Rich<'tokens, Token<'src>, primitives::Span>,
+) -> crate::error::ParseErrorInfo {
+    let span = error.span();
+    let location = text_pos::location(source, span.start, Encoding::Utf32);
+    let (line, col) = (location.line as usize + 1, location.column as usize + 1);
+    // Collect contexts from labelled parsers
+    let contexts: Vec<(String, std::ops::Range<usize>)> = error
+        .contexts()
+        .map(|(label, ctx_span)| (label.to_string(), ctx_span.start..ctx_span.end))
         .collect();
 
-    (result, errors)
+    crate::error::ParseErrorInfo {
+        message: format!("Parse error at line {}, column {}: {}", line, col, error.reason()),
+        span: Some(span.start..span.end),
+        expected: error.expected().map(|exp| format!("{}", exp)).collect(),
+        found: error.found().map(|tok| format!("{}", tok)),
+        contexts,
+    }
 }
 
 // ============================================================================
@@ -350,8 +390,8 @@ pub fn parse_with_structured_errors_all(
 // ============================================================================
 
 use crate::ast::{
-    ConfigBlock, ConnectionBlock, LanguageBlock, Spanned, StartAgentBlock, SystemBlock, TopicBlock,
-    VariablesBlock,
+    ConfigBlock, ConnectionBlock, KnowledgeBlock, LanguageBlock, RecoveredBlock, Spanned,
+    StartAgentBlock, SystemBlock, TopicBlock, VariablesBlock,
 };
 use crate::lexer::Token;
 
@@ -362,10 +402,13 @@ enum TopLevelBlock {
     System(Spanned<SystemBlock>),
     StartAgent(Spanned<StartAgentBlock>),
     Topic(Spanned<TopicBlock>),
+    Knowledge(Spanned<KnowledgeBlock>),
     Language(Spanned<LanguageBlock>),
     Connection(Spanned<ConnectionBlock>),
     /// Multiple connections from a `connections:` wrapper block.
     Connections(Vec<Spanned<ConnectionBlock>>),
+    /// A block that failed to parse.
+    Recovered(Spanned<RecoveredBlock>),
 }
 
 /// Parse a complete agent file.
@@ -374,6 +417,16 @@ fn agent_file_parser<'tokens, 'src: 'tokens>() -> impl Parser<
     ParserInput<'tokens, 'src>,
     AgentFile,
     extra::Err<Rich<'tokens, Token<'src>, primitives::Span>>,
+> + Clone {
+    top_level_blocks().map(assemble_file)
+}
+
+/// Parse a sequence of top-level blocks, recovering from malformed ones.
+fn top_level_blocks<'tokens, 'src: 'tokens>() -> impl Parser<
+    'tokens,
+    ParserInput<'tokens, 'src>,
+    Vec<TopLevelBlock>,
+    extra::Err<Rich<'tokens, Token<'src>, primitives::Span>>,
 > + Clone {
     // Recovery strategy: when parsing fails, skip until we find a top-level keyword
     // and retry. This captures errors with proper context from .labelled() calls.
@@ -383,6 +436,7 @@ fn agent_file_parser<'tokens, 'src: 'tokens>() -> impl Parser<
         just(Token::Config).ignored(),
         just(Token::Variables).ignored(),
         just(Token::System).ignored(),
+        just(Token::Knowledge).ignored(),
         just(Token::Language).ignored(),
         just(Token::Connection).ignored(),
         just(Token::Connections).ignored(),
@@ -397,31 +451,104 @@ fn agent_file_parser<'tokens, 'src: 'tokens>() -> impl Parser<
             system_block().map(TopLevelBlock::System),
             start_agent_block().map(TopLevelBlock::StartAgent),
             topic_block().map(TopLevelBlock::Topic),
+            knowledge_block().map(TopLevelBlock::Knowledge),
             language_block().map(TopLevelBlock::Language),
             connection_block().map(TopLevelBlock::Connection),
             connections_wrapper_block().map(TopLevelBlock::Connections),
         )))
+        .recover_with(via_parser(
+            skip_toplevel_noise()
+                .ignore_then(recovered_block())
+                .map(TopLevelBlock::Recovered),
+        ))
         .recover_with(skip_then_retry_until(any().ignored(), recovery_until))
         .repeated()
         .collect::<Vec<_>>()
         .then_ignore(skip_toplevel_noise())
         .then_ignore(end())
-        .map(|blocks| {
-            let mut file = AgentFile::default();
-
-            for block in blocks {
-                match block {
-                    TopLevelBlock::Config(c) => file.config = Some(c),
-                    TopLevelBlock::Variables(v) => file.variables = Some(v),
-                    TopLevelBlock::System(s) => file.system = Some(s),
-                    TopLevelBlock::StartAgent(sa) => file.start_agent = Some(sa),
-                    TopLevelBlock::Topic(t) => file.topics.push(t),
-                    TopLevelBlock::Language(l) => file.language = Some(l),
-                    TopLevelBlock::Connection(c) => file.connections.push(c),
-                    TopLevelBlock::Connections(cs) => file.connections.extend(cs),
-                }
+}
+
+/// Build an agent file from its top-level blocks, in source order.
+fn assemble_file(blocks: Vec<TopLevelBlock>) -> AgentFile {
+    let mut file = AgentFile::default();
+
+    for block in blocks {
+        match block {
+            TopLevelBlock::Config(c) => {
+                keep_first(&mut file.config, &mut file.duplicate_configs, c)
+            }
+            TopLevelBlock::Variables(v) => {
+                keep_first(&mut file.variables, &mut file.duplicate_variables, v)
             }
+            TopLevelBlock::System(s) => {
+                keep_first(&mut file.system, &mut file.duplicate_systems, s)
+            }
+            TopLevelBlock::StartAgent(sa) => {
+                keep_first(&mut file.start_agent, &mut file.duplicate_start_agents, sa)
+            }
+            TopLevelBlock::Topic(t) => file.topics.push(t),
+            TopLevelBlock::Knowledge(k) => file.knowledge = Some(k),
+            TopLevelBlock::Language(l) => file.language = Some(l),
+            TopLevelBlock::Connection(c) => file.connections.push(c),
+            TopLevelBlock::Connections(cs) => file.connections.extend(cs),
+            TopLevelBlock::Recovered(r) => file.recovered_blocks.push(r),
+        }
+    }
 
-            file
+    file
+}
+
+/// Skip a malformed top-level block: its header line and indented body.
+fn recovered_block<'tokens, 'src: 'tokens>() -> impl Parser<
+    'tokens,
+    ParserInput<'tokens, 'src>,
+    Spanned<RecoveredBlock>,
+    extra::Err<Rich<'tokens, Token<'src>, primitives::Span>>,
+> + Clone {
+    let keyword = select! {
+        Token::Config => "config",
+        Token::Variables => "variables",
+        Token::System => "system",
+        Token::StartAgent => "start_agent",
+        Token::Topic => "topic",
+        Token::Knowledge => "knowledge",
+        Token::Language => "language",
+        Token::Connection => "connection",
+        Token::Connections => "connections",
+    };
+    let name = select! { Token::Ident(name) => name }
+        .map_with(|name, e| Spanned::new(name.to_string(), primitives::to_ast_span(e.span())));
+    let rest_of_line = none_of([Token::Newline, Token::Indent, Token::Dedent]).repeated();
+    // An indented body, with any nested indentation balanced
+    let body = recursive(|body| {
+        just(Token::Indent)
+            .ignore_then(
+                choice((body, none_of([Token::Indent, Token::Dedent]).ignored())).repeated(),
+            )
+            .then_ignore(just(Token::Dedent))
+    });
+
+    keyword
+        .then(name.or_not())
+        .then_ignore(rest_of_line)
+        .then_ignore(just(Token::Newline).repeated())
+        .then_ignore(body.or_not())
+        .map_with(|(kind, name), e| {
+            Spanned::new(
+                RecoveredBlock {
+                    kind: kind.to_string(),
+                    name,
+                },
+                primitives::to_ast_span(e.span()),
+            )
         })
 }
+
+/// Store a single-instance block, keeping any later repeats as duplicates.
+fn keep_first<T>(first: &mut Option<T>, duplicates: &mut Vec<T>, block: T) {
+    if first.is_none() {
+        *first = Some(block);
+    } else {
+        duplicates.push(block);
+    }
+}
@@ -3,7 +3,7 @@
 //! This module contains parsers for identifiers, strings, numbers,
 //! newlines, indentation, and noise-skipping utilities.
 
-use crate::ast::Spanned;
+use crate::ast::{Currency, Date, Number, Spanned};
 use crate::lexer::Token;
 use chumsky::input::MappedInput;
 use chumsky::prelude::*;
@@ -72,14 +72,74 @@ pub fn spanned_string<'tokens, 'src: 'tokens>() -> impl Parser<
 pub fn number_lit<'tokens, 'src: 'tokens>() -> impl Parser<
     'tokens,
     ParserInput<'tokens, 'src>,
-    f64,
+    Number,
     extra::Err<Rich<'tokens, Token<'src>, Span>>,
 > + Clone {
     select! {
-        Token::NumberLit(n) => n,
+        // The lexer only produces digits with an optional fraction
+        Token::NumberLit(n) => n.parse().unwrap(),
     }
 }
 
+/// Parse a date literal: `date(2024-01-31)`. Dates that do not exist are
+/// errors.
+pub fn date_lit<'tokens, 'src: 'tokens>() -> impl Parser<
+    'tokens,
+    ParserInput<'tokens, 'src>,
+    Date,
+    extra::Err<Rich<'tokens, Token<'src>, Span>>,
+> + Clone {
+    select! { Token::DateLit(d) => d }.validate(|d: &str, e, emitter| {
+        // The lexer only produces `date(` digits `-` digits `-` digits `)`
+        d["date(".len()..d.len() - 1]
+            .parse()
+            .unwrap_or_else(|message: String| {
+                emitter.emit(Rich::custom(e.span(), message));
+                // Placeholder so parsing continues; the error fails the parse
+                Date {
+                    year: 1970,
+                    month: 1,
+                    day: 1,
+                }
+            })
+    })
+}
+
+/// Parse a currency literal: `$199.99` or `currency(199.99, "USD")`.
+pub fn currency_lit<'tokens, 'src: 'tokens>() -> impl Parser<
+    'tokens,
+    ParserInput<'tokens, 'src>,
+    Currency,
+    extra::Err<Rich<'tokens, Token<'src>, Span>>,
+> + Clone {
+    let dollars = select! {
+        Token::CurrencyLit(c) => Currency { amount: c[1..].parse().unwrap(), code: None },
+    };
+    let with_code = just(Token::Currency)
+        .ignore_then(
+            number_lit()
+                .then_ignore(just(Token::Comma))
+                .then(string_lit())
+                .delimited_by(just(Token::LParen), just(Token::RParen)),
+        )
+        .validate(|(amount, code), e, emitter| {
+            if !Currency::is_valid_code(code) {
+                emitter.emit(Rich::custom(
+                    e.span(),
+                    format!(
+                        "invalid currency code '{}', expected three uppercase letters like \"USD\"",
+                        code
+                    ),
+                ));
+            }
+            Currency {
+                amount,
+                code: Some(code.to_string()),
+            }
+        });
+    dollars.or(with_code)
+}
+
 /// Parse a newline token (for line tracking).
 pub fn newline<'tokens, 'src: 'tokens>(
 ) -> impl Parser<'tokens, ParserInput<'tokens, 'src>, (), extra::Err<Rich<'tokens, Token<'src>, Span>>>
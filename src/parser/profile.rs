@@ -0,0 +1,305 @@
+//! Per-block parse profiling.
+//!
+//! [`parse_with_profile`] lexes the file once, splits the token stream at
+//! top-level keywords, and parses each block on its own so its time can be
+//! measured. It's meant for tracking down slow files (e.g. huge instruction
+//! bodies), not for producing an AST.
+
+use super::primitives::{self, skip_toplevel_noise, ParserInput, SpannedToken};
+use super::{
+    config_block, connection_block, connections_wrapper_block, language_block, start_agent_block,
+    system_block, topic_block, variables_block, TopLevelBlock,
+};
+use crate::lexer::{self, Token};
+use crate::text_pos::{Encoding, LineIndex};
+use chumsky::input::Input as _;
+use chumsky::prelude::*;
+use serde::{Serialize, Serializer};
+use std::fmt;
+use std::ops::Range;
+use std::time::{Duration, Instant};
+
+/// Timing and size of a parse, broken down by top-level block.
+#[derive(Debug, Clone, Serialize)]
+pub struct ParseProfile {
+    /// Time spent lexing the whole file.
+    #[serde(serialize_with = "millis")]
+    pub lex_time: Duration,
+    /// Total number of tokens, including indentation tokens.
+    pub token_count: usize,
+    /// Top-level blocks in source order.
+    pub blocks: Vec<BlockProfile>,
+    /// Lexer errors. When non-empty, no blocks were parsed.
+    pub lex_errors: usize,
+    /// Wall time for the whole profile run.
+    #[serde(serialize_with = "millis")]
+    pub total_time: Duration,
+}
+
+/// Timing and size of a single top-level block.
+#[derive(Debug, Clone, Serialize)]
+pub struct BlockProfile {
+    /// Block keyword, e.g. `"topic"` or `"variables"`.
+    pub kind: String,
+    /// Block name for named blocks (`topic`, `start_agent`, `connection`).
+    pub name: Option<String>,
+    /// Byte range of the block's tokens.
+    pub span: Range<usize>,
+    /// 1-indexed first and last line.
+    pub lines: (usize, usize),
+    /// Number of tokens in the block.
+    pub token_count: usize,
+    /// Time spent parsing the block.
+    #[serde(serialize_with = "millis")]
+    pub parse_time: Duration,
+    /// Number of parse errors in the block.
+    pub error_count: usize,
+}
+
+impl ParseProfile {
+    /// Blocks sorted by parse time, slowest first.
+    pub fn slowest(&self) -> Vec<&BlockProfile> {
+        let mut blocks: Vec<_> = self.blocks.iter().collect();
+        blocks.sort_by_key(|b| std::cmp::Reverse(b.parse_time));
+        blocks
+    }
+}
+
+impl fmt::Display for ParseProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:<32} {:>10} {:>8}  lines", "block", "time (ms)", "tokens")?;
+        writeln!(
+            f,
+            "{:<32} {:>10.3} {:>8}",
+            "(lex)",
+            self.lex_time.as_secs_f64() * 1000.0,
+            self.token_count
+        )?;
+        for block in &self.blocks {
+            let label = match &block.name {
+                Some(name) => format!("{} {}", block.kind, name),
+                None => block.kind.clone(),
+            };
+            write!(
+                f,
+                "{:<32} {:>10.3} {:>8}  {}-{}",
+                label,
+                block.parse_time.as_secs_f64() * 1000.0,
+                block.token_count,
+                block.lines.0,
+                block.lines.1
+            )?;
+            if block.error_count > 0 {
+                write!(f, "  ({} errors)", block.error_count)?;
+            }
+            writeln!(f)?;
+        }
+        write!(f, "{:<32} {:>10.3}", "(total)", self.total_time.as_secs_f64() * 1000.0)
+    }
+}
+
+fn millis<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64() * 1000.0)
+}
+
+/// Profile parsing of `source`, block by block.
+///
+/// ```rust
+/// use busbar_sf_agentscript::parser::parse_with_profile;
+///
+/// let source = "config:\n   agent_name: \"Test\"\n\ntopic main:\n   description: \"Main\"\n";
+/// let profile = parse_with_profile(source);
+/// assert_eq!(profile.blocks.len(), 2);
+/// assert_eq!(profile.blocks[1].kind, "topic");
+/// assert_eq!(profile.blocks[1].name.as_deref(), Some("main"));
+/// println!("{}", profile);
+/// ```
+pub fn parse_with_profile(source: &str) -> ParseProfile {
+    let start = Instant::now();
+    let tokens = lexer::lex_with_indentation(source);
+    let lex_time = start.elapsed();
+
+    let tokens = match tokens {
+        Ok(tokens) => tokens,
+        Err(errors) => {
+            return ParseProfile {
+                lex_time,
+                token_count: 0,
+                blocks: Vec::new(),
+                lex_errors: errors.len(),
+                total_time: start.elapsed(),
+            }
+        }
+    };
+
+    let blocks = split_blocks(&tokens)
+        .into_iter()
+        .map(|chunk| profile_block(source, chunk))
+        .collect();
+
+    ParseProfile {
+        lex_time,
+        token_count: tokens.len(),
+        blocks,
+        lex_errors: 0,
+        total_time: start.elapsed(),
+    }
+}
+
+/// Split a token stream at top-level block keywords. Leading noise
+/// (comments, blank lines) is kept with the block that follows it.
+pub(super) fn split_blocks<'a, 'src>(
+    tokens: &'a [SpannedToken<'src>],
+) -> Vec<&'a [SpannedToken<'src>]> {
+    let mut chunks = Vec::new();
+    let mut depth = 0usize;
+    let mut chunk_start = 0;
+    let mut seen_block = false;
+
+    for (i, (token, _)) in tokens.iter().enumerate() {
+        match token {
+            Token::Indent => depth += 1,
+            Token::Dedent => depth = depth.saturating_sub(1),
+            _ if depth == 0 && is_block_keyword(token) => {
+                if seen_block {
+                    chunks.push(&tokens[chunk_start..i]);
+                    chunk_start = i;
+                }
+                seen_block = true;
+            }
+            _ => {}
+        }
+    }
+    if chunk_start < tokens.len() {
+        chunks.push(&tokens[chunk_start..]);
+    }
+    chunks
+}
+
+fn is_block_keyword(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::Config
+            | Token::Variables
+            | Token::System
+            | Token::StartAgent
+            | Token::Topic
+            | Token::Knowledge
+            | Token::Language
+            | Token::Connection
+            | Token::Connections
+    )
+}
+
+fn profile_block(source: &str, tokens: &[SpannedToken<'_>]) -> BlockProfile {
+    let keyword = tokens.iter().find(|(t, _)| is_block_keyword(t));
+    let kind = keyword
+        .map(|(t, _)| t.to_string())
+        .unwrap_or_else(|| "(trailing)".to_string());
+
+    let span_start = tokens.first().map(|(_, s)| s.start).unwrap_or(0);
+    let span_end = tokens.last().map(|(_, s)| s.end).unwrap_or(span_start);
+    let first = keyword.map(|(_, s)| s.start).unwrap_or(span_start);
+    let content_end = tokens
+        .iter()
+        .rev()
+        .find(|(t, _)| !matches!(t, Token::Newline | Token::Dedent | Token::Indent))
+        .map(|(_, s)| s.end)
+        .unwrap_or(span_end);
+
+    let eoi = primitives::Span::new((), span_end..span_end);
+    let start = Instant::now();
+    let (output, errors) = block_parser()
+        .parse(tokens.split_token_span(eoi))
+        .into_output_errors();
+    let parse_time = start.elapsed();
+
+    let name = output.and_then(|block| match block {
+        TopLevelBlock::Topic(t) => Some(t.node.name.node),
+        TopLevelBlock::StartAgent(sa) => Some(sa.node.name.node),
+        TopLevelBlock::Connection(c) => Some(c.node.name.node),
+        _ => None,
+    });
+
+    let index = LineIndex::new(source);
+    BlockProfile {
+        kind,
+        name,
+        span: span_start..span_end,
+        lines: (
+            index.location(first, Encoding::Utf32).line as usize + 1,
+            index
+                .location(content_end.saturating_sub(1).max(first), Encoding::Utf32)
+                .line as usize
+                + 1,
+        ),
+        token_count: tokens.len(),
+        parse_time,
+        error_count: errors.len(),
+    }
+}
+
+/// Parse exactly one top-level block.
+fn block_parser<'tokens, 'src: 'tokens>() -> impl Parser<
+    'tokens,
+    ParserInput<'tokens, 'src>,
+    TopLevelBlock,
+    extra::Err<Rich<'tokens, Token<'src>, primitives::Span>>,
+> + Clone {
+    skip_toplevel_noise()
+        .ignore_then(choice((
+            config_block().map(TopLevelBlock::Config),
+            variables_block().map(TopLevelBlock::Variables),
+            system_block().map(TopLevelBlock::System),
+            start_agent_block().map(TopLevelBlock::StartAgent),
+            topic_block().map(TopLevelBlock::Topic),
+            language_block().map(TopLevelBlock::Language),
+            connection_block().map(TopLevelBlock::Connection),
+            connections_wrapper_block().map(TopLevelBlock::Connections),
+        )))
+        .then_ignore(skip_toplevel_noise())
+        .then_ignore(end())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile_blocks() {
+        let source = r#"# Header comment
+config:
+   agent_name: "Test"
+
+variables:
+   x: mutable string = ""
+
+topic main:
+   description: "Main"
+   reasoning:
+      instructions: "Help"
+
+topic other:
+   description: "Other"
+"#;
+        let profile = parse_with_profile(source);
+        let kinds: Vec<_> = profile.blocks.iter().map(|b| b.kind.as_str()).collect();
+        assert_eq!(kinds, ["config", "variables", "topic", "topic"]);
+        assert!(profile.blocks.iter().all(|b| b.error_count == 0));
+        assert_eq!(profile.blocks[3].name.as_deref(), Some("other"));
+        assert_eq!(profile.blocks[2].lines, (8, 11));
+        assert_eq!(
+            profile.blocks.iter().map(|b| b.token_count).sum::<usize>(),
+            profile.token_count
+        );
+        assert!(profile.to_string().contains("topic main"));
+    }
+
+    #[test]
+    fn test_profile_reports_block_errors() {
+        let source = "config:\n   agent_name: \"Test\"\n\ntopic main:\n   description: 42\n";
+        let profile = parse_with_profile(source);
+        assert_eq!(profile.blocks[0].error_count, 0);
+        assert!(profile.blocks[1].error_count > 0);
+    }
+}
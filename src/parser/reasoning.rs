@@ -3,13 +3,13 @@
 //! Parses reasoning blocks containing instructions and actions.
 
 use crate::ast::{
-    IfClause, ReasoningAction, ReasoningActionTarget, ReasoningBlock, Reference, RunClause,
-    SetClause, Spanned, WithClause, WithValue,
+    ActionClause, IfClause, ReasoningAction, ReasoningActionTarget, ReasoningBlock, Reference,
+    RunClause, SetClause, Spanned, WithClause, WithValue,
 };
 use crate::lexer::Token;
 use chumsky::prelude::*;
 
-use super::expressions::{expr, reference};
+use super::expressions::{expr, reference, spanned_reference};
 use super::instructions::any_instructions;
 use super::primitives::{
     dedent, description_entry, ident, indent, newline, skip_block_noise, spanned_ident, string_lit,
@@ -77,20 +77,15 @@ pub(crate) fn with_clause<'tokens, 'src: 'tokens>() -> impl Parser<
         string_lit().map(|s| s.to_string()),
         just(Token::Description).to("description".to_string()),
         just(Token::Id).to("id".to_string()),
-    ));
+    ))
+    .map_with(|name, e| Spanned::new(name, to_ast_span(e.span())));
 
     just(Token::With)
         .ignore_then(param_name)
         .then_ignore(just(Token::Assign))
-        .then(expr().map(|e| WithValue::Expr(e.node)))
+        .then(expr().map(|e| Spanned::new(WithValue::Expr(e.node), e.span)))
         .map_with(|(param, value), e| {
-            Spanned::new(
-                WithClause {
-                    param: Spanned::new(param.to_string(), to_ast_span(e.span())),
-                    value: Spanned::new(value, to_ast_span(e.span())),
-                },
-                to_ast_span(e.span()),
-            )
+            Spanned::new(WithClause { param, value }, to_ast_span(e.span()))
         })
 }
 
@@ -103,17 +98,11 @@ pub(crate) fn set_clause<'tokens, 'src: 'tokens>() -> impl Parser<
     extra::Err<Rich<'tokens, Token<'src>, Span>>,
 > + Clone {
     just(Token::Set)
-        .ignore_then(reference())
+        .ignore_then(spanned_reference())
         .then_ignore(just(Token::Assign))
         .then(expr())
         .map_with(|(target, source), e| {
-            Spanned::new(
-                SetClause {
-                    target: Spanned::new(target, to_ast_span(e.span())),
-                    source,
-                },
-                to_ast_span(e.span()),
-            )
+            Spanned::new(SetClause { target, source }, to_ast_span(e.span()))
         })
 }
 
@@ -154,7 +143,7 @@ pub(crate) fn run_clause<'tokens, 'src: 'tokens>() -> impl Parser<
         .boxed();
 
     just(Token::Run)
-        .ignore_then(reference())
+        .ignore_then(spanned_reference())
         .then(nested_block)
         .map_with(|(action, entries), e| {
             let mut with_clauses = Vec::new();
@@ -167,7 +156,7 @@ pub(crate) fn run_clause<'tokens, 'src: 'tokens>() -> impl Parser<
             }
             Spanned::new(
                 RunClause {
-                    action: Spanned::new(action, to_ast_span(e.span())),
+                    action,
                     with_clauses,
                     set_clauses,
                 },
@@ -188,6 +177,20 @@ enum ReasoningActionEntry {
     If(Spanned<IfClause>),
 }
 
+impl ReasoningActionEntry {
+    fn clause(&self) -> ActionClause {
+        match self {
+            ReasoningActionEntry::Description(_) => ActionClause::Description,
+            ReasoningActionEntry::With(_) => ActionClause::With,
+            ReasoningActionEntry::Set(_) => ActionClause::Set,
+            ReasoningActionEntry::AvailableWhen(_) => ActionClause::AvailableWhen,
+            ReasoningActionEntry::Run(_) => ActionClause::Run,
+            ReasoningActionEntry::Transition(_) => ActionClause::Transition,
+            ReasoningActionEntry::If(_) => ActionClause::If,
+        }
+    }
+}
+
 /// Parse a reasoning action definition.
 pub(crate) fn reasoning_action<'tokens, 'src: 'tokens>() -> impl Parser<
     'tokens,
@@ -269,9 +272,11 @@ pub(crate) fn reasoning_action<'tokens, 'src: 'tokens>() -> impl Parser<
                 run_clauses: Vec::new(),
                 if_clauses: Vec::new(),
                 transition: None,
+                clause_order: Vec::new(),
             };
 
             for entry in entries {
+                action.clause_order.push(entry.clause());
                 match entry {
                     ReasoningActionEntry::Description(d) => action.description = Some(d),
                     ReasoningActionEntry::With(w) => action.with_clauses.push(w),
@@ -291,7 +296,7 @@ pub(crate) fn reasoning_action<'tokens, 'src: 'tokens>() -> impl Parser<
 #[derive(Clone)]
 enum ReasoningEntry {
     Instructions(Spanned<crate::ast::Instructions>),
-    Actions(Vec<Spanned<ReasoningAction>>),
+    Actions(Spanned<Vec<Spanned<ReasoningAction>>>),
 }
 
 /// Parse the reasoning block.
@@ -336,7 +341,9 @@ pub(crate) fn reasoning_block<'tokens, 'src: 'tokens>() -> impl Parser<
                         entries.unwrap_or_default()
                     })
                     .labelled("reasoning actions")
-                    .map(ReasoningEntry::Actions),
+                    .map_with(|actions, e| {
+                        ReasoningEntry::Actions(Spanned::new(actions, to_ast_span(e.span())))
+                    }),
             ))
             .separated_by(skip_block_noise())
             .allow_trailing()
@@ -354,9 +361,7 @@ pub(crate) fn reasoning_block<'tokens, 'src: 'tokens>() -> impl Parser<
             for entry in entries {
                 match entry {
                     ReasoningEntry::Instructions(i) => block.instructions = Some(i),
-                    ReasoningEntry::Actions(a) => {
-                        block.actions = Some(Spanned::new(a, to_ast_span(e.span())))
-                    }
+                    ReasoningEntry::Actions(a) => block.actions = Some(a),
                 }
             }
 
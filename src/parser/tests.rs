@@ -36,9 +36,9 @@ mod tests {
 
         // Test expression parsing directly
         let expr_tokens = vec![
-            (Token::NumberLit(5.0), primitives::Span::new(0, 1)),
+            (Token::NumberLit("5"), primitives::Span::new(0, 1)),
             (Token::Plus, primitives::Span::new(2, 3)),
-            (Token::NumberLit(3.0), primitives::Span::new(4, 5)),
+            (Token::NumberLit("3"), primitives::Span::new(4, 5)),
         ];
         let expr_stream = expr_tokens.as_slice().spanned(primitives::Span::new(5, 5));
         let result = expr().parse(expr_stream);
@@ -3,8 +3,8 @@
 //! Parses `topic` and `start_agent` blocks.
 
 use crate::ast::{
-    ActionsBlock, DirectiveBlock, ReasoningBlock, Spanned, StartAgentBlock, TopicBlock,
-    TopicSystemOverride,
+    identifier_for, ActionsBlock, DirectiveBlock, ReasoningBlock, Spanned, StartAgentBlock,
+    TopicBlock, TopicSystemOverride,
 };
 use crate::lexer::Token;
 use chumsky::prelude::*;
@@ -117,6 +117,7 @@ pub(crate) fn start_agent_block<'tokens, 'src: 'tokens>() -> impl Parser<
                 before_reasoning: None,
                 reasoning: None,
                 after_reasoning: None,
+                doc: None,
             };
 
             for entry in entries {
@@ -143,19 +144,21 @@ pub(crate) fn topic_block<'tokens, 'src: 'tokens>() -> impl Parser<
     extra::Err<Rich<'tokens, Token<'src>, Span>>,
 > + Clone {
     just(Token::Topic)
-        .ignore_then(spanned_ident())
+        .ignore_then(topic_name())
         .then_ignore(just(Token::Colon))
         .then(topic_content().labelled("topic content"))
         .labelled("topic block")
-        .map_with(|(name, entries), e| {
+        .map_with(|((name, quoted_name), entries), e| {
             let mut block = TopicBlock {
                 name,
+                quoted_name,
                 description: None,
                 system: None,
                 actions: None,
                 before_reasoning: None,
                 reasoning: None,
                 after_reasoning: None,
+                doc: None,
             };
 
             for entry in entries {
@@ -173,3 +176,24 @@ pub(crate) fn topic_block<'tokens, 'src: 'tokens>() -> impl Parser<
             Spanned::new(block, to_ast_span(e.span()))
         })
 }
+
+/// Parse a topic name: an identifier, or a quoted name like `"Claims – EU"`
+/// paired with its identifier form.
+fn topic_name<'tokens, 'src: 'tokens>() -> impl Parser<
+    'tokens,
+    ParserInput<'tokens, 'src>,
+    (Spanned<String>, Option<Spanned<String>>),
+    extra::Err<Rich<'tokens, Token<'src>, Span>>,
+> + Clone {
+    let quoted = spanned_string().validate(|quoted: Spanned<String>, e, emitter| {
+        let name = identifier_for(&quoted.node);
+        if name.is_empty() {
+            emitter.emit(Rich::custom(
+                e.span(),
+                format!("invalid topic name \"{}\", expected a letter or digit", quoted.node),
+            ));
+        }
+        (Spanned::new(name, quoted.span.clone()), Some(quoted))
+    });
+    spanned_ident().map(|name| (name, None)).or(quoted)
+}
@@ -111,6 +111,7 @@ pub(crate) fn variable_decl<'tokens, 'src: 'tokens>() -> impl Parser<
                     default,
                     description,
                     source,
+                    doc: None,
                 },
                 to_ast_span(e.span()),
             )
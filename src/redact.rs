@@ -0,0 +1,504 @@
+//! Redaction for sharing agents outside the organization.
+//!
+//! [`redact`] returns a copy of an agent with its free text masked or
+//! stripped: instructions, system and progress messages, descriptions and
+//! labels, string literals, connection values and action targets. Names,
+//! references, types and expressions are kept, so the redacted file still
+//! parses, validates and reproduces structural bugs.
+//!
+//! Masking keeps a string's shape: letters become `x`/`X` and digits
+//! become `0`, while whitespace, punctuation and `{!...}` interpolations
+//! are left alone.
+//!
+//! # Example
+//!
+//! ```rust
+//! use busbar_sf_agentscript::redact::{redact, RedactionConfig};
+//! use busbar_sf_agentscript::{parse, serialize};
+//!
+//! let source = r#"
+//! topic refunds:
+//!    description: "Refunds up to $500"
+//!    reasoning:
+//!       instructions: "Approve if {!@variables.tier} is Gold"
+//! "#;
+//!
+//! let redacted = serialize(&redact(&parse(source).unwrap(), &RedactionConfig::default()));
+//! assert!(redacted.contains("description: \"Xxxxxxx xx xx $000\""));
+//! assert!(redacted.contains("{!@variables.tier}"));
+//! assert!(!redacted.contains("Gold"));
+//! assert!(parse(&redacted).is_ok());
+//! ```
+
+use crate::ast::{
+    ActionDef, AgentFile, ConfigBlock, DirectiveBlock, Expr, InstructionPart, Instructions,
+    ReasoningAction, ReasoningBlock, SetClause, Spanned, Stmt, SystemBlock, TopicSystemOverride,
+    VariablesBlock, WithClause, WithValue,
+};
+
+/// What to do with one category of text.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Redaction {
+    /// Leave the text as written.
+    Keep,
+    /// Replace letters with `x`/`X` and digits with `0`, keeping
+    /// `{!...}` interpolations.
+    #[default]
+    Mask,
+    /// Replace the text with an empty string. Action targets keep their
+    /// scheme, such as `flow://`.
+    Strip,
+}
+
+impl Redaction {
+    fn apply(self, text: &mut String) {
+        match self {
+            Redaction::Keep => {}
+            Redaction::Mask => *text = mask(text),
+            Redaction::Strip => text.clear(),
+        }
+    }
+}
+
+/// Options for [`redact`]. The default masks every category.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RedactionConfig {
+    /// System, topic and reasoning instructions. Interpolations and
+    /// conditions inside them are kept.
+    pub instructions: Redaction,
+    /// System welcome and error messages, progress indicator messages, and
+    /// `respond` messages.
+    pub messages: Redaction,
+    /// Descriptions, labels and `#` doc comments.
+    pub descriptions: Redaction,
+    /// String literals in expressions, and `default_agent_user`.
+    pub string_literals: Redaction,
+    /// Connection entry values.
+    pub connections: Redaction,
+    /// Action targets, such as `flow://Process_Refund`.
+    pub targets: Redaction,
+}
+
+impl RedactionConfig {
+    /// Apply the same redaction to every category.
+    pub fn all(redaction: Redaction) -> Self {
+        Self {
+            instructions: redaction,
+            messages: redaction,
+            descriptions: redaction,
+            string_literals: redaction,
+            connections: redaction,
+            targets: redaction,
+        }
+    }
+}
+
+/// Return a copy of `ast` with its free text redacted as configured.
+pub fn redact(ast: &AgentFile, config: &RedactionConfig) -> AgentFile {
+    let mut ast = ast.clone();
+    let redactor = Redactor { config };
+
+    for config in ast.config.iter_mut().chain(&mut ast.duplicate_configs) {
+        redactor.config(&mut config.node);
+    }
+    for variables in ast.variables.iter_mut().chain(&mut ast.duplicate_variables) {
+        redactor.variables(&mut variables.node);
+    }
+    for system in ast.system.iter_mut().chain(&mut ast.duplicate_systems) {
+        redactor.system(&mut system.node);
+    }
+    for entry in ast.connections.iter_mut().flat_map(|c| &mut c.node.entries) {
+        config.connections.apply(&mut entry.node.value.node);
+    }
+    for entry in ast.knowledge.iter_mut().flat_map(|k| &mut k.node.entries) {
+        redactor.expr(&mut entry.node.value.node);
+    }
+    for entry in ast.language.iter_mut().flat_map(|l| &mut l.node.entries) {
+        redactor.expr(&mut entry.node.value.node);
+    }
+
+    let start_agents = ast
+        .start_agent
+        .iter_mut()
+        .chain(&mut ast.duplicate_start_agents)
+        .map(|sa| {
+            let sa = &mut sa.node;
+            (
+                &mut sa.description,
+                &mut sa.doc,
+                &mut sa.system,
+                &mut sa.actions,
+                [&mut sa.before_reasoning, &mut sa.after_reasoning],
+                &mut sa.reasoning,
+            )
+        });
+    let topics = ast.topics.iter_mut().map(|t| {
+        let t = &mut t.node;
+        (
+            &mut t.description,
+            &mut t.doc,
+            &mut t.system,
+            &mut t.actions,
+            [&mut t.before_reasoning, &mut t.after_reasoning],
+            &mut t.reasoning,
+        )
+    });
+    for (description, doc, system, actions, directives, reasoning) in start_agents.chain(topics) {
+        redactor.descriptions([description, doc]);
+        if let Some(system) = system {
+            redactor.system_override(&mut system.node);
+        }
+        for action in actions.iter_mut().flat_map(|a| &mut a.node.actions) {
+            redactor.action_def(&mut action.node);
+        }
+        for directives in directives.into_iter().flatten() {
+            redactor.directives(&mut directives.node);
+        }
+        if let Some(reasoning) = reasoning {
+            redactor.reasoning(&mut reasoning.node);
+        }
+    }
+
+    ast
+}
+
+/// Replace letters with `x`/`X` and digits with `0`, leaving `{!...}`
+/// interpolations intact.
+fn mask(text: &str) -> String {
+    let mut masked = String::with_capacity(text.len());
+    let mut rest = text;
+    while !rest.is_empty() {
+        let (plain, interpolation) = match rest.find("{!") {
+            Some(start) => {
+                let end = rest[start..]
+                    .find('}')
+                    .map_or(rest.len(), |e| start + e + 1);
+                (&rest[..start], &rest[start..end])
+            }
+            None => (rest, ""),
+        };
+        masked.extend(plain.chars().map(|c| {
+            if c.is_uppercase() {
+                'X'
+            } else if c.is_alphabetic() {
+                'x'
+            } else if c.is_numeric() {
+                '0'
+            } else {
+                c
+            }
+        }));
+        masked.push_str(interpolation);
+        rest = &rest[plain.len() + interpolation.len()..];
+    }
+    masked
+}
+
+struct Redactor<'a> {
+    config: &'a RedactionConfig,
+}
+
+impl Redactor<'_> {
+    fn descriptions<const N: usize>(&self, texts: [&mut Option<Spanned<String>>; N]) {
+        for text in texts.into_iter().flatten() {
+            self.config.descriptions.apply(&mut text.node);
+        }
+    }
+
+    fn config(&self, config: &mut ConfigBlock) {
+        self.descriptions([&mut config.agent_label, &mut config.description]);
+        if let Some(user) = &mut config.default_agent_user {
+            self.config.string_literals.apply(&mut user.node);
+        }
+    }
+
+    fn variables(&self, variables: &mut VariablesBlock) {
+        for var in &mut variables.variables {
+            let var = &mut var.node;
+            self.descriptions([&mut var.description, &mut var.doc]);
+            if let Some(default) = &mut var.default {
+                self.expr(&mut default.node);
+            }
+        }
+    }
+
+    fn system(&self, system: &mut SystemBlock) {
+        if let Some(messages) = &mut system.messages {
+            let messages = &mut messages.node;
+            for message in [&mut messages.welcome, &mut messages.error]
+                .into_iter()
+                .flatten()
+            {
+                self.config.messages.apply(&mut message.node);
+            }
+        }
+        if let Some(instructions) = &mut system.instructions {
+            self.instructions(&mut instructions.node);
+        }
+    }
+
+    fn system_override(&self, system: &mut TopicSystemOverride) {
+        if let Some(instructions) = &mut system.instructions {
+            self.instructions(&mut instructions.node);
+        }
+    }
+
+    fn action_def(&self, action: &mut ActionDef) {
+        self.descriptions([&mut action.description, &mut action.label, &mut action.doc]);
+        if let Some(message) = &mut action.progress_indicator_message {
+            self.config.messages.apply(&mut message.node);
+        }
+        if let Some(target) = &mut action.target {
+            let target = &mut target.node;
+            match target.split_once("://") {
+                Some((scheme, name)) => {
+                    let mut name = name.to_string();
+                    self.config.targets.apply(&mut name);
+                    *target = format!("{}://{}", scheme, name);
+                }
+                None => self.config.targets.apply(target),
+            }
+        }
+        let params = action.inputs.iter_mut().chain(&mut action.outputs);
+        for param in params.flat_map(|p| &mut p.node) {
+            let param = &mut param.node;
+            self.descriptions([&mut param.description, &mut param.label]);
+        }
+    }
+
+    fn directives(&self, directives: &mut DirectiveBlock) {
+        self.stmts(&mut directives.statements);
+    }
+
+    fn stmts(&self, stmts: &mut [Spanned<Stmt>]) {
+        for stmt in stmts {
+            match &mut stmt.node {
+                Stmt::Set { value, .. } => self.expr(&mut value.node),
+                Stmt::Run {
+                    with_clauses,
+                    set_clauses,
+                    ..
+                } => {
+                    self.with_clauses(with_clauses);
+                    self.set_clauses(set_clauses);
+                }
+                Stmt::If {
+                    condition,
+                    then_block,
+                    else_block,
+                } => {
+                    self.expr(&mut condition.node);
+                    self.stmts(then_block);
+                    if let Some(else_block) = else_block {
+                        self.stmts(else_block);
+                    }
+                }
+                Stmt::Respond { message } => self.config.messages.apply(&mut message.node),
+                Stmt::Transition { .. } | Stmt::Escalate => {}
+            }
+        }
+    }
+
+    fn reasoning(&self, reasoning: &mut ReasoningBlock) {
+        if let Some(instructions) = &mut reasoning.instructions {
+            self.instructions(&mut instructions.node);
+        }
+        for action in reasoning.actions.iter_mut().flat_map(|a| &mut a.node) {
+            self.reasoning_action(&mut action.node);
+        }
+    }
+
+    fn reasoning_action(&self, action: &mut ReasoningAction) {
+        self.descriptions([&mut action.description]);
+        if let Some(condition) = &mut action.available_when {
+            self.expr(&mut condition.node);
+        }
+        self.with_clauses(&mut action.with_clauses);
+        self.set_clauses(&mut action.set_clauses);
+        for run in &mut action.run_clauses {
+            self.with_clauses(&mut run.node.with_clauses);
+            self.set_clauses(&mut run.node.set_clauses);
+        }
+        for clause in &mut action.if_clauses {
+            self.expr(&mut clause.node.condition.node);
+        }
+    }
+
+    fn with_clauses(&self, clauses: &mut [Spanned<WithClause>]) {
+        for clause in clauses {
+            let WithValue::Expr(expr) = &mut clause.node.value.node;
+            self.expr(expr);
+        }
+    }
+
+    fn set_clauses(&self, clauses: &mut [Spanned<SetClause>]) {
+        for clause in clauses {
+            self.expr(&mut clause.node.source.node);
+        }
+    }
+
+    fn instructions(&self, instructions: &mut Instructions) {
+        match instructions {
+            Instructions::Simple(text) => self.config.instructions.apply(text),
+            Instructions::Static(lines) => {
+                for line in lines {
+                    self.config.instructions.apply(&mut line.node);
+                }
+            }
+            Instructions::Dynamic(parts) => self.instruction_parts(parts),
+        }
+    }
+
+    fn instruction_parts(&self, parts: &mut [Spanned<InstructionPart>]) {
+        for part in parts {
+            match &mut part.node {
+                InstructionPart::Text(text) | InstructionPart::Heading { title: text, .. } => {
+                    self.config.instructions.apply(text)
+                }
+                InstructionPart::Interpolation(expr) => self.expr(expr),
+                InstructionPart::Conditional {
+                    condition,
+                    then_parts,
+                    else_parts,
+                } => {
+                    self.expr(&mut condition.node);
+                    self.instruction_parts(then_parts);
+                    if let Some(else_parts) = else_parts {
+                        self.instruction_parts(else_parts);
+                    }
+                }
+            }
+        }
+    }
+
+    fn expr(&self, expr: &mut Expr) {
+        match expr {
+            Expr::String(text) => self.config.string_literals.apply(text),
+            Expr::Template(items) | Expr::List(items) => {
+                items.iter_mut().for_each(|i| self.expr(&mut i.node))
+            }
+            Expr::Object(fields) => fields.values_mut().for_each(|v| self.expr(&mut v.node)),
+            Expr::BinOp { left, right, .. } => {
+                self.expr(&mut left.node);
+                self.expr(&mut right.node);
+            }
+            Expr::UnaryOp { operand, .. } => self.expr(&mut operand.node),
+            Expr::Ternary {
+                condition,
+                then_expr,
+                else_expr,
+            } => {
+                self.expr(&mut condition.node);
+                self.expr(&mut then_expr.node);
+                self.expr(&mut else_expr.node);
+            }
+            Expr::Property { object, .. } => self.expr(&mut object.node),
+            Expr::Index { object, index } => {
+                self.expr(&mut object.node);
+                self.expr(&mut index.node);
+            }
+            Expr::Call { args, .. } => args.iter_mut().for_each(|a| self.expr(&mut a.node)),
+            Expr::Reference(_)
+            | Expr::Number(_)
+            | Expr::Date(_)
+            | Expr::Currency(_)
+            | Expr::Bool(_)
+            | Expr::None
+            | Expr::SlotFill => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse, serialize};
+
+    const SOURCE: &str = r#"config:
+   agent_name: "refund_agent"
+   description: "Handles refunds for Acme"
+   default_agent_user: "bot@acme.com"
+
+variables:
+   tier: mutable string = "Gold"
+      description: "Loyalty tier"
+
+system:
+   messages:
+      welcome: "Welcome to Acme"
+      error: "Sorry"
+   instructions: "Never refund more than 500 dollars"
+
+connection messaging:
+   outbound_route_name: "Acme_Queue"
+
+start_agent selector:
+   description: "Route refunds"
+   reasoning:
+      instructions: "Route"
+      actions:
+         go: @utils.transition to @topic.refunds
+
+topic refunds:
+   description: "Refund processing"
+   actions:
+      process_refund:
+         description: "Issue a refund"
+         target: "flow://Acme_Refund"
+   reasoning:
+      instructions: ->
+         | Refund {!@variables.tier} customers first.
+      actions:
+         refund: @actions.process_refund
+            available when @variables.tier == "Gold"
+"#;
+
+    #[test]
+    fn test_redact_masks_text_and_keeps_structure() {
+        let ast = parse(SOURCE).unwrap();
+        let redacted = serialize(&redact(&ast, &RedactionConfig::default()));
+
+        for secret in ["Acme", "Gold", "refunds for", "500", "Loyalty", "Sorry"] {
+            assert!(!redacted.contains(secret), "{} leaked:\n{}", secret, redacted);
+        }
+        assert!(redacted.contains("agent_name: \"refund_agent\""));
+        assert!(redacted.contains("target: \"flow://Xxxx_Xxxxxx\""));
+        assert!(redacted.contains("{!@variables.tier}"));
+        assert!(redacted.contains("@variables.tier == \"Xxxx\""));
+
+        // Same shape: the redacted file parses to the same names
+        let reparsed = parse(&redacted).unwrap();
+        let names = |ast: &AgentFile| -> Vec<String> {
+            ast.topics
+                .iter()
+                .map(|t| t.node.name.node.clone())
+                .collect()
+        };
+        assert_eq!(names(&reparsed), names(&ast));
+        assert_eq!(
+            serialize(&redact(&ast, &RedactionConfig::all(Redaction::Keep))),
+            serialize(&ast)
+        );
+    }
+
+    #[test]
+    fn test_redact_strip() {
+        let ast = parse(SOURCE).unwrap();
+        let config = RedactionConfig {
+            instructions: Redaction::Keep,
+            ..RedactionConfig::all(Redaction::Strip)
+        };
+        let redacted = redact(&ast, &config);
+
+        let topic = &redacted.topics[0].node;
+        assert_eq!(topic.description.as_ref().unwrap().node, "");
+        let action = &topic.actions.as_ref().unwrap().node.actions[0].node;
+        assert_eq!(action.target.as_ref().unwrap().node, "flow://");
+        let system = redacted.system.as_ref().unwrap();
+        assert_eq!(
+            system.node.instructions.as_ref().unwrap().node,
+            Instructions::Simple("Never refund more than 500 dollars".to_string())
+        );
+        assert!(parse(&serialize(&redacted)).is_ok());
+    }
+}
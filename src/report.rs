@@ -0,0 +1,460 @@
+//! Self-contained HTML reports.
+//!
+//! [`generate_html`] renders one HTML file describing an agent for people
+//! who won't open an editor: a topology diagram, every diagnostic, a
+//! reference of variables and actions, the Salesforce dependencies and the
+//! per-topic metrics with the health score. All CSS and JavaScript is
+//! inline, so the file can be published as a CI artifact and opened offline.
+//!
+//! The diagram is drawn in the browser from the graph export with layout
+//! hints ([`GraphExport::from_graph_with_layout`]), embedded as JSON.
+//!
+//! # Example
+//!
+//! ```rust
+//! use busbar_sf_agentscript::{parse, report};
+//!
+//! let source = r#"
+//! config:
+//!    agent_name: "Support"
+//!
+//! start_agent main:
+//!    description: "Route"
+//!    reasoning:
+//!       instructions: "Route"
+//! "#;
+//!
+//! let html = report::generate_html(&parse(source).unwrap());
+//! assert!(html.starts_with("<!DOCTYPE html>"));
+//! assert!(html.contains("<title>Support report</title>"));
+//! ```
+
+use crate::ast::{ActionsBlock, AgentFile, Spanned, VariableKind};
+use crate::graph::dependencies::extract_dependencies;
+use crate::graph::health::{health_report, HealthOptions};
+use crate::graph::{GraphExport, LayoutOptions, RefGraph};
+use crate::lint::{run_lints, LintConfig};
+use crate::serializer::serialize_expr;
+use crate::typecheck::ExprType;
+use crate::validation::{validate_ast, Severity};
+use std::fmt::Write;
+
+/// A row of the diagnostics table.
+struct Diagnostic {
+    severity: Severity,
+    /// Which check reported it: `semantic`, `graph` or `lint`.
+    check: &'static str,
+    code: Option<&'static str>,
+    message: String,
+}
+
+/// Render a single-file HTML report of an agent.
+pub fn generate_html(ast: &AgentFile) -> String {
+    let name = ast
+        .config
+        .as_ref()
+        .map_or("Agent", |c| c.node.agent_name.node.as_str());
+    let graph = RefGraph::from_ast(ast);
+
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+    writeln!(html, "<title>{} report</title>", escape(name)).unwrap();
+    writeln!(html, "<style>{}</style>\n</head>\n<body>", STYLE).unwrap();
+    writeln!(html, "<h1>{}</h1>", escape(name)).unwrap();
+    if let Some(description) = ast
+        .config
+        .as_ref()
+        .and_then(|c| c.node.description.as_ref())
+    {
+        writeln!(html, "<p>{}</p>", escape(&description.node)).unwrap();
+    }
+
+    html.push_str("<h2>Topology</h2>\n");
+    match &graph {
+        Ok(graph) => {
+            let export = GraphExport::from_graph_with_layout(graph, &LayoutOptions::default());
+            let json = serde_json::to_string(&export).expect("graph export serializes");
+            // `</script>` inside the data would end the element early
+            writeln!(
+                html,
+                "<svg id=\"topology\" role=\"img\" aria-label=\"Topic graph\">{}</svg>\n\
+                 <script type=\"application/json\" id=\"graph-data\">{}</script>\n\
+                 <script>{}</script>",
+                SVG_DEFS,
+                json.replace("</", "<\\/"),
+                SCRIPT
+            )
+            .unwrap();
+        }
+        Err(error) => {
+            writeln!(html, "<p>The graph could not be built: {}</p>", escape(&error.to_string()))
+                .unwrap();
+        }
+    }
+
+    write_diagnostics(&mut html, &diagnostics(ast, graph.as_ref().ok()));
+    write_reference(&mut html, ast);
+    write_dependencies(&mut html, ast);
+    if let Ok(graph) = &graph {
+        write_metrics(&mut html, ast, graph);
+    }
+
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+fn diagnostics(ast: &AgentFile, graph: Option<&RefGraph>) -> Vec<Diagnostic> {
+    let mut diagnostics: Vec<Diagnostic> = validate_ast(ast)
+        .into_iter()
+        .map(|error| Diagnostic {
+            severity: error.severity,
+            check: "semantic",
+            code: error.code,
+            message: error.message,
+        })
+        .collect();
+    let Some(graph) = graph else {
+        return diagnostics;
+    };
+
+    let result = graph.validate();
+    for (errors, severity) in [
+        (result.errors, Severity::Error),
+        (result.warnings, Severity::Warning),
+    ] {
+        diagnostics.extend(errors.into_iter().map(|error| Diagnostic {
+            severity: severity.clone(),
+            check: "graph",
+            code: None,
+            message: error.message(),
+        }));
+    }
+    diagnostics.extend(
+        run_lints(ast, graph, &LintConfig::default())
+            .into_iter()
+            .map(|lint| Diagnostic {
+                severity: lint.severity,
+                check: "lint",
+                code: Some(lint.rule),
+                message: lint.message,
+            }),
+    );
+    diagnostics
+}
+
+fn write_diagnostics(html: &mut String, diagnostics: &[Diagnostic]) {
+    let errors = diagnostics
+        .iter()
+        .filter(|d| d.severity == Severity::Error)
+        .count();
+    writeln!(
+        html,
+        "<h2>Diagnostics</h2>\n<p>{} error{}, {} warning{}</p>",
+        errors,
+        plural(errors),
+        diagnostics.len() - errors,
+        plural(diagnostics.len() - errors)
+    )
+    .unwrap();
+    if diagnostics.is_empty() {
+        return;
+    }
+    html.push_str(
+        "<table>\n<tr><th>Severity</th><th>Check</th><th>Code</th><th>Message</th></tr>\n",
+    );
+    for diagnostic in diagnostics {
+        let severity = match diagnostic.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        writeln!(
+            html,
+            "<tr class=\"{0}\"><td>{0}</td><td>{1}</td><td>{2}</td><td>{3}</td></tr>",
+            severity,
+            diagnostic.check,
+            diagnostic.code.unwrap_or(""),
+            escape(&diagnostic.message)
+        )
+        .unwrap();
+    }
+    html.push_str("</table>\n");
+}
+
+fn write_reference(html: &mut String, ast: &AgentFile) {
+    let variables: Vec<_> = ast
+        .variables
+        .iter()
+        .flat_map(|v| &v.node.variables)
+        .collect();
+    html.push_str("<h2>Variables</h2>\n");
+    if variables.is_empty() {
+        html.push_str("<p>None</p>\n");
+    } else {
+        html.push_str(
+            "<table>\n<tr><th>Name</th><th>Kind</th><th>Type</th><th>Default</th><th>Description</th></tr>\n",
+        );
+        for var in variables {
+            let var = &var.node;
+            let kind = match var.kind {
+                VariableKind::Mutable => "mutable",
+                VariableKind::Linked => "linked",
+            };
+            let default = var.default.as_ref().map(|d| serialize_expr(&d.node));
+            writeln!(
+                html,
+                "<tr><td><code>{}</code></td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                escape(&var.name.node),
+                kind,
+                escape(&ExprType::from(&var.ty.node).to_string()),
+                default.map_or(String::new(), |d| format!("<code>{}</code>", escape(&d))),
+                description(&var.description)
+            )
+            .unwrap();
+        }
+        html.push_str("</table>\n");
+    }
+
+    let blocks: Vec<(&String, &Option<Spanned<ActionsBlock>>)> = ast
+        .start_agent
+        .iter()
+        .map(|sa| (&sa.node.name.node, &sa.node.actions))
+        .chain(
+            ast.topics
+                .iter()
+                .map(|t| (&t.node.name.node, &t.node.actions)),
+        )
+        .collect();
+    let actions: Vec<_> = blocks
+        .iter()
+        .flat_map(|(block, actions)| {
+            actions
+                .iter()
+                .flat_map(|a| &a.node.actions)
+                .map(move |action| (*block, &action.node))
+        })
+        .collect();
+    html.push_str("<h2>Actions</h2>\n");
+    if actions.is_empty() {
+        html.push_str("<p>None</p>\n");
+        return;
+    }
+    html.push_str(
+        "<table>\n<tr><th>Topic</th><th>Action</th><th>Target</th><th>Description</th></tr>\n",
+    );
+    for (block, action) in actions {
+        writeln!(
+            html,
+            "<tr><td>{}</td><td><code>{}</code></td><td><code>{}</code></td><td>{}</td></tr>",
+            escape(block),
+            escape(&action.name.node),
+            action
+                .target
+                .as_ref()
+                .map_or(String::new(), |t| escape(&t.node)),
+            description(&action.description)
+        )
+        .unwrap();
+    }
+    html.push_str("</table>\n");
+}
+
+fn write_dependencies(html: &mut String, ast: &AgentFile) {
+    let report = extract_dependencies(ast);
+    let sections = [
+        ("SObjects", &report.sobjects),
+        ("Fields", &report.fields),
+        ("Flows", &report.flows),
+        ("Apex classes", &report.apex_classes),
+        ("Knowledge bases", &report.knowledge_bases),
+        ("Connections", &report.connections),
+        ("Prompt templates", &report.prompt_templates),
+        ("External services", &report.external_services),
+    ];
+    html.push_str("<h2>Dependencies</h2>\n");
+    if sections.iter().all(|(_, names)| names.is_empty()) {
+        html.push_str("<p>None</p>\n");
+        return;
+    }
+    html.push_str("<dl>\n");
+    for (heading, names) in sections {
+        if names.is_empty() {
+            continue;
+        }
+        let mut names: Vec<&String> = names.iter().collect();
+        names.sort();
+        writeln!(html, "<dt>{}</dt>", heading).unwrap();
+        for name in names {
+            writeln!(html, "<dd><code>{}</code></dd>", escape(name)).unwrap();
+        }
+    }
+    html.push_str("</dl>\n");
+}
+
+fn write_metrics(html: &mut String, ast: &AgentFile, graph: &RefGraph) {
+    let health = health_report(ast, &HealthOptions::default());
+    writeln!(html, "<h2>Metrics</h2>\n<p>Health score: <strong>{}</strong>/100", health.score)
+        .unwrap();
+    for component in &health.components {
+        write!(html, " &middot; {} {:.0}", component.name, component.score).unwrap();
+    }
+    html.push_str("</p>\n<table>\n<tr><th>Topic</th><th>Fan-in</th><th>Fan-out</th><th>Depth</th><th>Reasoning actions</th><th>Complexity</th><th>Instruction nesting</th></tr>\n");
+    for topic in graph.metrics().topics {
+        writeln!(
+            html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            escape(&topic.name),
+            topic.fan_in,
+            topic.fan_out,
+            topic.depth.map_or("unreachable".to_string(), |d| d.to_string()),
+            topic.reasoning_actions,
+            topic.complexity,
+            topic.max_instruction_nesting
+        )
+        .unwrap();
+    }
+    html.push_str("</table>\n");
+}
+
+fn description(text: &Option<Spanned<String>>) -> String {
+    text.as_ref().map_or(String::new(), |d| escape(&d.node))
+}
+
+fn plural(count: usize) -> &'static str {
+    if count == 1 {
+        ""
+    } else {
+        "s"
+    }
+}
+
+/// Escape text for HTML element content and attribute values.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+const STYLE: &str = r##"
+body { font-family: system-ui, sans-serif; margin: 2rem auto; max-width: 72rem; padding: 0 1rem; color: #1f2328; }
+h2 { border-bottom: 1px solid #d0d7de; padding-bottom: .25rem; margin-top: 2rem; }
+table { border-collapse: collapse; width: 100%; font-size: .9rem; }
+th, td { border: 1px solid #d0d7de; padding: .3rem .5rem; text-align: left; vertical-align: top; }
+th { background: #f6f8fa; }
+tr.error td:first-child { color: #cf222e; font-weight: 600; }
+tr.warning td:first-child { color: #9a6700; font-weight: 600; }
+dt { font-weight: 600; margin-top: .5rem; }
+#topology { width: 100%; max-height: 40rem; border: 1px solid #d0d7de; border-radius: 6px; }
+.node rect { fill: #fff; stroke: #57606a; }
+.node text { font-size: 12px; }
+.node-start_agent rect { fill: #dafbe1; }
+.node-topic rect { fill: #ddf4ff; }
+.node-action_def rect { fill: #fff8c5; }
+.node-reasoning_action rect { fill: #fbefff; }
+.node-variable rect { fill: #f6f8fa; }
+.node-external rect { fill: #ffebe9; }
+.edge { stroke: #8c959f; fill: none; }
+.edge-transitions_to, .edge-routes, .edge-delegates { stroke: #0969da; }
+.edge-writes { stroke-dasharray: 4 3; }
+"##;
+
+const SVG_DEFS: &str = r##"<defs><marker id="arrow" viewBox="0 0 10 10" refX="10" refY="5" markerWidth="6" markerHeight="6" orient="auto-start-reverse"><path d="M 0 0 L 10 5 L 0 10 z" fill="#8c959f"/></marker></defs>"##;
+
+const SCRIPT: &str = r##"
+(function () {
+  const data = JSON.parse(document.getElementById('graph-data').textContent);
+  const svg = document.getElementById('topology');
+  const ns = 'http://www.w3.org/2000/svg';
+  const w = 150, h = 36, pad = 20;
+  const add = (name, attrs, parent) => {
+    const el = document.createElementNS(ns, name);
+    for (const key in attrs) el.setAttribute(key, attrs[key]);
+    parent.appendChild(el);
+    return el;
+  };
+  const byId = new Map(data.nodes.map(n => [n.id, n]));
+  const width = Math.max(0, ...data.nodes.map(n => n.x)) + w + 2 * pad;
+  const height = Math.max(0, ...data.nodes.map(n => n.y)) + h + 2 * pad;
+  svg.setAttribute('viewBox', `${-w / 2 - pad} ${-h / 2 - pad} ${width} ${height}`);
+  for (const e of data.edges) {
+    const s = byId.get(e.source), t = byId.get(e.target);
+    if (!s || !t || s === t) continue;
+    const down = t.y >= s.y;
+    add('line', {
+      x1: s.x, y1: s.y + (down ? h : -h) / 2, x2: t.x, y2: t.y + (down ? -h : h) / 2,
+      class: 'edge edge-' + e.edge_type, 'marker-end': 'url(#arrow)'
+    }, svg);
+  }
+  for (const n of data.nodes) {
+    const g = add('g', { class: 'node node-' + n.node_type, transform: `translate(${n.x},${n.y})` }, svg);
+    add('rect', { x: -w / 2, y: -h / 2, width: w, height: h, rx: 6 }, g);
+    const name = n.name || n.node_type;
+    const text = add('text', { 'text-anchor': 'middle', 'dominant-baseline': 'middle' }, g);
+    text.textContent = name.length > 22 ? name.slice(0, 21) + '…' : name;
+    add('title', {}, g).textContent = n.node_type + ': ' + (n.topic ? n.topic + '.' : '') + name;
+  }
+})();
+"##;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_html() {
+        let source = r#"config:
+   agent_name: "Support"
+   description: "Helps with <orders> & more"
+
+variables:
+   order_id: mutable string = ""
+      description: "Current order"
+
+start_agent selector:
+   description: "Route"
+   reasoning:
+      instructions: "Route"
+      actions:
+         go: @utils.transition to @topic.orders
+
+topic orders:
+   description: "Orders </script>"
+   actions:
+      lookup:
+         description: "Look up an order"
+         target: "flow://Lookup_Order"
+   reasoning:
+      instructions: "Help"
+      actions:
+         do_lookup: @actions.lookup
+            with id=@variables.order_id
+"#;
+        let html = generate_html(&crate::parse(source).unwrap());
+
+        assert!(html.contains("<p>Helps with &lt;orders&gt; &amp; more</p>"));
+        for heading in [
+            "Topology",
+            "Diagnostics",
+            "Variables",
+            "Actions",
+            "Dependencies",
+            "Metrics",
+        ] {
+            assert!(html.contains(&format!("<h2>{}</h2>", heading)), "{}", heading);
+        }
+        assert!(html.contains("<td><code>order_id</code></td>"));
+        assert!(html.contains("<code>flow://Lookup_Order</code>"));
+        assert!(html.contains("<dd><code>Lookup_Order</code></dd>"));
+        assert!(html.contains("Health score: <strong>"));
+
+        // The embedded graph parses and cannot close its script element early
+        let data = html
+            .split("<script type=\"application/json\" id=\"graph-data\">")
+            .nth(1)
+            .and_then(|rest| rest.split("</script>").next())
+            .unwrap();
+        let graph: serde_json::Value = serde_json::from_str(data).unwrap();
+        assert!(graph["nodes"][0]["x"].is_number());
+        assert_eq!(html.matches("</script>").count(), 2);
+    }
+}
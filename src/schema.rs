@@ -0,0 +1,622 @@
+//! JSON Schema for the serialized AST.
+//!
+//! [`ast_json_schema`] describes the JSON that serde produces for an
+//! [`AgentFile`](crate::AgentFile) (the output of WASM `parse_agent` and
+//! `parse_agent_to_json`, and the `ast` field of
+//! [`to_versioned_json`](crate::serializer::to_versioned_json)). TypeScript
+//! tooling can generate types from it instead of maintaining them by hand.
+//!
+//! The schema follows serde's default representation:
+//! - every struct field is present, with `None` serialized as `null`
+//! - enums are externally tagged: unit variants are strings, other variants
+//!   are single-key objects (`{"Reference": {...}}`)
+//! - spans are `{"start": n, "end": n}` byte offsets
+//!
+//! # Example
+//!
+//! ```rust
+//! use busbar_sf_agentscript::schema::ast_json_schema;
+//!
+//! let schema = ast_json_schema();
+//! assert_eq!(schema["title"], "AgentFile");
+//! assert!(schema["$defs"]["Expr"].is_object());
+//! ```
+
+use crate::serializer::AST_FORMAT_VERSION;
+use serde_json::{json, Map, Value};
+
+/// The JSON Schema (draft 2020-12) for serialized [`AgentFile`](crate::AgentFile) ASTs.
+///
+/// The `$id` carries the crate version, and `x-ast-format-version` the
+/// [`AST_FORMAT_VERSION`] of the JSON it describes.
+pub fn ast_json_schema() -> Value {
+    let mut defs = Map::new();
+    let mut def = |name: &str, schema: Value| {
+        defs.insert(name.to_string(), schema);
+    };
+
+    def(
+        "Span",
+        json!({
+            "type": "object",
+            "description": "Byte range in the source",
+            "properties": {
+                "start": { "type": "integer", "minimum": 0 },
+                "end": { "type": "integer", "minimum": 0 },
+            },
+            "required": ["start", "end"],
+            "additionalProperties": false,
+        }),
+    );
+
+    // Top level
+    def(
+        "ConfigBlock",
+        object(&[
+            ("agent_name", spanned(string())),
+            ("agent_label", opt(spanned(string()))),
+            ("description", opt(spanned(string()))),
+            ("agent_type", opt(spanned(string()))),
+            ("default_agent_user", opt(spanned(string()))),
+            ("target_environment", opt(spanned(string()))),
+        ]),
+    );
+    def("VariablesBlock", object(&[("variables", array(spanned(r("VariableDecl"))))]));
+    def(
+        "VariableDecl",
+        object(&[
+            ("name", spanned(string())),
+            ("kind", r("VariableKind")),
+            ("ty", spanned(r("Type"))),
+            ("default", opt(spanned(r("Expr")))),
+            ("description", opt(spanned(string()))),
+            ("source", opt(spanned(r("Reference")))),
+            ("doc", opt(spanned(string()))),
+        ]),
+    );
+    def("VariableKind", unit_enum(&["Mutable", "Linked"]));
+    def(
+        "Type",
+        one_of(vec![
+            unit_enum(&[
+                "String",
+                "Number",
+                "Boolean",
+                "Object",
+                "Date",
+                "Timestamp",
+                "Currency",
+                "Id",
+                "Datetime",
+                "Time",
+                "Integer",
+                "Long",
+            ]),
+            variant("List", r("Type")),
+        ]),
+    );
+    def(
+        "SystemBlock",
+        object(&[
+            ("messages", opt(spanned(r("SystemMessages")))),
+            ("instructions", opt(spanned(r("Instructions")))),
+        ]),
+    );
+    def(
+        "SystemMessages",
+        object(&[
+            ("welcome", opt(spanned(string()))),
+            ("error", opt(spanned(string()))),
+        ]),
+    );
+    def(
+        "ConnectionBlock",
+        object(&[
+            ("name", spanned(string())),
+            ("entries", array(spanned(r("ConnectionEntry")))),
+        ]),
+    );
+    def(
+        "ConnectionEntry",
+        object(&[("name", spanned(string())), ("value", spanned(string()))]),
+    );
+    def("KnowledgeBlock", object(&[("entries", array(spanned(r("KnowledgeEntry"))))]));
+    def(
+        "KnowledgeEntry",
+        object(&[("name", spanned(string())), ("value", spanned(r("Expr")))]),
+    );
+    def("LanguageBlock", object(&[("entries", array(spanned(r("LanguageEntry"))))]));
+    def(
+        "LanguageEntry",
+        object(&[("name", spanned(string())), ("value", spanned(r("Expr")))]),
+    );
+
+    // Topics and the start agent share a shape
+    let topic_fields = [
+        ("name", spanned(string())),
+        ("description", opt(spanned(string()))),
+        ("system", opt(spanned(r("TopicSystemOverride")))),
+        ("actions", opt(spanned(r("ActionsBlock")))),
+        ("before_reasoning", opt(spanned(r("DirectiveBlock")))),
+        ("reasoning", opt(spanned(r("ReasoningBlock")))),
+        ("after_reasoning", opt(spanned(r("DirectiveBlock")))),
+        ("doc", opt(spanned(string()))),
+    ];
+    def("StartAgentBlock", object(&topic_fields));
+    let mut quoted_topic_fields = topic_fields.to_vec();
+    quoted_topic_fields.insert(1, ("quoted_name", opt(spanned(string()))));
+    def("TopicBlock", object(&quoted_topic_fields));
+    def(
+        "RecoveredBlock",
+        object(&[("kind", string()), ("name", opt(spanned(string())))]),
+    );
+    def(
+        "TopicSystemOverride",
+        object(&[("instructions", opt(spanned(r("Instructions"))))]),
+    );
+
+    // Actions
+    def("ActionsBlock", object(&[("actions", array(spanned(r("ActionDef"))))]));
+    def(
+        "ActionDef",
+        object(&[
+            ("name", spanned(string())),
+            ("description", opt(spanned(string()))),
+            ("label", opt(spanned(string()))),
+            ("require_user_confirmation", opt(spanned(boolean()))),
+            ("include_in_progress_indicator", opt(spanned(boolean()))),
+            ("progress_indicator_message", opt(spanned(string()))),
+            ("inputs", opt(spanned(array(spanned(r("ParamDef")))))),
+            ("outputs", opt(spanned(array(spanned(r("ParamDef")))))),
+            ("target", opt(spanned(string()))),
+            ("doc", opt(spanned(string()))),
+        ]),
+    );
+    def(
+        "ParamDef",
+        object(&[
+            ("name", spanned(string())),
+            ("ty", spanned(r("Type"))),
+            ("description", opt(spanned(string()))),
+            ("label", opt(spanned(string()))),
+            ("is_required", opt(spanned(boolean()))),
+            ("filter_from_agent", opt(spanned(boolean()))),
+            ("is_displayable", opt(spanned(boolean()))),
+            ("complex_data_type_name", opt(spanned(string()))),
+        ]),
+    );
+
+    // Directives
+    def("DirectiveBlock", object(&[("statements", array(spanned(r("Stmt"))))]));
+    def(
+        "Stmt",
+        one_of(vec![
+            variant(
+                "Set",
+                object(&[
+                    ("target", spanned(r("Reference"))),
+                    ("value", spanned(r("Expr"))),
+                ]),
+            ),
+            variant(
+                "Run",
+                object(&[
+                    ("action", spanned(r("Reference"))),
+                    ("with_clauses", array(spanned(r("WithClause")))),
+                    ("set_clauses", array(spanned(r("SetClause")))),
+                ]),
+            ),
+            variant(
+                "If",
+                object(&[
+                    ("condition", spanned(r("Expr"))),
+                    ("then_block", array(spanned(r("Stmt")))),
+                    ("else_block", opt(array(spanned(r("Stmt"))))),
+                ]),
+            ),
+            variant("Transition", object(&[("target", spanned(r("Reference")))])),
+            unit_enum(&["Escalate"]),
+            variant("Respond", object(&[("message", spanned(string()))])),
+        ]),
+    );
+    def(
+        "WithClause",
+        object(&[
+            ("param", spanned(string())),
+            ("value", spanned(r("WithValue"))),
+        ]),
+    );
+    def("WithValue", variant("Expr", r("Expr")));
+    def(
+        "SetClause",
+        object(&[
+            ("target", spanned(r("Reference"))),
+            ("source", spanned(r("Expr"))),
+        ]),
+    );
+
+    // Reasoning
+    def(
+        "ReasoningBlock",
+        object(&[
+            ("instructions", opt(spanned(r("Instructions")))),
+            ("actions", opt(spanned(array(spanned(r("ReasoningAction")))))),
+        ]),
+    );
+    def(
+        "ReasoningAction",
+        object(&[
+            ("name", spanned(string())),
+            ("target", spanned(r("ReasoningActionTarget"))),
+            ("description", opt(spanned(string()))),
+            ("available_when", opt(spanned(r("Expr")))),
+            ("with_clauses", array(spanned(r("WithClause")))),
+            ("set_clauses", array(spanned(r("SetClause")))),
+            ("run_clauses", array(spanned(r("RunClause")))),
+            ("if_clauses", array(spanned(r("IfClause")))),
+            ("transition", opt(spanned(r("Reference")))),
+            ("clause_order", array(r("ActionClause"))),
+        ]),
+    );
+    def(
+        "ActionClause",
+        unit_enum(&[
+            "Description",
+            "AvailableWhen",
+            "With",
+            "Set",
+            "Run",
+            "If",
+            "Transition",
+        ]),
+    );
+    def(
+        "ReasoningActionTarget",
+        one_of(vec![
+            unit_enum(&["Escalate", "SetVariables"]),
+            variant("Action", r("Reference")),
+            variant("TransitionTo", r("Reference")),
+            variant("TopicDelegate", r("Reference")),
+        ]),
+    );
+    def(
+        "RunClause",
+        object(&[
+            ("action", spanned(r("Reference"))),
+            ("with_clauses", array(spanned(r("WithClause")))),
+            ("set_clauses", array(spanned(r("SetClause")))),
+        ]),
+    );
+    def(
+        "IfClause",
+        object(&[
+            ("condition", spanned(r("Expr"))),
+            ("transition", opt(spanned(r("Reference")))),
+        ]),
+    );
+
+    // Instructions
+    def(
+        "Instructions",
+        one_of(vec![
+            variant("Simple", string()),
+            variant("Static", array(spanned(string()))),
+            variant("Dynamic", array(spanned(r("InstructionPart")))),
+        ]),
+    );
+    def(
+        "InstructionPart",
+        one_of(vec![
+            variant("Text", string()),
+            variant("Interpolation", r("Expr")),
+            variant(
+                "Heading",
+                object(&[
+                    ("level", json!({ "type": "integer", "minimum": 2 })),
+                    ("title", string()),
+                ]),
+            ),
+            variant(
+                "Conditional",
+                object(&[
+                    ("condition", spanned(r("Expr"))),
+                    ("then_parts", array(spanned(r("InstructionPart")))),
+                    ("else_parts", opt(array(spanned(r("InstructionPart"))))),
+                ]),
+            ),
+        ]),
+    );
+
+    // Expressions
+    def(
+        "Expr",
+        one_of(vec![
+            unit_enum(&["None", "SlotFill"]),
+            variant("Reference", r("Reference")),
+            variant("String", string()),
+            variant("Template", array(spanned(r("Expr")))),
+            variant("Number", json!({ "type": "number" })),
+            variant("Date", json!({ "type": "string", "format": "date" })),
+            variant(
+                "Currency",
+                object(&[
+                    ("amount", json!({ "type": "number" })),
+                    ("code", opt(string())),
+                ]),
+            ),
+            variant("Bool", boolean()),
+            variant("List", array(spanned(r("Expr")))),
+            variant(
+                "Object",
+                json!({ "type": "object", "additionalProperties": spanned(r("Expr")) }),
+            ),
+            variant(
+                "BinOp",
+                object(&[
+                    ("left", spanned(r("Expr"))),
+                    ("op", r("BinOp")),
+                    ("right", spanned(r("Expr"))),
+                ]),
+            ),
+            variant("UnaryOp", object(&[("op", r("UnaryOp")), ("operand", spanned(r("Expr")))])),
+            variant(
+                "Ternary",
+                object(&[
+                    ("condition", spanned(r("Expr"))),
+                    ("then_expr", spanned(r("Expr"))),
+                    ("else_expr", spanned(r("Expr"))),
+                ]),
+            ),
+            variant(
+                "Property",
+                object(&[("object", spanned(r("Expr"))), ("field", spanned(string()))]),
+            ),
+            variant(
+                "Index",
+                object(&[
+                    ("object", spanned(r("Expr"))),
+                    ("index", spanned(r("Expr"))),
+                ]),
+            ),
+            variant(
+                "Call",
+                object(&[
+                    ("callee", spanned(string())),
+                    ("args", array(spanned(r("Expr")))),
+                ]),
+            ),
+        ]),
+    );
+    def("Reference", object(&[("namespace", string()), ("path", array(string()))]));
+    def(
+        "BinOp",
+        unit_enum(&[
+            "Eq", "Ne", "Lt", "Gt", "Le", "Ge", "Is", "IsNot", "And", "Or", "Add", "Sub", "Mul",
+            "Div", "Mod",
+        ]),
+    );
+    def("UnaryOp", unit_enum(&["Not", "Neg"]));
+
+    let mut schema = object(&[
+        ("config", opt(spanned(r("ConfigBlock")))),
+        ("duplicate_configs", array(spanned(r("ConfigBlock")))),
+        ("variables", opt(spanned(r("VariablesBlock")))),
+        ("duplicate_variables", array(spanned(r("VariablesBlock")))),
+        ("system", opt(spanned(r("SystemBlock")))),
+        ("duplicate_systems", array(spanned(r("SystemBlock")))),
+        ("connections", array(spanned(r("ConnectionBlock")))),
+        ("knowledge", opt(spanned(r("KnowledgeBlock")))),
+        ("language", opt(spanned(r("LanguageBlock")))),
+        ("start_agent", opt(spanned(r("StartAgentBlock")))),
+        ("duplicate_start_agents", array(spanned(r("StartAgentBlock")))),
+        ("topics", array(spanned(r("TopicBlock")))),
+        ("recovered_blocks", array(spanned(r("RecoveredBlock")))),
+    ]);
+    let root = schema.as_object_mut().expect("object() builds an object");
+    root.insert("$schema".to_string(), json!("https://json-schema.org/draft/2020-12/schema"));
+    root.insert(
+        "$id".to_string(),
+        json!(format!("urn:busbar-sf-agentscript:ast:{}", env!("CARGO_PKG_VERSION"))),
+    );
+    root.insert("title".to_string(), json!("AgentFile"));
+    root.insert("x-ast-format-version".to_string(), json!(AST_FORMAT_VERSION));
+    root.insert("$defs".to_string(), Value::Object(defs));
+    schema
+}
+
+pub(crate) fn r(name: &str) -> Value {
+    json!({ "$ref": format!("#/$defs/{}", name) })
+}
+
+pub(crate) fn string() -> Value {
+    json!({ "type": "string" })
+}
+
+pub(crate) fn boolean() -> Value {
+    json!({ "type": "boolean" })
+}
+
+pub(crate) fn array(items: Value) -> Value {
+    json!({ "type": "array", "items": items })
+}
+
+/// `Option<T>`: the value or `null`.
+pub(crate) fn opt(schema: Value) -> Value {
+    json!({ "anyOf": [schema, { "type": "null" }] })
+}
+
+/// `Spanned<T>`: `{"node": T, "span": Span}`.
+pub(crate) fn spanned(node: Value) -> Value {
+    object(&[("node", node), ("span", r("Span"))])
+}
+
+/// A struct: every field is required, no others allowed.
+pub(crate) fn object(fields: &[(&str, Value)]) -> Value {
+    let properties: Map<String, Value> = fields
+        .iter()
+        .map(|(name, schema)| (name.to_string(), schema.clone()))
+        .collect();
+    let required: Vec<&str> = fields.iter().map(|(name, _)| *name).collect();
+    json!({
+        "type": "object",
+        "properties": properties,
+        "required": required,
+        "additionalProperties": false,
+    })
+}
+
+/// Unit enum variants, serialized as their names.
+pub(crate) fn unit_enum(variants: &[&str]) -> Value {
+    json!({ "type": "string", "enum": variants })
+}
+
+/// A data-carrying enum variant: `{"Variant": content}`.
+pub(crate) fn variant(name: &str, content: Value) -> Value {
+    object(&[(name, content)])
+}
+
+pub(crate) fn one_of(variants: Vec<Value>) -> Value {
+    json!({ "oneOf": variants })
+}
+
+/// Check `value` against the subset of JSON Schema [`ast_json_schema`] and
+/// the [`typescript`](crate::typescript) API schemas use.
+#[cfg(test)]
+pub(crate) fn check(schema: &Value, value: &Value, defs: &Value, path: &str) -> Result<(), String> {
+    if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+        let name = reference.trim_start_matches("#/$defs/");
+        let def = defs
+            .get(name)
+            .ok_or_else(|| format!("{}: missing def {}", path, name))?;
+        return check(def, value, defs, path);
+    }
+    if let Some(any_of) = schema.get("anyOf").and_then(Value::as_array) {
+        return any_of
+            .iter()
+            .find_map(|s| check(s, value, defs, path).ok())
+            .ok_or_else(|| format!("{}: no anyOf branch matches {}", path, value));
+    }
+    if let Some(one_of) = schema.get("oneOf").and_then(Value::as_array) {
+        let matches = one_of
+            .iter()
+            .filter(|s| check(s, value, defs, path).is_ok())
+            .count();
+        return match matches {
+            1 => Ok(()),
+            n => Err(format!("{}: {} oneOf branches match {}", path, n, value)),
+        };
+    }
+    let ty = schema.get("type").and_then(Value::as_str).unwrap_or("any");
+    let type_ok = match ty {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => value.is_u64() || value.is_i64(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        _ => true,
+    };
+    if !type_ok {
+        return Err(format!("{}: expected {}, got {}", path, ty, value));
+    }
+    if let Some(allowed) = schema.get("enum").and_then(Value::as_array) {
+        if !allowed.contains(value) {
+            return Err(format!("{}: {} not in enum", path, value));
+        }
+    }
+    if let Some(prefix) = schema.get("prefixItems").and_then(Value::as_array) {
+        let items = value.as_array().map_or(&[][..], Vec::as_slice);
+        if items.len() != prefix.len() {
+            return Err(format!("{}: expected {} items, got {}", path, prefix.len(), value));
+        }
+        for (i, (schema, item)) in prefix.iter().zip(items).enumerate() {
+            check(schema, item, defs, &format!("{}[{}]", path, i))?;
+        }
+    }
+    if let Some(items) = schema.get("items") {
+        for (i, item) in value.as_array().into_iter().flatten().enumerate() {
+            check(items, item, defs, &format!("{}[{}]", path, i))?;
+        }
+    }
+    if let Some(object) = value.as_object() {
+        let properties = schema.get("properties").and_then(Value::as_object);
+        for required in schema
+            .get("required")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            let key = required.as_str().unwrap();
+            if !object.contains_key(key) {
+                return Err(format!("{}: missing {}", path, key));
+            }
+        }
+        for (key, child) in object {
+            let child_path = format!("{}.{}", path, key);
+            match (properties.and_then(|p| p.get(key)), schema.get("additionalProperties")) {
+                (Some(s), _) => check(s, child, defs, &child_path)?,
+                (None, Some(Value::Bool(false))) => {
+                    return Err(format!("{}: unexpected field", child_path))
+                }
+                (None, Some(s)) if s.is_object() => check(s, child, defs, &child_path)?,
+                (None, _) => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_conforms(source: &str) {
+        let ast = crate::parse(source).expect("Failed to parse");
+        let schema = ast_json_schema();
+        let value = serde_json::to_value(&ast).unwrap();
+        if let Err(e) = check(&schema, &value, &schema["$defs"], "$") {
+            panic!("AST JSON does not match schema: {}", e);
+        }
+    }
+
+    #[test]
+    fn test_comprehensive_demo_conforms_to_schema() {
+        assert_conforms(include_str!("../examples/ComprehensiveDemo.agent"));
+    }
+
+    #[test]
+    fn test_expressions_conform_to_schema() {
+        assert_conforms(
+            r#"config:
+   agent_name: "Test"
+
+variables:
+   items: mutable list[string] = []
+   count: mutable number = 0
+   data: mutable object = {}
+   greeting: mutable string = "Hi {!@variables.count}"
+
+topic main:
+   description: "Main"
+   before_reasoning:
+      if not @variables.count > 1 and @variables.count != None:
+         set @variables.count = @variables.count + 1
+      else:
+         set @variables.count = 0
+   after_reasoning:
+      if @variables.count > 3:
+         respond "Too many"
+         escalate
+   reasoning:
+      instructions: ->
+         | Count {!@variables.count}
+         if @variables.count == 0:
+            | Empty
+      actions:
+         go: @utils.transition to @topic.main
+            available when @variables.count >= 2
+"#,
+        );
+    }
+}
@@ -31,12 +31,43 @@
 //! - Consistent spacing and newlines
 //! - Proper quoting of strings
 //! - Correct reference formatting (`@namespace.path`)
+//!
+//! # Versioned JSON
+//!
+//! The AST's serde representation changes as the AST evolves. Tools that store
+//! ASTs as JSON (caches, visual editor documents) should use
+//! [`to_versioned_json`] and [`from_versioned_json`], which stamp the JSON with
+//! [`AST_FORMAT_VERSION`] and run [`migrate`] on older documents when loading.
+//!
+//! # Stable JSON (v1)
+//!
+//! [`to_json_v1`] and [`from_json_v1`] read and write a frozen public format
+//! for external consumers (the SF CLI plugin, WASM callers). It is the AST
+//! JSON as of format version 3, carrying an `"ast_version": 1` field, with
+//! these deliberate renames:
+//!
+//! | AST field | v1 field | Where |
+//! |-----------|----------|-------|
+//! | `ty` | `type` | variable declarations, action inputs and outputs |
+//!
+//! Later AST changes are absorbed by the conversion: [`from_json_v1`] loads v1
+//! through [`migrate`], and [`to_json_v1`] maps the current AST back down to the
+//! v1 shape, so v1 documents stay valid across releases. Data v1 has no field
+//! for (recovered blocks, the source order of reasoning action clauses, quoted
+//! topic names) is dropped; clauses loaded from v1 are in canonical order, and
+//! quoted topics keep only their identifier form.
 
 use crate::ast::*;
+use serde::Serialize;
+use serde_json::{json, Value};
 use std::fmt::Write;
+use std::ops::Range;
 
 /// Serialize an AgentFile AST to AgentScript source code.
 ///
+/// Blocks in [`AgentFile::recovered_blocks`] have no parsed content and are
+/// not written.
+///
 /// # Example
 ///
 /// ```rust
@@ -47,15 +78,731 @@ use std::fmt::Write;
 /// assert!(source.contains("agent_name: \"Test\""));
 /// ```
 pub fn serialize(agent: &AgentFile) -> String {
+    serialize_with_options(agent, &SerializeOptions::default())
+}
+
+/// Options for [`serialize_with_options`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SerializeOptions {
+    /// Order of the clauses in reasoning action bodies.
+    pub clause_order: ClauseOrder,
+}
+
+/// How reasoning action clauses are ordered in serialized output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ClauseOrder {
+    /// The order the clauses were written in, from
+    /// [`ReasoningAction::clause_order`].
+    #[default]
+    Source,
+    /// The order the platform expects: description, available when, with,
+    /// set, run, if, transition.
+    Canonical,
+}
+
+/// Serialize an AgentFile AST to AgentScript source code with options.
+///
+/// # Example
+///
+/// ```rust
+/// use busbar_sf_agentscript::parse;
+/// use busbar_sf_agentscript::serializer::{serialize_with_options, ClauseOrder, SerializeOptions};
+///
+/// let source = "topic main:\n   reasoning:\n      actions:\n         go: @actions.go\n            set @variables.x = @outputs.x\n            with id = ...\n";
+/// let ast = parse(source).unwrap();
+/// let options = SerializeOptions { clause_order: ClauseOrder::Canonical };
+/// let output = serialize_with_options(&ast, &options);
+/// assert!(output.find("with id").unwrap() < output.find("set @variables.x").unwrap());
+/// ```
+pub fn serialize_with_options(agent: &AgentFile, options: &SerializeOptions) -> String {
     let mut w = Writer::new();
+    w.options = *options;
     w.write_agent_file(agent);
     w.finish()
 }
 
+/// Serialize a single expression to AgentScript source.
+///
+/// # Example
+///
+/// ```rust
+/// use busbar_sf_agentscript::{serializer::serialize_expr, Expr, Reference};
+///
+/// let expr = Expr::Reference(Reference::new("variables", vec!["name".to_string()]));
+/// assert_eq!(serialize_expr(&expr), "@variables.name");
+/// ```
+pub fn serialize_expr(expr: &Expr) -> String {
+    Writer::new().expr_to_string(expr)
+}
+
+/// Maps AST nodes to where they appear in serialized output.
+///
+/// Built by [`serialize_with_source_map`]. Each entry is a `Spanned` node,
+/// identified by its path in the AST's JSON form (e.g. `topics[0].node.name`).
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SourceMap {
+    /// Entries in AST order, parents before children.
+    pub entries: Vec<SourceMapEntry>,
+}
+
+/// One node in a [`SourceMap`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SourceMapEntry {
+    /// Path of the node in the AST's JSON form.
+    pub path: String,
+    /// Byte range of the node in the serialized output.
+    pub output: Range<usize>,
+    /// The node's span in the AST that was serialized.
+    pub original: Range<usize>,
+}
+
+impl SourceMap {
+    /// Look up a node by path.
+    pub fn get(&self, path: &str) -> Option<&SourceMapEntry> {
+        self.entries.iter().find(|e| e.path == path)
+    }
+
+    /// The innermost node whose output range contains `offset`.
+    pub fn entry_at_output(&self, offset: usize) -> Option<&SourceMapEntry> {
+        self.entries
+            .iter()
+            .filter(|e| e.output.contains(&offset))
+            .min_by_key(|e| e.output.len())
+    }
+
+    /// The innermost node whose original span contains `offset`.
+    pub fn entry_at_original(&self, offset: usize) -> Option<&SourceMapEntry> {
+        self.entries
+            .iter()
+            .filter(|e| e.original.contains(&offset))
+            .min_by_key(|e| e.original.len())
+    }
+}
+
+/// Serialize an AgentFile AST and map its nodes to the generated text.
+///
+/// The output is identical to [`serialize`]. Nodes the serializer doesn't
+/// reproduce exactly (and everything below them) have no entry. If the output
+/// doesn't re-parse, the map is empty.
+///
+/// # Example
+///
+/// ```rust
+/// use busbar_sf_agentscript::parse;
+/// use busbar_sf_agentscript::serializer::serialize_with_source_map;
+///
+/// let source = "config:\n    agent_name:   \"Test\"\n";
+/// let ast = parse(source).unwrap();
+/// let (output, map) = serialize_with_source_map(&ast);
+///
+/// let entry = map.get("config.node.agent_name").unwrap();
+/// assert_eq!(&output[entry.output.clone()], "\"Test\"");
+/// assert_eq!(&source[entry.original.clone()], "\"Test\"");
+/// ```
+pub fn serialize_with_source_map(agent: &AgentFile) -> (String, SourceMap) {
+    let output = serialize(agent);
+    let mut map = SourceMap::default();
+    if let Ok(parsed) = crate::parse(&output) {
+        crate::spanner::transfer_spans(agent, &parsed, &mut |path, original, output| {
+            map.entries.push(SourceMapEntry {
+                path: path.to_string(),
+                output,
+                original,
+            });
+        });
+    }
+    (output, map)
+}
+
+/// Current version of the AST JSON format.
+///
+/// Bump this and add a handler to `MIGRATIONS` whenever a change to the AST
+/// types changes their serde representation. [`to_json_v1`] must then map the
+/// new shape back to the frozen v1 format.
+pub const AST_FORMAT_VERSION: u32 = 6;
+
+/// Upgrades AST JSON by one format version.
+type MigrationFn = fn(Value) -> Result<Value, MigrationError>;
+
+/// Migration handlers; `MIGRATIONS[n]` upgrades AST JSON from version `n` to `n + 1`.
+const MIGRATIONS: &[MigrationFn] = &[
+    migrate_v0_to_v1,
+    migrate_v1_to_v2,
+    migrate_v2_to_v3,
+    migrate_v3_to_v4,
+    migrate_v4_to_v5,
+    migrate_v5_to_v6,
+];
+
+/// Error loading or migrating versioned AST JSON.
+#[derive(Debug, thiserror::Error)]
+pub enum MigrationError {
+    #[error("AST format version {found} is newer than the supported version {supported}")]
+    UnsupportedVersion { found: u32, supported: u32 },
+
+    #[error("Invalid AST format version: {0}")]
+    InvalidVersion(Value),
+
+    #[error("Migration from AST format version {version} failed: {message}")]
+    Migration { version: u32, message: String },
+
+    #[error("Invalid AST JSON: {0}")]
+    Deserialize(#[from] serde_json::Error),
+}
+
+/// Serialize an AgentFile AST to JSON stamped with [`AST_FORMAT_VERSION`].
+///
+/// The result has the shape `{"format_version": 6, "ast": {...}}`.
+///
+/// # Example
+///
+/// ```rust
+/// use busbar_sf_agentscript::parse;
+/// use busbar_sf_agentscript::serializer::{from_versioned_json, to_versioned_json, AST_FORMAT_VERSION};
+///
+/// let ast = parse("config:\n   agent_name: \"Test\"\n").unwrap();
+/// let json = to_versioned_json(&ast);
+/// assert_eq!(json["format_version"], AST_FORMAT_VERSION);
+/// assert_eq!(from_versioned_json(json).unwrap(), ast);
+/// ```
+pub fn to_versioned_json(agent: &AgentFile) -> Value {
+    json!({
+        "format_version": AST_FORMAT_VERSION,
+        "ast": agent,
+    })
+}
+
+/// Load an AgentFile AST from JSON, migrating it to the current format first.
+///
+/// Accepts the output of [`to_versioned_json`] from this or any earlier
+/// release. A bare AST object without a version stamp is treated as version 0,
+/// the format written before versioning was introduced.
+pub fn from_versioned_json(json: Value) -> Result<AgentFile, MigrationError> {
+    let (version, ast) = match json {
+        Value::Object(mut map) if map.contains_key("format_version") => {
+            let version = map.remove("format_version").unwrap_or_default();
+            let version = version
+                .as_u64()
+                .and_then(|v| u32::try_from(v).ok())
+                .ok_or(MigrationError::InvalidVersion(version))?;
+            (version, map.remove("ast").unwrap_or(Value::Null))
+        }
+        ast => (0, ast),
+    };
+    Ok(serde_json::from_value(migrate(ast, version)?)?)
+}
+
+/// Upgrade AST JSON from `from_version` to [`AST_FORMAT_VERSION`].
+///
+/// `json` is the AST itself, without the version envelope. Handlers run in
+/// order, one version step at a time.
+///
+/// # Example
+///
+/// ```rust
+/// use busbar_sf_agentscript::serializer::{migrate, MigrationError, AST_FORMAT_VERSION};
+///
+/// let err = migrate(serde_json::json!({}), AST_FORMAT_VERSION + 1).unwrap_err();
+/// assert!(matches!(err, MigrationError::UnsupportedVersion { .. }));
+/// ```
+pub fn migrate(json: Value, from_version: u32) -> Result<Value, MigrationError> {
+    if from_version > AST_FORMAT_VERSION {
+        return Err(MigrationError::UnsupportedVersion {
+            found: from_version,
+            supported: AST_FORMAT_VERSION,
+        });
+    }
+    MIGRATIONS[from_version as usize..]
+        .iter()
+        .try_fold(json, |json, handler| handler(json))
+}
+
+/// Version of the stable public JSON format written by [`to_json_v1`].
+pub const JSON_V1_VERSION: u32 = 1;
+
+/// The [`AST_FORMAT_VERSION`] the v1 public format was frozen at.
+const JSON_V1_AST_FORMAT: u32 = 3;
+
+/// Field renames from the AST JSON to the v1 format, as `(ast, v1)` pairs.
+const JSON_V1_RENAMES: &[(&str, &str)] = &[("ty", "type")];
+
+/// Top-level fields added to the AST after v1 was frozen, left out of v1.
+const JSON_V1_OMITTED: &[&str] = &["recovered_blocks"];
+
+/// Reasoning action fields added after v1 was frozen, left out of v1.
+const JSON_V1_OMITTED_ACTION_FIELDS: &[&str] = &["clause_order"];
+
+/// Topic fields added after v1 was frozen, left out of v1.
+const JSON_V1_OMITTED_TOPIC_FIELDS: &[&str] = &["quoted_name"];
+
+/// Serialize an AgentFile AST to the stable v1 JSON format.
+///
+/// See the [module docs](self#stable-json-v1) for how v1 differs from the
+/// AST's own serde representation.
+///
+/// # Example
+///
+/// ```rust
+/// use busbar_sf_agentscript::parse;
+/// use busbar_sf_agentscript::serializer::{from_json_v1, to_json_v1};
+///
+/// let ast = parse("variables:\n   name: mutable string = \"\"\n").unwrap();
+/// let json = to_json_v1(&ast);
+/// assert_eq!(json["ast_version"], 1);
+/// assert_eq!(json["variables"]["node"]["variables"][0]["node"]["type"]["node"], "String");
+/// assert_eq!(from_json_v1(json).unwrap(), ast);
+/// ```
+pub fn to_json_v1(agent: &AgentFile) -> Value {
+    let mut json = serde_json::to_value(agent).expect("AST always serializes to JSON");
+    rename_fields(&mut json, |key| {
+        JSON_V1_RENAMES
+            .iter()
+            .find(|(ast, _)| *ast == key)
+            .map(|(_, v1)| *v1)
+    });
+    for_each_reasoning_action(&mut json, &mut |action| {
+        for key in JSON_V1_OMITTED_ACTION_FIELDS {
+            action.remove(*key);
+        }
+    });
+    for_each_topic(&mut json, &mut |topic| {
+        for key in JSON_V1_OMITTED_TOPIC_FIELDS {
+            topic.remove(*key);
+        }
+    });
+    if let Value::Object(map) = &mut json {
+        for key in JSON_V1_OMITTED {
+            map.remove(*key);
+        }
+        map.insert("ast_version".to_string(), json!(JSON_V1_VERSION));
+    }
+    json
+}
+
+/// Load an AgentFile AST from the stable v1 JSON format.
+///
+/// Fails if `ast_version` is missing or not 1.
+pub fn from_json_v1(mut json: Value) -> Result<AgentFile, MigrationError> {
+    let version = json
+        .as_object_mut()
+        .and_then(|map| map.remove("ast_version"))
+        .unwrap_or_default();
+    match version.as_u64() {
+        Some(v) if v == u64::from(JSON_V1_VERSION) => {}
+        Some(v) if v > u64::from(JSON_V1_VERSION) => {
+            return Err(MigrationError::UnsupportedVersion {
+                found: u32::try_from(v).unwrap_or(u32::MAX),
+                supported: JSON_V1_VERSION,
+            })
+        }
+        _ => return Err(MigrationError::InvalidVersion(version)),
+    }
+    rename_fields(&mut json, |key| {
+        JSON_V1_RENAMES
+            .iter()
+            .find(|(_, v1)| *v1 == key)
+            .map(|(ast, _)| *ast)
+    });
+    Ok(serde_json::from_value(migrate(json, JSON_V1_AST_FORMAT)?)?)
+}
+
+/// Rename object keys throughout AST JSON, leaving the user-chosen keys of
+/// object literals (`{"Object": {...}}`) alone.
+fn rename_fields(json: &mut Value, rename: impl Fn(&str) -> Option<&'static str> + Copy) {
+    match json {
+        Value::Object(map) => {
+            let renamed: Vec<(String, &str)> = map
+                .keys()
+                .filter_map(|key| rename(key).map(|to| (key.clone(), to)))
+                .collect();
+            for (from, to) in renamed {
+                if let Some(value) = map.remove(&from) {
+                    map.insert(to.to_string(), value);
+                }
+            }
+            for (key, value) in map.iter_mut() {
+                match value {
+                    Value::Object(literal) if key == "Object" => {
+                        for entry in literal.values_mut() {
+                            rename_fields(entry, rename);
+                        }
+                    }
+                    _ => rename_fields(value, rename),
+                }
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                rename_fields(item, rename);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Version 0 is the unversioned format. Its AST shape is identical to
+/// version 1; only the envelope is new.
+fn migrate_v0_to_v1(json: Value) -> Result<Value, MigrationError> {
+    if json.is_object() {
+        Ok(json)
+    } else {
+        Err(MigrationError::Migration {
+            version: 0,
+            message: "expected an AST object".to_string(),
+        })
+    }
+}
+
+/// Version 2 keeps duplicate `start_agent` blocks in `duplicate_start_agents`.
+fn migrate_v1_to_v2(mut json: Value) -> Result<Value, MigrationError> {
+    let Some(ast) = json.as_object_mut() else {
+        return Err(MigrationError::Migration {
+            version: 1,
+            message: "expected an AST object".to_string(),
+        });
+    };
+    ast.entry("duplicate_start_agents")
+        .or_insert_with(|| json!([]));
+    Ok(json)
+}
+
+/// Version 3 keeps duplicate `config`, `variables` and `system` blocks.
+fn migrate_v2_to_v3(mut json: Value) -> Result<Value, MigrationError> {
+    let Some(ast) = json.as_object_mut() else {
+        return Err(MigrationError::Migration {
+            version: 2,
+            message: "expected an AST object".to_string(),
+        });
+    };
+    for key in [
+        "duplicate_configs",
+        "duplicate_variables",
+        "duplicate_systems",
+    ] {
+        ast.entry(key).or_insert_with(|| json!([]));
+    }
+    Ok(json)
+}
+
+/// Version 4 records blocks skipped by parser recovery in `recovered_blocks`.
+fn migrate_v3_to_v4(mut json: Value) -> Result<Value, MigrationError> {
+    let Some(ast) = json.as_object_mut() else {
+        return Err(MigrationError::Migration {
+            version: 3,
+            message: "expected an AST object".to_string(),
+        });
+    };
+    ast.entry("recovered_blocks").or_insert_with(|| json!([]));
+    Ok(json)
+}
+
+/// Version 5 records the source order of reasoning action clauses in
+/// `clause_order`. Older documents get an empty order, which reads as
+/// canonical order.
+fn migrate_v4_to_v5(mut json: Value) -> Result<Value, MigrationError> {
+    if !json.is_object() {
+        return Err(MigrationError::Migration {
+            version: 4,
+            message: "expected an AST object".to_string(),
+        });
+    }
+    for_each_reasoning_action(&mut json, &mut |action| {
+        action.entry("clause_order").or_insert_with(|| json!([]));
+    });
+    Ok(json)
+}
+
+/// Version 6 keeps the name of a quoted topic as written in `quoted_name`.
+/// Older documents only have identifier names.
+fn migrate_v5_to_v6(mut json: Value) -> Result<Value, MigrationError> {
+    if !json.is_object() {
+        return Err(MigrationError::Migration {
+            version: 5,
+            message: "expected an AST object".to_string(),
+        });
+    }
+    for_each_topic(&mut json, &mut |topic| {
+        topic.entry("quoted_name").or_insert(Value::Null);
+    });
+    Ok(json)
+}
+
+/// Call `f` on every topic block object in AST JSON.
+fn for_each_topic(json: &mut Value, f: &mut impl FnMut(&mut serde_json::Map<String, Value>)) {
+    let topics = json.get_mut("topics").and_then(Value::as_array_mut);
+    for topic in topics.into_iter().flatten() {
+        if let Some(topic) = topic.get_mut("node").and_then(Value::as_object_mut) {
+            f(topic);
+        }
+    }
+}
+
+/// Call `f` on every reasoning action object in AST JSON.
+fn for_each_reasoning_action(
+    json: &mut Value,
+    f: &mut impl FnMut(&mut serde_json::Map<String, Value>),
+) {
+    let Value::Object(ast) = json else {
+        return;
+    };
+    for (key, blocks) in ast.iter_mut() {
+        if !matches!(key.as_str(), "start_agent" | "duplicate_start_agents" | "topics") {
+            continue;
+        }
+        let blocks = match blocks {
+            Value::Array(blocks) => blocks.iter_mut().collect(),
+            block => vec![block],
+        };
+        for block in blocks {
+            let actions = block
+                .pointer_mut("/node/reasoning/node/actions/node")
+                .and_then(Value::as_array_mut);
+            for action in actions.into_iter().flatten() {
+                if let Some(action) = action.get_mut("node").and_then(Value::as_object_mut) {
+                    f(action);
+                }
+            }
+        }
+    }
+}
+
+/// A problem found by [`from_json_strict`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsonFieldError {
+    /// Location of the problem, e.g. `topics[0].node.description`.
+    pub path: String,
+    /// What's wrong at that location.
+    pub kind: JsonFieldErrorKind,
+    /// A likely intended field name, for typos.
+    pub suggestion: Option<String>,
+}
+
+/// Kind of [`JsonFieldError`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JsonFieldErrorKind {
+    /// The object has a field the AST type doesn't define.
+    UnknownField,
+    /// A required field is absent.
+    MissingField,
+    /// Any other deserialization error (wrong type, unknown enum variant, ...).
+    Invalid(String),
+}
+
+impl std::fmt::Display for JsonFieldError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let path = if self.path.is_empty() {
+            "$"
+        } else {
+            &self.path
+        };
+        match &self.kind {
+            JsonFieldErrorKind::UnknownField => write!(f, "{}: unknown field", path)?,
+            JsonFieldErrorKind::MissingField => write!(f, "{}: missing field", path)?,
+            JsonFieldErrorKind::Invalid(message) => write!(f, "{}: {}", path, message)?,
+        }
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, " (did you mean `{}`?)", suggestion)?;
+        }
+        Ok(())
+    }
+}
+
+/// Deserialize an AgentFile AST from JSON, rejecting fields the AST doesn't know.
+///
+/// Plain `serde_json::from_value` silently drops unknown fields, so a typo in
+/// a tool that generates AST JSON loses data without any error. This reports
+/// every unknown field, or the first missing / invalid field, with its path
+/// and a suggested spelling where one is close.
+///
+/// # Example
+///
+/// ```rust
+/// use busbar_sf_agentscript::serializer::from_json_strict;
+///
+/// let mut json = serde_json::to_value(
+///     busbar_sf_agentscript::parse("config:\n   agent_name: \"Test\"\n").unwrap(),
+/// )
+/// .unwrap();
+/// json["config"]["node"]["agent_lable"] = serde_json::json!(null);
+///
+/// let errors = from_json_strict(&json).unwrap_err();
+/// assert_eq!(
+///     errors[0].to_string(),
+///     "config.node.agent_lable: unknown field (did you mean `agent_label`?)"
+/// );
+/// ```
+pub fn from_json_strict(json: &Value) -> Result<AgentFile, Vec<JsonFieldError>> {
+    match serde_json::from_value::<AgentFile>(json.clone()) {
+        Ok(ast) => {
+            // Every AST field serializes, so anything the round trip drops is unknown.
+            let canonical = serde_json::to_value(&ast).expect("AST always serializes to JSON");
+            let mut errors = Vec::new();
+            unknown_fields(json, &canonical, String::new(), &mut errors);
+            if errors.is_empty() {
+                Ok(ast)
+            } else {
+                Err(errors)
+            }
+        }
+        Err(_) => Err(vec![locate_error(json)]),
+    }
+}
+
+fn unknown_fields(
+    input: &Value,
+    canonical: &Value,
+    path: String,
+    errors: &mut Vec<JsonFieldError>,
+) {
+    match (input, canonical) {
+        (Value::Object(input), Value::Object(canonical)) => {
+            for (key, value) in input {
+                let child = join_path(&path, key);
+                match canonical.get(key) {
+                    Some(expected) => unknown_fields(value, expected, child, errors),
+                    None => errors.push(JsonFieldError {
+                        path: child,
+                        kind: JsonFieldErrorKind::UnknownField,
+                        suggestion: closest(key, canonical.keys()),
+                    }),
+                }
+            }
+        }
+        (Value::Array(input), Value::Array(canonical)) => {
+            for (i, (value, expected)) in input.iter().zip(canonical).enumerate() {
+                unknown_fields(value, expected, format!("{}[{}]", path, i), errors);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Find where deserialization of `json` fails.
+///
+/// `serde_json` only reports line/column positions, so this re-parses the
+/// pretty-printed input and maps the error line back to a path.
+fn locate_error(json: &Value) -> JsonFieldError {
+    let pretty = serde_json::to_string_pretty(json).expect("JSON values always serialize");
+    let err =
+        serde_json::from_str::<AgentFile>(&pretty).expect_err("deserialization failed before");
+
+    let mut lines = Vec::new();
+    line_paths(json, String::new(), &mut lines);
+    let path = lines
+        .get(err.line().saturating_sub(1))
+        .cloned()
+        .unwrap_or_default();
+
+    // serde_json appends " at line X column Y"; the path replaces that
+    let message = err.to_string();
+    let message = message
+        .rsplit_once(" at line ")
+        .map_or(message.as_str(), |(m, _)| m);
+    match message
+        .strip_prefix("missing field `")
+        .and_then(|m| m.strip_suffix('`'))
+    {
+        Some(field) => {
+            let object = lookup(json, &path).and_then(Value::as_object);
+            JsonFieldError {
+                path: join_path(&path, field),
+                kind: JsonFieldErrorKind::MissingField,
+                suggestion: object.and_then(|o| closest(field, o.keys())),
+            }
+        }
+        None => JsonFieldError {
+            path,
+            kind: JsonFieldErrorKind::Invalid(message.to_string()),
+            suggestion: None,
+        },
+    }
+}
+
+/// Record, for each line of `serde_json::to_string_pretty(value)`, the path of
+/// the value that line belongs to. Closing brackets map to their container.
+fn line_paths(value: &Value, path: String, lines: &mut Vec<String>) {
+    // The opening line of `value` has already been pushed by the caller (or
+    // is pushed here for the root).
+    if lines.is_empty() {
+        lines.push(path.clone());
+    }
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (key, child) in map {
+                let child_path = join_path(&path, key);
+                lines.push(child_path.clone());
+                line_paths(child, child_path, lines);
+            }
+            lines.push(path);
+        }
+        Value::Array(items) if !items.is_empty() => {
+            for (i, child) in items.iter().enumerate() {
+                let child_path = format!("{}[{}]", path, i);
+                lines.push(child_path.clone());
+                line_paths(child, child_path, lines);
+            }
+            lines.push(path);
+        }
+        _ => {}
+    }
+}
+
+fn join_path(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+/// Resolve a path produced by [`line_paths`].
+fn lookup<'a>(json: &'a Value, path: &str) -> Option<&'a Value> {
+    let mut current = json;
+    for segment in path.split('.').filter(|s| !s.is_empty()) {
+        let (key, indices) = segment.split_once('[').unwrap_or((segment, ""));
+        if !key.is_empty() {
+            current = current.get(key)?;
+        }
+        for index in indices.split('[').filter(|s| !s.is_empty()) {
+            current = current.get(index.trim_end_matches(']').parse::<usize>().ok()?)?;
+        }
+    }
+    Some(current)
+}
+
+/// The candidate closest to `name` by edit distance, if it's close enough to
+/// plausibly be a typo.
+pub(crate) fn closest<'a>(
+    name: &str,
+    candidates: impl Iterator<Item = &'a String>,
+) -> Option<String> {
+    candidates
+        .map(|c| (edit_distance(name, c), c))
+        .filter(|(d, _)| *d <= (name.len() / 3).max(2))
+        .min_by_key(|(d, _)| *d)
+        .map(|(_, c)| c.clone())
+}
+
+pub(crate) fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
 /// Internal writer for building output.
 struct Writer {
     output: String,
     indent: usize,
+    options: SerializeOptions,
 }
 
 impl Writer {
@@ -63,6 +810,7 @@ impl Writer {
         Self {
             output: String::new(),
             indent: 0,
+            options: SerializeOptions::default(),
         }
     }
 
@@ -106,23 +854,36 @@ impl Writer {
         self.output.push('\n');
     }
 
+    /// Write a doc comment as `#` lines at the current indentation.
+    fn write_doc_comment(&mut self, doc: &Option<Spanned<String>>) {
+        if let Some(doc) = doc {
+            for line in doc.node.lines() {
+                if line.is_empty() {
+                    self.writeln("#");
+                } else {
+                    self.writeln(&format!("# {}", line));
+                }
+            }
+        }
+    }
+
     // ========================================================================
     // Top-Level File Structure
     // ========================================================================
 
     fn write_agent_file(&mut self, agent: &AgentFile) {
         // Write blocks in standard order
-        if let Some(config) = &agent.config {
+        for config in agent.config.iter().chain(&agent.duplicate_configs) {
             self.write_config_block(&config.node);
             self.newline();
         }
 
-        if let Some(variables) = &agent.variables {
+        for variables in agent.variables.iter().chain(&agent.duplicate_variables) {
             self.write_variables_block(&variables.node);
             self.newline();
         }
 
-        if let Some(system) = &agent.system {
+        for system in agent.system.iter().chain(&agent.duplicate_systems) {
             self.write_system_block(&system.node);
             self.newline();
         }
@@ -142,7 +903,11 @@ impl Writer {
             self.newline();
         }
 
-        if let Some(start_agent) = &agent.start_agent {
+        for start_agent in agent
+            .start_agent
+            .iter()
+            .chain(&agent.duplicate_start_agents)
+        {
             self.write_start_agent_block(&start_agent.node);
             self.newline();
         }
@@ -189,6 +954,12 @@ impl Writer {
             self.newline();
         }
 
+        if let Some(target) = &config.target_environment {
+            self.write_indent();
+            write!(self.output, "target_environment: \"{}\"", escape_string(&target.node)).unwrap();
+            self.newline();
+        }
+
         self.dedent();
     }
 
@@ -208,6 +979,7 @@ impl Writer {
     }
 
     fn write_variable_decl(&mut self, var: &VariableDecl) {
+        self.write_doc_comment(&var.doc);
         self.write_indent();
         write!(self.output, "{}: ", var.name.node).unwrap();
 
@@ -353,6 +1125,7 @@ impl Writer {
     // ========================================================================
 
     fn write_start_agent_block(&mut self, start_agent: &StartAgentBlock) {
+        self.write_doc_comment(&start_agent.doc);
         self.write_indent();
         write!(self.output, "start_agent {}:", start_agent.name.node).unwrap();
         self.newline();
@@ -399,8 +1172,13 @@ impl Writer {
     // ========================================================================
 
     fn write_topic_block(&mut self, topic: &TopicBlock) {
+        self.write_doc_comment(&topic.doc);
         self.write_indent();
-        write!(self.output, "topic {}:", topic.name.node).unwrap();
+        match &topic.quoted_name {
+            Some(quoted) => write!(self.output, "topic \"{}\":", escape_string(&quoted.node)),
+            None => write!(self.output, "topic {}:", topic.name.node),
+        }
+        .unwrap();
         self.newline();
 
         self.indent();
@@ -467,6 +1245,7 @@ impl Writer {
     }
 
     fn write_action_def(&mut self, action: &ActionDef) {
+        self.write_doc_comment(&action.doc);
         self.write_indent();
         write!(self.output, "{}:", action.name.node).unwrap();
         self.newline();
@@ -646,23 +1425,49 @@ impl Writer {
                 then_block,
                 else_block,
             } => {
-                self.write_indent();
-                write!(self.output, "if {}:", self.expr_to_string(&condition.node)).unwrap();
-                self.newline();
-
-                self.indent();
-                for then_stmt in then_block {
-                    self.write_statement(&then_stmt.node, _in_reasoning);
-                }
-                self.dedent();
+                let (mut keyword, mut condition, mut then_block, mut else_block) =
+                    ("if", condition, then_block, else_block);
+                loop {
+                    self.write_indent();
+                    write!(self.output, "{} {}:", keyword, self.expr_to_string(&condition.node))
+                        .unwrap();
+                    self.newline();
 
-                if let Some(else_stmts) = else_block {
-                    self.writeln("else:");
                     self.indent();
-                    for else_stmt in else_stmts {
-                        self.write_statement(&else_stmt.node, _in_reasoning);
+                    for then_stmt in then_block {
+                        self.write_statement(&then_stmt.node, _in_reasoning);
                     }
                     self.dedent();
+
+                    match else_block.as_deref() {
+                        // An `if` alone in the else branch is an `elif`
+                        Some(
+                            [Spanned {
+                                node:
+                                    Stmt::If {
+                                        condition: elif_condition,
+                                        then_block: elif_block,
+                                        else_block: rest,
+                                    },
+                                ..
+                            }],
+                        ) => {
+                            keyword = "elif";
+                            condition = elif_condition;
+                            then_block = elif_block;
+                            else_block = rest;
+                        }
+                        Some(else_stmts) => {
+                            self.writeln("else:");
+                            self.indent();
+                            for else_stmt in else_stmts {
+                                self.write_statement(&else_stmt.node, _in_reasoning);
+                            }
+                            self.dedent();
+                            break;
+                        }
+                        None => break,
+                    }
                 }
             }
             Stmt::Transition { target } => {
@@ -671,6 +1476,12 @@ impl Writer {
                     .unwrap();
                 self.newline();
             }
+            Stmt::Escalate => self.writeln("escalate"),
+            Stmt::Respond { message } => {
+                self.write_indent();
+                write!(self.output, "respond \"{}\"", escape_string(&message.node)).unwrap();
+                self.newline();
+            }
         }
     }
 
@@ -713,42 +1524,51 @@ impl Writer {
 
         self.indent();
 
-        if let Some(desc) = &action.description {
-            self.write_indent();
-            write!(self.output, "description: \"{}\"", escape_string(&desc.node)).unwrap();
-            self.newline();
-        }
-
-        if let Some(available) = &action.available_when {
-            self.write_indent();
-            write!(self.output, "available when {}", self.expr_to_string(&available.node)).unwrap();
-            self.newline();
+        let mut clauses = action.clauses();
+        if self.options.clause_order == ClauseOrder::Canonical {
+            clauses.sort_by_key(|(kind, _)| *kind);
         }
-
-        for with_clause in &action.with_clauses {
-            self.write_with_clause(&with_clause.node);
-        }
-
-        for set_clause in &action.set_clauses {
-            self.write_set_clause(&set_clause.node);
+        for (kind, index) in clauses {
+            self.write_action_clause(action, kind, index);
         }
 
-        for run_clause in &action.run_clauses {
-            self.write_run_clause(&run_clause.node);
-        }
-
-        for if_clause in &action.if_clauses {
-            self.write_if_clause(&if_clause.node);
-        }
+        self.dedent();
+    }
 
-        if let Some(transition) = &action.transition {
-            self.write_indent();
-            write!(self.output, "transition to {}", self.reference_to_string(&transition.node))
-                .unwrap();
-            self.newline();
+    fn write_action_clause(&mut self, action: &ReasoningAction, kind: ActionClause, index: usize) {
+        match kind {
+            ActionClause::Description => {
+                if let Some(desc) = &action.description {
+                    self.write_indent();
+                    write!(self.output, "description: \"{}\"", escape_string(&desc.node)).unwrap();
+                    self.newline();
+                }
+            }
+            ActionClause::AvailableWhen => {
+                if let Some(available) = &action.available_when {
+                    self.write_indent();
+                    write!(self.output, "available when {}", self.expr_to_string(&available.node))
+                        .unwrap();
+                    self.newline();
+                }
+            }
+            ActionClause::With => self.write_with_clause(&action.with_clauses[index].node),
+            ActionClause::Set => self.write_set_clause(&action.set_clauses[index].node),
+            ActionClause::Run => self.write_run_clause(&action.run_clauses[index].node),
+            ActionClause::If => self.write_if_clause(&action.if_clauses[index].node),
+            ActionClause::Transition => {
+                if let Some(transition) = &action.transition {
+                    self.write_indent();
+                    write!(
+                        self.output,
+                        "transition to {}",
+                        self.reference_to_string(&transition.node)
+                    )
+                    .unwrap();
+                    self.newline();
+                }
+            }
         }
-
-        self.dedent();
     }
 
     fn write_with_clause(&mut self, with: &WithClause) {
@@ -832,60 +1652,188 @@ impl Writer {
                 write!(self.output, "->").unwrap();
                 self.newline();
                 self.indent();
-                for part in parts {
-                    self.write_instruction_part(&part.node);
-                }
+                self.write_instruction_parts(parts);
                 self.dedent();
             }
         }
     }
 
-    fn write_instruction_part(&mut self, part: &InstructionPart) {
-        match part {
-            InstructionPart::Text(text) => {
-                // Text may contain newlines - first line gets |, continuation lines get extra indent
-                let lines: Vec<&str> = text.split('\n').collect();
-                for (i, line) in lines.iter().enumerate() {
-                    self.write_indent();
-                    if i == 0 {
-                        write!(self.output, "| {}", line).unwrap();
+    /// Write instruction parts as `|` lines.
+    ///
+    /// This undoes how the parser splits `|` lines into parts. Interpolations
+    /// share a line with the text around them, since `{!expr}` is only read
+    /// inside a `|` line. Indented continuation lines follow a line ending
+    /// in text as a separate part, and newlines between continuation lines
+    /// are kept in the text after an interpolation.
+    fn write_instruction_parts(&mut self, parts: &[Spanned<InstructionPart>]) {
+        // Whether a line is started and not yet ended
+        let mut open = false;
+        let mut after_interpolation = false;
+        // Whether lines are continuing the last `|` line, and whether the
+        // next text part is the continuation of that line's text
+        let mut continuation = false;
+        let mut pending = false;
+        for (i, part) in parts.iter().enumerate() {
+            match &part.node {
+                InstructionPart::Text(text) => {
+                    let ends_line = text.ends_with('\n');
+                    let mut lines = text.strip_suffix('\n').unwrap_or(text).split('\n');
+                    let first = lines.next().unwrap_or_default().trim();
+                    // Only a `|` line's text ends in a newline. Text after an
+                    // interpolation on a continuation line reads the same on a
+                    // new `|` line, unless a newline follows in the block.
+                    let starts_line = !pending
+                        && (!after_interpolation
+                            || continuation
+                                && (ends_line
+                                    || !text.contains('\n')
+                                        && !continues_with_newline(&parts[i + 1..])));
+                    if starts_line {
+                        if open {
+                            self.newline();
+                        }
+                        self.write_indent();
+                        write!(self.output, "| {}", first).unwrap();
+                        open = true;
+                        continuation = false;
+                    } else if open {
+                        if !first.is_empty()
+                            && !first.starts_with(['.', ',', ';', ':', '!', '?', ')'])
+                        {
+                            self.output.push(' ');
+                        }
+                        self.output.push_str(first);
+                    } else if !first.is_empty() {
+                        self.write_indent();
+                        write!(self.output, "  {}", first).unwrap();
+                        open = true;
+                    }
+                    for line in lines {
+                        if open {
+                            self.newline();
+                        }
+                        let line = line.trim();
+                        if !line.is_empty() {
+                            self.write_indent();
+                            write!(self.output, "  {}", line).unwrap();
+                        }
+                        open = true;
+                    }
+                    if ends_line && open {
+                        self.newline();
+                        open = false;
+                    }
+                    after_interpolation = false;
+                    pending = !continuation && ends_line;
+                    continuation |= pending;
+                }
+                InstructionPart::Interpolation(expr) => {
+                    if open {
+                        if !self.output.ends_with(['$', '(', ' ']) {
+                            self.output.push(' ');
+                        }
                     } else {
-                        // Continuation line - extra indent, no pipe
-                        write!(self.output, "  {}", line).unwrap();
+                        self.write_indent();
+                        self.output.push_str(if continuation { "  " } else { "| " });
+                    }
+                    write!(self.output, "{{!{}}}", self.expr_to_string(expr)).unwrap();
+                    open = true;
+                    after_interpolation = true;
+                    pending = false;
+                    // A newline in the text after a later interpolation is
+                    // only kept on continuation lines, so they start here
+                    if !continuation && continues_with_newline(&parts[i + 1..]) {
+                        self.newline();
+                        open = false;
+                        continuation = true;
                     }
-                    self.newline();
                 }
-            }
-            InstructionPart::Interpolation(expr) => {
-                self.write_indent();
-                write!(self.output, "{{!{}}}", self.expr_to_string(expr)).unwrap();
-                self.newline();
-            }
-            InstructionPart::Conditional {
-                condition,
-                then_parts,
-                else_parts,
-            } => {
-                self.write_indent();
-                write!(self.output, "if {}:", self.expr_to_string(&condition.node)).unwrap();
-                self.newline();
-
-                self.indent();
-                for then_part in then_parts {
-                    self.write_instruction_part(&then_part.node);
+                InstructionPart::Heading { level, title } => {
+                    if open {
+                        self.newline();
+                        open = false;
+                    }
+                    continuation = false;
+                    pending = false;
+                    after_interpolation = false;
+                    self.write_indent();
+                    write!(self.output, "| {} {}", "#".repeat(*level), title).unwrap();
+                    self.newline();
                 }
-                self.dedent();
-
-                if let Some(else_ps) = else_parts {
-                    self.writeln("else:");
-                    self.indent();
-                    for else_part in else_ps {
-                        self.write_instruction_part(&else_part.node);
+                InstructionPart::Conditional {
+                    condition,
+                    then_parts,
+                    else_parts,
+                } => {
+                    if open {
+                        self.newline();
+                        open = false;
+                    }
+                    continuation = false;
+                    pending = false;
+                    after_interpolation = false;
+                    let (mut keyword, mut condition, mut then_parts, mut else_parts) =
+                        ("if", condition, then_parts, else_parts);
+                    loop {
+                        self.write_indent();
+                        write!(
+                            self.output,
+                            "{} {}:",
+                            keyword,
+                            self.expr_to_string(&condition.node)
+                        )
+                        .unwrap();
+                        self.newline();
+
+                        self.indent();
+                        self.write_instruction_branch(then_parts);
+                        self.dedent();
+
+                        match else_parts.as_deref() {
+                            // A conditional alone in the else branch is an `elif`
+                            Some(
+                                [Spanned {
+                                    node:
+                                        InstructionPart::Conditional {
+                                            condition: elif_condition,
+                                            then_parts: elif_parts,
+                                            else_parts: rest,
+                                        },
+                                    ..
+                                }],
+                            ) => {
+                                keyword = "elif";
+                                condition = elif_condition;
+                                then_parts = elif_parts;
+                                else_parts = rest;
+                            }
+                            Some(else_ps) => {
+                                self.writeln("else:");
+                                self.indent();
+                                self.write_instruction_branch(else_ps);
+                                self.dedent();
+                                break;
+                            }
+                            None => break,
+                        }
                     }
-                    self.dedent();
                 }
             }
         }
+        if open {
+            self.newline();
+        }
+    }
+
+    /// Write the body of an `if` or `else`. A body the parser kept nothing
+    /// of (such as one holding only `run` statements) becomes an empty `|`
+    /// line, so it still has a body.
+    fn write_instruction_branch(&mut self, parts: &[Spanned<InstructionPart>]) {
+        if parts.is_empty() {
+            self.writeln("|");
+        } else {
+            self.write_instruction_parts(parts);
+        }
     }
 
     // ========================================================================
@@ -931,14 +1879,19 @@ impl Writer {
         match expr {
             Expr::Reference(r) => self.reference_to_string(r),
             Expr::String(s) => format!("\"{}\"", escape_string(s)),
-            Expr::Number(n) => {
-                // Format numbers nicely
-                if n.fract() == 0.0 && n.is_finite() {
-                    format!("{:.0}", n)
-                } else {
-                    format!("{}", n)
-                }
+            Expr::Template(parts) => {
+                let parts: String = parts
+                    .iter()
+                    .map(|part| match &part.node {
+                        Expr::String(text) => escape_string(text),
+                        expr => format!("{{!{}}}", self.expr_to_string(expr)),
+                    })
+                    .collect();
+                format!("\"{}\"", parts)
             }
+            Expr::Number(n) => n.to_string(),
+            Expr::Date(d) => format!("date({})", d),
+            Expr::Currency(c) => c.to_string(),
             Expr::Bool(b) => {
                 if *b {
                     "True".to_string()
@@ -956,20 +1909,28 @@ impl Writer {
             Expr::Object(map) => {
                 let pairs: Vec<_> = map
                     .iter()
-                    .map(|(k, v)| format!("{}: {}", k, self.expr_to_string(&v.node)))
+                    .map(|(k, v)| format!("{}: {}", object_key(k), self.expr_to_string(&v.node)))
                     .collect();
                 format!("{{{}}}", pairs.join(", "))
             }
             Expr::BinOp { left, op, right } => {
+                // Binary operators are left-associative, so a right operand
+                // of equal precedence needs parentheses too.
+                let prec = op.precedence();
                 format!(
                     "{} {} {}",
-                    self.expr_to_string(&left.node),
+                    self.operand_to_string(&left.node, prec),
                     self.binop_to_string(op),
-                    self.expr_to_string(&right.node)
+                    self.operand_to_string(&right.node, prec + 1)
                 )
             }
             Expr::UnaryOp { op, operand } => {
-                format!("{} {}", self.unaryop_to_string(op), self.expr_to_string(&operand.node))
+                let operand = self.operand_to_string(&operand.node, op.precedence());
+                match op {
+                    // `-1`, like the number it usually is
+                    UnaryOp::Neg => format!("-{}", operand),
+                    UnaryOp::Not => format!("{} {}", self.unaryop_to_string(op), operand),
+                }
             }
             Expr::Ternary {
                 condition,
@@ -978,21 +1939,40 @@ impl Writer {
             } => {
                 format!(
                     "{} if {} else {}",
-                    self.expr_to_string(&then_expr.node),
-                    self.expr_to_string(&condition.node),
-                    self.expr_to_string(&else_expr.node)
+                    self.operand_to_string(&then_expr.node, 1),
+                    self.operand_to_string(&condition.node, 1),
+                    self.operand_to_string(&else_expr.node, 1)
                 )
             }
             Expr::Property { object, field } => {
-                format!("{}.{}", self.expr_to_string(&object.node), field.node)
+                format!("{}.{}", self.operand_to_string(&object.node, u8::MAX), field.node)
             }
             Expr::Index { object, index } => {
                 format!(
                     "{}[{}]",
-                    self.expr_to_string(&object.node),
+                    self.operand_to_string(&object.node, u8::MAX),
                     self.expr_to_string(&index.node)
                 )
             }
+            Expr::Call { callee, args } => {
+                let args: Vec<String> = args.iter().map(|a| self.expr_to_string(&a.node)).collect();
+                format!("{}({})", callee.node, args.join(", "))
+            }
+        }
+    }
+
+    /// Serialize an operand, parenthesized if it binds looser than `min_prec`.
+    fn operand_to_string(&self, expr: &Expr, min_prec: u8) -> String {
+        let prec = match expr {
+            Expr::Ternary { .. } => 0,
+            Expr::BinOp { op, .. } => op.precedence(),
+            Expr::UnaryOp { op, .. } => op.precedence(),
+            _ => u8::MAX,
+        };
+        if prec < min_prec {
+            format!("({})", self.expr_to_string(expr))
+        } else {
+            self.expr_to_string(expr)
         }
     }
 
@@ -1010,6 +1990,9 @@ impl Writer {
             BinOp::Or => "or",
             BinOp::Add => "+",
             BinOp::Sub => "-",
+            BinOp::Mul => "*",
+            BinOp::Div => "/",
+            BinOp::Mod => "%",
         }
     }
 
@@ -1021,8 +2004,22 @@ impl Writer {
     }
 }
 
+/// An object literal key, quoted unless it lexes as a single word.
+fn object_key(key: &str) -> String {
+    let mut chars = key.chars();
+    let bare = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if bare {
+        key.to_string()
+    } else {
+        format!("\"{}\"", escape_string(key))
+    }
+}
+
 /// Escape special characters in strings.
-fn escape_string(s: &str) -> String {
+pub(crate) fn escape_string(s: &str) -> String {
     s.replace('\\', "\\\\")
         .replace('"', "\\\"")
         .replace('\n', "\\n")
@@ -1030,10 +2027,185 @@ fn escape_string(s: &str) -> String {
         .replace('\t', "\\t")
 }
 
+/// Whether a text part right after an interpolation, before the next `|`
+/// line or `if`, holds a newline from between continuation lines.
+///
+/// The parser ends a `|` line's text with `\n` when continuation lines
+/// follow, so only other newlines count.
+fn continues_with_newline(parts: &[Spanned<InstructionPart>]) -> bool {
+    let mut after_interpolation = true;
+    for part in parts {
+        match &part.node {
+            InstructionPart::Text(text) if after_interpolation => {
+                let text = text.strip_suffix('\n').unwrap_or(text);
+                if text.contains('\n') {
+                    return true;
+                }
+                after_interpolation = false;
+            }
+            InstructionPart::Interpolation(_) => after_interpolation = true,
+            InstructionPart::Text(_)
+            | InstructionPart::Heading { .. }
+            | InstructionPart::Conditional { .. } => return false,
+        }
+    }
+    false
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_versioned_json_round_trip_and_legacy() {
+        let ast = crate::parse(
+            "config:\n   agent_name: \"Test\"\n\ntopic main:\n   description: \"Main\"\n",
+        )
+        .unwrap();
+
+        let json = to_versioned_json(&ast);
+        assert_eq!(json["format_version"], AST_FORMAT_VERSION);
+        assert_eq!(from_versioned_json(json).unwrap(), ast);
+
+        // Unversioned JSON written by older releases still loads
+        let mut legacy = serde_json::to_value(&ast).unwrap();
+        for key in [
+            "duplicate_configs",
+            "duplicate_variables",
+            "duplicate_systems",
+            "duplicate_start_agents",
+            "recovered_blocks",
+        ] {
+            legacy.as_object_mut().unwrap().remove(key);
+        }
+        assert_eq!(from_versioned_json(legacy.clone()).unwrap(), ast);
+        let v1 = json!({"format_version": 1, "ast": legacy});
+        assert_eq!(from_versioned_json(v1).unwrap(), ast);
+
+        // Version 4 reasoning actions have no clause order
+        let action = "/topics/0/node/reasoning/node/actions/node/0/node/clause_order";
+        let v4 = json!({"topics": [{"node": {"reasoning": {"node": {"actions": {"node": [{"node": {}}]}}}}}]});
+        assert_eq!(migrate(v4, 4).unwrap().pointer(action), Some(&json!([])));
+
+        // Version 5 topics have no quoted name
+        let v5 = json!({"topics": [{"node": {"name": {"node": "main"}}}]});
+        let migrated = migrate(v5, 5).unwrap();
+        assert_eq!(migrated.pointer("/topics/0/node/quoted_name"), Some(&Value::Null));
+
+        let future = json!({"format_version": AST_FORMAT_VERSION + 1, "ast": {}});
+        assert!(matches!(
+            from_versioned_json(future),
+            Err(MigrationError::UnsupportedVersion { .. })
+        ));
+        let bad = json!({"format_version": "one", "ast": {}});
+        assert!(matches!(from_versioned_json(bad), Err(MigrationError::InvalidVersion(_))));
+    }
+
+    #[test]
+    fn test_json_v1_renames_and_round_trip() {
+        let source = r#"variables:
+   data: mutable object = {"ty": 1}
+
+topic main:
+   description: "Main"
+   actions:
+      lookup:
+         description: "Look up"
+         inputs:
+            id: string
+         target: "flow://Lookup"
+"#;
+        let ast = crate::parse(source).unwrap();
+        let json = to_json_v1(&ast);
+        let var = &json["variables"]["node"]["variables"][0]["node"];
+        assert_eq!(var["type"]["node"], "Object");
+        assert!(var.get("ty").is_none());
+        // Object literal keys are data, not fields
+        assert!(var["default"]["node"]["Object"].get("ty").is_some());
+        let input = &json["topics"][0]["node"]["actions"]["node"]["actions"][0]["node"]["inputs"];
+        assert_eq!(input["node"][0]["node"]["type"]["node"], "String");
+
+        assert_eq!(from_json_v1(json.clone()).unwrap(), ast);
+
+        let with_action = crate::parse(
+            "topic main:\n   reasoning:\n      actions:\n         go: @actions.go\n            with id = ...\n",
+        )
+        .unwrap();
+        let action = &to_json_v1(&with_action)["topics"][0]["node"]["reasoning"]["node"]["actions"]
+            ["node"][0]["node"];
+        assert!(action.get("with_clauses").is_some());
+        assert!(action.get("clause_order").is_none());
+
+        let quoted = crate::parse("topic \"Claims – EU\":\n   description: \"EU\"\n").unwrap();
+        let topic = &to_json_v1(&quoted)["topics"][0]["node"];
+        assert_eq!(topic["name"]["node"], "Claims_EU");
+        assert!(topic.get("quoted_name").is_none());
+
+        let mut unversioned = json.clone();
+        unversioned.as_object_mut().unwrap().remove("ast_version");
+        assert!(matches!(from_json_v1(unversioned), Err(MigrationError::InvalidVersion(_))));
+        let mut future = json;
+        future["ast_version"] = json!(2);
+        assert!(matches!(
+            from_json_v1(future),
+            Err(MigrationError::UnsupportedVersion { found: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn test_serialize_with_source_map() {
+        let source = "config:\n   agent_name: \"Test\"\n\ntopic main:\n  description:   \"Main\"\n";
+        let ast = crate::parse(source).unwrap();
+        let (output, map) = serialize_with_source_map(&ast);
+        assert_eq!(output, serialize(&ast));
+
+        let desc = map.get("topics[0].node.description").unwrap();
+        assert_eq!(&output[desc.output.clone()], "\"Main\"");
+        assert_eq!(&source[desc.original.clone()], "\"Main\"");
+
+        // Innermost node wins
+        let inside = desc.output.start + 1;
+        assert_eq!(map.entry_at_output(inside).unwrap().path, desc.path);
+        let topic = map
+            .entry_at_original(source.find("topic").unwrap())
+            .unwrap();
+        assert_eq!(topic.path, "topics[0]");
+    }
+
+    #[test]
+    fn test_from_json_strict() {
+        let ast = crate::parse(
+            "config:\n   agent_name: \"Test\"\n\ntopic main:\n   description: \"Main\"\n",
+        )
+        .unwrap();
+        let json = serde_json::to_value(&ast).unwrap();
+        assert_eq!(from_json_strict(&json).unwrap(), ast);
+
+        let mut unknown = json.clone();
+        unknown["topics"][0]["node"]["descriptoin"] = json!("typo");
+        unknown["topics"][0]["extra"] = json!(1);
+        let errors = from_json_strict(&unknown).unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].path, "topics[0].extra");
+        assert_eq!(errors[0].suggestion, None);
+        assert_eq!(errors[1].path, "topics[0].node.descriptoin");
+        assert_eq!(errors[1].suggestion.as_deref(), Some("description"));
+
+        let mut missing = json.clone();
+        let topic = missing["topics"][0]["node"].as_object_mut().unwrap();
+        let name = topic.remove("name").unwrap();
+        topic.insert("nmae".to_string(), name);
+        let errors = from_json_strict(&missing).unwrap_err();
+        assert_eq!(
+            errors,
+            [JsonFieldError {
+                path: "topics[0].node.name".to_string(),
+                kind: JsonFieldErrorKind::MissingField,
+                suggestion: Some("nmae".to_string()),
+            }]
+        );
+    }
+
     #[test]
     fn test_serialize_minimal_config() {
         let mut agent = AgentFile::new();
@@ -1044,6 +2216,7 @@ mod tests {
                 description: None,
                 agent_type: None,
                 default_agent_user: None,
+                target_environment: None,
             },
             0..10,
         ));
@@ -1066,6 +2239,7 @@ mod tests {
                         default: Some(Spanned::new(Expr::String("default".to_string()), 0..7)),
                         description: Some(Spanned::new("Test variable".to_string(), 0..13)),
                         source: None,
+                        doc: None,
                     },
                     0..50,
                 )],
@@ -1085,12 +2259,14 @@ mod tests {
         agent.topics = vec![Spanned::new(
             TopicBlock {
                 name: Spanned::new("main".to_string(), 0..4),
+                quoted_name: None,
                 description: Some(Spanned::new("Main topic".to_string(), 0..10)),
                 system: None,
                 actions: None,
                 before_reasoning: None,
                 reasoning: None,
                 after_reasoning: None,
+                doc: None,
             },
             0..50,
         )];
@@ -1100,6 +2276,20 @@ mod tests {
         assert!(output.contains("description: \"Main topic\""));
     }
 
+    #[test]
+    fn test_serialize_doc_comments() {
+        let source = "variables:\n   # Customer name.\n   #\n   # Set by the lookup flow.\n   name: mutable string = \"\"\n";
+        let agent = crate::parse(source).unwrap();
+        let output = serialize(&agent);
+        assert!(output.contains(
+            "   # Customer name.\n   #\n   # Set by the lookup flow.\n   name: mutable string"
+        ));
+
+        let reparsed = crate::parse(&output).unwrap();
+        let var = &reparsed.variables.unwrap().node.variables[0].node;
+        assert_eq!(var.doc.as_ref().unwrap().node, "Customer name.\n\nSet by the lookup flow.");
+    }
+
     #[test]
     fn test_escape_string() {
         assert_eq!(escape_string("hello"), "hello");
@@ -1121,8 +2311,9 @@ mod tests {
     fn test_expr_to_string() {
         let w = Writer::new();
         assert_eq!(w.expr_to_string(&Expr::String("test".to_string())), "\"test\"");
-        assert_eq!(w.expr_to_string(&Expr::Number(42.0)), "42");
-        assert_eq!(w.expr_to_string(&Expr::Number(3.15)), "3.15");
+        assert_eq!(w.expr_to_string(&Expr::Number(Number::Int(42))), "42");
+        assert_eq!(w.expr_to_string(&Expr::Number(Number::from(3.15))), "3.15");
+        assert_eq!(w.expr_to_string(&Expr::Number("2.50".parse().unwrap())), "2.50");
         assert_eq!(w.expr_to_string(&Expr::Bool(true)), "True");
         assert_eq!(w.expr_to_string(&Expr::Bool(false)), "False");
         assert_eq!(w.expr_to_string(&Expr::None), "None");
@@ -0,0 +1,1092 @@
+//! Dry-run simulation of an agent against mock data.
+//!
+//! [`simulate`] interprets an agent without an org or an LLM: it starts at
+//! `start_agent`, runs `before_reasoning` and `after_reasoning` directives,
+//! evaluates conditions against the current variable values, and follows
+//! transitions. The parts a real run would get from outside come from
+//! [`MockData`]:
+//!
+//! - `variables`: initial values, overriding declared defaults
+//! - `actions`: the outputs each action returns, by action name
+//! - `choices`: the reasoning action the planner picks in each topic, either
+//!   one name or a list consumed one per visit
+//!
+//! A topic without a choice ends the run after its directives, as if the
+//! planner answered the user directly.
+//!
+//! [`run_scenario`] simulates a [`Scenario`] and checks its expectations,
+//! for pass/fail tests of agent flows.
+//!
+//! # Example
+//!
+//! ```rust
+//! use busbar_sf_agentscript::simulation::{simulate, MockData, SimulationOutcome};
+//! use serde_json::json;
+//!
+//! let source = r#"
+//! variables:
+//!    verified: mutable boolean = False
+//!
+//! start_agent main:
+//!    description: "Route"
+//!    reasoning:
+//!       instructions: "Route"
+//!       actions:
+//!          go_orders: @utils.transition to @topic.orders
+//!             available when @variables.verified
+//!
+//! topic orders:
+//!    description: "Orders"
+//!    reasoning:
+//!       instructions: "Report the status"
+//! "#;
+//! let ast = busbar_sf_agentscript::parse(source).unwrap();
+//! let mock: MockData = serde_json::from_value(json!({
+//!     "variables": { "verified": true },
+//!     "choices": { "main": "go_orders" }
+//! }))
+//! .unwrap();
+//!
+//! let trace = simulate(&ast, &mock);
+//! assert_eq!(trace.outcome, SimulationOutcome::Success);
+//! assert_eq!(trace.topic_transitions, ["main", "orders"]);
+//! ```
+
+use crate::ast::{
+    ActionsBlock, AgentFile, BinOp, Currency, DirectiveBlock, Expr, Number, ReasoningAction,
+    ReasoningActionTarget, ReasoningBlock, Reference, SetClause, Spanned, Stmt, UnaryOp,
+    WithClause, WithValue,
+};
+use crate::serializer::serialize_expr;
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+/// Topic visits after which a run is stopped as a transition loop.
+const MAX_TOPIC_VISITS: usize = 32;
+
+/// Everything a simulation takes from outside the agent.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct MockData {
+    /// Initial variable values, overriding declared defaults.
+    pub variables: Map<String, Value>,
+    /// Outputs of each action, by action name.
+    pub actions: Map<String, Value>,
+    /// The reasoning action picked in each topic, by topic name.
+    pub choices: IndexMap<String, Choices>,
+}
+
+/// Reasoning actions picked in one topic, one per visit.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(from = "OneOrMany")]
+pub struct Choices(pub Vec<String>);
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OneOrMany {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl From<OneOrMany> for Choices {
+    fn from(value: OneOrMany) -> Self {
+        match value {
+            OneOrMany::One(choice) => Choices(vec![choice]),
+            OneOrMany::Many(choices) => Choices(choices),
+        }
+    }
+}
+
+/// How a simulation ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SimulationOutcome {
+    /// A topic finished without transitioning.
+    Success,
+    /// The agent escalated to a human.
+    Escalated,
+    /// The run could not continue; see [`SimulationTrace::error`].
+    Error,
+}
+
+/// A variable assignment made by a step.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct VariableChange {
+    pub name: String,
+    pub old_value: Value,
+    pub new_value: Value,
+}
+
+/// An action invoked by a step.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ActionInvocation {
+    pub action_name: String,
+    pub inputs: Map<String, Value>,
+    pub outputs: Value,
+}
+
+/// One executed statement or reasoning decision.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SimulationStep {
+    /// Where the step ran, e.g. `"orders:before_reasoning"`.
+    pub phase: String,
+    /// `set`, `run`, `if`, `transition`, `escalate`, `respond`, `reasoning`,
+    /// or `reasoning_action`.
+    pub statement_type: String,
+    /// Human-readable description.
+    pub detail: String,
+    pub variable_changes: Vec<VariableChange>,
+    pub action_invocations: Vec<ActionInvocation>,
+}
+
+/// The result of [`simulate`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SimulationTrace {
+    pub steps: Vec<SimulationStep>,
+    /// Variable values when the run ended.
+    pub final_context: Map<String, Value>,
+    pub outcome: SimulationOutcome,
+    /// Why the run stopped, when the outcome is [`SimulationOutcome::Error`].
+    pub error: Option<String>,
+    /// Topics in the order they were entered, starting with `start_agent`.
+    pub topic_transitions: Vec<String>,
+}
+
+impl SimulationTrace {
+    /// Names of all actions invoked, in order.
+    pub fn invoked_actions(&self) -> impl Iterator<Item = &str> {
+        self.steps
+            .iter()
+            .flat_map(|s| &s.action_invocations)
+            .map(|a| a.action_name.as_str())
+    }
+}
+
+/// Simulate an agent from `start_agent` with the given mock data.
+pub fn simulate(ast: &AgentFile, mock: &MockData) -> SimulationTrace {
+    let mut sim = Simulator {
+        mock,
+        variables: initial_variables(ast, mock),
+        steps: Vec::new(),
+        visits: HashMap::new(),
+    };
+    let mut topic_transitions = Vec::new();
+
+    let mut current = ast.start_agent.as_ref().map(|s| s.node.name.node.clone());
+    let (outcome, error) = loop {
+        let Some(name) = current.take() else {
+            break (SimulationOutcome::Error, Some("Agent has no start_agent".to_string()));
+        };
+        let Some(block) = Block::find(ast, &name) else {
+            break (SimulationOutcome::Error, Some(format!("Unknown topic '{}'", name)));
+        };
+        if topic_transitions.len() == MAX_TOPIC_VISITS {
+            break (
+                SimulationOutcome::Error,
+                Some(format!("Stopped after {} topic visits", MAX_TOPIC_VISITS)),
+            );
+        }
+        topic_transitions.push(name.clone());
+
+        match sim.run_block(&name, &block) {
+            Ok(Some(Flow::Transition(next))) => current = Some(next),
+            Ok(Some(Flow::Escalate)) => break (SimulationOutcome::Escalated, None),
+            Ok(None) => break (SimulationOutcome::Success, None),
+            Err(message) => break (SimulationOutcome::Error, Some(message)),
+        }
+    };
+
+    SimulationTrace {
+        steps: sim.steps,
+        final_context: sim.variables,
+        outcome,
+        error,
+        topic_transitions,
+    }
+}
+
+/// A named dry run with expected results.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Scenario {
+    pub name: Option<String>,
+    #[serde(alias = "mockData")]
+    pub mock_data: MockData,
+    pub expect: ScenarioExpectations,
+}
+
+/// What a [`Scenario`] checks. Unset expectations are not checked.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ScenarioExpectations {
+    pub outcome: Option<SimulationOutcome>,
+    /// The exact sequence of topics entered.
+    pub topics: Option<Vec<String>>,
+    /// The topic the run ended in.
+    #[serde(alias = "finalTopic")]
+    pub final_topic: Option<String>,
+    /// Final values of these variables.
+    pub variables: Map<String, Value>,
+    /// Actions that must have been invoked.
+    pub actions: Vec<String>,
+}
+
+/// The result of [`run_scenario`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ScenarioResult {
+    pub name: Option<String>,
+    pub passed: bool,
+    /// One message per unmet expectation.
+    pub failures: Vec<String>,
+    pub trace: SimulationTrace,
+}
+
+/// Simulate a scenario and check its expectations.
+pub fn run_scenario(ast: &AgentFile, scenario: &Scenario) -> ScenarioResult {
+    let trace = simulate(ast, &scenario.mock_data);
+    let expect = &scenario.expect;
+    let mut failures = Vec::new();
+
+    if let Some(outcome) = expect.outcome {
+        if trace.outcome != outcome {
+            failures.push(format!(
+                "expected outcome {:?}, got {:?}{}",
+                outcome,
+                trace.outcome,
+                trace
+                    .error
+                    .as_ref()
+                    .map(|e| format!(" ({})", e))
+                    .unwrap_or_default()
+            ));
+        }
+    }
+    if let Some(topics) = &expect.topics {
+        if &trace.topic_transitions != topics {
+            failures.push(format!(
+                "expected topics {}, got {}",
+                topics.join(" → "),
+                trace.topic_transitions.join(" → ")
+            ));
+        }
+    }
+    if let Some(topic) = &expect.final_topic {
+        let last = trace.topic_transitions.last();
+        if last != Some(topic) {
+            failures.push(format!(
+                "expected to end in '{}', ended in '{}'",
+                topic,
+                last.map_or("", String::as_str)
+            ));
+        }
+    }
+    for (name, expected) in &expect.variables {
+        let actual = trace.final_context.get(name).unwrap_or(&Value::Null);
+        if !values_equal(actual, expected) {
+            failures
+                .push(format!("expected @variables.{} to be {}, got {}", name, expected, actual));
+        }
+    }
+    for action in &expect.actions {
+        if !trace.invoked_actions().any(|a| a == action) {
+            failures.push(format!("expected action '{}' to be invoked", action));
+        }
+    }
+
+    ScenarioResult {
+        name: scenario.name.clone(),
+        passed: failures.is_empty(),
+        failures,
+        trace,
+    }
+}
+
+// ============================================================================
+// Interpreter
+// ============================================================================
+
+/// Where control goes after a block or statement.
+enum Flow {
+    Transition(String),
+    Escalate,
+}
+
+/// The parts `start_agent` and topics have in common.
+struct Block<'a> {
+    actions: Option<&'a Spanned<ActionsBlock>>,
+    before_reasoning: Option<&'a Spanned<DirectiveBlock>>,
+    reasoning: Option<&'a Spanned<ReasoningBlock>>,
+    after_reasoning: Option<&'a Spanned<DirectiveBlock>>,
+}
+
+impl<'a> Block<'a> {
+    fn find(ast: &'a AgentFile, name: &str) -> Option<Self> {
+        if let Some(start) = ast
+            .start_agent
+            .as_ref()
+            .filter(|s| s.node.name.node == name)
+        {
+            let s = &start.node;
+            return Some(Block {
+                actions: s.actions.as_ref(),
+                before_reasoning: s.before_reasoning.as_ref(),
+                reasoning: s.reasoning.as_ref(),
+                after_reasoning: s.after_reasoning.as_ref(),
+            });
+        }
+        let t = &ast.topics.iter().find(|t| t.node.name.node == name)?.node;
+        Some(Block {
+            actions: t.actions.as_ref(),
+            before_reasoning: t.before_reasoning.as_ref(),
+            reasoning: t.reasoning.as_ref(),
+            after_reasoning: t.after_reasoning.as_ref(),
+        })
+    }
+}
+
+struct Simulator<'a> {
+    mock: &'a MockData,
+    variables: Map<String, Value>,
+    steps: Vec<SimulationStep>,
+    /// Visits per topic, to pick the next of several choices.
+    visits: HashMap<String, usize>,
+}
+
+fn initial_variables(ast: &AgentFile, mock: &MockData) -> Map<String, Value> {
+    let empty = Map::new();
+    let mut variables = Map::new();
+    for v in ast.variables.iter().flat_map(|v| &v.node.variables) {
+        let name = &v.node.name.node;
+        let value = match (mock.variables.get(name), &v.node.default) {
+            (Some(value), _) => value.clone(),
+            (None, Some(default)) => eval(&default.node, &variables, &empty),
+            (None, None) => Value::Null,
+        };
+        variables.insert(name.clone(), value);
+    }
+    for (name, value) in &mock.variables {
+        variables
+            .entry(name.clone())
+            .or_insert_with(|| value.clone());
+    }
+    variables
+}
+
+impl Simulator<'_> {
+    fn run_block(&mut self, name: &str, block: &Block<'_>) -> Result<Option<Flow>, String> {
+        if let Some(before) = block.before_reasoning {
+            let phase = format!("{}:before_reasoning", name);
+            if let Some(flow) = self.run_stmts(&phase, block, &before.node.statements)? {
+                return Ok(Some(flow));
+            }
+        }
+
+        if let Some(flow) = self.reason(name, block)? {
+            return Ok(Some(flow));
+        }
+
+        if let Some(after) = block.after_reasoning {
+            let phase = format!("{}:after_reasoning", name);
+            if let Some(flow) = self.run_stmts(&phase, block, &after.node.statements)? {
+                return Ok(Some(flow));
+            }
+        }
+        Ok(None)
+    }
+
+    fn run_stmts(
+        &mut self,
+        phase: &str,
+        block: &Block<'_>,
+        stmts: &[Spanned<Stmt>],
+    ) -> Result<Option<Flow>, String> {
+        for stmt in stmts {
+            let flow = match &stmt.node {
+                Stmt::Set { target, value } => {
+                    let new_value = self.eval(&value.node, &Map::new());
+                    let change = self.assign(&target.node, new_value);
+                    self.step(
+                        phase,
+                        "set",
+                        format!(
+                            "set {} = {}",
+                            target.node.full_path(),
+                            serialize_expr(&value.node)
+                        ),
+                    )
+                    .variable_changes
+                    .extend(change);
+                    None
+                }
+                Stmt::Run {
+                    action,
+                    with_clauses,
+                    set_clauses,
+                } => {
+                    let (invocation, changes) =
+                        self.invoke(block, &action.node, with_clauses, set_clauses)?;
+                    let step = self.step(phase, "run", format!("run {}", action.node.full_path()));
+                    step.action_invocations.push(invocation);
+                    step.variable_changes.extend(changes);
+                    None
+                }
+                Stmt::If {
+                    condition,
+                    then_block,
+                    else_block,
+                } => {
+                    let taken = truthy(&self.eval(&condition.node, &Map::new()));
+                    self.step(
+                        phase,
+                        "if",
+                        format!("if {} → {}", serialize_expr(&condition.node), taken),
+                    );
+                    match (taken, else_block) {
+                        (true, _) => self.run_stmts(phase, block, then_block)?,
+                        (false, Some(else_block)) => self.run_stmts(phase, block, else_block)?,
+                        (false, None) => None,
+                    }
+                }
+                Stmt::Transition { target } => {
+                    self.step(
+                        phase,
+                        "transition",
+                        format!("transition to {}", target.node.full_path()),
+                    );
+                    Some(transition_target(&target.node)?)
+                }
+                Stmt::Escalate => {
+                    self.step(phase, "escalate", "escalate".to_string());
+                    Some(Flow::Escalate)
+                }
+                Stmt::Respond { message } => {
+                    self.step(phase, "respond", message.node.clone());
+                    None
+                }
+            };
+            if flow.is_some() {
+                return Ok(flow);
+            }
+        }
+        Ok(None)
+    }
+
+    /// Record the planner's choice in a topic and run it.
+    fn reason(&mut self, name: &str, block: &Block<'_>) -> Result<Option<Flow>, String> {
+        let Some(reasoning) = block.reasoning else {
+            return Ok(None);
+        };
+        let phase = format!("{}:reasoning", name);
+        let actions: Vec<&Spanned<ReasoningAction>> = reasoning
+            .node
+            .actions
+            .iter()
+            .flat_map(|a| &a.node)
+            .collect();
+        let available: Vec<&str> = actions
+            .iter()
+            .filter(|a| {
+                a.node
+                    .available_when
+                    .as_ref()
+                    .is_none_or(|cond| truthy(&self.eval(&cond.node, &Map::new())))
+            })
+            .map(|a| a.node.name.node.as_str())
+            .collect();
+        self.step(&phase, "reasoning", format!("available: {}", available.join(", ")));
+
+        let visit = self.visits.entry(name.to_string()).or_default();
+        let choice = self.mock.choices.get(name).and_then(|c| c.0.get(*visit));
+        *visit += 1;
+        let Some(choice) = choice else {
+            return Ok(None);
+        };
+        let Some(action) = actions.iter().find(|a| &a.node.name.node == choice) else {
+            return Err(format!("Topic '{}' has no reasoning action '{}'", name, choice));
+        };
+        if !available.contains(&choice.as_str()) {
+            return Err(format!("Reasoning action '{}' is not available in '{}'", choice, name));
+        }
+        let a = &action.node;
+
+        let mut step = SimulationStep {
+            phase: phase.clone(),
+            statement_type: "reasoning_action".to_string(),
+            detail: format!("chose {}", choice),
+            variable_changes: Vec::new(),
+            action_invocations: Vec::new(),
+        };
+        let flow = match &a.target.node {
+            ReasoningActionTarget::Action(reference) => {
+                let (invocation, changes) =
+                    self.invoke(block, reference, &a.with_clauses, &a.set_clauses)?;
+                // `if` clauses may test the action's `@outputs`
+                let outputs = match &invocation.outputs {
+                    Value::Object(map) => map.clone(),
+                    _ => Map::new(),
+                };
+                step.action_invocations.push(invocation);
+                step.variable_changes.extend(changes);
+                for run in &a.run_clauses {
+                    let (invocation, changes) = self.invoke(
+                        block,
+                        &run.node.action.node,
+                        &run.node.with_clauses,
+                        &run.node.set_clauses,
+                    )?;
+                    step.action_invocations.push(invocation);
+                    step.variable_changes.extend(changes);
+                }
+                let mut flow = None;
+                for clause in &a.if_clauses {
+                    if truthy(&self.eval(&clause.node.condition.node, &outputs)) {
+                        if let Some(target) = &clause.node.transition {
+                            flow = Some(transition_target(&target.node)?);
+                            break;
+                        }
+                    }
+                }
+                match (flow, &a.transition) {
+                    (Some(flow), _) => Some(flow),
+                    (None, Some(target)) => Some(transition_target(&target.node)?),
+                    (None, None) => None,
+                }
+            }
+            ReasoningActionTarget::TransitionTo(target)
+            | ReasoningActionTarget::TopicDelegate(target) => Some(transition_target(target)?),
+            ReasoningActionTarget::Escalate => Some(Flow::Escalate),
+            ReasoningActionTarget::SetVariables => {
+                for with in &a.with_clauses {
+                    let WithValue::Expr(expr) = &with.node.value.node;
+                    let value = self.eval(expr, &Map::new());
+                    let target = Reference::new("variables", vec![with.node.param.node.clone()]);
+                    step.variable_changes.extend(self.assign(&target, value));
+                }
+                None
+            }
+        };
+        self.steps.push(step);
+        Ok(flow)
+    }
+
+    /// Invoke an action defined in `block`, binding its inputs and applying
+    /// `set` clauses to its mocked outputs.
+    fn invoke(
+        &mut self,
+        block: &Block<'_>,
+        action: &Reference,
+        with_clauses: &[Spanned<WithClause>],
+        set_clauses: &[Spanned<SetClause>],
+    ) -> Result<(ActionInvocation, Vec<VariableChange>), String> {
+        let name = action.path.first().cloned().unwrap_or_default();
+        let defined = block
+            .actions
+            .is_some_and(|a| a.node.actions.iter().any(|d| d.node.name.node == name));
+        if !defined {
+            return Err(format!("Action '{}' is not defined", action.full_path()));
+        }
+
+        let inputs: Map<String, Value> = with_clauses
+            .iter()
+            .map(|w| {
+                let WithValue::Expr(expr) = &w.node.value.node;
+                (w.node.param.node.clone(), self.eval(expr, &Map::new()))
+            })
+            .collect();
+        let outputs = self
+            .mock
+            .actions
+            .get(&name)
+            .cloned()
+            .unwrap_or_else(|| Value::Object(Map::new()));
+        let output_scope = match &outputs {
+            Value::Object(map) => map.clone(),
+            _ => Map::new(),
+        };
+
+        let mut changes = Vec::new();
+        for set in set_clauses {
+            let value = self.eval(&set.node.source.node, &output_scope);
+            changes.extend(self.assign(&set.node.target.node, value));
+        }
+
+        Ok((
+            ActionInvocation {
+                action_name: name,
+                inputs,
+                outputs,
+            },
+            changes,
+        ))
+    }
+
+    fn assign(&mut self, target: &Reference, value: Value) -> Option<VariableChange> {
+        if target.namespace != "variables" {
+            return None;
+        }
+        let name = target.path.first()?.clone();
+        let old_value = self.variables.insert(name.clone(), value.clone());
+        Some(VariableChange {
+            name,
+            old_value: old_value.unwrap_or(Value::Null),
+            new_value: value,
+        })
+    }
+
+    fn eval(&self, expr: &Expr, outputs: &Map<String, Value>) -> Value {
+        eval(expr, &self.variables, outputs)
+    }
+
+    fn step(&mut self, phase: &str, statement_type: &str, detail: String) -> &mut SimulationStep {
+        self.steps.push(SimulationStep {
+            phase: phase.to_string(),
+            statement_type: statement_type.to_string(),
+            detail,
+            variable_changes: Vec::new(),
+            action_invocations: Vec::new(),
+        });
+        self.steps.last_mut().unwrap()
+    }
+}
+
+fn transition_target(target: &Reference) -> Result<Flow, String> {
+    match (target.namespace.as_str(), target.path.first()) {
+        ("topic", Some(name)) => Ok(Flow::Transition(name.clone())),
+        _ => Err(format!("Cannot transition to {}", target.full_path())),
+    }
+}
+
+// ============================================================================
+// Expressions
+// ============================================================================
+
+/// Evaluate an expression. `@outputs.*` resolves against `outputs`; values
+/// the interpreter cannot know (slot fills, unknown references) are `null`.
+fn eval(expr: &Expr, variables: &Map<String, Value>, outputs: &Map<String, Value>) -> Value {
+    let eval = |e: &Spanned<Expr>| eval(&e.node, variables, outputs);
+    match expr {
+        Expr::Reference(r) => {
+            let scope = match r.namespace.as_str() {
+                "variables" => variables,
+                "outputs" => outputs,
+                _ => return Value::String(r.full_path()),
+            };
+            let mut path = r.path.iter();
+            let root = path.next().and_then(|name| scope.get(name)).cloned();
+            path.fold(root.unwrap_or(Value::Null), |value, field| {
+                value.get(field).cloned().unwrap_or(Value::Null)
+            })
+        }
+        Expr::String(s) => Value::String(s.clone()),
+        Expr::Template(parts) => Value::String(parts.iter().map(|p| display(&eval(p))).collect()),
+        Expr::Number(n) | Expr::Currency(Currency { amount: n, .. }) => match n {
+            Number::Int(n) => Value::from(*n),
+            n => number(n.as_f64()),
+        },
+        // ISO dates compare correctly as strings
+        Expr::Date(d) => Value::String(d.to_string()),
+        Expr::Bool(b) => Value::Bool(*b),
+        Expr::None | Expr::SlotFill => Value::Null,
+        Expr::List(items) => Value::Array(items.iter().map(eval).collect()),
+        Expr::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(key, value)| (key.clone(), eval(value)))
+                .collect(),
+        ),
+        Expr::BinOp { left, op, right } => {
+            let left = eval(left);
+            match op {
+                BinOp::And if !truthy(&left) => Value::Bool(false),
+                BinOp::Or if truthy(&left) => Value::Bool(true),
+                BinOp::And | BinOp::Or => Value::Bool(truthy(&eval(right))),
+                _ => binary(*op, &left, &eval(right)),
+            }
+        }
+        Expr::UnaryOp { op, operand } => {
+            let operand = eval(operand);
+            match op {
+                UnaryOp::Not => Value::Bool(!truthy(&operand)),
+                UnaryOp::Neg => match operand.as_i64().and_then(i64::checked_neg) {
+                    Some(n) => Value::from(n),
+                    None => operand.as_f64().map_or(Value::Null, |n| number(-n)),
+                },
+            }
+        }
+        Expr::Ternary {
+            condition,
+            then_expr,
+            else_expr,
+        } => {
+            if truthy(&eval(condition)) {
+                eval(then_expr)
+            } else {
+                eval(else_expr)
+            }
+        }
+        Expr::Property { object, field } => eval(object)
+            .get(&field.node)
+            .cloned()
+            .unwrap_or(Value::Null),
+        Expr::Index { object, index } => {
+            let object = eval(object);
+            match eval(index) {
+                Value::String(key) => object.get(&key).cloned(),
+                Value::Number(n) => n.as_u64().and_then(|i| object.get(i as usize).cloned()),
+                _ => None,
+            }
+            .unwrap_or(Value::Null)
+        }
+        Expr::Call { callee, args } => {
+            let args: Vec<Value> = args.iter().map(eval).collect();
+            call(&callee.node, &args)
+        }
+    }
+}
+
+fn binary(op: BinOp, left: &Value, right: &Value) -> Value {
+    // Whole numbers stay exact unless the result overflows or divides
+    let integers = left.as_i64().zip(right.as_i64());
+    let exact = match (op, integers) {
+        (BinOp::Add, Some((a, b))) => a.checked_add(b),
+        (BinOp::Sub, Some((a, b))) => a.checked_sub(b),
+        (BinOp::Mul, Some((a, b))) => a.checked_mul(b),
+        (BinOp::Mod, Some((a, b))) => a.checked_rem(b),
+        _ => None,
+    };
+    if let Some(n) = exact {
+        return Value::from(n);
+    }
+
+    let numbers = left.as_f64().zip(right.as_f64());
+    match op {
+        BinOp::Eq | BinOp::Is => Value::Bool(values_equal(left, right)),
+        BinOp::Ne | BinOp::IsNot => Value::Bool(!values_equal(left, right)),
+        BinOp::Lt | BinOp::Gt | BinOp::Le | BinOp::Ge => {
+            let ordering = match (left, right) {
+                (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+                _ => numbers.and_then(|(a, b)| a.partial_cmp(&b)),
+            };
+            Value::Bool(ordering.is_some_and(|o| match op {
+                BinOp::Lt => o.is_lt(),
+                BinOp::Gt => o.is_gt(),
+                BinOp::Le => o.is_le(),
+                _ => o.is_ge(),
+            }))
+        }
+        BinOp::Add => match (left, right) {
+            (Value::String(a), Value::String(b)) => Value::String(format!("{}{}", a, b)),
+            (Value::Array(a), Value::Array(b)) => {
+                Value::Array(a.iter().chain(b).cloned().collect())
+            }
+            _ => numbers.map_or(Value::Null, |(a, b)| number(a + b)),
+        },
+        BinOp::Sub => numbers.map_or(Value::Null, |(a, b)| number(a - b)),
+        BinOp::Mul => numbers.map_or(Value::Null, |(a, b)| number(a * b)),
+        BinOp::Div => numbers
+            .filter(|(_, b)| *b != 0.0)
+            .map_or(Value::Null, |(a, b)| number(a / b)),
+        BinOp::Mod => numbers
+            .filter(|(_, b)| *b != 0.0)
+            .map_or(Value::Null, |(a, b)| number(a % b)),
+        BinOp::And | BinOp::Or => unreachable!("short-circuited in eval"),
+    }
+}
+
+/// Evaluate a [built-in function](crate::ast::BUILTIN_FUNCTIONS).
+fn call(name: &str, args: &[Value]) -> Value {
+    let text = |i: usize| args.get(i).and_then(Value::as_str);
+    match (name, args) {
+        ("len", [Value::String(s)]) => Value::from(s.chars().count()),
+        ("len", [Value::Array(items)]) => Value::from(items.len()),
+        ("len", [Value::Object(fields)]) => Value::from(fields.len()),
+        ("lower", _) => text(0).map_or(Value::Null, |s| Value::from(s.to_lowercase())),
+        ("upper", _) => text(0).map_or(Value::Null, |s| Value::from(s.to_uppercase())),
+        ("trim", _) => text(0).map_or(Value::Null, |s| Value::from(s.trim())),
+        ("contains", [Value::Array(items), needle]) => {
+            Value::Bool(items.iter().any(|item| values_equal(item, needle)))
+        }
+        ("contains", _) => match (text(0), text(1)) {
+            (Some(s), Some(needle)) => Value::Bool(s.contains(needle)),
+            _ => Value::Null,
+        },
+        ("starts_with", _) => match (text(0), text(1)) {
+            (Some(s), Some(prefix)) => Value::Bool(s.starts_with(prefix)),
+            _ => Value::Null,
+        },
+        ("ends_with", _) => match (text(0), text(1)) {
+            (Some(s), Some(suffix)) => Value::Bool(s.ends_with(suffix)),
+            _ => Value::Null,
+        },
+        _ => Value::Null,
+    }
+}
+
+/// Whole numbers become JSON integers so traces read naturally.
+fn number(n: f64) -> Value {
+    if n.fract() == 0.0 && n.abs() < i64::MAX as f64 {
+        Value::from(n as i64)
+    } else {
+        serde_json::Number::from_f64(n).map_or(Value::Null, Value::Number)
+    }
+}
+
+/// How a value reads when interpolated into text, spelled as in AgentScript.
+fn display(value: &Value) -> String {
+    match value {
+        Value::Null => "None".to_string(),
+        Value::Bool(true) => "True".to_string(),
+        Value::Bool(false) => "False".to_string(),
+        Value::String(s) => s.clone(),
+        value => value.to_string(),
+    }
+}
+
+fn truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64().is_some_and(|n| n != 0.0),
+        Value::String(s) => !s.is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        Value::Object(fields) => !fields.is_empty(),
+    }
+}
+
+/// Equality that treats `1` and `1.0` as the same number.
+fn values_equal(left: &Value, right: &Value) -> bool {
+    match (left.as_f64(), right.as_f64()) {
+        (Some(a), Some(b)) => a == b,
+        _ => left == right,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const SOURCE: &str = r#"
+variables:
+   order_id: mutable string = ""
+   status: mutable string = ""
+   attempts: mutable number = 0
+
+start_agent main:
+   description: "Route"
+   reasoning:
+      instructions: "Route"
+      actions:
+         go_orders: @utils.transition to @topic.orders
+
+topic orders:
+   description: "Orders"
+   actions:
+      lookup:
+         description: "Look up an order"
+         inputs:
+            id: string
+         outputs:
+            status: string
+         target: "flow://Lookup"
+   before_reasoning:
+      set @variables.attempts = @variables.attempts + 1
+   reasoning:
+      instructions: "Help"
+      actions:
+         do_lookup: @actions.lookup
+            with id=@variables.order_id
+            set @variables.status = @outputs.status
+            if @outputs.status == "lost":
+               transition to @topic.orders
+         escalate: @utils.escalate
+            available when @variables.status == "lost"
+"#;
+
+    fn ast() -> AgentFile {
+        crate::parse(SOURCE).unwrap()
+    }
+
+    #[test]
+    fn test_simulate_with_mocks() {
+        let mock: MockData = serde_json::from_value(json!({
+            "variables": { "order_id": "A-1" },
+            "actions": { "lookup": { "status": "lost" } },
+            "choices": { "main": "go_orders", "orders": ["do_lookup", "escalate"] }
+        }))
+        .unwrap();
+        let trace = simulate(&ast(), &mock);
+
+        assert_eq!(trace.outcome, SimulationOutcome::Escalated, "{:?}", trace.error);
+        assert_eq!(trace.topic_transitions, ["main", "orders", "orders"]);
+        assert_eq!(trace.final_context["attempts"], json!(2));
+        assert_eq!(trace.final_context["status"], json!("lost"));
+
+        let lookup = trace
+            .steps
+            .iter()
+            .flat_map(|s| &s.action_invocations)
+            .next()
+            .unwrap();
+        assert_eq!(lookup.action_name, "lookup");
+        assert_eq!(lookup.inputs["id"], json!("A-1"));
+
+        // Without a choice the first topic ends the run
+        let trace = simulate(&ast(), &MockData::default());
+        assert_eq!(trace.outcome, SimulationOutcome::Success);
+        assert_eq!(trace.topic_transitions, ["main"]);
+
+        // Choosing an unavailable action is an error
+        let mock: MockData = serde_json::from_value(json!({
+            "choices": { "main": "go_orders", "orders": "escalate" }
+        }))
+        .unwrap();
+        let trace = simulate(&ast(), &mock);
+        assert_eq!(trace.outcome, SimulationOutcome::Error);
+        assert_eq!(
+            trace.error.as_deref(),
+            Some("Reasoning action 'escalate' is not available in 'orders'")
+        );
+    }
+
+    #[test]
+    fn test_simulate_respond_and_escalate_statements() {
+        let ast = crate::parse(
+            r#"variables:
+   attempts: mutable number = 3
+
+start_agent main:
+   description: "Route"
+   before_reasoning:
+      if @variables.attempts > 2:
+         respond "Let me get someone to help."
+         escalate
+      transition to @topic.help
+
+topic help:
+   description: "Help"
+"#,
+        )
+        .unwrap();
+        let trace = simulate(&ast, &MockData::default());
+        assert_eq!(trace.outcome, SimulationOutcome::Escalated, "{:?}", trace.error);
+        let steps: Vec<_> = trace
+            .steps
+            .iter()
+            .map(|s| (s.statement_type.as_str(), s.detail.as_str()))
+            .collect();
+        assert_eq!(
+            steps[1..],
+            [
+                ("respond", "Let me get someone to help."),
+                ("escalate", "escalate")
+            ]
+        );
+
+        let mock: MockData =
+            serde_json::from_value(json!({ "variables": { "attempts": 0 } })).unwrap();
+        let trace = simulate(&ast, &mock);
+        assert_eq!(trace.outcome, SimulationOutcome::Success);
+        assert_eq!(trace.topic_transitions, ["main", "help"]);
+    }
+
+    #[test]
+    fn test_string_concatenation_and_templates() {
+        let ast = crate::parse(
+            r#"variables:
+   name: mutable string = "Ada"
+   count: mutable number = 2
+   greeting: mutable string = ""
+   summary: mutable string = ""
+
+start_agent main:
+   description: "Main"
+   before_reasoning:
+      set @variables.greeting = "Hello " + @variables.name + "!"
+      set @variables.summary = "{!@variables.name} has {!@variables.count} orders, vip: {!@variables.vip}"
+"#,
+        )
+        .unwrap();
+        let trace = simulate(&ast, &MockData::default());
+        assert_eq!(trace.final_context["greeting"], json!("Hello Ada!"));
+        assert_eq!(trace.final_context["summary"], json!("Ada has 2 orders, vip: None"));
+    }
+
+    #[test]
+    fn test_integer_arithmetic_is_exact() {
+        let big = Expr::Number(Number::Int(9_007_199_254_740_993));
+        let one = Spanned::new(Expr::Number(Number::Int(1)), 0..0);
+        let sum = Expr::BinOp {
+            left: Box::new(Spanned::new(big, 0..0)),
+            op: crate::ast::BinOp::Add,
+            right: Box::new(one.clone()),
+        };
+        let (variables, outputs) = (Map::new(), Map::new());
+        assert_eq!(eval(&sum, &variables, &outputs), json!(9_007_199_254_740_994i64));
+
+        let half = Expr::Number(Number::from(0.5));
+        let mixed = Expr::BinOp {
+            left: Box::new(Spanned::new(half, 0..0)),
+            op: crate::ast::BinOp::Add,
+            right: Box::new(one),
+        };
+        assert_eq!(eval(&mixed, &variables, &outputs), json!(1.5));
+    }
+
+    #[test]
+    fn test_date_and_currency_values() {
+        let source = "variables:\n   renewal: mutable date = date(2024-01-31)\n   price: mutable currency = currency(2, \"USD\")\n";
+        let ast = crate::parse(source).unwrap();
+        let variables = initial_variables(&ast, &MockData::default());
+        assert_eq!(variables["renewal"], json!("2024-01-31"));
+        assert_eq!(variables["price"], json!(2));
+
+        let earlier = Expr::BinOp {
+            left: Box::new(Spanned::new(
+                Expr::Reference(Reference::new("variables", vec!["renewal".to_string()])),
+                0..0,
+            )),
+            op: BinOp::Lt,
+            right: Box::new(Spanned::new(Expr::Date("2024-02-01".parse().unwrap()), 0..0)),
+        };
+        assert_eq!(eval(&earlier, &variables, &Map::new()), json!(true));
+    }
+
+    #[test]
+    fn test_run_scenario() {
+        let scenario: Scenario = serde_json::from_value(json!({
+            "name": "found order",
+            "mockData": {
+                "actions": { "lookup": { "status": "shipped" } },
+                "choices": { "main": "go_orders", "orders": "do_lookup" }
+            },
+            "expect": {
+                "outcome": "success",
+                "topics": ["main", "orders"],
+                "variables": { "status": "shipped", "attempts": 1.0 },
+                "actions": ["lookup"]
+            }
+        }))
+        .unwrap();
+        let result = run_scenario(&ast(), &scenario);
+        assert!(result.passed, "{:?}", result.failures);
+
+        let mut failing = scenario.clone();
+        failing.expect.final_topic = Some("main".to_string());
+        failing
+            .expect
+            .variables
+            .insert("status".to_string(), json!("lost"));
+        let result = run_scenario(&ast(), &failing);
+        assert!(!result.passed);
+        assert_eq!(
+            result.failures,
+            [
+                "expected to end in 'main', ended in 'orders'",
+                "expected @variables.status to be \"lost\", got \"shipped\"",
+            ]
+        );
+    }
+}
@@ -0,0 +1,434 @@
+//! Structural equality of AST nodes, ignoring spans.
+//!
+//! Two parses of the same agent differ in their spans whenever whitespace
+//! or comments moved, so `==` on the ASTs fails. [`SpanlessEq`] compares
+//! everything else: it is `PartialEq` with every [`Spanned::span`] skipped.
+//!
+//! # Example
+//!
+//! ```rust
+//! use busbar_sf_agentscript::parse;
+//! use busbar_sf_agentscript::spanless::SpanlessEq;
+//!
+//! let a = parse("topic main:\n   description: \"Main\"\n").unwrap();
+//! let b = parse("# Main topic\n\ntopic main:\n    description:   \"Main\"\n").unwrap();
+//! assert_ne!(a, b);
+//! assert!(a.eq_ignore_spans(&b));
+//! assert!(a.topics[0].eq_ignore_spans(&b.topics[0]));
+//! ```
+
+use crate::ast::*;
+use indexmap::IndexMap;
+
+/// Equality that ignores source spans.
+pub trait SpanlessEq {
+    /// Whether `self` and `other` are equal apart from their spans.
+    fn eq_ignore_spans(&self, other: &Self) -> bool;
+}
+
+impl<T: SpanlessEq> SpanlessEq for Spanned<T> {
+    fn eq_ignore_spans(&self, other: &Self) -> bool {
+        self.node.eq_ignore_spans(&other.node)
+    }
+}
+
+impl<T: SpanlessEq> SpanlessEq for Option<T> {
+    fn eq_ignore_spans(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Some(a), Some(b)) => a.eq_ignore_spans(b),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+}
+
+impl<T: SpanlessEq> SpanlessEq for Vec<T> {
+    fn eq_ignore_spans(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().zip(other).all(|(a, b)| a.eq_ignore_spans(b))
+    }
+}
+
+impl<T: SpanlessEq> SpanlessEq for Box<T> {
+    fn eq_ignore_spans(&self, other: &Self) -> bool {
+        (**self).eq_ignore_spans(other)
+    }
+}
+
+/// Like `IndexMap`'s `==`, key order doesn't matter.
+impl<T: SpanlessEq> SpanlessEq for IndexMap<String, T> {
+    fn eq_ignore_spans(&self, other: &Self) -> bool {
+        self.len() == other.len()
+            && self
+                .iter()
+                .all(|(key, a)| other.get(key).is_some_and(|b| a.eq_ignore_spans(b)))
+    }
+}
+
+/// Types without spans inside, compared with `==`.
+macro_rules! spanless_by_eq {
+    ($($ty:ty),* $(,)?) => {
+        $(impl SpanlessEq for $ty {
+            fn eq_ignore_spans(&self, other: &Self) -> bool {
+                self == other
+            }
+        })*
+    };
+}
+
+spanless_by_eq!(
+    String,
+    bool,
+    ActionClause,
+    BinOp,
+    Currency,
+    Date,
+    Number,
+    OutputRef,
+    ReasoningActionTarget,
+    Reference,
+    Type,
+    UnaryOp,
+    VariableKind,
+);
+
+/// Structs compared field by field. Every field must be listed, so a new
+/// field can't be skipped by accident.
+macro_rules! spanless_struct {
+    ($($ty:ident { $($field:ident),* $(,)? })*) => {
+        $(impl SpanlessEq for $ty {
+            fn eq_ignore_spans(&self, other: &Self) -> bool {
+                let $ty { $($field),* } = self;
+                $($field.eq_ignore_spans(&other.$field))&&*
+            }
+        })*
+    };
+}
+
+spanless_struct! {
+    AgentFile {
+        config,
+        duplicate_configs,
+        variables,
+        duplicate_variables,
+        system,
+        duplicate_systems,
+        connections,
+        knowledge,
+        language,
+        start_agent,
+        duplicate_start_agents,
+        topics,
+        recovered_blocks,
+    }
+    ConfigBlock { agent_name, agent_label, description, agent_type, default_agent_user, target_environment }
+    VariablesBlock { variables }
+    VariableDecl { name, kind, ty, default, description, source, doc }
+    SystemBlock { messages, instructions }
+    SystemMessages { welcome, error }
+    ConnectionBlock { name, entries }
+    ConnectionEntry { name, value }
+    KnowledgeBlock { entries }
+    KnowledgeEntry { name, value }
+    LanguageBlock { entries }
+    LanguageEntry { name, value }
+    StartAgentBlock {
+        name,
+        description,
+        system,
+        actions,
+        before_reasoning,
+        reasoning,
+        after_reasoning,
+        doc,
+    }
+    TopicBlock {
+        name,
+        quoted_name,
+        description,
+        system,
+        actions,
+        before_reasoning,
+        reasoning,
+        after_reasoning,
+        doc,
+    }
+    RecoveredBlock { kind, name }
+    TopicSystemOverride { instructions }
+    ActionsBlock { actions }
+    ActionDef {
+        name,
+        description,
+        label,
+        require_user_confirmation,
+        include_in_progress_indicator,
+        progress_indicator_message,
+        inputs,
+        outputs,
+        target,
+        doc,
+    }
+    ParamDef {
+        name,
+        ty,
+        description,
+        label,
+        is_required,
+        filter_from_agent,
+        is_displayable,
+        complex_data_type_name,
+    }
+    DirectiveBlock { statements }
+    WithClause { param, value }
+    SetClause { target, source }
+    ReasoningBlock { instructions, actions }
+    ReasoningAction {
+        name,
+        target,
+        description,
+        available_when,
+        with_clauses,
+        set_clauses,
+        run_clauses,
+        if_clauses,
+        transition,
+        clause_order,
+    }
+    RunClause { action, with_clauses, set_clauses }
+    IfClause { condition, transition }
+}
+
+impl SpanlessEq for Stmt {
+    fn eq_ignore_spans(&self, other: &Self) -> bool {
+        match (self, other) {
+            (
+                Stmt::Set { target, value },
+                Stmt::Set {
+                    target: target2,
+                    value: value2,
+                },
+            ) => target.eq_ignore_spans(target2) && value.eq_ignore_spans(value2),
+            (
+                Stmt::Run {
+                    action,
+                    with_clauses,
+                    set_clauses,
+                },
+                Stmt::Run {
+                    action: action2,
+                    with_clauses: with_clauses2,
+                    set_clauses: set_clauses2,
+                },
+            ) => {
+                action.eq_ignore_spans(action2)
+                    && with_clauses.eq_ignore_spans(with_clauses2)
+                    && set_clauses.eq_ignore_spans(set_clauses2)
+            }
+            (
+                Stmt::If {
+                    condition,
+                    then_block,
+                    else_block,
+                },
+                Stmt::If {
+                    condition: condition2,
+                    then_block: then_block2,
+                    else_block: else_block2,
+                },
+            ) => {
+                condition.eq_ignore_spans(condition2)
+                    && then_block.eq_ignore_spans(then_block2)
+                    && else_block.eq_ignore_spans(else_block2)
+            }
+            (Stmt::Transition { target }, Stmt::Transition { target: target2 }) => {
+                target.eq_ignore_spans(target2)
+            }
+            (Stmt::Escalate, Stmt::Escalate) => true,
+            (Stmt::Respond { message }, Stmt::Respond { message: message2 }) => {
+                message.eq_ignore_spans(message2)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl SpanlessEq for WithValue {
+    fn eq_ignore_spans(&self, other: &Self) -> bool {
+        let (WithValue::Expr(a), WithValue::Expr(b)) = (self, other);
+        a.eq_ignore_spans(b)
+    }
+}
+
+impl SpanlessEq for Instructions {
+    fn eq_ignore_spans(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Instructions::Simple(a), Instructions::Simple(b)) => a == b,
+            (Instructions::Static(a), Instructions::Static(b)) => a.eq_ignore_spans(b),
+            (Instructions::Dynamic(a), Instructions::Dynamic(b)) => a.eq_ignore_spans(b),
+            _ => false,
+        }
+    }
+}
+
+impl SpanlessEq for InstructionPart {
+    fn eq_ignore_spans(&self, other: &Self) -> bool {
+        match (self, other) {
+            (InstructionPart::Text(a), InstructionPart::Text(b)) => a == b,
+            (
+                InstructionPart::Heading { level, title },
+                InstructionPart::Heading {
+                    level: level2,
+                    title: title2,
+                },
+            ) => level == level2 && title == title2,
+            (InstructionPart::Interpolation(a), InstructionPart::Interpolation(b)) => {
+                a.eq_ignore_spans(b)
+            }
+            (
+                InstructionPart::Conditional {
+                    condition,
+                    then_parts,
+                    else_parts,
+                },
+                InstructionPart::Conditional {
+                    condition: condition2,
+                    then_parts: then_parts2,
+                    else_parts: else_parts2,
+                },
+            ) => {
+                condition.eq_ignore_spans(condition2)
+                    && then_parts.eq_ignore_spans(then_parts2)
+                    && else_parts.eq_ignore_spans(else_parts2)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl SpanlessEq for Expr {
+    fn eq_ignore_spans(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Expr::Reference(a), Expr::Reference(b)) => a == b,
+            (Expr::String(a), Expr::String(b)) => a == b,
+            (Expr::Number(a), Expr::Number(b)) => a == b,
+            (Expr::Date(a), Expr::Date(b)) => a == b,
+            (Expr::Currency(a), Expr::Currency(b)) => a == b,
+            (Expr::Bool(a), Expr::Bool(b)) => a == b,
+            (Expr::None, Expr::None) | (Expr::SlotFill, Expr::SlotFill) => true,
+            (Expr::Template(a), Expr::Template(b)) | (Expr::List(a), Expr::List(b)) => {
+                a.eq_ignore_spans(b)
+            }
+            (Expr::Object(a), Expr::Object(b)) => a.eq_ignore_spans(b),
+            (
+                Expr::BinOp { left, op, right },
+                Expr::BinOp {
+                    left: left2,
+                    op: op2,
+                    right: right2,
+                },
+            ) => op == op2 && left.eq_ignore_spans(left2) && right.eq_ignore_spans(right2),
+            (
+                Expr::UnaryOp { op, operand },
+                Expr::UnaryOp {
+                    op: op2,
+                    operand: operand2,
+                },
+            ) => op == op2 && operand.eq_ignore_spans(operand2),
+            (
+                Expr::Ternary {
+                    condition,
+                    then_expr,
+                    else_expr,
+                },
+                Expr::Ternary {
+                    condition: condition2,
+                    then_expr: then_expr2,
+                    else_expr: else_expr2,
+                },
+            ) => {
+                condition.eq_ignore_spans(condition2)
+                    && then_expr.eq_ignore_spans(then_expr2)
+                    && else_expr.eq_ignore_spans(else_expr2)
+            }
+            (
+                Expr::Property { object, field },
+                Expr::Property {
+                    object: object2,
+                    field: field2,
+                },
+            ) => object.eq_ignore_spans(object2) && field.eq_ignore_spans(field2),
+            (
+                Expr::Index { object, index },
+                Expr::Index {
+                    object: object2,
+                    index: index2,
+                },
+            ) => object.eq_ignore_spans(object2) && index.eq_ignore_spans(index2),
+            (
+                Expr::Call { callee, args },
+                Expr::Call {
+                    callee: callee2,
+                    args: args2,
+                },
+            ) => callee.eq_ignore_spans(callee2) && args.eq_ignore_spans(args2),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spans_are_ignored() {
+        let a = crate::parse(
+            r#"variables:
+   count: mutable number = 1 + 2 * 3
+topic main:
+   description: "Main"
+   reasoning:
+      instructions: ->
+         | Count: {!@variables.count}
+      actions:
+         go: @actions.go
+            with id = @variables.count
+"#,
+        )
+        .unwrap();
+        let b = crate::parse(
+            r#"variables:
+    count: mutable number = 1 +  2*3
+
+topic main:
+    description: "Main"
+    reasoning:
+        instructions: ->
+            | Count: {!@variables.count}
+        actions:
+            go: @actions.go
+                with id=@variables.count
+"#,
+        )
+        .unwrap();
+        assert_ne!(a, b);
+        assert!(a.eq_ignore_spans(&b));
+
+        let c = crate::parse(&crate::serialize(&a).replace("1 + 2", "(1 + 2)")).unwrap();
+        assert!(!a.eq_ignore_spans(&c));
+    }
+
+    #[test]
+    fn test_object_key_order() {
+        let expr = |s: &str| match crate::parse(s).unwrap().variables.unwrap().node.variables[0]
+            .node
+            .default
+            .clone()
+        {
+            Some(default) => default,
+            None => unreachable!(),
+        };
+        let a = expr("variables:\n   o: mutable object = {a: 1, b: 2}\n");
+        let b = expr("variables:\n   o: mutable object = {b: 2, a: 1}\n");
+        assert!(a.eq_ignore_spans(&b));
+    }
+}
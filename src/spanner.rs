@@ -0,0 +1,288 @@
+//! Span attachment for programmatically built ASTs.
+//!
+//! ASTs built with [`builder`](crate::builder) or produced by importers have
+//! placeholder spans that point at no real text. Once such an AST is written
+//! out with [`serialize`], editor features that rely on spans (hover,
+//! go-to-definition, diagnostics) have nothing to work with.
+//!
+//! [`attach_spans`] serializes the AST, re-parses the output, and copies each
+//! parsed node's span onto the matching node of the original AST, so spans
+//! point into the generated source.
+//!
+//! # Example
+//!
+//! ```rust
+//! use busbar_sf_agentscript::builder::TopicBuilder;
+//! use busbar_sf_agentscript::spanner::attach_spans;
+//! use busbar_sf_agentscript::AgentFile;
+//!
+//! let mut agent = AgentFile::new();
+//! agent
+//!     .add_topic(
+//!         TopicBuilder::new("orders")
+//!             .description("Order questions")
+//!             .instructions("Help with orders"),
+//!     )
+//!     .unwrap();
+//!
+//! let source = attach_spans(&mut agent).unwrap();
+//! let name = &agent.topics[0].node.name;
+//! assert_eq!(&source[name.span.clone()], "orders");
+//! ```
+
+use crate::{parse, serialize, AgentFile};
+use serde_json::Value;
+use std::ops::Range;
+
+/// Replace the spans in `agent` with spans into its serialized source.
+///
+/// Returns the serialized source the new spans refer to. Nodes the serializer
+/// doesn't reproduce exactly keep their old spans. Fails with the parse
+/// errors if the serialized source doesn't parse.
+pub fn attach_spans(agent: &mut AgentFile) -> Result<String, Vec<String>> {
+    let source = serialize(agent);
+    let parsed = parse(&source)?;
+
+    *agent = transfer_spans(agent, &parsed, &mut |_, _, _| {});
+    Ok(source)
+}
+
+/// Called with a node's path, old span and new span.
+pub(crate) type SpanVisitor<'a> = dyn FnMut(&str, Range<usize>, Range<usize>) + 'a;
+
+/// Copy spans from `from` onto a copy of `target` wherever the two ASTs have
+/// the same shape. `visit` is called with the node path, the old span and the
+/// new span of every node whose span was copied.
+pub(crate) fn transfer_spans(
+    target: &AgentFile,
+    from: &AgentFile,
+    visit: &mut SpanVisitor,
+) -> AgentFile {
+    let mut target = serde_json::to_value(target).expect("AST always serializes to JSON");
+    let spans = serde_json::to_value(from).expect("AST always serializes to JSON");
+    copy_spans(&mut target, &spans, "", visit);
+    serde_json::from_value(target).expect("span copy keeps the AST shape")
+}
+
+/// Walk two AST JSON trees in parallel, copying `span` fields from `from`
+/// wherever the structure matches.
+fn copy_spans(target: &mut Value, from: &Value, path: &str, visit: &mut SpanVisitor) {
+    match (target, from) {
+        (Value::Object(target), Value::Object(from)) => {
+            for (key, value) in target.iter_mut() {
+                let Some(source) = from.get(key) else {
+                    continue;
+                };
+                if key == "span" {
+                    if let (Some(old), Some(new)) = (as_span(value), as_span(source)) {
+                        visit(path, old, new);
+                        *value = source.clone();
+                    }
+                } else if path.is_empty() {
+                    copy_spans(value, source, key, visit);
+                } else {
+                    copy_spans(value, source, &format!("{}.{}", path, key), visit);
+                }
+            }
+        }
+        (Value::Array(target), Value::Array(from)) if target.len() == from.len() => {
+            for (i, (value, source)) in target.iter_mut().zip(from).enumerate() {
+                copy_spans(value, source, &format!("{}[{}]", path, i), visit);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn as_span(value: &Value) -> Option<Range<usize>> {
+    let start = value.get("start")?.as_u64()?;
+    let end = value.get("end")?.as_u64()?;
+    Some(start as usize..end as usize)
+}
+
+/// A degenerate span found by [`audit_spans`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpanIssue {
+    /// Path of the node in the AST's JSON form (e.g. `topics[0].node.name`).
+    pub path: String,
+    /// The node's span.
+    pub span: Range<usize>,
+    /// What is wrong with it.
+    pub kind: SpanIssueKind,
+}
+
+/// Kinds of [`SpanIssue`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SpanIssueKind {
+    /// The span covers no text.
+    Empty,
+    /// The span runs past the end of the source.
+    OutOfBounds,
+    /// The span is not inside the span of the enclosing node.
+    OutsideParent(Range<usize>),
+}
+
+/// Check that every `Spanned` node in a parsed AST covers some of `source`
+/// and lies within its enclosing node.
+///
+/// Diagnostics, hover and rename all locate nodes by span, so a parser path
+/// that produces a zero-length or misplaced span shows up here. Only
+/// meaningful for ASTs parsed from `source`; built ASTs have placeholder
+/// spans.
+///
+/// # Example
+///
+/// ```rust
+/// use busbar_sf_agentscript::spanner::audit_spans;
+///
+/// let source = "topic main:\n   description: \"Main\"\n";
+/// let ast = busbar_sf_agentscript::parse(source).unwrap();
+/// assert!(audit_spans(&ast, source).is_empty());
+/// ```
+pub fn audit_spans(agent: &AgentFile, source: &str) -> Vec<SpanIssue> {
+    let json = serde_json::to_value(agent).expect("AST always serializes to JSON");
+    let mut issues = Vec::new();
+    audit(&json, "", None, source.len(), &mut issues);
+    issues
+}
+
+fn audit(
+    value: &Value,
+    path: &str,
+    parent: Option<&Range<usize>>,
+    len: usize,
+    issues: &mut Vec<SpanIssue>,
+) {
+    let join = |key: &str| {
+        if path.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", path, key)
+        }
+    };
+    match value {
+        Value::Object(map) => {
+            let span = map.get("node").and(map.get("span")).and_then(as_span);
+            if let Some(span) = &span {
+                let mut report = |kind| {
+                    issues.push(SpanIssue {
+                        path: path.to_string(),
+                        span: span.clone(),
+                        kind,
+                    })
+                };
+                if span.start >= span.end {
+                    report(SpanIssueKind::Empty);
+                } else if span.end > len {
+                    report(SpanIssueKind::OutOfBounds);
+                } else if let Some(parent) =
+                    parent.filter(|p| span.start < p.start || span.end > p.end)
+                {
+                    report(SpanIssueKind::OutsideParent(parent.clone()));
+                }
+            }
+            let parent = span.as_ref().or(parent);
+            for (key, child) in map {
+                match key.as_str() {
+                    "span" => {}
+                    // Doc comments precede the node they document.
+                    "doc" => audit(child, &join(key), None, len, issues),
+                    _ => audit(child, &join(key), parent, len, issues),
+                }
+            }
+        }
+        Value::Array(items) => {
+            for (i, item) in items.iter().enumerate() {
+                audit(item, &format!("{}[{}]", path, i), parent, len, issues);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{ActionDefBuilder, TopicBuilder, VariableBuilder};
+    use crate::{Expr, Type};
+
+    #[test]
+    fn test_attach_spans_to_built_agent() {
+        let mut agent = AgentFile::new();
+        agent
+            .add_variable(VariableBuilder::mutable(
+                "order_id",
+                Type::String,
+                Expr::String(String::new()),
+            ))
+            .unwrap();
+        agent
+            .add_topic(
+                TopicBuilder::new("orders")
+                    .description("Order questions")
+                    .action(
+                        ActionDefBuilder::new("lookup")
+                            .description("Look up an order")
+                            .input("id", Type::String)
+                            .target("flow://Lookup"),
+                    )
+                    .invoke("do_lookup", "lookup"),
+            )
+            .unwrap();
+
+        let source = attach_spans(&mut agent).unwrap();
+        let var = &agent.variables.as_ref().unwrap().node.variables[0];
+        assert_eq!(&source[var.node.name.span.clone()], "order_id");
+
+        let topic = &agent.topics[0].node;
+        assert_eq!(&source[topic.name.span.clone()], "orders");
+        let action = &topic.actions.as_ref().unwrap().node.actions[0];
+        assert_eq!(&source[action.node.name.span.clone()], "lookup");
+
+        // Already attached: a second pass is a no-op
+        let before = agent.clone();
+        assert_eq!(attach_spans(&mut agent).unwrap(), source);
+        assert_eq!(agent, before);
+    }
+
+    #[test]
+    fn test_audit_spans() {
+        let source = include_str!("../examples/ComprehensiveDemo.agent");
+        let mut agent = crate::parse(source).unwrap();
+        assert_eq!(audit_spans(&agent, source), Vec::new());
+
+        agent.topics[0].node.name.span = 0..0;
+        let issues = audit_spans(&agent, source);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].path, "topics[0].node.name");
+        assert_eq!(issues[0].kind, SpanIssueKind::Empty);
+    }
+
+    #[test]
+    fn test_clause_child_spans_are_precise() {
+        let source = r#"topic main:
+   description: "Main"
+   reasoning:
+      instructions: "Help"
+      actions:
+         go: @actions.lookup
+            with id = @variables.order_id
+            set @variables.status = @outputs.status
+            run @actions.notify
+"#;
+        let agent = crate::parse(source).unwrap();
+        assert_eq!(audit_spans(&agent, source), Vec::new());
+
+        let reasoning = &agent.topics[0].node.reasoning.as_ref().unwrap().node;
+        let actions = reasoning.actions.as_ref().unwrap();
+        assert!(source[actions.span.clone()].starts_with("actions:"));
+        let action = &actions.node[0].node;
+        let with = &action.with_clauses[0].node;
+        assert_eq!(&source[with.param.span.clone()], "id");
+        assert_eq!(&source[with.value.span.clone()], "@variables.order_id");
+        let set = &action.set_clauses[0].node;
+        assert_eq!(&source[set.target.span.clone()], "@variables.status");
+        let run = &action.run_clauses[0].node;
+        assert_eq!(&source[run.action.span.clone()], "@actions.notify");
+    }
+}
@@ -0,0 +1,635 @@
+//! Plain-English agent summaries.
+//!
+//! [`summarize`] describes an agent from its AST alone: what it is for, what
+//! each topic does, which variables carry state, which external systems it
+//! calls, and when it hands off to a human. The wording comes from fixed
+//! templates, so the same agent always gets the same summary, suitable for
+//! PR descriptions and editor context.
+//!
+//! # Example
+//!
+//! ```rust
+//! use busbar_sf_agentscript::{parse, summary::summarize};
+//!
+//! let source = r#"
+//! config:
+//!    agent_name: "Support"
+//!    description: "Helps customers with orders"
+//!
+//! start_agent selector:
+//!    description: "Route"
+//!    reasoning:
+//!       instructions: "Route"
+//!       actions:
+//!          go: @utils.transition to @topic.orders
+//!
+//! topic orders:
+//!    description: "Order questions"
+//!    actions:
+//!       lookup:
+//!          description: "Look up an order"
+//!          target: "flow://Lookup"
+//!    reasoning:
+//!       instructions: "Help"
+//!       actions:
+//!          do_lookup: @actions.lookup
+//! "#;
+//!
+//! let summary = summarize(&parse(source).unwrap());
+//! assert_eq!(summary.purpose, "Helps customers with orders");
+//! assert_eq!(summary.external_systems[0].name, "Lookup");
+//!
+//! let text = summary.to_string();
+//! assert!(text.starts_with("Support: Helps customers with orders"));
+//! assert!(text.contains("- orders (worker): Order questions. Uses lookup."));
+//! ```
+
+use crate::ast::{
+    ActionsBlock, AgentFile, DirectiveBlock, ReasoningActionTarget, ReasoningBlock, Reference,
+    SetClause, Spanned, Stmt, VariableKind,
+};
+use crate::typecheck::ExprType;
+use serde::Serialize;
+use std::fmt;
+
+/// A deterministic summary of an agent, from [`summarize`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AgentSummary {
+    /// The agent's label, or its name.
+    pub name: String,
+    /// The agent's description, or a sentence counting its topics.
+    pub purpose: String,
+    /// start_agent followed by each topic, in source order.
+    pub topics: Vec<TopicSummary>,
+    /// Linked variables and mutable variables that something sets.
+    pub key_variables: Vec<VariableSummary>,
+    /// Distinct action targets, in source order.
+    pub external_systems: Vec<ExternalSystem>,
+    /// One sentence on when and how the agent escalates to a human.
+    pub escalation: String,
+}
+
+/// What a topic mainly does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TopicRole {
+    /// The start_agent block, where every conversation begins
+    Entry,
+    /// Escalates to a human
+    Escalation,
+    /// Invokes actions
+    Worker,
+    /// Only hands off to other topics
+    Router,
+    /// Answers from its instructions alone
+    Informational,
+}
+
+impl TopicRole {
+    /// Lowercase name used in the text summary.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TopicRole::Entry => "entry",
+            TopicRole::Escalation => "escalation",
+            TopicRole::Worker => "worker",
+            TopicRole::Router => "router",
+            TopicRole::Informational => "informational",
+        }
+    }
+}
+
+/// Summary of start_agent or a topic.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TopicSummary {
+    /// Topic name; for start_agent, the name after `start_agent`.
+    pub name: String,
+    pub role: TopicRole,
+    pub description: Option<String>,
+    /// Actions invoked, by definition name.
+    pub actions: Vec<String>,
+    /// Topics transitioned or delegated to.
+    pub transitions_to: Vec<String>,
+}
+
+/// Summary of a key variable.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct VariableSummary {
+    pub name: String,
+    /// `mutable` or `linked`.
+    pub kind: String,
+    /// Type as written, such as `string` or `list[number]`.
+    pub ty: String,
+    pub description: Option<String>,
+    /// For linked variables, the reference they are linked to.
+    pub source: Option<String>,
+    /// Topics that set the variable.
+    pub set_by: Vec<String>,
+}
+
+/// An external system an action calls.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ExternalSystem {
+    /// Target scheme, such as `flow` or `apex`; `unknown` if there is none.
+    pub kind: String,
+    /// Target without the scheme.
+    pub name: String,
+    /// Topics defining an action with this target.
+    pub used_by: Vec<String>,
+}
+
+/// Summarize an agent.
+pub fn summarize(ast: &AgentFile) -> AgentSummary {
+    let config = ast.config.as_ref().map(|c| &c.node);
+    let name = config
+        .and_then(|c| c.agent_label.as_ref())
+        .or(config.map(|c| &c.agent_name))
+        .map_or_else(|| "Agent".to_string(), |n| n.node.clone());
+    let purpose = match config.and_then(|c| c.description.as_ref()) {
+        Some(description) => description.node.clone(),
+        None => format!("An agent with {} topic{}.", ast.topics.len(), plural(ast.topics.len())),
+    };
+
+    let mut blocks = Vec::new();
+    if let Some(start) = &ast.start_agent {
+        let start = &start.node;
+        blocks.push(Block {
+            name: &start.name.node,
+            description: start.description.as_ref(),
+            actions: start.actions.as_ref(),
+            directives: [
+                start.before_reasoning.as_ref(),
+                start.after_reasoning.as_ref(),
+            ],
+            reasoning: start.reasoning.as_ref(),
+            is_entry: true,
+        });
+    }
+    for topic in &ast.topics {
+        let topic = &topic.node;
+        blocks.push(Block {
+            name: &topic.name.node,
+            description: topic.description.as_ref(),
+            actions: topic.actions.as_ref(),
+            directives: [
+                topic.before_reasoning.as_ref(),
+                topic.after_reasoning.as_ref(),
+            ],
+            reasoning: topic.reasoning.as_ref(),
+            is_entry: false,
+        });
+    }
+
+    let mut topics = Vec::new();
+    let mut writes: Vec<(String, String)> = Vec::new();
+    let mut escalating = Vec::new();
+    let mut external_systems: Vec<ExternalSystem> = Vec::new();
+    for block in &blocks {
+        let scan = block.scan();
+        for variable in scan.writes {
+            push_unique(&mut writes, (variable, block.name.clone()));
+        }
+        if scan.escalates {
+            escalating.push(block.name.clone());
+        }
+        for action in block.actions.iter().flat_map(|a| &a.node.actions) {
+            let Some(target) = &action.node.target else {
+                continue;
+            };
+            let (kind, name) = target
+                .node
+                .split_once("://")
+                .unwrap_or(("unknown", &target.node));
+            match external_systems
+                .iter_mut()
+                .find(|s| s.kind == kind && s.name == name)
+            {
+                Some(system) => push_unique(&mut system.used_by, block.name.clone()),
+                None => external_systems.push(ExternalSystem {
+                    kind: kind.to_string(),
+                    name: name.to_string(),
+                    used_by: vec![block.name.clone()],
+                }),
+            }
+        }
+
+        let role = if block.is_entry {
+            TopicRole::Entry
+        } else if scan.escalates {
+            TopicRole::Escalation
+        } else if !scan.actions.is_empty() {
+            TopicRole::Worker
+        } else if !scan.transitions.is_empty() {
+            TopicRole::Router
+        } else {
+            TopicRole::Informational
+        };
+        topics.push(TopicSummary {
+            name: block.name.clone(),
+            role,
+            description: block.description.map(|d| d.node.clone()),
+            actions: scan.actions,
+            transitions_to: scan.transitions,
+        });
+    }
+
+    let key_variables = ast
+        .variables
+        .iter()
+        .flat_map(|v| &v.node.variables)
+        .filter_map(|var| {
+            let var = &var.node;
+            let set_by: Vec<String> = writes
+                .iter()
+                .filter(|(variable, _)| *variable == var.name.node)
+                .map(|(_, topic)| topic.clone())
+                .collect();
+            let kind = match var.kind {
+                VariableKind::Mutable if set_by.is_empty() => return None,
+                VariableKind::Mutable => "mutable",
+                VariableKind::Linked => "linked",
+            };
+            Some(VariableSummary {
+                name: var.name.node.clone(),
+                kind: kind.to_string(),
+                ty: ExprType::from(&var.ty.node).to_string(),
+                description: var.description.as_ref().map(|d| d.node.clone()),
+                source: var.source.as_ref().map(|s| s.node.full_path()),
+                set_by,
+            })
+        })
+        .collect();
+
+    let connections: Vec<&str> = ast
+        .connections
+        .iter()
+        .map(|c| c.node.name.node.as_str())
+        .collect();
+    let escalation = if escalating.is_empty() {
+        "The agent never escalates to a human.".to_string()
+    } else {
+        let mut sentence = format!("Escalates to a human from {}", join(&escalating));
+        if !connections.is_empty() {
+            sentence.push_str(&format!(
+                " through the {} connection{}",
+                join(&connections),
+                plural(connections.len())
+            ));
+        }
+        sentence.push('.');
+        sentence
+    };
+
+    AgentSummary {
+        name,
+        purpose,
+        topics,
+        key_variables,
+        external_systems,
+        escalation,
+    }
+}
+
+impl AgentFile {
+    /// The [`summarize`] text of this agent, for notebooks and REPLs where
+    /// the `Debug` output of the whole AST is too long to read.
+    pub fn display_summary(&self) -> String {
+        summarize(self).to_string()
+    }
+
+    /// Rich display hook for [evcxr](https://github.com/evcxr/evcxr)
+    /// notebooks, which call it instead of printing the `Debug` output.
+    pub fn evcxr_display(&self) {
+        println!(
+            "EVCXR_BEGIN_CONTENT text/plain\n{}\nEVCXR_END_CONTENT",
+            self.display_summary().trim_end()
+        );
+    }
+}
+
+impl fmt::Display for AgentSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}: {}", self.name, self.purpose)?;
+
+        if !self.topics.is_empty() {
+            writeln!(f, "\nTopics:")?;
+            for topic in &self.topics {
+                write!(f, "- {} ({})", topic.name, topic.role.as_str())?;
+                if let Some(description) = &topic.description {
+                    write!(f, ": {}", sentence(description))?;
+                } else {
+                    write!(f, ":")?;
+                }
+                if !topic.actions.is_empty() {
+                    write!(f, " Uses {}.", join(&topic.actions))?;
+                }
+                if !topic.transitions_to.is_empty() {
+                    let verb = if topic.role == TopicRole::Entry {
+                        "Routes to"
+                    } else {
+                        "Hands off to"
+                    };
+                    write!(f, " {} {}.", verb, join(&topic.transitions_to))?;
+                }
+                writeln!(f)?;
+            }
+        }
+
+        if !self.key_variables.is_empty() {
+            writeln!(f, "\nKey variables:")?;
+            for var in &self.key_variables {
+                write!(f, "- {} ({} {})", var.name, var.kind, var.ty)?;
+                if let Some(description) = &var.description {
+                    write!(f, ": {}", sentence(description))?;
+                }
+                if let Some(source) = &var.source {
+                    write!(f, " Linked to {}.", source)?;
+                }
+                if !var.set_by.is_empty() {
+                    write!(f, " Set in {}.", join(&var.set_by))?;
+                }
+                writeln!(f)?;
+            }
+        }
+
+        if !self.external_systems.is_empty() {
+            writeln!(f, "\nExternal systems:")?;
+            for system in &self.external_systems {
+                writeln!(
+                    f,
+                    "- {} {} (used by {})",
+                    system.kind,
+                    system.name,
+                    join(&system.used_by)
+                )?;
+            }
+        }
+
+        writeln!(f, "\nEscalation: {}", self.escalation)
+    }
+}
+
+/// The parts of start_agent and topic blocks a summary looks at.
+struct Block<'a> {
+    name: &'a String,
+    description: Option<&'a Spanned<String>>,
+    actions: Option<&'a Spanned<ActionsBlock>>,
+    directives: [Option<&'a Spanned<DirectiveBlock>>; 2],
+    reasoning: Option<&'a Spanned<ReasoningBlock>>,
+    is_entry: bool,
+}
+
+/// What a block does, in source order without duplicates.
+#[derive(Default)]
+struct Scan {
+    actions: Vec<String>,
+    transitions: Vec<String>,
+    writes: Vec<String>,
+    escalates: bool,
+}
+
+impl Block<'_> {
+    fn scan(&self) -> Scan {
+        let mut scan = Scan::default();
+        for directives in self.directives.into_iter().flatten() {
+            scan.stmts(&directives.node.statements);
+        }
+        for action in self
+            .reasoning
+            .iter()
+            .flat_map(|r| r.node.actions.iter().flat_map(|a| &a.node))
+        {
+            let action = &action.node;
+            match &action.target.node {
+                ReasoningActionTarget::Action(reference) => scan.action(reference),
+                ReasoningActionTarget::TransitionTo(reference)
+                | ReasoningActionTarget::TopicDelegate(reference) => scan.transition(reference),
+                ReasoningActionTarget::Escalate => scan.escalates = true,
+                ReasoningActionTarget::SetVariables => {}
+            }
+            scan.set_clauses(&action.set_clauses);
+            for run in &action.run_clauses {
+                scan.action(&run.node.action.node);
+                scan.set_clauses(&run.node.set_clauses);
+            }
+            for clause in &action.if_clauses {
+                if let Some(transition) = &clause.node.transition {
+                    scan.transition(&transition.node);
+                }
+            }
+            if let Some(transition) = &action.transition {
+                scan.transition(&transition.node);
+            }
+        }
+        scan
+    }
+}
+
+impl Scan {
+    fn action(&mut self, reference: &Reference) {
+        if let ("actions", Some(name)) = (reference.namespace.as_str(), reference.path.first()) {
+            push_unique(&mut self.actions, name.clone());
+        }
+    }
+
+    fn transition(&mut self, reference: &Reference) {
+        if let ("topic", Some(name)) = (reference.namespace.as_str(), reference.path.first()) {
+            push_unique(&mut self.transitions, name.clone());
+        }
+    }
+
+    fn write(&mut self, reference: &Reference) {
+        if let ("variables", Some(name)) = (reference.namespace.as_str(), reference.path.first()) {
+            push_unique(&mut self.writes, name.clone());
+        }
+    }
+
+    fn set_clauses(&mut self, clauses: &[Spanned<SetClause>]) {
+        for clause in clauses {
+            self.write(&clause.node.target.node);
+        }
+    }
+
+    fn stmts(&mut self, stmts: &[Spanned<Stmt>]) {
+        for stmt in stmts {
+            match &stmt.node {
+                Stmt::Set { target, .. } => self.write(&target.node),
+                Stmt::Run {
+                    action,
+                    set_clauses,
+                    ..
+                } => {
+                    self.action(&action.node);
+                    self.set_clauses(set_clauses);
+                }
+                Stmt::If {
+                    then_block,
+                    else_block,
+                    ..
+                } => {
+                    self.stmts(then_block);
+                    if let Some(else_block) = else_block {
+                        self.stmts(else_block);
+                    }
+                }
+                Stmt::Transition { target } => self.transition(&target.node),
+                Stmt::Escalate => self.escalates = true,
+                Stmt::Respond { .. } => {}
+            }
+        }
+    }
+}
+
+fn push_unique<T: PartialEq>(items: &mut Vec<T>, item: T) {
+    if !items.contains(&item) {
+        items.push(item);
+    }
+}
+
+fn plural(count: usize) -> &'static str {
+    if count == 1 {
+        ""
+    } else {
+        "s"
+    }
+}
+
+/// Join names as English: `a`, `a and b`, `a, b and c`.
+fn join<S: AsRef<str>>(names: &[S]) -> String {
+    match names {
+        [] => String::new(),
+        [only] => only.as_ref().to_string(),
+        [rest @ .., last] => format!(
+            "{} and {}",
+            rest.iter()
+                .map(AsRef::as_ref)
+                .collect::<Vec<_>>()
+                .join(", "),
+            last.as_ref()
+        ),
+    }
+}
+
+/// `text` ending with a full stop.
+fn sentence(text: &str) -> String {
+    let text = text.trim();
+    if text.ends_with(['.', '!', '?']) {
+        text.to_string()
+    } else {
+        format!("{}.", text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summarize() {
+        let source = r#"config:
+   agent_name: "support_agent"
+   agent_label: "Support"
+
+variables:
+   customer_id: linked string
+      source: @session.customer_id
+      description: "Signed-in customer"
+   order_id: mutable string = ""
+   unused: mutable string = ""
+
+connection messaging:
+   escalation_message: "Connecting you to an agent"
+
+start_agent selector:
+   description: "Route customers"
+   reasoning:
+      instructions: "Route"
+      actions:
+         go_orders: @utils.transition to @topic.orders
+         go_help: @utils.transition to @topic.help
+
+topic orders:
+   description: "Order questions"
+   actions:
+      lookup:
+         description: "Look up an order"
+         target: "flow://Lookup"
+      refund:
+         description: "Refund an order"
+         target: "apex://Refunds"
+   reasoning:
+      instructions: "Help"
+      actions:
+         do_lookup: @actions.lookup
+            set @variables.order_id = @outputs.id
+         do_refund: @actions.refund
+         to_help: @utils.transition to @topic.help
+
+topic help:
+   description: "General help"
+   reasoning:
+      instructions: "Help"
+      actions:
+         human: @utils.escalate
+
+topic faq:
+   description: "FAQ"
+   reasoning:
+      instructions: "Answer"
+"#;
+        let ast = crate::parse(source).unwrap();
+        let summary = summarize(&ast);
+        assert_eq!(summary.name, "Support");
+        assert_eq!(summary.purpose, "An agent with 3 topics.");
+
+        let roles: Vec<_> = summary
+            .topics
+            .iter()
+            .map(|t| (t.name.as_str(), t.role))
+            .collect();
+        assert_eq!(
+            roles,
+            [
+                ("selector", TopicRole::Entry),
+                ("orders", TopicRole::Worker),
+                ("help", TopicRole::Escalation),
+                ("faq", TopicRole::Informational),
+            ]
+        );
+
+        let variables: Vec<_> = summary
+            .key_variables
+            .iter()
+            .map(|v| v.name.as_str())
+            .collect();
+        assert_eq!(variables, ["customer_id", "order_id"]);
+        assert_eq!(summary.key_variables[1].set_by, ["orders"]);
+
+        assert_eq!(
+            summary.escalation,
+            "Escalates to a human from help through the messaging connection."
+        );
+
+        assert_eq!(
+            summary.to_string(),
+            "Support: An agent with 3 topics.
+
+Topics:
+- selector (entry): Route customers. Routes to orders and help.
+- orders (worker): Order questions. Uses lookup and refund. Hands off to help.
+- help (escalation): General help.
+- faq (informational): FAQ.
+
+Key variables:
+- customer_id (linked string): Signed-in customer. Linked to @session.customer_id.
+- order_id (mutable string) Set in orders.
+
+External systems:
+- flow Lookup (used by orders)
+- apex Refunds (used by orders)
+
+Escalation: Escalates to a human from help through the messaging connection.
+"
+        );
+        assert_eq!(ast.display_summary(), summary.to_string());
+    }
+}
@@ -0,0 +1,181 @@
+//! Action target URIs.
+//!
+//! An action's `target:` names what it invokes as `scheme://name`, such as
+//! `flow://CreateCustomerAccount` or `apex://OrderService.createOrder`.
+//! [`TargetUri`] is the parsed form, available from
+//! [`ActionDef::target_uri`](crate::ast::ActionDef::target_uri).
+//!
+//! # Example
+//!
+//! ```rust
+//! use busbar_sf_agentscript::target_uri::{TargetScheme, TargetUri, TargetUriError};
+//!
+//! let uri: TargetUri = "flow://CreateCustomerAccount".parse().unwrap();
+//! assert_eq!(uri.scheme, TargetScheme::Flow);
+//! assert_eq!(uri.name, "CreateCustomerAccount");
+//! assert_eq!(uri.to_string(), "flow://CreateCustomerAccount");
+//!
+//! assert_eq!(
+//!     "apex://".parse::<TargetUri>(),
+//!     Err(TargetUriError::EmptyName(TargetScheme::Apex))
+//! );
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+/// The kind of thing an action target invokes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TargetScheme {
+    /// `flow://` - an autolaunched Flow
+    Flow,
+    /// `apex://` - an invocable Apex class, optionally `Class.method`
+    Apex,
+    /// `prompt://` - a prompt template
+    Prompt,
+    /// `connectapi://` - a Connect API resource
+    ConnectApi,
+    /// `standard://` - a standard invocable action
+    Standard,
+    /// `service://` - an external service operation
+    Service,
+    /// `create://` - create a record of an object
+    Create,
+    /// `read://` - read a record or `Object.Field`
+    Read,
+    /// `update://` - update a record of an object
+    Update,
+    /// `delete://` - delete a record of an object
+    Delete,
+    /// `query://` - query records of an object
+    Query,
+}
+
+impl TargetScheme {
+    /// Every scheme, in declaration order.
+    pub const ALL: [TargetScheme; 11] = [
+        TargetScheme::Flow,
+        TargetScheme::Apex,
+        TargetScheme::Prompt,
+        TargetScheme::ConnectApi,
+        TargetScheme::Standard,
+        TargetScheme::Service,
+        TargetScheme::Create,
+        TargetScheme::Read,
+        TargetScheme::Update,
+        TargetScheme::Delete,
+        TargetScheme::Query,
+    ];
+
+    /// The scheme as written before `://`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TargetScheme::Flow => "flow",
+            TargetScheme::Apex => "apex",
+            TargetScheme::Prompt => "prompt",
+            TargetScheme::ConnectApi => "connectapi",
+            TargetScheme::Standard => "standard",
+            TargetScheme::Service => "service",
+            TargetScheme::Create => "create",
+            TargetScheme::Read => "read",
+            TargetScheme::Update => "update",
+            TargetScheme::Delete => "delete",
+            TargetScheme::Query => "query",
+        }
+    }
+}
+
+impl fmt::Display for TargetScheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Why a target string is not a valid [`TargetUri`].
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum TargetUriError {
+    /// No `scheme://` prefix
+    #[error("target '{0}' has no scheme, expected e.g. 'flow://{0}'")]
+    MissingScheme(String),
+
+    /// A scheme that is not one of [`TargetScheme::ALL`]
+    #[error("unknown target scheme '{0}://'")]
+    UnknownScheme(String),
+
+    /// Nothing after `scheme://`
+    #[error("target '{0}://' has no name")]
+    EmptyName(TargetScheme),
+}
+
+/// A parsed action target: `scheme://name`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct TargetUri {
+    pub scheme: TargetScheme,
+    /// Everything after `://`, e.g. `OrderService.createOrder`.
+    pub name: String,
+}
+
+impl FromStr for TargetUri {
+    type Err = TargetUriError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((scheme, name)) = s.split_once("://") else {
+            return Err(TargetUriError::MissingScheme(s.to_string()));
+        };
+        let scheme = TargetScheme::ALL
+            .into_iter()
+            .find(|known| known.as_str() == scheme)
+            .ok_or_else(|| TargetUriError::UnknownScheme(scheme.to_string()))?;
+        if name.trim().is_empty() {
+            return Err(TargetUriError::EmptyName(scheme));
+        }
+        Ok(TargetUri {
+            scheme,
+            name: name.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for TargetUri {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}://{}", self.scheme, self.name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_target_uri() {
+        for scheme in TargetScheme::ALL {
+            let text = format!("{}://Name", scheme);
+            let uri: TargetUri = text.parse().unwrap();
+            assert_eq!(uri.scheme, scheme);
+            assert_eq!(uri.to_string(), text);
+        }
+
+        let uri: TargetUri = "apex://OrderService.createOrder".parse().unwrap();
+        assert_eq!(uri.name, "OrderService.createOrder");
+
+        assert_eq!(
+            "Lookup".parse::<TargetUri>(),
+            Err(TargetUriError::MissingScheme("Lookup".to_string()))
+        );
+        assert_eq!(
+            "flwo://Lookup".parse::<TargetUri>(),
+            Err(TargetUriError::UnknownScheme("flwo".to_string()))
+        );
+        assert_eq!(
+            "prompt://  ".parse::<TargetUri>(),
+            Err(TargetUriError::EmptyName(TargetScheme::Prompt))
+        );
+        assert_eq!(
+            TargetUriError::EmptyName(TargetScheme::Flow).to_string(),
+            "target 'flow://' has no name"
+        );
+    }
+}
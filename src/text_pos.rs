@@ -0,0 +1,220 @@
+//! Conversions between byte offsets and line/column locations.
+//!
+//! Spans in the AST are byte offsets into the UTF-8 source. Editors and
+//! reports want zero-based lines and columns instead, and disagree on the
+//! unit a column is counted in: LSP defaults to UTF-16 code units,
+//! JavaScript strings are UTF-16, and terminals usually want characters.
+//! [`LineIndex`] answers all of these from one precomputed table of line
+//! starts.
+//!
+//! # Example
+//!
+//! ```rust
+//! use busbar_sf_agentscript::text_pos::{Encoding, LineIndex, Location};
+//!
+//! let source = "topic main:\n   description: \"🚀 Launch\"\n";
+//! let index = LineIndex::new(source);
+//!
+//! let offset = source.find("Launch").unwrap();
+//! assert_eq!(index.location(offset, Encoding::Utf8), Location::new(1, 22));
+//! assert_eq!(index.location(offset, Encoding::Utf16), Location::new(1, 20));
+//! assert_eq!(index.location(offset, Encoding::Utf32), Location::new(1, 19));
+//!
+//! assert_eq!(index.offset(Location::new(1, 20), Encoding::Utf16), offset);
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+/// The unit columns are counted in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Encoding {
+    /// UTF-8 bytes.
+    Utf8,
+    /// UTF-16 code units, as used by LSP and JavaScript.
+    #[default]
+    Utf16,
+    /// Unicode scalar values (`char`s).
+    Utf32,
+}
+
+impl Encoding {
+    fn width(self, c: char) -> usize {
+        match self {
+            Encoding::Utf8 => c.len_utf8(),
+            Encoding::Utf16 => c.len_utf16(),
+            Encoding::Utf32 => 1,
+        }
+    }
+}
+
+/// A zero-based line and column.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize,
+)]
+pub struct Location {
+    /// Zero-based line number.
+    pub line: u32,
+    /// Zero-based column, in the [`Encoding`] it was computed with.
+    pub column: u32,
+}
+
+impl Location {
+    /// Create a location.
+    pub fn new(line: u32, column: u32) -> Self {
+        Self { line, column }
+    }
+}
+
+/// Line start offsets of a source text, for offset ↔ location conversion.
+///
+/// Building the index is linear in the source; each conversion afterwards
+/// is a binary search plus a walk over one line.
+#[derive(Debug, Clone)]
+pub struct LineIndex<'src> {
+    source: &'src str,
+    line_starts: Vec<usize>,
+}
+
+impl<'src> LineIndex<'src> {
+    /// Index the lines of `source`.
+    pub fn new(source: &'src str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self {
+            source,
+            line_starts,
+        }
+    }
+
+    /// The indexed source.
+    pub fn source(&self) -> &'src str {
+        self.source
+    }
+
+    /// Number of lines. A trailing newline starts an (empty) last line.
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// Byte range of a line, excluding its line terminator.
+    pub fn line_range(&self, line: u32) -> Option<Range<usize>> {
+        let range = self.line_bounds(line)?;
+        let text = &self.source[range.clone()];
+        Some(range.start..range.start + text.trim_end_matches('\r').len())
+    }
+
+    /// Byte range of a line up to, but excluding, its `\n`.
+    fn line_bounds(&self, line: u32) -> Option<Range<usize>> {
+        let start = *self.line_starts.get(line as usize)?;
+        let end = self
+            .line_starts
+            .get(line as usize + 1)
+            .map_or(self.source.len(), |next| next - 1);
+        Some(start..end)
+    }
+
+    /// Location of a byte offset.
+    ///
+    /// Offsets past the end of the source are clamped to it, and offsets
+    /// inside a multi-byte character are moved back to its start.
+    pub fn location(&self, offset: usize, encoding: Encoding) -> Location {
+        let mut offset = offset.min(self.source.len());
+        while !self.source.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        let start = self.line_starts[line];
+        let column: usize = self.source[start..offset]
+            .chars()
+            .map(|c| encoding.width(c))
+            .sum();
+        Location::new(line as u32, column as u32)
+    }
+
+    /// Start and end locations of a span.
+    pub fn span_locations(&self, span: &Range<usize>, encoding: Encoding) -> Range<Location> {
+        self.location(span.start, encoding)..self.location(span.end, encoding)
+    }
+
+    /// Byte offset of a location.
+    ///
+    /// Lines past the end map to the end of the source, and columns past
+    /// the end of a line (or inside a multi-unit character) to the end of
+    /// that line (or the start of that character).
+    pub fn offset(&self, location: Location, encoding: Encoding) -> usize {
+        let Some(range) = self.line_bounds(location.line) else {
+            return self.source.len();
+        };
+        let mut remaining = location.column as usize;
+        for (i, c) in self.source[range.clone()].char_indices() {
+            let width = encoding.width(c);
+            if remaining < width {
+                return range.start + i;
+            }
+            remaining -= width;
+        }
+        range.end
+    }
+
+    /// Byte span between two locations.
+    pub fn span(&self, locations: Range<Location>, encoding: Encoding) -> Range<usize> {
+        self.offset(locations.start, encoding)..self.offset(locations.end, encoding)
+    }
+}
+
+/// Location of a byte offset in `source`, without keeping an index around.
+///
+/// Prefer [`LineIndex`] when converting more than one offset.
+pub fn location(source: &str, offset: usize, encoding: Encoding) -> Location {
+    LineIndex::new(source).location(offset, encoding)
+}
+
+/// Byte offset of a location in `source`, without keeping an index around.
+///
+/// Prefer [`LineIndex`] when converting more than one location.
+pub fn offset(source: &str, location: Location, encoding: Encoding) -> usize {
+    LineIndex::new(source).offset(location, encoding)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_every_boundary() {
+        let source = "a: \"é\"\r\n\n  b: \"𝄞x\"\nlast";
+        let index = LineIndex::new(source);
+        assert_eq!(index.line_count(), 4);
+        assert_eq!(&source[index.line_range(0).unwrap()], "a: \"é\"");
+        assert_eq!(index.line_range(1), Some(9..9));
+        assert_eq!(index.line_range(4), None);
+
+        for encoding in [Encoding::Utf8, Encoding::Utf16, Encoding::Utf32] {
+            for (offset, _) in source.char_indices() {
+                let location = index.location(offset, encoding);
+                assert_eq!(index.offset(location, encoding), offset, "{encoding:?} {offset}");
+            }
+        }
+
+        let clef = source.find('𝄞').unwrap();
+        assert_eq!(index.location(clef + 4, Encoding::Utf16), Location::new(2, 8));
+        assert_eq!(index.location(clef + 2, Encoding::Utf16), Location::new(2, 6));
+        assert_eq!(index.offset(Location::new(2, 7), Encoding::Utf16), clef);
+    }
+
+    #[test]
+    fn test_clamps_out_of_range() {
+        let source = "ab\ncd\n";
+        let index = LineIndex::new(source);
+        assert_eq!(index.location(100, Encoding::Utf16), Location::new(2, 0));
+        assert_eq!(index.offset(Location::new(0, 9), Encoding::Utf16), 2);
+        assert_eq!(index.offset(Location::new(9, 0), Encoding::Utf16), source.len());
+        assert_eq!(
+            index.span_locations(&(1..4), Encoding::Utf8),
+            Location::new(0, 1)..Location::new(1, 1)
+        );
+        assert_eq!(index.span(Location::new(0, 1)..Location::new(1, 1), Encoding::Utf8), 1..4);
+    }
+}
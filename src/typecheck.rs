@@ -0,0 +1,1013 @@
+//! Type inference and type checking for expressions.
+//!
+//! This module infers an [`ExprType`] for expressions using the declared
+//! types of variables and the output parameters of action definitions, and
+//! reports mismatches such as:
+//!
+//! - comparing a `boolean` variable to a string literal
+//! - binding a `number` action output to a `string` variable via `set`
+//! - passing a value of the wrong type to an action input via `with`
+//! - declaring a variable default that does not match its type
+//! - passing a non-string to a string function such as `lower(...)`
+//!
+//! Calls to unknown functions, or with the wrong number of arguments, are
+//! reported separately by [`check_calls`].
+//!
+//! Inference is deliberately lenient: anything that cannot be resolved
+//! (slot fills, context references, unknown outputs) is typed as
+//! [`ExprType::Unknown`] and never produces a diagnostic.
+//!
+//! # Example
+//!
+//! ```rust
+//! use busbar_sf_agentscript::{parse, typecheck::check_types};
+//!
+//! let source = r#"
+//! variables:
+//!    verified: mutable boolean = False
+//!
+//! topic main:
+//!    description: "Main"
+//!    reasoning:
+//!       instructions: "Help"
+//!       actions:
+//!          go: @utils.transition to @topic.main
+//!             available when @variables.verified == "yes"
+//! "#;
+//!
+//! let ast = parse(source).unwrap();
+//! let mismatches = check_types(&ast);
+//! assert_eq!(mismatches.len(), 1);
+//! ```
+
+use crate::ast::{
+    ActionDef, ActionsBlock, AgentFile, BinOp, BuiltinFunction, DirectiveBlock, Expr,
+    InstructionPart, Instructions, ReasoningActionTarget, ReasoningBlock, Reference, SetClause,
+    Spanned, Stmt, Type, UnaryOp, WithClause, WithValue, BUILTIN_FUNCTIONS,
+};
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+
+/// The inferred type of an expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExprType {
+    String,
+    Number,
+    Boolean,
+    Object,
+    Date,
+    Timestamp,
+    Currency,
+    Id,
+    Datetime,
+    Time,
+    Integer,
+    Long,
+    List(Box<ExprType>),
+    /// The `None` literal, compatible with every type.
+    None,
+    /// The type could not be inferred.
+    Unknown,
+}
+
+impl ExprType {
+    /// Check if this type is one of the numeric types.
+    pub fn is_numeric(&self) -> bool {
+        matches!(self, ExprType::Number | ExprType::Integer | ExprType::Long | ExprType::Currency)
+    }
+
+    /// Check if values of this type are written as string literals.
+    pub fn is_string_like(&self) -> bool {
+        matches!(
+            self,
+            ExprType::String
+                | ExprType::Id
+                | ExprType::Date
+                | ExprType::Datetime
+                | ExprType::Time
+                | ExprType::Timestamp
+        )
+    }
+
+    /// Check if the type is known (not `Unknown` or `None`).
+    pub fn is_known(&self) -> bool {
+        !matches!(self, ExprType::Unknown | ExprType::None)
+    }
+
+    /// Check whether a value of type `other` can be used where `self` is expected.
+    ///
+    /// Compatibility is symmetric and lenient: numeric types are
+    /// interchangeable, string-like types accept strings, and unknown
+    /// types are compatible with everything.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use busbar_sf_agentscript::typecheck::ExprType;
+    ///
+    /// assert!(ExprType::Integer.is_compatible_with(&ExprType::Number));
+    /// assert!(ExprType::Id.is_compatible_with(&ExprType::String));
+    /// assert!(!ExprType::Boolean.is_compatible_with(&ExprType::String));
+    /// ```
+    pub fn is_compatible_with(&self, other: &ExprType) -> bool {
+        match (self, other) {
+            (a, b) if !a.is_known() || !b.is_known() => true,
+            (a, b) if a == b => true,
+            (a, b) if a.is_numeric() && b.is_numeric() => true,
+            (a, b) if a.is_string_like() && b.is_string_like() => true,
+            (ExprType::Timestamp, b) if b.is_numeric() => true,
+            (a, ExprType::Timestamp) if a.is_numeric() => true,
+            (ExprType::List(a), ExprType::List(b)) => a.is_compatible_with(b),
+            (ExprType::Object, ExprType::List(_)) | (ExprType::List(_), ExprType::Object) => true,
+            _ => false,
+        }
+    }
+}
+
+impl From<&Type> for ExprType {
+    fn from(ty: &Type) -> Self {
+        match ty {
+            Type::String => ExprType::String,
+            Type::Number => ExprType::Number,
+            Type::Boolean => ExprType::Boolean,
+            Type::Object => ExprType::Object,
+            Type::Date => ExprType::Date,
+            Type::Timestamp => ExprType::Timestamp,
+            Type::Currency => ExprType::Currency,
+            Type::Id => ExprType::Id,
+            Type::Datetime => ExprType::Datetime,
+            Type::Time => ExprType::Time,
+            Type::Integer => ExprType::Integer,
+            Type::Long => ExprType::Long,
+            Type::List(inner) => ExprType::List(Box::new(ExprType::from(inner.as_ref()))),
+        }
+    }
+}
+
+impl fmt::Display for ExprType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExprType::String => write!(f, "string"),
+            ExprType::Number => write!(f, "number"),
+            ExprType::Boolean => write!(f, "boolean"),
+            ExprType::Object => write!(f, "object"),
+            ExprType::Date => write!(f, "date"),
+            ExprType::Timestamp => write!(f, "timestamp"),
+            ExprType::Currency => write!(f, "currency"),
+            ExprType::Id => write!(f, "id"),
+            ExprType::Datetime => write!(f, "datetime"),
+            ExprType::Time => write!(f, "time"),
+            ExprType::Integer => write!(f, "integer"),
+            ExprType::Long => write!(f, "long"),
+            ExprType::List(inner) => write!(f, "list[{}]", inner),
+            ExprType::None => write!(f, "None"),
+            ExprType::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+/// A type mismatch found while checking an expression.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeMismatch {
+    /// Human-readable description of the mismatch.
+    pub message: String,
+    /// Span of the offending expression.
+    pub span: Range<usize>,
+    /// The type required by the context.
+    pub expected: ExprType,
+    /// The type that was inferred.
+    pub found: ExprType,
+}
+
+/// Typing environment used during inference.
+///
+/// Holds the declared variable types and, while checking a single action
+/// invocation, the output types of the invoked action (for `@outputs.x`).
+#[derive(Debug, Clone, Default)]
+pub struct TypeEnv {
+    variables: HashMap<String, ExprType>,
+    outputs: HashMap<String, ExprType>,
+}
+
+impl TypeEnv {
+    /// Build an environment from the variable declarations of a file.
+    pub fn from_ast(ast: &AgentFile) -> Self {
+        let mut env = Self::default();
+        if let Some(vars) = &ast.variables {
+            for var in &vars.node.variables {
+                env.variables
+                    .insert(var.node.name.node.clone(), ExprType::from(&var.node.ty.node));
+            }
+        }
+        env
+    }
+
+    /// Get the declared type of a variable.
+    pub fn variable(&self, name: &str) -> Option<&ExprType> {
+        self.variables.get(name)
+    }
+
+    /// Return a copy of this environment with `@outputs` bound to an action's outputs.
+    pub fn with_outputs(&self, action: Option<&ActionDef>) -> Self {
+        let mut env = self.clone();
+        env.outputs = action
+            .and_then(|a| a.outputs.as_ref())
+            .map(|outputs| {
+                outputs
+                    .node
+                    .iter()
+                    .map(|p| (p.node.name.node.clone(), ExprType::from(&p.node.ty.node)))
+                    .collect()
+            })
+            .unwrap_or_default();
+        env
+    }
+
+    /// Infer the type of a reference.
+    pub fn infer_reference(&self, reference: &Reference) -> ExprType {
+        let lookup = match reference.namespace.as_str() {
+            "variables" => &self.variables,
+            "outputs" => &self.outputs,
+            _ => return ExprType::Unknown,
+        };
+        match reference.path.as_slice() {
+            [name] => lookup.get(name).cloned().unwrap_or(ExprType::Unknown),
+            _ => ExprType::Unknown,
+        }
+    }
+
+    /// Infer the type of an expression.
+    pub fn infer(&self, expr: &Expr) -> ExprType {
+        match expr {
+            Expr::Reference(r) => self.infer_reference(r),
+            Expr::String(_) | Expr::Template(_) => ExprType::String,
+            Expr::Number(_) => ExprType::Number,
+            Expr::Date(_) => ExprType::Date,
+            Expr::Currency(_) => ExprType::Currency,
+            Expr::Bool(_) => ExprType::Boolean,
+            Expr::None => ExprType::None,
+            Expr::SlotFill => ExprType::Unknown,
+            Expr::List(items) => {
+                let elem = items
+                    .iter()
+                    .map(|i| self.infer(&i.node))
+                    .find(ExprType::is_known)
+                    .unwrap_or(ExprType::Unknown);
+                ExprType::List(Box::new(elem))
+            }
+            Expr::Object(_) => ExprType::Object,
+            Expr::BinOp { left, op, right } => match op {
+                BinOp::Eq
+                | BinOp::Ne
+                | BinOp::Lt
+                | BinOp::Gt
+                | BinOp::Le
+                | BinOp::Ge
+                | BinOp::Is
+                | BinOp::IsNot
+                | BinOp::And
+                | BinOp::Or => ExprType::Boolean,
+                BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div | BinOp::Mod => {
+                    let l = self.infer(&left.node);
+                    let r = self.infer(&right.node);
+                    if l.is_numeric() && r.is_numeric() {
+                        ExprType::Number
+                    } else if *op == BinOp::Add && (l == ExprType::String || r == ExprType::String)
+                    {
+                        ExprType::String
+                    } else {
+                        ExprType::Unknown
+                    }
+                }
+            },
+            Expr::UnaryOp { op, .. } => match op {
+                UnaryOp::Not => ExprType::Boolean,
+                UnaryOp::Neg => ExprType::Number,
+            },
+            Expr::Ternary {
+                then_expr,
+                else_expr,
+                ..
+            } => {
+                let t = self.infer(&then_expr.node);
+                let e = self.infer(&else_expr.node);
+                if t.is_known() && t.is_compatible_with(&e) {
+                    t
+                } else if e.is_known() && t == ExprType::None {
+                    e
+                } else {
+                    ExprType::Unknown
+                }
+            }
+            Expr::Property { .. } => ExprType::Unknown,
+            Expr::Index { object, .. } => match self.infer(&object.node) {
+                ExprType::List(inner) => *inner,
+                _ => ExprType::Unknown,
+            },
+            Expr::Call { callee, .. } => match callee.node.as_str() {
+                "len" => ExprType::Number,
+                "lower" | "upper" | "trim" => ExprType::String,
+                "contains" | "starts_with" | "ends_with" => ExprType::Boolean,
+                _ => ExprType::Unknown,
+            },
+        }
+    }
+}
+
+/// A call to an unknown function or with the wrong number of arguments.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CallError {
+    /// The callee is not a built-in function.
+    UnknownFunction {
+        /// The name that was called.
+        name: String,
+        /// Span of the name.
+        span: Range<usize>,
+        /// The closest built-in name, if any is close.
+        suggestion: Option<&'static str>,
+    },
+    /// The function was called with the wrong number of arguments.
+    WrongArity {
+        /// The function that was called.
+        function: &'static BuiltinFunction,
+        /// Number of arguments passed.
+        found: usize,
+        /// Span of the call.
+        span: Range<usize>,
+    },
+}
+
+impl fmt::Display for CallError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CallError::UnknownFunction { name, .. } => write!(f, "Unknown function '{}'", name),
+            CallError::WrongArity {
+                function, found, ..
+            } => write!(
+                f,
+                "{}() takes {} argument{} but {} {} given",
+                function.name,
+                function.arity,
+                if function.arity == 1 { "" } else { "s" },
+                found,
+                if *found == 1 { "was" } else { "were" }
+            ),
+        }
+    }
+}
+
+/// Check every expression in the file and return all type mismatches.
+pub fn check_types(ast: &AgentFile) -> Vec<TypeMismatch> {
+    check(ast).0
+}
+
+/// Check every function call in the file against [`BUILTIN_FUNCTIONS`].
+///
+/// # Example
+///
+/// ```rust
+/// use busbar_sf_agentscript::{parse, typecheck::check_calls};
+///
+/// let source = "topic main:\n   description: \"Main\"\n   reasoning:\n      instructions: \"Help\"\n      actions:\n         go: @utils.transition to @topic.main\n            available when lenght(@variables.items) > 0\n";
+/// let errors = check_calls(&parse(source).unwrap());
+/// assert_eq!(errors[0].to_string(), "Unknown function 'lenght'");
+/// ```
+pub fn check_calls(ast: &AgentFile) -> Vec<CallError> {
+    check(ast).1
+}
+
+/// Run the checker once, returning type mismatches and call errors.
+pub(crate) fn check(ast: &AgentFile) -> (Vec<TypeMismatch>, Vec<CallError>) {
+    let env = TypeEnv::from_ast(ast);
+    let mut checker = Checker {
+        env: &env,
+        mismatches: Vec::new(),
+        calls: Vec::new(),
+    };
+    checker.check_file(ast);
+    (checker.mismatches, checker.calls)
+}
+
+struct Checker<'a> {
+    env: &'a TypeEnv,
+    mismatches: Vec<TypeMismatch>,
+    calls: Vec<CallError>,
+}
+
+impl Checker<'_> {
+    fn check_file(&mut self, ast: &AgentFile) {
+        if let Some(vars) = &ast.variables {
+            for var in &vars.node.variables {
+                if let Some(default) = &var.node.default {
+                    let expected = ExprType::from(&var.node.ty.node);
+                    self.check_expr(self.env, default);
+                    let found = self.env.infer(&default.node);
+                    if !expected.is_compatible_with(&found) {
+                        self.push(
+                            format!(
+                                "Default value of variable '{}' has type {}, expected {}",
+                                var.node.name.node, found, expected
+                            ),
+                            default.span.clone(),
+                            expected,
+                            found,
+                        );
+                    }
+                }
+            }
+        }
+
+        if let Some(system) = &ast.system {
+            if let Some(instructions) = &system.node.instructions {
+                self.check_instructions(&instructions.node);
+            }
+        }
+
+        if let Some(start) = &ast.start_agent {
+            let s = &start.node;
+            self.check_scope(
+                s.actions.as_ref(),
+                s.before_reasoning.as_ref(),
+                s.reasoning.as_ref(),
+                s.after_reasoning.as_ref(),
+            );
+            if let Some(instructions) = s
+                .system
+                .as_ref()
+                .and_then(|sys| sys.node.instructions.as_ref())
+            {
+                self.check_instructions(&instructions.node);
+            }
+        }
+
+        for topic in &ast.topics {
+            let t = &topic.node;
+            self.check_scope(
+                t.actions.as_ref(),
+                t.before_reasoning.as_ref(),
+                t.reasoning.as_ref(),
+                t.after_reasoning.as_ref(),
+            );
+            if let Some(instructions) = t
+                .system
+                .as_ref()
+                .and_then(|sys| sys.node.instructions.as_ref())
+            {
+                self.check_instructions(&instructions.node);
+            }
+        }
+    }
+
+    /// Check a topic or start_agent body, resolving `@actions.x` against its action defs.
+    fn check_scope(
+        &mut self,
+        actions: Option<&Spanned<ActionsBlock>>,
+        before: Option<&Spanned<DirectiveBlock>>,
+        reasoning: Option<&Spanned<ReasoningBlock>>,
+        after: Option<&Spanned<DirectiveBlock>>,
+    ) {
+        let defs: HashMap<&str, &ActionDef> = actions
+            .map(|a| {
+                a.node
+                    .actions
+                    .iter()
+                    .map(|d| (d.node.name.node.as_str(), &d.node))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let resolve = |r: &Reference| -> Option<&ActionDef> {
+            if r.namespace == "actions" {
+                r.path.first().and_then(|n| defs.get(n.as_str()).copied())
+            } else {
+                None
+            }
+        };
+
+        for block in [before, after].into_iter().flatten() {
+            self.check_stmts(&block.node.statements, &resolve);
+        }
+
+        let Some(reasoning) = reasoning else { return };
+        if let Some(instructions) = &reasoning.node.instructions {
+            self.check_instructions(&instructions.node);
+        }
+        let Some(reasoning_actions) = &reasoning.node.actions else {
+            return;
+        };
+        for action in &reasoning_actions.node {
+            let ra = &action.node;
+            let def = match &ra.target.node {
+                ReasoningActionTarget::Action(r) => resolve(r),
+                _ => None,
+            };
+            if let Some(cond) = &ra.available_when {
+                self.check_expr(self.env, cond);
+            }
+            self.check_invocation(def, &ra.with_clauses, &ra.set_clauses);
+            for run in &ra.run_clauses {
+                let run_def = resolve(&run.node.action.node);
+                self.check_invocation(run_def, &run.node.with_clauses, &run.node.set_clauses);
+            }
+            for clause in &ra.if_clauses {
+                let env = self.env.with_outputs(def);
+                self.check_expr(&env, &clause.node.condition);
+            }
+        }
+    }
+
+    fn check_stmts<'d>(
+        &mut self,
+        stmts: &[Spanned<Stmt>],
+        resolve: &impl Fn(&Reference) -> Option<&'d ActionDef>,
+    ) {
+        for stmt in stmts {
+            match &stmt.node {
+                Stmt::Set { target, value } => {
+                    self.check_assignment(self.env, target, value);
+                }
+                Stmt::Run {
+                    action,
+                    with_clauses,
+                    set_clauses,
+                } => {
+                    self.check_invocation(resolve(&action.node), with_clauses, set_clauses);
+                }
+                Stmt::If {
+                    condition,
+                    then_block,
+                    else_block,
+                } => {
+                    self.check_expr(self.env, condition);
+                    self.check_stmts(then_block, resolve);
+                    if let Some(else_block) = else_block {
+                        self.check_stmts(else_block, resolve);
+                    }
+                }
+                Stmt::Transition { .. } | Stmt::Escalate | Stmt::Respond { .. } => {}
+            }
+        }
+    }
+
+    /// Check the `with` and `set` clauses of a single action invocation.
+    fn check_invocation(
+        &mut self,
+        def: Option<&ActionDef>,
+        with_clauses: &[Spanned<WithClause>],
+        set_clauses: &[Spanned<SetClause>],
+    ) {
+        let inputs: HashMap<&str, ExprType> = def
+            .and_then(|d| d.inputs.as_ref())
+            .map(|inputs| {
+                inputs
+                    .node
+                    .iter()
+                    .map(|p| (p.node.name.node.as_str(), ExprType::from(&p.node.ty.node)))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        for clause in with_clauses {
+            let WithValue::Expr(value) = &clause.node.value.node;
+            let value = Spanned::new(value.clone(), clause.node.value.span.clone());
+            self.check_expr(self.env, &value);
+            let Some(expected) = inputs.get(clause.node.param.node.as_str()) else {
+                continue;
+            };
+            let found = self.env.infer(&value.node);
+            if !expected.is_compatible_with(&found) {
+                self.push(
+                    format!(
+                        "Input '{}' expects {}, but the bound value has type {}",
+                        clause.node.param.node, expected, found
+                    ),
+                    value.span,
+                    expected.clone(),
+                    found,
+                );
+            }
+        }
+
+        let env = self.env.with_outputs(def);
+        for clause in set_clauses {
+            self.check_assignment(&env, &clause.node.target, &clause.node.source);
+        }
+    }
+
+    /// Check that `value` can be stored in the variable referenced by `target`.
+    fn check_assignment(
+        &mut self,
+        env: &TypeEnv,
+        target: &Spanned<Reference>,
+        value: &Spanned<Expr>,
+    ) {
+        self.check_expr(env, value);
+        let expected = env.infer_reference(&target.node);
+        let found = env.infer(&value.node);
+        if !expected.is_compatible_with(&found) {
+            self.push(
+                format!(
+                    "Cannot assign {} to {} of type {}",
+                    found,
+                    target.node.full_path(),
+                    expected
+                ),
+                value.span.clone(),
+                expected,
+                found,
+            );
+        }
+    }
+
+    /// Recursively check operator operands within an expression.
+    fn check_expr(&mut self, env: &TypeEnv, expr: &Spanned<Expr>) {
+        match &expr.node {
+            Expr::BinOp { left, op, right } => {
+                self.check_expr(env, left);
+                self.check_expr(env, right);
+                let l = env.infer(&left.node);
+                let r = env.infer(&right.node);
+                match op {
+                    BinOp::Eq | BinOp::Ne | BinOp::Is | BinOp::IsNot => {
+                        if !l.is_compatible_with(&r) {
+                            self.push(
+                                format!("Cannot compare {} with {}", l, r),
+                                expr.span.clone(),
+                                l,
+                                r,
+                            );
+                        }
+                    }
+                    BinOp::Lt | BinOp::Gt | BinOp::Le | BinOp::Ge => {
+                        if !l.is_compatible_with(&r) {
+                            self.push(
+                                format!("Cannot compare {} with {}", l, r),
+                                expr.span.clone(),
+                                l,
+                                r,
+                            );
+                        } else if l == ExprType::Boolean || r == ExprType::Boolean {
+                            self.push(
+                                "Ordering comparison is not defined for boolean values".to_string(),
+                                expr.span.clone(),
+                                ExprType::Number,
+                                ExprType::Boolean,
+                            );
+                        }
+                    }
+                    BinOp::Sub | BinOp::Mul | BinOp::Div | BinOp::Mod => {
+                        for (side, ty) in [(left, l), (right, r)] {
+                            if ty.is_known() && !ty.is_numeric() {
+                                self.push(
+                                    format!("Arithmetic operand has type {}, expected number", ty),
+                                    side.span.clone(),
+                                    ExprType::Number,
+                                    ty,
+                                );
+                            }
+                        }
+                    }
+                    BinOp::And | BinOp::Or | BinOp::Add => {}
+                }
+            }
+            Expr::UnaryOp { op, operand } => {
+                self.check_expr(env, operand);
+                let ty = env.infer(&operand.node);
+                if *op == UnaryOp::Neg && ty.is_known() && !ty.is_numeric() {
+                    self.push(
+                        format!("Cannot negate a value of type {}", ty),
+                        operand.span.clone(),
+                        ExprType::Number,
+                        ty,
+                    );
+                }
+            }
+            Expr::Ternary {
+                condition,
+                then_expr,
+                else_expr,
+            } => {
+                self.check_expr(env, condition);
+                self.check_expr(env, then_expr);
+                self.check_expr(env, else_expr);
+            }
+            Expr::Template(items) | Expr::List(items) => {
+                for item in items {
+                    self.check_expr(env, item);
+                }
+            }
+            Expr::Object(fields) => {
+                for value in fields.values() {
+                    self.check_expr(env, value);
+                }
+            }
+            Expr::Property { object, .. } => self.check_expr(env, object),
+            Expr::Index { object, index } => {
+                self.check_expr(env, object);
+                self.check_expr(env, index);
+            }
+            Expr::Call { callee, args } => {
+                for arg in args {
+                    self.check_expr(env, arg);
+                }
+                self.check_call(env, callee, args, expr.span.clone());
+            }
+            Expr::Reference(_)
+            | Expr::String(_)
+            | Expr::Number(_)
+            | Expr::Date(_)
+            | Expr::Currency(_)
+            | Expr::Bool(_)
+            | Expr::None
+            | Expr::SlotFill => {}
+        }
+    }
+
+    fn check_call(
+        &mut self,
+        env: &TypeEnv,
+        callee: &Spanned<String>,
+        args: &[Spanned<Expr>],
+        span: Range<usize>,
+    ) {
+        let Some(function) = BuiltinFunction::lookup(&callee.node) else {
+            let suggestion = BUILTIN_FUNCTIONS
+                .iter()
+                .map(|f| (crate::serializer::edit_distance(&callee.node, f.name), f.name))
+                .filter(|(d, _)| *d <= (callee.node.len() / 3).max(2))
+                .min_by_key(|(d, _)| *d)
+                .map(|(_, name)| name);
+            self.calls.push(CallError::UnknownFunction {
+                name: callee.node.clone(),
+                span: callee.span.clone(),
+                suggestion,
+            });
+            return;
+        };
+        if args.len() != function.arity {
+            self.calls.push(CallError::WrongArity {
+                function,
+                found: args.len(),
+                span,
+            });
+            return;
+        }
+
+        let types: Vec<ExprType> = args.iter().map(|a| env.infer(&a.node)).collect();
+        let string_or_list = |ty: &ExprType| ty.is_string_like() || matches!(ty, ExprType::List(_));
+        // (argument index, whether its type is accepted)
+        let checks: Vec<(usize, bool)> = match function.name {
+            "len" => vec![(0, string_or_list(&types[0]))],
+            "contains" if types[0].is_string_like() => {
+                vec![(1, types[1].is_string_like())]
+            }
+            "contains" => vec![(0, string_or_list(&types[0]))],
+            _ => (0..args.len())
+                .map(|i| (i, types[i].is_string_like()))
+                .collect(),
+        };
+        for (i, accepted) in checks {
+            if types[i].is_known() && !accepted {
+                self.push(
+                    format!(
+                        "Argument {} of {}() has type {}, expected {}",
+                        i + 1,
+                        function.name,
+                        types[i],
+                        if i == 0 && matches!(function.name, "len" | "contains") {
+                            "string or list"
+                        } else {
+                            "string"
+                        }
+                    ),
+                    args[i].span.clone(),
+                    ExprType::String,
+                    types[i].clone(),
+                );
+            }
+        }
+    }
+
+    fn check_instructions(&mut self, instructions: &Instructions) {
+        if let Instructions::Dynamic(parts) = instructions {
+            self.check_instruction_parts(parts);
+        }
+    }
+
+    fn check_instruction_parts(&mut self, parts: &[Spanned<InstructionPart>]) {
+        for part in parts {
+            match &part.node {
+                InstructionPart::Text(_) | InstructionPart::Heading { .. } => {}
+                InstructionPart::Interpolation(expr) => {
+                    let expr = Spanned::new(expr.clone(), part.span.clone());
+                    self.check_expr(self.env, &expr);
+                }
+                InstructionPart::Conditional {
+                    condition,
+                    then_parts,
+                    else_parts,
+                } => {
+                    self.check_expr(self.env, condition);
+                    self.check_instruction_parts(then_parts);
+                    if let Some(else_parts) = else_parts {
+                        self.check_instruction_parts(else_parts);
+                    }
+                }
+            }
+        }
+    }
+
+    fn push(&mut self, message: String, span: Range<usize>, expected: ExprType, found: ExprType) {
+        self.mismatches.push(TypeMismatch {
+            message,
+            span,
+            expected,
+            found,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(source: &str) -> Vec<TypeMismatch> {
+        let ast = crate::parse(source).expect("Failed to parse");
+        check_types(&ast)
+    }
+
+    #[test]
+    fn test_boolean_compared_to_string_literal() {
+        let source = r#"config:
+   agent_name: "Test"
+
+variables:
+   verified: mutable boolean = False
+
+topic main:
+   description: "Main"
+   reasoning:
+      instructions: "Help"
+      actions:
+         go: @utils.transition to @topic.main
+            available when @variables.verified == "yes"
+"#;
+        let mismatches = check(source);
+        assert_eq!(mismatches.len(), 1, "got: {:?}", mismatches);
+        assert_eq!(mismatches[0].expected, ExprType::Boolean);
+        assert_eq!(mismatches[0].found, ExprType::String);
+        assert!(mismatches[0]
+            .message
+            .contains("Cannot compare boolean with string"));
+    }
+
+    #[test]
+    fn test_number_output_bound_to_string_variable() {
+        let source = r#"config:
+   agent_name: "Test"
+
+variables:
+   total: mutable string = ""
+
+topic main:
+   description: "Main"
+
+   actions:
+      compute:
+         description: "Compute a total"
+         outputs:
+            amount: number
+               description: "The amount"
+         target: "flow://Compute"
+
+   reasoning:
+      instructions: "Help"
+      actions:
+         do_compute: @actions.compute
+            description: "Compute"
+            set @variables.total = @outputs.amount
+"#;
+        let mismatches = check(source);
+        assert_eq!(mismatches.len(), 1, "got: {:?}", mismatches);
+        assert_eq!(mismatches[0].expected, ExprType::String);
+        assert_eq!(mismatches[0].found, ExprType::Number);
+    }
+
+    #[test]
+    fn test_compatible_types_produce_no_mismatches() {
+        let source = r#"config:
+   agent_name: "Test"
+
+variables:
+   count: mutable number = 0
+   name: mutable string = ""
+   tags: mutable list[string] = []
+
+topic main:
+   description: "Main"
+   before_reasoning:
+      set @variables.count = @variables.count + 1
+      if @variables.name != "":
+         set @variables.name = "known"
+   reasoning:
+      instructions: "Help"
+"#;
+        let mismatches = check(source);
+        assert!(mismatches.is_empty(), "got: {:?}", mismatches);
+    }
+
+    #[test]
+    fn test_multiplicative_operands_must_be_numbers() {
+        let source = r#"config:
+   agent_name: "Test"
+
+variables:
+   premium: mutable number = 0
+   rate: mutable number = 0
+   name: mutable string = ""
+
+topic main:
+   description: "Main"
+   before_reasoning:
+      set @variables.premium = @variables.premium * @variables.rate / 12 % 100
+      set @variables.rate = @variables.name * 2
+   reasoning:
+      instructions: "Help"
+"#;
+        let mismatches = check(source);
+        assert_eq!(mismatches.len(), 1, "got: {:?}", mismatches);
+        assert_eq!(mismatches[0].expected, ExprType::Number);
+        assert_eq!(mismatches[0].found, ExprType::String);
+        assert!(mismatches[0].message.contains("Arithmetic operand"));
+    }
+
+    #[test]
+    fn test_function_argument_types() {
+        let mismatches = check(
+            r#"variables:
+   name: mutable string = ""
+   count: mutable number = 0
+   tags: mutable list[string] = []
+
+topic main:
+   description: "Main"
+   reasoning:
+      instructions: "Help"
+      actions:
+         go: @utils.transition to @topic.main
+            available when lower(@variables.count) == "x" and len(@variables.tags) > len(@variables.name) and contains(@variables.tags, "a")
+"#,
+        );
+        assert_eq!(mismatches.len(), 1, "{mismatches:?}");
+        assert_eq!(mismatches[0].message, "Argument 1 of lower() has type number, expected string");
+        assert_eq!(mismatches[0].found, ExprType::Number);
+    }
+
+    #[test]
+    fn test_default_value_mismatch() {
+        let source = r#"config:
+   agent_name: "Test"
+
+variables:
+   enabled: mutable boolean = "yes"
+"#;
+        let mismatches = check(source);
+        assert_eq!(mismatches.len(), 1, "got: {:?}", mismatches);
+        assert!(mismatches[0].message.contains("enabled"));
+    }
+
+    #[test]
+    fn test_date_and_currency_literals() {
+        let source = r#"variables:
+   renewal: mutable date = date(2024-01-31)
+   price: mutable currency = $199.99
+   total: mutable number = $5
+   count: mutable integer = date(2024-01-31)
+   due: mutable date = $5
+"#;
+        let mismatches = check(source);
+        let found: Vec<_> = mismatches.iter().map(|m| (&m.expected, &m.found)).collect();
+        assert_eq!(
+            found,
+            [
+                (&ExprType::Integer, &ExprType::Date),
+                (&ExprType::Date, &ExprType::Currency),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_infer_literals_and_operators() {
+        let env = TypeEnv::default();
+        assert_eq!(env.infer(&Expr::Number(crate::ast::Number::Int(1))), ExprType::Number);
+        assert_eq!(env.infer(&Expr::SlotFill), ExprType::Unknown);
+        assert_eq!(
+            env.infer(&Expr::UnaryOp {
+                op: UnaryOp::Not,
+                operand: Box::new(Spanned::new(Expr::Bool(true), 0..4)),
+            }),
+            ExprType::Boolean
+        );
+        assert_eq!(ExprType::List(Box::new(ExprType::String)).to_string(), "list[string]");
+    }
+}
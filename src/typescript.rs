@@ -0,0 +1,619 @@
+//! TypeScript declarations for the WASM API.
+//!
+//! wasm-bindgen declares every `JsValue` as `any`. [`typescript_declarations`]
+//! renders the types the WASM functions actually take and return; the WASM
+//! build appends them to the generated `.d.ts`, and the exports refer to
+//! them by name, so `parse_agent` returns an `AgentFile` and `simulate` a
+//! `SimulationTrace`.
+//!
+//! The AST types come from [`ast_json_schema`]. The other structures are
+//! described here with the same JSON Schema subset, and tests check both
+//! against what serde produces. Values converted with serde_wasm_bindgen's
+//! default serializer reach JavaScript with `undefined` where the JSON has
+//! `null`, and with `Map`s for maps; the simulation results are converted
+//! to plain JSON.
+//!
+//! The declarations are checked in as `src/wasm.d.ts`, so any change to the
+//! shape of the API shows up in review. `cargo test` fails when the file is
+//! stale; run it with `UPDATE_SNAPSHOTS=1` to regenerate the file.
+//!
+//! # Example
+//!
+//! ```rust
+//! use busbar_sf_agentscript::typescript::typescript_declarations;
+//!
+//! let declarations = typescript_declarations();
+//! assert!(declarations.contains("export interface AgentFile {"));
+//! assert!(declarations.contains("export interface SimulationTrace {"));
+//! ```
+
+use crate::schema::{
+    array, ast_json_schema, boolean, object, one_of, opt, r, string, unit_enum, variant,
+};
+use serde_json::{json, Value};
+
+/// How a value is converted to JavaScript.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Conversion {
+    /// serde_wasm_bindgen's default: `None` is `undefined`, maps are `Map`s.
+    Js,
+    /// JSON-compatible: plain objects and `null`, as in the schema.
+    Json,
+}
+
+const HEADER: &str = "\
+// Generated by busbar_sf_agentscript::typescript::typescript_declarations.
+// Do not edit: regenerate with `UPDATE_SNAPSHOTS=1 cargo test`.
+
+";
+
+/// TypeScript declarations for the values the WASM API passes to and from
+/// JavaScript.
+pub fn typescript_declarations() -> String {
+    let mut out = String::from(HEADER);
+    for (name, schema, conversion) in defs() {
+        declare(&mut out, &name, &schema, conversion);
+        if name == "Span" {
+            out.push_str("export interface Spanned<T> {\n  node: T;\n  span: Span;\n}\n\n");
+        }
+    }
+    out.truncate(out.trim_end().len());
+    out.push('\n');
+    out
+}
+
+/// Every declared type, in declaration order: the AST (root first, then
+/// its definitions by name), then the rest of the API.
+fn defs() -> Vec<(String, Value, Conversion)> {
+    let mut root = ast_json_schema();
+    let ast_defs = root
+        .as_object_mut()
+        .and_then(|root| root.remove("$defs"))
+        .and_then(|defs| match defs {
+            Value::Object(defs) => Some(defs),
+            _ => None,
+        })
+        .unwrap_or_default();
+    let mut ast_defs: Vec<(String, Value)> = ast_defs.into_iter().collect();
+    ast_defs.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    let mut defs = vec![("AgentFile".to_string(), root, Conversion::Js)];
+    defs.extend(
+        ast_defs
+            .into_iter()
+            .map(|(name, schema)| (name, schema, Conversion::Js)),
+    );
+    defs.extend(
+        API_DEFS
+            .iter()
+            .map(|&(name, conversion)| (name.to_string(), api_def(name), conversion)),
+    );
+    defs
+}
+
+/// The API structures outside the AST, and how each reaches JavaScript.
+const API_DEFS: &[(&str, Conversion)] = &[
+    ("ParseProgress", Conversion::Js),
+    ("Severity", Conversion::Js),
+    ("SemanticError", Conversion::Js),
+    ("ValidationReport", Conversion::Js),
+    ("Location", Conversion::Js),
+    ("FormatResult", Conversion::Js),
+    ("MockData", Conversion::Json),
+    ("SimulationOutcome", Conversion::Json),
+    ("VariableChange", Conversion::Json),
+    ("ActionInvocation", Conversion::Json),
+    ("SimulationStep", Conversion::Json),
+    ("SimulationTrace", Conversion::Json),
+    ("ScenarioResult", Conversion::Json),
+    ("NodeKind", Conversion::Js),
+    ("EdgeKind", Conversion::Js),
+    ("NodeRepr", Conversion::Js),
+    ("EdgeRepr", Conversion::Js),
+    ("GraphRepr", Conversion::Js),
+    ("ValidationErrorRepr", Conversion::Js),
+    ("ValidationResultRepr", Conversion::Js),
+    ("GraphStats", Conversion::Js),
+    ("TopicMetricsRepr", Conversion::Js),
+    ("GraphMetricsRepr", Conversion::Js),
+    ("DependencyType", Conversion::Js),
+    ("Dependency", Conversion::Js),
+    ("DependencyReport", Conversion::Js),
+];
+
+/// The schema of the API structure `name`.
+fn api_def(name: &str) -> Value {
+    match name {
+        "ParseProgress" => described(
+            "Passed to the `parse_agent_with_progress` callback",
+            object(&[
+                ("blocks_parsed", count()),
+                ("blocks_total", count()),
+                ("bytes_parsed", count()),
+                ("bytes_total", count()),
+            ]),
+        ),
+        "Severity" => unit_enum(&["Error", "Warning"]),
+        "SemanticError" => object(&[
+            ("message", string()),
+            ("span", opt(r("Span"))),
+            ("severity", r("Severity")),
+            ("hint", opt(string())),
+            ("code", opt(string())),
+            ("related", array(tuple(&[r("Span"), string()]))),
+        ]),
+        "ValidationReport" => described(
+            "Returned by `validate_agent_semantic`",
+            object(&[
+                ("errors", array(r("SemanticError"))),
+                ("warnings", array(r("SemanticError"))),
+            ]),
+        ),
+        "Location" => described(
+            "Zero-based line and UTF-16 column, returned by `offset_to_location`",
+            object(&[("line", count()), ("column", count())]),
+        ),
+        "FormatResult" => described(
+            "Returned by `format_check`",
+            object(&[
+                ("formatted", string()),
+                ("is_idempotent", boolean()),
+                ("roundtrip_ok", boolean()),
+                ("difference", opt(string())),
+            ]),
+        ),
+        "MockData" => described(
+            "The data `simulate` runs against; every field is optional",
+            optional_fields(object(&[
+                ("variables", map(json!({}))),
+                ("actions", map(json!({}))),
+                ("choices", map(json!({ "anyOf": [string(), array(string())] }))),
+            ])),
+        ),
+        "SimulationOutcome" => unit_enum(&["success", "escalated", "error"]),
+        "VariableChange" => object(&[
+            ("name", string()),
+            ("old_value", json!({})),
+            ("new_value", json!({})),
+        ]),
+        "ActionInvocation" => object(&[
+            ("action_name", string()),
+            ("inputs", map(json!({}))),
+            ("outputs", json!({})),
+        ]),
+        "SimulationStep" => object(&[
+            ("phase", string()),
+            ("statement_type", string()),
+            ("detail", string()),
+            ("variable_changes", array(r("VariableChange"))),
+            ("action_invocations", array(r("ActionInvocation"))),
+        ]),
+        "SimulationTrace" => described(
+            "Returned by `simulate`",
+            object(&[
+                ("steps", array(r("SimulationStep"))),
+                ("final_context", map(json!({}))),
+                ("outcome", r("SimulationOutcome")),
+                ("error", opt(string())),
+                ("topic_transitions", array(string())),
+            ]),
+        ),
+        "ScenarioResult" => described(
+            "Returned by `runScenario`",
+            object(&[
+                ("name", opt(string())),
+                ("passed", boolean()),
+                ("failures", array(string())),
+                ("trace", r("SimulationTrace")),
+            ]),
+        ),
+        "NodeKind" => unit_enum(&[
+            "topic",
+            "start_agent",
+            "action_def",
+            "reasoning_action",
+            "variable",
+            "external",
+            "util",
+        ]),
+        "EdgeKind" => unit_enum(&[
+            "routes",
+            "transitions_to",
+            "delegates",
+            "invokes",
+            "reads",
+            "writes",
+            "chains",
+            "escalates",
+        ]),
+        "NodeRepr" => object(&[
+            ("node_type", r("NodeKind")),
+            ("name", opt(string())),
+            ("topic", opt(string())),
+            ("target", opt(string())),
+            ("mutable", opt(boolean())),
+            ("span_start", count()),
+            ("span_end", count()),
+        ]),
+        "EdgeRepr" => described(
+            "`source` and `target` are positions in `GraphRepr.nodes`",
+            object(&[
+                ("source", count()),
+                ("target", count()),
+                ("edge_type", r("EdgeKind")),
+            ]),
+        ),
+        "GraphRepr" => described(
+            "Returned by `build_graph` and `build_graph_from_source`",
+            object(&[
+                ("nodes", array(r("NodeRepr"))),
+                ("edges", array(r("EdgeRepr"))),
+                ("topics", array(string())),
+                ("variables", array(string())),
+            ]),
+        ),
+        "ValidationErrorRepr" => object(&[
+            ("error_type", string()),
+            ("message", string()),
+            ("span_start", opt(count())),
+            ("span_end", opt(count())),
+        ]),
+        "ValidationResultRepr" => described(
+            "Returned by `validate_graph`",
+            object(&[
+                ("errors", array(r("ValidationErrorRepr"))),
+                ("warnings", array(r("ValidationErrorRepr"))),
+                ("is_valid", boolean()),
+            ]),
+        ),
+        "GraphStats" => described(
+            "Returned by `get_graph_stats`",
+            object(&[
+                ("topics", count()),
+                ("action_defs", count()),
+                ("reasoning_actions", count()),
+                ("variables", count()),
+                ("connections", count()),
+                ("knowledge_bases", count()),
+                ("has_start_agent", boolean()),
+                ("transitions", count()),
+                ("invocations", count()),
+                ("reads", count()),
+                ("writes", count()),
+            ]),
+        ),
+        "TopicMetricsRepr" => object(&[
+            ("name", string()),
+            ("fan_in", count()),
+            ("fan_out", count()),
+            ("depth", opt(count())),
+            ("reasoning_actions", count()),
+            ("complexity", count()),
+            ("max_instruction_nesting", count()),
+        ]),
+        "GraphMetricsRepr" => described(
+            "Returned by `get_graph_metrics`",
+            object(&[
+                ("topics", array(r("TopicMetricsRepr"))),
+                ("max_depth", opt(count())),
+                ("total_complexity", count()),
+            ]),
+        ),
+        "DependencyType" => one_of(vec![
+            variant("SObject", string()),
+            variant("Field", object(&[("object", string()), ("field", string())])),
+            variant("Flow", string()),
+            variant("ApexClass", string()),
+            variant("ApexMethod", object(&[("class", string()), ("method", string())])),
+            variant("KnowledgeBase", string()),
+            variant("Connection", string()),
+            variant("PromptTemplate", string()),
+            variant("ExternalService", string()),
+            variant("Custom", string()),
+        ]),
+        "Dependency" => object(&[
+            ("dep_type", r("DependencyType")),
+            ("used_in", string()),
+            ("action_name", string()),
+            ("span", tuple(&[count(), count()])),
+        ]),
+        "DependencyReport" => {
+            let names = array(string());
+            let grouped = map(array(r("Dependency")));
+            described(
+                "Returned by `extract_dependencies`",
+                object(&[
+                    ("sobjects", names.clone()),
+                    ("fields", names.clone()),
+                    ("flows", names.clone()),
+                    ("apex_classes", names.clone()),
+                    ("knowledge_bases", names.clone()),
+                    ("connections", names.clone()),
+                    ("prompt_templates", names.clone()),
+                    ("external_services", names),
+                    ("all_dependencies", array(r("Dependency"))),
+                    ("by_type", grouped.clone()),
+                    ("by_topic", grouped),
+                ]),
+            )
+        }
+        _ => unreachable!("no API definition {}", name),
+    }
+}
+
+fn count() -> Value {
+    json!({ "type": "integer", "minimum": 0 })
+}
+
+/// A map with string keys.
+fn map(values: Value) -> Value {
+    json!({ "type": "object", "additionalProperties": values })
+}
+
+/// A tuple, serialized as a fixed-length array.
+fn tuple(items: &[Value]) -> Value {
+    json!({
+        "type": "array",
+        "prefixItems": items,
+        "minItems": items.len(),
+        "maxItems": items.len(),
+    })
+}
+
+fn described(description: &str, mut schema: Value) -> Value {
+    schema["description"] = json!(description);
+    schema
+}
+
+/// An input struct whose fields all default when missing.
+fn optional_fields(mut schema: Value) -> Value {
+    schema["required"] = json!([]);
+    schema
+}
+
+/// Append the declaration of `name`.
+fn declare(out: &mut String, name: &str, schema: &Value, conversion: Conversion) {
+    if let Some(description) = schema.get("description").and_then(Value::as_str) {
+        out.push_str(&format!("/** {} */\n", description));
+    }
+    if schema.get("properties").is_some() && spanned_node(schema).is_none() {
+        out.push_str(&format!(
+            "export interface {} {}\n\n",
+            name,
+            object_type(schema, conversion, 0)
+        ));
+    } else {
+        out.push_str(&format!("export type {} = {};\n\n", name, ts_type(schema, conversion, 0)));
+    }
+}
+
+/// The TypeScript type of values matching `schema`, nested `indent` levels deep.
+fn ts_type(schema: &Value, conversion: Conversion, indent: usize) -> String {
+    if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+        return reference.trim_start_matches("#/$defs/").to_string();
+    }
+    if let Some(node) = spanned_node(schema) {
+        return format!("Spanned<{}>", ts_type(node, conversion, indent));
+    }
+    if let Some(variants) = schema
+        .get("anyOf")
+        .or_else(|| schema.get("oneOf"))
+        .and_then(Value::as_array)
+    {
+        let variants: Vec<String> = variants
+            .iter()
+            .map(|variant| ts_type(variant, conversion, indent))
+            .collect();
+        return variants.join(" | ");
+    }
+    if let Some(values) = schema.get("enum").and_then(Value::as_array) {
+        let values: Vec<String> = values.iter().map(Value::to_string).collect();
+        return values.join(" | ");
+    }
+    match schema.get("type").and_then(Value::as_str) {
+        Some("string") => "string".to_string(),
+        Some("integer" | "number") => "number".to_string(),
+        Some("boolean") => "boolean".to_string(),
+        Some("null") if conversion == Conversion::Js => "undefined".to_string(),
+        Some("null") => "null".to_string(),
+        Some("array") => {
+            if let Some(items) = schema.get("prefixItems").and_then(Value::as_array) {
+                let items: Vec<String> = items
+                    .iter()
+                    .map(|item| ts_type(item, conversion, indent))
+                    .collect();
+                return format!("[{}]", items.join(", "));
+            }
+            let item = ts_type(&schema["items"], conversion, indent);
+            if item.contains(" | ") {
+                format!("({})[]", item)
+            } else {
+                format!("{}[]", item)
+            }
+        }
+        Some("object") => object_type(schema, conversion, indent),
+        _ => "unknown".to_string(),
+    }
+}
+
+/// A struct as an object literal type, or a map as a `Map` or `Record`.
+fn object_type(schema: &Value, conversion: Conversion, indent: usize) -> String {
+    let Some(properties) = schema.get("properties").and_then(Value::as_object) else {
+        let values = match schema.get("additionalProperties") {
+            Some(values) if values.is_object() => ts_type(values, conversion, indent),
+            _ => "unknown".to_string(),
+        };
+        return match conversion {
+            Conversion::Js => format!("Map<string, {}>", values),
+            Conversion::Json => format!("Record<string, {}>", values),
+        };
+    };
+    let required: Vec<&str> = schema
+        .get("required")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .collect();
+    let mut names: Vec<&String> = properties.keys().collect();
+    names.sort();
+
+    let pad = "  ".repeat(indent + 1);
+    let mut out = String::from("{\n");
+    for name in names {
+        let optional = if required.contains(&name.as_str()) {
+            ""
+        } else {
+            "?"
+        };
+        out.push_str(&format!(
+            "{}{}{}: {};\n",
+            pad,
+            name,
+            optional,
+            ts_type(&properties[name], conversion, indent + 1)
+        ));
+    }
+    out.push_str(&"  ".repeat(indent));
+    out.push('}');
+    out
+}
+
+/// The node schema, if `schema` is a `Spanned<T>`.
+fn spanned_node(schema: &Value) -> Option<&Value> {
+    let properties = schema.get("properties")?.as_object()?;
+    let is_spanned = properties.len() == 2 && properties.get("span") == Some(&r("Span"));
+    is_spanned.then(|| properties.get("node")).flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::check;
+    use serde::Serialize;
+
+    const SNAPSHOT: &str = include_str!("wasm.d.ts");
+
+    #[test]
+    fn test_declarations_snapshot() {
+        let declarations = typescript_declarations();
+        if declarations == SNAPSHOT {
+            return;
+        }
+        if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            let path = concat!(env!("CARGO_MANIFEST_DIR"), "/src/wasm.d.ts");
+            std::fs::write(path, &declarations).unwrap();
+        } else {
+            panic!(
+                "src/wasm.d.ts is out of date with the WASM API types; \
+                 review the change and regenerate it with `UPDATE_SNAPSHOTS=1 cargo test`"
+            );
+        }
+    }
+
+    /// Check the JSON of `value` against the declared type `name`.
+    fn assert_conforms<T: Serialize>(name: &str, value: &T) {
+        let mut all_defs = json!({});
+        for (def, schema, _) in defs() {
+            assert!(all_defs.get(&def).is_none(), "{} is declared twice", def);
+            all_defs[def] = schema;
+        }
+        let value = serde_json::to_value(value).unwrap();
+        if let Err(e) = check(&r(name), &value, &all_defs, "$") {
+            panic!("{} JSON does not match its declaration: {}", name, e);
+        }
+    }
+
+    const SOURCE: &str = r#"config:
+   agent_name: "Test"
+
+variables:
+   count: mutable number = 0
+
+start_agent selector:
+   description: "Route"
+   reasoning:
+      actions:
+         go: @utils.transition to @topic.main
+
+topic main:
+   description: "Main"
+   actions:
+      lookup:
+         description: "Look up"
+         inputs:
+            id: string
+         outputs:
+            total: number
+         target: "flow://Lookup"
+   before_reasoning:
+      run @actions.lookup
+         with id = "1"
+         set @variables.count = @outputs.total
+   reasoning:
+      instructions: "Help"
+
+config:
+   agent_name: "Duplicate"
+"#;
+
+    #[test]
+    fn test_api_values_conform() {
+        let ast = crate::parse(SOURCE).unwrap();
+        assert_conforms("AgentFile", &ast);
+
+        let issues = crate::validate_ast(&ast);
+        assert!(issues.iter().any(|issue| !issue.related.is_empty()));
+        for issue in &issues {
+            assert_conforms("SemanticError", issue);
+        }
+
+        let location = crate::text_pos::location(SOURCE, 20, crate::text_pos::Encoding::Utf16);
+        assert_conforms("Location", &location);
+        assert_conforms("FormatResult", &crate::formatter::format_check(SOURCE).unwrap());
+        assert_conforms("ParseProgress", &crate::parser::ParseProgress::default());
+
+        let mock = json!({
+            "variables": { "count": 1 },
+            "actions": { "lookup": { "total": 3 } },
+            "choices": { "selector": ["go"] },
+        });
+        assert_conforms("MockData", &mock);
+        let mock: crate::simulation::MockData = serde_json::from_value(mock).unwrap();
+        let trace = crate::simulation::simulate(&ast, &mock);
+        assert!(!trace.steps.is_empty());
+        assert_conforms("SimulationTrace", &trace);
+
+        let scenario: crate::simulation::Scenario =
+            serde_json::from_value(json!({ "name": "go", "expect": { "outcome": "error" } }))
+                .unwrap();
+        assert_conforms("ScenarioResult", &crate::simulation::run_scenario(&ast, &scenario));
+    }
+
+    #[cfg(feature = "graph")]
+    #[test]
+    fn test_graph_values_conform() {
+        use crate::graph::{export, EdgeKind, NodeKind, RefGraph};
+
+        let ast = crate::parse(include_str!("../examples/ComprehensiveDemo.agent")).unwrap();
+        let graph = RefGraph::from_ast(&ast).unwrap();
+        assert_conforms("GraphRepr", &export::GraphRepr::from(&graph));
+        assert_conforms(
+            "ValidationResultRepr",
+            &export::ValidationResultRepr::from(&graph.validate()),
+        );
+        assert_conforms("GraphStats", &graph.stats());
+        assert_conforms("GraphMetricsRepr", &export::GraphMetricsRepr::from(&graph.metrics()));
+        let report = crate::graph::dependencies::extract_dependencies(&ast);
+        assert!(!report.all_dependencies.is_empty());
+        assert_conforms("DependencyReport", &report);
+
+        // Every kind is declared, not just those in the example
+        for kind in NodeKind::ALL {
+            assert_conforms("NodeKind", &kind);
+        }
+        for kind in EdgeKind::ALL {
+            assert_conforms("EdgeKind", &kind);
+        }
+    }
+}
@@ -1,8 +1,17 @@
 use crate::ast::{
-    ActionDef, AgentFile, ConnectionEntry, Expr, LanguageEntry, Type, VariableDecl, VariableKind,
+    ActionClause, ActionDef, ActionsBlock, AgentFile, ConnectionEntry, Expr, InstructionPart,
+    Instructions, LanguageEntry, NamespaceRule, ReasoningAction, ReasoningActionTarget,
+    ReasoningBlock, Reference, SetClause, Spanned, Stmt, Type, VariableDecl, VariableKind,
+    WithClause, WithValue, BUILTIN_FUNCTIONS,
 };
-use serde::Serialize;
+use crate::serializer::closest;
+use crate::target_uri::{TargetScheme, TargetUriError};
+use crate::typecheck::CallError;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
 use std::ops::Range;
+use std::str::FromStr;
 
 #[derive(Debug, Clone, Serialize, PartialEq, Eq)]
 pub enum Severity {
@@ -16,9 +25,229 @@ pub struct SemanticError {
     pub span: Option<Range<usize>>,
     pub severity: Severity,
     pub hint: Option<String>,
+    /// Stable identifier of the rule that produced this diagnostic
+    /// (e.g. `"missing-description"`), used for severity mapping.
+    pub code: Option<&'static str>,
+    /// Other locations involved in the diagnostic, each with a label
+    /// (e.g. the first of two duplicate blocks).
+    pub related: Vec<(Range<usize>, String)>,
+}
+
+/// The environment an agent is being checked for.
+///
+/// Diagnostics are reported at their base severity in `dev`. Stricter targets
+/// escalate selected warnings to errors, so the same file can be iterated on
+/// freely but is blocked from a production deploy:
+///
+/// | Code | dev | sandbox | prod |
+/// |------|-----|---------|------|
+/// | `input-keyword-collision` | warning | error | error |
+/// | `missing-description` | warning | warning | error |
+/// | `missing-start-agent` | warning | warning | error |
+/// | `token-budget` | warning | warning | error |
+///
+/// `token-budget` is a lint rule (feature `graph`); `lint::run_lints_for`
+/// applies the same table to lint findings.
+///
+/// An agent declares its target with `target_environment:` in `config:`
+/// ([`from_config`](Self::from_config)); tools let a flag or option
+/// override it.
+///
+/// ```rust
+/// use busbar_sf_agentscript::validation::{validate_ast_for, Severity, TargetEnvironment};
+///
+/// let ast = busbar_sf_agentscript::parse("topic main:\n   reasoning:\n      instructions: \"Help\"\n").unwrap();
+/// let dev = validate_ast_for(&ast, TargetEnvironment::Dev);
+/// let prod = validate_ast_for(&ast, TargetEnvironment::Prod);
+/// assert_eq!(dev[0].severity, Severity::Warning);
+/// assert_eq!(prod[0].severity, Severity::Error);
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TargetEnvironment {
+    #[default]
+    Dev,
+    Sandbox,
+    Prod,
+}
+
+impl TargetEnvironment {
+    /// Check whether a diagnostic with `code` is an error in this environment.
+    pub fn escalates(self, code: &str) -> bool {
+        match self {
+            TargetEnvironment::Dev => false,
+            TargetEnvironment::Sandbox => code == "input-keyword-collision",
+            TargetEnvironment::Prod => {
+                matches!(
+                    code,
+                    "input-keyword-collision"
+                        | "missing-description"
+                        | "missing-start-agent"
+                        | "token-budget"
+                )
+            }
+        }
+    }
+
+    /// The target declared by `target_environment:` in the agent's config,
+    /// if it is set to a known environment.
+    ///
+    /// ```rust
+    /// use busbar_sf_agentscript::validation::TargetEnvironment;
+    ///
+    /// let ast = busbar_sf_agentscript::parse(
+    ///     "config:\n   agent_name: \"Support\"\n   target_environment: \"prod\"\n",
+    /// )
+    /// .unwrap();
+    /// assert_eq!(TargetEnvironment::from_config(&ast), Some(TargetEnvironment::Prod));
+    /// ```
+    pub fn from_config(ast: &AgentFile) -> Option<Self> {
+        let config = ast.config.as_ref()?;
+        config.node.target_environment.as_ref()?.node.parse().ok()
+    }
+
+    /// Escalate diagnostics to errors according to this environment.
+    pub fn apply(self, errors: &mut [SemanticError]) {
+        for error in errors {
+            if error.code.is_some_and(|code| self.escalates(code)) {
+                error.severity = Severity::Error;
+            }
+        }
+    }
+}
+
+impl FromStr for TargetEnvironment {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "dev" | "development" => Ok(TargetEnvironment::Dev),
+            "sandbox" => Ok(TargetEnvironment::Sandbox),
+            "prod" | "production" => Ok(TargetEnvironment::Prod),
+            _ => Err(format!("unknown target environment '{}', expected dev, sandbox, or prod", s)),
+        }
+    }
+}
+
+impl fmt::Display for TargetEnvironment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TargetEnvironment::Dev => "dev",
+            TargetEnvironment::Sandbox => "sandbox",
+            TargetEnvironment::Prod => "prod",
+        })
+    }
+}
+
+/// The context namespaces, and paths within them, that `linked` variables
+/// may be sourced from.
+///
+/// Passed to [`validate_ast_with_context`] to flag `source:` references the
+/// runtime will not be able to resolve. A namespace with no paths accepts
+/// any path; otherwise a path matches itself and anything below it, so
+/// `user` also covers `user.email`.
+///
+/// ```rust
+/// use busbar_sf_agentscript::validation::{validate_ast_with_context, ContextSchema};
+///
+/// let source = "variables:\n   email: linked string\n      source: @messagingSession.userEmial\n";
+/// let ast = busbar_sf_agentscript::parse(source).unwrap();
+/// let schema = ContextSchema::salesforce();
+/// let errors = validate_ast_with_context(&ast, Some(&schema));
+/// assert_eq!(errors[0].code, Some("unknown-context-path"));
+/// assert_eq!(errors[0].hint.as_deref(), Some("Did you mean '@messagingSession.userEmail'?"));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContextSchema {
+    /// Known paths by namespace, dot-separated (e.g. `user.email`).
+    pub namespaces: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl ContextSchema {
+    /// A schema with no namespaces, which flags every linked source.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The context available to agents deployed to Salesforce: bot context
+    /// variables under `@context`, plus the messaging session and Omni-Channel.
+    pub fn salesforce() -> Self {
+        Self::new()
+            .with_namespace("context", Vec::<String>::new())
+            .with_namespace(
+                "messagingSession",
+                [
+                    "channelType",
+                    "endUserId",
+                    "endUserLanguage",
+                    "sessionID",
+                    "userEmail",
+                ],
+            )
+            .with_namespace("omnichannel", ["availability"])
+    }
+
+    /// Add a namespace, or more paths to an existing one.
+    pub fn with_namespace(
+        mut self,
+        namespace: impl Into<String>,
+        paths: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        self.namespaces
+            .entry(namespace.into())
+            .or_default()
+            .extend(paths.into_iter().map(Into::into));
+        self
+    }
+
+    fn check(&self, reference: &Reference) -> Option<(String, &'static str, Option<String>)> {
+        let Some(paths) = self.namespaces.get(&reference.namespace) else {
+            let suggestion = closest(&reference.namespace, self.namespaces.keys())
+                .map(|namespace| format!("@{}", namespace));
+            return Some((
+                format!("Unknown context namespace '@{}'", reference.namespace),
+                "unknown-context-namespace",
+                suggestion,
+            ));
+        };
+        let path = reference.path.join(".");
+        let known = paths.is_empty()
+            || paths.iter().any(|known| {
+                path == *known
+                    || path
+                        .strip_prefix(known.as_str())
+                        .is_some_and(|rest| rest.starts_with('.'))
+            });
+        if known {
+            return None;
+        }
+        let suggestion =
+            closest(&path, paths.iter()).map(|known| format!("@{}.{}", reference.namespace, known));
+        Some((
+            format!("Unknown context path '{}'", reference.full_path()),
+            "unknown-context-path",
+            suggestion,
+        ))
+    }
+}
+
+/// Validate an AST and map severities for a target environment.
+pub fn validate_ast_for(ast: &AgentFile, target: TargetEnvironment) -> Vec<SemanticError> {
+    let mut errors = validate_ast(ast);
+    target.apply(&mut errors);
+    errors
 }
 
 pub fn validate_ast(ast: &AgentFile) -> Vec<SemanticError> {
+    validate_ast_with_context(ast, None)
+}
+
+/// Validate an AST, also checking linked variable sources against `context`
+/// when one is given.
+pub fn validate_ast_with_context(
+    ast: &AgentFile,
+    context: Option<&ContextSchema>,
+) -> Vec<SemanticError> {
     let mut errors = Vec::new();
 
     // Rule 1 & 2: Variables
@@ -60,13 +289,527 @@ pub fn validate_ast(ast: &AgentFile) -> Vec<SemanticError> {
         }
     }
 
+    // Rule 6: Expression Type Checking
+    let (mismatches, call_errors) = crate::typecheck::check(ast);
+    for mismatch in mismatches {
+        errors.push(SemanticError {
+            message: mismatch.message,
+            span: Some(mismatch.span),
+            severity: Severity::Error,
+            hint: None,
+            code: Some("type-mismatch"),
+            related: Vec::new(),
+        });
+    }
+
+    // Rule 7: Missing Descriptions
+    // The planner routes on topic and action descriptions.
+    if let Some(start_agent) = &ast.start_agent {
+        let sa = &start_agent.node;
+        check_description("start_agent", &sa.name, &sa.description, &mut errors);
+        check_action_descriptions(&sa.actions, &mut errors);
+    }
+    for topic in &ast.topics {
+        let t = &topic.node;
+        check_description("Topic", &t.name, &t.description, &mut errors);
+        check_action_descriptions(&t.actions, &mut errors);
+    }
+
+    // Rule 8: Entry Point
+    // The runtime starts every conversation in the single start_agent block.
+    for duplicate in &ast.duplicate_start_agents {
+        errors.push(SemanticError {
+            message: format!(
+                "Duplicate start_agent '{}'; only the first start_agent block is used",
+                duplicate.node.name.node
+            ),
+            span: Some(duplicate.node.name.span.clone()),
+            severity: Severity::Error,
+            hint: Some("Merge the blocks or turn this one into a topic".to_string()),
+            code: Some("duplicate-start-agent"),
+            related: ast
+                .start_agent
+                .iter()
+                .map(|first| (first.node.name.span.clone(), "first start_agent".to_string()))
+                .collect(),
+        });
+    }
+    if let (None, Some(first)) = (&ast.start_agent, ast.topics.first()) {
+        errors.push(SemanticError {
+            message: "Agent has topics but no start_agent block".to_string(),
+            span: Some(first.node.name.span.clone()),
+            severity: Severity::Warning,
+            hint: Some("Add a start_agent block that routes to the topics".to_string()),
+            code: Some("missing-start-agent"),
+            related: Vec::new(),
+        });
+    }
+
+    // Rule 9: Duplicate Blocks
+    // Only the first config, variables, or system block takes effect.
+    check_duplicate_blocks("config", &ast.config, &ast.duplicate_configs, &mut errors);
+    check_duplicate_blocks("variables", &ast.variables, &ast.duplicate_variables, &mut errors);
+    check_duplicate_blocks("system", &ast.system, &ast.duplicate_systems, &mut errors);
+
+    // Rule 10: Reasoning Action Clause Order
+    // The platform expects description, available when, with, set, run, if,
+    // then transition.
+    let reasoning_blocks = ast
+        .start_agent
+        .iter()
+        .filter_map(|sa| sa.node.reasoning.as_ref())
+        .chain(ast.topics.iter().filter_map(|t| t.node.reasoning.as_ref()));
+    for reasoning in reasoning_blocks {
+        for action in reasoning.node.actions.iter().flat_map(|a| &a.node) {
+            check_clause_order(&action.node, &mut errors);
+        }
+    }
+
+    // Rule 11: Function Calls
+    // Expressions may only call built-in functions, with their arity.
+    for error in call_errors {
+        let (span, hint, code) = match &error {
+            CallError::UnknownFunction {
+                span, suggestion, ..
+            } => (
+                span.clone(),
+                match suggestion {
+                    Some(name) => format!("Did you mean '{}'?", name),
+                    None => format!(
+                        "Available functions: {}",
+                        BUILTIN_FUNCTIONS
+                            .iter()
+                            .map(|f| f.name)
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ),
+                },
+                "unknown-function",
+            ),
+            CallError::WrongArity { function, span, .. } => {
+                (span.clone(), function.signature.to_string(), "call-arity")
+            }
+        };
+        errors.push(SemanticError {
+            message: error.to_string(),
+            span: Some(span),
+            severity: Severity::Error,
+            hint: Some(hint),
+            code: Some(code),
+            related: Vec::new(),
+        });
+    }
+
+    // Rule 12: Linked Variable Sources
+    // The runtime can only fill linked variables from context it provides.
+    if let (Some(schema), Some(vars_block)) = (context, &ast.variables) {
+        for var in &vars_block.node.variables {
+            let Some(source) = &var.node.source else {
+                continue;
+            };
+            if let Some((message, code, suggestion)) = schema.check(&source.node) {
+                errors.push(SemanticError {
+                    message: format!("{} in source of variable '{}'", message, var.node.name.node),
+                    span: Some(source.span.clone()),
+                    severity: Severity::Warning,
+                    hint: Some(match suggestion {
+                        Some(suggestion) => format!("Did you mean '{}'?", suggestion),
+                        None => format!(
+                            "Known namespaces: {}",
+                            schema
+                                .namespaces
+                                .keys()
+                                .map(|namespace| format!("@{}", namespace))
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        ),
+                    }),
+                    code: Some(code),
+                    related: Vec::new(),
+                });
+            }
+        }
+    }
+
+    // Rule 14: Reference Path Depth
+    // Variables, topics and outputs are flat; only object variables have
+    // properties.
+    let object_variables: BTreeSet<&str> = ast
+        .variables
+        .iter()
+        .flat_map(|v| &v.node.variables)
+        .filter(|v| v.node.ty.node == Type::Object)
+        .map(|v| v.node.name.node.as_str())
+        .collect();
+    for_each_reference(ast, &mut |reference, start| {
+        check_reference_depth(reference, start, &object_variables, &mut errors);
+    });
+
+    // Rule 16: Target Environment
+    let target = ast
+        .config
+        .as_ref()
+        .and_then(|c| c.node.target_environment.as_ref());
+    if let Some(target) = target {
+        if let Err(message) = target.node.parse::<TargetEnvironment>() {
+            errors.push(SemanticError {
+                message: format!("{} in config", capitalize(&message)),
+                span: Some(target.span.clone()),
+                severity: Severity::Error,
+                hint: Some("Use \"dev\", \"sandbox\" or \"prod\"".to_string()),
+                code: Some("unknown-target-environment"),
+                related: Vec::new(),
+            });
+        }
+    }
+
+    sort_diagnostics(&mut errors);
     errors
 }
 
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Sort diagnostics by position, then by code and message, so that their
+/// order doesn't depend on the order the rules ran in. Diagnostics without a
+/// span go last.
+pub fn sort_diagnostics(diagnostics: &mut [SemanticError]) {
+    fn key(e: &SemanticError) -> (bool, Option<(usize, usize)>, Option<&str>, &str) {
+        (e.span.is_none(), e.span.as_ref().map(|s| (s.start, s.end)), e.code, &e.message)
+    }
+    diagnostics.sort_by(|a, b| key(a).cmp(&key(b)));
+}
+
+fn check_reference_depth(
+    reference: &Reference,
+    start: usize,
+    object_variables: &BTreeSet<&str>,
+    errors: &mut Vec<SemanticError>,
+) {
+    let Some(rule) = NamespaceRule::get(&reference.namespace) else {
+        return;
+    };
+    if reference.path.len() <= rule.segments
+        || rule.object_paths && object_variables.contains(reference.path[0].as_str())
+    {
+        return;
+    }
+    let allowed =
+        Reference::new(reference.namespace.clone(), reference.path[..rule.segments].to_vec());
+    let extra = reference.path.len() - rule.segments;
+    let hint = if rule.object_paths {
+        format!("Only object variables have properties; did you mean '{}'?", allowed.full_path())
+    } else {
+        format!("Did you mean '{}'?", allowed.full_path())
+    };
+    errors.push(SemanticError {
+        message: format!(
+            "Reference '{}' has {} extra path segment{}; @{} references take {}",
+            reference.full_path(),
+            extra,
+            if extra == 1 { "" } else { "s" },
+            rule.namespace,
+            rule.segments
+        ),
+        span: Some(start + allowed.full_path().len()..start + reference.full_path().len()),
+        severity: Severity::Error,
+        hint: Some(hint),
+        code: Some("reference-depth"),
+        related: Vec::new(),
+    });
+}
+
+/// Call `f` with every reference in `ast` and the byte offset of its `@`.
+fn for_each_reference(ast: &AgentFile, f: &mut impl FnMut(&Reference, usize)) {
+    for var in ast.variables.iter().flat_map(|v| &v.node.variables) {
+        if let Some(default) = &var.node.default {
+            expr_references(default, f);
+        }
+        if let Some(source) = &var.node.source {
+            f(&source.node, source.span.start);
+        }
+    }
+    let blocks = ast
+        .start_agent
+        .iter()
+        .map(|sa| {
+            let sa = &sa.node;
+            let system = sa
+                .system
+                .as_ref()
+                .and_then(|s| s.node.instructions.as_ref());
+            (system, [&sa.before_reasoning, &sa.after_reasoning], &sa.reasoning)
+        })
+        .chain(ast.topics.iter().map(|t| {
+            let t = &t.node;
+            let system = t.system.as_ref().and_then(|s| s.node.instructions.as_ref());
+            (system, [&t.before_reasoning, &t.after_reasoning], &t.reasoning)
+        }));
+    for (system, directives, reasoning) in blocks {
+        if let Some(system) = system {
+            instruction_references(system, f);
+        }
+        for directive in directives.into_iter().flatten() {
+            stmt_references(&directive.node.statements, f);
+        }
+        if let Some(reasoning) = reasoning {
+            reasoning_references(reasoning, f);
+        }
+    }
+}
+
+fn reasoning_references(
+    reasoning: &Spanned<ReasoningBlock>,
+    f: &mut impl FnMut(&Reference, usize),
+) {
+    if let Some(instructions) = &reasoning.node.instructions {
+        instruction_references(instructions, f);
+    }
+    for action in reasoning.node.actions.iter().flat_map(|a| &a.node) {
+        let ra = &action.node;
+        match &ra.target.node {
+            // The reference ends the target: `@utils.transition to @topic.x`
+            ReasoningActionTarget::Action(reference)
+            | ReasoningActionTarget::TransitionTo(reference)
+            | ReasoningActionTarget::TopicDelegate(reference) => {
+                f(reference, ra.target.span.end - reference.full_path().len());
+            }
+            ReasoningActionTarget::Escalate | ReasoningActionTarget::SetVariables => {}
+        }
+        if let Some(condition) = &ra.available_when {
+            expr_references(condition, f);
+        }
+        clause_references(&ra.with_clauses, &ra.set_clauses, f);
+        for run in &ra.run_clauses {
+            f(&run.node.action.node, run.node.action.span.start);
+            clause_references(&run.node.with_clauses, &run.node.set_clauses, f);
+        }
+        for clause in &ra.if_clauses {
+            expr_references(&clause.node.condition, f);
+            if let Some(transition) = &clause.node.transition {
+                f(&transition.node, transition.span.start);
+            }
+        }
+        if let Some(transition) = &ra.transition {
+            f(&transition.node, transition.span.start);
+        }
+    }
+}
+
+fn stmt_references(stmts: &[Spanned<Stmt>], f: &mut impl FnMut(&Reference, usize)) {
+    for stmt in stmts {
+        match &stmt.node {
+            Stmt::Set { target, value } => {
+                f(&target.node, target.span.start);
+                expr_references(value, f);
+            }
+            Stmt::Run {
+                action,
+                with_clauses,
+                set_clauses,
+            } => {
+                f(&action.node, action.span.start);
+                clause_references(with_clauses, set_clauses, f);
+            }
+            Stmt::If {
+                condition,
+                then_block,
+                else_block,
+            } => {
+                expr_references(condition, f);
+                stmt_references(then_block, f);
+                if let Some(else_block) = else_block {
+                    stmt_references(else_block, f);
+                }
+            }
+            Stmt::Transition { target } => f(&target.node, target.span.start),
+            Stmt::Escalate | Stmt::Respond { .. } => {}
+        }
+    }
+}
+
+fn clause_references(
+    with_clauses: &[Spanned<WithClause>],
+    set_clauses: &[Spanned<SetClause>],
+    f: &mut impl FnMut(&Reference, usize),
+) {
+    for clause in with_clauses {
+        let WithValue::Expr(expr) = &clause.node.value.node;
+        expr_references(&Spanned::new(expr.clone(), clause.node.value.span.clone()), f);
+    }
+    for clause in set_clauses {
+        f(&clause.node.target.node, clause.node.target.span.start);
+        expr_references(&clause.node.source, f);
+    }
+}
+
+fn instruction_references(
+    instructions: &Spanned<Instructions>,
+    f: &mut impl FnMut(&Reference, usize),
+) {
+    if let Instructions::Dynamic(parts) = &instructions.node {
+        instruction_part_references(parts, f);
+    }
+}
+
+fn instruction_part_references(
+    parts: &[Spanned<InstructionPart>],
+    f: &mut impl FnMut(&Reference, usize),
+) {
+    for part in parts {
+        match &part.node {
+            InstructionPart::Text(_) | InstructionPart::Heading { .. } => {}
+            // The part's span starts at `{!`
+            InstructionPart::Interpolation(expr) => {
+                let span = part.span.start + 2..part.span.end;
+                expr_references(&Spanned::new(expr.clone(), span), f);
+            }
+            InstructionPart::Conditional {
+                condition,
+                then_parts,
+                else_parts,
+            } => {
+                expr_references(condition, f);
+                instruction_part_references(then_parts, f);
+                if let Some(else_parts) = else_parts {
+                    instruction_part_references(else_parts, f);
+                }
+            }
+        }
+    }
+}
+
+fn expr_references(expr: &Spanned<Expr>, f: &mut impl FnMut(&Reference, usize)) {
+    let mut child = |e: &Spanned<Expr>| expr_references(e, f);
+    match &expr.node {
+        Expr::Reference(reference) => f(reference, expr.span.start),
+        // `@variables.x.field` parses as property access on `@variables.x`;
+        // check the path as written
+        Expr::Property { object, .. } => match expr.node.reference_path() {
+            Some(reference) => f(&reference, expr.span.start),
+            None => child(object),
+        },
+        Expr::Template(items) | Expr::List(items) => items.iter().for_each(child),
+        Expr::Object(fields) => fields.values().for_each(child),
+        Expr::BinOp { left, right, .. } => {
+            child(left);
+            child(right);
+        }
+        Expr::UnaryOp { operand, .. } => child(operand),
+        Expr::Ternary {
+            condition,
+            then_expr,
+            else_expr,
+        } => {
+            child(condition);
+            child(then_expr);
+            child(else_expr);
+        }
+        Expr::Index { object, index } => {
+            child(object);
+            child(index);
+        }
+        Expr::Call { args, .. } => args.iter().for_each(child),
+        Expr::String(_)
+        | Expr::Number(_)
+        | Expr::Date(_)
+        | Expr::Currency(_)
+        | Expr::Bool(_)
+        | Expr::None
+        | Expr::SlotFill => {}
+    }
+}
+
+fn check_clause_order(action: &ReasoningAction, errors: &mut Vec<SemanticError>) {
+    let Some(((kind, index), after)) = action.first_misordered_clause() else {
+        return;
+    };
+    let expected: Vec<&str> = ActionClause::CANONICAL
+        .iter()
+        .map(|k| k.keyword())
+        .collect();
+    errors.push(SemanticError {
+        message: format!(
+            "'{}' clause in action '{}' should come before '{}'",
+            kind.keyword(),
+            action.name.node,
+            after.keyword()
+        ),
+        span: action.clause_span(kind, index),
+        severity: Severity::Warning,
+        hint: Some(format!("Expected clause order: {}", expected.join(", "))),
+        code: Some("clause-order"),
+        related: Vec::new(),
+    });
+}
+
+fn check_duplicate_blocks<T>(
+    keyword: &str,
+    first: &Option<Spanned<T>>,
+    duplicates: &[Spanned<T>],
+    errors: &mut Vec<SemanticError>,
+) {
+    let header = |block: &Spanned<T>| block.span.start..block.span.start + keyword.len();
+    for duplicate in duplicates {
+        errors.push(SemanticError {
+            message: format!("Duplicate '{}:' block; only the first one is used", keyword),
+            span: Some(header(duplicate)),
+            severity: Severity::Error,
+            hint: Some(format!("Merge this block into the first '{}:' block", keyword)),
+            code: Some("duplicate-block"),
+            related: first
+                .iter()
+                .map(|first| (header(first), format!("first '{}:' block", keyword)))
+                .collect(),
+        });
+    }
+}
+
+fn check_action_descriptions(
+    actions: &Option<Spanned<ActionsBlock>>,
+    errors: &mut Vec<SemanticError>,
+) {
+    for action in actions.iter().flat_map(|a| &a.node.actions) {
+        let a = &action.node;
+        check_description("Action", &a.name, &a.description, errors);
+    }
+}
+
+fn check_description(
+    kind: &str,
+    name: &Spanned<String>,
+    description: &Option<Spanned<String>>,
+    errors: &mut Vec<SemanticError>,
+) {
+    if description
+        .as_ref()
+        .is_some_and(|d| !d.node.trim().is_empty())
+    {
+        return;
+    }
+    errors.push(SemanticError {
+        message: format!("{} '{}' has no description", kind, name.node),
+        span: Some(name.span.clone()),
+        severity: Severity::Warning,
+        hint: Some("The planner uses descriptions to choose topics and actions".to_string()),
+        code: Some("missing-description"),
+        related: Vec::new(),
+    });
+}
+
 fn validate_variable(var: &VariableDecl, errors: &mut Vec<SemanticError>) {
-    // Rule 1: Mutable Variable Type Restrictions
+    // Rule 1: Mutable Variable Type Restrictions, for lists by element type
     if let VariableKind::Mutable = var.kind {
-        match var.ty.node {
+        let element = match &var.ty.node {
+            Type::List(inner) => inner.as_ref(),
+            ty => ty,
+        };
+        match element {
             Type::Integer | Type::Long | Type::Datetime | Type::Time => {
                 errors.push(SemanticError {
                     message: format!(
@@ -76,12 +819,16 @@ fn validate_variable(var: &VariableDecl, errors: &mut Vec<SemanticError>) {
                     span: Some(var.ty.span.clone()),
                     severity: Severity::Error,
                     hint: Some("Allowed mutable types: String, Boolean, Number, Currency, Date, Id, Object, Timestamp".to_string()),
+                    code: Some("mutable-variable-type"),
+                    related: Vec::new(),
                 });
             }
             _ => {}
         }
     }
 
+    check_list_type(&var.ty, &format!("Variable '{}'", var.name.node), errors);
+
     // Rule 2: Context Variable Object Type
     // Linked variables (source starts with @context.) cannot be Object type.
     if let VariableKind::Linked = var.kind {
@@ -96,6 +843,8 @@ fn validate_variable(var: &VariableDecl, errors: &mut Vec<SemanticError>) {
                         span: Some(var.ty.span.clone()),
                         severity: Severity::Error,
                         hint: None,
+                        code: Some("context-variable-type"),
+                        related: Vec::new(),
                     });
                 }
             }
@@ -121,6 +870,8 @@ fn validate_language_entry(entry: &LanguageEntry, errors: &mut Vec<SemanticError
                         span: Some(entry.value.span.clone()),
                         severity: Severity::Error,
                         hint: Some(format!("Valid locales are: {}", valid_locales.join(", "))),
+                        code: Some("invalid-locale"),
+                        related: Vec::new(),
                     });
                 }
             }
@@ -139,11 +890,22 @@ fn validate_connection_entry(entry: &ConnectionEntry, errors: &mut Vec<SemanticE
             span: Some(entry.value.span.clone()),
             severity: Severity::Error,
             hint: None,
+            code: Some("outbound-route-type"),
+            related: Vec::new(),
         });
     }
 }
 
 fn validate_action_def(action: &ActionDef, errors: &mut Vec<SemanticError>) {
+    check_action_target(action, errors);
+
+    for params in [&action.inputs, &action.outputs].into_iter().flatten() {
+        for param in &params.node {
+            let owner = format!("Action parameter '{}'", param.node.name.node);
+            check_list_type(&param.node.ty, &owner, errors);
+        }
+    }
+
     // Rule 5: Action Input Keyword Collision
     if let Some(inputs) = &action.inputs {
         for param in &inputs.node {
@@ -158,6 +920,8 @@ fn validate_action_def(action: &ActionDef, errors: &mut Vec<SemanticError>) {
                         span: Some(param.node.name.span.clone()),
                         severity: Severity::Warning,
                         hint: None,
+                        code: Some("input-keyword-collision"),
+                        related: Vec::new(),
                     });
                 }
                 _ => {}
@@ -165,3 +929,477 @@ fn validate_action_def(action: &ActionDef, errors: &mut Vec<SemanticError>) {
         }
     }
 }
+
+fn check_list_type(ty: &Spanned<Type>, owner: &str, errors: &mut Vec<SemanticError>) {
+    // Rule 15: List Element Types
+    // Lists hold scalars or objects; a list of lists has no platform type.
+    if let Type::List(inner) = &ty.node {
+        if let Type::List(_) = inner.as_ref() {
+            errors.push(SemanticError {
+                message: format!("{} has a nested list type, which is not supported", owner),
+                span: Some(ty.span.clone()),
+                severity: Severity::Error,
+                hint: Some("Use list[object] with a list field in each object".to_string()),
+                code: Some("nested-list-type"),
+                related: Vec::new(),
+            });
+        }
+    }
+}
+
+fn check_action_target(action: &ActionDef, errors: &mut Vec<SemanticError>) {
+    // Rule 13: Action Target URIs
+    // Targets must name a known scheme and something to invoke.
+    let (Some(target), Some(Err(error))) = (&action.target, action.target_uri()) else {
+        return;
+    };
+    let schemes: Vec<String> = TargetScheme::ALL
+        .iter()
+        .map(|s| s.as_str().to_string())
+        .collect();
+    let (severity, hint, code) = match &error {
+        TargetUriError::MissingScheme(_) => (
+            Severity::Warning,
+            format!("Known schemes: {}", schemes.join(", ")),
+            "unknown-target-scheme",
+        ),
+        TargetUriError::UnknownScheme(scheme) => (
+            Severity::Warning,
+            match closest(scheme, schemes.iter()) {
+                Some(suggestion) => format!("Did you mean '{}://'?", suggestion),
+                None => format!("Known schemes: {}", schemes.join(", ")),
+            },
+            "unknown-target-scheme",
+        ),
+        TargetUriError::EmptyName(scheme) => (
+            Severity::Error,
+            format!("Name the {} to invoke, e.g. '{}://Name'", scheme, scheme),
+            "empty-target-name",
+        ),
+    };
+    errors.push(SemanticError {
+        message: format!("Action '{}': {}", action.name.node, error),
+        span: Some(target.span.clone()),
+        severity,
+        hint: Some(hint),
+        code: Some(code),
+        related: Vec::new(),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = r#"topic main:
+   description: "Main"
+
+   actions:
+      lookup:
+         inputs:
+            id: string
+         target: "flow://Lookup"
+"#;
+
+    fn severity_of(errors: &[SemanticError], code: &str) -> Severity {
+        errors
+            .iter()
+            .find(|e| e.code == Some(code))
+            .map(|e| e.severity.clone())
+            .unwrap_or_else(|| panic!("no '{}' diagnostic", code))
+    }
+
+    #[test]
+    fn test_missing_description_warning() {
+        let ast = crate::parse(SOURCE).unwrap();
+        let errors = validate_ast(&ast);
+        let missing: Vec<_> = errors
+            .iter()
+            .filter(|e| e.code == Some("missing-description"))
+            .collect();
+        assert_eq!(missing.len(), 1);
+        assert_eq!(missing[0].message, "Action 'lookup' has no description");
+    }
+
+    #[test]
+    fn test_target_environment_escalation() {
+        // Keyword-named inputs don't parse, but can be built programmatically
+        let mut ast = crate::parse(SOURCE).unwrap();
+        ast.add_action(
+            "main",
+            crate::builder::ActionDefBuilder::new("create")
+                .description("Create")
+                .input("label", Type::String),
+        )
+        .unwrap();
+
+        let dev = validate_ast_for(&ast, TargetEnvironment::Dev);
+        assert_eq!(severity_of(&dev, "input-keyword-collision"), Severity::Warning);
+        assert_eq!(severity_of(&dev, "missing-description"), Severity::Warning);
+
+        let sandbox = validate_ast_for(&ast, TargetEnvironment::Sandbox);
+        assert_eq!(severity_of(&sandbox, "input-keyword-collision"), Severity::Error);
+        assert_eq!(severity_of(&sandbox, "missing-description"), Severity::Warning);
+
+        let prod = validate_ast_for(&ast, TargetEnvironment::Prod);
+        assert_eq!(severity_of(&prod, "missing-description"), Severity::Error);
+
+        assert_eq!("Production".parse(), Ok(TargetEnvironment::Prod));
+        assert!("staging".parse::<TargetEnvironment>().is_err());
+    }
+
+    #[test]
+    fn test_configured_target_environment() {
+        let source = format!(
+            "config:\n   agent_name: \"Support\"\n   target_environment: \"prod\"\n\n{}",
+            SOURCE
+        );
+        let ast = crate::parse(&source).unwrap();
+        let target = TargetEnvironment::from_config(&ast);
+        assert_eq!(target, Some(TargetEnvironment::Prod));
+        assert_eq!(
+            crate::serialize(&ast)
+                .matches("target_environment: \"prod\"")
+                .count(),
+            1
+        );
+
+        let ast = crate::parse(&source.replace("\"prod\"", "\"staging\"")).unwrap();
+        assert_eq!(TargetEnvironment::from_config(&ast), None);
+        assert_eq!(severity_of(&validate_ast(&ast), "unknown-target-environment"), Severity::Error);
+    }
+
+    #[test]
+    fn test_start_agent_presence_and_uniqueness() {
+        let ast = crate::parse(SOURCE).unwrap();
+        let dev = validate_ast_for(&ast, TargetEnvironment::Dev);
+        assert_eq!(severity_of(&dev, "missing-start-agent"), Severity::Warning);
+        let prod = validate_ast_for(&ast, TargetEnvironment::Prod);
+        assert_eq!(severity_of(&prod, "missing-start-agent"), Severity::Error);
+
+        let source = format!(
+            "start_agent first:\n   description: \"First\"\n\nstart_agent second:\n   description: \"Second\"\n\n{}",
+            SOURCE
+        );
+        let ast = crate::parse(&source).unwrap();
+        assert_eq!(ast.start_agent.as_ref().unwrap().node.name.node, "first");
+        assert_eq!(ast.duplicate_start_agents.len(), 1);
+
+        let errors = validate_ast(&ast);
+        assert!(!errors.iter().any(|e| e.code == Some("missing-start-agent")));
+        let duplicate = errors
+            .iter()
+            .find(|e| e.code == Some("duplicate-start-agent"))
+            .unwrap();
+        assert_eq!(duplicate.severity, Severity::Error);
+        assert_eq!(&source[duplicate.span.clone().unwrap()], "second");
+
+        // Duplicates survive a round trip
+        let reparsed = crate::parse(&crate::serialize(&ast)).unwrap();
+        assert_eq!(reparsed.duplicate_start_agents.len(), 1);
+    }
+
+    #[test]
+    fn test_duplicate_blocks() {
+        let source = format!(
+            "config:\n   agent_name: \"A\"\n\nvariables:\n   a: mutable string = \"\"\n\n{}\nconfig:\n   agent_name: \"B\"\n\nvariables:\n   b: mutable string = \"\"\n",
+            SOURCE
+        );
+        let ast = crate::parse(&source).unwrap();
+        assert_eq!(ast.config.as_ref().unwrap().node.agent_name.node, "A");
+        assert_eq!(ast.duplicate_configs.len(), 1);
+        assert_eq!(ast.duplicate_variables.len(), 1);
+        assert!(ast.duplicate_systems.is_empty());
+
+        let errors = validate_ast(&ast);
+        let duplicates: Vec<_> = errors
+            .iter()
+            .filter(|e| e.code == Some("duplicate-block"))
+            .collect();
+        assert_eq!(duplicates.len(), 2);
+        let config = duplicates[0];
+        let second = source.rfind("config:").unwrap();
+        assert_eq!(config.span, Some(second..second + "config".len()));
+        assert_eq!(config.related, vec![(0..6, "first 'config:' block".to_string())]);
+    }
+
+    #[test]
+    fn test_clause_order() {
+        use crate::serializer::{serialize_with_options, ClauseOrder, SerializeOptions};
+
+        let source = r#"topic main:
+   description: "Main"
+   reasoning:
+      actions:
+         lookup: @actions.lookup
+            set @variables.result = @outputs.result
+            description: "Look up"
+            with id = ...
+"#;
+        let ast = crate::parse(source).unwrap();
+        let action = &ast.topics[0]
+            .node
+            .reasoning
+            .as_ref()
+            .unwrap()
+            .node
+            .actions
+            .as_ref()
+            .unwrap()
+            .node[0];
+        assert_eq!(
+            action.node.clause_order,
+            vec![
+                ActionClause::Set,
+                ActionClause::Description,
+                ActionClause::With
+            ]
+        );
+
+        let errors = validate_ast(&ast);
+        let order: Vec<_> = errors
+            .iter()
+            .filter(|e| e.code == Some("clause-order"))
+            .collect();
+        assert_eq!(order.len(), 1);
+        assert_eq!(order[0].severity, Severity::Warning);
+        assert!(order[0].message.contains("'description' clause"));
+        let span = order[0].span.clone().unwrap();
+        assert_eq!(&source[span], "\"Look up\"");
+
+        // Source order survives a round trip; canonical order fixes the warning
+        let reparsed = crate::parse(&crate::serialize(&ast)).unwrap();
+        let clause_order = |file: &AgentFile| {
+            let reasoning = &file.topics[0].node.reasoning.as_ref().unwrap().node;
+            reasoning.actions.as_ref().unwrap().node[0]
+                .node
+                .clause_order
+                .clone()
+        };
+        assert_eq!(clause_order(&reparsed), clause_order(&ast));
+        let options = SerializeOptions {
+            clause_order: ClauseOrder::Canonical,
+        };
+        let fixed = crate::parse(&serialize_with_options(&ast, &options)).unwrap();
+        assert!(!validate_ast(&fixed)
+            .iter()
+            .any(|e| e.code == Some("clause-order")));
+    }
+
+    #[test]
+    fn test_function_calls() {
+        let source = r#"variables:
+   items: mutable list[string] = []
+
+topic main:
+   description: "Main"
+   reasoning:
+      instructions: "Help"
+      actions:
+         go: @utils.transition to @topic.main
+            description: "Go"
+            available when lenn(@variables.items) > 0 and contains(@variables.items) and len(@variables.items) > 0
+"#;
+        let ast = crate::parse(source).unwrap();
+        let errors = validate_ast(&ast);
+
+        let unknown: Vec<_> = errors
+            .iter()
+            .filter(|e| e.code == Some("unknown-function"))
+            .collect();
+        assert_eq!(unknown.len(), 1);
+        assert_eq!(unknown[0].message, "Unknown function 'lenn'");
+        assert_eq!(unknown[0].hint.as_deref(), Some("Did you mean 'len'?"));
+        assert_eq!(&source[unknown[0].span.clone().unwrap()], "lenn");
+
+        let arity: Vec<_> = errors
+            .iter()
+            .filter(|e| e.code == Some("call-arity"))
+            .collect();
+        assert_eq!(arity.len(), 1);
+        assert_eq!(arity[0].message, "contains() takes 2 arguments but 1 was given");
+        assert_eq!(&source[arity[0].span.clone().unwrap()], "contains(@variables.items)");
+    }
+
+    #[test]
+    fn test_action_targets() {
+        let source = r#"topic main:
+   description: "Main"
+   actions:
+      a:
+         description: "A"
+         target: "flwo://Lookup"
+      b:
+         description: "B"
+         target: "apex://"
+      c:
+         description: "C"
+         target: "Lookup"
+      d:
+         description: "D"
+         target: "connectapi://chatter/feeds"
+"#;
+        let ast = crate::parse(source).unwrap();
+        let errors: Vec<_> = validate_ast(&ast)
+            .into_iter()
+            .filter(|e| e.code.is_some_and(|c| c.contains("target")))
+            .collect();
+        assert_eq!(errors.len(), 3);
+
+        assert_eq!(errors[0].code, Some("unknown-target-scheme"));
+        assert_eq!(errors[0].message, "Action 'a': unknown target scheme 'flwo://'");
+        assert_eq!(errors[0].hint.as_deref(), Some("Did you mean 'flow://'?"));
+        assert_eq!(&source[errors[0].span.clone().unwrap()], "\"flwo://Lookup\"");
+
+        assert_eq!(errors[1].code, Some("empty-target-name"));
+        assert_eq!(errors[1].severity, Severity::Error);
+
+        assert_eq!(errors[2].code, Some("unknown-target-scheme"));
+        assert_eq!(errors[2].severity, Severity::Warning);
+    }
+
+    #[test]
+    fn test_context_sources() {
+        let source = r#"variables:
+   email: linked string
+      source: @messagingSession.userEmial
+   region: linked string
+      source: @context.account.region
+   queue: linked string
+      source: @omnichanel.queue
+   order: linked string
+      source: @store.order.total
+"#;
+        let ast = crate::parse(source).unwrap();
+        assert!(validate_ast(&ast)
+            .iter()
+            .all(|e| !e.code.is_some_and(|c| c.starts_with("unknown-context"))));
+
+        let schema = ContextSchema::salesforce();
+        let errors: Vec<_> = validate_ast_with_context(&ast, Some(&schema))
+            .into_iter()
+            .filter(|e| e.code.is_some_and(|c| c.starts_with("unknown-context")))
+            .collect();
+        assert_eq!(errors.len(), 3);
+        assert!(errors.iter().all(|e| e.severity == Severity::Warning));
+        assert_eq!(
+            errors[0].message,
+            "Unknown context path '@messagingSession.userEmial' in source of variable 'email'"
+        );
+        assert_eq!(&source[errors[0].span.clone().unwrap()], "source: @messagingSession.userEmial");
+        assert_eq!(errors[1].code, Some("unknown-context-namespace"));
+        assert_eq!(errors[1].hint.as_deref(), Some("Did you mean '@omnichannel'?"));
+        assert_eq!(
+            errors[2].hint.as_deref(),
+            Some("Known namespaces: @context, @messagingSession, @omnichannel")
+        );
+
+        // Custom namespaces, where a known path covers the paths below it
+        let schema = ContextSchema::salesforce().with_namespace("store", ["order"]);
+        let errors = validate_ast_with_context(&ast, Some(&schema));
+        assert!(errors.iter().all(|e| !e.message.contains("@store")));
+    }
+
+    #[test]
+    fn test_reference_depth() {
+        let source = r#"variables:
+   order: mutable object = {}
+   name: mutable string = ""
+start_agent main:
+   description: "Main"
+   reasoning:
+      instructions: ->
+         | Hello {!@variables.name.first}
+      actions:
+         go: @utils.transition to @topic.support.deep
+   after_reasoning:
+      if @variables.order.total > 0:
+         set @variables.name.last = "x"
+topic support:
+   description: "Support"
+"#;
+        let ast = crate::parse(source).unwrap();
+        let errors: Vec<_> = validate_ast(&ast)
+            .into_iter()
+            .filter(|e| e.code == Some("reference-depth"))
+            .collect();
+        // `@variables.order.total` is a property of an object variable
+        assert_eq!(errors.len(), 3);
+        assert!(errors.iter().all(|e| e.severity == Severity::Error));
+        let extra: Vec<_> = errors
+            .iter()
+            .map(|e| &source[e.span.clone().unwrap()])
+            .collect();
+        assert_eq!(extra, [".first", ".deep", ".last"]);
+        assert_eq!(
+            errors[0].message,
+            "Reference '@variables.name.first' has 1 extra path segment; @variables references take 1"
+        );
+        assert_eq!(
+            errors[0].hint.as_deref(),
+            Some("Only object variables have properties; did you mean '@variables.name'?")
+        );
+        assert_eq!(errors[1].hint.as_deref(), Some("Did you mean '@topic.support'?"));
+    }
+
+    #[test]
+    fn test_diagnostics_sorted_by_position() {
+        let source = r#"variables:
+   order: mutable object = {}
+topic main:
+   reasoning:
+      instructions: ->
+         | {!@variables.order.total} {!@variables.missing} {!@topic.main.x}
+topic other:
+   reasoning:
+      instructions: "Hi"
+"#;
+        let errors = validate_ast(&crate::parse(source).unwrap());
+        assert!(errors.len() >= 3);
+        let starts: Vec<_> = errors
+            .iter()
+            .map(|e| e.span.as_ref().map_or(usize::MAX, |s| s.start))
+            .collect();
+        assert!(starts.windows(2).all(|pair| pair[0] <= pair[1]));
+    }
+
+    #[test]
+    fn test_list_types() {
+        let source = r#"variables:
+   tags: mutable list[string] = []
+   grid: mutable list[list[number]] = []
+   counts: mutable list[integer] = []
+
+topic main:
+   description: "Main"
+
+   actions:
+      lookup:
+         inputs:
+            keys: list<list<string>>
+         target: "flow://Lookup"
+"#;
+        let errors = validate_ast(&crate::parse(source).unwrap());
+        let nested: Vec<_> = errors
+            .iter()
+            .filter(|e| e.code == Some("nested-list-type"))
+            .collect();
+        assert_eq!(nested.len(), 2);
+        assert_eq!(
+            nested[0].message,
+            "Variable 'grid' has a nested list type, which is not supported"
+        );
+        assert_eq!(&source[nested[0].span.clone().unwrap()], "list[list[number]]");
+        assert_eq!(
+            nested[1].message,
+            "Action parameter 'keys' has a nested list type, which is not supported"
+        );
+
+        let element: Vec<_> = errors
+            .iter()
+            .filter(|e| e.code == Some("mutable-variable-type"))
+            .map(|e| &source[e.span.clone().unwrap()])
+            .collect();
+        assert_eq!(element, ["list[integer]"]);
+    }
+}
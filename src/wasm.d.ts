@@ -0,0 +1,539 @@
+// Generated by busbar_sf_agentscript::typescript::typescript_declarations.
+// Do not edit: regenerate with `UPDATE_SNAPSHOTS=1 cargo test`.
+
+export interface AgentFile {
+  config: Spanned<ConfigBlock> | undefined;
+  connections: Spanned<ConnectionBlock>[];
+  duplicate_configs: Spanned<ConfigBlock>[];
+  duplicate_start_agents: Spanned<StartAgentBlock>[];
+  duplicate_systems: Spanned<SystemBlock>[];
+  duplicate_variables: Spanned<VariablesBlock>[];
+  knowledge: Spanned<KnowledgeBlock> | undefined;
+  language: Spanned<LanguageBlock> | undefined;
+  recovered_blocks: Spanned<RecoveredBlock>[];
+  start_agent: Spanned<StartAgentBlock> | undefined;
+  system: Spanned<SystemBlock> | undefined;
+  topics: Spanned<TopicBlock>[];
+  variables: Spanned<VariablesBlock> | undefined;
+}
+
+export type ActionClause = "Description" | "AvailableWhen" | "With" | "Set" | "Run" | "If" | "Transition";
+
+export interface ActionDef {
+  description: Spanned<string> | undefined;
+  doc: Spanned<string> | undefined;
+  include_in_progress_indicator: Spanned<boolean> | undefined;
+  inputs: Spanned<Spanned<ParamDef>[]> | undefined;
+  label: Spanned<string> | undefined;
+  name: Spanned<string>;
+  outputs: Spanned<Spanned<ParamDef>[]> | undefined;
+  progress_indicator_message: Spanned<string> | undefined;
+  require_user_confirmation: Spanned<boolean> | undefined;
+  target: Spanned<string> | undefined;
+}
+
+export interface ActionsBlock {
+  actions: Spanned<ActionDef>[];
+}
+
+export type BinOp = "Eq" | "Ne" | "Lt" | "Gt" | "Le" | "Ge" | "Is" | "IsNot" | "And" | "Or" | "Add" | "Sub" | "Mul" | "Div" | "Mod";
+
+export interface ConfigBlock {
+  agent_label: Spanned<string> | undefined;
+  agent_name: Spanned<string>;
+  agent_type: Spanned<string> | undefined;
+  default_agent_user: Spanned<string> | undefined;
+  description: Spanned<string> | undefined;
+  target_environment: Spanned<string> | undefined;
+}
+
+export interface ConnectionBlock {
+  entries: Spanned<ConnectionEntry>[];
+  name: Spanned<string>;
+}
+
+export interface ConnectionEntry {
+  name: Spanned<string>;
+  value: Spanned<string>;
+}
+
+export interface DirectiveBlock {
+  statements: Spanned<Stmt>[];
+}
+
+export type Expr = "None" | "SlotFill" | {
+  Reference: Reference;
+} | {
+  String: string;
+} | {
+  Template: Spanned<Expr>[];
+} | {
+  Number: number;
+} | {
+  Date: string;
+} | {
+  Currency: {
+    amount: number;
+    code: string | undefined;
+  };
+} | {
+  Bool: boolean;
+} | {
+  List: Spanned<Expr>[];
+} | {
+  Object: Map<string, Spanned<Expr>>;
+} | {
+  BinOp: {
+    left: Spanned<Expr>;
+    op: BinOp;
+    right: Spanned<Expr>;
+  };
+} | {
+  UnaryOp: {
+    op: UnaryOp;
+    operand: Spanned<Expr>;
+  };
+} | {
+  Ternary: {
+    condition: Spanned<Expr>;
+    else_expr: Spanned<Expr>;
+    then_expr: Spanned<Expr>;
+  };
+} | {
+  Property: {
+    field: Spanned<string>;
+    object: Spanned<Expr>;
+  };
+} | {
+  Index: {
+    index: Spanned<Expr>;
+    object: Spanned<Expr>;
+  };
+} | {
+  Call: {
+    args: Spanned<Expr>[];
+    callee: Spanned<string>;
+  };
+};
+
+export interface IfClause {
+  condition: Spanned<Expr>;
+  transition: Spanned<Reference> | undefined;
+}
+
+export type InstructionPart = {
+  Text: string;
+} | {
+  Interpolation: Expr;
+} | {
+  Heading: {
+    level: number;
+    title: string;
+  };
+} | {
+  Conditional: {
+    condition: Spanned<Expr>;
+    else_parts: Spanned<InstructionPart>[] | undefined;
+    then_parts: Spanned<InstructionPart>[];
+  };
+};
+
+export type Instructions = {
+  Simple: string;
+} | {
+  Static: Spanned<string>[];
+} | {
+  Dynamic: Spanned<InstructionPart>[];
+};
+
+export interface KnowledgeBlock {
+  entries: Spanned<KnowledgeEntry>[];
+}
+
+export interface KnowledgeEntry {
+  name: Spanned<string>;
+  value: Spanned<Expr>;
+}
+
+export interface LanguageBlock {
+  entries: Spanned<LanguageEntry>[];
+}
+
+export interface LanguageEntry {
+  name: Spanned<string>;
+  value: Spanned<Expr>;
+}
+
+export interface ParamDef {
+  complex_data_type_name: Spanned<string> | undefined;
+  description: Spanned<string> | undefined;
+  filter_from_agent: Spanned<boolean> | undefined;
+  is_displayable: Spanned<boolean> | undefined;
+  is_required: Spanned<boolean> | undefined;
+  label: Spanned<string> | undefined;
+  name: Spanned<string>;
+  ty: Spanned<Type>;
+}
+
+export interface ReasoningAction {
+  available_when: Spanned<Expr> | undefined;
+  clause_order: ActionClause[];
+  description: Spanned<string> | undefined;
+  if_clauses: Spanned<IfClause>[];
+  name: Spanned<string>;
+  run_clauses: Spanned<RunClause>[];
+  set_clauses: Spanned<SetClause>[];
+  target: Spanned<ReasoningActionTarget>;
+  transition: Spanned<Reference> | undefined;
+  with_clauses: Spanned<WithClause>[];
+}
+
+export type ReasoningActionTarget = "Escalate" | "SetVariables" | {
+  Action: Reference;
+} | {
+  TransitionTo: Reference;
+} | {
+  TopicDelegate: Reference;
+};
+
+export interface ReasoningBlock {
+  actions: Spanned<Spanned<ReasoningAction>[]> | undefined;
+  instructions: Spanned<Instructions> | undefined;
+}
+
+export interface RecoveredBlock {
+  kind: string;
+  name: Spanned<string> | undefined;
+}
+
+export interface Reference {
+  namespace: string;
+  path: string[];
+}
+
+export interface RunClause {
+  action: Spanned<Reference>;
+  set_clauses: Spanned<SetClause>[];
+  with_clauses: Spanned<WithClause>[];
+}
+
+export interface SetClause {
+  source: Spanned<Expr>;
+  target: Spanned<Reference>;
+}
+
+/** Byte range in the source */
+export interface Span {
+  end: number;
+  start: number;
+}
+
+export interface Spanned<T> {
+  node: T;
+  span: Span;
+}
+
+export interface StartAgentBlock {
+  actions: Spanned<ActionsBlock> | undefined;
+  after_reasoning: Spanned<DirectiveBlock> | undefined;
+  before_reasoning: Spanned<DirectiveBlock> | undefined;
+  description: Spanned<string> | undefined;
+  doc: Spanned<string> | undefined;
+  name: Spanned<string>;
+  reasoning: Spanned<ReasoningBlock> | undefined;
+  system: Spanned<TopicSystemOverride> | undefined;
+}
+
+export type Stmt = {
+  Set: {
+    target: Spanned<Reference>;
+    value: Spanned<Expr>;
+  };
+} | {
+  Run: {
+    action: Spanned<Reference>;
+    set_clauses: Spanned<SetClause>[];
+    with_clauses: Spanned<WithClause>[];
+  };
+} | {
+  If: {
+    condition: Spanned<Expr>;
+    else_block: Spanned<Stmt>[] | undefined;
+    then_block: Spanned<Stmt>[];
+  };
+} | {
+  Transition: {
+    target: Spanned<Reference>;
+  };
+} | "Escalate" | {
+  Respond: {
+    message: Spanned<string>;
+  };
+};
+
+export interface SystemBlock {
+  instructions: Spanned<Instructions> | undefined;
+  messages: Spanned<SystemMessages> | undefined;
+}
+
+export interface SystemMessages {
+  error: Spanned<string> | undefined;
+  welcome: Spanned<string> | undefined;
+}
+
+export interface TopicBlock {
+  actions: Spanned<ActionsBlock> | undefined;
+  after_reasoning: Spanned<DirectiveBlock> | undefined;
+  before_reasoning: Spanned<DirectiveBlock> | undefined;
+  description: Spanned<string> | undefined;
+  doc: Spanned<string> | undefined;
+  name: Spanned<string>;
+  quoted_name: Spanned<string> | undefined;
+  reasoning: Spanned<ReasoningBlock> | undefined;
+  system: Spanned<TopicSystemOverride> | undefined;
+}
+
+export interface TopicSystemOverride {
+  instructions: Spanned<Instructions> | undefined;
+}
+
+export type Type = "String" | "Number" | "Boolean" | "Object" | "Date" | "Timestamp" | "Currency" | "Id" | "Datetime" | "Time" | "Integer" | "Long" | {
+  List: Type;
+};
+
+export type UnaryOp = "Not" | "Neg";
+
+export interface VariableDecl {
+  default: Spanned<Expr> | undefined;
+  description: Spanned<string> | undefined;
+  doc: Spanned<string> | undefined;
+  kind: VariableKind;
+  name: Spanned<string>;
+  source: Spanned<Reference> | undefined;
+  ty: Spanned<Type>;
+}
+
+export type VariableKind = "Mutable" | "Linked";
+
+export interface VariablesBlock {
+  variables: Spanned<VariableDecl>[];
+}
+
+export interface WithClause {
+  param: Spanned<string>;
+  value: Spanned<WithValue>;
+}
+
+export interface WithValue {
+  Expr: Expr;
+}
+
+/** Passed to the `parse_agent_with_progress` callback */
+export interface ParseProgress {
+  blocks_parsed: number;
+  blocks_total: number;
+  bytes_parsed: number;
+  bytes_total: number;
+}
+
+export type Severity = "Error" | "Warning";
+
+export interface SemanticError {
+  code: string | undefined;
+  hint: string | undefined;
+  message: string;
+  related: [Span, string][];
+  severity: Severity;
+  span: Span | undefined;
+}
+
+/** Returned by `validate_agent_semantic` */
+export interface ValidationReport {
+  errors: SemanticError[];
+  warnings: SemanticError[];
+}
+
+/** Zero-based line and UTF-16 column, returned by `offset_to_location` */
+export interface Location {
+  column: number;
+  line: number;
+}
+
+/** Returned by `format_check` */
+export interface FormatResult {
+  difference: string | undefined;
+  formatted: string;
+  is_idempotent: boolean;
+  roundtrip_ok: boolean;
+}
+
+/** The data `simulate` runs against; every field is optional */
+export interface MockData {
+  actions?: Record<string, unknown>;
+  choices?: Record<string, string | string[]>;
+  variables?: Record<string, unknown>;
+}
+
+export type SimulationOutcome = "success" | "escalated" | "error";
+
+export interface VariableChange {
+  name: string;
+  new_value: unknown;
+  old_value: unknown;
+}
+
+export interface ActionInvocation {
+  action_name: string;
+  inputs: Record<string, unknown>;
+  outputs: unknown;
+}
+
+export interface SimulationStep {
+  action_invocations: ActionInvocation[];
+  detail: string;
+  phase: string;
+  statement_type: string;
+  variable_changes: VariableChange[];
+}
+
+/** Returned by `simulate` */
+export interface SimulationTrace {
+  error: string | null;
+  final_context: Record<string, unknown>;
+  outcome: SimulationOutcome;
+  steps: SimulationStep[];
+  topic_transitions: string[];
+}
+
+/** Returned by `runScenario` */
+export interface ScenarioResult {
+  failures: string[];
+  name: string | null;
+  passed: boolean;
+  trace: SimulationTrace;
+}
+
+export type NodeKind = "topic" | "start_agent" | "action_def" | "reasoning_action" | "variable" | "external" | "util";
+
+export type EdgeKind = "routes" | "transitions_to" | "delegates" | "invokes" | "reads" | "writes" | "chains" | "escalates";
+
+export interface NodeRepr {
+  mutable: boolean | undefined;
+  name: string | undefined;
+  node_type: NodeKind;
+  span_end: number;
+  span_start: number;
+  target: string | undefined;
+  topic: string | undefined;
+}
+
+/** `source` and `target` are positions in `GraphRepr.nodes` */
+export interface EdgeRepr {
+  edge_type: EdgeKind;
+  source: number;
+  target: number;
+}
+
+/** Returned by `build_graph` and `build_graph_from_source` */
+export interface GraphRepr {
+  edges: EdgeRepr[];
+  nodes: NodeRepr[];
+  topics: string[];
+  variables: string[];
+}
+
+export interface ValidationErrorRepr {
+  error_type: string;
+  message: string;
+  span_end: number | undefined;
+  span_start: number | undefined;
+}
+
+/** Returned by `validate_graph` */
+export interface ValidationResultRepr {
+  errors: ValidationErrorRepr[];
+  is_valid: boolean;
+  warnings: ValidationErrorRepr[];
+}
+
+/** Returned by `get_graph_stats` */
+export interface GraphStats {
+  action_defs: number;
+  connections: number;
+  has_start_agent: boolean;
+  invocations: number;
+  knowledge_bases: number;
+  reads: number;
+  reasoning_actions: number;
+  topics: number;
+  transitions: number;
+  variables: number;
+  writes: number;
+}
+
+export interface TopicMetricsRepr {
+  complexity: number;
+  depth: number | undefined;
+  fan_in: number;
+  fan_out: number;
+  max_instruction_nesting: number;
+  name: string;
+  reasoning_actions: number;
+}
+
+/** Returned by `get_graph_metrics` */
+export interface GraphMetricsRepr {
+  max_depth: number | undefined;
+  topics: TopicMetricsRepr[];
+  total_complexity: number;
+}
+
+export type DependencyType = {
+  SObject: string;
+} | {
+  Field: {
+    field: string;
+    object: string;
+  };
+} | {
+  Flow: string;
+} | {
+  ApexClass: string;
+} | {
+  ApexMethod: {
+    class: string;
+    method: string;
+  };
+} | {
+  KnowledgeBase: string;
+} | {
+  Connection: string;
+} | {
+  PromptTemplate: string;
+} | {
+  ExternalService: string;
+} | {
+  Custom: string;
+};
+
+export interface Dependency {
+  action_name: string;
+  dep_type: DependencyType;
+  span: [number, number];
+  used_in: string;
+}
+
+/** Returned by `extract_dependencies` */
+export interface DependencyReport {
+  all_dependencies: Dependency[];
+  apex_classes: string[];
+  by_topic: Map<string, Dependency[]>;
+  by_type: Map<string, Dependency[]>;
+  connections: string[];
+  external_services: string[];
+  fields: string[];
+  flows: string[];
+  knowledge_bases: string[];
+  prompt_templates: string[];
+  sobjects: string[];
+}
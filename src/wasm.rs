@@ -6,7 +6,7 @@
 //! # Example (JavaScript)
 //!
 //! ```javascript
-//! import init, { parse_agent, parse_agent_to_json, serialize_agent } from './sf_agentscript.js';
+//! import init, { parse_agent, parse_agent_to_json, parse_agent_with_progress, serialize_agent, simulate, runScenario } from './sf_agentscript.js';
 //!
 //! await init();
 //!
@@ -17,14 +17,32 @@
 //! // Or parse to JSON string
 //! const json = parse_agent_to_json(source);
 //!
+//! // In a Web Worker, report progress on large files
+//! const ast2 = parse_agent_with_progress(source, (p) => {
+//!   postMessage({ progress: p.bytes_parsed / p.bytes_total });
+//! });
+//!
 //! // Serialize AST back to source
 //! const regenerated = serialize_agent(ast);
+//!
+//! // Dry-run against mock data, or check a scenario
+//! const trace = simulate(source, { choices: { main: "go_orders" } });
+//! const result = runScenario(source, JSON.stringify(scenario));
 //! ```
+//!
+//! # TypeScript
+//!
+//! The generated `.d.ts` declares the objects these functions take and
+//! return (`AgentFile`, `SimulationTrace`, ...) from `wasm.d.ts`, which
+//! [`crate::typescript`] generates and a test keeps current.
 
 use crate::validation::Severity;
 use serde::Serialize;
 use wasm_bindgen::prelude::*;
 
+#[wasm_bindgen(typescript_custom_section)]
+const TS_DECLARATIONS: &str = include_str!("wasm.d.ts");
+
 #[derive(Serialize)]
 struct ValidationReport {
     errors: Vec<crate::validation::SemanticError>,
@@ -46,7 +64,7 @@ pub fn init() {
 /// # Returns
 /// * `Ok(JsValue)` - The parsed AST as a JavaScript object
 /// * `Err(JsValue)` - Error message if parsing fails
-#[wasm_bindgen]
+#[wasm_bindgen(unchecked_return_type = "AgentFile")]
 pub fn parse_agent(source: &str) -> Result<JsValue, JsValue> {
     match crate::parse(source) {
         Ok(ast) => serde_wasm_bindgen::to_value(&ast)
@@ -75,6 +93,77 @@ pub fn parse_agent_to_json(source: &str) -> Result<String, JsValue> {
     }
 }
 
+/// Parse AgentScript source code and return the AST in the stable v1 JSON format.
+///
+/// Unlike `parse_agent_to_json`, whose shape follows the internal AST, the v1
+/// format only changes with a new `ast_version`.
+///
+/// # Arguments
+/// * `source` - The AgentScript source code to parse
+///
+/// # Returns
+/// * `Ok(String)` - The parsed AST as v1 JSON
+/// * `Err(JsValue)` - Error message if parsing fails
+#[wasm_bindgen]
+pub fn parse_agent_to_json_v1(source: &str) -> Result<String, JsValue> {
+    match crate::parse(source) {
+        Ok(ast) => serde_json::to_string_pretty(&crate::serializer::to_json_v1(&ast))
+            .map_err(|e| JsValue::from_str(&format!("JSON serialization error: {}", e))),
+        Err(errs) => Err(JsValue::from_str(&errs.join("\n"))),
+    }
+}
+
+/// Parse AgentScript source code one top-level block at a time, reporting
+/// progress.
+///
+/// The parse itself is synchronous, so run it in a Web Worker and post the
+/// progress to the page to keep the editor responsive on large files.
+///
+/// # Arguments
+/// * `source` - The AgentScript source code to parse
+/// * `on_progress` - Called after lexing and after each block with
+///   `{ blocks_parsed, blocks_total, bytes_parsed, bytes_total }`. Returning
+///   `false` cancels the parse.
+///
+/// # Returns
+/// * `Ok(JsValue)` - The parsed AST as a JavaScript object
+/// * `Err(JsValue)` - Error message if parsing fails or is cancelled, or the
+///   error thrown by `on_progress`
+#[wasm_bindgen(unchecked_return_type = "AgentFile")]
+pub fn parse_agent_with_progress(
+    source: &str,
+    #[wasm_bindgen(unchecked_param_type = "(progress: ParseProgress) => boolean | void")]
+    on_progress: &js_sys::Function,
+) -> Result<JsValue, JsValue> {
+    let token = crate::parser::CancellationToken::new();
+    let mut callback_error = None;
+    let output = crate::parser::parse_with_progress(source, &token, |progress| {
+        let result = serde_wasm_bindgen::to_value(&progress)
+            .map_err(JsValue::from)
+            .and_then(|progress| on_progress.call1(&JsValue::NULL, &progress));
+        match result {
+            Ok(keep_going) if keep_going.as_bool() == Some(false) => token.cancel(),
+            Ok(_) => {}
+            Err(e) => {
+                callback_error = Some(e);
+                token.cancel();
+            }
+        }
+    });
+    if let Some(e) = callback_error {
+        return Err(e);
+    }
+    match output {
+        Ok((Some(ast), errors)) if errors.is_empty() => serde_wasm_bindgen::to_value(&ast)
+            .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e))),
+        Ok((_, errors)) => {
+            let messages: Vec<String> = errors.iter().map(ToString::to_string).collect();
+            Err(JsValue::from_str(&messages.join("\n")))
+        }
+        Err(cancelled) => Err(JsValue::from_str(&cancelled.to_string())),
+    }
+}
+
 /// Validate AgentScript source code without returning the full AST.
 ///
 /// Returns `true` if the source is valid, or throws an error with
@@ -115,7 +204,7 @@ pub fn validate_agent(source: &str) -> Result<bool, JsValue> {
 /// # Returns
 /// * `Ok(JsValue)` - Object with `errors` and `warnings` arrays
 /// * `Err(JsValue)` - Error message if serialization fails
-#[wasm_bindgen]
+#[wasm_bindgen(unchecked_return_type = "ValidationReport")]
 pub fn validate_agent_semantic(source: &str) -> Result<JsValue, JsValue> {
     let (errors, warnings) = match crate::parse(source) {
         Ok(ast) => {
@@ -132,6 +221,8 @@ pub fn validate_agent_semantic(source: &str) -> Result<JsValue, JsValue> {
                     span: None,
                     severity: Severity::Error,
                     hint: None,
+                    code: None,
+                    related: Vec::new(),
                 })
                 .collect();
             (errors, vec![])
@@ -148,6 +239,37 @@ pub fn version() -> String {
     env!("CARGO_PKG_VERSION").to_string()
 }
 
+/// Get the JSON Schema describing the AST returned by `parse_agent`.
+///
+/// # Returns
+/// * `String` - The schema as a JSON string
+#[wasm_bindgen]
+pub fn ast_schema() -> String {
+    crate::schema::ast_json_schema().to_string()
+}
+
+/// Convert a byte offset from an AST span to a line and column.
+///
+/// Spans are UTF-8 byte offsets; the returned `{ line, column }` is
+/// zero-based with the column in UTF-16 code units, so it indexes
+/// JavaScript strings and editor positions directly.
+///
+/// # Arguments
+/// * `source` - The AgentScript source code the span came from
+/// * `offset` - A byte offset, e.g. `span.start`
+#[wasm_bindgen(unchecked_return_type = "Location")]
+pub fn offset_to_location(source: &str, offset: usize) -> Result<JsValue, JsValue> {
+    let location = crate::text_pos::location(source, offset, crate::text_pos::Encoding::Utf16);
+    serde_wasm_bindgen::to_value(&location).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// Convert a zero-based line and UTF-16 column back to a byte offset.
+#[wasm_bindgen]
+pub fn location_to_offset(source: &str, line: u32, column: u32) -> usize {
+    let location = crate::text_pos::Location::new(line, column);
+    crate::text_pos::offset(source, location, crate::text_pos::Encoding::Utf16)
+}
+
 /// Serialize an AST back to AgentScript source code.
 ///
 /// Takes a JavaScript object representing an AST (as returned by `parse_agent`)
@@ -160,12 +282,54 @@ pub fn version() -> String {
 /// * `Ok(String)` - The serialized AgentScript source code
 /// * `Err(JsValue)` - Error message if serialization fails
 #[wasm_bindgen]
-pub fn serialize_agent(ast: JsValue) -> Result<String, JsValue> {
+pub fn serialize_agent(
+    #[wasm_bindgen(unchecked_param_type = "AgentFile")] ast: JsValue,
+) -> Result<String, JsValue> {
     let agent: crate::ast::AgentFile = serde_wasm_bindgen::from_value(ast)
         .map_err(|e| JsValue::from_str(&format!("Failed to deserialize AST: {}", e)))?;
     Ok(crate::serialize(&agent))
 }
 
+/// Serialize an AST given as JSON, rejecting unknown or missing fields.
+///
+/// Like `serialize_agent`, but fails with one line per problem (field path
+/// plus a suggested spelling) instead of silently dropping fields the AST
+/// doesn't define.
+///
+/// # Arguments
+/// * `ast_json` - The AST as a JSON string
+///
+/// # Returns
+/// * `Ok(String)` - The serialized AgentScript source code
+/// * `Err(JsValue)` - Newline-separated field errors
+#[wasm_bindgen]
+pub fn serialize_agent_strict(ast_json: &str) -> Result<String, JsValue> {
+    let json: serde_json::Value = serde_json::from_str(ast_json)
+        .map_err(|e| JsValue::from_str(&format!("Invalid JSON: {}", e)))?;
+    let agent = crate::serializer::from_json_strict(&json).map_err(|errors| {
+        let lines: Vec<String> = errors.iter().map(ToString::to_string).collect();
+        JsValue::from_str(&lines.join("\n"))
+    })?;
+    Ok(crate::serialize(&agent))
+}
+
+/// Serialize an AST given in the stable v1 JSON format.
+///
+/// # Arguments
+/// * `ast_json` - The AST as v1 JSON (from `parse_agent_to_json_v1`)
+///
+/// # Returns
+/// * `Ok(String)` - The serialized AgentScript source code
+/// * `Err(JsValue)` - Error message if the JSON isn't valid v1
+#[wasm_bindgen]
+pub fn serialize_agent_v1(ast_json: &str) -> Result<String, JsValue> {
+    let json: serde_json::Value = serde_json::from_str(ast_json)
+        .map_err(|e| JsValue::from_str(&format!("Invalid JSON: {}", e)))?;
+    let agent =
+        crate::serializer::from_json_v1(json).map_err(|e| JsValue::from_str(&e.to_string()))?;
+    Ok(crate::serialize(&agent))
+}
+
 /// Parse AgentScript source, then serialize it back.
 ///
 /// This is useful for formatting/normalizing AgentScript code.
@@ -183,3 +347,67 @@ pub fn normalize_agent(source: &str) -> Result<String, JsValue> {
         Err(errs) => Err(JsValue::from_str(&errs.join("\n"))),
     }
 }
+
+/// Format AgentScript source and verify the result.
+///
+/// # Returns
+/// * `Ok(JsValue)` - `{ formatted, is_idempotent, roundtrip_ok, difference }`
+/// * `Err(JsValue)` - Error message if parsing fails
+#[wasm_bindgen(unchecked_return_type = "FormatResult")]
+pub fn format_check(source: &str) -> Result<JsValue, JsValue> {
+    let result = crate::formatter::format_check(source)
+        .map_err(|errs| JsValue::from_str(&errs.join("\n")))?;
+    serde_wasm_bindgen::to_value(&result).map_err(JsValue::from)
+}
+
+/// Dry-run an agent against mock data.
+///
+/// `mock_data` is an object with optional `variables` (initial values),
+/// `actions` (outputs per action name) and `choices` (the reasoning action
+/// picked per topic, a name or a list consumed one per visit). See
+/// [`crate::simulation`].
+///
+/// # Returns
+/// * `Ok(JsValue)` - The trace: `{ steps, final_context, outcome, error, topic_transitions }`
+/// * `Err(JsValue)` - Error message if parsing fails or the mock data is malformed
+#[wasm_bindgen(unchecked_return_type = "SimulationTrace")]
+pub fn simulate(
+    source: &str,
+    #[wasm_bindgen(unchecked_param_type = "MockData | null | undefined")] mock_data: JsValue,
+) -> Result<JsValue, JsValue> {
+    let ast = crate::parse(source).map_err(|errs| JsValue::from_str(&errs.join("\n")))?;
+    let mock: crate::simulation::MockData = if mock_data.is_undefined() || mock_data.is_null() {
+        Default::default()
+    } else {
+        serde_wasm_bindgen::from_value(mock_data)
+            .map_err(|e| JsValue::from_str(&format!("Invalid mock data: {}", e)))?
+    };
+    let trace = crate::simulation::simulate(&ast, &mock);
+    to_plain_value(&trace)
+}
+
+/// Run a scenario: simulate with its mock data and check its expectations.
+///
+/// `scenario_json` is a JSON object with `name`, `mockData` (as for
+/// `simulate`) and `expect` (`outcome`, `topics`, `finalTopic`,
+/// `variables`, `actions`).
+///
+/// # Returns
+/// * `Ok(JsValue)` - `{ name, passed, failures, trace }`
+/// * `Err(JsValue)` - Error message if parsing fails or the scenario is malformed
+#[wasm_bindgen(js_name = runScenario, unchecked_return_type = "ScenarioResult")]
+pub fn run_scenario(source: &str, scenario_json: &str) -> Result<JsValue, JsValue> {
+    let ast = crate::parse(source).map_err(|errs| JsValue::from_str(&errs.join("\n")))?;
+    let scenario: crate::simulation::Scenario = serde_json::from_str(scenario_json)
+        .map_err(|e| JsValue::from_str(&format!("Invalid scenario: {}", e)))?;
+    let result = crate::simulation::run_scenario(&ast, &scenario);
+    to_plain_value(&result)
+}
+
+/// Convert to a JS value with plain objects rather than `Map`s, since
+/// simulation values are arbitrary JSON.
+fn to_plain_value<T: Serialize>(value: &T) -> Result<JsValue, JsValue> {
+    value
+        .serialize(&serde_wasm_bindgen::Serializer::json_compatible())
+        .map_err(|e| JsValue::from_str(&e.to_string()))
+}
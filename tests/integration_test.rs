@@ -271,4 +271,175 @@ topic main:
             .collect();
         assert!(tokens.iter().any(|t| matches!(t, lexer::Token::Config)));
     }
+
+    #[test]
+    fn test_malformed_topic_is_recovered() {
+        let source = r#"topic broken:
+   description: "Broken"
+   reasoning:
+      actions:
+         go: @@ bad
+
+topic good:
+   description: "Good"
+"#;
+        let (result, errors) = busbar_sf_agentscript::parser::parse_with_errors(source);
+        assert_eq!(errors.len(), 1);
+
+        let file = result.expect("partial AST");
+        assert_eq!(file.topics.len(), 1);
+        assert_eq!(file.topics[0].node.name.node, "good");
+
+        let recovered = &file.recovered_blocks[0];
+        assert_eq!(recovered.node.kind, "topic");
+        assert_eq!(recovered.node.name.as_ref().unwrap().node, "broken");
+        assert!(source[recovered.span.clone()].starts_with("topic broken:"));
+        assert!(source[recovered.span.clone()].contains("@@ bad"));
+    }
+
+    /// Render an expression fully parenthesized, for precedence goldens.
+    fn render(expr: &busbar_sf_agentscript::ast::Expr) -> String {
+        use busbar_sf_agentscript::ast::{BinOp, Expr, UnaryOp};
+        match expr {
+            Expr::Reference(r) => r.path.last().cloned().unwrap_or_default(),
+            Expr::Number(n) => n.to_string(),
+            Expr::Bool(b) => b.to_string(),
+            Expr::String(s) => format!("{s:?}"),
+            Expr::None => "None".to_string(),
+            Expr::BinOp { left, op, right } => {
+                let op = match op {
+                    BinOp::Eq => "==",
+                    BinOp::Ne => "!=",
+                    BinOp::Lt => "<",
+                    BinOp::Gt => ">",
+                    BinOp::Le => "<=",
+                    BinOp::Ge => ">=",
+                    BinOp::Is => "is",
+                    BinOp::IsNot => "is not",
+                    BinOp::And => "and",
+                    BinOp::Or => "or",
+                    BinOp::Add => "+",
+                    BinOp::Sub => "-",
+                    BinOp::Mul => "*",
+                    BinOp::Div => "/",
+                    BinOp::Mod => "%",
+                };
+                format!("({} {op} {})", render(&left.node), render(&right.node))
+            }
+            Expr::UnaryOp { op, operand } => match op {
+                UnaryOp::Not => format!("(not {})", render(&operand.node)),
+                UnaryOp::Neg => format!("(-{})", render(&operand.node)),
+            },
+            Expr::Ternary {
+                condition,
+                then_expr,
+                else_expr,
+            } => format!(
+                "({} if {} else {})",
+                render(&then_expr.node),
+                render(&condition.node),
+                render(&else_expr.node)
+            ),
+            Expr::Property { object, field } => format!("{}.{}", render(&object.node), field.node),
+            Expr::Call { callee, args } => {
+                let args: Vec<String> = args.iter().map(|a| render(&a.node)).collect();
+                format!("{}({})", callee.node, args.join(", "))
+            }
+            other => format!("{other:?}"),
+        }
+    }
+
+    const PRECEDENCE_GOLDENS: &[(&str, &str)] = &[
+        ("@variables.a or @variables.b and @variables.c", "(a or (b and c))"),
+        ("@variables.a and @variables.b or @variables.c", "((a and b) or c)"),
+        ("(@variables.a or @variables.b) and @variables.c", "((a or b) and c)"),
+        ("not @variables.a == 1", "(not (a == 1))"),
+        ("not @variables.a and @variables.b", "((not a) and b)"),
+        ("not not @variables.a", "(not (not a))"),
+        ("@variables.a + 1 == 3 - @variables.b", "((a + 1) == (3 - b))"),
+        ("@variables.a - 1 - 2", "((a - 1) - 2)"),
+        ("@variables.a - (1 - 2)", "(a - (1 - 2))"),
+        ("-@variables.a + 1", "((-a) + 1)"),
+        (
+            "@variables.a is None or @variables.b is not None",
+            "((a is None) or (b is not None))",
+        ),
+        (
+            "@variables.a < 1 and not (@variables.b or @variables.c)",
+            "((a < 1) and (not (b or c)))",
+        ),
+        ("1 if @variables.a or @variables.b else 2", "(1 if (a or b) else 2)"),
+        ("@variables.a + @variables.b * 2", "(a + (b * 2))"),
+        ("(@variables.a + @variables.b) * 2", "((a + b) * 2)"),
+        ("@variables.a * 2 / 4 % 3", "(((a * 2) / 4) % 3)"),
+        ("@variables.a / (2 * @variables.b)", "(a / (2 * b))"),
+        ("-@variables.a * 2 > @variables.b % 2", "(((-a) * 2) > (b % 2))"),
+        ("len(@variables.a) + 1 > 2", "((len(a) + 1) > 2)"),
+        (
+            "not contains(lower(@variables.a), \"refund\")",
+            "(not contains(lower(a), \"refund\"))",
+        ),
+        (
+            "starts_with(@variables.a, @variables.b or \"x\")",
+            "starts_with(a, (b or \"x\"))",
+        ),
+    ];
+
+    #[test]
+    fn test_expression_precedence_goldens() {
+        use busbar_sf_agentscript::ast::{InstructionPart, Instructions};
+
+        for (source_expr, expected) in PRECEDENCE_GOLDENS {
+            let source = format!(
+                r#"topic main:
+   description: "Main"
+   reasoning:
+      instructions:->
+         if {source_expr}:
+            | Then {{!{source_expr}}}
+      actions:
+         go: @utils.transition to @topic.main
+            available when {source_expr}
+"#
+            );
+            let file = parse_source(&source).unwrap_or_else(|e| panic!("{source_expr}: {e:?}"));
+            let reasoning = file.topics[0].node.reasoning.as_ref().unwrap();
+
+            let actions = &reasoning.node.actions.as_ref().unwrap().node;
+            let available = actions[0].node.available_when.as_ref().unwrap();
+            assert_eq!(render(&available.node), *expected, "available when {source_expr}");
+
+            let Instructions::Dynamic(parts) = &reasoning.node.instructions.as_ref().unwrap().node
+            else {
+                panic!("expected dynamic instructions");
+            };
+            let InstructionPart::Conditional {
+                condition,
+                then_parts,
+                ..
+            } = &parts[0].node
+            else {
+                panic!("expected conditional");
+            };
+            assert_eq!(render(&condition.node), *expected, "if {source_expr}");
+            assert_eq!(
+                &source[condition.span.clone()],
+                *source_expr,
+                "condition span for {source_expr}"
+            );
+
+            let interpolation = then_parts.iter().find_map(|part| match &part.node {
+                InstructionPart::Interpolation(expr) => Some(expr),
+                _ => None,
+            });
+            assert_eq!(render(interpolation.unwrap()), *expected, "interpolation {source_expr}");
+
+            // Serializing adds parentheses only where precedence needs them.
+            let reparsed = parse_source(&busbar_sf_agentscript::serialize(&file)).unwrap();
+            let reasoning = reparsed.topics[0].node.reasoning.as_ref().unwrap();
+            let actions = &reasoning.node.actions.as_ref().unwrap().node;
+            let available = actions[0].node.available_when.as_ref().unwrap();
+            assert_eq!(render(&available.node), *expected, "round trip {source_expr}");
+        }
+    }
 }
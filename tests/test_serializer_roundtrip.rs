@@ -5,7 +5,9 @@
 //! 2. We can serialize AST back to AgentScript source
 //! 3. Re-parsing the serialized source produces equivalent AST
 
-use busbar_sf_agentscript::{parse, serialize};
+use busbar_sf_agentscript::ast::Number;
+use busbar_sf_agentscript::formatter::format_check;
+use busbar_sf_agentscript::{parse, serialize, Expr};
 
 #[test]
 fn test_roundtrip_minimal_config() {
@@ -181,6 +183,33 @@ topic main:
     assert_eq!(reparsed.language.as_ref().unwrap().node.entries.len(), 1);
 }
 
+#[test]
+fn test_roundtrip_knowledge_block() {
+    // The `knowledge:` block is parsed, serialized and reparsed with its entries intact.
+    let original = r#"config:
+   agent_name: "KnowledgeAgent"
+
+knowledge:
+   policy_documents: "KnowledgeBase://InsurancePolicies"
+   citations_enabled: True
+
+topic main:
+   description: "Main"
+"#;
+
+    let ast = parse(original).expect("Failed to parse original");
+    let knowledge = ast.knowledge.as_ref().expect("knowledge block not parsed");
+    assert_eq!(knowledge.node.entries.len(), 2);
+    assert_eq!(knowledge.node.entries[0].node.name.node, "policy_documents");
+
+    let serialized = serialize(&ast);
+    assert!(serialized.contains("knowledge:"), "Missing knowledge block");
+    assert!(serialized.contains("KnowledgeBase://InsurancePolicies"));
+
+    let reparsed = parse(&serialized).expect("Failed to reparse serialized");
+    assert_eq!(reparsed.knowledge.as_ref().unwrap().node.entries.len(), 2);
+}
+
 #[test]
 fn test_roundtrip_before_and_after_reasoning() {
     // Covers `before_reasoning:` and `after_reasoning:` directive blocks inside a topic.
@@ -216,3 +245,128 @@ topic main:
     assert!(topic.before_reasoning.is_some(), "before_reasoning lost after roundtrip");
     assert!(topic.after_reasoning.is_some(), "after_reasoning lost after roundtrip");
 }
+
+#[test]
+fn test_roundtrip_number_literals() {
+    // Integers stay integers and decimals keep the digits they were written with.
+    let original = r#"variables:
+   count: mutable integer = 42
+   price: mutable number = 1.50
+   ratio: mutable number = 2.0
+"#;
+
+    let ast = parse(original).expect("Failed to parse original");
+    let serialized = serialize(&ast);
+
+    assert!(serialized.contains("= 42\n"), "Integer changed: {}", serialized);
+    assert!(serialized.contains("= 1.50\n"), "Decimal digits lost: {}", serialized);
+    assert!(serialized.contains("= 2.0\n"), "Decimal point lost: {}", serialized);
+
+    let reparsed = parse(&serialized).expect("Failed to reparse serialized");
+    let defaults: Vec<Expr> = reparsed
+        .variables
+        .as_ref()
+        .unwrap()
+        .node
+        .variables
+        .iter()
+        .map(|v| v.node.default.as_ref().unwrap().node.clone())
+        .collect();
+    assert_eq!(
+        defaults,
+        [
+            Expr::Number(Number::Int(42)),
+            Expr::Number(Number::from(1.5)),
+            Expr::Number(Number::from(2.0)),
+        ]
+    );
+}
+
+#[test]
+fn test_roundtrip_date_and_currency_literals() {
+    // Typed literals serialize back to the syntax they were written in.
+    let original = r#"variables:
+   renewal: mutable date = date(2024-01-31)
+   price: mutable currency = $199.99
+   refund: mutable currency = currency(5, "EUR")
+
+topic main:
+   description: "Main"
+
+   reasoning:
+      instructions: ->
+         if @variables.renewal < date(2025-01-01) and @variables.price > $100:
+            | Offer the $20 discount.
+"#;
+
+    let ast = parse(original).expect("Failed to parse original");
+    let serialized = serialize(&ast);
+
+    assert!(serialized.contains("= date(2024-01-31)\n"), "{}", serialized);
+    assert!(serialized.contains("= $199.99\n"), "{}", serialized);
+    assert!(serialized.contains("= currency(5, \"EUR\")\n"), "{}", serialized);
+    assert!(serialized.contains("< date(2025-01-01) and"), "{}", serialized);
+    assert!(serialized.contains("Offer the $20 discount."), "{}", serialized);
+
+    let reparsed = parse(&serialized).expect("Failed to reparse serialized");
+    assert_eq!(serialize(&reparsed), serialized);
+}
+
+#[test]
+fn test_roundtrip_quoted_topic_name() {
+    let original = r#"topic "Claims – EU":
+   description: "EU claims"
+"#;
+
+    let ast = parse(original).expect("Failed to parse original");
+    let serialized = serialize(&ast);
+    assert!(serialized.contains("topic \"Claims – EU\":\n"), "{}", serialized);
+
+    let reparsed = parse(&serialized).expect("Failed to reparse serialized");
+    assert_eq!(reparsed.topics[0].node.name.node, "Claims_EU");
+    assert_eq!(serialize(&reparsed), serialized);
+}
+
+#[test]
+fn test_roundtrip_interpolations_and_continuations() {
+    let original = r#"topic main:
+   description: "Main"
+   reasoning:
+      instructions:->
+         if @variables.policy != "":
+            | Policy: {!@variables.policy}
+              Status: {!@variables.status}
+              Monthly Premium: ${!@variables.premium}
+         if @variables.flagged:
+            run @actions.flag
+
+         | Use {!@actions.lookup} to find a policy.
+           Use {!@actions.renew} to renew it.
+         | Options:
+           - Renew
+           - Cancel
+"#;
+
+    let result = format_check(original).expect("Failed to parse original");
+    assert!(result.roundtrip_ok, "{:?}\n{}", result.difference, result.formatted);
+    assert!(result.is_idempotent, "{}", result.formatted);
+    assert!(result
+        .formatted
+        .contains("| Use {!@actions.lookup} to find a policy.\n"));
+}
+
+#[test]
+fn test_examples_format_stably() {
+    let examples = concat!(env!("CARGO_MANIFEST_DIR"), "/examples");
+    for entry in std::fs::read_dir(examples).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|ext| ext != "agent") {
+            continue;
+        }
+        let source = std::fs::read_to_string(&path).unwrap();
+        let Ok(result) = format_check(&source) else {
+            continue;
+        };
+        assert!(result.is_stable(), "{}: {:?}", path.display(), result.diagnostic());
+    }
+}